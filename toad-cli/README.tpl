@@ -0,0 +1,23 @@
+[![crates.io](https://img.shields.io/crates/v/toad-cli.svg)](https://crates.io/crates/toad-cli)
+[![docs.rs](https://docs.rs/toad-cli/badge.svg)](https://docs.rs/toad-cli/latest)
+{{badges}}
+
+# {{crate}}
+
+{{readme}}
+
+## License
+
+Licensed under either of
+
+* Apache License, Version 2.0, ([LICENSE-APACHE](LICENSE-APACHE) or https://www.apache.org/licenses/LICENSE-2.0)
+* MIT license ([LICENSE-MIT](LICENSE-MIT) or https://opensource.org/licenses/MIT)
+
+at your option.
+
+### Contribution
+
+Unless you explicitly state otherwise, any contribution intentionally
+submitted for inclusion in the work by you, as defined in the Apache-2.0
+license, shall be dual licensed as above, without any additional terms or
+conditions.
@@ -0,0 +1,272 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use toad_msg::ContentFormat;
+
+use crate::uri::{self, Uri};
+
+/// Usage text printed by `toad help` and when arguments are invalid
+pub const USAGE: &str = "\
+usage: toad <command> [options]
+
+commands:
+  get <uri>                  send a GET request and print the response
+  post <uri>                 send a POST request and print the response
+  put <uri>                  send a PUT request and print the response
+  delete <uri>               send a DELETE request and print the response
+  observe <uri>              register as an observer and print notifications
+  discover [<uri>]           list the resources in a server's /.well-known/core
+  serve <dir>                serve the files in a directory
+  help                       print this message
+
+options:
+  -p, --payload <string>     request payload
+  -f, --format <format>      Content-Format of the request payload
+  -a, --accept <format>      Content-Format to ask the server for
+  -t, --timeout <seconds>    seconds to wait for a response (default 10)
+  -n, --count <n>            stop observing after n notifications
+  -m, --multicast            discover all CoAP devices on the local network
+  -b, --bind <addr>          address to serve on (default 0.0.0.0:5683)
+  -v, --verbose              print full responses and enable debug logging
+
+<format> may be one of text, link, xml, octets, exi, json, or a number.
+<uri> looks like coap://host[:port]/path?query";
+
+/// Which request method to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+  Get,
+  Post,
+  Put,
+  Delete,
+}
+
+/// A parsed command
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+  /// Send a single request
+  Request {
+    method: Method,
+    uri: Uri,
+    payload: Option<String>,
+    format: Option<ContentFormat>,
+    accept: Option<ContentFormat>,
+  },
+  /// Observe a resource
+  Observe {
+    uri: Uri,
+    count: Option<usize>,
+  },
+  /// Get `/.well-known/core` from a server, or all servers
+  /// in the `All CoAP Nodes` multicast group when `uri` is `None`
+  Discover {
+    uri: Option<Uri>,
+  },
+  /// Serve files in a directory
+  Serve {
+    dir: PathBuf,
+    bind: String,
+  },
+  /// Print usage
+  Help,
+}
+
+/// Command-line arguments
+#[derive(Debug, Clone, PartialEq)]
+pub struct Args {
+  /// The command to run
+  pub command: Command,
+  /// How long to wait for responses
+  pub timeout: Duration,
+  /// Print full responses and enable debug logging
+  pub verbose: bool,
+}
+
+/// Errors encountered while parsing [`Args`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+  /// No command was given, or it was not recognized
+  UnknownCommand(Option<String>),
+  /// A required positional argument was missing
+  Missing(&'static str),
+  /// An option was not recognized
+  UnknownOption(String),
+  /// An option was given an invalid value
+  InvalidValue(String, String),
+  /// A URI could not be parsed
+  Uri(uri::Error),
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      | Error::UnknownCommand(Some(c)) => write!(f, "unknown command `{}`", c),
+      | Error::UnknownCommand(None) => write!(f, "no command given"),
+      | Error::Missing(what) => write!(f, "missing {}", what),
+      | Error::UnknownOption(o) => write!(f, "unknown option `{}`", o),
+      | Error::InvalidValue(o, v) => write!(f, "invalid value `{}` for `{}`", v, o),
+      | Error::Uri(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+fn parse_format(opt: &str, s: &str) -> Result<ContentFormat, Error> {
+  match s {
+    | "text" => Ok(ContentFormat::Text),
+    | "link" => Ok(ContentFormat::LinkFormat),
+    | "xml" => Ok(ContentFormat::Xml),
+    | "octets" => Ok(ContentFormat::OctetStream),
+    | "exi" => Ok(ContentFormat::Exi),
+    | "json" => Ok(ContentFormat::Json),
+    | n => n.parse::<u16>()
+            .map(ContentFormat::Other)
+            .map_err(|_| Error::InvalidValue(opt.to_string(), s.to_string())),
+  }
+}
+
+impl Args {
+  /// Parse arguments, excluding the program name
+  pub fn parse<I>(args: I) -> Result<Self, Error>
+    where I: IntoIterator<Item = String>
+  {
+    let mut positional = Vec::new();
+    let mut payload = None;
+    let mut format = None;
+    let mut accept = None;
+    let mut count = None;
+    let mut multicast = false;
+    let mut bind = None;
+    let mut timeout = Duration::from_secs(10);
+    let mut verbose = false;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+      let mut value = || {
+        args.next()
+            .ok_or_else(|| Error::InvalidValue(arg.clone(), String::new()))
+      };
+
+      match arg.as_str() {
+        | "-p" | "--payload" => payload = Some(value()?),
+        | "-f" | "--format" => format = Some(parse_format(&arg, &value()?)?),
+        | "-a" | "--accept" => accept = Some(parse_format(&arg, &value()?)?),
+        | "-t" | "--timeout" => {
+          let v = value()?;
+          timeout = v.parse::<f32>()
+                     .ok()
+                     .filter(|s| s.is_finite() && *s > 0.)
+                     .map(Duration::from_secs_f32)
+                     .ok_or_else(|| Error::InvalidValue(arg.clone(), v))?;
+        },
+        | "-n" | "--count" => {
+          let v = value()?;
+          count = Some(v.parse().map_err(|_| Error::InvalidValue(arg.clone(), v))?);
+        },
+        | "-b" | "--bind" => bind = Some(value()?),
+        | "-m" | "--multicast" => multicast = true,
+        | "-v" | "--verbose" => verbose = true,
+        | "-h" | "--help" => positional.insert(0, "help".to_string()),
+        | opt if opt.starts_with('-') && opt.len() > 1 => {
+          return Err(Error::UnknownOption(opt.to_string()))
+        },
+        | _ => positional.push(arg),
+      }
+    }
+
+    let mut positional = positional.into_iter();
+    let command = positional.next();
+    let uri = |p: Option<String>| {
+      p.ok_or(Error::Missing("<uri>"))
+       .and_then(|p| Uri::parse(&p).map_err(Error::Uri))
+    };
+
+    let request = |method, p| -> Result<Command, Error> {
+      Ok(Command::Request { method,
+                            uri: uri(p)?,
+                            payload: payload.clone(),
+                            format,
+                            accept })
+    };
+
+    let command = match command.as_deref() {
+      | Some("get") => request(Method::Get, positional.next())?,
+      | Some("post") => request(Method::Post, positional.next())?,
+      | Some("put") => request(Method::Put, positional.next())?,
+      | Some("delete") => request(Method::Delete, positional.next())?,
+      | Some("observe") => Command::Observe { uri: uri(positional.next())?,
+                                             count },
+      | Some("discover") => match (positional.next(), multicast) {
+        | (Some(p), false) => Command::Discover { uri: Some(uri(Some(p))?) },
+        | (None, true) => Command::Discover { uri: None },
+        | (Some(_), true) => {
+          return Err(Error::InvalidValue("--multicast".into(), "<uri>".into()))
+        },
+        | (None, false) => return Err(Error::Missing("<uri> or --multicast")),
+      },
+      | Some("serve") => {
+        Command::Serve { dir: positional.next()
+                                        .map(PathBuf::from)
+                                        .ok_or(Error::Missing("<dir>"))?,
+                         bind: bind.unwrap_or_else(|| "0.0.0.0:5683".into()) }
+      },
+      | Some("help") => Command::Help,
+      | other => return Err(Error::UnknownCommand(other.map(String::from))),
+    };
+
+    Ok(Args { command,
+              timeout,
+              verbose })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(s: &str) -> Result<Args, Error> {
+    Args::parse(s.split_whitespace().map(String::from))
+  }
+
+  #[test]
+  fn request() {
+    let args = parse("post coap://localhost/hello -p hi -f text -a json -t 0.5").unwrap();
+
+    assert_eq!(args.timeout, Duration::from_millis(500));
+    assert_eq!(args.command,
+               Command::Request { method: Method::Post,
+                                  uri: Uri::parse("coap://localhost/hello").unwrap(),
+                                  payload: Some("hi".into()),
+                                  format: Some(ContentFormat::Text),
+                                  accept: Some(ContentFormat::Json) });
+  }
+
+  #[test]
+  fn discover() {
+    assert_eq!(parse("discover --multicast").unwrap().command,
+               Command::Discover { uri: None });
+    assert_eq!(parse("discover"),
+               Err(Error::Missing("<uri> or --multicast")));
+  }
+
+  #[test]
+  fn serve() {
+    assert_eq!(parse("serve ./www -b 0.0.0.0:1234").unwrap().command,
+               Command::Serve { dir: "./www".into(),
+                                bind: "0.0.0.0:1234".into() });
+  }
+
+  #[test]
+  fn errors() {
+    assert_eq!(parse(""), Err(Error::UnknownCommand(None)));
+    assert_eq!(parse("fetch a"), Err(Error::UnknownCommand(Some("fetch".into()))));
+    assert_eq!(parse("get"), Err(Error::Missing("<uri>")));
+    assert_eq!(parse("get a --nope"), Err(Error::UnknownOption("--nope".into())));
+    assert_eq!(parse("get a -a yaml"),
+               Err(Error::InvalidValue("-a".into(), "yaml".into())));
+    assert_eq!(parse("observe a -n"),
+               Err(Error::InvalidValue("-n".into(), "".into())));
+  }
+}
@@ -0,0 +1,162 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use no_std_net::{IpAddr, SocketAddr};
+use toad::client::Client;
+use toad::config::Config;
+use toad::multicast::ALL_COAP_DEVICES_IP;
+use toad::net::Addrd;
+use toad::platform::Platform as _;
+use toad::req::Req;
+use toad::resp::Resp;
+use toad::time::Millis;
+use toad_msg::{ContentFormat, MessageOptions};
+
+use crate::args::Method;
+use crate::uri::{Uri, DEFAULT_PORT};
+use crate::{Std, T};
+
+/// Bind a client to an ephemeral port
+fn client(config: Config) -> io::Result<Std> {
+  Std::try_new("0.0.0.0:0", config)
+}
+
+fn timed_out(uri: impl std::fmt::Display) -> io::Error {
+  io::Error::new(io::ErrorKind::TimedOut,
+                 format!("no response from {} in time", uri))
+}
+
+/// Create a request for `uri`, including its query parameters
+fn request(method: Method, uri: &Uri) -> Req<T> {
+  let mut req = match method {
+    | Method::Get => Req::get(&uri.path),
+    | Method::Post => Req::post(&uri.path),
+    | Method::Put => Req::put(&uri.path),
+    | Method::Delete => Req::delete(&uri.path),
+  };
+
+  uri.query.iter().for_each(|q| {
+                    req.msg_mut().add_query(q).ok();
+                  });
+
+  req
+}
+
+/// Print a response: the code & payload, or the whole message when `verbose`
+fn print(resp: &Addrd<Resp<T>>, verbose: bool) {
+  if verbose {
+    println!("{:#?}", resp);
+    return;
+  }
+
+  let code = resp.data().code().to_string();
+  let payload = &resp.data().msg().payload.0;
+  match std::str::from_utf8(payload) {
+    | _ if payload.is_empty() => println!("{}", code),
+    | Ok(s) => println!("{}\n{}", code, s),
+    | Err(_) => println!("{}\n<{} bytes of binary data>", code, payload.len()),
+  }
+}
+
+/// Block until a response arrives, or `timeout` elapses
+fn await_resp(client: &Std,
+              (token, addr): (toad_msg::Token, SocketAddr),
+              timeout: Duration)
+              -> io::Result<Addrd<Resp<T>>> {
+  let start = Instant::now();
+
+  loop {
+    match client.poll_resp(token, addr) {
+      | Ok(resp) => break Ok(resp),
+      | Err(nb::Error::WouldBlock) if start.elapsed() < timeout => continue,
+      | Err(nb::Error::WouldBlock) => break Err(timed_out(addr)),
+      | Err(nb::Error::Other(e)) => break Err(e),
+    }
+  }
+}
+
+/// `toad get|post|put|delete <uri>`
+pub fn send(method: Method,
+            uri: &Uri,
+            payload: Option<&str>,
+            format: Option<ContentFormat>,
+            accept: Option<ContentFormat>,
+            timeout: Duration,
+            verbose: bool)
+            -> io::Result<()> {
+  let client = client(Config::default())?;
+  let addr = uri.resolve()?;
+
+  let mut req = request(method, uri);
+  if let Some(payload) = payload {
+    req.set_payload(payload);
+  }
+  if let Some(format) = format {
+    req.msg_mut().set_content_format(format).ok();
+  }
+  if let Some(accept) = accept {
+    req.msg_mut().set_accept(accept).ok();
+  }
+
+  let (_, token) = nb::block!(client.send_msg(Addrd(req.into(), addr)))?;
+  let resp = await_resp(&client, (token, addr), timeout)?;
+  print(&resp, verbose);
+
+  Ok(())
+}
+
+/// `toad observe <uri>`
+pub fn observe(uri: &Uri, count: Option<usize>, verbose: bool) -> io::Result<()> {
+  let client = client(Config::default())?;
+  let addr = uri.resolve()?;
+
+  let mut sub = client.observe(addr, &uri.path)?;
+
+  for _ in 0..count.unwrap_or(usize::MAX) {
+    let notification = nb::block!(sub.next())?;
+    print(&notification, verbose);
+  }
+
+  sub.cancel()
+}
+
+/// `toad discover [<uri>] [--multicast]`
+///
+/// Prints the [link-format](https://www.rfc-editor.org/rfc/rfc6690) resource
+/// descriptions of each server that responds.
+pub fn discover(uri: Option<&Uri>, timeout: Duration, verbose: bool) -> io::Result<()> {
+  let mut req = Req::<T>::get(".well-known/core");
+
+  match uri {
+    | Some(uri) => {
+      let client = client(Config::default())?;
+      let addr = uri.resolve()?;
+
+      let (_, token) = nb::block!(client.send_msg(Addrd(req.into(), addr)))?;
+      let resp = await_resp(&client, (token, addr), timeout)?;
+      print(&resp, verbose);
+    },
+    | None => {
+      let mut config = Config::default();
+      config.msg.multicast_response_leisure = Millis::new(timeout.as_millis() as u64);
+
+      let client = client(config)?;
+      let addr = SocketAddr::new(IpAddr::V4(ALL_COAP_DEVICES_IP), DEFAULT_PORT);
+
+      // multicast requests must be non-confirmable
+      req.non();
+
+      let resps = client.multicast::<Vec<_>>(Addrd(req, addr))?;
+      if resps.is_empty() {
+        return Err(timed_out(addr));
+      }
+
+      resps.iter().for_each(|resp| {
+                    println!("{}:", resp.addr());
+                    print(resp, verbose);
+                  });
+    },
+  }
+
+  Ok(())
+}
@@ -0,0 +1,83 @@
+//! `toad` is a command-line CoAP client & server built on
+//! the [`toad`](https://docs.rs/toad) runtime, for poking at CoAP
+//! devices while developing & debugging.
+//!
+//! ```text
+//! $ toad get coap://coap.me/hello
+//! 2.05
+//! world
+//!
+//! $ toad post coap://127.0.0.1/sensors -p '{"on":true}' -f json
+//! $ toad observe coap://127.0.0.1/time -n 3
+//! $ toad discover --multicast
+//! $ toad serve ./public
+//! ```
+//!
+//! Run `toad help` for all commands & options.
+
+// x-release-please-start-version
+#![doc(html_root_url = "https://docs.rs/toad-cli/0.0.0")]
+// x-release-please-end
+
+use std::process::ExitCode;
+
+use toad::std::dtls;
+use toad::step::runtime;
+
+mod args;
+mod client;
+mod serve;
+mod uri;
+
+use args::{Args, Command, USAGE};
+
+pub(crate) type T = toad::std::PlatformTypes<dtls::N>;
+pub(crate) type Std = toad::std::Platform<dtls::N, runtime::std::Runtime<dtls::N>>;
+
+fn main() -> ExitCode {
+  let args = match Args::parse(std::env::args().skip(1)) {
+    | Ok(args) => args,
+    | Err(e) => {
+      eprintln!("error: {}\n\n{}", e, USAGE);
+      return ExitCode::from(2);
+    },
+  };
+
+  let level = if args.verbose {
+    log::LevelFilter::Debug
+  } else {
+    log::LevelFilter::Warn
+  };
+  simple_logger::SimpleLogger::new().with_level(level)
+                                    .init()
+                                    .ok();
+
+  let result = match &args.command {
+    | Command::Request { method,
+                         uri,
+                         payload,
+                         format,
+                         accept, } => client::send(*method,
+                                                   uri,
+                                                   payload.as_deref(),
+                                                   *format,
+                                                   *accept,
+                                                   args.timeout,
+                                                   args.verbose),
+    | Command::Observe { uri, count } => client::observe(uri, *count, args.verbose),
+    | Command::Discover { uri } => client::discover(uri.as_ref(), args.timeout, args.verbose),
+    | Command::Serve { dir, bind } => serve::serve(dir, bind),
+    | Command::Help => {
+      println!("{}", USAGE);
+      Ok(())
+    },
+  };
+
+  match result {
+    | Ok(()) => ExitCode::SUCCESS,
+    | Err(e) => {
+      eprintln!("error: {}", e);
+      ExitCode::FAILURE
+    },
+  }
+}
@@ -0,0 +1,29 @@
+use std::io;
+use std::path::Path;
+
+use toad::config::Config;
+use toad::server::ap::state::{Complete, Hydrated};
+use toad::server::resources::Dir;
+use toad::server::{path, respond, Ap, BlockingServer, Init};
+
+use crate::{Std, T};
+
+/// Respond 4.04 Not Found to requests that did not match a file
+fn not_found(ap: Ap<Hydrated, T, (), io::Error>) -> Ap<Complete, T, (), io::Error> {
+  ap.pipe(path::rest(|_, p| Ap::ok(p.to_string())))
+    .bind(|p| respond::not_found(format!("{} not found", p).into()))
+}
+
+/// `toad serve <dir>`
+pub fn serve(dir: &Path, bind: &str) -> io::Result<()> {
+  if !dir.is_dir() {
+    return Err(io::Error::new(io::ErrorKind::NotFound,
+                              format!("{} is not a directory", dir.display())));
+  }
+
+  let server = Std::try_new(bind, Config::default())?;
+  let files = Dir::new(dir);
+
+  server.run(Init::none(), |run| run.maybe(|ap| ap.pipe(files.serve())).maybe(not_found))
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))
+}
@@ -0,0 +1,156 @@
+use std::fmt;
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use no_std_net::{SocketAddrV4, SocketAddrV6};
+
+/// The default port for the `coap` scheme
+pub const DEFAULT_PORT: u16 = 5683;
+
+/// A parsed `coap://` URI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Uri {
+  /// Hostname or IP address, without brackets for IPv6 literals
+  pub host: String,
+  /// Port, defaulting to [`DEFAULT_PORT`]
+  pub port: u16,
+  /// Path without leading `/`, e.g. `sensors/temp`
+  pub path: String,
+  /// Query parameters, e.g. `["unit=c"]` for `?unit=c`
+  pub query: Vec<String>,
+}
+
+/// Errors encountered while parsing a [`Uri`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+  /// The URI did not start with `coap://`
+  UnsupportedScheme(String),
+  /// The host was empty or an IPv6 literal was missing its closing `]`
+  InvalidHost,
+  /// The port was not a valid u16
+  InvalidPort(String),
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      | Error::UnsupportedScheme(s) => write!(f, "unsupported scheme `{}`, expected `coap`", s),
+      | Error::InvalidHost => write!(f, "invalid host"),
+      | Error::InvalidPort(p) => write!(f, "invalid port `{}`", p),
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl Uri {
+  /// Parse a URI like `coap://host:port/path?query`
+  ///
+  /// The scheme may be omitted, in which case `coap` is assumed.
+  pub fn parse(s: &str) -> Result<Self, Error> {
+    let rest = match s.split_once("://") {
+      | Some(("coap", rest)) => rest,
+      | Some((scheme, _)) => return Err(Error::UnsupportedScheme(scheme.to_string())),
+      | None => s,
+    };
+
+    let (authority, path_and_query) = match rest.find(|c| c == '/' || c == '?') {
+      | Some(ix) => rest.split_at(ix),
+      | None => (rest, ""),
+    };
+
+    let (host, port) = match authority.strip_prefix('[') {
+      | Some(v6) => match v6.split_once(']') {
+        | Some((host, port)) => (host, port.strip_prefix(':')),
+        | None => return Err(Error::InvalidHost),
+      },
+      | None => match authority.split_once(':') {
+        | Some((host, port)) => (host, Some(port)),
+        | None => (authority, None),
+      },
+    };
+
+    if host.is_empty() {
+      return Err(Error::InvalidHost);
+    }
+
+    let port = match port {
+      | Some(p) => p.parse().map_err(|_| Error::InvalidPort(p.to_string()))?,
+      | None => DEFAULT_PORT,
+    };
+
+    let (path, query) = match path_and_query.split_once('?') {
+      | Some((path, query)) => (path, Some(query)),
+      | None => (path_and_query, None),
+    };
+
+    Ok(Uri { host: host.to_string(),
+             port,
+             path: path.trim_start_matches('/').to_string(),
+             query: query.into_iter()
+                         .flat_map(|q| q.split('&'))
+                         .filter(|q| !q.is_empty())
+                         .map(String::from)
+                         .collect() })
+  }
+
+  /// Resolve the host to a socket address, preferring IPv4
+  pub fn resolve(&self) -> std::io::Result<no_std_net::SocketAddr> {
+    let addrs = (self.host.as_str(), self.port).to_socket_addrs()?
+                                                .collect::<Vec<_>>();
+
+    addrs.iter()
+         .find(|a| a.is_ipv4())
+         .or_else(|| addrs.first())
+         .map(|a| match a {
+           | SocketAddr::V4(a) => SocketAddrV4::new(a.ip().octets().into(), a.port()).into(),
+           | SocketAddr::V6(a) => {
+             SocketAddrV6::new(a.ip().octets().into(), a.port(), a.flowinfo(), a.scope_id()).into()
+           },
+         })
+         .ok_or_else(|| {
+           std::io::Error::new(std::io::ErrorKind::NotFound,
+                               format!("could not resolve {}", self.host))
+         })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_full() {
+    assert_eq!(Uri::parse("coap://example.com:1234/sensors/temp?unit=c&fmt=json"),
+               Ok(Uri { host: "example.com".into(),
+                        port: 1234,
+                        path: "sensors/temp".into(),
+                        query: vec!["unit=c".into(), "fmt=json".into()] }));
+  }
+
+  #[test]
+  fn parse_defaults() {
+    assert_eq!(Uri::parse("127.0.0.1"),
+               Ok(Uri { host: "127.0.0.1".into(),
+                        port: DEFAULT_PORT,
+                        path: "".into(),
+                        query: vec![] }));
+  }
+
+  #[test]
+  fn parse_ipv6() {
+    let uri = Uri::parse("coap://[::1]:5684/.well-known/core").unwrap();
+    assert_eq!(uri.host, "::1");
+    assert_eq!(uri.port, 5684);
+    assert_eq!(uri.path, ".well-known/core");
+
+    assert_eq!(Uri::parse("coap://[::1/a"), Err(Error::InvalidHost));
+  }
+
+  #[test]
+  fn parse_errors() {
+    assert_eq!(Uri::parse("http://a/b"),
+               Err(Error::UnsupportedScheme("http".into())));
+    assert_eq!(Uri::parse("coap://:80/"), Err(Error::InvalidHost));
+    assert_eq!(Uri::parse("coap://a:b/"), Err(Error::InvalidPort("b".into())));
+  }
+}
@@ -22,6 +22,8 @@
 #[cfg(feature = "alloc")]
 extern crate alloc as std_alloc;
 
+use core::ops::{Deref, DerefMut};
+
 /// A cursor over a byte array (std- and alloc-less port of [`std::io::Cursor`])
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Cursor<T> {
@@ -213,6 +215,110 @@ impl<T: AsRef<[u8]>> Cursor<T> {
   pub fn position(&self) -> usize {
     self.cursor
   }
+
+  /// Move the cursor to `pos` within the buffer,
+  /// e.g. a position previously yielded by [`Cursor::position`].
+  ///
+  /// Positions past the end of the buffer are clamped to the end.
+  /// Returns the new position.
+  ///
+  /// Runs in O(1) time.
+  ///
+  /// ```
+  /// use toad_cursor::Cursor;
+  ///
+  /// let mut cur = Cursor::new([1u8, 2, 3]);
+  /// cur.skip(2);
+  /// assert_eq!(cur.seek(1), 1);
+  /// assert_eq!(cur.next(), Some(2));
+  /// assert_eq!(cur.seek(10), 3);
+  /// assert!(cur.is_exhausted());
+  /// ```
+  pub fn seek(&mut self, pos: usize) -> usize {
+    self.cursor = pos.min(self.len);
+    self.cursor
+  }
+
+  /// Remember the current position, so that bytes consumed
+  /// through the returned [`Checkpoint`] are given back
+  /// when it is dropped unless it is [committed](Checkpoint::commit).
+  ///
+  /// Useful for speculatively parsing a structure that may
+  /// turn out not to be present.
+  ///
+  /// ```
+  /// use toad_cursor::Cursor;
+  ///
+  /// let mut cur = Cursor::new([1u8, 2, 3]);
+  ///
+  /// {
+  ///   let mut cp = cur.checkpoint();
+  ///   assert_eq!(cp.take(2), &[1, 2]);
+  ///   // dropped without committing
+  /// }
+  /// assert_eq!(cur.position(), 0);
+  ///
+  /// let mut cp = cur.checkpoint();
+  /// assert_eq!(cp.take(2), &[1, 2]);
+  /// cp.commit();
+  /// assert_eq!(cur.position(), 2);
+  /// ```
+  pub fn checkpoint(&mut self) -> Checkpoint<'_, T> {
+    Checkpoint { pos: self.cursor,
+                 cursor: self,
+                 committed: false }
+  }
+}
+
+/// A guard over a [`Cursor`] that rolls the cursor back to where it
+/// was when the checkpoint was made, unless [`Checkpoint::commit`] is invoked.
+///
+/// Dereferences to the [`Cursor`], see [`Cursor::checkpoint`].
+#[derive(Debug)]
+pub struct Checkpoint<'a, T: AsRef<[u8]>> {
+  cursor: &'a mut Cursor<T>,
+  pos: usize,
+  committed: bool,
+}
+
+impl<'a, T: AsRef<[u8]>> Checkpoint<'a, T> {
+  /// Keep the bytes consumed since the checkpoint was made
+  pub fn commit(mut self) {
+    self.committed = true;
+  }
+
+  /// Roll the cursor back to the checkpoint now,
+  /// without dropping the checkpoint
+  pub fn rollback(&mut self) {
+    self.cursor.seek(self.pos);
+  }
+
+  /// The position the cursor will be rolled back to
+  pub fn checkpoint_position(&self) -> usize {
+    self.pos
+  }
+}
+
+impl<'a, T: AsRef<[u8]>> Deref for Checkpoint<'a, T> {
+  type Target = Cursor<T>;
+
+  fn deref(&self) -> &Cursor<T> {
+    self.cursor
+  }
+}
+
+impl<'a, T: AsRef<[u8]>> DerefMut for Checkpoint<'a, T> {
+  fn deref_mut(&mut self) -> &mut Cursor<T> {
+    self.cursor
+  }
+}
+
+impl<'a, T: AsRef<[u8]>> Drop for Checkpoint<'a, T> {
+  fn drop(&mut self) {
+    if !self.committed {
+      self.rollback();
+    }
+  }
 }
 
 #[cfg(test)]
@@ -316,6 +422,79 @@ mod tests {
     assert_eq!(til_slash(&mut cur), "");
   }
 
+  #[test]
+  pub fn seek_to_position() {
+    let mut cur = Cursor::new(vec![1, 2, 3, 4]);
+    cur.skip(3);
+    assert_eq!(cur.seek(1), 1);
+    assert_eq!(cur.peek_until_end(), &[2, 3, 4]);
+    assert_eq!(cur.seek(4), 4);
+    assert!(cur.is_exhausted());
+    assert_eq!(cur.seek(5), 4);
+    assert_eq!(cur.seek(0), 0);
+    assert_eq!(cur.remaining(), 4);
+  }
+
+  #[test]
+  pub fn checkpoint_rolls_back_on_drop() {
+    let mut cur = Cursor::new(vec![1, 2, 3, 4]);
+    cur.skip(1);
+
+    {
+      let mut cp = cur.checkpoint();
+      assert_eq!(cp.take(2), &[2, 3]);
+      assert_eq!(cp.position(), 3);
+    }
+
+    assert_eq!(cur.position(), 1);
+    assert_eq!(cur.peek_until_end(), &[2, 3, 4]);
+  }
+
+  #[test]
+  pub fn checkpoint_commit() {
+    let mut cur = Cursor::new(vec![1, 2, 3, 4]);
+
+    let mut cp = cur.checkpoint();
+    cp.skip(2);
+    cp.commit();
+
+    assert_eq!(cur.position(), 2);
+  }
+
+  #[test]
+  pub fn checkpoint_rollback_then_commit() {
+    let mut cur = Cursor::new(vec![1, 2, 3, 4]);
+
+    let mut cp = cur.checkpoint();
+    cp.skip(3);
+    cp.rollback();
+    assert_eq!(cp.position(), 0);
+    assert_eq!(cp.checkpoint_position(), 0);
+    cp.skip(1);
+    cp.commit();
+
+    assert_eq!(cur.position(), 1);
+  }
+
+  #[test]
+  pub fn nested_checkpoints() {
+    let mut cur = Cursor::new(vec![1, 2, 3, 4]);
+
+    {
+      let mut outer = cur.checkpoint();
+      outer.skip(1);
+
+      {
+        let mut inner = outer.checkpoint();
+        inner.skip(2);
+        inner.commit();
+      }
+      assert_eq!(outer.position(), 3);
+    }
+
+    assert_eq!(cur.position(), 0);
+  }
+
   #[test]
   pub fn seek() {
     let mut cur = Cursor::new(vec![1, 2, 3, 4]);
@@ -0,0 +1,406 @@
+//! This microcrate provides a [`toad::net::Socket`] implementation
+//! that delegates to `extern "C"` callbacks, allowing toad to run on
+//! top of existing RTOS network stacks (e.g. Zephyr or RIOT's BSD-style sockets)
+//! without rewriting the IP layer.
+//!
+//! ## Usage
+//! The host application fills out a [`Callbacks`] table with functions
+//! wrapping its socket API, then either:
+//! - registers it globally with [`toad_ffi_net_set_callbacks`] so that
+//!   [`Socket::bind`](toad::net::Socket::bind) works as usual, or
+//! - passes it directly to [`Socket::bind_with`].
+//!
+//! ```c
+//! static intptr_t zephyr_send(intptr_t fd, const uint8_t *buf, size_t len, const toad_ffi_net_sockaddr *addr) {
+//!   struct sockaddr_in to = to_zephyr_addr(addr);
+//!   ssize_t n = zsock_sendto(fd, buf, len, ZSOCK_MSG_DONTWAIT, (struct sockaddr *)&to, sizeof(to));
+//!   if (n < 0 && errno == EAGAIN) return TOAD_FFI_WOULD_BLOCK;
+//!   return n < 0 ? -errno : n;
+//! }
+//! ```
+//!
+//! ## Return values
+//! All callbacks return a signed integer, where:
+//! - non-negative values indicate success (e.g. number of bytes, or a socket handle)
+//! - [`WOULD_BLOCK`] indicates that the operation can't complete right now
+//! - any other negative value is an error code, surfaced as [`Error::Code`]
+
+// docs
+#![doc(html_root_url = "https://docs.rs/toad-ffi-net/0.0.0")]
+#![cfg_attr(any(docsrs, feature = "docs"), feature(doc_cfg))]
+// -
+// style
+#![allow(clippy::unused_unit)]
+// -
+// deny
+#![deny(missing_docs)]
+#![deny(missing_debug_implementations)]
+#![deny(missing_copy_implementations)]
+#![deny(unsafe_op_in_unsafe_fn)]
+// -
+// warnings
+#![cfg_attr(not(test), warn(unreachable_pub))]
+// -
+// features
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc as std_alloc;
+
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use no_std_net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use tinyvec::ArrayVec;
+use toad::net::Addrd;
+
+/// Returned by callbacks when the operation would block
+pub const WOULD_BLOCK: isize = isize::MIN;
+
+/// [`WOULD_BLOCK`], exported for C callers
+#[no_mangle]
+pub static TOAD_FFI_WOULD_BLOCK: isize = WOULD_BLOCK;
+
+/// An IP address passed across the FFI boundary
+///
+/// IPv4 addresses occupy the first 4 bytes of `octets`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IpAddress {
+  /// `true` when this is an IPv6 address
+  pub is_v6: bool,
+  /// Address octets in network order
+  pub octets: [u8; 16],
+}
+
+/// A socket address (IP + port) passed across the FFI boundary
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SockAddr {
+  /// IP Address
+  pub ip: IpAddress,
+  /// Port, in host byte order
+  pub port: u16,
+}
+
+impl From<IpAddr> for IpAddress {
+  fn from(ip: IpAddr) -> Self {
+    let mut octets = [0u8; 16];
+    match ip {
+      | IpAddr::V4(ip) => {
+        octets[..4].copy_from_slice(&ip.octets());
+        Self { is_v6: false,
+               octets }
+      },
+      | IpAddr::V6(ip) => Self { is_v6: true,
+                                 octets: ip.octets() },
+    }
+  }
+}
+
+impl From<IpAddress> for IpAddr {
+  fn from(ip: IpAddress) -> Self {
+    let o = ip.octets;
+    if ip.is_v6 {
+      IpAddr::V6(Ipv6Addr::from(o))
+    } else {
+      IpAddr::V4(Ipv4Addr::new(o[0], o[1], o[2], o[3]))
+    }
+  }
+}
+
+impl From<SocketAddr> for SockAddr {
+  fn from(addr: SocketAddr) -> Self {
+    Self { ip: addr.ip().into(),
+           port: addr.port() }
+  }
+}
+
+impl From<SockAddr> for SocketAddr {
+  fn from(addr: SockAddr) -> Self {
+    SocketAddr::new(addr.ip.into(), addr.port)
+  }
+}
+
+/// Table of functions implementing a datagram socket
+///
+/// `handle`s are opaque to toad, and are whatever was returned by `bind`
+/// (e.g. a file descriptor).
+///
+/// See the [crate documentation](crate#return-values) for the meaning of return values.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Callbacks {
+  /// Create a non-blocking udp socket bound to `addr`, returning a handle for it
+  pub bind: unsafe extern "C" fn(addr: *const SockAddr) -> isize,
+
+  /// Send `len` bytes starting at `buf` to `addr`
+  ///
+  /// Returns the number of bytes sent.
+  pub send:
+    unsafe extern "C" fn(handle: isize, buf: *const u8, len: usize, addr: *const SockAddr) -> isize,
+
+  /// Receive a datagram into the `len` bytes starting at `buf`,
+  /// writing the sender's address to `addr`.
+  ///
+  /// If `peek` is true, the datagram must not be removed from the socket's
+  /// receive queue.
+  ///
+  /// Datagrams larger than `len` should be truncated, not considered an error.
+  ///
+  /// Returns the number of bytes written to `buf`.
+  pub recv: unsafe extern "C" fn(handle: isize,
+                                 buf: *mut u8,
+                                 len: usize,
+                                 addr: *mut SockAddr,
+                                 peek: bool)
+                                 -> isize,
+
+  /// Write the local address of the socket to `addr`
+  pub local_addr: unsafe extern "C" fn(handle: isize, addr: *mut SockAddr) -> isize,
+
+  /// Join the multicast group `addr`
+  pub join_multicast: unsafe extern "C" fn(handle: isize, addr: *const IpAddress) -> isize,
+}
+
+static CALLBACKS: AtomicPtr<Callbacks> = AtomicPtr::new(ptr::null_mut());
+
+/// Register the [`Callbacks`] used by [`Socket::bind`](toad::net::Socket::bind)
+///
+/// # Safety
+/// `callbacks` must be null, or point to a `Callbacks` that is never
+/// moved or freed (e.g. a `static`).
+#[no_mangle]
+pub unsafe extern "C" fn toad_ffi_net_set_callbacks(callbacks: *const Callbacks) {
+  CALLBACKS.store(callbacks as *mut Callbacks, Ordering::Release);
+}
+
+fn global_callbacks() -> Option<&'static Callbacks> {
+  // SAFETY: `toad_ffi_net_set_callbacks` requires that the pointer
+  // is either null or valid for 'static
+  unsafe { CALLBACKS.load(Ordering::Acquire).as_ref() }
+}
+
+/// Errors yielded by [`Socket`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+  /// A callback returned a negative value other than [`WOULD_BLOCK`]
+  Code(isize),
+  /// [`toad_ffi_net_set_callbacks`] has not been invoked
+  CallbacksNotRegistered,
+  /// The address passed to `bind` did not yield any addresses
+  NoAddress,
+}
+
+fn check(n: isize) -> nb::Result<usize, Error> {
+  match n {
+    | WOULD_BLOCK => Err(nb::Error::WouldBlock),
+    | n if n < 0 => Err(nb::Error::Other(Error::Code(n))),
+    | n => Ok(n as usize),
+  }
+}
+
+fn check_blocking(n: isize) -> Result<usize, Error> {
+  check(n).map_err(|e| match e {
+            | nb::Error::WouldBlock => Error::Code(WOULD_BLOCK),
+            | nb::Error::Other(e) => e,
+          })
+}
+
+/// [`toad::net::Socket`] implementation that delegates to [`Callbacks`]
+#[derive(Debug, Clone, Copy)]
+pub struct Socket {
+  callbacks: &'static Callbacks,
+  handle: isize,
+}
+
+impl Socket {
+  /// Create a socket bound to `addr` using a specific set of [`Callbacks`]
+  pub fn bind_with(callbacks: &'static Callbacks, addr: SocketAddr) -> Result<Self, Error> {
+    let addr = SockAddr::from(addr);
+
+    // SAFETY: `addr` is a valid pointer for the duration of the call
+    let handle = check_blocking(unsafe { (callbacks.bind)(&addr) })?;
+
+    Ok(Self { callbacks,
+              handle: handle as isize })
+  }
+
+  /// Wrap a handle that has already been bound
+  pub fn from_handle(callbacks: &'static Callbacks, handle: isize) -> Self {
+    Self { callbacks, handle }
+  }
+
+  /// Get the handle returned by [`Callbacks::bind`]
+  pub fn handle(&self) -> isize {
+    self.handle
+  }
+
+  fn recv_(&self, buffer: &mut [u8], peek: bool) -> nb::Result<Addrd<usize>, Error> {
+    let mut addr = SockAddr::default();
+
+    // SAFETY: `buffer` and `addr` are valid for writes for the duration of the call
+    let n = unsafe {
+      (self.callbacks.recv)(self.handle,
+                            buffer.as_mut_ptr(),
+                            buffer.len(),
+                            &mut addr,
+                            peek)
+    };
+
+    check(n).map(|n| Addrd(n.min(buffer.len()), addr.into()))
+  }
+}
+
+impl toad::net::Socket for Socket {
+  type Error = Error;
+  type Dgram = ArrayVec<[u8; 1152]>;
+
+  fn local_addr(&self) -> SocketAddr {
+    let mut addr = SockAddr::default();
+
+    // SAFETY: `addr` is valid for writes for the duration of the call
+    let n = unsafe { (self.callbacks.local_addr)(self.handle, &mut addr) };
+
+    check_blocking(n).map(|_| addr.into())
+                     .unwrap_or(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0))
+  }
+
+  fn empty_dgram() -> Self::Dgram {
+    ArrayVec::from([0u8; 1152])
+  }
+
+  fn bind_raw<A: ToSocketAddrs>(addr: A) -> Result<Self, Self::Error> {
+    let callbacks = global_callbacks().ok_or(Error::CallbacksNotRegistered)?;
+    let addr = addr.to_socket_addrs()
+                   .ok()
+                   .and_then(|mut a| a.next())
+                   .ok_or(Error::NoAddress)?;
+
+    Self::bind_with(callbacks, addr)
+  }
+
+  fn send(&self, msg: Addrd<&[u8]>) -> nb::Result<(), Self::Error> {
+    let addr = SockAddr::from(msg.addr());
+
+    // SAFETY: `msg` and `addr` are valid for reads for the duration of the call
+    let n = unsafe {
+      (self.callbacks.send)(self.handle,
+                            msg.data().as_ptr(),
+                            msg.data().len(),
+                            &addr)
+    };
+
+    check(n).map(|_| ())
+  }
+
+  fn recv(&self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
+    self.recv_(buffer, false)
+  }
+
+  fn peek(&self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
+    self.recv_(buffer, true)
+  }
+
+  fn join_multicast(&self, addr: IpAddr) -> Result<(), Self::Error> {
+    let addr = IpAddress::from(addr);
+
+    // SAFETY: `addr` is valid for reads for the duration of the call
+    let n = unsafe { (self.callbacks.join_multicast)(self.handle, &addr) };
+
+    check_blocking(n).map(|_| ())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Mutex;
+
+  use toad::net::{ipv4_socketaddr, Addrd, Socket as _};
+
+  use super::*;
+
+  static SENT: Mutex<Vec<(Vec<u8>, SockAddr)>> = Mutex::new(Vec::new());
+
+  unsafe extern "C" fn bind(addr: *const SockAddr) -> isize {
+    unsafe { (*addr).port as isize }
+  }
+
+  unsafe extern "C" fn send(_: isize, buf: *const u8, len: usize, addr: *const SockAddr) -> isize {
+    let bytes = unsafe { core::slice::from_raw_parts(buf, len) }.to_vec();
+    SENT.lock().unwrap().push((bytes, unsafe { *addr }));
+    len as isize
+  }
+
+  unsafe extern "C" fn recv(handle: isize,
+                            buf: *mut u8,
+                            len: usize,
+                            addr: *mut SockAddr,
+                            peek: bool)
+                            -> isize {
+    let mut sent = SENT.lock().unwrap();
+    if sent.is_empty() {
+      return WOULD_BLOCK;
+    }
+
+    let (bytes, _) = if peek { sent[0].clone() } else { sent.remove(0) };
+    let n = bytes.len().min(len);
+    unsafe {
+      core::ptr::copy_nonoverlapping(bytes.as_ptr(), buf, n);
+      *addr = SockAddr::from(ipv4_socketaddr([127, 0, 0, 1], handle as u16));
+    }
+    n as isize
+  }
+
+  unsafe extern "C" fn local_addr(handle: isize, addr: *mut SockAddr) -> isize {
+    unsafe { *addr = SockAddr::from(ipv4_socketaddr([0, 0, 0, 0], handle as u16)) };
+    0
+  }
+
+  unsafe extern "C" fn join_multicast(_: isize, _: *const IpAddress) -> isize {
+    -22
+  }
+
+  static CALLBACKS: Callbacks = Callbacks { bind,
+                                            send,
+                                            recv,
+                                            local_addr,
+                                            join_multicast };
+
+  #[test]
+  fn sockaddr_roundtrip() {
+    let v4 = ipv4_socketaddr([192, 168, 0, 1], 5683);
+    assert_eq!(SocketAddr::from(SockAddr::from(v4)), v4);
+
+    let v6 = SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1)), 5683);
+    assert_eq!(SocketAddr::from(SockAddr::from(v6)), v6);
+  }
+
+  #[test]
+  fn socket_delegates_to_callbacks() {
+    let sock = Socket::bind_with(&CALLBACKS, ipv4_socketaddr([0, 0, 0, 0], 1234)).unwrap();
+    assert_eq!(sock.handle(), 1234);
+    assert_eq!(sock.local_addr(), ipv4_socketaddr([0, 0, 0, 0], 1234));
+
+    let mut buf = [0u8; 2];
+    assert_eq!(sock.recv(&mut buf), Err(nb::Error::WouldBlock));
+
+    sock.send(Addrd(&[1, 2, 3], ipv4_socketaddr([127, 0, 0, 1], 1234)))
+        .unwrap();
+
+    let from = ipv4_socketaddr([127, 0, 0, 1], 1234);
+    assert_eq!(sock.peek(&mut buf), Ok(Addrd(2, from)));
+    assert_eq!(sock.recv(&mut buf), Ok(Addrd(2, from)));
+    assert_eq!(buf, [1, 2]);
+    assert_eq!(sock.recv(&mut buf), Err(nb::Error::WouldBlock));
+
+    assert_eq!(sock.join_multicast(IpAddr::V4(Ipv4Addr::new(224, 0, 1, 187))),
+               Err(Error::Code(-22)));
+  }
+
+  #[test]
+  fn bind_without_registered_callbacks() {
+    assert_eq!(Socket::bind_raw(ipv4_socketaddr([0, 0, 0, 0], 1)).unwrap_err(),
+               Error::CallbacksNotRegistered);
+  }
+}
@@ -1,6 +1,6 @@
 //! # toad-hash
 //!
-//! This microcrate contains a no_std and no-alloc `Hasher` implementation
+//! This microcrate contains no_std and no-alloc `Hasher` implementations
 //! using the Blake2 hash algorithm
 
 // docs
@@ -28,7 +28,7 @@ extern crate alloc as std_alloc;
 use core::fmt::Debug;
 use core::hash::Hasher;
 
-use blake2::digest::typenum::U8;
+use blake2::digest::typenum::{U16, U8};
 use blake2::{Blake2b, Digest};
 
 /// Heap-allocless [`Hasher`] implementation that uses
@@ -85,3 +85,62 @@ impl Hasher for Blake2Hasher {
     self.0.update(bytes);
   }
 }
+
+/// Heap-allocless [`Hasher`] implementation that uses
+/// the [`blake2`] algo to generate a 128 bit hash.
+///
+/// Prefer this over [`Blake2Hasher`] where collisions are costly,
+/// e.g. when a hash stands in for the data it was computed from.
+///
+/// ```
+/// use core::hash::{Hash, Hasher};
+///
+/// use toad_hash::Blake2Hasher128;
+///
+/// let mut hasher_a = Blake2Hasher128::new();
+/// let mut hasher_b = Blake2Hasher128::new();
+///
+/// "hello".hash(&mut hasher_a);
+/// "hello".hash(&mut hasher_b);
+/// assert_eq!(hasher_a.finish_128(), hasher_b.finish_128());
+/// assert_eq!(hasher_a.finish(), hasher_b.finish());
+///
+/// 123_u16.hash(&mut hasher_a);
+/// "not 123!".hash(&mut hasher_b);
+/// assert_ne!(hasher_a.finish_128(), hasher_b.finish_128());
+/// ```
+#[derive(Default, Clone)]
+pub struct Blake2Hasher128(Blake2b<U16>);
+
+impl Blake2Hasher128 {
+  /// Create a new `Blake2Hasher128`
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Get the 128 bit hash of the data written so far
+  ///
+  /// [`Hasher::finish`] yields this folded to 64 bits.
+  pub fn finish_128(&self) -> u128 {
+    u128::from_be_bytes(self.0.clone().finalize().into())
+  }
+}
+
+impl Debug for Blake2Hasher128 {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_tuple("Blake2Hasher128")
+     .field(&"<Blake2bCore<U16>>")
+     .finish()
+  }
+}
+
+impl Hasher for Blake2Hasher128 {
+  fn finish(&self) -> u64 {
+    let hash = self.finish_128();
+    (hash >> 64) as u64 ^ hash as u64
+  }
+
+  fn write(&mut self, bytes: &[u8]) {
+    self.0.update(bytes);
+  }
+}
@@ -0,0 +1,98 @@
+package dev.toad;
+
+import dev.toad.msg.Code;
+import dev.toad.msg.Message;
+import dev.toad.msg.MessageOption;
+import java.net.InetSocketAddress;
+import java.util.ArrayList;
+
+/**
+ * A CoAP client backed by the toad runtime.
+ *
+ * <p>The native methods are registered by
+ * {@code toad_jni::dev::toad::Client::register_natives}, which must be
+ * invoked when the native library is loaded.
+ *
+ * <p>Instances are not safe to share between threads without
+ * external synchronization; all public methods are {@code synchronized}.
+ */
+public final class Client implements AutoCloseable {
+  private static final long OPT_OBSERVE = 6;
+
+  private long ptr;
+
+  private Client(long ptr) {
+    this.ptr = ptr;
+  }
+
+  /** Create a client bound to a local address, e.g. {@code "0.0.0.0:5683"} */
+  public static Client bind(String addr) {
+    return new Client(Client.init(addr));
+  }
+
+  /** Send a GET request and block until a response is received */
+  public synchronized Message get(InetSocketAddress to, String path) {
+    return this.request(Message.request(to, Code.GET, path, new byte[] {}));
+  }
+
+  /** Send a POST request and block until a response is received */
+  public synchronized Message post(InetSocketAddress to, String path, byte[] payload) {
+    return this.request(Message.request(to, Code.POST, path, payload));
+  }
+
+  /** Send a request and block until a response is received */
+  public synchronized Message request(Message req) {
+    byte[] token = Client.send(this.ptr, req);
+    return Client.pollResp(this.ptr, token, req.addr);
+  }
+
+  /**
+   * Register interest in a resource, returning an
+   * {@link Observation} that yields each notification.
+   */
+  public synchronized Observation observe(InetSocketAddress to, String path) {
+    Message req = Message.request(to, Code.GET, path, new byte[] {});
+    ArrayList<byte[]> register = new ArrayList<>();
+    register.add(new byte[] {});
+    req.options.add(new MessageOption(OPT_OBSERVE, register));
+
+    byte[] token = Client.send(this.ptr, req);
+    return new Observation(this, token, to);
+  }
+
+  @Override
+  public synchronized void close() {
+    if (this.ptr != 0) {
+      Client.drop(this.ptr);
+      this.ptr = 0;
+    }
+  }
+
+  /** A stream of notifications for an observed resource */
+  public static final class Observation {
+    private final Client client;
+    private final byte[] token;
+    private final InetSocketAddress addr;
+
+    private Observation(Client client, byte[] token, InetSocketAddress addr) {
+      this.client = client;
+      this.token = token;
+      this.addr = addr;
+    }
+
+    /** Block until the next notification is received */
+    public Message next() {
+      synchronized (this.client) {
+        return Client.pollResp(this.client.ptr, this.token, this.addr);
+      }
+    }
+  }
+
+  private static native long init(String addr);
+
+  private static native void drop(long ptr);
+
+  private static native byte[] send(long ptr, Message msg);
+
+  private static native Message pollResp(long ptr, byte[] token, InetSocketAddress addr);
+}
@@ -0,0 +1,105 @@
+package dev.toad;
+
+import dev.toad.msg.Message;
+import java.util.ArrayList;
+
+/**
+ * A CoAP server backed by the toad runtime.
+ *
+ * <p>Requests are dispatched to the handlers registered with {@link
+ * #route(String, Handler)}, in the order they were registered.
+ *
+ * <p>The native methods are registered by
+ * {@code toad_jni::dev::toad::Server::register_natives}, which must be
+ * invoked when the native library is loaded.
+ *
+ * <p>Routes should be registered before the server is {@link #run() run}.
+ */
+public final class Server implements AutoCloseable {
+  /** Handles requests to a route */
+  @FunctionalInterface
+  public interface Handler {
+    /**
+     * Handle a request, returning the response to send
+     * (see {@link Message#response(dev.toad.msg.Code, byte[])})
+     * or {@code null} to let the next matching route handle it.
+     */
+    Message handle(Message req);
+  }
+
+  static final class Route {
+    final String path;
+    final Handler handler;
+
+    Route(String path, Handler handler) {
+      this.path = path;
+      this.handler = handler;
+    }
+  }
+
+  private long ptr;
+  private final ArrayList<Route> routes;
+  private final Object runLock;
+  private volatile boolean running;
+
+  private Server(long ptr) {
+    this.ptr = ptr;
+    this.routes = new ArrayList<>();
+    this.runLock = new Object();
+    this.running = false;
+  }
+
+  /** Create a server bound to a local address, e.g. {@code "0.0.0.0:5683"} */
+  public static Server bind(String addr) {
+    return new Server(Server.init(addr));
+  }
+
+  /**
+   * Handle requests to {@code path} (e.g. {@code "sensors/temperature"})
+   * with {@code handler}.
+   *
+   * <p>Requests not handled by any route are responded to with 4.04 Not Found.
+   */
+  public synchronized Server route(String path, Handler handler) {
+    String trimmed = path;
+    while (trimmed.startsWith("/")) {
+      trimmed = trimmed.substring(1);
+    }
+    while (trimmed.endsWith("/")) {
+      trimmed = trimmed.substring(0, trimmed.length() - 1);
+    }
+
+    this.routes.add(new Route(trimmed, handler));
+    return this;
+  }
+
+  /** Handle requests on the current thread until {@link #stop()} is invoked */
+  public void run() {
+    synchronized (this.runLock) {
+      this.running = true;
+      Server.run(this.ptr, this);
+    }
+  }
+
+  /** Stop a server started with {@link #run()} once it finishes the request it is handling */
+  public void stop() {
+    this.running = false;
+  }
+
+  @Override
+  public synchronized void close() {
+    this.stop();
+    synchronized (this.runLock) {
+      if (this.ptr != 0) {
+        Server.drop(this.ptr);
+        this.ptr = 0;
+      }
+    }
+  }
+
+  private static native long init(String addr);
+
+  private static native void drop(long ptr);
+
+  private static native void run(long ptr, Server server);
+}
@@ -0,0 +1,42 @@
+package dev.toad.msg;
+
+/** A CoAP message code, e.g. {@code 2.05} */
+public final class Code {
+  public static final Code EMPTY = new Code(0, 0);
+  public static final Code GET = new Code(0, 1);
+  public static final Code POST = new Code(0, 2);
+  public static final Code PUT = new Code(0, 3);
+  public static final Code DELETE = new Code(0, 4);
+  public static final Code CREATED = new Code(2, 1);
+  public static final Code DELETED = new Code(2, 2);
+  public static final Code CHANGED = new Code(2, 4);
+  public static final Code CONTENT = new Code(2, 5);
+  public static final Code BAD_REQUEST = new Code(4, 0);
+  public static final Code NOT_FOUND = new Code(4, 4);
+  public static final Code INTERNAL_SERVER_ERROR = new Code(5, 0);
+
+  public final int clazz;
+  public final int detail;
+
+  public Code(int clazz, int detail) {
+    this.clazz = clazz;
+    this.detail = detail;
+  }
+
+  @Override
+  public boolean equals(Object other) {
+    return other instanceof Code
+        && ((Code) other).clazz == this.clazz
+        && ((Code) other).detail == this.detail;
+  }
+
+  @Override
+  public int hashCode() {
+    return this.clazz * 100 + this.detail;
+  }
+
+  @Override
+  public String toString() {
+    return String.format("%d.%02d", this.clazz, this.detail);
+  }
+}
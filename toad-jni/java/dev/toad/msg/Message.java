@@ -0,0 +1,73 @@
+package dev.toad.msg;
+
+import java.net.InetSocketAddress;
+import java.nio.charset.StandardCharsets;
+import java.util.ArrayList;
+
+/** A CoAP message, along with the address it was sent to or received from */
+public final class Message {
+  public static final int CON = 0;
+  public static final int NON = 1;
+  public static final int ACK = 2;
+  public static final int RESET = 3;
+
+  private static final long OPT_URI_PATH = 11;
+
+  public InetSocketAddress addr;
+  public int type;
+  public Code code;
+  public int id;
+  public byte[] token;
+  public ArrayList<MessageOption> options;
+  public byte[] payload;
+
+  public Message() {
+    this.type = CON;
+    this.code = Code.EMPTY;
+    this.token = new byte[] {};
+    this.options = new ArrayList<>();
+    this.payload = new byte[] {};
+  }
+
+  /**
+   * Create a confirmable request; the message id and token
+   * will be filled in by the runtime.
+   */
+  public static Message request(InetSocketAddress to, Code code, String path, byte[] payload) {
+    Message m = new Message();
+    m.addr = to;
+    m.code = code;
+    m.payload = payload;
+
+    ArrayList<byte[]> segments = new ArrayList<>();
+    for (String s : path.split("/")) {
+      if (!s.isEmpty()) {
+        segments.add(s.getBytes(StandardCharsets.UTF_8));
+      }
+    }
+    m.options.add(new MessageOption(OPT_URI_PATH, segments));
+
+    return m;
+  }
+
+  /**
+   * Create a response to be returned from a {@code dev.toad.Server.Handler};
+   * the address, type, id and token will be filled in by the runtime.
+   */
+  public static Message response(Code code, byte[] payload) {
+    Message m = new Message();
+    m.code = code;
+    m.payload = payload;
+    return m;
+  }
+
+  /** Create a response with a UTF-8 string payload */
+  public static Message response(Code code, String payload) {
+    return Message.response(code, payload.getBytes(StandardCharsets.UTF_8));
+  }
+
+  /** Interpret the payload as a UTF-8 string */
+  public String payloadString() {
+    return new String(this.payload, StandardCharsets.UTF_8);
+  }
+}
@@ -0,0 +1,14 @@
+package dev.toad.msg;
+
+import java.util.ArrayList;
+
+/** A CoAP option number and all values provided for it */
+public final class MessageOption {
+  public final long number;
+  public final ArrayList<byte[]> values;
+
+  public MessageOption(long number, ArrayList<byte[]> values) {
+    this.number = number;
+    this.values = values;
+  }
+}
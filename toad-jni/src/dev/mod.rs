@@ -0,0 +1,2 @@
+/// dev/toad/*
+pub mod toad;
@@ -0,0 +1,154 @@
+use core::ffi::c_void;
+
+use jni::objects::{JClass, JObject, JThrowable};
+use jni::sys::{jlong, jobject};
+use jni::NativeMethod;
+use toad::config::Config;
+use toad::platform::Platform;
+use toad::std::dtls;
+use toad::step::runtime;
+use toad_msg::Token;
+
+use super::msg::Message;
+use crate::java::io::IOException;
+use crate::java::lang::Throwable;
+use crate::java::net::InetSocketAddress;
+use crate::java::{self, Object, ResultExt, ResultYieldToJavaOrThrow, Signature};
+
+pub(super) type Runtime = toad::std::Platform<dtls::N, runtime::std::Runtime<dtls::N>>;
+
+/// `dev.toad.Client`
+///
+/// A CoAP client backed by the toad runtime.
+///
+/// The java class holds a pointer to a [`toad::std::Platform`]
+/// and implements `get`, `post` and `observe` in terms of the native
+/// methods registered by [`Client::register_natives`].
+///
+/// The sources for the java classes live in `toad-jni/java/dev/toad`.
+pub struct Client(java::lang::Object);
+
+java::object_newtype!(Client);
+impl java::Class for Client {
+  const PATH: &'static str = "dev/toad/Client";
+}
+
+impl Client {
+  /// Register the native methods backing `dev.toad.Client`.
+  ///
+  /// This should be invoked from `JNI_OnLoad` in the library
+  /// that java loads with `System.loadLibrary`:
+  ///
+  /// ```rust,no_run
+  /// use jni::sys::{jint, JNI_VERSION_1_8};
+  /// use jni::JavaVM;
+  ///
+  /// #[no_mangle]
+  /// pub extern "system" fn JNI_OnLoad(vm: JavaVM, _: *mut core::ffi::c_void) -> jint {
+  ///   let mut e = vm.get_env().unwrap();
+  ///   toad_jni::dev::toad::Client::register_natives(&mut e).unwrap();
+  ///   JNI_VERSION_1_8
+  /// }
+  /// ```
+  ///
+  /// This also initializes the [global jvm handle](crate::global).
+  pub fn register_natives(e: &mut java::Env) -> Result<(), Throwable> {
+    let vm = e.get_java_vm().to_throwable(e)?;
+    crate::global::init_with(vm);
+
+    fn native(name: &str, sig: Signature, fn_ptr: *mut c_void) -> NativeMethod {
+      NativeMethod { name: name.into(),
+                     sig: sig.as_str().into(),
+                     fn_ptr }
+    }
+
+    let methods = [native("init", Signature::of::<fn(String) -> i64>(), init as *mut c_void),
+                   native("drop", Signature::of::<fn(i64)>(), drop_runtime as *mut c_void),
+                   native("send",
+                          Signature::of::<fn(i64, Message) -> Vec<i8>>(),
+                          send as *mut c_void),
+                   native("pollResp",
+                          Signature::of::<fn(i64, Vec<i8>, InetSocketAddress) -> Message>(),
+                          poll_resp as *mut c_void)];
+
+    e.register_native_methods(Self::PATH, &methods)
+     .to_throwable(e)
+  }
+}
+
+pub(super) fn io_exception(e: &mut java::Env, err: std::io::Error) -> Throwable {
+  IOException::new(e, err.to_string()).to_throwable(e)
+}
+
+/// # Safety
+/// `ptr` must have been yielded by [`init`] and not yet passed to [`drop_runtime`]
+pub(super) unsafe fn runtime<'a>(ptr: jlong) -> &'a Runtime {
+  &*(ptr as *const Runtime)
+}
+
+/// `static native long init(String addr)`
+pub(super) extern "system" fn init<'local>(mut env: java::Env<'local>,
+                                _: JClass<'local>,
+                                addr: JObject<'local>)
+                                -> jlong {
+  let e = &mut env;
+  let addr = java::lang::Object::from_local(e, addr).upcast_to::<String>(e);
+
+  match Runtime::try_new(addr, Config::default()) {
+    | Ok(runtime) => Box::into_raw(Box::new(runtime)) as jlong,
+    | Err(err) => {
+      let err = io_exception(e, err);
+      let err = JThrowable::from(err.downcast(e).to_local(e));
+      e.throw(err).unwrap();
+      0
+    },
+  }
+}
+
+/// `static native void drop(long ptr)`
+pub(super) extern "system" fn drop_runtime<'local>(_: java::Env<'local>, _: JClass<'local>, ptr: jlong) {
+  // SAFETY: the java class only invokes `drop` once, with
+  // a pointer yielded by `init`
+  unsafe { drop(Box::from_raw(ptr as *mut Runtime)) }
+}
+
+/// `static native byte[] send(long ptr, Message msg)`
+extern "system" fn send<'local>(mut env: java::Env<'local>,
+                                _: JClass<'local>,
+                                ptr: jlong,
+                                msg: JObject<'local>)
+                                -> jobject {
+  let e = &mut env;
+  let msg = java::lang::Object::from_local(e, msg).upcast_to::<Message>(e)
+                                                  .to_toad(e);
+
+  // SAFETY: `ptr` is owned by the java class
+  let runtime = unsafe { runtime(ptr) };
+
+  match nb::block!(runtime.send_msg(msg.clone())) {
+    | Ok((_, token)) => Ok(token.as_bytes().iter().map(|b| *b as i8).collect::<Vec<i8>>()),
+    | Err(err) => Err(io_exception(e, err)),
+  }.yield_to_java_or_throw(e)
+}
+
+/// `static native Message pollResp(long ptr, byte[] token, InetSocketAddress addr)`
+extern "system" fn poll_resp<'local>(mut env: java::Env<'local>,
+                                     _: JClass<'local>,
+                                     ptr: jlong,
+                                     token: JObject<'local>,
+                                     addr: JObject<'local>)
+                                     -> jobject {
+  let e = &mut env;
+  let token = java::lang::Object::from_local(e, token).upcast_to::<Vec<i8>>(e);
+  let token = Token(token.into_iter().take(8).map(|b| b as u8).collect());
+  let addr = java::lang::Object::from_local(e, addr).upcast_to::<InetSocketAddress>(e)
+                                                    .to_no_std(e);
+
+  // SAFETY: `ptr` is owned by the java class
+  let runtime = unsafe { runtime(ptr) };
+
+  match nb::block!(runtime.poll_resp(token, addr)) {
+    | Ok(resp) => Ok(Message::new(e, resp.as_ref().map(|r| r.msg()))),
+    | Err(err) => Err(io_exception(e, err)),
+  }.yield_to_java_or_throw(e)
+}
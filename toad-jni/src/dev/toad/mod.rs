@@ -0,0 +1,10 @@
+/// dev/toad/msg/*
+pub mod msg;
+
+mod client;
+#[doc(inline)]
+pub use client::Client;
+
+mod server;
+#[doc(inline)]
+pub use server::Server;
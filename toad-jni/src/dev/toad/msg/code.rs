@@ -0,0 +1,25 @@
+use crate::java;
+
+/// `dev.toad.msg.Code`
+pub struct Code(java::lang::Object);
+
+java::object_newtype!(Code);
+impl java::Class for Code {
+  const PATH: &'static str = "dev/toad/msg/Code";
+}
+
+impl Code {
+  /// `Code(int clazz, int detail)`
+  pub fn new(e: &mut java::Env, code: toad_msg::Code) -> Self {
+    static CTOR: java::Constructor<Code, fn(i32, i32)> = java::Constructor::new();
+    CTOR.invoke(e, code.class.into(), code.detail.into())
+  }
+
+  /// Convert this to a [`toad_msg::Code`]
+  pub fn to_toad(&self, e: &mut java::Env) -> toad_msg::Code {
+    static CLASS: java::Field<Code, i32> = java::Field::new("clazz");
+    static DETAIL: java::Field<Code, i32> = java::Field::new("detail");
+
+    toad_msg::Code::new(CLASS.get(e, self) as u8, DETAIL.get(e, self) as u8)
+  }
+}
@@ -0,0 +1,130 @@
+use no_std_net::SocketAddr;
+use toad::net::Addrd;
+use toad_msg::{Id, Payload, Token, Type};
+
+use super::{Code, MessageOption};
+use crate::java;
+use crate::java::net::InetSocketAddress;
+
+/// `dev.toad.msg.Message`
+///
+/// A CoAP message along with the address of the remote
+/// host it was sent to / received from.
+pub struct Message(java::lang::Object);
+
+java::object_newtype!(Message);
+impl java::Class for Message {
+  const PATH: &'static str = "dev/toad/msg/Message";
+}
+
+static ADDR: java::Field<Message, InetSocketAddress> = java::Field::new("addr");
+static TYPE: java::Field<Message, i32> = java::Field::new("type");
+static CODE: java::Field<Message, Code> = java::Field::new("code");
+static ID: java::Field<Message, i32> = java::Field::new("id");
+static TOKEN: java::Field<Message, Vec<i8>> = java::Field::new("token");
+static OPTIONS: java::Field<Message, java::util::ArrayList<MessageOption>> =
+  java::Field::new("options");
+static PAYLOAD: java::Field<Message, Vec<i8>> = java::Field::new("payload");
+
+fn bytes_to_java(bytes: &[u8]) -> Vec<i8> {
+  bytes.iter().map(|b| *b as i8).collect()
+}
+
+fn bytes_from_java(bytes: Vec<i8>) -> Vec<u8> {
+  bytes.into_iter().map(|b| b as u8).collect()
+}
+
+impl Message {
+  /// Create a java `Message` from a [`toad_msg::alloc::Message`]
+  pub fn new(e: &mut java::Env, msg: Addrd<&toad_msg::alloc::Message>) -> Self {
+    static CTOR: java::Constructor<Message, fn()> = java::Constructor::new();
+
+    let m = CTOR.invoke(e);
+
+    let addr = InetSocketAddress::from_no_std(e, msg.addr());
+    ADDR.set(e, &m, addr);
+
+    let ty = match msg.data().ty {
+      | Type::Con => 0,
+      | Type::Non => 1,
+      | Type::Ack => 2,
+      | Type::Reset => 3,
+    };
+    TYPE.set(e, &m, ty);
+
+    let code = Code::new(e, msg.data().code);
+    CODE.set(e, &m, code);
+
+    ID.set(e, &m, msg.data().id.0.into());
+    TOKEN.set(e, &m, bytes_to_java(msg.data().token.as_bytes()));
+
+    let options = java::util::ArrayList::<MessageOption>::new(e);
+    msg.data()
+       .opts
+       .iter()
+       .for_each(|(num, vals)| options.append(e, MessageOption::new(e, *num, vals)));
+    OPTIONS.set(e, &m, options);
+
+    PAYLOAD.set(e, &m, bytes_to_java(&msg.data().payload.0));
+
+    m
+  }
+
+  /// The address this message was sent to / received from
+  pub fn addr(&self, e: &mut java::Env) -> SocketAddr {
+    ADDR.get(e, self).to_no_std(e)
+  }
+
+  /// The message type
+  pub fn ty(&self, e: &mut java::Env) -> Type {
+    match TYPE.get(e, self) {
+      | 0 => Type::Con,
+      | 1 => Type::Non,
+      | 2 => Type::Ack,
+      | _ => Type::Reset,
+    }
+  }
+
+  /// The message code
+  pub fn code(&self, e: &mut java::Env) -> toad_msg::Code {
+    CODE.get(e, self).to_toad(e)
+  }
+
+  /// The message id
+  pub fn id(&self, e: &mut java::Env) -> Id {
+    Id(ID.get(e, self) as u16)
+  }
+
+  /// The message token
+  pub fn token(&self, e: &mut java::Env) -> Token {
+    Token(TOKEN.get(e, self)
+               .into_iter()
+               .take(8)
+               .map(|b| b as u8)
+               .collect())
+  }
+
+  /// The message options
+  pub fn options(&self, e: &mut java::Env) -> Vec<MessageOption> {
+    OPTIONS.get(e, self).into_iter().collect()
+  }
+
+  /// The message payload
+  pub fn payload(&self, e: &mut java::Env) -> Vec<u8> {
+    bytes_from_java(PAYLOAD.get(e, self))
+  }
+
+  /// Convert this to a [`toad_msg::alloc::Message`]
+  pub fn to_toad(&self, e: &mut java::Env) -> Addrd<toad_msg::alloc::Message> {
+    let mut msg = toad_msg::alloc::Message::new(self.ty(e), self.code(e), self.id(e), self.token(e));
+
+    self.options(e).into_iter().for_each(|o| {
+                                 let (num, vals) = (o.number(e), o.values(e));
+                                 msg.opts.insert(num, vals);
+                               });
+
+    msg.payload = Payload(self.payload(e));
+
+    Addrd(msg, self.addr(e))
+  }
+}
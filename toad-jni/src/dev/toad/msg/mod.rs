@@ -0,0 +1,11 @@
+mod code;
+#[doc(inline)]
+pub use code::Code;
+
+mod message;
+#[doc(inline)]
+pub use message::Message;
+
+mod option;
+#[doc(inline)]
+pub use option::MessageOption;
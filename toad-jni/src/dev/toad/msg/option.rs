@@ -0,0 +1,45 @@
+use toad_msg::{OptNumber, OptValue};
+
+use crate::java;
+
+/// `dev.toad.msg.MessageOption`
+///
+/// A CoAP option number, and all of the values provided for it
+pub struct MessageOption(java::lang::Object);
+
+java::object_newtype!(MessageOption);
+impl java::Class for MessageOption {
+  const PATH: &'static str = "dev/toad/msg/MessageOption";
+}
+
+impl MessageOption {
+  /// `MessageOption(long number, ArrayList<byte[]> values)`
+  pub fn new<'a, V>(e: &mut java::Env, number: OptNumber, values: V) -> Self
+    where V: IntoIterator<Item = &'a OptValue<Vec<u8>>>
+  {
+    static CTOR: java::Constructor<MessageOption, fn(i64, java::util::ArrayList<Vec<i8>>)> =
+      java::Constructor::new();
+
+    let list = java::util::ArrayList::<Vec<i8>>::new(e);
+    values.into_iter().for_each(|v| list.append(e, v.0.iter().map(|b| *b as i8).collect()));
+
+    CTOR.invoke(e, number.0.into(), list)
+  }
+
+  /// The option number
+  pub fn number(&self, e: &mut java::Env) -> OptNumber {
+    static NUMBER: java::Field<MessageOption, i64> = java::Field::new("number");
+    OptNumber(NUMBER.get(e, self) as u32)
+  }
+
+  /// The values provided for this option
+  pub fn values(&self, e: &mut java::Env) -> Vec<OptValue<Vec<u8>>> {
+    static VALUES: java::Field<MessageOption, java::util::ArrayList<Vec<i8>>> =
+      java::Field::new("values");
+
+    VALUES.get(e, self)
+          .into_iter()
+          .map(|v| OptValue(v.into_iter().map(|b| b as u8).collect()))
+          .collect()
+  }
+}
@@ -0,0 +1,224 @@
+use core::ffi::c_void;
+use std::time::Duration;
+
+use jni::objects::{JClass, JObject, JThrowable};
+use jni::sys::jlong;
+use jni::NativeMethod;
+use toad::net::Addrd;
+use toad::platform::Platform;
+use toad::req::Req;
+use toad::resp::{code, Resp};
+use toad::std::{dtls, PlatformTypes};
+use toad_msg::MessageOptions;
+
+use super::client::{self, io_exception, runtime, Runtime};
+use super::msg::{Code, Message};
+use crate::java::lang::Throwable;
+use crate::java::Nullable;
+use crate::java::{self, Object, ResultExt, Signature};
+
+/// `dev.toad.Server`
+///
+/// A CoAP server backed by the toad runtime.
+///
+/// Java code registers handlers for paths with `Server.route`, and
+/// `Server.run` hands control to the native method registered by
+/// [`Server::register_natives`], which polls the runtime for requests and
+/// invokes the handler of the first matching route that returns a response.
+///
+/// The sources for the java classes live in `toad-jni/java/dev/toad`.
+pub struct Server(java::lang::Object);
+
+java::object_newtype!(Server);
+impl java::Class for Server {
+  const PATH: &'static str = "dev/toad/Server";
+}
+
+/// `dev.toad.Server.Route`
+struct Route(java::lang::Object);
+
+java::object_newtype!(Route);
+impl java::Class for Route {
+  const PATH: &'static str = "dev/toad/Server$Route";
+}
+
+/// `dev.toad.Server.Handler`
+struct Handler(java::lang::Object);
+
+java::object_newtype!(Handler);
+impl java::Class for Handler {
+  const PATH: &'static str = "dev/toad/Server$Handler";
+}
+
+impl Route {
+  fn path(&self, e: &mut java::Env) -> String {
+    static PATH: java::Field<Route, String> = java::Field::new("path");
+    PATH.get(e, self)
+  }
+
+  fn handler(&self, e: &mut java::Env) -> Handler {
+    static HANDLER: java::Field<Route, Handler> = java::Field::new("handler");
+    HANDLER.get(e, self)
+  }
+}
+
+impl Handler {
+  /// `Message handle(Message req)`
+  fn handle(&self, e: &mut java::Env, req: Message) -> Result<Option<Message>, Throwable> {
+    static HANDLE: java::Method<Handler, fn(Message) -> Result<Nullable<Message>, Throwable>> =
+      java::Method::new("handle");
+
+    HANDLE.invoke(e, self, req).map(|resp| resp.into_option(e))
+  }
+}
+
+impl Server {
+  /// Register the native methods backing `dev.toad.Server`.
+  ///
+  /// This should be invoked from `JNI_OnLoad` alongside
+  /// [`Client::register_natives`](super::Client::register_natives).
+  ///
+  /// This also initializes the [global jvm handle](crate::global).
+  pub fn register_natives(e: &mut java::Env) -> Result<(), Throwable> {
+    let vm = e.get_java_vm().to_throwable(e)?;
+    crate::global::init_with(vm);
+
+    fn native(name: &str, sig: Signature, fn_ptr: *mut c_void) -> NativeMethod {
+      NativeMethod { name: name.into(),
+                     sig: sig.as_str().into(),
+                     fn_ptr }
+    }
+
+    let methods = [native("init",
+                          Signature::of::<fn(String) -> i64>(),
+                          client::init as *mut c_void),
+                   native("drop",
+                          Signature::of::<fn(i64)>(),
+                          client::drop_runtime as *mut c_void),
+                   native("run", Signature::of::<fn(i64, Server)>(), run as *mut c_void)];
+
+    e.register_native_methods(Self::PATH, &methods)
+     .to_throwable(e)
+  }
+
+  /// `volatile boolean running`
+  pub fn running(&self, e: &mut java::Env) -> bool {
+    static RUNNING: java::Field<Server, bool> = java::Field::new("running");
+    RUNNING.get(e, self)
+  }
+
+  fn routes(&self, e: &mut java::Env) -> java::util::ArrayList<Route> {
+    static ROUTES: java::Field<Server, java::util::ArrayList<Route>> = java::Field::new("routes");
+    ROUTES.get(e, self)
+  }
+
+  /// Find the response to a request by invoking the handlers of
+  /// matching routes until one returns a response.
+  ///
+  /// Yields 4.04 when no route handles the request, and 5.00 with an empty payload
+  /// when a handler throws (the exception is logged).
+  fn handle(&self, e: &mut java::Env, req: &Addrd<Req<PlatformTypes<dtls::N>>>) -> Message {
+    let path = req.data().msg().path_string().unwrap_or_default();
+    let java_req = Message::new(e, req.as_ref().map(|r| r.msg()));
+
+    for route in self.routes(e) {
+      if route.path(e) != path {
+        continue;
+      }
+
+      let java_req = java_req.downcast_ref(e).upcast_to::<Message>(e);
+      match route.handler(e).handle(e, java_req) {
+        | Ok(Some(resp)) => return resp,
+        | Ok(None) => continue,
+        | Err(err) => {
+          // the exception may contain details that should not be shared with the client
+          log::error!("handler for {:?} threw {:?}", path, err);
+          return response(e, code::INTERNAL_SERVER_ERROR, vec![]);
+        },
+      }
+    }
+
+    response(e, code::NOT_FOUND, format!("resource {} not found", path).into_bytes())
+  }
+}
+
+/// How long [`run`] waits between polls of a runtime that
+/// has no requests for it
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// `static Message Message.response(Code code, byte[] payload)`
+fn response(e: &mut java::Env, code: toad_msg::Code, payload: Vec<u8>) -> Message {
+  static RESPONSE: java::StaticMethod<Message, fn(Code, Vec<i8>) -> Message> =
+    java::StaticMethod::new("response");
+
+  let code = Code::new(e, code);
+  RESPONSE.invoke(e, code, payload.into_iter().map(|b| b as i8).collect())
+}
+
+/// Respond to `req` with the code, options and payload of a
+/// `Message` returned by a java handler
+fn respond(e: &mut java::Env,
+           runtime: &Runtime,
+           req: &Addrd<Req<PlatformTypes<dtls::N>>>,
+           java_resp: Message)
+           -> Result<(), Throwable> {
+  let mut resp = match Resp::for_request(req.data()) {
+    | Some(resp) => resp,
+    | None => return Ok(()),
+  };
+
+  resp.set_code(java_resp.code(e));
+  java_resp.options(e).into_iter().for_each(|o| {
+                                     let (num, vals) = (o.number(e), o.values(e));
+                                     resp.msg_mut().opts.insert(num, vals);
+                                   });
+  resp.set_payload(java_resp.payload(e));
+
+  nb::block!(runtime.send_msg(Addrd(resp.into(), req.addr()))).map(|_| ())
+                                                              .map_err(|err| io_exception(e, err))
+}
+
+/// `static native void run(long ptr, Server server)`
+extern "system" fn run<'local>(mut env: java::Env<'local>,
+                               _: JClass<'local>,
+                               ptr: jlong,
+                               server: JObject<'local>) {
+  let e = &mut env;
+  let server = java::lang::Object::from_local(e, server).upcast_to::<Server>(e);
+
+  // SAFETY: `ptr` is owned by the java class, which does not
+  // drop it while `run` is executing
+  let runtime = unsafe { runtime(ptr) };
+
+  let result = loop {
+    if !server.running(e) {
+      break Ok(());
+    }
+
+    let req = match runtime.poll_req() {
+      | Ok(req) => req,
+      | Err(nb::Error::WouldBlock) => {
+        std::thread::sleep(IDLE_POLL_INTERVAL);
+        continue;
+      },
+      | Err(nb::Error::Other(err)) => break Err(io_exception(e, err)),
+    };
+
+    // every request is handled in its own local frame, so that the local
+    // references created while marshaling it are freed once it's been handled
+    let handled = e.with_local_frame(32, |e| {
+                     let resp = server.handle(e, &req);
+                     Ok::<_, jni::errors::Error>(respond(e, runtime, &req, resp))
+                   })
+                   .unwrap_java(e);
+
+    if let Err(err) = handled {
+      break Err(err);
+    }
+  };
+
+  if let Err(err) = result {
+    let err = JThrowable::from(err.downcast(e).to_local(e));
+    e.throw(err).unwrap();
+  }
+}
@@ -0,0 +1,242 @@
+use core::marker::PhantomData;
+
+use jni::objects::{JBooleanArray,
+                   JByteArray,
+                   JCharArray,
+                   JDoubleArray,
+                   JFloatArray,
+                   JIntArray,
+                   JLongArray,
+                   JObject,
+                   JObjectArray,
+                   JShortArray};
+
+use crate::java::{self, Object, ResultExt, Signature, Type};
+
+/// A reference to a java array `T[]`
+///
+/// Unlike [`Vec`]`<T>` (which copies the array into / out of the JVM
+/// whenever it's converted), elements of a `JavaArray` are read and written
+/// in place in the JVM.
+///
+/// `T` may be any [`java::Type`] implementing [`java::Object`], including
+/// primitives (`JavaArray<i32>` is `int[]`), classes (`JavaArray<String>` is `String[]`),
+/// and other arrays (`JavaArray<JavaArray<i32>>` and `JavaArray<Vec<i32>>` are both `int[][]`).
+///
+/// ```rust,no_run
+/// use toad_jni::java::{self, JavaArray, Object};
+///
+/// let mut e = java::env();
+/// let e = &mut e;
+///
+/// // `String.format(String, Object...)`
+/// static FORMAT: java::StaticMethod<String, fn(String, JavaArray<java::lang::Object>) -> String> =
+///   java::StaticMethod::new("format");
+///
+/// let args = JavaArray::<java::lang::Object>::new(e, 1);
+/// let arg = java::lang::Integer::new(e, 12);
+/// args.set(e, 0, arg.downcast(e));
+///
+/// assert_eq!(FORMAT.invoke(e, "%d bottles".into(), args), "12 bottles".to_string());
+/// ```
+pub struct JavaArray<T>(java::lang::Object, PhantomData<T>);
+
+impl<T> JavaArray<T> where T: java::Object
+{
+  /// Create a new array of length `len`
+  ///
+  /// Elements are initialized to java's default value for `T`;
+  /// `0` for numeric primitives, `false` for `boolean` and `null` for objects.
+  pub fn new(e: &mut java::Env, len: i32) -> Self {
+    macro_rules! go {
+      ($new_array:ident) => {{
+        let arr = e.$new_array(len).unwrap_java(e);
+        java::lang::Object::from_local(e, arr)
+      }};
+    }
+
+    let arr = match T::SIG {
+      | i8::SIG => go!(new_byte_array),
+      | i16::SIG => go!(new_short_array),
+      | i32::SIG => go!(new_int_array),
+      | i64::SIG => go!(new_long_array),
+      | f32::SIG => go!(new_float_array),
+      | f64::SIG => go!(new_double_array),
+      | u16::SIG => go!(new_char_array),
+      | bool::SIG => go!(new_boolean_array),
+      | sig => {
+        let arr = e.new_object_array(len, sig.class_path(), JObject::null())
+                   .unwrap_java(e);
+        java::lang::Object::from_local(e, arr)
+      },
+    };
+
+    Self(arr, PhantomData)
+  }
+
+  /// Copy the elements of a [`Vec`] into a new array
+  pub fn from_vec(e: &mut java::Env, vec: Vec<T>) -> Self {
+    let arr = Self::new(e, vec.len() as i32);
+    vec.into_iter()
+       .enumerate()
+       .for_each(|(ix, t)| arr.set(e, ix as i32, t));
+    arr
+  }
+
+  /// Copy the elements of this array into a [`Vec`]
+  pub fn to_vec(&self, e: &mut java::Env) -> Vec<T> {
+    (0..self.length(e)).map(|ix| self.get(e, ix)).collect()
+  }
+
+  /// The length of the array (`arr.length`)
+  pub fn length(&self, e: &mut java::Env) -> i32 {
+    let arr = <&JObjectArray>::from(self.0.as_local());
+    e.get_array_length(arr).unwrap_java(e)
+  }
+
+  /// Get the element at index `ix` (`arr[ix]`)
+  ///
+  /// # Panics
+  /// If `ix` is out of bounds
+  pub fn get(&self, e: &mut java::Env, ix: i32) -> T {
+    macro_rules! go {
+      ($arr:ty => $get_region:ident) => {{
+        let arr = <&$arr>::from(self.0.as_local());
+        let mut el = [Default::default()];
+        e.$get_region(arr, ix, &mut el).unwrap_java(e);
+        let val = el[0].downcast_value(e);
+        T::upcast_value(e, val)
+      }};
+    }
+
+    match T::SIG {
+      | i8::SIG => go!(JByteArray => get_byte_array_region),
+      | i16::SIG => go!(JShortArray => get_short_array_region),
+      | i32::SIG => go!(JIntArray => get_int_array_region),
+      | i64::SIG => go!(JLongArray => get_long_array_region),
+      | f32::SIG => go!(JFloatArray => get_float_array_region),
+      | f64::SIG => go!(JDoubleArray => get_double_array_region),
+      | u16::SIG => go!(JCharArray => get_char_array_region),
+      | bool::SIG => {
+        let arr = <&JBooleanArray>::from(self.0.as_local());
+        let mut el = [0u8];
+        e.get_boolean_array_region(arr, ix, &mut el).unwrap_java(e);
+        let val = (el[0] == jni::sys::JNI_TRUE).downcast_value(e);
+        T::upcast_value(e, val)
+      },
+      | _ => {
+        let arr = <&JObjectArray>::from(self.0.as_local());
+        let el = e.get_object_array_element(arr, ix).unwrap_java(e);
+        java::lang::Object::from_local(e, el).upcast_to::<T>(e)
+      },
+    }
+  }
+
+  /// Set the element at index `ix` (`arr[ix] = t`)
+  ///
+  /// # Panics
+  /// If `ix` is out of bounds
+  pub fn set(&self, e: &mut java::Env, ix: i32, t: T) {
+    macro_rules! go {
+      ($arr:ty => $set_region:ident, $jv:ident) => {{
+        let arr = <&$arr>::from(self.0.as_local());
+        let el = t.downcast_value(e).$jv().unwrap_java(e);
+        e.$set_region(arr, ix, &[el]).unwrap_java(e);
+      }};
+    }
+
+    match T::SIG {
+      | i8::SIG => go!(JByteArray => set_byte_array_region, b),
+      | i16::SIG => go!(JShortArray => set_short_array_region, s),
+      | i32::SIG => go!(JIntArray => set_int_array_region, i),
+      | i64::SIG => go!(JLongArray => set_long_array_region, j),
+      | f32::SIG => go!(JFloatArray => set_float_array_region, f),
+      | f64::SIG => go!(JDoubleArray => set_double_array_region, d),
+      | u16::SIG => go!(JCharArray => set_char_array_region, c),
+      | bool::SIG => {
+        let arr = <&JBooleanArray>::from(self.0.as_local());
+        let el = t.downcast_value(e).z().unwrap_java(e);
+        e.set_boolean_array_region(arr, ix, &[el as u8]).unwrap_java(e);
+      },
+      | _ => {
+        let arr = <&JObjectArray>::from(self.0.as_local());
+        let el = t.downcast(e);
+        e.set_object_array_element(arr, ix, el.as_local())
+         .unwrap_java(e);
+      },
+    }
+  }
+}
+
+impl<T> java::Object for JavaArray<T> where T: java::Object
+{
+  fn upcast(_: &mut java::Env, jobj: java::lang::Object) -> Self {
+    Self(jobj, PhantomData)
+  }
+
+  fn downcast(self, _: &mut java::Env) -> java::lang::Object {
+    self.0
+  }
+
+  fn downcast_ref(&self, e: &mut java::Env) -> java::lang::Object {
+    self.0.downcast_ref(e)
+  }
+}
+
+impl<T> Type for JavaArray<T> where T: java::Object
+{
+  const SIG: Signature = Signature::array_of(T::SIG);
+  fn jni() -> jni::signature::JavaType {
+    jni::signature::JavaType::Array(Box::new(T::jni()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::java::{self, JavaArray, Object};
+
+  #[test]
+  fn primitive_array() {
+    let mut e = crate::test::init();
+    let e = &mut e;
+
+    let arr = JavaArray::<i32>::from_vec(e, vec![1, 2, 3]);
+    assert_eq!(arr.length(e), 3);
+
+    arr.set(e, 1, 20);
+    assert_eq!(arr.get(e, 1), 20);
+    assert_eq!(arr.to_vec(e), vec![1, 20, 3]);
+
+    let arr = JavaArray::<bool>::from_vec(e, vec![false, true]);
+    assert_eq!(arr.to_vec(e), vec![false, true]);
+  }
+
+  #[test]
+  fn object_array() {
+    let mut e = crate::test::init();
+    let e = &mut e;
+
+    let arr = JavaArray::<String>::from_vec(e, vec!["a".into(), "b".into()]);
+    assert!(arr.downcast_ref(e).is_instance_of::<Vec<String>>(e));
+    assert_eq!(arr.to_vec(e), vec!["a".to_string(), "b".to_string()]);
+  }
+
+  #[test]
+  fn nested_array() {
+    let mut e = crate::test::init();
+    let e = &mut e;
+
+    assert_eq!(java::Signature::of::<JavaArray<JavaArray<i32>>>().as_str(), "[[I");
+
+    let rows = vec![JavaArray::<i32>::from_vec(e, vec![1, 2]),
+                    JavaArray::<i32>::from_vec(e, vec![3])];
+    let grid = JavaArray::from_vec(e, rows);
+    assert!(grid.downcast_ref(e).is_instance_of::<Vec<Vec<i32>>>(e));
+
+    let grid = grid.to_vec(e)
+                   .into_iter()
+                   .map(|row| row.to_vec(e))
+                   .collect::<Vec<_>>();
+    assert_eq!(grid, vec![vec![1, 2], vec![3]]);
+  }
+}
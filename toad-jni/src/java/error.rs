@@ -0,0 +1,145 @@
+use jni::objects::JThrowable;
+
+use crate::java::{self, Object};
+
+/// An error encountered while calling into the JVM
+///
+/// Yielded by the fallible lenses (e.g. [`java::Field::try_get`],
+/// [`java::Constructor::try_invoke`], or a [`java::Method`] returning
+/// [`Result`]`<T, java::Error>`) instead of panicking.
+///
+/// Rust errors can be converted to this with [`Error::rust`],
+/// and any `Error` can be [thrown](Error::throw) to java.
+pub enum Error {
+  /// A java exception was thrown
+  Exception {
+    /// The fully qualified name of the exception's class, e.g. `java.io.IOException`
+    class: String,
+    /// `Throwable.getMessage()`
+    message: Option<String>,
+    /// The exception
+    throwable: java::lang::Throwable,
+  },
+  /// A JNI call failed without a java exception being thrown
+  Jni(jni::errors::Error),
+  /// An error raised by rust code
+  ///
+  /// This is thrown to java as a `java.lang.RuntimeException`.
+  Rust(String),
+}
+
+impl Error {
+  /// Convert a [`jni::errors::Error`] to an `Error`.
+  ///
+  /// If a java exception is pending, it is cleared and
+  /// yielded as [`Error::Exception`].
+  pub fn from_jni(e: &mut java::Env, err: jni::errors::Error) -> Self {
+    match e.exception_check() {
+      | Ok(true) => {
+        let ex = e.exception_occurred().unwrap();
+        e.exception_clear().unwrap();
+        let throwable = java::lang::Object::from_local(e, ex).upcast_to::<java::lang::Throwable>(e);
+        Self::from_throwable(e, throwable)
+      },
+      | _ => Self::Jni(err),
+    }
+  }
+
+  /// Create an [`Error::Exception`] from a [`java::lang::Throwable`]
+  pub fn from_throwable(e: &mut java::Env, throwable: java::lang::Throwable) -> Self {
+    Self::Exception { class: throwable.class_name(e),
+                      message: throwable.message(e),
+                      throwable }
+  }
+
+  /// Create an [`Error::Rust`] from any rust error
+  ///
+  /// ```rust,no_run
+  /// use toad_jni::java;
+  ///
+  /// let err = "foo".parse::<u8>().map_err(java::Error::rust).unwrap_err();
+  /// assert_eq!(err.to_string(), "invalid digit found in string");
+  /// ```
+  pub fn rust(err: impl ToString) -> Self {
+    Self::Rust(err.to_string())
+  }
+
+  /// Throw this error to java.
+  ///
+  /// [`Error::Exception`]s are rethrown as-is, other errors
+  /// are thrown as a `java.lang.RuntimeException` with the
+  /// error's message.
+  ///
+  /// When invoked in a native method implementation, the exception
+  /// will be thrown in java once the native method returns.
+  pub fn throw(self, e: &mut java::Env) {
+    match self {
+      | Self::Exception { throwable, .. } => {
+        let throwable = JThrowable::from(throwable.downcast(e).to_local(e));
+        e.throw(throwable).unwrap()
+      },
+      | other => e.throw_new("java/lang/RuntimeException", other.to_string())
+                  .unwrap(),
+    }
+  }
+}
+
+impl core::fmt::Display for Error {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    match self {
+      | Self::Exception { class,
+                          message: Some(message),
+                          .. } => write!(f, "{}: {}", class, message),
+      | Self::Exception { class, .. } => write!(f, "{}", class),
+      | Self::Jni(err) => write!(f, "{}", err),
+      | Self::Rust(msg) => write!(f, "{}", msg),
+    }
+  }
+}
+
+impl core::fmt::Debug for Error {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    match self {
+      | Self::Exception { throwable, .. } => write!(f, "{:?}", throwable),
+      | Self::Jni(err) => write!(f, "Jni({:?})", err),
+      | Self::Rust(msg) => write!(f, "Rust({:?})", msg),
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+  use crate::java;
+  use crate::java::io::IOException;
+
+  #[test]
+  fn from_throwable() {
+    let mut e = crate::test::init();
+    let e = &mut e;
+    let foo = IOException::new(e, "foo").to_throwable(e);
+
+    let err = java::Error::from_throwable(e, foo);
+    assert!(matches!(&err,
+                     java::Error::Exception { class, message: Some(message), .. }
+                     if class == "java.io.IOException" && message == "foo"));
+    assert_eq!(err.to_string(), "java.io.IOException: foo");
+  }
+
+  #[test]
+  fn lens_yields_thrown_exception() {
+    let mut e = crate::test::init();
+    let e = &mut e;
+
+    static VALUE_OF: java::StaticMethod<java::lang::Integer,
+                                        fn(String) -> Result<java::lang::Integer, java::Error>> =
+      java::StaticMethod::new("valueOf");
+
+    let err = VALUE_OF.invoke(e, "foo".to_string()).unwrap_err();
+    assert!(matches!(&err,
+                     java::Error::Exception { class, .. }
+                     if class == "java.lang.NumberFormatException"));
+    assert!(!e.exception_check().unwrap());
+  }
+}
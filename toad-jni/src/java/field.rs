@@ -26,33 +26,55 @@ impl<C, T> Field<C, T>
   }
 
   /// Get the value of this field
+  ///
+  /// # Panics
+  /// If a java exception is thrown, see [`Field::try_get`]
   pub fn get(&self, e: &mut java::Env, inst: &C) -> T {
+    self.try_get(e, inst)
+        .unwrap_or_else(|err| panic!("{:?}", err))
+  }
+
+  /// Get the value of this field, yielding a [`java::Error`]
+  /// if a java exception is thrown
+  pub fn try_get(&self, e: &mut java::Env, inst: &C) -> Result<T, java::Error> {
     let id = self.id.read().unwrap();
     if id.is_none() {
       drop(id);
 
+      let found = e.get_field_id(C::PATH, self.name, T::SIG)
+                   .to_java_error(e)?;
       let mut id = self.id.write().unwrap();
-      *id = Some(e.get_field_id(C::PATH, self.name, T::SIG).unwrap_java(e));
+      *id = Some(found);
       drop(id);
 
-      self.get(e, inst)
+      self.try_get(e, inst)
     } else {
       let inst = inst.downcast_ref(e);
       let val =
         e.get_field_unchecked(&inst,
                               id.unwrap(),
                               jni::signature::ReturnType::from_str(T::SIG.as_str()).unwrap())
-         .unwrap_java(e);
-      T::upcast_value(e, val)
+         .to_java_error(e)?;
+      Ok(T::upcast_value(e, val))
     }
   }
 
   /// Set the value of this field
+  ///
+  /// # Panics
+  /// If a java exception is thrown, see [`Field::try_set`]
   pub fn set(&self, e: &mut java::Env, inst: &C, t: T) {
+    self.try_set(e, inst, t)
+        .unwrap_or_else(|err| panic!("{:?}", err))
+  }
+
+  /// Set the value of this field, yielding a [`java::Error`]
+  /// if a java exception is thrown
+  pub fn try_set(&self, e: &mut java::Env, inst: &C, t: T) -> Result<(), java::Error> {
     let inst = inst.downcast_ref(e);
     let t = t.downcast_value(e);
     e.set_field(inst, self.name, T::SIG, (&t).into())
-     .unwrap_java(e);
+     .to_java_error(e)
   }
 }
 
@@ -75,21 +97,31 @@ impl<C, T> StaticField<C, T>
   }
 
   /// Get the static field value
+  ///
+  /// # Panics
+  /// If a java exception is thrown, see [`StaticField::try_get`]
   pub fn get(&self, e: &mut java::Env) -> T {
+    self.try_get(e).unwrap_or_else(|err| panic!("{:?}", err))
+  }
+
+  /// Get the static field value, yielding a [`java::Error`]
+  /// if a java exception is thrown
+  pub fn try_get(&self, e: &mut java::Env) -> Result<T, java::Error> {
     let id = self.id.read().unwrap();
     if id.is_none() {
       drop(id);
 
+      let found = e.get_static_field_id(C::PATH, self.name, T::SIG)
+                   .to_java_error(e)?;
       let mut id = self.id.write().unwrap();
-      *id = Some(e.get_static_field_id(C::PATH, self.name, T::SIG)
-                  .unwrap_java(e));
+      *id = Some(found);
       drop(id);
 
-      self.get(e)
+      self.try_get(e)
     } else {
       let val = e.get_static_field_unchecked(C::PATH, id.unwrap(), T::jni())
-                 .unwrap();
-      T::upcast_value(e, val)
+                 .to_java_error(e)?;
+      Ok(T::upcast_value(e, val))
     }
   }
 }
@@ -188,29 +188,31 @@ impl<C, FA, FB, FC, FD, FE, FR> Method<C, fn(FA, FB, FC, FD, FE) -> FR>
   }
 }
 
-impl<C, FR> Method<C, fn() -> Result<FR, java::lang::Throwable>>
+impl<C, FR, Er> Method<C, fn() -> Result<FR, Er>>
   where C: Class,
-        FR: Class
+        FR: Class,
+        Er: java::FromJniError
 {
   /// Call the method
-  pub fn invoke(&self, e: &mut java::Env, inst: &C) -> Result<FR, java::lang::Throwable> {
+  pub fn invoke(&self, e: &mut java::Env, inst: &C) -> Result<FR, Er> {
     let inst = inst.downcast_ref(e);
     let mid = self.find(e);
     unsafe {
       e.call_method_unchecked(&inst, mid, Signature::of::<fn() -> FR>().return_type(), &[])
-       .to_throwable(e)
+       .map_err(|err| Er::from_jni_error(e, err))
        .map(|jv| FR::upcast_value(e, jv))
     }
   }
 }
 
-impl<C, FA, FR> Method<C, fn(FA) -> Result<FR, java::lang::Throwable>>
+impl<C, FA, FR, Er> Method<C, fn(FA) -> Result<FR, Er>>
   where C: Class,
         FA: Object,
-        FR: Class
+        FR: Class,
+        Er: java::FromJniError
 {
   /// Call the method
-  pub fn invoke(&self, e: &mut java::Env, inst: &C, fa: FA) -> Result<FR, java::lang::Throwable> {
+  pub fn invoke(&self, e: &mut java::Env, inst: &C, fa: FA) -> Result<FR, Er> {
     let inst = inst.downcast_ref(e);
     let fa = fa.downcast_value(e);
     let mid = self.find(e);
@@ -219,17 +221,18 @@ impl<C, FA, FR> Method<C, fn(FA) -> Result<FR, java::lang::Throwable>>
                               mid,
                               Signature::of::<fn(FA) -> FR>().return_type(),
                               &[fa.as_jni()])
-       .to_throwable(e)
+       .map_err(|err| Er::from_jni_error(e, err))
        .map(|jv| FR::upcast_value(e, jv))
     }
   }
 }
 
-impl<C, FA, FB, FR> Method<C, fn(FA, FB) -> Result<FR, java::lang::Throwable>>
+impl<C, FA, FB, FR, Er> Method<C, fn(FA, FB) -> Result<FR, Er>>
   where C: Class,
         FA: Object,
         FB: Object,
-        FR: Class
+        FR: Class,
+        Er: java::FromJniError
 {
   /// Call the method
   pub fn invoke(&self,
@@ -237,7 +240,7 @@ impl<C, FA, FB, FR> Method<C, fn(FA, FB) -> Result<FR, java::lang::Throwable>>
                 inst: &C,
                 fa: FA,
                 fb: FB)
-                -> Result<FR, java::lang::Throwable> {
+                -> Result<FR, Er> {
     let inst = inst.downcast_ref(e);
     let (fa, fb) = (fa.downcast_value(e), fb.downcast_value(e));
     let mid = self.find(e);
@@ -246,18 +249,19 @@ impl<C, FA, FB, FR> Method<C, fn(FA, FB) -> Result<FR, java::lang::Throwable>>
                               mid,
                               Signature::of::<fn(FA, FB) -> FR>().return_type(),
                               &[fa.as_jni(), fb.as_jni()])
-       .to_throwable(e)
+       .map_err(|err| Er::from_jni_error(e, err))
        .map(|jv| FR::upcast_value(e, jv))
     }
   }
 }
 
-impl<C, FA, FB, FC, FR> Method<C, fn(FA, FB, FC) -> Result<FR, java::lang::Throwable>>
+impl<C, FA, FB, FC, FR, Er> Method<C, fn(FA, FB, FC) -> Result<FR, Er>>
   where C: Class,
         FA: Object,
         FB: Object,
         FC: Object,
-        FR: Class
+        FR: Class,
+        Er: java::FromJniError
 {
   /// Call the method
   pub fn invoke(&self,
@@ -266,7 +270,7 @@ impl<C, FA, FB, FC, FR> Method<C, fn(FA, FB, FC) -> Result<FR, java::lang::Throw
                 fa: FA,
                 fb: FB,
                 fc: FC)
-                -> Result<FR, java::lang::Throwable> {
+                -> Result<FR, Er> {
     let inst = inst.downcast_ref(e);
     let (fa, fb, fc) = (fa.downcast_value(e), fb.downcast_value(e), fc.downcast_value(e));
     let mid = self.find(e);
@@ -275,19 +279,20 @@ impl<C, FA, FB, FC, FR> Method<C, fn(FA, FB, FC) -> Result<FR, java::lang::Throw
                               mid,
                               Signature::of::<fn(FA, FB, FC) -> FR>().return_type(),
                               &[fa.as_jni(), fb.as_jni(), fc.as_jni()])
-       .to_throwable(e)
+       .map_err(|err| Er::from_jni_error(e, err))
        .map(|jv| FR::upcast_value(e, jv))
     }
   }
 }
 
-impl<C, FA, FB, FC, FD, FR> Method<C, fn(FA, FB, FC, FD) -> Result<FR, java::lang::Throwable>>
+impl<C, FA, FB, FC, FD, FR, Er> Method<C, fn(FA, FB, FC, FD) -> Result<FR, Er>>
   where C: Class,
         FA: Object,
         FB: Object,
         FC: Object,
         FD: Object,
-        FR: Class
+        FR: Class,
+        Er: java::FromJniError
 {
   /// Call the method
   pub fn invoke(&self,
@@ -297,7 +302,7 @@ impl<C, FA, FB, FC, FD, FR> Method<C, fn(FA, FB, FC, FD) -> Result<FR, java::lan
                 fb: FB,
                 fc: FC,
                 fd: FD)
-                -> Result<FR, java::lang::Throwable> {
+                -> Result<FR, Er> {
     let inst = inst.downcast_ref(e);
     let (fa, fb, fc, fd) =
       (fa.downcast_value(e), fb.downcast_value(e), fc.downcast_value(e), fd.downcast_value(e));
@@ -307,21 +312,22 @@ impl<C, FA, FB, FC, FD, FR> Method<C, fn(FA, FB, FC, FD) -> Result<FR, java::lan
                               mid,
                               Signature::of::<fn(FA, FB, FC, FD) -> FR>().return_type(),
                               &[fa.as_jni(), fb.as_jni(), fc.as_jni(), fd.as_jni()])
-       .to_throwable(e)
+       .map_err(|err| Er::from_jni_error(e, err))
        .map(|jv| FR::upcast_value(e, jv))
     }
   }
 }
 
-impl<C, FA, FB, FC, FD, FE, FR>
-  Method<C, fn(FA, FB, FC, FD, FE) -> Result<FR, java::lang::Throwable>>
+impl<C, FA, FB, FC, FD, FE, FR, Er>
+  Method<C, fn(FA, FB, FC, FD, FE) -> Result<FR, Er>>
   where C: Class,
         FA: Object,
         FB: Object,
         FC: Object,
         FD: Object,
         FE: Object,
-        FR: Class
+        FR: Class,
+        Er: java::FromJniError
 {
   /// Call the method
   pub fn invoke(&self,
@@ -332,7 +338,7 @@ impl<C, FA, FB, FC, FD, FE, FR>
                 fc: FC,
                 fd: FD,
                 fe: FE)
-                -> Result<FR, java::lang::Throwable> {
+                -> Result<FR, Er> {
     let inst = inst.downcast_ref(e);
     let (fa, fb, fc, fd, fe) = (fa.downcast_value(e),
                                 fb.downcast_value(e),
@@ -349,7 +355,7 @@ impl<C, FA, FB, FC, FD, FE, FR>
                                 fc.as_jni(),
                                 fd.as_jni(),
                                 fe.as_jni()])
-       .to_throwable(e)
+       .map_err(|err| Er::from_jni_error(e, err))
        .map(|jv| FR::upcast_value(e, jv))
     }
   }
@@ -535,65 +541,69 @@ impl<C, FA, FB, FC, FD, FE, FR> StaticMethod<C, fn(FA, FB, FC, FD, FE) -> FR>
   }
 }
 
-impl<C, FR> StaticMethod<C, fn() -> Result<FR, java::lang::Throwable>>
+impl<C, FR, Er> StaticMethod<C, fn() -> Result<FR, Er>>
   where C: Class,
-        FR: Class
+        FR: Class,
+        Er: java::FromJniError
 {
   /// Invoke the static method
-  pub fn invoke(&self, e: &mut java::Env) -> Result<FR, java::lang::Throwable> {
+  pub fn invoke(&self, e: &mut java::Env) -> Result<FR, Er> {
     let (class, mid) = self.find(e);
     unsafe {
-      e.call_static_method_unchecked(class, mid, Signature::of::<fn() -> Result<FR, java::lang::Throwable>>().return_type(), &[])
-       .to_throwable(e).map(|jv| FR::upcast_value(e, jv))
+      e.call_static_method_unchecked(class, mid, Signature::of::<fn() -> Result<FR, Er>>().return_type(), &[])
+       .map_err(|err| Er::from_jni_error(e, err)).map(|jv| FR::upcast_value(e, jv))
     }
   }
 }
 
-impl<C, FA, FR> StaticMethod<C, fn(FA) -> Result<FR, java::lang::Throwable>>
+impl<C, FA, FR, Er> StaticMethod<C, fn(FA) -> Result<FR, Er>>
   where C: Class,
         FA: Object,
-        FR: Class
+        FR: Class,
+        Er: java::FromJniError
 {
   /// Invoke the static method
-  pub fn invoke(&self, e: &mut java::Env, fa: FA) -> Result<FR, java::lang::Throwable> {
+  pub fn invoke(&self, e: &mut java::Env, fa: FA) -> Result<FR, Er> {
     let fa = fa.downcast_value(e);
     let (class, mid) = self.find(e);
     unsafe {
       e.call_static_method_unchecked(class,
                                      mid,
-                                     Signature::of::<fn(FA) -> Result<FR, java::lang::Throwable>>().return_type(),
+                                     Signature::of::<fn(FA) -> Result<FR, Er>>().return_type(),
                                      &[fa.as_jni()])
-       .to_throwable(e).map(|jv| FR::upcast_value(e, jv))
+       .map_err(|err| Er::from_jni_error(e, err)).map(|jv| FR::upcast_value(e, jv))
     }
   }
 }
 
-impl<C, FA, FB, FR> StaticMethod<C, fn(FA, FB) -> Result<FR, java::lang::Throwable>>
+impl<C, FA, FB, FR, Er> StaticMethod<C, fn(FA, FB) -> Result<FR, Er>>
   where C: Class,
         FA: Object,
         FB: Object,
-        FR: Class
+        FR: Class,
+        Er: java::FromJniError
 {
   /// Invoke the static method
-  pub fn invoke(&self, e: &mut java::Env, fa: FA, fb: FB) -> Result<FR, java::lang::Throwable> {
+  pub fn invoke(&self, e: &mut java::Env, fa: FA, fb: FB) -> Result<FR, Er> {
     let (fa, fb) = (fa.downcast_value(e), fb.downcast_value(e));
     let (class, mid) = self.find(e);
     unsafe {
       e.call_static_method_unchecked(class,
                                      mid,
-                                     Signature::of::<fn(FA, FB) -> Result<FR, java::lang::Throwable>>().return_type(),
+                                     Signature::of::<fn(FA, FB) -> Result<FR, Er>>().return_type(),
                                      &[fa.as_jni(), fb.as_jni()])
-       .to_throwable(e).map(|jv| FR::upcast_value(e, jv))
+       .map_err(|err| Er::from_jni_error(e, err)).map(|jv| FR::upcast_value(e, jv))
     }
   }
 }
 
-impl<C, FA, FB, FC, FR> StaticMethod<C, fn(FA, FB, FC) -> Result<FR, java::lang::Throwable>>
+impl<C, FA, FB, FC, FR, Er> StaticMethod<C, fn(FA, FB, FC) -> Result<FR, Er>>
   where C: Class,
         FA: Object,
         FB: Object,
         FC: Object,
-        FR: Class
+        FR: Class,
+        Er: java::FromJniError
 {
   /// Invoke the static method
   pub fn invoke(&self,
@@ -601,26 +611,27 @@ impl<C, FA, FB, FC, FR> StaticMethod<C, fn(FA, FB, FC) -> Result<FR, java::lang:
                 fa: FA,
                 fb: FB,
                 fc: FC)
-                -> Result<FR, java::lang::Throwable> {
+                -> Result<FR, Er> {
     let (fa, fb, fc) = (fa.downcast_value(e), fb.downcast_value(e), fc.downcast_value(e));
     let (class, mid) = self.find(e);
     unsafe {
       e.call_static_method_unchecked(class,
                                      mid,
-                                     Signature::of::<fn(FA, FB, FC) -> Result<FR, java::lang::Throwable>>().return_type(),
+                                     Signature::of::<fn(FA, FB, FC) -> Result<FR, Er>>().return_type(),
                                      &[fa.as_jni(), fb.as_jni(), fc.as_jni()])
-       .to_throwable(e).map(|jv| FR::upcast_value(e, jv))
+       .map_err(|err| Er::from_jni_error(e, err)).map(|jv| FR::upcast_value(e, jv))
     }
   }
 }
 
-impl<C, FA, FB, FC, FD, FR> StaticMethod<C, fn(FA, FB, FC, FD) -> Result<FR, java::lang::Throwable>>
+impl<C, FA, FB, FC, FD, FR, Er> StaticMethod<C, fn(FA, FB, FC, FD) -> Result<FR, Er>>
   where C: Class,
         FA: Object,
         FB: Object,
         FC: Object,
         FD: Object,
-        FR: Class
+        FR: Class,
+        Er: java::FromJniError
 {
   /// Invoke the static method
   pub fn invoke(&self,
@@ -629,29 +640,30 @@ impl<C, FA, FB, FC, FD, FR> StaticMethod<C, fn(FA, FB, FC, FD) -> Result<FR, jav
                 fb: FB,
                 fc: FC,
                 fd: FD)
-                -> Result<FR, java::lang::Throwable> {
+                -> Result<FR, Er> {
     let (fa, fb, fc, fd) =
       (fa.downcast_value(e), fb.downcast_value(e), fc.downcast_value(e), fd.downcast_value(e));
     let (class, mid) = self.find(e);
     unsafe {
       e.call_static_method_unchecked(class,
                                      mid,
-                                     Signature::of::<fn(FA, FB, FC, FD) -> Result<FR, java::lang::Throwable>>().return_type(),
+                                     Signature::of::<fn(FA, FB, FC, FD) -> Result<FR, Er>>().return_type(),
                                      &[fa.as_jni(), fb.as_jni(), fc.as_jni(), fd.as_jni()])
-       .to_throwable(e).map(|jv| FR::upcast_value(e, jv))
+       .map_err(|err| Er::from_jni_error(e, err)).map(|jv| FR::upcast_value(e, jv))
     }
   }
 }
 
-impl<C, FA, FB, FC, FD, FE, FR>
-  StaticMethod<C, fn(FA, FB, FC, FD, FE) -> Result<FR, java::lang::Throwable>>
+impl<C, FA, FB, FC, FD, FE, FR, Er>
+  StaticMethod<C, fn(FA, FB, FC, FD, FE) -> Result<FR, Er>>
   where C: Class,
         FA: Object,
         FB: Object,
         FC: Object,
         FD: Object,
         FE: Object,
-        FR: Class
+        FR: Class,
+        Er: java::FromJniError
 {
   /// Invoke the static method
   pub fn invoke(&self,
@@ -661,7 +673,7 @@ impl<C, FA, FB, FC, FD, FE, FR>
                 fc: FC,
                 fd: FD,
                 fe: FE)
-                -> Result<FR, java::lang::Throwable> {
+                -> Result<FR, Er> {
     let (fa, fb, fc, fd, fe) = (fa.downcast_value(e),
                                 fb.downcast_value(e),
                                 fc.downcast_value(e),
@@ -671,13 +683,13 @@ impl<C, FA, FB, FC, FD, FE, FR>
     unsafe {
       e.call_static_method_unchecked(class,
                                      mid,
-                                     Signature::of::<fn(FA, FB, FC, FD, FE) -> Result<FR, java::lang::Throwable>>().return_type(),
+                                     Signature::of::<fn(FA, FB, FC, FD, FE) -> Result<FR, Er>>().return_type(),
                                      &[fa.as_jni(),
                                        fb.as_jni(),
                                        fc.as_jni(),
                                        fd.as_jni(),
                                        fe.as_jni()])
-       .to_throwable(e).map(|jv| FR::upcast_value(e, jv))
+       .map_err(|err| Er::from_jni_error(e, err)).map(|jv| FR::upcast_value(e, jv))
     }
   }
 }
@@ -702,17 +714,17 @@ impl<C, F> Constructor<C, F>
   }
 
   /// Get & cache the method ID for this constructor
-  fn find(&self, e: &mut java::Env) -> JMethodID {
+  fn find(&self, e: &mut java::Env) -> Result<JMethodID, java::Error> {
     let mid = self.id.read().unwrap();
 
     if mid.is_none() {
       drop(mid);
-      let mid = e.get_method_id(C::PATH, "<init>", F::SIG).unwrap_java(e);
+      let mid = e.get_method_id(C::PATH, "<init>", F::SIG).to_java_error(e)?;
       let mut field = self.id.write().unwrap();
       *field = Some(mid);
-      mid
+      Ok(mid)
     } else {
-      mid.unwrap()
+      Ok(mid.unwrap())
     }
   }
 }
@@ -720,10 +732,19 @@ impl<C, F> Constructor<C, F>
 impl<C> Constructor<C, fn()> where C: Class
 {
   /// Invoke the constructor
+  ///
+  /// # Panics
+  /// If a java exception is thrown, see [`Constructor::try_invoke`]
   pub fn invoke(&self, e: &mut java::Env) -> C {
+    self.try_invoke(e).unwrap_or_else(|err| panic!("{:?}", err))
+  }
+
+  /// Invoke the constructor, yielding a [`java::Error`]
+  /// if a java exception is thrown
+  pub fn try_invoke(&self, e: &mut java::Env) -> Result<C, java::Error> {
     let jobj = e.new_object(C::PATH, Signature::of::<fn()>(), &[])
-                .unwrap_java(e);
-    java::lang::Object::from_local(e, jobj).upcast_to::<C>(e)
+                .to_java_error(e)?;
+    Ok(java::lang::Object::from_local(e, jobj).upcast_to::<C>(e))
   }
 }
 
@@ -732,15 +753,24 @@ impl<C, FA> Constructor<C, fn(FA)>
         FA: Object
 {
   /// Invoke the constructor
+  ///
+  /// # Panics
+  /// If a java exception is thrown, see [`Constructor::try_invoke`]
   pub fn invoke(&self, e: &mut java::Env, fa: FA) -> C {
+    self.try_invoke(e, fa).unwrap_or_else(|err| panic!("{:?}", err))
+  }
+
+  /// Invoke the constructor, yielding a [`java::Error`]
+  /// if a java exception is thrown
+  pub fn try_invoke(&self, e: &mut java::Env, fa: FA) -> Result<C, java::Error> {
     let fa = fa.downcast_value(e);
-    let mid = self.find(e);
+    let mid = self.find(e)?;
     let jv = unsafe {
       e.new_object_unchecked(C::PATH, mid, &[fa.as_jni()])
-       .unwrap_java(e)
+       .to_java_error(e)?
     };
 
-    java::lang::Object::from_local(e, jv).upcast_to::<C>(e)
+    Ok(java::lang::Object::from_local(e, jv).upcast_to::<C>(e))
   }
 }
 
@@ -750,14 +780,23 @@ impl<C, FA, FB> Constructor<C, fn(FA, FB)>
         FB: Object
 {
   /// Invoke the constructor
+  ///
+  /// # Panics
+  /// If a java exception is thrown, see [`Constructor::try_invoke`]
   pub fn invoke(&self, e: &mut java::Env, fa: FA, fb: FB) -> C {
+    self.try_invoke(e, fa, fb).unwrap_or_else(|err| panic!("{:?}", err))
+  }
+
+  /// Invoke the constructor, yielding a [`java::Error`]
+  /// if a java exception is thrown
+  pub fn try_invoke(&self, e: &mut java::Env, fa: FA, fb: FB) -> Result<C, java::Error> {
     let (fa, fb) = (fa.downcast_value(e), fb.downcast_value(e));
-    let mid = self.find(e);
+    let mid = self.find(e)?;
     let jv = unsafe {
       e.new_object_unchecked(C::PATH, mid, &[fa.as_jni(), fb.as_jni()])
-       .unwrap_java(e)
+       .to_java_error(e)?
     };
-    java::lang::Object::from_local(e, jv).upcast_to::<C>(e)
+    Ok(java::lang::Object::from_local(e, jv).upcast_to::<C>(e))
   }
 }
 
@@ -768,14 +807,23 @@ impl<C, FA, FB, FC> Constructor<C, fn(FA, FB, FC)>
         FC: Object
 {
   /// Invoke the constructor
+  ///
+  /// # Panics
+  /// If a java exception is thrown, see [`Constructor::try_invoke`]
   pub fn invoke(&self, e: &mut java::Env, fa: FA, fb: FB, fc: FC) -> C {
+    self.try_invoke(e, fa, fb, fc).unwrap_or_else(|err| panic!("{:?}", err))
+  }
+
+  /// Invoke the constructor, yielding a [`java::Error`]
+  /// if a java exception is thrown
+  pub fn try_invoke(&self, e: &mut java::Env, fa: FA, fb: FB, fc: FC) -> Result<C, java::Error> {
     let (fa, fb, fc) = (fa.downcast_value(e), fb.downcast_value(e), fc.downcast_value(e));
-    let mid = self.find(e);
+    let mid = self.find(e)?;
     let jv = unsafe {
       e.new_object_unchecked(C::PATH, mid, &[fa.as_jni(), fb.as_jni(), fc.as_jni()])
-       .unwrap_java(e)
+       .to_java_error(e)?
     };
-    java::lang::Object::from_local(e, jv).upcast_to::<C>(e)
+    Ok(java::lang::Object::from_local(e, jv).upcast_to::<C>(e))
   }
 }
 
@@ -787,17 +835,32 @@ impl<C, FA, FB, FC, FD> Constructor<C, fn(FA, FB, FC, FD)>
         FD: Object
 {
   /// Invoke the constructor
+  ///
+  /// # Panics
+  /// If a java exception is thrown, see [`Constructor::try_invoke`]
   pub fn invoke(&self, e: &mut java::Env, fa: FA, fb: FB, fc: FC, fd: FD) -> C {
+    self.try_invoke(e, fa, fb, fc, fd).unwrap_or_else(|err| panic!("{:?}", err))
+  }
+
+  /// Invoke the constructor, yielding a [`java::Error`]
+  /// if a java exception is thrown
+  pub fn try_invoke(&self,
+                    e: &mut java::Env,
+                    fa: FA,
+                    fb: FB,
+                    fc: FC,
+                    fd: FD)
+                    -> Result<C, java::Error> {
     let (fa, fb, fc, fd) =
       (fa.downcast_value(e), fb.downcast_value(e), fc.downcast_value(e), fd.downcast_value(e));
-    let mid = self.find(e);
+    let mid = self.find(e)?;
     let jv = unsafe {
       e.new_object_unchecked(C::PATH,
                              mid,
                              &[fa.as_jni(), fb.as_jni(), fc.as_jni(), fd.as_jni()])
-       .unwrap_java(e)
+       .to_java_error(e)?
     };
-    java::lang::Object::from_local(e, jv).upcast_to::<C>(e)
+    Ok(java::lang::Object::from_local(e, jv).upcast_to::<C>(e))
   }
 }
 
@@ -810,13 +873,29 @@ impl<C, FA, FB, FC, FD, FE> Constructor<C, fn(FA, FB, FC, FD, FE)>
         FE: Object
 {
   /// Invoke the constructor
+  ///
+  /// # Panics
+  /// If a java exception is thrown, see [`Constructor::try_invoke`]
   pub fn invoke(&self, e: &mut java::Env, fa: FA, fb: FB, fc: FC, fd: FD, fe: FE) -> C {
+    self.try_invoke(e, fa, fb, fc, fd, fe).unwrap_or_else(|err| panic!("{:?}", err))
+  }
+
+  /// Invoke the constructor, yielding a [`java::Error`]
+  /// if a java exception is thrown
+  pub fn try_invoke(&self,
+                    e: &mut java::Env,
+                    fa: FA,
+                    fb: FB,
+                    fc: FC,
+                    fd: FD,
+                    fe: FE)
+                    -> Result<C, java::Error> {
     let (fa, fb, fc, fd, fe) = (fa.downcast_value(e),
                                 fb.downcast_value(e),
                                 fc.downcast_value(e),
                                 fd.downcast_value(e),
                                 fe.downcast_value(e));
-    let mid = self.find(e);
+    let mid = self.find(e)?;
     let jv = unsafe {
       e.new_object_unchecked(C::PATH,
                              mid,
@@ -825,8 +904,8 @@ impl<C, FA, FB, FC, FD, FE> Constructor<C, fn(FA, FB, FC, FD, FE)>
                                fc.as_jni(),
                                fd.as_jni(),
                                fe.as_jni()])
-       .unwrap_java(e)
+       .to_java_error(e)?
     };
-    java::lang::Object::from_local(e, jv).upcast_to::<C>(e)
+    Ok(java::lang::Object::from_local(e, jv).upcast_to::<C>(e))
   }
 }
@@ -17,7 +17,28 @@ impl java::Class for Throwable {
   const PATH: &'static str = "java/lang/Throwable";
 }
 
+/// `java.lang.Class`
+struct JavaClass(java::lang::Object);
+java::object_newtype!(JavaClass);
+impl java::Class for JavaClass {
+  const PATH: &'static str = "java/lang/Class";
+}
+
 impl Throwable {
+  /// `java.lang.Throwable.getMessage()`
+  pub fn message(&self, e: &mut java::Env) -> Option<String> {
+    java::Method::<Self, fn() -> Nullable<String>>::new("getMessage").invoke(e, self)
+                                                                     .into_option(e)
+  }
+
+  /// The fully qualified name of this exception's class, e.g. `java.io.IOException`
+  ///
+  /// (`this.getClass().getName()`)
+  pub fn class_name(&self, e: &mut java::Env) -> String {
+    let class = java::Method::<Self, fn() -> JavaClass>::new("getClass").invoke(e, self);
+    java::Method::<JavaClass, fn() -> String>::new("getName").invoke(e, &class)
+  }
+
   /// `java.lang.Throwable.getStackTrace()`
   pub fn stack_trace(&self, e: &mut java::Env) -> Vec<StackTraceElement> {
     java::Method::<Self, fn() -> Vec<StackTraceElement>>::new("getStackTrace").invoke(e, self)
@@ -71,6 +92,16 @@ impl core::fmt::Debug for Throwable {
 mod tests {
   use crate::java::io::IOException;
 
+  #[test]
+  fn message_and_class_name() {
+    let mut e = crate::test::init();
+    let e = &mut e;
+    let foo = IOException::new(e, "foo").to_throwable(e);
+
+    assert_eq!(foo.message(e), Some("foo".to_string()));
+    assert_eq!(foo.class_name(e), "java.io.IOException".to_string());
+  }
+
   #[test]
   fn dbg() {
     let mut e = crate::test::init();
@@ -27,7 +27,12 @@ pub use nullable::Nullable;
 mod result;
 
 #[doc(inline)]
-pub use result::{ResultExt, ResultYieldToJavaOrThrow};
+pub use result::{FromJniError, ResultExt, ResultYieldToJavaOrThrow};
+
+mod error;
+
+#[doc(inline)]
+pub use error::Error;
 
 mod no_upcast;
 
@@ -54,6 +59,11 @@ mod ty;
 #[doc(inline)]
 pub use ty::{Signature, Type};
 
+mod array;
+
+#[doc(inline)]
+pub use array::JavaArray;
+
 mod function;
 
 #[doc(inline)]
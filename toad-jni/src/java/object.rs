@@ -171,7 +171,7 @@ impl<T> Object for Vec<T> where T: java::Object
       | u16::SIG => go!(new_char_array, set_char_array_region),
       | bool::SIG => go!(new_boolean_array, set_boolean_array_region),
       | _ => {
-        let arr = e.new_object_array(self.len() as i32, java::lang::Object::SIG, JObject::null())
+        let arr = e.new_object_array(self.len() as i32, T::SIG.class_path(), JObject::null())
                    .unwrap_java(e);
         let arr_ref = &arr;
         self.iter().enumerate().for_each(|(ix, o)| {
@@ -186,6 +186,47 @@ impl<T> Object for Vec<T> where T: java::Object
   }
 }
 
+impl Object for Vec<u8> {
+  fn upcast(e: &mut java::Env, jobj: java::lang::Object) -> Self {
+    let arr = <&JByteArray>::from(jobj.as_local());
+    e.convert_byte_array(arr).unwrap_java(e)
+  }
+
+  fn downcast(self, e: &mut java::Env) -> java::lang::Object {
+    self.downcast_ref(e)
+  }
+
+  fn downcast_ref(&self, e: &mut java::Env) -> java::lang::Object {
+    let arr = e.byte_array_from_slice(self).unwrap_java(e);
+    java::lang::Object::from_local(e, arr)
+  }
+}
+
+impl<T> Object for Option<T> where T: java::Class
+{
+  fn upcast(e: &mut java::Env, jobj: java::lang::Object) -> Self {
+    if jobj.is_null() {
+      None
+    } else {
+      Some(jobj.upcast_to::<T>(e))
+    }
+  }
+
+  fn downcast(self, e: &mut java::Env) -> java::lang::Object {
+    match self {
+      | Some(t) => t.downcast(e),
+      | None => ().downcast(e),
+    }
+  }
+
+  fn downcast_ref(&self, e: &mut java::Env) -> java::lang::Object {
+    match self {
+      | Some(t) => t.downcast_ref(e),
+      | None => ().downcast(e),
+    }
+  }
+}
+
 impl<T> Object for T where T: java::Primitive
 {
   fn upcast(e: &mut java::Env, jobj: java::lang::Object) -> Self {
@@ -27,6 +27,42 @@ impl<T> ResultYieldToJavaOrThrow for Result<T, Throwable> where T: java::Object
   }
 }
 
+impl<T> ResultYieldToJavaOrThrow for Result<T, java::Error> where T: java::Object
+{
+  fn yield_to_java_or_throw(self, e: &mut java::Env) -> jobject {
+    match self {
+      | Ok(ok) => ok.yield_to_java(e),
+      | Err(err) => {
+        err.throw(e);
+        *JObject::null()
+      },
+    }
+  }
+}
+
+/// Errors that can be yielded by fallible lenses, e.g.
+/// [`java::Method`]`<C, fn() -> `[`Result`]`<R, E>>`
+///
+/// Implemented for [`java::Error`] and [`java::lang::Throwable`].
+pub trait FromJniError: Sized {
+  /// Convert a failed JNI call to `Self`, clearing the pending java exception (if any)
+  fn from_jni_error(e: &mut java::Env, err: jni::errors::Error) -> Self;
+}
+
+impl FromJniError for java::Error {
+  fn from_jni_error(e: &mut java::Env, err: jni::errors::Error) -> Self {
+    java::Error::from_jni(e, err)
+  }
+}
+
+impl FromJniError for Throwable {
+  /// # Panics
+  /// if the JNI call failed without a java exception being thrown
+  fn from_jni_error(e: &mut java::Env, err: jni::errors::Error) -> Self {
+    Err::<(), _>(err).to_throwable(e).unwrap_err()
+  }
+}
+
 /// [`toad_jni::errors::Result`] interop helpers
 pub trait ResultExt<T> {
   /// If a java exception occurred, toString it and panic
@@ -34,6 +70,9 @@ pub trait ResultExt<T> {
 
   /// If a java exception occurred, convert to [`java::lang::Throwable`]
   fn to_throwable(self, e: &mut java::Env) -> Result<T, Throwable>;
+
+  /// Convert the error to a [`java::Error`]
+  fn to_java_error(self, e: &mut java::Env) -> Result<T, java::Error>;
 }
 
 impl<T> ResultExt<T> for jni::errors::Result<T> {
@@ -65,4 +104,8 @@ impl<T> ResultExt<T> for jni::errors::Result<T> {
       | o => Ok(o.unwrap()),
     }
   }
+
+  fn to_java_error(self, e: &mut java::Env) -> Result<T, java::Error> {
+    self.map_err(|err| java::Error::from_jni(e, err))
+  }
 }
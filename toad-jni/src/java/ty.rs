@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::fmt::Display;
 
 use jni::objects::{GlobalRef, JObject};
@@ -79,7 +80,7 @@ impl Signature {
     Self::empty().concat(Self::ARGS_OPEN)
   }
 
-  const fn array_of(t: Self) -> Self {
+  pub(crate) const fn array_of(t: Self) -> Self {
     Self::empty().concat(Self::ARRAY_OF).concat(t)
   }
 
@@ -138,6 +139,18 @@ impl Signature {
     self
   }
 
+  /// The name of the class described by this signature,
+  /// in the format expected by `FindClass`.
+  ///
+  /// Class signatures (`Ljava/lang/String;`) yield the class path
+  /// (`java/lang/String`), while array signatures (`[I`) are yielded unchanged.
+  pub(crate) fn class_path(&self) -> &str {
+    let s = self.as_str();
+    s.strip_prefix(Self::CLASS_PATH_OPEN.as_str())
+     .and_then(|s| s.strip_suffix(Self::CLASS_PATH_CLOSE.as_str()))
+     .unwrap_or(s)
+  }
+
   /// Convert a [`Signature`] reference to [`str`]
   pub fn as_str(&self) -> &str {
     match core::str::from_utf8(&self.bytes[0..self.len]) {
@@ -160,6 +173,8 @@ impl AsRef<str> for Signature {
 }
 
 mod type_sealed {
+  use std::collections::BTreeMap;
+
   use jni::objects::GlobalRef;
 
   use crate::java;
@@ -167,8 +182,20 @@ mod type_sealed {
   #[allow(unreachable_pub)]
   pub trait TypeSealed {}
   impl<T> TypeSealed for T where T: java::Class {}
-  impl<T> TypeSealed for Result<T, java::lang::Throwable> where T: java::Type {}
+  impl<T, E> TypeSealed for Result<T, E>
+    where T: java::Type,
+          E: java::FromJniError
+  {
+  }
   impl<T> TypeSealed for Vec<T> where T: java::Type {}
+  impl<T> TypeSealed for java::JavaArray<T> where T: java::Object {}
+  impl TypeSealed for Vec<u8> {}
+  impl<T> TypeSealed for Option<T> where T: java::Class {}
+  impl<K, V> TypeSealed for BTreeMap<K, V>
+    where K: java::Type,
+          V: java::Type
+  {
+  }
   impl<R> TypeSealed for fn() -> R where R: java::Type {}
   impl<A, R> TypeSealed for fn(A) -> R where R: java::Type {}
   impl<A, B, R> TypeSealed for fn(A, B) -> R where R: java::Type {}
@@ -197,11 +224,15 @@ mod type_sealed {
 /// |rust type|java type|notes|
 /// |--|--|--|
 /// |`T where T: `[`java::Class`]|fully qualified class path||
-/// |[`Result`]`<T, `[`java::lang::Throwable`]`>`|`T::PATH`|[`java::Class`] must be implemented for `T`|
+/// |[`Result`]`<T, E>`|`T::PATH`|[`java::Class`] must be implemented for `T`. `E` is [`java::Error`] or [`java::lang::Throwable`] (see [`java::FromJniError`])|
 /// |[`java::Nullable`]`<T>`|`T::PATH`|[`java::Class`] must be implemented for `T`|
 /// |[`java::NoUpcast`]`<T>`|`java::lang::Object`|[`java::Class`] must be implemented for `T`. Used when a method should have the signature of returning `T`, but you would like the object reference without [`java::Object::upcast`]ing.|
 /// |[`java::lang::Object`]|`java.lang.Object`||
 /// |[`Vec`]`<T>`|`T[]`|`T` must be [`java::Type`]|
+/// |[`Vec`]`<u8>`|`byte[]`|bytes are reinterpreted as signed|
+/// |[`java::JavaArray`]`<T>`|`T[]`|`T` must be [`java::Object`]. Unlike [`Vec`], elements are accessed in place rather than copied.|
+/// |[`Option`]`<T>`|`T::PATH`|[`java::Class`] must be implemented for `T`. `None` is `null`.|
+/// |[`BTreeMap`]`<K, V>`|`java.util.Map`|`K` and `V` must be [`java::Type`]|
 /// |[`String`]|`java.lang.String`|[`java::Class`] and [`java::Object`] implemented for [`String`]|
 /// |`()`|`void`||
 /// |`u16`|`char`||
@@ -233,7 +264,9 @@ impl<T> Type for T where T: java::Class
   }
 }
 
-impl<T> Type for Result<T, java::lang::Throwable> where T: java::Class
+impl<T, E> Type for Result<T, E>
+  where T: java::Class,
+        E: java::FromJniError
 {
   const SIG: Signature = Signature::class(T::PATH);
   fn jni() -> jni::signature::JavaType {
@@ -319,6 +352,31 @@ impl<T> Type for Vec<T> where T: Type
   }
 }
 
+impl Type for Vec<u8> {
+  const SIG: Signature = Signature::array_of(i8::SIG);
+  fn jni() -> jni::signature::JavaType {
+    jni::signature::JavaType::Array(Box::new(i8::jni()))
+  }
+}
+
+impl<T> Type for Option<T> where T: java::Class
+{
+  const SIG: Signature = T::SIG;
+  fn jni() -> jni::signature::JavaType {
+    T::jni()
+  }
+}
+
+impl<K, V> Type for BTreeMap<K, V>
+  where K: Type,
+        V: Type
+{
+  const SIG: Signature = Signature::class("java/util/Map");
+  fn jni() -> jni::signature::JavaType {
+    jni::signature::JavaType::Object("java/util/Map".into())
+  }
+}
+
 impl<R> Type for fn() -> R where R: Type
 {
   const SIG: Signature = Signature::function().ret(R::SIG);
@@ -430,4 +488,12 @@ pub mod test {
     assert_eq!(Signature::of::<fn() -> f64>().return_type(),
                Primitive(Double));
   }
+
+  #[test]
+  fn class_path() {
+    assert_eq!(Signature::of::<String>().class_path(), "java/lang/String");
+    assert_eq!(Signature::of::<Vec<i32>>().class_path(), "[I");
+    assert_eq!(Signature::of::<Vec<Vec<String>>>().class_path(),
+               "[[Ljava/lang/String;");
+  }
 }
@@ -0,0 +1,164 @@
+use core::marker::PhantomData;
+use std::collections::BTreeMap;
+
+use crate::java::{self, Object};
+
+/// java/util/Map
+pub struct Map<K, V>(java::lang::Object, PhantomData<(K, V)>);
+
+impl<K, V> java::Class for Map<K, V>
+  where K: java::Object,
+        V: java::Object
+{
+  const PATH: &'static str = "java/util/Map";
+}
+
+impl<K, V> java::Object for Map<K, V>
+  where K: java::Object,
+        V: java::Object
+{
+  fn upcast(_e: &mut java::Env, jobj: java::lang::Object) -> Self {
+    Self(jobj, PhantomData)
+  }
+
+  fn downcast(self, _e: &mut java::Env) -> java::lang::Object {
+    self.0
+  }
+
+  fn downcast_ref(&self, e: &mut java::Env) -> java::lang::Object {
+    self.0.downcast_ref(e)
+  }
+}
+
+/// java/util/LinkedHashMap
+///
+/// Used as the concrete [`Map`] implementation when creating maps from rust,
+/// since it preserves the order entries are inserted in.
+struct LinkedHashMap(java::lang::Object);
+
+java::object_newtype!(LinkedHashMap);
+impl java::Class for LinkedHashMap {
+  const PATH: &'static str = "java/util/LinkedHashMap";
+}
+
+/// java/util/Set
+struct Set(java::lang::Object);
+
+java::object_newtype!(Set);
+impl java::Class for Set {
+  const PATH: &'static str = "java/util/Set";
+}
+
+/// java/util/Map$Entry
+struct Entry(java::lang::Object);
+
+java::object_newtype!(Entry);
+impl java::Class for Entry {
+  const PATH: &'static str = "java/util/Map$Entry";
+}
+
+type ObjectMap = Map<java::lang::Object, java::lang::Object>;
+
+impl<K, V> Map<K, V>
+  where K: java::Object,
+        V: java::Object
+{
+  fn cast<K2, V2>(self) -> Map<K2, V2> {
+    Map(self.0, PhantomData)
+  }
+
+  fn cast_ref(&self) -> &ObjectMap {
+    // SAFETY:
+    // this is safe because there are no values of type `K` or `V`
+    // stored in this struct; simply just casting the PhantomData
+    // to a different PhantomData.
+    unsafe { core::mem::transmute(self) }
+  }
+
+  /// Create a new, empty map (backed by `java.util.LinkedHashMap`)
+  pub fn new(e: &mut java::Env) -> Self {
+    static CTOR: java::Constructor<LinkedHashMap, fn()> = java::Constructor::new();
+    let map = CTOR.invoke(e).downcast(e);
+    ObjectMap::upcast(e, map).cast()
+  }
+
+  /// java.util.Map.size()
+  pub fn size(&self, e: &mut java::Env) -> i32 {
+    static SIZE: java::Method<ObjectMap, fn() -> i32> = java::Method::new("size");
+    SIZE.invoke(e, self.cast_ref())
+  }
+
+  /// java.util.Map.get(Object)
+  pub fn get(&self, e: &mut java::Env, k: &K) -> Option<V> {
+    static GET: java::Method<ObjectMap, fn(java::lang::Object) -> java::lang::Object> =
+      java::Method::new("get");
+    let k = k.downcast_ref(e);
+    let v = GET.invoke(e, self.cast_ref(), k);
+
+    if v.is_null() {
+      None
+    } else {
+      Some(v.upcast_to::<V>(e))
+    }
+  }
+
+  fn put_object(&self, e: &mut java::Env, k: java::lang::Object, v: java::lang::Object) {
+    static PUT: java::Method<ObjectMap,
+                               fn(java::lang::Object, java::lang::Object) -> java::lang::Object> =
+      java::Method::new("put");
+    PUT.invoke(e, self.cast_ref(), k, v);
+  }
+
+  /// java.util.Map.put(Object, Object)
+  pub fn put(&self, e: &mut java::Env, k: K, v: V) {
+    let (k, v) = (k.downcast(e), v.downcast(e));
+    self.put_object(e, k, v)
+  }
+
+  /// Collect all of the key-value pairs in the map
+  pub fn entries(&self, e: &mut java::Env) -> Vec<(K, V)> {
+    static ENTRY_SET: java::Method<ObjectMap, fn() -> Set> = java::Method::new("entrySet");
+    static TO_ARRAY: java::Method<Set, fn() -> Vec<java::lang::Object>> =
+      java::Method::new("toArray");
+    static GET_KEY: java::Method<Entry, fn() -> java::lang::Object> = java::Method::new("getKey");
+    static GET_VALUE: java::Method<Entry, fn() -> java::lang::Object> =
+      java::Method::new("getValue");
+
+    let set = ENTRY_SET.invoke(e, self.cast_ref());
+    TO_ARRAY.invoke(e, &set)
+            .into_iter()
+            .map(|entry| {
+              let entry = entry.upcast_to::<Entry>(e);
+              let k = GET_KEY.invoke(e, &entry).upcast_to::<K>(e);
+              let v = GET_VALUE.invoke(e, &entry).upcast_to::<V>(e);
+              (k, v)
+            })
+            .collect()
+  }
+}
+
+/// `BTreeMap<K, V>` is marshaled to a `java.util.Map<K, V>`
+/// (instantiated as a `java.util.LinkedHashMap`, preserving key order)
+impl<K, V> java::Object for BTreeMap<K, V>
+  where K: java::Object + Ord,
+        V: java::Object
+{
+  fn upcast(e: &mut java::Env, jobj: java::lang::Object) -> Self {
+    Map::<K, V>::upcast(e, jobj).entries(e).into_iter().collect()
+  }
+
+  fn downcast(self, e: &mut java::Env) -> java::lang::Object {
+    let map = Map::<K, V>::new(e);
+    self.into_iter().for_each(|(k, v)| map.put(e, k, v));
+    map.downcast(e)
+  }
+
+  fn downcast_ref(&self, e: &mut java::Env) -> java::lang::Object {
+    let map = Map::<K, V>::new(e);
+    self.iter().for_each(|(k, v)| {
+                 let (k, v) = (k.downcast_ref(e), v.downcast_ref(e));
+                 map.put_object(e, k, v)
+               });
+    map.downcast(e)
+  }
+}
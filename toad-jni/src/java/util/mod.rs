@@ -3,6 +3,11 @@ mod list;
 #[doc(inline)]
 pub use list::{ArrayList, ArrayListIter};
 
+/// `java.util.Map`
+mod map;
+#[doc(inline)]
+pub use map::Map;
+
 /// `java.util.Optional`
 mod optional;
 #[doc(inline)]
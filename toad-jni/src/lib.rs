@@ -125,6 +125,9 @@
 /// java language features and class shims
 pub mod java;
 
+/// Java-facing CoAP API (`dev.toad.*`) backed by the toad runtime
+pub mod dev;
+
 /// Global JVM handles
 pub mod global {
   use jni::{InitArgsBuilder, JavaVM};
@@ -215,6 +218,50 @@ mod test {
     assert!(o.is_empty(&mut e));
   }
 
+  #[test]
+  fn test_byte_array() {
+    let mut e = init();
+    let e = &mut e;
+
+    let bytes = vec![0u8, 1, 127, 128, 255];
+    let arr = bytes.downcast_ref(e);
+    assert_eq!(arr.upcast_to::<Vec<u8>>(e), bytes);
+    assert_eq!(arr.upcast_to::<Vec<i8>>(e), vec![0i8, 1, 127, -128, -1]);
+  }
+
+  #[test]
+  fn test_map() {
+    use std::collections::BTreeMap;
+
+    let mut e = init();
+    let e = &mut e;
+
+    let map = BTreeMap::from([("a".to_string(), 1i32), ("b".to_string(), 2i32)]);
+    let jmap = map.downcast_ref(e).upcast_to::<java::util::Map<String, i32>>(e);
+
+    assert_eq!(jmap.size(e), 2);
+    assert_eq!(jmap.get(e, &"b".to_string()), Some(2));
+    assert_eq!(jmap.get(e, &"c".to_string()), None);
+
+    jmap.put(e, "c".to_string(), 3);
+    let map = jmap.downcast(e).upcast_to::<BTreeMap<String, i32>>(e);
+    assert_eq!(map.get("c"), Some(&3));
+    assert_eq!(map.len(), 3);
+  }
+
+  #[test]
+  fn test_option_null() {
+    let mut e = init();
+    let e = &mut e;
+
+    let none = Option::<String>::None.downcast(e);
+    assert!(none.is_null());
+    assert_eq!(none.upcast_to::<Option<String>>(e), None);
+
+    let some = Some("foo".to_string()).downcast(e);
+    assert_eq!(some.upcast_to::<Option<String>>(e), Some("foo".to_string()));
+  }
+
   #[test]
   fn test_time() {
     init();
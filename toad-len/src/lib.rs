@@ -69,6 +69,137 @@ pub trait Len {
   fn is_full(&self) -> bool;
 }
 
+/// References have the same [`Len`] as the data they point to
+///
+/// ```
+/// use toad_len::Len;
+///
+/// let bytes: &[u8] = &[1, 2, 3];
+/// assert_eq!(Len::len(&bytes), 3);
+/// assert_eq!(Len::len(&"foo"), 3);
+/// ```
+impl<T: Len + ?Sized> Len for &T {
+  const CAPACITY: Option<usize> = T::CAPACITY;
+
+  fn len(&self) -> usize {
+    T::len(self)
+  }
+
+  fn is_full(&self) -> bool {
+    T::is_full(self)
+  }
+}
+
+/// Slices can't grow, so they are always full.
+///
+/// ```
+/// use toad_len::Len;
+///
+/// let bytes: &[u8] = &[1, 2, 3];
+/// assert_eq!(Len::len(bytes), 3);
+/// assert!(Len::is_full(bytes));
+/// ```
+impl<T> Len for [T] {
+  const CAPACITY: Option<usize> = None;
+
+  fn len(&self) -> usize {
+    <[T]>::len(self)
+  }
+
+  fn is_full(&self) -> bool {
+    true
+  }
+}
+
+/// Arrays can't grow, so they are always full.
+///
+/// ```
+/// use toad_len::Len;
+///
+/// assert_eq!(<[u8; 4] as Len>::CAPACITY, Some(4));
+/// assert_eq!(Len::len(&[0u8; 4]), 4);
+/// assert!(Len::is_full(&[0u8; 4]));
+/// ```
+impl<T, const N: usize> Len for [T; N] {
+  const CAPACITY: Option<usize> = Some(N);
+
+  fn len(&self) -> usize {
+    N
+  }
+
+  fn is_full(&self) -> bool {
+    true
+  }
+}
+
+/// The length of a string slice in bytes.
+/// String slices can't grow, so they are always full.
+///
+/// ```
+/// use toad_len::Len;
+///
+/// assert_eq!(Len::len("🐸"), 4);
+/// assert!(Len::is_full(""));
+/// ```
+impl Len for str {
+  const CAPACITY: Option<usize> = None;
+
+  fn len(&self) -> usize {
+    str::len(self)
+  }
+
+  fn is_full(&self) -> bool {
+    true
+  }
+}
+
+/// The length of a string in bytes
+///
+/// ```
+/// use toad_len::Len;
+///
+/// assert_eq!(Len::len(&String::from("foo")), 3);
+/// assert!(!Len::is_full(&String::from("foo")));
+/// ```
+#[cfg(feature = "alloc")]
+impl Len for std_alloc::string::String {
+  const CAPACITY: Option<usize> = None;
+
+  fn len(&self) -> usize {
+    self.len()
+  }
+
+  fn is_full(&self) -> bool {
+    false
+  }
+}
+
+/// An `Option` is a collection of zero or one elements
+///
+/// ```
+/// use toad_len::Len;
+///
+/// assert_eq!(<Option<u8> as Len>::CAPACITY, Some(1));
+/// assert_eq!(Len::len(&None::<u8>), 0);
+/// assert_eq!(Len::len(&Some(1u8)), 1);
+/// assert!(Len::is_full(&Some(1u8)));
+/// ```
+impl<T> Len for Option<T> {
+  const CAPACITY: Option<usize> = Some(1);
+
+  fn len(&self) -> usize {
+    if self.is_some() {
+      1
+    } else {
+      0
+    }
+  }
+
+  fn is_full(&self) -> bool {
+    self.is_some()
+  }
+}
+
 #[cfg(feature = "alloc")]
 impl<T> Len for std_alloc::vec::Vec<T> {
   const CAPACITY: Option<usize> = None;
@@ -25,6 +25,8 @@ extern crate alloc as std_alloc;
 
 use core::borrow::Borrow;
 use core::hash::Hash;
+#[cfg(feature = "alloc")]
+use core::marker::PhantomData;
 use core::ops::{Deref, DerefMut};
 use core::{iter, slice};
 #[cfg(feature = "std")]
@@ -87,6 +89,33 @@ pub trait Map<K: Ord + Eq + Hash, V>:
 
   /// See [`HashMap.iter_mut`]
   fn iter_mut(&mut self) -> IterMut<'_, K, V>;
+
+  /// Iterate over the entries of the map in ascending order of their keys.
+  ///
+  /// [`Map::iter`] yields entries in whatever order the backing collection
+  /// stores them in; this is ascending for [`BTreeMap`], insertion order for
+  /// arrays and arbitrary for [`HashMap`].
+  ///
+  /// When the entries aren't already stored in ascending order, they are sorted
+  /// when iteration starts. With the `alloc` feature, references to the entries are
+  /// collected and sorted (`O(n log n)`); without it, no allocation is made
+  /// at the cost of a pass over the map per entry yielded (`O(n²)`).
+  ///
+  /// ```
+  /// use std::collections::HashMap;
+  ///
+  /// use toad_map::Map;
+  ///
+  /// let map = HashMap::from([(3, "c"), (1, "a"), (2, "b")]);
+  /// let kvs = map.iter_sorted().collect::<Vec<_>>();
+  ///
+  /// assert_eq!(kvs, vec![(&1, &"a"), (&2, &"b"), (&3, &"c")]);
+  /// ```
+  fn iter_sorted(&self) -> SortedIter<'_, Self, K, V>
+    where Self: Sized
+  {
+    SortedIter::new(self)
+  }
 }
 
 #[cfg(feature = "alloc")]
@@ -443,6 +472,85 @@ impl<'a, K: Eq + Hash, V> Iterator for IterMut<'a, K, V> {
   }
 }
 
+/// An iterator over the entries of a `Map` in ascending order of their keys.
+///
+/// This `struct` is created by the [`iter_sorted`] method on [`Map`].
+/// See its documentation for more.
+///
+/// [`iter_sorted`]: Map::iter_sorted
+#[derive(Debug)]
+pub struct SortedIter<'a, M, K: Eq + Hash, V> {
+  sorting: Sorting<'a, M, K, V>,
+}
+
+#[derive(Debug)]
+enum Sorting<'a, M, K: Eq + Hash, V> {
+  /// The map's entries are already in ascending order
+  Ordered(Iter<'a, K, V>),
+  /// The map's entries, sorted when iteration started
+  #[cfg(feature = "alloc")]
+  Sorted(std_alloc::vec::IntoIter<(&'a K, &'a V)>, PhantomData<&'a M>),
+  /// Find the least key greater than the last one yielded,
+  /// with a pass over the map
+  #[cfg(not(feature = "alloc"))]
+  Scanning { map: &'a M, last: Option<&'a K> },
+}
+
+impl<'a, M, K, V> SortedIter<'a, M, K, V>
+  where M: Map<K, V>,
+        K: Ord + Eq + Hash
+{
+  fn new(map: &'a M) -> Self {
+    let mut keys = map.iter().map(|(k, _)| k);
+    let ascending = match keys.next() {
+      | Some(first) => keys.try_fold(first, |prev, k| if prev < k { Some(k) } else { None })
+                           .is_some(),
+      | None => true,
+    };
+
+    if ascending {
+      return Self { sorting: Sorting::Ordered(map.iter()) };
+    }
+
+    #[cfg(feature = "alloc")]
+    {
+      let mut kvs = map.iter().collect::<std_alloc::vec::Vec<_>>();
+      kvs.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+      Self { sorting: Sorting::Sorted(kvs.into_iter(), PhantomData) }
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    {
+      Self { sorting: Sorting::Scanning { map, last: None } }
+    }
+  }
+}
+
+impl<'a, M, K, V> Iterator for SortedIter<'a, M, K, V>
+  where M: Map<K, V>,
+        K: Ord + Eq + Hash
+{
+  type Item = (&'a K, &'a V);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    match &mut self.sorting {
+      | Sorting::Ordered(ordered) => ordered.next(),
+      #[cfg(feature = "alloc")]
+      | Sorting::Sorted(sorted, _) => sorted.next(),
+      #[cfg(not(feature = "alloc"))]
+      | Sorting::Scanning { map, last } => {
+        let (map, prev): (&'a M, _) = (*map, *last);
+        let next = map.iter()
+                      .filter(|(k, _)| prev.map(|prev| *k > prev).unwrap_or(true))
+                      .min_by(|(a, _), (b, _)| a.cmp(b))?;
+
+        *last = Some(next.0);
+        Some(next)
+      },
+    }
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -595,4 +703,23 @@ mod tests {
 
     each_impl!(test_iter_mut);
   }
+
+  #[test]
+  fn iter_sorted() {
+    fn test_iter_sorted<M: Map<String, String>>(mut map: M) {
+      map.insert("c".into(), "c".into()).unwrap();
+      map.insert("a".into(), "a".into()).unwrap();
+      map.insert("b".into(), "b".into()).unwrap();
+
+      let kvs = map.iter_sorted().collect::<Vec<_>>();
+
+      assert_eq!(kvs,
+                 vec![(&"a".into(), &"a".into()),
+                      (&"b".into(), &"b".into()),
+                      (&"c".into(), &"c".into()),
+                      (&"foo".into(), &"bar".into()),]);
+    }
+
+    each_impl!(test_iter_sorted);
+  }
 }
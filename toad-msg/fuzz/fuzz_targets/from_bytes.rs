@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use toad_msg::alloc::Message;
+use toad_msg::{TryFromBytes, TryIntoBytes};
+
+// Parsing arbitrary bytes must never panic, and any message
+// that parses successfully must re-serialize to bytes that
+// parse to the same message.
+fuzz_target!(|bytes: &[u8]| {
+  if let Ok(msg) = Message::try_from_bytes(bytes) {
+    let bytes = msg.clone()
+                   .try_into_bytes::<Vec<u8>>()
+                   .expect("parsed message should serialize");
+    let reparsed = Message::try_from_bytes(&bytes).expect("serialized message should parse");
+
+    assert_eq!(msg, reparsed);
+    assert_eq!(msg.ty, reparsed.ty);
+  }
+});
@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use toad_msg::alloc::Message;
+use toad_msg::{TryFromBytes, TryIntoBytes};
+
+// Structured messages must survive serialization
+// and parsing unchanged.
+fuzz_target!(|msg: Message| {
+  let bytes = msg.clone()
+                 .try_into_bytes::<Vec<u8>>()
+                 .expect("message should serialize");
+  let parsed = Message::try_from_bytes(&bytes).expect("serialized message should parse");
+
+  assert_eq!(msg, parsed);
+  assert_eq!(msg.ty, parsed.ty);
+});
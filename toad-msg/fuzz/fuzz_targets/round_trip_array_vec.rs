@@ -0,0 +1,27 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tinyvec::ArrayVec;
+use toad_msg::{OptNumber, OptValue, TryFromBytes, TryIntoBytes};
+
+type Message = toad_msg::Message<ArrayVec<[u8; 128]>,
+                                 ArrayVec<[(OptNumber,
+                                            ArrayVec<[OptValue<ArrayVec<[u8; 32]>>; 4]>);
+                                           16]>>;
+
+// Same as `round_trip`, but using fixed-capacity
+// collections as a no_std platform would.
+fuzz_target!(|msg: Message| {
+  if let Ok(bytes) = msg.clone().try_into_bytes::<ArrayVec<[u8; 1152]>>() {
+    let mut parsed = Message::try_from_bytes(&bytes).expect("serialized message should parse");
+    assert_eq!(msg.ty, parsed.ty);
+
+    // ArrayVec maps preserve insertion order, while parsed
+    // options will always be in ascending order.
+    let mut msg = msg;
+    msg.opts.sort_by_key(|(num, _)| *num);
+    parsed.opts.sort_by_key(|(num, _)| *num);
+
+    assert_eq!(msg, parsed);
+  }
+});
@@ -0,0 +1,123 @@
+//! [`arbitrary::Arbitrary`] implementations for message structs,
+//! used to generate structured inputs when fuzzing.
+//!
+//! Generated values are always representable on the wire; e.g. [`Code`]s
+//! are limited to 3-bit classes and 5-bit details and [`Token`]s
+//! are at most 8 bytes long. This means that a generated [`Message`]
+//! is expected to survive a round-trip through [`TryIntoBytes`](crate::TryIntoBytes)
+//! and [`TryFromBytes`](crate::TryFromBytes) unchanged.
+//!
+//! Collections with a fixed capacity (e.g. [`tinyvec::ArrayVec`]) will never
+//! be generated with more elements than they can hold.
+
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+use toad_array::Array;
+use toad_len::Len;
+use toad_map::Map;
+
+use crate::{Code, Id, Message, Opt, OptDelta, OptNumber, OptValue, OptionMap, Payload, Token,
+            Type, Version};
+
+fn arbitrary_array<'a, A, F>(u: &mut Unstructured<'a>, mut f: F) -> Result<A>
+  where A: Array,
+        A::Item: Arbitrary<'a>,
+        F: FnMut(&mut Unstructured<'a>) -> Result<A::Item>
+{
+  let len = u.arbitrary_len::<A::Item>()?;
+  let len = A::CAPACITY.map(|max| len.min(max)).unwrap_or(len);
+
+  (0..len).map(|_| f(u)).collect()
+}
+
+impl<'a> Arbitrary<'a> for Code {
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    Ok(Code::new(u.int_in_range(0..=0b111)?, u.int_in_range(0..=0b11111)?))
+  }
+}
+
+impl<'a> Arbitrary<'a> for Id {
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    u16::arbitrary(u).map(Id)
+  }
+}
+
+impl<'a> Arbitrary<'a> for Type {
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    Ok(*u.choose(&[Type::Con, Type::Non, Type::Ack, Type::Reset])?)
+  }
+}
+
+impl<'a> Arbitrary<'a> for Version {
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    u.int_in_range(0..=0b11).map(Version)
+  }
+}
+
+impl<'a> Arbitrary<'a> for Token {
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    arbitrary_array(u, u8::arbitrary).map(Token)
+  }
+}
+
+impl<'a> Arbitrary<'a> for OptNumber {
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    u16::arbitrary(u).map(|n| OptNumber(n as u32))
+  }
+}
+
+impl<'a> Arbitrary<'a> for OptDelta {
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    u16::arbitrary(u).map(OptDelta)
+  }
+}
+
+impl<'a, C> Arbitrary<'a> for OptValue<C> where C: Array<Item = u8>
+{
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    arbitrary_array(u, u8::arbitrary).map(OptValue)
+  }
+}
+
+impl<'a, C> Arbitrary<'a> for Opt<C> where C: Array<Item = u8>
+{
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    Ok(Opt { delta: OptDelta::arbitrary(u)?,
+             value: OptValue::arbitrary(u)? })
+  }
+}
+
+impl<'a, C> Arbitrary<'a> for Payload<C> where C: Array<Item = u8>
+{
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    arbitrary_array(u, u8::arbitrary).map(Payload)
+  }
+}
+
+impl<'a, C, O> Arbitrary<'a> for Message<C, O>
+  where C: Array<Item = u8>,
+        O: OptionMap
+{
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    let mut opts = O::default();
+
+    for _ in 0..u.arbitrary_len::<(OptNumber, OptValue<O::OptValue>)>()? {
+      let num = OptNumber::arbitrary(u)?;
+      let values = arbitrary_array::<O::OptValues, _>(u, OptValue::arbitrary)?;
+
+      // options with no values are not representable on the wire
+      if values.is_empty() || opts.has(&num) || opts.is_full() {
+        continue;
+      }
+
+      opts.insert(num, values).ok();
+    }
+
+    Ok(Message { id: Id::arbitrary(u)?,
+                 ty: Type::arbitrary(u)?,
+                 ver: Version::arbitrary(u)?,
+                 token: Token::arbitrary(u)?,
+                 code: Code::arbitrary(u)?,
+                 opts,
+                 payload: Payload::arbitrary(u)? })
+  }
+}
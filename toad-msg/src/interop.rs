@@ -0,0 +1,243 @@
+//! Conversions between [`Message`] and [`coap_lite::Packet`]
+//!
+//! Allows users migrating from `coap_lite` to switch to `toad_msg` incrementally.
+//!
+//! ```
+//! use toad_msg::alloc::Message;
+//! use toad_msg::{Code, Id, Token, Type};
+//!
+//! let msg = Message::new(Type::Con, Code::GET, Id(1), Token(Default::default()));
+//!
+//! let packet = coap_lite::Packet::try_from(msg.clone()).unwrap();
+//! assert_eq!(packet.header.message_id, 1);
+//!
+//! let msg_again = Message::try_from(packet).unwrap();
+//! assert_eq!(msg, msg_again);
+//! ```
+
+use std_alloc::vec::Vec;
+
+use coap_lite::{CoapOption, MessageClass, MessageType, Packet};
+use toad_array::Array;
+use toad_len::Len;
+use toad_map::{InsertError, Map};
+
+use crate::{Code, Id, Message, OptNumber, OptValue, OptionMap, Payload, Token, Type, Version};
+
+/// Errors that may be encountered when converting between
+/// [`Message`] and [`coap_lite::Packet`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Error {
+  /// The option number is larger than `coap_lite` can represent (`u16::MAX`)
+  OptNumberTooLarge(OptNumber),
+  /// The packet's token was longer than 8 bytes
+  TokenTooLong(usize),
+  /// A collection in the target [`Message`] type did not have
+  /// enough capacity for the packet's token, options or payload
+  CapacityExhausted,
+}
+
+impl From<Type> for MessageType {
+  fn from(ty: Type) -> Self {
+    match ty {
+      | Type::Con => MessageType::Confirmable,
+      | Type::Non => MessageType::NonConfirmable,
+      | Type::Ack => MessageType::Acknowledgement,
+      | Type::Reset => MessageType::Reset,
+    }
+  }
+}
+
+impl From<MessageType> for Type {
+  fn from(ty: MessageType) -> Self {
+    match ty {
+      | MessageType::Confirmable => Type::Con,
+      | MessageType::NonConfirmable => Type::Non,
+      | MessageType::Acknowledgement => Type::Ack,
+      | MessageType::Reset => Type::Reset,
+    }
+  }
+}
+
+impl From<Code> for MessageClass {
+  fn from(code: Code) -> Self {
+    MessageClass::from(u8::from(code))
+  }
+}
+
+impl From<MessageClass> for Code {
+  fn from(class: MessageClass) -> Self {
+    Code::from(u8::from(class))
+  }
+}
+
+impl<C, O> TryFrom<Message<C, O>> for Packet
+  where C: Array<Item = u8>,
+        O: OptionMap
+{
+  type Error = Error;
+
+  fn try_from(msg: Message<C, O>) -> Result<Self, Self::Error> {
+    let mut packet = Packet::new();
+
+    packet.header.set_version(msg.ver.0);
+    packet.header.set_type(msg.ty.into());
+    packet.header.code = msg.code.into();
+    packet.header.message_id = msg.id.0;
+    packet.set_token(msg.token.0.to_vec());
+
+    for (num, values) in msg.opts.iter() {
+      let num = u16::try_from(num.0).map_err(|_| Error::OptNumberTooLarge(*num))?;
+
+      for value in values.iter() {
+        packet.add_option(CoapOption::from(num), value.0.to_vec());
+      }
+    }
+
+    packet.payload = msg.payload.0.into_iter().collect();
+
+    Ok(packet)
+  }
+}
+
+impl<C, O> TryFrom<Packet> for Message<C, O>
+  where C: Array<Item = u8>,
+        O: OptionMap
+{
+  type Error = Error;
+
+  fn try_from(packet: Packet) -> Result<Self, Self::Error> {
+    fn collect<A: Array>(items: impl ExactSizeIterator<Item = A::Item>) -> Result<A, Error> {
+      match A::CAPACITY {
+        | Some(max) if items.len() > max => Err(Error::CapacityExhausted),
+        | _ => Ok(items.collect()),
+      }
+    }
+
+    let token = packet.get_token();
+    if token.len() > 8 {
+      return Err(Error::TokenTooLong(token.len()));
+    }
+
+    let mut opts = O::default();
+    for (num, values) in packet.options() {
+      let values = values.iter()
+                         .map(|v| collect(v.iter().copied()).map(OptValue))
+                         .collect::<Result<Vec<_>, _>>()?;
+      let values = collect::<O::OptValues>(values.into_iter())?;
+
+      match opts.insert(OptNumber(*num as u32), values) {
+        | Ok(()) => (),
+        | Err(InsertError::Exists(_)) => unreachable!("coap_lite options are keyed by number"),
+        | Err(InsertError::CapacityExhausted) => return Err(Error::CapacityExhausted),
+      }
+    }
+
+    Ok(Message { id: Id(packet.header.message_id),
+                 ty: packet.header.get_type().into(),
+                 ver: Version(packet.header.get_version()),
+                 token: Token(token.iter().copied().collect()),
+                 code: packet.header.code.into(),
+                 opts,
+                 payload: Payload(collect(packet.payload.iter().copied())?) })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std_alloc::collections::BTreeMap;
+
+  use proptest::prelude::*;
+
+  use super::*;
+  use crate::alloc::Message;
+  use crate::{TryFromBytes, TryIntoBytes};
+
+  fn message() -> impl Strategy<Value = Message> {
+    let bytes = |max: usize| prop::collection::vec(any::<u8>(), 0..=max);
+    let opts =
+      prop::collection::btree_map(any::<u16>(), prop::collection::vec(bytes(300), 1..=3), 0..=8);
+
+    (any::<u16>(),
+     prop::sample::select(vec![Type::Con, Type::Non, Type::Ack, Type::Reset]),
+     bytes(8),
+     0u8..=0b111,
+     0u8..=0b11111,
+     opts,
+     bytes(256)).prop_map(|(id, ty, token, class, detail, opts, payload)| {
+                  let opts = opts.into_iter()
+                                 .map(|(num, vals)| {
+                                   (OptNumber(num as u32),
+                                    vals.into_iter().map(OptValue).collect::<Vec<_>>())
+                                 })
+                                 .collect::<BTreeMap<_, _>>();
+
+                  Message { id: Id(id),
+                            ty,
+                            ver: Version(1),
+                            token: Token(token.into_iter().collect()),
+                            code: Code::new(class, detail),
+                            opts,
+                            payload: Payload(payload) }
+                })
+                .prop_filter("coap_lite rejects packets larger than 1280 bytes",
+                             |msg| msg.len() <= 1280)
+  }
+
+  proptest! {
+    #[test]
+    fn toad_and_coap_lite_serialize_identically(msg in message()) {
+      let toad_bytes = msg.clone().try_into_bytes::<Vec<u8>>().unwrap();
+      let coap_lite_bytes = Packet::try_from(msg).unwrap().to_bytes().unwrap();
+
+      prop_assert_eq!(toad_bytes, coap_lite_bytes);
+    }
+
+    #[test]
+    fn coap_lite_parses_toad_bytes(msg in message()) {
+      let bytes = msg.clone().try_into_bytes::<Vec<u8>>().unwrap();
+      let parsed = Message::try_from(Packet::from_bytes(&bytes).unwrap()).unwrap();
+
+      prop_assert_eq!(parsed.ty, msg.ty);
+      prop_assert_eq!(parsed, msg);
+    }
+
+    #[test]
+    fn toad_parses_coap_lite_bytes(msg in message()) {
+      let bytes = Packet::try_from(msg.clone()).unwrap().to_bytes().unwrap();
+      let parsed = Message::try_from_bytes(&bytes).unwrap();
+
+      prop_assert_eq!(parsed.ty, msg.ty);
+      prop_assert_eq!(parsed, msg);
+    }
+
+    #[test]
+    fn conversion_round_trips(msg in message()) {
+      let converted = Message::try_from(Packet::try_from(msg.clone()).unwrap()).unwrap();
+
+      prop_assert_eq!(converted.ty, msg.ty);
+      prop_assert_eq!(converted, msg);
+    }
+  }
+
+  #[test]
+  fn token_too_long() {
+    let mut packet = Packet::new();
+    packet.set_token(vec![0; 9]);
+
+    assert_eq!(Message::try_from(packet), Err(Error::TokenTooLong(9)));
+  }
+
+  #[test]
+  fn capacity_exhausted() {
+    type Small = crate::Message<tinyvec::ArrayVec<[u8; 1]>,
+                                tinyvec::ArrayVec<[(OptNumber,
+                                                    tinyvec::ArrayVec<[OptValue<tinyvec::ArrayVec<[u8; 1]>>; 1]>);
+                                                   1]>>;
+
+    let mut packet = Packet::new();
+    packet.payload = vec![1, 2];
+
+    assert_eq!(Small::try_from(packet).map(|_| ()), Err(Error::CapacityExhausted));
+  }
+}
@@ -84,6 +84,19 @@ extern crate alloc as std_alloc;
 #[doc(hidden)]
 pub mod from_bytes;
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(any(docsrs, feature = "docs"), doc(cfg(feature = "arbitrary")))]
+mod arbitrary;
+
+#[cfg(feature = "serde")]
+#[cfg_attr(any(docsrs, feature = "docs"), doc(cfg(feature = "serde")))]
+mod serde;
+
+/// Conversions to and from [`coap_lite`] types
+#[cfg(feature = "interop")]
+#[cfg_attr(any(docsrs, feature = "docs"), doc(cfg(feature = "interop")))]
+pub mod interop;
+
 #[allow(missing_docs)]
 pub mod cache_key;
 
@@ -0,0 +1,303 @@
+use toad_array::{AppendCopy, Array};
+use toad_len::Len;
+
+use super::opt::known::observe;
+use super::{Code, ContentFormat, Id, Message, MessageOptions, OptNumber, OptValue, OptionMap,
+            SetOptionError, Token, Type};
+
+/// An error encountered by [`MessageBuilder::build`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BuildError<OV, OVs> {
+  /// Setting an option failed
+  Option(SetOptionError<OV, OVs>),
+  /// The payload did not fit in the message's payload collection
+  PayloadTooLong {
+    /// Length of the payload that was provided
+    len: usize,
+    /// Maximum payload length supported by the message
+    capacity: usize,
+  },
+}
+
+impl<OV, OVs> From<SetOptionError<OV, OVs>> for BuildError<OV, OVs> {
+  fn from(e: SetOptionError<OV, OVs>) -> Self {
+    Self::Option(e)
+  }
+}
+
+/// Error yielded by [`MessageBuilder::build`] for `Message<P, O>`
+pub type MessageBuildError<O> =
+  BuildError<OptValue<<O as OptionMap>::OptValue>, <O as OptionMap>::OptValues>;
+
+/// Fluent API for constructing a [`Message`]
+///
+/// Created with [`Message::builder`].
+///
+/// Every method is infallible; the first error encountered
+/// (e.g. running out of space for options in a `no_std` message)
+/// is stored and yielded by [`MessageBuilder::build`], and any
+/// methods invoked after that point have no effect.
+///
+/// ```
+/// use toad_msg::alloc::Message;
+/// use toad_msg::ContentFormat::Json;
+/// use toad_msg::{Code, MessageOptions, Type};
+///
+/// let msg = Message::builder().con()
+///                             .get()
+///                             .path("a/b")
+///                             .query("x=1")
+///                             .accept(Json)
+///                             .payload(b"hello")
+///                             .build()
+///                             .unwrap();
+///
+/// assert_eq!(msg.ty, Type::Con);
+/// assert_eq!(msg.code, Code::GET);
+/// assert_eq!(msg.path::<Vec<_>>(), Ok(vec!["a", "b"]));
+/// assert_eq!(msg.query::<Vec<_>>(), Ok(vec!["x=1"]));
+/// assert_eq!(msg.accept(), Some(Json));
+/// assert_eq!(msg.payload.as_bytes(), b"hello");
+/// ```
+pub struct MessageBuilder<P, O>
+  where O: OptionMap
+{
+  msg: Message<P, O>,
+  error: Option<MessageBuildError<O>>,
+}
+
+impl<P, O> core::fmt::Debug for MessageBuilder<P, O>
+  where P: core::fmt::Debug,
+        O: OptionMap + core::fmt::Debug,
+        O::OptValue: core::fmt::Debug,
+        O::OptValues: core::fmt::Debug
+{
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("MessageBuilder")
+     .field("msg", &self.msg)
+     .field("error", &self.error)
+     .finish()
+  }
+}
+
+impl<P, O> Message<P, O>
+  where P: Array<Item = u8> + AppendCopy<u8>,
+        O: OptionMap
+{
+  /// Create a [`MessageBuilder`]
+  ///
+  /// The message defaults to an empty CON message with
+  /// [`Id`] `0` and an empty [`Token`].
+  pub fn builder() -> MessageBuilder<P, O> {
+    MessageBuilder { msg: Message::new(Type::Con, Code::EMPTY, Id(0), Token(Default::default())),
+                     error: None }
+  }
+}
+
+impl<P, O> MessageBuilder<P, O>
+  where P: Array<Item = u8> + AppendCopy<u8>,
+        O: OptionMap
+{
+  fn try_update<F>(mut self, f: F) -> Self
+    where F: FnOnce(&mut Message<P, O>) -> Result<(), MessageBuildError<O>>
+  {
+    if self.error.is_none() {
+      self.error = f(&mut self.msg).err();
+    }
+
+    self
+  }
+
+  fn update<F>(mut self, f: F) -> Self
+    where F: FnOnce(&mut Message<P, O>)
+  {
+    f(&mut self.msg);
+    self
+  }
+
+  /// Set the message [`Type`]
+  pub fn ty(self, ty: Type) -> Self {
+    self.update(|m| m.ty = ty)
+  }
+
+  /// Make this a [`Type::Con`] message
+  pub fn con(self) -> Self {
+    self.ty(Type::Con)
+  }
+
+  /// Make this a [`Type::Non`] message
+  pub fn non(self) -> Self {
+    self.ty(Type::Non)
+  }
+
+  /// Make this a [`Type::Ack`] message
+  pub fn ack(self) -> Self {
+    self.ty(Type::Ack)
+  }
+
+  /// Make this a [`Type::Reset`] message
+  pub fn reset(self) -> Self {
+    self.ty(Type::Reset)
+  }
+
+  /// Set the message [`Code`]
+  pub fn code(self, code: Code) -> Self {
+    self.update(|m| m.code = code)
+  }
+
+  /// Make this a [`Code::GET`] request
+  pub fn get(self) -> Self {
+    self.code(Code::GET)
+  }
+
+  /// Make this a [`Code::POST`] request
+  pub fn post(self) -> Self {
+    self.code(Code::POST)
+  }
+
+  /// Make this a [`Code::PUT`] request
+  pub fn put(self) -> Self {
+    self.code(Code::PUT)
+  }
+
+  /// Make this a [`Code::DELETE`] request
+  pub fn delete(self) -> Self {
+    self.code(Code::DELETE)
+  }
+
+  /// Set the message [`Id`]
+  pub fn id(self, id: Id) -> Self {
+    self.update(|m| m.id = id)
+  }
+
+  /// Set the message [`Token`]
+  pub fn token(self, token: Token) -> Self {
+    self.update(|m| m.token = token)
+  }
+
+  /// Add a value for an arbitrary option, alongside any existing values.
+  ///
+  /// See [`MessageOptions::add`]
+  pub fn option(self, n: OptNumber, v: OptValue<O::OptValue>) -> Self {
+    self.try_update(|m| MessageOptions::add(m, n, v).map_err(BuildError::from))
+  }
+
+  /// See [`MessageOptions::set_host`]
+  pub fn host<S: AsRef<str>>(self, host: S) -> Self {
+    self.try_update(|m| m.set_host(host).map_err(BuildError::from))
+  }
+
+  /// See [`MessageOptions::set_port`]
+  pub fn port(self, port: u16) -> Self {
+    self.try_update(|m| m.set_port(port).map_err(BuildError::from))
+  }
+
+  /// Add segments to the request path, see [`MessageOptions::set_path`]
+  pub fn path<S: AsRef<str>>(self, path: S) -> Self {
+    self.try_update(|m| m.set_path(path).map_err(BuildError::from))
+  }
+
+  /// See [`MessageOptions::add_query`]
+  pub fn query<S: AsRef<str>>(self, query: S) -> Self {
+    self.try_update(|m| m.add_query(query).map_err(BuildError::from))
+  }
+
+  /// See [`MessageOptions::set_content_format`]
+  pub fn content_format(self, format: ContentFormat) -> Self {
+    self.try_update(|m| m.set_content_format(format).map_err(BuildError::from))
+  }
+
+  /// See [`MessageOptions::set_accept`]
+  pub fn accept(self, format: ContentFormat) -> Self {
+    self.try_update(|m| m.set_accept(format).map_err(BuildError::from))
+  }
+
+  /// See [`MessageOptions::set_observe`]
+  pub fn observe(self, action: observe::Action) -> Self {
+    self.try_update(|m| m.set_observe(action).map_err(BuildError::from))
+  }
+
+  /// See [`MessageOptions::set_max_age`]
+  pub fn max_age(self, max_age_seconds: u32) -> Self {
+    self.try_update(|m| m.set_max_age(max_age_seconds).map_err(BuildError::from))
+  }
+
+  /// Set the message payload, discarding any existing payload
+  pub fn payload<B: AsRef<[u8]>>(self, payload: B) -> Self {
+    self.try_update(|m| {
+          let payload = payload.as_ref();
+
+          match P::CAPACITY {
+            | Some(capacity) if payload.len() > capacity => {
+              Err(BuildError::PayloadTooLong { len: payload.len(),
+                                               capacity })
+            },
+            | _ => {
+              let mut bytes = P::default();
+              bytes.append_copy(payload);
+              m.payload.0 = bytes;
+              Ok(())
+            },
+          }
+        })
+  }
+
+  /// Finish building the message, yielding the first
+  /// error encountered if there was one.
+  pub fn build(self) -> Result<Message<P, O>, MessageBuildError<O>> {
+    match self.error {
+      | Some(e) => Err(e),
+      | None => Ok(self.msg),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use tinyvec::ArrayVec;
+
+  use super::*;
+  use crate::alloc;
+
+  type TinyMessage = Message<ArrayVec<[u8; 4]>,
+                             ArrayVec<[(OptNumber, ArrayVec<[OptValue<ArrayVec<[u8; 8]>>; 1]>); 2]>>;
+
+  #[test]
+  fn defaults() {
+    let msg = alloc::Message::builder().build().unwrap();
+    assert_eq!(msg,
+               alloc::Message::new(Type::Con, Code::EMPTY, Id(0), Token(Default::default())));
+  }
+
+  #[test]
+  fn payload_too_long() {
+    let err = TinyMessage::builder().payload(b"12345").build().unwrap_err();
+    assert_eq!(err, BuildError::PayloadTooLong { len: 5, capacity: 4 });
+  }
+
+  #[test]
+  fn first_error_is_yielded() {
+    // Uri-Path may only be repeated once in a TinyMessage,
+    // so the second segment fails
+    let err = TinyMessage::builder().path("a/b")
+                                    .payload(b"12345")
+                                    .build()
+                                    .unwrap_err();
+    assert!(matches!(err, BuildError::Option(SetOptionError::RepeatedTooManyTimes(_))));
+  }
+
+  #[test]
+  fn no_std() {
+    let msg = TinyMessage::builder().non()
+                                    .post()
+                                    .path("a")
+                                    .payload(b"hi")
+                                    .build()
+                                    .unwrap();
+
+    assert_eq!(msg.ty, Type::Non);
+    assert_eq!(msg.code, Code::POST);
+    assert_eq!(msg.path::<ArrayVec<[_; 1]>>().unwrap().as_slice(), &["a"]);
+    assert_eq!(msg.payload.0.as_slice(), b"hi");
+  }
+}
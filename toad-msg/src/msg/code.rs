@@ -1,5 +1,3 @@
-#[cfg(feature = "alloc")]
-use std_alloc::string::{String, ToString};
 use toad_macros::rfc_7252_doc;
 
 #[doc = rfc_7252_doc!("12.1")]
@@ -66,7 +64,7 @@ impl Code {
   ///
   /// This is to avoid unnecessary heap allocation,
   /// you can create a `String` with `FromIterator::<String>::from_iter`,
-  /// or if the `alloc` feature of `toad` is enabled there is a `ToString` implementation provided for Code.
+  /// or use the [`Display`](core::fmt::Display) implementation provided for Code.
   /// ```
   /// use toad_msg::Code;
   ///
@@ -113,20 +111,187 @@ impl Code {
   pub const GET: Self = Self::new(0, 1);
 
   #[doc = rfc_7252_doc!("5.8.2")]
-  pub const POST: Self = Self::new(0, 3);
+  pub const POST: Self = Self::new(0, 2);
 
   #[doc = rfc_7252_doc!("5.8.3")]
-  pub const PUT: Self = Self::new(0, 2);
+  pub const PUT: Self = Self::new(0, 3);
 
   #[doc = rfc_7252_doc!("5.8.4")]
   pub const DELETE: Self = Self::new(0, 4);
 }
 
-#[cfg(feature = "alloc")]
-#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
-impl ToString for Code {
-  fn to_string(&self) -> String {
-    String::from_iter(self.to_human())
+macro_rules! known_codes {
+  ($($(#[$meta:meta])* $name:ident = ($class:literal, $detail:literal) $str:literal,)*) => {
+    /// Codes registered in the
+    /// [CoAP Method Codes](https://www.iana.org/assignments/core-parameters/core-parameters.xhtml#method-codes)
+    /// and [CoAP Response Codes](https://www.iana.org/assignments/core-parameters/core-parameters.xhtml#response-codes)
+    /// registries, allowing codes to be matched on exhaustively.
+    ///
+    /// Obtained with [`Code::known`].
+    ///
+    /// ```
+    /// use toad_msg::code::Known;
+    /// use toad_msg::Code;
+    ///
+    /// assert_eq!(Code::new(2, 05).known(), Known::Content);
+    /// assert_eq!(Code::new(2, 05).known().to_string(), "2.05 Content");
+    ///
+    /// assert_eq!(Code::new(2, 06).known(), Known::Unknown(Code::new(2, 06)));
+    /// assert_eq!(Code::new(2, 06).known().to_string(), "2.06");
+    ///
+    /// assert_eq!(Code::from(Known::NotFound), Code::new(4, 04));
+    /// ```
+    #[derive(Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Debug)]
+    #[non_exhaustive]
+    pub enum Known {
+      $($(#[$meta])* $name,)*
+      /// A code not included in this enum.
+      ///
+      /// Note that this will never contain a code
+      /// that has a variant, when created with [`Code::known`].
+      Unknown(Code),
+    }
+
+    impl Known {
+      /// Get the [`Code`] for this variant
+      pub const fn code(&self) -> Code {
+        match self {
+          $(Self::$name => Code::new($class, $detail),)*
+          Self::Unknown(code) => *code,
+        }
+      }
+
+      /// Get the registered name for this code, e.g. `"Content"` for 2.05
+      /// and `"GET"` for 0.01.
+      pub const fn name(&self) -> Option<&'static str> {
+        match self {
+          $(Self::$name => Some($str),)*
+          Self::Unknown(_) => None,
+        }
+      }
+    }
+
+    impl Code {
+      /// Convert this code to a [`Known`] code, for use in exhaustive matching.
+      ///
+      /// ```
+      /// use toad_msg::code::Known;
+      /// use toad_msg::Code;
+      ///
+      /// match Code::new(4, 04).known() {
+      ///   | Known::NotFound => (),
+      ///   | other => panic!("{}", other),
+      /// }
+      /// ```
+      pub const fn known(&self) -> Known {
+        match (self.class, self.detail) {
+          $(($class, $detail) => Known::$name,)*
+          _ => Known::Unknown(*self),
+        }
+      }
+    }
+  };
+}
+
+known_codes! {
+  /// 0.00 EMPTY
+  Empty = (0, 0) "EMPTY",
+  /// 0.01 GET
+  Get = (0, 1) "GET",
+  /// 0.02 POST
+  Post = (0, 2) "POST",
+  /// 0.03 PUT
+  Put = (0, 3) "PUT",
+  /// 0.04 DELETE
+  Delete = (0, 4) "DELETE",
+  /// 0.05 FETCH ([RFC8132](https://www.rfc-editor.org/rfc/rfc8132))
+  Fetch = (0, 5) "FETCH",
+  /// 0.06 PATCH ([RFC8132](https://www.rfc-editor.org/rfc/rfc8132))
+  Patch = (0, 6) "PATCH",
+  /// 0.07 iPATCH ([RFC8132](https://www.rfc-editor.org/rfc/rfc8132))
+  IPatch = (0, 7) "iPATCH",
+  /// 2.01 Created
+  Created = (2, 1) "Created",
+  /// 2.02 Deleted
+  Deleted = (2, 2) "Deleted",
+  /// 2.03 Valid
+  Valid = (2, 3) "Valid",
+  /// 2.04 Changed
+  Changed = (2, 4) "Changed",
+  /// 2.05 Content
+  Content = (2, 5) "Content",
+  /// 2.31 Continue ([RFC7959](https://www.rfc-editor.org/rfc/rfc7959))
+  Continue = (2, 31) "Continue",
+  /// 4.00 Bad Request
+  BadRequest = (4, 0) "Bad Request",
+  /// 4.01 Unauthorized
+  Unauthorized = (4, 1) "Unauthorized",
+  /// 4.02 Bad Option
+  BadOption = (4, 2) "Bad Option",
+  /// 4.03 Forbidden
+  Forbidden = (4, 3) "Forbidden",
+  /// 4.04 Not Found
+  NotFound = (4, 4) "Not Found",
+  /// 4.05 Method Not Allowed
+  MethodNotAllowed = (4, 5) "Method Not Allowed",
+  /// 4.06 Not Acceptable
+  NotAcceptable = (4, 6) "Not Acceptable",
+  /// 4.08 Request Entity Incomplete ([RFC7959](https://www.rfc-editor.org/rfc/rfc7959))
+  RequestEntityIncomplete = (4, 8) "Request Entity Incomplete",
+  /// 4.09 Conflict ([RFC8132](https://www.rfc-editor.org/rfc/rfc8132))
+  Conflict = (4, 9) "Conflict",
+  /// 4.12 Precondition Failed
+  PreconditionFailed = (4, 12) "Precondition Failed",
+  /// 4.13 Request Entity Too Large
+  RequestEntityTooLarge = (4, 13) "Request Entity Too Large",
+  /// 4.15 Unsupported Content-Format
+  UnsupportedContentFormat = (4, 15) "Unsupported Content-Format",
+  /// 4.22 Unprocessable Entity ([RFC8132](https://www.rfc-editor.org/rfc/rfc8132))
+  UnprocessableEntity = (4, 22) "Unprocessable Entity",
+  /// 4.29 Too Many Requests ([RFC8516](https://www.rfc-editor.org/rfc/rfc8516))
+  TooManyRequests = (4, 29) "Too Many Requests",
+  /// 5.00 Internal Server Error
+  InternalServerError = (5, 0) "Internal Server Error",
+  /// 5.01 Not Implemented
+  NotImplemented = (5, 1) "Not Implemented",
+  /// 5.02 Bad Gateway
+  BadGateway = (5, 2) "Bad Gateway",
+  /// 5.03 Service Unavailable
+  ServiceUnavailable = (5, 3) "Service Unavailable",
+  /// 5.04 Gateway Timeout
+  GatewayTimeout = (5, 4) "Gateway Timeout",
+  /// 5.05 Proxying Not Supported
+  ProxyingNotSupported = (5, 5) "Proxying Not Supported",
+}
+
+impl From<Known> for Code {
+  fn from(known: Known) -> Self {
+    known.code()
+  }
+}
+
+impl From<Code> for Known {
+  fn from(code: Code) -> Self {
+    code.known()
+  }
+}
+
+impl core::fmt::Display for Known {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    write!(f, "{}", self.code())?;
+
+    match self.name() {
+      | Some(name) => write!(f, " {}", name),
+      | None => Ok(()),
+    }
+  }
+}
+
+impl core::fmt::Display for Code {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    self.to_human()
+        .iter()
+        .try_for_each(|c| core::fmt::Write::write_char(f, *c))
   }
 }
 
@@ -175,4 +340,32 @@ mod tests {
     let expected = 0b01000101_u8;
     assert_eqb!(actual, expected)
   }
+
+  #[test]
+  fn known_round_trips() {
+    (0..=u8::MAX).map(Code::from)
+                 .for_each(|code| assert_eq!(Code::from(code.known()), code));
+  }
+
+  #[test]
+  fn method_consts_match_known() {
+    assert_eq!(Code::GET.known(), Known::Get);
+    assert_eq!(Code::POST.known(), Known::Post);
+    assert_eq!(Code::PUT.known(), Known::Put);
+    assert_eq!(Code::DELETE.known(), Known::Delete);
+    assert_eq!(Code::EMPTY.known(), Known::Empty);
+  }
+
+  #[test]
+  fn display_known() {
+    assert_eq!(Known::Get.to_string(), "0.01 GET");
+    assert_eq!(Known::NotFound.to_string(), "4.04 Not Found");
+    assert_eq!(Known::Unknown(Code::new(7, 31)).to_string(), "7.31");
+  }
+
+  #[test]
+  fn display_code() {
+    assert_eq!(Code::new(2, 5).to_string(), "2.05");
+    assert_eq!(Code::new(4, 15).to_string(), "4.15");
+  }
 }
@@ -0,0 +1,305 @@
+use core::fmt::{self, Display, Formatter, Write};
+use core::str::from_utf8;
+
+use toad_array::Array;
+use toad_map::Map;
+
+use super::opt::known::{no_repeat, repeat};
+use super::opt::percent::{self, Component};
+use super::opt::registry::{RegisteredOpt, Registry};
+use super::{Block, CodeKind, ContentFormat, Message, OptFormat, OptNumber, OptionMap};
+
+/// Writes bytes as uppercase hex prefixed with `0x`
+fn write_hex(f: &mut Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+  f.write_str("0x")?;
+  bytes.iter().try_for_each(|b| write!(f, "{:02X}", b))
+}
+
+/// Interpret an option value as a big-endian unsigned integer
+fn uint(bytes: &[u8]) -> u64 {
+  bytes.iter().fold(0u64, |n, b| (n << 8) | u64::from(*b))
+}
+
+/// Iterate over the option numbers in a map in ascending order,
+/// regardless of the order the map stores them in.
+fn numbers_ascending<O>(opts: &O) -> impl Iterator<Item = OptNumber> + '_
+  where O: OptionMap
+{
+  let next_after = move |prev: Option<OptNumber>| {
+    opts.iter()
+        .map(|(n, _)| *n)
+        .filter(|n| prev.map(|p| *n > p).unwrap_or(true))
+        .min()
+  };
+
+  core::iter::successors(next_after(None), move |n| next_after(Some(*n)))
+}
+
+/// A single option formatted for humans,
+/// e.g. `Content-Format: application/json`.
+///
+/// The value is rendered according to the option's number:
+///  * Uri-Path, Uri-Query and other string options are shown as text
+///  * Content-Format & Accept are shown as media types
+///  * Block1 & Block2 are shown as `NUM/M/SZ` ([RFC7959 Section 2.2](https://www.rfc-editor.org/rfc/rfc7959#section-2.2))
+///  * Other uint options are shown as decimal integers
+///  * Everything else (e.g. ETag) is shown as hex
+///
+/// The alternate flag (`{:#}`) quotes string values, matching the examples in RFC7252.
+///
+/// ```
+/// use toad_msg::no_repeat::{BLOCK2, CONTENT_FORMAT};
+/// use toad_msg::repeat::{ETAG, PATH};
+/// use toad_msg::OptDisplay;
+///
+/// assert_eq!(OptDisplay(CONTENT_FORMAT, &[0, 50]).to_string(),
+///            "Content-Format: application/json");
+/// assert_eq!(OptDisplay(PATH, b"temp").to_string(), "Uri-Path: temp");
+/// assert_eq!(format!("{:#}", OptDisplay(PATH, b"temp")), "Uri-Path: \"temp\"");
+/// assert_eq!(OptDisplay(ETAG, &[0xAB, 0x01]).to_string(), "ETag: 0xAB01");
+/// assert_eq!(OptDisplay(BLOCK2, &[0x1A]).to_string(), "Block2: 1/1/64");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptDisplay<'a>(pub OptNumber, pub &'a [u8]);
+
+impl<'a> Display for OptDisplay<'a> {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    fmt_opt(f, &Registry::KNOWN, self.0, self.1)
+  }
+}
+
+/// Format an option, using `registry` for its name
+/// and (when not special-cased) the format of its value
+pub(crate) fn fmt_opt(f: &mut Formatter<'_>,
+                      registry: &Registry,
+                      num: OptNumber,
+                      bytes: &[u8])
+                      -> fmt::Result {
+  let def = registry.get(num);
+  match def {
+    | Some(def) => write!(f, "{}: ", def.name)?,
+    | None => write!(f, "Option({}): ", num.0)?,
+  }
+
+  match (num, def.map(|d| d.format)) {
+    | (no_repeat::CONTENT_FORMAT | no_repeat::ACCEPT, _) => {
+      write!(f, "{}", ContentFormat::from(uint(bytes) as u16))
+    },
+    | (no_repeat::BLOCK1 | no_repeat::BLOCK2, _) => {
+      let block = Block::from(uint(bytes) as u32);
+      write!(f, "{}/{}/{}", block.num(), block.more() as u8, block.size())
+    },
+    | (_, Some(OptFormat::String)) => match from_utf8(bytes) {
+      | Ok(s) if f.alternate() => write!(f, "{:?}", s),
+      | Ok(s) => f.write_str(s),
+      | Err(_) => write_hex(f, bytes),
+    },
+    | (_, Some(OptFormat::Uint)) => write!(f, "{}", uint(bytes)),
+    | _ if bytes.is_empty() => Ok(()),
+    | _ => write_hex(f, bytes),
+  }
+}
+
+/// Human-readable message dumps.
+///
+/// By default messages are formatted as a single line,
+/// suitable for logging:
+///
+/// ```text
+/// CON GET /sensors/temp MID=0x1234 Tok=0xFE Accept: application/json
+/// ```
+///
+/// The alternate flag (`{:#}`) formats messages over multiple
+/// lines, in the style of the examples in RFC7252 Appendix A:
+///
+/// ```text
+/// Header: GET (T=CON, Code=0.01, MID=0x1234)
+/// Token: 0xFE
+/// Uri-Path: "sensors"
+/// Uri-Path: "temp"
+/// Accept: application/json
+/// ```
+///
+/// ```
+/// use toad_msg::alloc::Message;
+/// use toad_msg::{Code, ContentFormat, Id, MessageOptions, Payload, Token, Type};
+///
+/// let mut msg = Message::new(Type::Con, Code::GET, Id(0x1234), Token::try_from_slice(&[0xFE]).unwrap());
+/// msg.set_path("sensors/temp").unwrap();
+/// msg.set_accept(ContentFormat::Json).unwrap();
+///
+/// assert_eq!(msg.to_string(),
+///            "CON GET /sensors/temp MID=0x1234 Tok=0xFE Accept: application/json");
+///
+/// let mut rep = msg.ack(Id(0x1234));
+/// rep.code = Code::new(2, 5);
+/// rep.set_content_format(ContentFormat::Text).unwrap();
+/// rep.payload = Payload(b"22.3 C".to_vec());
+///
+/// assert_eq!(rep.to_string(),
+///            "ACK 2.05 Content MID=0x1234 Tok=0xFE Content-Format: text/plain; charset=utf-8 (6 byte payload)");
+/// assert_eq!(format!("{:#}", rep),
+///            [r#"Header: 2.05 Content (T=ACK, Code=2.05, MID=0x1234)"#,
+///             r#"Token: 0xFE"#,
+///             r#"Content-Format: text/plain; charset=utf-8"#,
+///             r#"Payload: "22.3 C""#].join("\n"));
+/// ```
+impl<P, O> Display for Message<P, O>
+  where P: Array<Item = u8>,
+        O: OptionMap
+{
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    WithRegistry(self, &Registry::KNOWN).fmt(f)
+  }
+}
+
+impl<P, O> Message<P, O>
+  where P: Array<Item = u8>,
+        O: OptionMap
+{
+  /// Format this message for humans like its [`Display`] impl,
+  /// naming and formatting options using the definitions in `registry`.
+  ///
+  /// ```
+  /// use toad_msg::alloc::Message;
+  /// use toad_msg::opt::registry::{OptionDef, Registry, KNOWN};
+  /// use toad_msg::{Code, Id, MessageOptions, OptFormat, OptNumber, OptValue, Token, Type};
+  ///
+  /// const VENDOR: &[OptionDef] = &[OptionDef { number: OptNumber(65000),
+  ///                                            name: "Firmware",
+  ///                                            repeatable: false,
+  ///                                            min_len: 1,
+  ///                                            max_len: 32,
+  ///                                            format: OptFormat::String }];
+  ///
+  /// let mut msg = Message::new(Type::Non, Code::GET, Id(1), Token(Default::default()));
+  /// msg.add(OptNumber(65000), OptValue(b"1.2.0".to_vec())).unwrap();
+  ///
+  /// assert_eq!(msg.to_string(), "NON GET MID=0x0001 Option(65000): 0x312E322E30");
+  /// assert_eq!(msg.display_with(&Registry(&[KNOWN, VENDOR])).to_string(),
+  ///            "NON GET MID=0x0001 Firmware: 1.2.0");
+  /// ```
+  pub fn display_with<'a>(&'a self, registry: &'a Registry) -> impl Display + 'a {
+    WithRegistry(self, registry)
+  }
+}
+
+/// A message formatted using a [`Registry`]
+struct WithRegistry<'a, P, O>(&'a Message<P, O>, &'a Registry);
+
+impl<'a, P, O> Display for WithRegistry<'a, P, O>
+  where P: Array<Item = u8>,
+        O: OptionMap
+{
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    let WithRegistry(msg, registry) = *self;
+    let code = msg.code.known();
+    let opts = || {
+      numbers_ascending(&msg.opts).flat_map(move |n| {
+                                     msg.opts
+                                         .get(&n)
+                                         .into_iter()
+                                         .flat_map(|vs| vs.iter())
+                                         .map(move |v| RegisteredOpt(registry, n, v.as_bytes()))
+                                   })
+    };
+
+    if f.alternate() {
+      let name = code.name().filter(|_| msg.code.kind() != CodeKind::Response);
+      match name {
+        | Some(name) => write!(f, "Header: {}", name)?,
+        | None => write!(f, "Header: {}", code)?,
+      }
+      write!(f, " (T={}, Code={}, MID=0x{:04X})", msg.ty, msg.code, msg.id.0)?;
+
+      if !msg.token.is_empty() {
+        f.write_str("\nToken: ")?;
+        write_hex(f, msg.token.as_bytes())?;
+      }
+
+      opts().try_for_each(|opt| write!(f, "\n{:#}", opt))?;
+
+      match from_utf8(&msg.payload.0) {
+        | _ if msg.payload.0.is_empty() => Ok(()),
+        | Ok(s) => write!(f, "\nPayload: {:?}", s),
+        | Err(_) => {
+          f.write_str("\nPayload: ")?;
+          write_hex(f, &msg.payload.0)
+        },
+      }
+    } else {
+      match code.name().filter(|_| msg.code.kind() != CodeKind::Response) {
+        | Some(name) => write!(f, "{} {}", msg.ty, name)?,
+        | None => write!(f, "{} {}", msg.ty, code)?,
+      }
+
+      let mut path = opts().filter(|o| o.1 == repeat::PATH).peekable();
+      let mut query = opts().filter(|o| o.1 == repeat::QUERY).peekable();
+      if path.peek().is_some() || query.peek().is_some() {
+        f.write_char(' ')?;
+        path.try_for_each(|o| write!(f, "/{}", percent::encode(o.2, Component::Path)))?;
+        query.enumerate().try_for_each(|(ix, o)| {
+                           let sep = if ix == 0 { '?' } else { '&' };
+                           write!(f, "{}{}", sep, percent::encode(o.2, Component::Query))
+                         })?;
+      }
+
+      write!(f, " MID=0x{:04X}", msg.id.0)?;
+
+      if !msg.token.is_empty() {
+        f.write_str(" Tok=")?;
+        write_hex(f, msg.token.as_bytes())?;
+      }
+
+      opts().filter(|o| o.1 != repeat::PATH && o.1 != repeat::QUERY)
+            .try_for_each(|opt| write!(f, " {}", opt))?;
+
+      match msg.payload.0.len() {
+        | 0 => Ok(()),
+        | n => write!(f, " ({} byte payload)", n),
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use tinyvec::ArrayVec;
+
+  use super::*;
+  use crate::{Code, Id, MessageOptions, Payload, Token, Type};
+
+  type ArrayMessage = Message<ArrayVec<[u8; 16]>,
+                              ArrayVec<[(OptNumber, ArrayVec<[crate::OptValue<ArrayVec<[u8; 16]>>; 4]>); 8]>>;
+
+  #[test]
+  fn options_are_displayed_in_ascending_order_regardless_of_insertion() {
+    let mut msg = ArrayMessage::new(Type::Non, Code::POST, Id(1), Token(Default::default()));
+    msg.set_content_format(ContentFormat::Json).unwrap();
+    msg.add_query("a=1").unwrap();
+    msg.add_query("b").unwrap();
+    msg.set_path("x").unwrap();
+    msg.set_host("coap.me").unwrap();
+
+    assert_eq!(msg.to_string(),
+               "NON POST /x?a=1&b MID=0x0001 Uri-Host: coap.me Content-Format: application/json");
+  }
+
+  #[test]
+  fn binary_payloads_are_displayed_as_hex() {
+    let mut msg = ArrayMessage::new(Type::Non, Code::new(2, 5), Id(1), Token(Default::default()));
+    msg.payload = Payload([0xFFu8, 0x00].into_iter().collect());
+
+    assert_eq!(msg.to_string(), "NON 2.05 Content MID=0x0001 (2 byte payload)");
+    assert_eq!(format!("{:#}", msg),
+               "Header: 2.05 Content (T=NON, Code=2.05, MID=0x0001)\nPayload: 0xFF00");
+  }
+
+  #[test]
+  fn unknown_options_are_displayed_by_number() {
+    assert_eq!(OptDisplay(OptNumber(65001), &[1]).to_string(),
+               "Option(65001): 0x01");
+    assert_eq!(OptDisplay(no_repeat::IF_NONE_MATCH, &[]).to_string(),
+               "If-None-Match: ");
+  }
+}
@@ -14,6 +14,12 @@ use crate::TryIntoBytes;
 /// Message Code
 pub mod code;
 
+/// Message builder
+pub mod builder;
+
+/// Human-readable formatting of messages & options
+pub mod display;
+
 /// Message parsing errors
 pub mod parse_error;
 
@@ -32,7 +38,9 @@ pub mod token;
 /// Message Version
 pub mod ver;
 
+pub use builder::*;
 pub use code::*;
+pub use display::*;
 pub use id::*;
 pub use opt::*;
 pub use parse_error::*;
@@ -126,7 +134,7 @@ impl<PayloadBytes: Array<Item = u8>, Options: OptionMap> Len for Message<Payload
     let payload_marker_size = 1;
     let payload_size = self.payload.0.len();
     let token_size = self.token.0.len();
-    let opts_size: usize = self.opts.opt_refs().map(|o| o.len()).sum();
+    let opts_size: usize = self.opts.opt_refs_sorted().map(|o| o.len()).sum();
 
     header_size + payload_marker_size + payload_size + token_size + opts_size
   }
@@ -262,6 +270,13 @@ impl<C, O> Hash for Message<C, O>
 pub enum SetOptionError<OV, OVs> {
   RepeatedTooManyTimes(OV),
   TooManyOptions(OptNumber, OVs),
+  InvalidHost(opt::host::HostError),
+}
+
+impl<OV, OVs> From<opt::host::HostError> for SetOptionError<OV, OVs> {
+  fn from(e: opt::host::HostError) -> Self {
+    Self::InvalidHost(e)
+  }
 }
 
 impl<P, O> MessageOptions for Message<P, O>
@@ -324,6 +339,20 @@ impl<P, O> MessageOptions for Message<P, O>
   fn remove(&mut self, n: OptNumber) -> Option<Self::OptValues> {
     self.remove(n)
   }
+
+  fn remove_value<F>(&mut self, n: OptNumber, f: F) -> Option<OptValue<Self::OptValueBytes>>
+    where F: FnMut(&OptValue<Self::OptValueBytes>) -> bool
+  {
+    self.remove_value(n, f)
+  }
+
+  fn replace_value(&mut self,
+                   n: OptNumber,
+                   index: usize,
+                   v: OptValue<Self::OptValueBytes>)
+                   -> Option<OptValue<Self::OptValueBytes>> {
+    self.replace_value(n, index, v)
+  }
 }
 
 /// Methods that allow accessing & setting options known to the toad library.
@@ -333,12 +362,12 @@ pub trait MessageOptions {
   /// [`OptionMap::OptValue`]
   type OptValueBytes: Array<Item = u8> + AppendCopy<u8>;
   /// [`SetOptionError`]
-  type SetError;
+  type SetError: From<opt::host::HostError>;
 
   /// Insert a new value for a given option
   ///
   /// Errors when there cannot be any more options, or the option
-  /// cannot be repeated any more (only applies to non-std environments)
+  /// cannot be repeated any more (see [`OptionMap::max_count`])
   #[doc = rfc_7252_doc!("5.4.5")]
   fn add(&mut self, n: OptNumber, v: OptValue<Self::OptValueBytes>) -> Result<(), Self::SetError>;
 
@@ -360,6 +389,29 @@ pub trait MessageOptions {
   /// Get the value of an option, taking the first if there are multiple.
   fn get_first(&self, n: OptNumber) -> Option<&OptValue<Self::OptValueBytes>>;
 
+  /// Get the value of an option, taking the first if there are multiple,
+  /// and [decode it](OptValue::view) according to the format registered for the option.
+  ///
+  /// ```
+  /// use toad_msg::alloc::Message;
+  /// use toad_msg::no_repeat::{CONTENT_FORMAT, MAX_AGE};
+  /// use toad_msg::{Code, Id, MessageOptions, OptValue, OptValueView, OptValueViewError, Token,
+  ///                Type};
+  ///
+  /// let mut msg = Message::new(Type::Con, Code::GET, Id(1), Token(Default::default()));
+  /// assert_eq!(msg.get_view(MAX_AGE), Ok(None));
+  ///
+  /// msg.set(MAX_AGE, OptValue(vec![0, 60])).unwrap();
+  /// assert_eq!(msg.get_view(MAX_AGE), Ok(Some(OptValueView::Uint(60))));
+  ///
+  /// msg.set(CONTENT_FORMAT, OptValue(vec![0, 0, 0])).unwrap();
+  /// assert!(matches!(msg.get_view(CONTENT_FORMAT),
+  ///                  Err(OptValueViewError::Length { .. })));
+  /// ```
+  fn get_view(&self, n: OptNumber) -> Result<Option<OptValueView<'_>>, OptValueViewError> {
+    self.get_first(n).map(|v| v.view(n)).transpose()
+  }
+
   /// Get the value of an option, and interpret it
   /// as a UTF-8 string
   fn get_str(&self, n: OptNumber) -> Result<Option<&str>, Utf8Error>;
@@ -369,44 +421,173 @@ pub trait MessageOptions {
     where F: FromIterator<&'a str>;
 
   /// Get the value of an option, and interpret it
-  /// as a u8
+  /// as a [uint](OptFormat::Uint) of at most 1 byte
   fn get_u8(&self, n: OptNumber) -> Option<u8>;
 
   /// Get the value of an option, and interpret it
-  /// as a u16
+  /// as a [uint](OptFormat::Uint) of at most 2 bytes
   fn get_u16(&self, n: OptNumber) -> Option<u16>;
 
   /// Get the value of an option, and interpret it
-  /// as a u32
+  /// as a [uint](OptFormat::Uint) of at most 4 bytes
   fn get_u32(&self, n: OptNumber) -> Option<u32>;
 
   /// Get the value of an option, and interpret it
-  /// as a u64
+  /// as a [uint](OptFormat::Uint) of at most 8 bytes
+  ///
+  /// uints may be encoded with any number of bytes up to the
+  /// size of the type (leading zero bytes are usually stripped),
+  /// so values shorter than the type are accepted.
+  ///
+  /// ```
+  /// use toad_msg::alloc::Message;
+  /// use toad_msg::{Code, Id, MessageOptions, OptNumber, OptValue, Token, Type};
+  ///
+  /// let mut msg = Message::new(Type::Con, Code::GET, Id(1), Token(Default::default()));
+  ///
+  /// msg.set(OptNumber(60), OptValue(vec![0x01, 0x00])).unwrap();
+  /// assert_eq!(msg.get_u64(OptNumber(60)), Some(256));
+  /// assert_eq!(msg.get_u16(OptNumber(60)), Some(256));
+  /// assert_eq!(msg.get_u8(OptNumber(60)), None);
+  ///
+  /// msg.set(OptNumber(60), OptValue(vec![])).unwrap();
+  /// assert_eq!(msg.get_u64(OptNumber(60)), Some(0));
+  /// ```
   fn get_u64(&self, n: OptNumber) -> Option<u64>;
 
+  /// Replace any / all existing values of an option with a [uint](OptFormat::Uint),
+  /// yielding the previous value(s).
+  ///
+  /// The value is encoded in as few bytes as possible; leading zero bytes
+  /// are stripped and 0 is encoded as an empty value.
+  ///
+  /// ```
+  /// use toad_msg::alloc::Message;
+  /// use toad_msg::{Code, Id, MessageOptions, OptNumber, OptValue, Token, Type};
+  ///
+  /// let mut msg = Message::new(Type::Con, Code::GET, Id(1), Token(Default::default()));
+  ///
+  /// msg.set_uint(OptNumber(60), 1024).unwrap();
+  /// assert_eq!(msg.get_first(OptNumber(60)), Some(&OptValue(vec![0x04, 0x00])));
+  /// assert_eq!(msg.get_u32(OptNumber(60)), Some(1024));
+  ///
+  /// msg.set_uint(OptNumber(60), 0).unwrap();
+  /// assert_eq!(msg.get_first(OptNumber(60)), Some(&OptValue(vec![])));
+  /// ```
+  fn set_uint(&mut self,
+              n: OptNumber,
+              value: u64)
+              -> Result<Option<Self::OptValues>, Self::SetError> {
+    let bytes = value.to_be_bytes();
+    let leading_zeros = (value.leading_zeros() / 8) as usize;
+    self.set(n, bytes[leading_zeros..].iter().copied().collect())
+  }
+
   /// Remove all values for the option from this message,
   /// returning them if there were any.
   fn remove(&mut self, n: OptNumber) -> Option<Self::OptValues>;
 
+  /// Remove the first value for the option matching a predicate,
+  /// returning it if there was one.
+  ///
+  /// If this removes the only value for the option, the option is removed.
+  ///
+  /// The default implementation [removes](MessageOptions::remove) all values
+  /// for the option and [adds](MessageOptions::add) back the rest.
+  ///
+  /// ```
+  /// use toad_msg::alloc::Message;
+  /// use toad_msg::{Code, Id, MessageOptions, Token, Type};
+  ///
+  /// let mut msg = Message::new(Type::Con, Code::GET, Id(1), Token(Default::default()));
+  /// msg.add_etag("a").unwrap();
+  /// msg.add_etag("b").unwrap();
+  ///
+  /// let removed = msg.remove_value(toad_msg::repeat::ETAG, |v| v.as_bytes() == b"a");
+  /// assert_eq!(removed.map(|v| v.0), Some(b"a".to_vec()));
+  /// assert_eq!(msg.count(toad_msg::repeat::ETAG), 1);
+  /// ```
+  fn remove_value<F>(&mut self, n: OptNumber, mut f: F) -> Option<OptValue<Self::OptValueBytes>>
+    where F: FnMut(&OptValue<Self::OptValueBytes>) -> bool
+  {
+    let mut vals = self.remove(n)?;
+    let removed = vals.iter().position(&mut f).and_then(|ix| vals.remove(ix));
+
+    vals.into_iter().for_each(|v| {
+                      self.add(n, v).ok();
+                    });
+
+    removed
+  }
+
+  /// Replace the value at `index` among the values for an option,
+  /// yielding the value it replaced.
+  ///
+  /// If there is no value at `index`, nothing is changed and `None` is returned.
+  ///
+  /// The default implementation [removes](MessageOptions::remove) all values
+  /// for the option and [adds](MessageOptions::add) them back with the value at `index` replaced.
+  ///
+  /// ```
+  /// use toad_msg::alloc::Message;
+  /// use toad_msg::repeat::QUERY;
+  /// use toad_msg::{Code, Id, MessageOptions, OptValue, Token, Type};
+  ///
+  /// let mut msg = Message::new(Type::Con, Code::GET, Id(1), Token(Default::default()));
+  /// msg.add_query("a=1").unwrap();
+  /// msg.add_query("b=2").unwrap();
+  ///
+  /// let old = msg.replace_value(QUERY, 1, OptValue(b"b=3".to_vec()));
+  /// assert_eq!(old.map(|v| v.0), Some(b"b=2".to_vec()));
+  /// assert_eq!(msg.get_strs::<Vec<_>>(QUERY), Ok(vec!["a=1", "b=3"]));
+  ///
+  /// assert_eq!(msg.replace_value(QUERY, 2, OptValue(b"c=4".to_vec())), None);
+  /// ```
+  fn replace_value(&mut self,
+                   n: OptNumber,
+                   index: usize,
+                   v: OptValue<Self::OptValueBytes>)
+                   -> Option<OptValue<Self::OptValueBytes>> {
+    let mut vals = self.remove(n)?;
+    let old = vals.get_mut(index).map(|old| core::mem::replace(old, v));
+
+    vals.into_iter().for_each(|v| {
+                      self.add(n, v).ok();
+                    });
+
+    old
+  }
+
   /// Update the value for the [Uri-Host](opt::known::no_repeat::HOST) option,
   /// discarding any existing values.
   ///
+  /// The host is [validated & normalized](opt::host::normalize) first;
+  /// reg-names are lowercased, and internationalized domain names
+  /// must be punycoded.
+  ///
   /// ```
   /// use toad_msg::alloc::Message;
-  /// use toad_msg::{Code, Id, MessageOptions, Token, Type};
+  /// use toad_msg::opt::host::HostError;
+  /// use toad_msg::{Code, Id, MessageOptions, SetOptionError, Token, Type};
   ///
   /// let mut msg = Message::new(Type::Con, Code::GET, Id(1), Token(Default::default()));
   ///
   /// msg.set_host("cheese.com").unwrap();
   /// assert_eq!(msg.host(), Ok(Some("cheese.com")));
+  ///
+  /// msg.set_host("Cheese.COM").unwrap();
+  /// assert_eq!(msg.host(), Ok(Some("cheese.com")));
+  ///
+  /// assert_eq!(msg.set_host("cheese.com:5683"),
+  ///            Err(SetOptionError::InvalidHost(HostError::InvalidChar(':'))));
+  /// assert_eq!(msg.host(), Ok(Some("cheese.com")));
   /// ```
   #[doc = rfc_7252_doc!("5.10.1")]
   fn set_host<S>(&mut self, host: S) -> Result<(), Self::SetError>
     where S: AsRef<str>
   {
-    self.set(opt::known::no_repeat::HOST,
-             host.as_ref().as_bytes().iter().copied().collect())
-        .map(|_| ())
+    let host = opt::host::normalize(host.as_ref())?.collect();
+    self.set(opt::known::no_repeat::HOST, host).map(|_| ())
   }
 
   /// [`opt::known::no_repeat::BLOCK1`]
@@ -418,8 +599,7 @@ pub trait MessageOptions {
   /// [`opt::known::no_repeat::BLOCK1`]
   fn set_block1(&mut self, size: u16, num: u32, more: bool) -> Result<(), Self::SetError> {
     let block = block::Block::new(size, num, more);
-    self.set(opt::known::no_repeat::BLOCK1,
-             OptValue(u32::from(block).to_be_bytes().iter().copied().collect()))
+    self.set_uint(opt::known::no_repeat::BLOCK1, u32::from(block).into())
         .map(|_| ())
   }
 
@@ -432,8 +612,7 @@ pub trait MessageOptions {
   /// [`opt::known::no_repeat::BLOCK2`]
   fn set_block2(&mut self, size: u16, num: u32, more: bool) -> Result<(), Self::SetError> {
     let block = block::Block::new(size, num, more);
-    self.set(opt::known::no_repeat::BLOCK2,
-             OptValue(u32::from(block).to_be_bytes().iter().copied().collect()))
+    self.set_uint(opt::known::no_repeat::BLOCK2, u32::from(block).into())
         .map(|_| ())
   }
 
@@ -457,8 +636,7 @@ pub trait MessageOptions {
   /// assert_eq!(msg.port(), Some(1234));
   /// ```
   fn set_port(&mut self, port: u16) -> Result<(), Self::SetError> {
-    self.set(opt::known::no_repeat::PORT,
-             port.to_be_bytes().into_iter().collect())
+    self.set_uint(opt::known::no_repeat::PORT, port.into())
         .map(|_| ())
   }
 
@@ -470,6 +648,10 @@ pub trait MessageOptions {
   /// Update the value for the [Uri-Path](opt::known::no_repeat::PATH) option,
   /// discarding any existing values.
   ///
+  /// The path is split into segments on `/`, and each segment
+  /// is [percent-decoded](opt::percent::decode) so that e.g. `%2F` may
+  /// be used to include a `/` within a segment.
+  ///
   /// ```
   /// use toad_msg::alloc::Message;
   /// use toad_msg::{Code, Id, MessageOptions, Token, Type};
@@ -483,6 +665,10 @@ pub trait MessageOptions {
   /// assert_eq!(msg.port(), Some(1234));
   /// assert_eq!(msg.path_string(),
   ///            Ok("cheese/havarti/suggestions".to_string()));
+  ///
+  /// msg.remove(toad_msg::opt::known::repeat::PATH);
+  /// msg.set_path("files/a%2Fb/caf%C3%A9").unwrap();
+  /// assert_eq!(msg.path::<Vec<_>>(), Ok(vec!["files", "a/b", "café"]));
   /// ```
   fn set_path<S>(&mut self, path: S) -> Result<(), Self::SetError>
     where S: AsRef<str>
@@ -491,25 +677,31 @@ pub trait MessageOptions {
         .split('/')
         .try_for_each(|segment| {
           self.add(opt::known::repeat::PATH,
-                   segment.as_bytes().iter().copied().collect())
+                   opt::percent::decode(segment).collect())
         })
         .map(|_| ())
   }
 
   /// Get an iterator over the [Uri-Path](opt::known::repeat::PATH) segments
+  ///
+  /// Segments are yielded decoded, see [`MessageOptions::set_path`].
   fn path<'a, F>(&'a self) -> Result<F, Utf8Error>
     where F: FromIterator<&'a str>
   {
     self.get_strs(opt::known::repeat::PATH)
   }
 
-  /// Get the fully built path, joining segments with '/'.
+  /// Get the fully built path, joining [percent-encoded](opt::percent::encode)
+  /// segments with '/'.
   #[cfg(feature = "std")]
   fn path_string<'a>(&'a self) -> Result<String, Utf8Error> {
     self.get_strs::<Vec<_>>(opt::known::repeat::PATH)
         .map(|segs| {
-          let mut s = segs.into_iter()
-                          .fold(String::new(), |s, seg| format!("{s}{seg}/"));
+          let mut s = segs.into_iter().fold(String::new(), |s, seg| {
+                                        format!("{s}{}/",
+                                                opt::percent::encode(seg,
+                                                                     opt::percent::Component::Path))
+                                      });
           s.pop();
           s
         })
@@ -517,11 +709,13 @@ pub trait MessageOptions {
 
   /// Insert a new value for the [Uri-Query](opt::known::repeat::QUERY) option,
   /// alongside any existing values.
+  ///
+  /// The query parameter is [percent-decoded](opt::percent::decode).
   fn add_query<S>(&mut self, query: S) -> Result<(), Self::SetError>
     where S: AsRef<str>
   {
     self.add(opt::known::repeat::QUERY,
-             query.as_ref().as_bytes().iter().copied().collect())
+             opt::percent::decode(query.as_ref()).collect())
   }
 
   /// Get all query parameters for this request
@@ -547,8 +741,8 @@ pub trait MessageOptions {
   /// discarding any existing values.
   #[doc = rfc_7252_doc!("5.10.3")]
   fn set_content_format(&mut self, format: ContentFormat) -> Result<(), Self::SetError> {
-    self.set(opt::known::no_repeat::CONTENT_FORMAT,
-             format.into_iter().collect())
+    self.set_uint(opt::known::no_repeat::CONTENT_FORMAT,
+                  u16::from(&format).into())
         .map(|_| ())
   }
 
@@ -572,8 +766,7 @@ pub trait MessageOptions {
   /// Set the value for the [Observe](opt::known::no_repeat::OBSERVE) option,
   /// discarding any existing values.
   fn set_observe(&mut self, a: observe::Action) -> Result<(), Self::SetError> {
-    self.set(opt::known::no_repeat::OBSERVE,
-             core::iter::once(u8::from(a)).collect())
+    self.set_uint(opt::known::no_repeat::OBSERVE, u8::from(a).into())
         .map(|_| ())
   }
 
@@ -587,7 +780,7 @@ pub trait MessageOptions {
   /// discarding any existing values.
   #[doc = rfc_7252_doc!("5.10.4")]
   fn set_accept(&mut self, format: ContentFormat) -> Result<(), Self::SetError> {
-    self.set(opt::known::no_repeat::ACCEPT, format.into_iter().collect())
+    self.set_uint(opt::known::no_repeat::ACCEPT, u16::from(&format).into())
         .map(|_| ())
   }
 
@@ -601,8 +794,7 @@ pub trait MessageOptions {
   /// discarding any existing values.
   #[doc = rfc_7252_doc!("5.10.9")]
   fn set_size1(&mut self, size_bytes: u64) -> Result<(), Self::SetError> {
-    self.set(opt::known::no_repeat::SIZE1,
-             size_bytes.to_be_bytes().into_iter().collect())
+    self.set_uint(opt::known::no_repeat::SIZE1, size_bytes)
         .map(|_| ())
   }
 
@@ -614,8 +806,7 @@ pub trait MessageOptions {
   /// Update the value for the [Size2](opt::known::no_repeat::SIZE2) option,
   /// discarding any existing values.
   fn set_size2(&mut self, size_bytes: u64) -> Result<(), Self::SetError> {
-    self.set(opt::known::no_repeat::SIZE2,
-             size_bytes.to_be_bytes().into_iter().collect())
+    self.set_uint(opt::known::no_repeat::SIZE2, size_bytes)
         .map(|_| ())
   }
 
@@ -663,8 +854,7 @@ pub trait MessageOptions {
   /// discarding any existing values.
   #[doc = rfc_7252_doc!("5.10.5")]
   fn set_max_age(&mut self, max_age_seconds: u32) -> Result<(), Self::SetError> {
-    self.set(opt::known::no_repeat::MAX_AGE,
-             max_age_seconds.to_be_bytes().into_iter().collect())
+    self.set_uint(opt::known::no_repeat::MAX_AGE, max_age_seconds.into())
         .map(|_| ())
   }
 
@@ -710,12 +900,9 @@ pub trait MessageOptions {
   fn add_if_match<B>(&mut self, tag: B) -> Result<(), Self::SetError>
     where B: AsRef<[u8]>
   {
-    if let Some(others) = self.remove(opt::known::repeat::IF_MATCH) {
-      others.into_iter()
-            .filter(|v| v.0.len() > 0)
-            .map(|v| self.add(opt::known::repeat::IF_MATCH, v))
-            .collect::<Result<(), _>>()?;
-    }
+    while self.remove_value(opt::known::repeat::IF_MATCH, |v| v.0.is_empty())
+              .is_some()
+    {}
 
     self.add(opt::known::repeat::IF_MATCH,
              tag.as_ref().iter().copied().collect())
@@ -866,8 +1053,11 @@ impl<PayloadBytes: Array<Item = u8> + AppendCopy<u8>, Options: OptionMap>
          n: OptNumber,
          v: OptValue<Options::OptValue>)
          -> Result<(), SetOptionError<OptValue<Options::OptValue>, Options::OptValues>> {
+    if self.opts.is_full_for(n) {
+      return Err(SetOptionError::RepeatedTooManyTimes(v));
+    }
+
     match (self.remove(n).unwrap_or_default(), &mut self.opts) {
-      | (vals, _) if vals.is_full() => Err(SetOptionError::RepeatedTooManyTimes(v)),
       | (vals, opts) if opts.is_full() => Err(SetOptionError::TooManyOptions(n, vals)),
       | (mut vals, opts) => {
         vals.append(v);
@@ -916,34 +1106,55 @@ impl<PayloadBytes: Array<Item = u8> + AppendCopy<u8>, Options: OptionMap>
 
   fn get_u8(&self, n: OptNumber) -> Option<u8> {
     self.get_first(n)
-        .filter(|bytes| bytes.0.len() == 1)
-        .map(|bytes| bytes.0[0])
+        .and_then(|v| decode_uint(v.as_bytes(), 1))
+        .map(|n| n as u8)
   }
 
   fn get_u16(&self, n: OptNumber) -> Option<u16> {
     self.get_first(n)
-        .filter(|bytes| bytes.0.len() == 2)
-        .map(|bytes| u16::from_be_bytes([bytes.0[0], bytes.0[1]]))
+        .and_then(|v| decode_uint(v.as_bytes(), 2))
+        .map(|n| n as u16)
   }
 
   fn get_u32(&self, n: OptNumber) -> Option<u32> {
     self.get_first(n)
-        .filter(|bytes| bytes.0.len() == 4)
-        .map(|bytes| u32::from_be_bytes([bytes.0[0], bytes.0[1], bytes.0[2], bytes.0[3]]))
+        .and_then(|v| decode_uint(v.as_bytes(), 4))
+        .map(|n| n as u32)
   }
 
   fn get_u64(&self, n: OptNumber) -> Option<u64> {
     self.get_first(n)
-        .filter(|bytes| bytes.0.len() == 8)
-        .map(|bytes| {
-          u64::from_be_bytes([bytes.0[0], bytes.0[1], bytes.0[2], bytes.0[3], bytes.0[4],
-                              bytes.0[5], bytes.0[6], bytes.0[7]])
-        })
+        .and_then(|v| decode_uint(v.as_bytes(), 8))
   }
 
   fn remove(&mut self, n: OptNumber) -> Option<Options::OptValues> {
     self.opts.remove(&n)
   }
+
+  fn remove_value<F>(&mut self, n: OptNumber, mut f: F) -> Option<OptValue<Options::OptValue>>
+    where F: FnMut(&OptValue<Options::OptValue>) -> bool
+  {
+    let vals = self.opts.get_mut(&n)?;
+    let ix = vals.iter().position(&mut f)?;
+    let removed = vals.remove(ix);
+
+    if vals.is_empty() {
+      self.remove(n);
+    }
+
+    removed
+  }
+
+  fn replace_value(&mut self,
+                   n: OptNumber,
+                   index: usize,
+                   v: OptValue<Options::OptValue>)
+                   -> Option<OptValue<Options::OptValue>> {
+    self.opts
+        .get_mut(&n)?
+        .get_mut(index)
+        .map(|old| core::mem::replace(old, v))
+  }
 }
 
 impl<Bytes: AsRef<[u8]>, PayloadBytes: Array<Item = u8> + AppendCopy<u8>, Options: OptionMap>
@@ -972,6 +1183,13 @@ impl<Bytes: AsRef<[u8]>, PayloadBytes: Array<Item = u8> + AppendCopy<u8>, Option
 
     let opts = Options::try_consume_bytes(&mut bytes).map_err(Self::Error::OptParseError)?;
 
+    match PayloadBytes::CAPACITY {
+      | Some(capacity) if bytes.remaining() > capacity => {
+        return Err(Self::Error::PayloadTooLong(capacity))
+      },
+      | _ => (),
+    }
+
     let mut payload = PayloadBytes::reserve(bytes.remaining());
     payload.append_copy(bytes.take_until_end());
     let payload = Payload(payload);
@@ -986,6 +1204,15 @@ impl<Bytes: AsRef<[u8]>, PayloadBytes: Array<Item = u8> + AppendCopy<u8>, Option
   }
 }
 
+/// Decode a big-endian [uint](OptFormat::Uint) no wider than `size` bytes
+fn decode_uint(bytes: &[u8], size: usize) -> Option<u64> {
+  if bytes.len() > size {
+    return None;
+  }
+
+  Some(bytes.iter().fold(0u64, |n, b| (n << 8) | u64::from(*b)))
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -1013,4 +1240,109 @@ mod tests {
     let id = Id::try_consume_bytes(&mut id_bytes).unwrap();
     assert_eq!(id, Id(34));
   }
+
+  #[test]
+  fn path_and_query_are_percent_decoded() {
+    let mut msg = alloc::Message::new(Type::Con, Code::GET, Id(1), Token(Default::default()));
+    msg.set_path("a%2Fb/%E2%9C%93").unwrap();
+    msg.add_query("q=a%26b").unwrap();
+
+    assert_eq!(msg.path::<Vec<_>>(), Ok(vec!["a/b", "✓"]));
+    assert_eq!(msg.query::<Vec<_>>(), Ok(vec!["q=a&b"]));
+    assert_eq!(msg.path_string(), Ok("a%2Fb/%E2%9C%93".to_string()));
+  }
+
+  #[test]
+  fn uint_options_are_minimally_encoded() {
+    use opt::known::no_repeat;
+
+    let mut msg = alloc::Message::new(Type::Con, Code::GET, Id(1), Token(Default::default()));
+    msg.set_port(80).unwrap();
+    msg.set_max_age(0).unwrap();
+    msg.set_size1(1 << 16).unwrap();
+    msg.set_block2(16, 0, false).unwrap();
+
+    assert_eq!(msg.get_first(no_repeat::PORT), Some(&OptValue(vec![80])));
+    assert_eq!(msg.get_first(no_repeat::MAX_AGE), Some(&OptValue(vec![])));
+    assert_eq!(msg.get_first(no_repeat::SIZE1), Some(&OptValue(vec![1, 0, 0])));
+    assert_eq!(msg.port(), Some(80));
+    assert_eq!(msg.max_age_seconds(), Some(0));
+    assert_eq!(msg.size1(), Some(1 << 16));
+    assert_eq!(msg.block2(), Some(block::Block::new(16, 0, false)));
+    assert_eq!(opt::registry::Registry::KNOWN.validate(&msg.opts), Ok(()));
+
+    msg.set(no_repeat::PORT, OptValue(vec![0, 0, 80])).unwrap();
+    assert_eq!(msg.port(), None);
+  }
+
+  #[test]
+  fn add_respects_repeatability() {
+    use opt::known::{no_repeat, repeat};
+
+    let mut msg = alloc::Message::new(Type::Con, Code::GET, Id(1), Token(Default::default()));
+
+    msg.add(no_repeat::CONTENT_FORMAT, OptValue(vec![50])).unwrap();
+    assert!(matches!(msg.add(no_repeat::CONTENT_FORMAT, OptValue(vec![0])),
+                     Err(SetOptionError::RepeatedTooManyTimes(_))));
+    assert_eq!(msg.get(no_repeat::CONTENT_FORMAT),
+               Some(&vec![OptValue(vec![50])]));
+
+    msg.add(repeat::ETAG, OptValue(vec![1])).unwrap();
+    msg.add(repeat::ETAG, OptValue(vec![2])).unwrap();
+    assert_eq!(msg.count(repeat::ETAG), 2);
+  }
+
+  #[test]
+  fn percent_decoding_into_fixed_capacity_arrays() {
+    type Msg = Message<tinyvec::ArrayVec<[u8; 0]>,
+                       tinyvec::ArrayVec<[(OptNumber,
+                                           tinyvec::ArrayVec<[OptValue<tinyvec::ArrayVec<[u8; 3]>>;
+                                                              1]>);
+                                          1]>>;
+
+    let mut msg = Msg::new(Type::Con, Code::GET, Id(1), Token(Default::default()));
+    msg.set_path("%61%62%63").unwrap();
+    assert_eq!(msg.path::<tinyvec::ArrayVec<[_; 1]>>().unwrap().as_slice(), &["abc"]);
+  }
+
+  #[test]
+  fn parsing_into_fixed_capacity_reports_capacity_errors() {
+    type Msg = Message<tinyvec::ArrayVec<[u8; 4]>,
+                       tinyvec::ArrayVec<[(OptNumber,
+                                           tinyvec::ArrayVec<[OptValue<tinyvec::ArrayVec<[u8; 3]>>;
+                                                              1]>);
+                                          1]>>;
+
+    let bytes = |f: &dyn Fn(&mut alloc::Message)| {
+      let mut msg = alloc::Message::new(Type::Con, Code::POST, Id(1), Token(Default::default()));
+      f(&mut msg);
+      msg.try_into_bytes::<Vec<u8>>().unwrap()
+    };
+
+    let fits = bytes(&|m| {
+      m.set_path("abc").unwrap();
+      m.set_payload(Payload(vec![0; 4]));
+    });
+    assert!(Msg::try_from_bytes(&fits).is_ok());
+
+    let payload = bytes(&|m| {
+      m.set_payload(Payload(vec![0; 5]));
+    });
+    assert_eq!(Msg::try_from_bytes(&payload).unwrap_err().capacity_error(),
+               Some(CapacityError::PayloadTooLong { capacity: 4 }));
+
+    let value = bytes(&|m| {
+      m.set_path("abcd").unwrap();
+    });
+    assert_eq!(Msg::try_from_bytes(&value).unwrap_err().capacity_error(),
+               Some(CapacityError::OptionValueTooLong { capacity: 3,
+                                                        actual: 4 }));
+
+    let count = bytes(&|m| {
+      m.set_path("a").unwrap();
+      m.set_content_format(ContentFormat::Json).unwrap();
+    });
+    assert_eq!(Msg::try_from_bytes(&count).unwrap_err().capacity_error(),
+               Some(CapacityError::TooManyOptions { capacity: 1 }));
+  }
 }
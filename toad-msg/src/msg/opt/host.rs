@@ -0,0 +1,164 @@
+//! Validation & normalization of [Uri-Host](super::known::no_repeat::HOST) option values.
+//!
+//! The value of Uri-Host must be an
+//! [IP-literal, IPv4address or reg-name](https://www.rfc-editor.org/rfc/rfc3986#section-3.2.2),
+//! and reg-names are normalized to lowercase
+//! ([RFC7252 section 6.4](https://www.rfc-editor.org/rfc/rfc7252#section-6.4)).
+//!
+//! Internationalized domain names must be converted to
+//! their ASCII ("punycode") form before being used as a Uri-Host.
+//!
+//! ```
+//! use toad_msg::opt::host::{normalize, HostError};
+//!
+//! let host = normalize("www.EXAMPLE.com").unwrap().collect::<Vec<u8>>();
+//! assert_eq!(host, b"www.example.com");
+//!
+//! assert_eq!(normalize("bücher.example").err(), Some(HostError::NonAscii));
+//! assert!(normalize("xn--bcher-kva.example").is_ok());
+//! ```
+
+/// Maximum length of a Uri-Host value, in bytes
+///
+/// [RFC7252 section 5.10](https://www.rfc-editor.org/rfc/rfc7252#section-5.10)
+pub const MAX_LEN: usize = 255;
+
+/// Reasons a string may not be used as a Uri-Host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HostError {
+  /// The host is empty
+  Empty,
+  /// The host is longer than [`MAX_LEN`] bytes
+  TooLong(usize),
+  /// The host contains characters that are not ASCII.
+  ///
+  /// Internationalized domain names must be punycoded.
+  NonAscii,
+  /// The host contains a character that is not allowed in a reg-name
+  InvalidChar(char),
+  /// The host starts with `[` but is not a well-formed IP-literal
+  InvalidIpLiteral,
+}
+
+fn is_reg_name_char(b: u8) -> bool {
+  // <https://www.rfc-editor.org/rfc/rfc3986#section-3.2.2>
+  // unreserved / sub-delims (percent-encoded octets are decoded in option values)
+  b.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=".contains(&b)
+}
+
+fn is_ip_literal(inner: &str) -> bool {
+  match inner.bytes().next() {
+    // <https://www.rfc-editor.org/rfc/rfc3986#section-3.2.2>
+    // IPvFuture = "v" 1*HEXDIG "." 1*( unreserved / sub-delims / ":" )
+    | Some(b'v' | b'V') => inner[1..].split_once('.')
+                                     .map(|(ver, addr)| {
+                                       !ver.is_empty()
+                                       && ver.bytes().all(|b| b.is_ascii_hexdigit())
+                                       && !addr.is_empty()
+                                       && addr.bytes().all(|b| is_reg_name_char(b) || b == b':')
+                                     })
+                                     .unwrap_or(false),
+    | Some(_) => {
+      inner.contains(':') && inner.bytes().all(|b| b.is_ascii_hexdigit() || b == b':' || b == b'.')
+    },
+    | None => false,
+  }
+}
+
+/// Check that `host` may be used as a Uri-Host, yielding
+/// the bytes of its normalized form.
+///
+/// IP-literals (e.g. `[2001:db8::7]`) and IPv4 addresses are yielded as-is,
+/// and reg-names (e.g. `www.example.com`) are lowercased.
+pub fn normalize(host: &str) -> Result<impl Iterator<Item = u8> + '_, HostError> {
+  if host.is_empty() {
+    return Err(HostError::Empty);
+  }
+
+  if host.len() > MAX_LEN {
+    return Err(HostError::TooLong(host.len()));
+  }
+
+  if !host.is_ascii() {
+    return Err(HostError::NonAscii);
+  }
+
+  let reg_name = match host.strip_prefix('[') {
+    | Some(inner) => match inner.strip_suffix(']') {
+      | Some(inner) if is_ip_literal(inner) => false,
+      | _ => return Err(HostError::InvalidIpLiteral),
+    },
+    | None => match host.bytes().find(|b| !is_reg_name_char(*b)) {
+      | Some(b) => return Err(HostError::InvalidChar(b as char)),
+      | None => true,
+    },
+  };
+
+  Ok(host.bytes().map(move |b| {
+                   if reg_name {
+                     b.to_ascii_lowercase()
+                   } else {
+                     b
+                   }
+                 }))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn norm(host: &str) -> Result<String, HostError> {
+    normalize(host).map(|bs| String::from_utf8(bs.collect()).unwrap())
+  }
+
+  #[test]
+  fn rfc3986_examples() {
+    // <https://www.rfc-editor.org/rfc/rfc3986#section-1.1.2>
+    assert_eq!(norm("ftp.is.co.za"), Ok("ftp.is.co.za".into()));
+    assert_eq!(norm("www.ietf.org"), Ok("www.ietf.org".into()));
+    assert_eq!(norm("192.0.2.16"), Ok("192.0.2.16".into()));
+    assert_eq!(norm("[2001:db8::7]"), Ok("[2001:db8::7]".into()));
+    assert_eq!(norm("example.com"), Ok("example.com".into()));
+
+    // <https://www.rfc-editor.org/rfc/rfc3986#section-6.2.2.1>
+    assert_eq!(norm("www.EXAMPLE.com"), Ok("www.example.com".into()));
+  }
+
+  #[test]
+  fn ip_literals() {
+    assert_eq!(norm("[::1]"), Ok("[::1]".into()));
+    assert_eq!(norm("[::ffff:192.0.2.1]"), Ok("[::ffff:192.0.2.1]".into()));
+    assert_eq!(norm("[v1.fe80::a+en1]"), Ok("[v1.fe80::a+en1]".into()));
+    assert_eq!(norm("[2001:DB8::7]"), Ok("[2001:DB8::7]".into()));
+
+    assert_eq!(norm("[::1"), Err(HostError::InvalidIpLiteral));
+    assert_eq!(norm("[]"), Err(HostError::InvalidIpLiteral));
+    assert_eq!(norm("[example.com]"), Err(HostError::InvalidIpLiteral));
+    assert_eq!(norm("[v.foo]"), Err(HostError::InvalidIpLiteral));
+    assert_eq!(norm("[v1.]"), Err(HostError::InvalidIpLiteral));
+  }
+
+  #[test]
+  fn invalid_reg_names() {
+    assert_eq!(norm(""), Err(HostError::Empty));
+    assert_eq!(norm("exa mple.com"), Err(HostError::InvalidChar(' ')));
+    assert_eq!(norm("example.com:5683"), Err(HostError::InvalidChar(':')));
+    assert_eq!(norm("user@example.com"), Err(HostError::InvalidChar('@')));
+    assert_eq!(norm("example.com/"), Err(HostError::InvalidChar('/')));
+    assert_eq!(norm("ex]ample"), Err(HostError::InvalidChar(']')));
+  }
+
+  #[test]
+  fn idna() {
+    assert_eq!(norm("bücher.example"), Err(HostError::NonAscii));
+    assert_eq!(norm("XN--BCHER-KVA.example"),
+               Ok("xn--bcher-kva.example".into()));
+  }
+
+  #[test]
+  fn too_long() {
+    let long = "a".repeat(MAX_LEN + 1);
+    assert_eq!(norm(&long), Err(HostError::TooLong(MAX_LEN + 1)));
+    assert!(norm(&long[1..]).is_ok());
+  }
+}
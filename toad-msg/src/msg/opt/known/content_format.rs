@@ -25,6 +25,30 @@ impl ContentFormat {
   }
 }
 
+/// Formats the content format as its media type,
+/// or its numeric value if it is [`ContentFormat::Other`]
+///
+/// ```
+/// use toad_msg::ContentFormat;
+///
+/// assert_eq!(ContentFormat::Json.to_string(), "application/json");
+/// assert_eq!(ContentFormat::Other(60).to_string(), "60");
+/// ```
+impl core::fmt::Display for ContentFormat {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    use ContentFormat::*;
+    match self {
+      | Text => f.write_str("text/plain; charset=utf-8"),
+      | LinkFormat => f.write_str("application/link-format"),
+      | Xml => f.write_str("application/xml"),
+      | OctetStream => f.write_str("application/octet-stream"),
+      | Exi => f.write_str("application/exi"),
+      | Json => f.write_str("application/json"),
+      | Other(n) => write!(f, "{}", n),
+    }
+  }
+}
+
 impl<'a> From<&'a ContentFormat> for u16 {
   fn from(f: &'a ContentFormat) -> Self {
     use ContentFormat::*;
@@ -1,7 +1,7 @@
 use core::hash::Hash;
 use core::iter::FromIterator;
 use core::marker::PhantomData;
-use core::ops::{Add, Sub};
+use core::ops::{Add, RangeInclusive, Sub};
 
 #[cfg(feature = "alloc")]
 use std_alloc::vec::Vec;
@@ -22,6 +22,16 @@ pub use parse_error::*;
 pub mod known;
 pub use known::*;
 
+pub mod percent;
+
+pub mod host;
+
+pub mod registry;
+
+/// Typed views of option values
+pub mod view;
+pub use view::*;
+
 use self::no_repeat::{BLOCK1, BLOCK2};
 
 /// An iterator over owned [`Opt`]s
@@ -133,7 +143,48 @@ pub trait OptionMap
   /// Note that not all options are repeatable.
   type OptValues: Array<Item = OptValue<Self::OptValue>>;
 
+  /// The maximum number of values that may be stored for option number `n`,
+  /// or `None` if there is no limit.
+  ///
+  /// This is the lesser of the capacity of [`OptionMap::OptValues`] and,
+  /// for options [known](registry::KNOWN) not to be repeatable, 1.
+  ///
+  /// ```
+  /// use std::collections::BTreeMap;
+  ///
+  /// use toad_msg::no_repeat::CONTENT_FORMAT;
+  /// use toad_msg::repeat::PATH;
+  /// use toad_msg::{OptNumber, OptValue, OptionMap};
+  ///
+  /// type Opts = BTreeMap<OptNumber, Vec<OptValue<Vec<u8>>>>;
+  ///
+  /// assert_eq!(Opts::max_count(CONTENT_FORMAT), Some(1));
+  /// assert_eq!(Opts::max_count(PATH), None);
+  /// ```
+  fn max_count(n: OptNumber) -> Option<usize> {
+    let repeat_limit = registry::Registry::KNOWN.get(n)
+                                                .filter(|def| !def.repeatable)
+                                                .map(|_| 1);
+
+    match (repeat_limit, Self::OptValues::CAPACITY) {
+      | (Some(a), Some(b)) => Some(a.min(b)),
+      | (a, b) => a.or(b),
+    }
+  }
+
+  /// Whether option number `n` has as many values as it may have
+  /// (see [`OptionMap::max_count`])
+  fn is_full_for(&self, n: OptNumber) -> bool {
+    let count = self.get(&n).map(|vs| vs.len()).unwrap_or(0);
+    Self::max_count(n).map(|max| count >= max)
+                      .unwrap_or(false)
+  }
+
   /// Iterate over the map, yielding raw option structures
+  ///
+  /// Options are yielded in the order the map stores them in,
+  /// which for e.g. `HashMap` is not ascending by number. Prefer
+  /// [`OptionMap::opt_refs_sorted`] when the order matters.
   fn opts(self) -> OptIter<Self, Self::IntoIter> {
     OptIter { iter: self.into_iter(),
               last_seen_num: OptNumber(0),
@@ -142,12 +193,27 @@ pub trait OptionMap
   }
 
   /// Iterate over the map, yielding raw option structures
+  ///
+  /// Like [`OptionMap::opts`], options are yielded in the order the map stores them in.
+  /// Prefer [`OptionMap::opt_refs_sorted`] when the order matters.
   fn opt_refs(&self) -> OptRefIter<'_, Self, toad_map::Iter<'_, OptNumber, Self::OptValues>> {
     OptRefIter { iter: self.iter(),
                  last_seen_num: OptNumber(0),
                  __p: PhantomData,
                  repeated: None }
   }
+
+  /// Iterate over the map in ascending order of [`OptNumber`], yielding raw option structures
+  ///
+  /// See [`Map::iter_sorted`]
+  fn opt_refs_sorted(
+    &self)
+    -> OptRefIter<'_, Self, toad_map::SortedIter<'_, Self, OptNumber, Self::OptValues>> {
+    OptRefIter { iter: self.iter_sorted(),
+                 last_seen_num: OptNumber(0),
+                 __p: PhantomData,
+                 repeated: None }
+  }
 }
 
 #[cfg(feature = "alloc")]
@@ -156,6 +222,12 @@ impl OptionMap for std_alloc::collections::BTreeMap<OptNumber, Vec<OptValue<Vec<
   type OptValues = Vec<OptValue<Vec<u8>>>;
 }
 
+#[cfg(feature = "std")]
+impl OptionMap for std::collections::HashMap<OptNumber, Vec<OptValue<Vec<u8>>>> {
+  type OptValue = Vec<u8>;
+  type OptValues = Vec<OptValue<Vec<u8>>>;
+}
+
 type ArrayVecMap<const N: usize, K, V> = ArrayVec<[(K, V); N]>;
 
 impl<const MAX_OPTS: usize, const MAX_INSTANCES: usize, const MAX_BYTES_PER_INSTANCE: usize>
@@ -294,6 +366,29 @@ impl<'a, C> Ord for OptRef<'a, C> where C: Array<Item = u8>
 
 impl<'a, C> Eq for OptRef<'a, C> where C: Array<Item = u8> {}
 
+impl<'a, C: Array<Item = u8>> OptRef<'a, C> {
+  /// See [`Opt::extend_bytes`]
+  pub fn extend_bytes(self, bytes: &mut impl Extend<u8>) {
+    let (del, del_bytes) = crate::to_bytes::opt_len_or_delta(self.delta.0);
+    let (len, len_bytes) = crate::to_bytes::opt_len_or_delta(self.value.0.len() as u16);
+    let del = del << 4;
+
+    let header = del | len;
+
+    bytes.extend(Some(header));
+
+    if let Some(bs) = del_bytes {
+      bytes.extend(bs);
+    }
+
+    if let Some(bs) = len_bytes {
+      bytes.extend(bs);
+    }
+
+    bytes.extend(self.value.0.iter().copied());
+  }
+}
+
 impl<'a, C: Array<Item = u8>> Len for OptRef<'a, C> {
   const CAPACITY: Option<usize> = None;
 
@@ -478,6 +573,99 @@ impl OptNumber {
     && self != &BLOCK1
     && self != &BLOCK2
   }
+
+  /// Get the registered name of this option, e.g. `"Content-Format"` for 12.
+  ///
+  /// ```
+  /// use toad_msg::no_repeat::CONTENT_FORMAT;
+  /// use toad_msg::OptNumber;
+  ///
+  /// assert_eq!(CONTENT_FORMAT.name(), Some("Content-Format"));
+  /// assert_eq!(OptNumber(65001).name(), None);
+  /// ```
+  pub const fn name(&self) -> Option<&'static str> {
+    match self.def() {
+      | Some(def) => Some(def.name),
+      | None => None,
+    }
+  }
+}
+
+/// The format of an option's value
+#[doc = rfc_7252_doc!("3.2")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OptFormat {
+  /// A zero-length value
+  Empty,
+  /// An opaque sequence of bytes
+  Opaque,
+  /// A non-negative integer, in network byte order
+  /// using as few bytes as possible
+  Uint,
+  /// A UTF-8 string
+  String,
+}
+
+impl OptNumber {
+  /// Get the registered [format](OptFormat) of this option's value
+  ///
+  /// ```
+  /// use toad_msg::no_repeat::{CONTENT_FORMAT, HOST};
+  /// use toad_msg::{OptFormat, OptNumber};
+  ///
+  /// assert_eq!(CONTENT_FORMAT.format(), Some(OptFormat::Uint));
+  /// assert_eq!(HOST.format(), Some(OptFormat::String));
+  /// assert_eq!(OptNumber(65001).format(), None);
+  /// ```
+  #[doc = rfc_7252_doc!("5.10")]
+  pub const fn format(&self) -> Option<OptFormat> {
+    match self.def() {
+      | Some(def) => Some(def.format),
+      | None => None,
+    }
+  }
+
+  /// Get the registered minimum & maximum length of this option's value, in bytes
+  ///
+  /// ```
+  /// use toad_msg::no_repeat::{HOST, MAX_AGE};
+  /// use toad_msg::OptNumber;
+  ///
+  /// assert_eq!(MAX_AGE.len_range(), Some(0..=4));
+  /// assert_eq!(HOST.len_range(), Some(1..=255));
+  /// assert_eq!(OptNumber(65001).len_range(), None);
+  /// ```
+  pub const fn len_range(&self) -> Option<RangeInclusive<usize>> {
+    match self.def() {
+      | Some(def) => Some(def.len_range()),
+      | None => None,
+    }
+  }
+
+  /// Get the [definition](registry::OptionDef) of this option,
+  /// if it is [known](registry::KNOWN) to toad
+  ///
+  /// ```
+  /// use toad_msg::no_repeat::HOST;
+  /// use toad_msg::repeat::PATH;
+  ///
+  /// assert!(!HOST.def().unwrap().repeatable);
+  /// assert!(PATH.def().unwrap().repeatable);
+  /// ```
+  pub const fn def(&self) -> Option<&'static registry::OptionDef> {
+    registry::Registry::KNOWN.get(*self)
+  }
+}
+
+/// Formats the option's [registered name](OptNumber::name),
+/// falling back to `Option(<number>)`
+impl core::fmt::Display for OptNumber {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    match self.name() {
+      | Some(name) => f.write_str(name),
+      | None => write!(f, "Option({})", self.0),
+    }
+  }
 }
 
 #[doc = rfc_7252_doc!("3.2")]
@@ -554,6 +742,14 @@ impl<Bytes: AsRef<[u8]>, V: Array<Item = u8> + AppendCopy<u8>> TryConsumeBytes<B
                                      OptParseError::ValueLengthReservedValue(15))?
               as usize;
 
+    match V::CAPACITY {
+      | Some(capacity) if len > capacity => {
+        return Err(Self::Error::OptionValueTooLong { capacity,
+                                                     actual: len })
+      },
+      | _ => (),
+    }
+
     let mut value = V::reserve(len);
     value.append_copy(bytes.take(len));
 
@@ -0,0 +1,180 @@
+//! [Percent-encoding](https://www.rfc-editor.org/rfc/rfc3986#section-2.1)
+//! of [Uri-Path](super::known::repeat::PATH) and [Uri-Query](super::known::repeat::QUERY)
+//! option values.
+//!
+//! Option values are stored on the wire **decoded**, e.g. the path `/a%2Fb/c`
+//! is represented as two Uri-Path options; `a/b` and `c`.
+//! ([RFC7252 section 6.4](https://www.rfc-editor.org/rfc/rfc7252#section-6.4))
+//!
+//! ```
+//! use toad_msg::opt::percent::{decode, encode, Component};
+//!
+//! let decoded = decode("caf%C3%A9%2Fbar").collect::<Vec<u8>>();
+//! assert_eq!(decoded, "café/bar".as_bytes());
+//!
+//! let encoded = encode(&decoded, Component::Path).to_string();
+//! assert_eq!(encoded, "caf%C3%A9%2Fbar");
+//! ```
+
+use core::fmt::{self, Write};
+
+/// The URI component that a value is being encoded for,
+/// which determines the characters that may be left unescaped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Component {
+  /// A single path segment.
+  ///
+  /// `/` must be escaped, since it would otherwise
+  /// be interpreted as a segment separator.
+  Path,
+  /// A single query parameter.
+  ///
+  /// `&` must be escaped, since it would otherwise
+  /// be interpreted as a parameter separator.
+  Query,
+}
+
+impl Component {
+  fn allows(&self, b: u8) -> bool {
+    // <https://www.rfc-editor.org/rfc/rfc3986#section-3.3>
+    let pchar = b.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@".contains(&b);
+
+    match self {
+      | Component::Path => pchar,
+      // <https://www.rfc-editor.org/rfc/rfc7252#section-6.5>
+      | Component::Query => (pchar || b == b'/' || b == b'?') && b != b'&',
+    }
+  }
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+  match b {
+    | b'0'..=b'9' => Some(b - b'0'),
+    | b'a'..=b'f' => Some(b - b'a' + 10),
+    | b'A'..=b'F' => Some(b - b'A' + 10),
+    | _ => None,
+  }
+}
+
+/// Iterator yielding the decoded bytes of a percent-encoded string.
+///
+/// See [`decode`]
+#[derive(Debug, Clone)]
+pub struct Decode<'a> {
+  bytes: &'a [u8],
+}
+
+impl<'a> Iterator for Decode<'a> {
+  type Item = u8;
+
+  fn next(&mut self) -> Option<u8> {
+    if let [b'%', hi, lo, rest @ ..] = self.bytes {
+      if let (Some(hi), Some(lo)) = (hex_value(*hi), hex_value(*lo)) {
+        self.bytes = rest;
+        return Some(hi << 4 | lo);
+      }
+    }
+
+    let (b, rest) = self.bytes.split_first()?;
+    self.bytes = rest;
+    Some(*b)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.bytes.len() / 3, Some(self.bytes.len()))
+  }
+}
+
+/// Decode a percent-encoded string.
+///
+/// This does not allocate; the decoded bytes may be collected
+/// directly into any [`Array`](toad_array::Array) (e.g. an option value).
+///
+/// `%` characters that are not followed by two hex digits
+/// are not valid percent-encodings, and are yielded unchanged.
+///
+/// ```
+/// use toad_msg::opt::percent::decode;
+///
+/// assert_eq!(decode("a%20b").collect::<Vec<u8>>(), b"a b");
+/// assert_eq!(decode("100%").collect::<Vec<u8>>(), b"100%");
+/// ```
+pub fn decode<S: AsRef<[u8]> + ?Sized>(s: &S) -> Decode<'_> {
+  Decode { bytes: s.as_ref() }
+}
+
+/// A byte string that will be percent-encoded when formatted.
+///
+/// See [`encode`]
+#[derive(Debug, Clone, Copy)]
+pub struct Encode<'a> {
+  bytes: &'a [u8],
+  component: Component,
+}
+
+impl<'a> fmt::Display for Encode<'a> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+    self.bytes.iter().try_for_each(|&b| {
+                       if self.component.allows(b) {
+                         f.write_char(b as char)
+                       } else {
+                         f.write_char('%')?;
+                         f.write_char(HEX[(b >> 4) as usize] as char)?;
+                         f.write_char(HEX[(b & 0xF) as usize] as char)
+                       }
+                     })
+  }
+}
+
+/// Percent-encode bytes for use in a URI.
+///
+/// This does not allocate; the returned value
+/// implements [`Display`](core::fmt::Display).
+///
+/// ```
+/// use toad_msg::opt::percent::{encode, Component};
+///
+/// assert_eq!(encode("a b/c", Component::Path).to_string(), "a%20b%2Fc");
+/// assert_eq!(encode("a=b&c", Component::Query).to_string(), "a=b%26c");
+/// ```
+pub fn encode<S: AsRef<[u8]> + ?Sized>(s: &S, component: Component) -> Encode<'_> {
+  Encode { bytes: s.as_ref(),
+           component }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn decode_is_lenient() {
+    assert_eq!(decode("%").collect::<Vec<u8>>(), b"%");
+    assert_eq!(decode("%4").collect::<Vec<u8>>(), b"%4");
+    assert_eq!(decode("%zz%41").collect::<Vec<u8>>(), b"%zzA");
+  }
+
+  #[test]
+  fn decode_is_case_insensitive() {
+    assert_eq!(decode("%c3%A9").collect::<Vec<u8>>(), "é".as_bytes());
+  }
+
+  #[test]
+  fn unreserved_are_not_encoded() {
+    let s = "AZaz09-._~";
+    assert_eq!(encode(s, Component::Path).to_string(), s);
+    assert_eq!(encode(s, Component::Query).to_string(), s);
+  }
+
+  #[test]
+  fn round_trip() {
+    let bytes = (0..=255u8).collect::<Vec<_>>();
+
+    for c in [Component::Path, Component::Query] {
+      let encoded = encode(&bytes, c).to_string();
+      assert!(encoded.is_ascii());
+      assert_eq!(decode(&encoded).collect::<Vec<u8>>(), bytes);
+    }
+  }
+}
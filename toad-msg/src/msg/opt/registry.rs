@@ -0,0 +1,376 @@
+//! Option definitions as data.
+//!
+//! Each [`OptionDef`] describes an option's number, name, repeatability,
+//! allowed value lengths and value [format](OptFormat).
+//!
+//! [`KNOWN`] contains the definitions of every option known to toad;
+//! applications using vendor-specific or experimental options can
+//! define their own at compile time and [extend](Registry) the known ones:
+//!
+//! ```
+//! use toad_msg::opt::registry::{OptionDef, Registry, KNOWN};
+//! use toad_msg::{OptFormat, OptNumber};
+//!
+//! const DEVICE_ID: OptNumber = OptNumber(65000);
+//!
+//! const VENDOR: &[OptionDef] = &[OptionDef { number: DEVICE_ID,
+//!                                            name: "Device-Id",
+//!                                            repeatable: false,
+//!                                            min_len: 4,
+//!                                            max_len: 4,
+//!                                            format: OptFormat::Opaque }];
+//!
+//! const REGISTRY: Registry = Registry(&[KNOWN, VENDOR]);
+//!
+//! assert_eq!(REGISTRY.get(DEVICE_ID).map(|d| d.name), Some("Device-Id"));
+//! assert_eq!(REGISTRY.get(OptNumber(12)).map(|d| d.name),
+//!            Some("Content-Format"));
+//! assert_eq!(Registry::KNOWN.get(DEVICE_ID), None);
+//! ```
+//!
+//! The registry is consumed by this crate (option formatting, [`OptValueView`]s and
+//! [`Registry::validate`]); the `toad` runtime's option policy step does not consult it yet.
+
+use core::fmt::{self, Display, Formatter};
+use core::ops::RangeInclusive;
+use core::str::from_utf8;
+
+use toad_len::Len;
+use toad_map::Map;
+
+use super::known::{no_repeat, repeat};
+use super::{OptFormat, OptNumber, OptValueView, OptValueViewError, OptionMap};
+
+/// The definition of an option
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OptionDef {
+  /// The option's number
+  pub number: OptNumber,
+  /// The option's name, e.g. `"Content-Format"`
+  pub name: &'static str,
+  /// Whether the option may occur more than once in a message
+  #[doc = toad_macros::rfc_7252_doc!("5.4.5")]
+  pub repeatable: bool,
+  /// Minimum length of the option's value, in bytes
+  pub min_len: usize,
+  /// Maximum length of the option's value, in bytes
+  pub max_len: usize,
+  /// The format of the option's value
+  pub format: OptFormat,
+}
+
+impl OptionDef {
+  /// The allowed lengths of the option's value, in bytes
+  pub const fn len_range(&self) -> RangeInclusive<usize> {
+    self.min_len..=self.max_len
+  }
+
+  /// Decode a value of this option according to its [format](OptionDef.format)
+  ///
+  /// ```
+  /// use toad_msg::no_repeat::MAX_AGE;
+  /// use toad_msg::opt::registry::Registry;
+  /// use toad_msg::OptValueView;
+  ///
+  /// let max_age = Registry::KNOWN.get(MAX_AGE).unwrap();
+  /// assert_eq!(max_age.view(&[0, 60]), Ok(OptValueView::Uint(60)));
+  /// assert!(max_age.view(&[0, 0, 0, 0, 60]).is_err());
+  /// ```
+  pub fn view<'a>(&self, bytes: &'a [u8]) -> Result<OptValueView<'a>, OptValueViewError> {
+    if !self.len_range().contains(&bytes.len()) {
+      return Err(OptValueViewError::Length { number: self.number,
+                                             len: bytes.len(),
+                                             min: self.min_len,
+                                             max: self.max_len });
+    }
+
+    match self.format {
+      | OptFormat::Empty => Ok(OptValueView::Empty),
+      | OptFormat::Uint => {
+        Ok(OptValueView::Uint(bytes.iter()
+                                   .fold(0u64, |n, b| (n << 8) | u64::from(*b))))
+      },
+      | OptFormat::String => from_utf8(bytes).map(OptValueView::String)
+                                             .map_err(|e| OptValueViewError::Utf8(self.number, e)),
+      | OptFormat::Opaque => Ok(OptValueView::Opaque(bytes)),
+    }
+  }
+}
+
+/// An option in a message does not conform to its [`OptionDef`]
+///
+/// See [`Registry::validate`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvalidOption {
+  /// A non-repeatable option occurred more than once
+  #[allow(missing_docs)]
+  Repeated { number: OptNumber, count: usize },
+  /// An option's value is invalid
+  Value(OptValueViewError),
+}
+
+impl InvalidOption {
+  /// The number of the invalid option
+  pub fn number(&self) -> OptNumber {
+    match self {
+      | Self::Repeated { number, .. } => *number,
+      | Self::Value(OptValueViewError::Length { number, .. }) => *number,
+      | Self::Value(OptValueViewError::Utf8(number, _)) => *number,
+    }
+  }
+}
+
+impl From<OptValueViewError> for InvalidOption {
+  fn from(e: OptValueViewError) -> Self {
+    Self::Value(e)
+  }
+}
+
+/// Definitions of all options known to toad
+///
+/// Includes options defined in RFC7252, RFC7641 (Observe)
+/// and RFC7959 (Block-wise transfers).
+#[doc = toad_macros::rfc_7252_doc!("5.10")]
+pub const KNOWN: &[OptionDef] =
+  &[def(repeat::IF_MATCH, "If-Match", true, 0, 8, OptFormat::Opaque),
+    def(no_repeat::HOST, "Uri-Host", false, 1, 255, OptFormat::String),
+    def(repeat::ETAG, "ETag", true, 1, 8, OptFormat::Opaque),
+    def(no_repeat::IF_NONE_MATCH, "If-None-Match", false, 0, 0, OptFormat::Empty),
+    // <https://www.rfc-editor.org/rfc/rfc7641#section-2>
+    def(no_repeat::OBSERVE, "Observe", false, 0, 3, OptFormat::Uint),
+    def(no_repeat::PORT, "Uri-Port", false, 0, 2, OptFormat::Uint),
+    def(repeat::LOCATION_PATH, "Location-Path", true, 0, 255, OptFormat::String),
+    def(repeat::PATH, "Uri-Path", true, 0, 255, OptFormat::String),
+    def(no_repeat::CONTENT_FORMAT, "Content-Format", false, 0, 2, OptFormat::Uint),
+    def(no_repeat::MAX_AGE, "Max-Age", false, 0, 4, OptFormat::Uint),
+    def(repeat::QUERY, "Uri-Query", true, 0, 255, OptFormat::String),
+    def(no_repeat::ACCEPT, "Accept", false, 0, 2, OptFormat::Uint),
+    def(repeat::LOCATION_QUERY, "Location-Query", true, 0, 255, OptFormat::String),
+    // <https://www.rfc-editor.org/rfc/rfc7959#section-2.1>
+    def(no_repeat::BLOCK2, "Block2", false, 0, 3, OptFormat::Uint),
+    def(no_repeat::BLOCK1, "Block1", false, 0, 3, OptFormat::Uint),
+    def(no_repeat::SIZE2, "Size2", false, 0, 4, OptFormat::Uint),
+    def(no_repeat::PROXY_URI, "Proxy-Uri", false, 1, 1034, OptFormat::String),
+    def(no_repeat::PROXY_SCHEME, "Proxy-Scheme", false, 1, 255, OptFormat::String),
+    def(no_repeat::SIZE1, "Size1", false, 0, 4, OptFormat::Uint)];
+
+const fn def(number: OptNumber,
+             name: &'static str,
+             repeatable: bool,
+             min_len: usize,
+             max_len: usize,
+             format: OptFormat)
+             -> OptionDef {
+  OptionDef { number,
+              name,
+              repeatable,
+              min_len,
+              max_len,
+              format }
+}
+
+/// A set of [`OptionDef`]s
+///
+/// Definitions are looked up from the last slice to the first,
+/// so later slices may redefine options defined in earlier ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Registry(pub &'static [&'static [OptionDef]]);
+
+impl Default for Registry {
+  fn default() -> Self {
+    Self::KNOWN
+  }
+}
+
+impl Registry {
+  /// A registry containing only the options [known](KNOWN) to toad
+  pub const KNOWN: Registry = Registry(&[KNOWN]);
+
+  /// Get the definition of an option
+  pub const fn get(&self, n: OptNumber) -> Option<&'static OptionDef> {
+    let mut i = self.0.len();
+    while i > 0 {
+      i -= 1;
+
+      let defs = self.0[i];
+      let mut j = 0;
+      while j < defs.len() {
+        if defs[j].number.0 == n.0 {
+          return Some(&defs[j]);
+        }
+        j += 1;
+      }
+    }
+
+    None
+  }
+
+  /// Check that every option in `opts` with a definition in this registry
+  /// is not repeated (unless repeatable) and has a valid value.
+  ///
+  /// Options without a definition are not checked.
+  ///
+  /// ```
+  /// use toad_msg::alloc::Message;
+  /// use toad_msg::no_repeat::MAX_AGE;
+  /// use toad_msg::opt::registry::{InvalidOption, Registry};
+  /// use toad_msg::{Code, Id, MessageOptions, OptValue, Token, Type};
+  ///
+  /// let mut msg = Message::new(Type::Con, Code::GET, Id(1), Token(Default::default()));
+  /// msg.set_path("a/b").unwrap();
+  /// msg.add(MAX_AGE, OptValue(vec![60])).unwrap();
+  /// assert_eq!(Registry::KNOWN.validate(&msg.opts), Ok(()));
+  ///
+  /// // `add` won't repeat options that aren't repeatable,
+  /// // but messages from elsewhere may
+  /// assert!(msg.add(MAX_AGE, OptValue(vec![30])).is_err());
+  /// msg.opts.get_mut(&MAX_AGE).unwrap().push(OptValue(vec![30]));
+  /// assert_eq!(Registry::KNOWN.validate(&msg.opts),
+  ///            Err(InvalidOption::Repeated { number: MAX_AGE,
+  ///                                          count: 2 }));
+  /// ```
+  pub fn validate<O>(&self, opts: &O) -> Result<(), InvalidOption>
+    where O: OptionMap
+  {
+    match self.invalid(opts).next() {
+      | Some(e) => Err(e),
+      | None => Ok(()),
+    }
+  }
+
+  /// Like [`Registry::validate`], but rather than stopping at the first
+  /// invalid option, yield an error for each option in `opts` that
+  /// does not conform to its definition.
+  ///
+  /// ```
+  /// use toad_msg::alloc::Message;
+  /// use toad_msg::no_repeat::{CONTENT_FORMAT, MAX_AGE, PORT};
+  /// use toad_msg::opt::registry::Registry;
+  /// use toad_msg::{Code, Id, MessageOptions, OptValue, Token, Type};
+  ///
+  /// let mut msg = Message::new(Type::Con, Code::GET, Id(1), Token(Default::default()));
+  /// msg.set(MAX_AGE, OptValue(vec![0; 5])).unwrap();
+  /// msg.set(PORT, OptValue(vec![0x16, 0x33])).unwrap();
+  /// msg.set(CONTENT_FORMAT, OptValue(vec![0; 3])).unwrap();
+  ///
+  /// let invalid = Registry::KNOWN.invalid(&msg.opts)
+  ///                              .map(|e| e.number())
+  ///                              .collect::<Vec<_>>();
+  /// assert_eq!(invalid, vec![CONTENT_FORMAT, MAX_AGE]);
+  /// ```
+  pub fn invalid<'a, O>(&'a self, opts: &'a O) -> impl Iterator<Item = InvalidOption> + 'a
+    where O: OptionMap
+  {
+    opts.iter()
+        .filter_map(move |(n, vs)| self.get(*n).map(|d| (d, vs)))
+        .filter_map(|(def, values)| {
+          if !def.repeatable && values.len() > 1 {
+            return Some(InvalidOption::Repeated { number: def.number,
+                                                  count: values.len() });
+          }
+
+          values.iter()
+                .find_map(|v| def.view(v.as_bytes()).err())
+                .map(InvalidOption::from)
+        })
+  }
+
+  /// Format an option for humans using the definitions in this registry
+  ///
+  /// See [`OptDisplay`](crate::OptDisplay).
+  pub fn display<'a>(&'a self, n: OptNumber, bytes: &'a [u8]) -> impl Display + 'a {
+    RegisteredOpt(self, n, bytes)
+  }
+}
+
+/// An option value formatted using a [`Registry`]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RegisteredOpt<'a>(pub &'a Registry, pub OptNumber, pub &'a [u8]);
+
+impl<'a> Display for RegisteredOpt<'a> {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    crate::msg::display::fmt_opt(f, self.0, self.1, self.2)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn known_defs_are_sorted_and_unique() {
+    assert!(KNOWN.windows(2).all(|w| w[0].number < w[1].number));
+  }
+
+  #[test]
+  fn known_defs_agree_with_known_constants() {
+    KNOWN.iter().for_each(|d| {
+                  let no_repeat = [no_repeat::HOST,
+                                   no_repeat::IF_NONE_MATCH,
+                                   no_repeat::OBSERVE,
+                                   no_repeat::PORT,
+                                   no_repeat::CONTENT_FORMAT,
+                                   no_repeat::MAX_AGE,
+                                   no_repeat::ACCEPT,
+                                   no_repeat::BLOCK2,
+                                   no_repeat::BLOCK1,
+                                   no_repeat::SIZE2,
+                                   no_repeat::PROXY_URI,
+                                   no_repeat::PROXY_SCHEME,
+                                   no_repeat::SIZE1];
+                  assert_eq!(d.repeatable, !no_repeat.contains(&d.number), "{}", d.name);
+                });
+  }
+
+  #[test]
+  fn later_defs_override_earlier() {
+    const OVERRIDE: &[OptionDef] = &[def(no_repeat::MAX_AGE,
+                                         "Max-Age",
+                                         false,
+                                         0,
+                                         8,
+                                         OptFormat::Uint)];
+    const REGISTRY: Registry = Registry(&[KNOWN, OVERRIDE]);
+
+    assert_eq!(Registry::KNOWN.get(no_repeat::MAX_AGE).map(|d| d.max_len),
+               Some(4));
+    assert_eq!(REGISTRY.get(no_repeat::MAX_AGE).map(|d| d.max_len), Some(8));
+    assert_eq!(REGISTRY.get(OptNumber(65000)), None);
+  }
+
+  #[test]
+  fn invalid_yields_each_invalid_option() {
+    use crate::alloc::Message;
+    use crate::{Code, Id, MessageOptions, OptValue, Token, Type};
+
+    let mut msg = Message::new(Type::Con, Code::GET, Id(1), Token(Default::default()));
+    msg.set_path("a/b").unwrap();
+    msg.set(no_repeat::HOST, OptValue(vec![0xff])).unwrap();
+    msg.set(no_repeat::MAX_AGE, OptValue(vec![60])).unwrap();
+    msg.opts.get_mut(&no_repeat::MAX_AGE).unwrap().push(OptValue(vec![30]));
+
+    let invalid = Registry::KNOWN.invalid(&msg.opts).collect::<Vec<_>>();
+    assert_eq!(invalid.len(), 2);
+    assert!(matches!(invalid[0], InvalidOption::Value(OptValueViewError::Utf8(no_repeat::HOST, _))));
+    assert_eq!(invalid[1],
+               InvalidOption::Repeated { number: no_repeat::MAX_AGE,
+                                         count: 2 });
+    assert_eq!(Registry::KNOWN.validate(&msg.opts), Err(invalid[0]));
+  }
+
+  #[test]
+  fn validate_skips_undefined_options() {
+    use crate::alloc::Message;
+    use crate::{Code, Id, MessageOptions, OptValue, Token, Type};
+
+    let mut msg = Message::new(Type::Con, Code::GET, Id(1), Token(Default::default()));
+    msg.add(OptNumber(65000), OptValue(vec![])).unwrap();
+    msg.add(OptNumber(65000), OptValue(vec![1; 300])).unwrap();
+    assert_eq!(Registry::KNOWN.validate(&msg.opts), Ok(()));
+
+    const VENDOR: &[OptionDef] =
+      &[def(OptNumber(65000), "Vendor", false, 0, 8, OptFormat::Opaque)];
+    assert!(matches!(Registry(&[KNOWN, VENDOR]).validate(&msg.opts),
+                     Err(InvalidOption::Repeated { count: 2, .. })));
+  }
+}
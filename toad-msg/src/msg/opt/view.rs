@@ -0,0 +1,127 @@
+use core::str::Utf8Error;
+
+use toad_array::Array;
+
+use super::{OptNumber, OptValue};
+
+/// An option value, decoded according to the [format](super::OptFormat)
+/// registered for its [option number](OptNumber::format)
+///
+/// Values of options that are not known to toad are [`OptValueView::Opaque`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OptValueView<'a> {
+  /// See [`OptFormat::Empty`](super::OptFormat::Empty)
+  Empty,
+  /// See [`OptFormat::Uint`](super::OptFormat::Uint)
+  Uint(u64),
+  /// See [`OptFormat::String`](super::OptFormat::String)
+  String(&'a str),
+  /// See [`OptFormat::Opaque`](super::OptFormat::Opaque)
+  Opaque(&'a [u8]),
+}
+
+/// An option value could not be decoded
+/// according to the format registered for its option number
+///
+/// See [`OptValue::view`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptValueViewError {
+  /// The value's length is not [allowed](OptNumber::len_range) for the option
+  #[allow(missing_docs)]
+  Length {
+    number: OptNumber,
+    len: usize,
+    min: usize,
+    max: usize,
+  },
+  /// The value of a [string](super::OptFormat::String) option is not UTF-8
+  Utf8(OptNumber, Utf8Error),
+}
+
+impl<C> OptValue<C> where C: Array<Item = u8>
+{
+  /// Decode this value according to the [definition](OptNumber::def) of option `n`
+  ///
+  /// ```
+  /// use toad_msg::no_repeat::{CONTENT_FORMAT, HOST};
+  /// use toad_msg::{OptNumber, OptValue, OptValueView, OptValueViewError};
+  ///
+  /// let val = |bytes: &[u8]| OptValue(bytes.to_vec());
+  ///
+  /// assert_eq!(val(&[0, 50]).view(CONTENT_FORMAT),
+  ///            Ok(OptValueView::Uint(50)));
+  /// assert_eq!(val(b"cheese.com").view(HOST),
+  ///            Ok(OptValueView::String("cheese.com")));
+  /// assert_eq!(val(&[1, 2]).view(OptNumber(65001)),
+  ///            Ok(OptValueView::Opaque(&[1, 2])));
+  ///
+  /// assert_eq!(val(&[0, 0, 0, 50]).view(CONTENT_FORMAT),
+  ///            Err(OptValueViewError::Length { number: CONTENT_FORMAT,
+  ///                                            len: 4,
+  ///                                            min: 0,
+  ///                                            max: 2 }));
+  /// ```
+  pub fn view(&self, n: OptNumber) -> Result<OptValueView<'_>, OptValueViewError> {
+    match n.def() {
+      | Some(def) => def.view(self.as_bytes()),
+      | None => Ok(OptValueView::Opaque(self.as_bytes())),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::opt::known::no_repeat::{BLOCK2, HOST, IF_NONE_MATCH, MAX_AGE, PROXY_URI};
+  use crate::opt::known::repeat::{ETAG, PATH};
+
+  fn val(bytes: &[u8]) -> OptValue<Vec<u8>> {
+    OptValue(bytes.to_vec())
+  }
+
+  #[test]
+  fn uint() {
+    assert_eq!(val(&[]).view(MAX_AGE), Ok(OptValueView::Uint(0)));
+    assert_eq!(val(&[1]).view(MAX_AGE), Ok(OptValueView::Uint(1)));
+    assert_eq!(val(&[0xff, 0xff, 0xff, 0xff]).view(MAX_AGE),
+               Ok(OptValueView::Uint(u32::MAX as u64)));
+    assert_eq!(val(&[1, 0, 0]).view(BLOCK2),
+               Ok(OptValueView::Uint(0x010000)));
+    assert_eq!(val(&[1, 0, 0, 0, 0]).view(MAX_AGE),
+               Err(OptValueViewError::Length { number: MAX_AGE,
+                                               len: 5,
+                                               min: 0,
+                                               max: 4 }));
+  }
+
+  #[test]
+  fn string() {
+    assert_eq!(val(b"").view(PATH), Ok(OptValueView::String("")));
+    assert_eq!(val("café".as_bytes()).view(PATH),
+               Ok(OptValueView::String("café")));
+    assert!(matches!(val(&[0xff]).view(PATH),
+                     Err(OptValueViewError::Utf8(PATH, _))));
+    assert_eq!(val(b"").view(HOST),
+               Err(OptValueViewError::Length { number: HOST,
+                                               len: 0,
+                                               min: 1,
+                                               max: 255 }));
+    assert!(val(&[b'a'; 1034]).view(PROXY_URI).is_ok());
+    assert!(val(&[b'a'; 1035]).view(PROXY_URI).is_err());
+  }
+
+  #[test]
+  fn empty() {
+    assert_eq!(val(&[]).view(IF_NONE_MATCH), Ok(OptValueView::Empty));
+    assert!(val(&[0]).view(IF_NONE_MATCH).is_err());
+  }
+
+  #[test]
+  fn opaque() {
+    assert_eq!(val(&[1, 2]).view(ETAG), Ok(OptValueView::Opaque(&[1, 2])));
+    assert!(val(&[]).view(ETAG).is_err());
+    assert!(val(&[0; 9]).view(ETAG).is_err());
+    assert_eq!(val(&[0; 9]).view(OptNumber(65001)),
+               Ok(OptValueView::Opaque(&[0; 9])));
+  }
+}
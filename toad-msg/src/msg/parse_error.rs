@@ -1,3 +1,4 @@
+use super::opt::parse_error::OptParseError;
 #[allow(unused_imports)]
 use crate::Type;
 
@@ -11,9 +12,11 @@ pub enum MessageParseError {
   InvalidTokenLength(u8),
 
   /// Error parsing option
-  OptParseError(super::opt::parse_error::OptParseError),
+  OptParseError(OptParseError),
 
   /// The rest of the message contained more bytes than there was capacity for
+  ///
+  /// Contains the payload capacity.
   PayloadTooLong(usize),
 
   /// The message type is invalid (see [`Type`] for information & valid values)
@@ -25,4 +28,74 @@ impl MessageParseError {
   pub fn eof() -> Self {
     Self::UnexpectedEndOfStream
   }
+
+  /// If this error was caused by the message not fitting in
+  /// the fixed capacity of the message's collections, get
+  /// which capacity was exceeded.
+  ///
+  /// ```
+  /// use std::collections::BTreeMap;
+  ///
+  /// use tinyvec::ArrayVec;
+  /// use toad_msg::{CapacityError, Code, Id, Message, OptNumber, OptValue, Payload, Token,
+  ///                TryFromBytes, TryIntoBytes, Type};
+  ///
+  /// type Tiny = Message<ArrayVec<[u8; 4]>, BTreeMap<OptNumber, Vec<OptValue<Vec<u8>>>>>;
+  ///
+  /// let mut msg = toad_msg::alloc::Message::new(Type::Non, Code::POST, Id(1), Token(Default::default()));
+  /// msg.set_payload(Payload(vec![0; 8]));
+  /// let bytes = msg.try_into_bytes::<Vec<u8>>().unwrap();
+  ///
+  /// let err = Tiny::try_from_bytes(&bytes).unwrap_err();
+  /// assert_eq!(err.capacity_error(),
+  ///            Some(CapacityError::PayloadTooLong { capacity: 4 }));
+  /// ```
+  pub fn capacity_error(&self) -> Option<CapacityError> {
+    match self {
+      | Self::PayloadTooLong(capacity) => Some(CapacityError::PayloadTooLong { capacity: *capacity }),
+      | Self::OptParseError(OptParseError::TooManyOptions(capacity)) => {
+        Some(CapacityError::TooManyOptions { capacity: *capacity })
+      },
+      | Self::OptParseError(OptParseError::OptionValueTooLong { capacity, actual }) => {
+        Some(CapacityError::OptionValueTooLong { capacity: *capacity,
+                                                 actual: *actual })
+      },
+      | _ => None,
+    }
+  }
+}
+
+/// A fixed capacity that a message could not fit in while parsing
+///
+/// See [`MessageParseError::capacity_error`]
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum CapacityError {
+  /// The message had more options than the option map can store
+  TooManyOptions {
+    /// The number of options that can be stored
+    capacity: usize,
+  },
+  /// An option value was longer than an option value can store
+  OptionValueTooLong {
+    /// The number of bytes an option value can store
+    capacity: usize,
+    /// The length of the option value in the message
+    actual: usize,
+  },
+  /// The payload was longer than the payload can store
+  PayloadTooLong {
+    /// The number of bytes the payload can store
+    capacity: usize,
+  },
+}
+
+impl CapacityError {
+  /// The capacity that was exceeded
+  pub fn capacity(&self) -> usize {
+    match self {
+      | Self::TooManyOptions { capacity }
+      | Self::OptionValueTooLong { capacity, .. }
+      | Self::PayloadTooLong { capacity } => *capacity,
+    }
+  }
 }
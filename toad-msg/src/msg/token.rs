@@ -23,8 +23,38 @@ impl Token {
     Token(Into::<[u8; 8]>::into(digest.finalize()).into())
   }
 
+  /// Create a token from a byte slice, returning `None`
+  /// if the slice is longer than the maximum token length of 8 bytes.
+  ///
+  /// ```
+  /// use toad_msg::Token;
+  ///
+  /// assert_eq!(Token::try_from_slice(&[1, 2]).map(|t| t.len()), Some(2));
+  /// assert_eq!(Token::try_from_slice(&[0; 9]), None);
+  /// ```
+  pub fn try_from_slice(bytes: &[u8]) -> Option<Token> {
+    tinyvec::ArrayVec::try_from(bytes).ok().map(Token)
+  }
+
   /// Convert a reference to a Token to a byte slice
   pub fn as_bytes(&self) -> &[u8] {
     &self.0
   }
+
+  /// The length of this token in bytes (0 to 8)
+  pub fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  /// Is this token zero-length?
+  ///
+  /// ```
+  /// use toad_msg::Token;
+  ///
+  /// assert!(Token(Default::default()).is_empty());
+  /// assert!(!Token::opaque(&[]).is_empty());
+  /// ```
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
 }
@@ -45,3 +45,22 @@ impl TryFrom<u8> for Type {
     }
   }
 }
+
+/// Formats the type as it is abbreviated in RFC7252, e.g. `CON` or `RST`
+///
+/// ```
+/// use toad_msg::Type;
+///
+/// assert_eq!(Type::Con.to_string(), "CON");
+/// assert_eq!(Type::Reset.to_string(), "RST");
+/// ```
+impl core::fmt::Display for Type {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.write_str(match self {
+                  | Type::Con => "CON",
+                  | Type::Non => "NON",
+                  | Type::Ack => "ACK",
+                  | Type::Reset => "RST",
+                })
+  }
+}
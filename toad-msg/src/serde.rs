@@ -0,0 +1,441 @@
+//! [`Serialize`] & [`Deserialize`] implementations for message structs,
+//! used to record messages as fixtures (e.g. JSON) and replay them in tests & tooling.
+//!
+//! Byte strings ([`Token`], [`OptValue`], [`Payload`]) are represented as arrays of bytes.
+//! [`Code`]s are represented as strings like `"2.05"` and [`Type`]s as strings like `"CON"`.
+//! Options are represented as a map from [`OptNumber`] to an array of values.
+//!
+//! Deserializing into collections with a fixed capacity (e.g. [`tinyvec::ArrayVec`])
+//! will fail when the input has more elements than they can hold.
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use ::serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use ::serde::ser::{Serialize, SerializeMap, SerializeStruct, Serializer};
+use toad_array::{Array, Indexed};
+use toad_len::Len;
+use toad_map::Map;
+
+use crate::{Code, Id, Message, OptNumber, OptValue, OptionMap, Payload, Token, Type, Version};
+
+/// Deserializes a sequence into an [`Array`],
+/// failing if it exceeds the array's capacity.
+struct ArrayVisitor<A>(PhantomData<A>);
+
+impl<'de, A> Visitor<'de> for ArrayVisitor<A>
+  where A: Array,
+        A::Item: Deserialize<'de>
+{
+  type Value = A;
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match A::CAPACITY {
+      | Some(max) => write!(f, "a sequence of at most {} elements", max),
+      | None => f.write_str("a sequence"),
+    }
+  }
+
+  fn visit_seq<S>(self, mut seq: S) -> Result<A, S::Error>
+    where S: SeqAccess<'de>
+  {
+    let mut a = A::default();
+
+    while let Some(item) = seq.next_element()? {
+      if a.is_full() {
+        return Err(de::Error::invalid_length(a.len() + 1, &self));
+      }
+
+      a.append(item);
+    }
+
+    Ok(a)
+  }
+}
+
+fn deserialize_array<'de, D, A>(d: D) -> Result<A, D::Error>
+  where D: Deserializer<'de>,
+        A: Array,
+        A::Item: Deserialize<'de>
+{
+  d.deserialize_seq(ArrayVisitor(PhantomData))
+}
+
+/// Deserializes a string with [`FromStrVisitor::parse`]
+struct FromStrVisitor<T> {
+  expecting: &'static str,
+  parse: fn(&str) -> Option<T>,
+}
+
+impl<'de, T> Visitor<'de> for FromStrVisitor<T> {
+  type Value = T;
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(self.expecting)
+  }
+
+  fn visit_str<E>(self, s: &str) -> Result<T, E>
+    where E: de::Error
+  {
+    (self.parse)(s).ok_or_else(|| E::invalid_value(de::Unexpected::Str(s), &self))
+  }
+}
+
+impl Serialize for Code {
+  fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    s.collect_str(self)
+  }
+}
+
+impl<'de> Deserialize<'de> for Code {
+  fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+    let parse = |s: &str| {
+      let (class, detail) = s.split_once('.')?;
+      match (class.parse::<u8>().ok()?, detail.len(), detail.parse::<u8>().ok()?) {
+        | (class @ 0..=0b111, 2, detail @ 0..=0b11111) => Some(Code::new(class, detail)),
+        | _ => None,
+      }
+    };
+
+    d.deserialize_str(FromStrVisitor { expecting: "a message code like \"2.05\"",
+                                       parse })
+  }
+}
+
+impl Serialize for Type {
+  fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    s.collect_str(self)
+  }
+}
+
+impl<'de> Deserialize<'de> for Type {
+  fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+    let parse = |s: &str| match s {
+      | "CON" => Some(Type::Con),
+      | "NON" => Some(Type::Non),
+      | "ACK" => Some(Type::Ack),
+      | "RST" => Some(Type::Reset),
+      | _ => None,
+    };
+
+    d.deserialize_str(FromStrVisitor { expecting: "one of \"CON\", \"NON\", \"ACK\", \"RST\"",
+                                       parse })
+  }
+}
+
+impl Serialize for Id {
+  fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_u16(self.0)
+  }
+}
+
+impl<'de> Deserialize<'de> for Id {
+  fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+    u16::deserialize(d).map(Id)
+  }
+}
+
+impl Serialize for Version {
+  fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_u8(self.0)
+  }
+}
+
+impl<'de> Deserialize<'de> for Version {
+  fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+    u8::deserialize(d).map(Version)
+  }
+}
+
+impl Serialize for OptNumber {
+  fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_u32(self.0)
+  }
+}
+
+impl<'de> Deserialize<'de> for OptNumber {
+  fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+    u32::deserialize(d).map(OptNumber)
+  }
+}
+
+impl Serialize for Token {
+  fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    s.collect_seq(self.as_bytes())
+  }
+}
+
+impl<'de> Deserialize<'de> for Token {
+  fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+    deserialize_array(d).map(Token)
+  }
+}
+
+impl<C> Serialize for OptValue<C> where C: Array<Item = u8>
+{
+  fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    s.collect_seq(self.as_bytes())
+  }
+}
+
+impl<'de, C> Deserialize<'de> for OptValue<C> where C: Array<Item = u8>
+{
+  fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+    deserialize_array(d).map(OptValue)
+  }
+}
+
+impl<C> Serialize for Payload<C> where C: Array<Item = u8>
+{
+  fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    s.collect_seq(self.as_bytes())
+  }
+}
+
+impl<'de, C> Deserialize<'de> for Payload<C> where C: Array<Item = u8>
+{
+  fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+    deserialize_array(d).map(Payload)
+  }
+}
+
+/// Serializes the values of one option as a sequence
+struct OptValues<'a, O: OptionMap>(&'a O::OptValues);
+
+impl<'a, O> Serialize for OptValues<'a, O> where O: OptionMap
+{
+  fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    s.collect_seq(self.0.iter())
+  }
+}
+
+/// Serializes an [`OptionMap`] as a map of numbers to values
+struct Opts<'a, O>(&'a O);
+
+impl<'a, O> Serialize for Opts<'a, O> where O: OptionMap
+{
+  fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    let mut map = s.serialize_map(Some(self.0.len()))?;
+    self.0
+        .iter()
+        .try_for_each(|(n, vs)| map.serialize_entry(n, &OptValues::<O>(vs)))?;
+    map.end()
+  }
+}
+
+/// Deserializes an [`OptionMap`] from a map of numbers to values
+struct OptsVisitor<O>(PhantomData<O>);
+
+impl<'de, O> Visitor<'de> for OptsVisitor<O> where O: OptionMap
+{
+  type Value = O;
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("a map of option numbers to arrays of option values")
+  }
+
+  fn visit_map<M>(self, mut map: M) -> Result<O, M::Error>
+    where M: MapAccess<'de>
+  {
+    /// Seed for the values of a single option
+    struct Values<O>(PhantomData<O>);
+
+    impl<'de, O> de::DeserializeSeed<'de> for Values<O> where O: OptionMap
+    {
+      type Value = O::OptValues;
+
+      fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        deserialize_array(d)
+      }
+    }
+
+    let mut opts = O::default();
+
+    while let Some(num) = map.next_key::<OptNumber>()? {
+      let values = map.next_value_seed(Values::<O>(PhantomData))?;
+
+      if opts.has(&num) {
+        return Err(de::Error::custom(format_args!("duplicate option {}", num.0)));
+      }
+
+      opts.insert(num, values)
+          .map_err(|_| de::Error::invalid_length(opts.len() + 1, &self))?;
+    }
+
+    Ok(opts)
+  }
+}
+
+const FIELDS: &[&str] = &["id", "ty", "ver", "token", "code", "opts", "payload"];
+
+impl<C, O> Serialize for Message<C, O>
+  where C: Array<Item = u8>,
+        O: OptionMap
+{
+  fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    let mut msg = s.serialize_struct("Message", FIELDS.len())?;
+    msg.serialize_field("id", &self.id)?;
+    msg.serialize_field("ty", &self.ty)?;
+    msg.serialize_field("ver", &self.ver)?;
+    msg.serialize_field("token", &self.token)?;
+    msg.serialize_field("code", &self.code)?;
+    msg.serialize_field("opts", &Opts(&self.opts))?;
+    msg.serialize_field("payload", &self.payload)?;
+    msg.end()
+  }
+}
+
+impl<'de, C, O> Deserialize<'de> for Message<C, O>
+  where C: Array<Item = u8>,
+        O: OptionMap
+{
+  fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+    enum Field {
+      Id,
+      Ty,
+      Ver,
+      Token,
+      Code,
+      Opts,
+      Payload,
+    }
+
+    impl<'de> Deserialize<'de> for Field {
+      fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct FieldVisitor;
+
+        impl<'de> Visitor<'de> for FieldVisitor {
+          type Value = Field;
+
+          fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a message field")
+          }
+
+          fn visit_str<E: de::Error>(self, s: &str) -> Result<Field, E> {
+            match s {
+              | "id" => Ok(Field::Id),
+              | "ty" => Ok(Field::Ty),
+              | "ver" => Ok(Field::Ver),
+              | "token" => Ok(Field::Token),
+              | "code" => Ok(Field::Code),
+              | "opts" => Ok(Field::Opts),
+              | "payload" => Ok(Field::Payload),
+              | other => Err(E::unknown_field(other, FIELDS)),
+            }
+          }
+        }
+
+        d.deserialize_identifier(FieldVisitor)
+      }
+    }
+
+    /// Seed for the options of a message
+    struct OptsSeed<O>(PhantomData<O>);
+
+    impl<'de, O> de::DeserializeSeed<'de> for OptsSeed<O> where O: OptionMap
+    {
+      type Value = O;
+
+      fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<O, D::Error> {
+        d.deserialize_map(OptsVisitor(PhantomData))
+      }
+    }
+
+    struct MessageVisitor<C, O>(PhantomData<(C, O)>);
+
+    impl<'de, C, O> Visitor<'de> for MessageVisitor<C, O>
+      where C: Array<Item = u8>,
+            O: OptionMap
+    {
+      type Value = Message<C, O>;
+
+      fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a CoAP message")
+      }
+
+      fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+        where M: MapAccess<'de>
+      {
+        let mut id = None;
+        let mut ty = None;
+        let mut ver = None;
+        let mut token = None;
+        let mut code = None;
+        let mut opts = None;
+        let mut payload = None;
+
+        while let Some(field) = map.next_key::<Field>()? {
+          match field {
+            | Field::Id => id = Some(map.next_value()?),
+            | Field::Ty => ty = Some(map.next_value()?),
+            | Field::Ver => ver = Some(map.next_value()?),
+            | Field::Token => token = Some(map.next_value()?),
+            | Field::Code => code = Some(map.next_value()?),
+            | Field::Opts => opts = Some(map.next_value_seed(OptsSeed::<O>(PhantomData))?),
+            | Field::Payload => payload = Some(map.next_value()?),
+          }
+        }
+
+        Ok(Message { id: id.ok_or_else(|| de::Error::missing_field("id"))?,
+                     ty: ty.ok_or_else(|| de::Error::missing_field("ty"))?,
+                     ver: ver.unwrap_or_default(),
+                     token: token.ok_or_else(|| de::Error::missing_field("token"))?,
+                     code: code.ok_or_else(|| de::Error::missing_field("code"))?,
+                     opts: opts.unwrap_or_default(),
+                     payload: payload.unwrap_or_default() })
+      }
+    }
+
+    d.deserialize_struct("Message", FIELDS, MessageVisitor(PhantomData))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use tinyvec::ArrayVec;
+
+  use super::*;
+  use crate::{ContentFormat, MessageOptions};
+
+  #[test]
+  fn message_round_trips_through_json() {
+    let mut msg = crate::alloc::Message::new(Type::Con,
+                                             Code::GET,
+                                             Id(1),
+                                             Token::try_from_slice(&[0xFE]).unwrap());
+    msg.set_path("sensors/temp").unwrap();
+    msg.set_content_format(ContentFormat::Json).unwrap();
+    msg.payload = Payload(b"{}".to_vec());
+
+    let json = serde_json::to_string(&msg).unwrap();
+    assert_eq!(json,
+               r#"{"id":1,"ty":"CON","ver":1,"token":[254],"code":"0.01","opts":{"11":[[115,101,110,115,111,114,115],[116,101,109,112]],"12":[[0,50]]},"payload":[123,125]}"#);
+    assert_eq!(serde_json::from_str::<crate::alloc::Message>(&json).unwrap(), msg);
+  }
+
+  #[test]
+  fn missing_optional_fields_are_defaulted() {
+    let msg = serde_json::from_str::<crate::alloc::Message>(
+      r#"{"id":1,"ty":"NON","token":[],"code":"2.05"}"#,
+    ).unwrap();
+
+    assert_eq!(msg,
+               crate::alloc::Message::new(Type::Non, Code::new(2, 5), Id(1), Token(Default::default())));
+  }
+
+  #[test]
+  fn invalid_codes_are_rejected() {
+    assert!(serde_json::from_str::<Code>(r#""2.5""#).is_err());
+    assert!(serde_json::from_str::<Code>(r#""8.00""#).is_err());
+    assert_eq!(serde_json::from_str::<Code>(r#""4.04""#).unwrap(), Code::new(4, 4));
+  }
+
+  #[test]
+  fn fixed_capacity_overflow_is_rejected() {
+    assert!(serde_json::from_str::<Token>("[1,2,3,4,5,6,7,8,9]").is_err());
+    assert!(serde_json::from_str::<Payload<ArrayVec<[u8; 2]>>>("[1,2,3]").is_err());
+    assert_eq!(serde_json::from_str::<Payload<ArrayVec<[u8; 2]>>>("[1,2]").unwrap()
+                                                                          .as_bytes(),
+               &[1, 2]);
+  }
+}
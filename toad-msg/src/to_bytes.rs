@@ -1,3 +1,5 @@
+use core::hash::Hasher;
+
 use tinyvec::ArrayVec;
 use toad_len::Len;
 
@@ -83,7 +85,7 @@ impl<PayloadBytes: Array<Item = u8>, Options: OptionMap> TryIntoBytes
     bytes.extend(id);
     bytes.extend(token);
 
-    for opt in self.opts.opts() {
+    for opt in self.opts.opt_refs_sorted() {
       opt.extend_bytes(&mut bytes);
     }
 
@@ -96,6 +98,72 @@ impl<PayloadBytes: Array<Item = u8>, Options: OptionMap> TryIntoBytes
   }
 }
 
+impl<PayloadBytes: Array<Item = u8>, Options: OptionMap> Message<PayloadBytes, Options> {
+  /// Feed the bytes that [`TryIntoBytes::try_into_bytes`] would yield
+  /// into a [`Hasher`], without serializing the message into a buffer first.
+  ///
+  /// For hashers whose output does not depend on how the input is split
+  /// across calls to [`Hasher::write`] (like [`toad_hash::Blake2Hasher`]),
+  /// this is equivalent to hashing the serialized message.
+  ///
+  /// ```
+  /// use core::hash::Hasher;
+  ///
+  /// use toad_hash::Blake2Hasher;
+  /// use toad_msg::alloc::Message;
+  /// use toad_msg::{Code, Id, Payload, Token, TryIntoBytes, Type};
+  ///
+  /// let msg = Message { id: Id(1),
+  ///                     ty: Type::Con,
+  ///                     ver: Default::default(),
+  ///                     token: Token(Default::default()),
+  ///                     code: Code::new(2, 05),
+  ///                     opts: Default::default(),
+  ///                     payload: Payload(b"hello!".to_vec()) };
+  ///
+  /// let mut incremental = Blake2Hasher::new();
+  /// msg.hash_wire_format(&mut incremental);
+  ///
+  /// let mut serialized = Blake2Hasher::new();
+  /// serialized.write(&msg.try_into_bytes::<Vec<u8>>().unwrap());
+  ///
+  /// assert_eq!(incremental.finish(), serialized.finish());
+  /// ```
+  pub fn hash_wire_format<H: Hasher>(&self, hasher: &mut H) {
+    let byte1: u8 = Byte1 { tkl: self.token.0.len() as u8,
+                            ver: self.ver,
+                            ty: self.ty }.into();
+    let code: u8 = self.code.into();
+    let id: [u8; 2] = self.id.into();
+
+    hasher.write(&[byte1, code]);
+    hasher.write(&id);
+    hasher.write(&self.token.0);
+
+    for opt in self.opts.opt_refs_sorted() {
+      let (del, del_bytes) = opt_len_or_delta(opt.delta.0);
+      let (len, len_bytes) = opt_len_or_delta(opt.value.0.len() as u16);
+
+      hasher.write(&[del << 4 | len]);
+
+      if let Some(bs) = del_bytes {
+        hasher.write(&bs);
+      }
+
+      if let Some(bs) = len_bytes {
+        hasher.write(&bs);
+      }
+
+      hasher.write(&opt.value.0);
+    }
+
+    if !self.payload.0.is_empty() {
+      hasher.write(&[0b11111111]);
+      hasher.write(&self.payload.0);
+    }
+  }
+}
+
 pub(crate) fn opt_len_or_delta(val: u16) -> (u8, Option<ArrayVec<[u8; 2]>>) {
   match val {
     | n if n >= 269 => {
@@ -224,6 +292,58 @@ mod tests {
                      });
   }
 
+  #[test]
+  fn hash_wire_format() {
+    use toad_hash::Blake2Hasher;
+
+    let hash_bytes = |bytes: &[u8]| {
+      let mut hasher = Blake2Hasher::new();
+      hasher.write(bytes);
+      hasher.finish()
+    };
+
+    let hash_msg = |msg: &alloc::Message| {
+      let mut hasher = Blake2Hasher::new();
+      msg.hash_wire_format(&mut hasher);
+      hasher.finish()
+    };
+
+    let (msg, expected) = test_msg();
+    assert_eq!(hash_msg(&msg), hash_bytes(&expected));
+
+    let mut msg = msg;
+    msg.payload = Payload(Default::default());
+    msg.opts
+       .insert(OptNumber(300), vec![OptValue(core::iter::repeat(1).take(300).collect())]);
+    let bytes: Vec<u8> = msg.clone().try_into_bytes().unwrap();
+    assert_eq!(hash_msg(&msg), hash_bytes(&bytes));
+  }
+
+  #[test]
+  fn hashmap_opts_ascending() {
+    use std::collections::HashMap;
+
+    let (msg, _) = test_msg();
+    let mut msg = msg;
+    for n in [60, 1, 35, 12, 3, 4, 11] {
+      msg.opts.insert(OptNumber(n), vec![OptValue(vec![n as u8])]);
+    }
+
+    let hashed = Message::<Vec<u8>, HashMap<OptNumber, Vec<OptValue<Vec<u8>>>>> {
+      id: msg.id,
+      ty: msg.ty,
+      ver: msg.ver,
+      token: msg.token,
+      code: msg.code,
+      opts: msg.opts.clone().into_iter().collect(),
+      payload: msg.payload.clone(),
+    };
+
+    let expected: Vec<u8> = msg.try_into_bytes().unwrap();
+    let actual: Vec<u8> = hashed.try_into_bytes().unwrap();
+    assert_eqb_iter!(actual, expected);
+  }
+
   #[test]
   fn no_payload_marker() {
     let msg = alloc::Message { id: Id(0),
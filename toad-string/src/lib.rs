@@ -39,6 +39,10 @@ pub struct FromUtf8Error;
 #[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Hash, Debug, Default)]
 pub struct FromUtf16Error;
 
+/// A string did not fit in the capacity of a [`String`]
+#[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Hash, Debug, Default)]
+pub struct CapacityError;
+
 /// [`String`]-returning copy of [`std::format`]
 ///
 /// ```
@@ -402,3 +406,203 @@ impl<const N: usize> PartialEq<&String<N>> for &str {
     *self == other.as_str()
   }
 }
+
+/// Operations common to [`String`] and [`std::string::String`],
+/// for code that is generic over whether strings live on the heap.
+///
+/// ```
+/// use toad_string::{String, Str};
+///
+/// fn greet<S: Str>(name: &str) -> Result<S, toad_string::CapacityError> {
+///   let mut s = S::try_from_str("hello, ")?;
+///   s.push_str(name)?;
+///   Ok(s)
+/// }
+///
+/// assert_eq!(greet::<std::string::String>("jason").unwrap().as_str(),
+///            "hello, jason");
+/// assert_eq!(greet::<String<16>>("jason").unwrap().as_str(), "hello, jason");
+/// assert!(greet::<String<8>>("jason").is_err());
+/// ```
+pub trait Str: Sized + Default + AsRef<str> {
+  /// Gets a string slice containing the entire string
+  fn as_str(&self) -> &str;
+
+  /// Append a string slice onto the end of this string,
+  /// leaving it unchanged if there is not enough room for all of `s`.
+  fn push_str(&mut self, s: &str) -> Result<(), CapacityError>;
+
+  /// Copy a string slice into a new string
+  fn try_from_str(s: &str) -> Result<Self, CapacityError> {
+    let mut new = Self::default();
+    new.push_str(s).map(|_| new)
+  }
+}
+
+impl<const N: usize> Str for String<N> {
+  fn as_str(&self) -> &str {
+    String::as_str(self)
+  }
+
+  fn push_str(&mut self, s: &str) -> Result<(), CapacityError> {
+    if self.len() + s.len() > N {
+      Err(CapacityError)
+    } else {
+      String::push_str(self, s);
+      Ok(())
+    }
+  }
+}
+
+#[cfg(feature = "alloc")]
+impl Str for std_alloc::string::String {
+  fn as_str(&self) -> &str {
+    std_alloc::string::String::as_str(self)
+  }
+
+  fn push_str(&mut self, s: &str) -> Result<(), CapacityError> {
+    std_alloc::string::String::push_str(self, s);
+    Ok(())
+  }
+}
+
+/// A string slice or an owned [`String`], similar to
+/// [`Cow<'a, str>`](std::borrow::Cow) without requiring an allocator.
+///
+/// This allows functions that usually yield a borrowed string
+/// (e.g. the value of an option) to sometimes yield a string that
+/// had to be built (e.g. by joining repeated options, or formatting a log message).
+///
+/// ```
+/// use toad_string::{String, StringOrRef};
+///
+/// fn path<'a>(segments: &[&'a str]) -> StringOrRef<'a, 64> {
+///   match segments {
+///     | [one] => StringOrRef::Ref(one),
+///     | many => {
+///       let mut s = String::<64>::new();
+///       many.iter().enumerate().for_each(|(ix, seg)| {
+///                                if ix > 0 {
+///                                  s.push('/');
+///                                }
+///                                s.push_str(seg);
+///                              });
+///       StringOrRef::Owned(s)
+///     },
+///   }
+/// }
+///
+/// assert_eq!(path(&["foo"]), "foo");
+/// assert!(!path(&["foo"]).is_owned());
+/// assert_eq!(path(&["foo", "bar"]), "foo/bar");
+/// assert!(path(&["foo", "bar"]).is_owned());
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub enum StringOrRef<'a, const N: usize> {
+  /// A borrowed string slice
+  Ref(&'a str),
+  /// An owned stack-allocated [`String`]
+  Owned(String<N>),
+}
+
+impl<'a, const N: usize> StringOrRef<'a, N> {
+  /// Gets a string slice containing the entire string
+  pub fn as_str(&self) -> &str {
+    match self {
+      | Self::Ref(s) => s,
+      | Self::Owned(s) => s.as_str(),
+    }
+  }
+
+  /// Is this an owned [`String`]?
+  pub fn is_owned(&self) -> bool {
+    matches!(self, Self::Owned(_))
+  }
+
+  /// Get an owned [`String`], copying the string slice
+  /// if this is borrowed.
+  ///
+  /// ```
+  /// use toad_string::{CapacityError, String, StringOrRef};
+  ///
+  /// assert_eq!(StringOrRef::<'_, 8>::Ref("foo").into_owned(),
+  ///            Ok(String::<8>::from("foo")));
+  /// assert_eq!(StringOrRef::<'_, 2>::Ref("foo").into_owned(),
+  ///            Err(CapacityError));
+  /// ```
+  pub fn into_owned(self) -> Result<String<N>, CapacityError> {
+    match self {
+      | Self::Ref(s) => String::try_from_str(s),
+      | Self::Owned(s) => Ok(s),
+    }
+  }
+
+  /// Get a mutable reference to an owned [`String`],
+  /// copying the string slice into one first if this is borrowed.
+  pub fn to_mut(&mut self) -> Result<&mut String<N>, CapacityError> {
+    match self {
+      | Self::Owned(s) => Ok(s),
+      | Self::Ref(s) => {
+        *self = Self::Owned(String::try_from_str(s)?);
+        self.to_mut()
+      },
+    }
+  }
+}
+
+impl<'a, const N: usize> Default for StringOrRef<'a, N> {
+  fn default() -> Self {
+    Self::Ref("")
+  }
+}
+
+impl<'a, const N: usize> From<&'a str> for StringOrRef<'a, N> {
+  fn from(s: &'a str) -> Self {
+    Self::Ref(s)
+  }
+}
+
+impl<'a, const N: usize> From<String<N>> for StringOrRef<'a, N> {
+  fn from(s: String<N>) -> Self {
+    Self::Owned(s)
+  }
+}
+
+impl<'a, const N: usize> Deref for StringOrRef<'a, N> {
+  type Target = str;
+  fn deref(&self) -> &str {
+    self.as_str()
+  }
+}
+
+impl<'a, const N: usize> AsRef<str> for StringOrRef<'a, N> {
+  fn as_ref(&self) -> &str {
+    self.as_str()
+  }
+}
+
+impl<'a, const N: usize> Display for StringOrRef<'a, N> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    write!(f, "{}", self.as_str())
+  }
+}
+
+impl<'a, 'b, const N: usize, const M: usize> PartialEq<StringOrRef<'b, M>> for StringOrRef<'a, N> {
+  fn eq(&self, other: &StringOrRef<'b, M>) -> bool {
+    self.as_str() == other.as_str()
+  }
+}
+
+impl<'a, const N: usize> Eq for StringOrRef<'a, N> {}
+
+impl<'a, const N: usize> PartialEq<&str> for StringOrRef<'a, N> {
+  fn eq(&self, other: &&str) -> bool {
+    self.as_str() == *other
+  }
+}
+
+impl<'a, const N: usize> PartialEq<str> for StringOrRef<'a, N> {
+  fn eq(&self, other: &str) -> bool {
+    self.as_str() == other
+  }
+}
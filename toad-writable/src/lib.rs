@@ -42,10 +42,94 @@ use toad_array::Array;
 /// write!(stringish, "Your number is: {}", 123).ok();
 /// assert_eq!(stringish.as_str(), "Your number is: 123");
 /// ```
+///
+/// Writes that would exceed the capacity of the collection fail,
+/// unless the `Writable` is [`truncating`](Writable::truncating):
+/// ```
+/// use core::fmt::Write;
+///
+/// use tinyvec::ArrayVec;
+/// use toad_writable::Writable;
+///
+/// let mut strict = Writable::from(ArrayVec::<[u8; 8]>::new());
+/// assert!(write!(strict, "Your number is: {}", 123).is_err());
+/// assert!(strict.overflowed());
+///
+/// let mut lenient = Writable::from(ArrayVec::<[u8; 8]>::new()).truncating();
+/// assert!(write!(lenient, "Your number is: {}", 123).is_ok());
+/// assert_eq!(lenient.as_str(), "Your num");
+/// assert!(lenient.overflowed());
+/// ```
 #[derive(Clone, Copy, Debug, Default)]
-pub struct Writable<A: Array<Item = u8>>(A);
+pub struct Writable<A: Array<Item = u8>> {
+  buf: A,
+  truncating: bool,
+  overflowed: bool,
+}
 
 impl<A: Array<Item = u8>> Writable<A> {
+  /// Make writes that would exceed the capacity of the collection
+  /// write as much as fits and succeed, rather than failing.
+  ///
+  /// Whether anything was cut off can be checked with [`Writable::overflowed`].
+  pub fn truncating(mut self) -> Self {
+    self.truncating = true;
+    self
+  }
+
+  /// Is this `Writable` [`truncating`](Writable::truncating)?
+  pub fn is_truncating(&self) -> bool {
+    self.truncating
+  }
+
+  /// Has a write been cut off or rejected because the collection was full?
+  pub fn overflowed(&self) -> bool {
+    self.overflowed
+  }
+
+  /// Write as much of `s` as fits in the collection,
+  /// yielding the number of bytes written.
+  ///
+  /// Strings are never split in the middle of a UTF-8 character,
+  /// so this may write less than the remaining capacity.
+  ///
+  /// If fewer than `s.len()` bytes were written, [`Writable::overflowed`]
+  /// will be `true`.
+  ///
+  /// ```
+  /// use tinyvec::ArrayVec;
+  /// use toad_writable::Writable;
+  ///
+  /// let mut w = Writable::from(ArrayVec::<[u8; 4]>::new());
+  /// assert_eq!(w.try_write_str("abc"), 3);
+  /// assert!(!w.overflowed());
+  ///
+  /// // "é" is 2 bytes, and only 1 byte of capacity remains
+  /// assert_eq!(w.try_write_str("é"), 0);
+  /// assert!(w.overflowed());
+  /// assert_eq!(w.as_str(), "abc");
+  /// ```
+  pub fn try_write_str(&mut self, s: &str) -> usize {
+    let fits = match A::CAPACITY {
+      | Some(max) => max.saturating_sub(self.buf.len()),
+      | None => s.len(),
+    };
+
+    let n = if fits >= s.len() {
+      s.len()
+    } else {
+      (0..=fits).rev().find(|ix| s.is_char_boundary(*ix)).unwrap_or(0)
+    };
+
+    self.buf.extend(s[..n].bytes());
+
+    if n < s.len() {
+      self.overflowed = true;
+    }
+
+    n
+  }
+
   /// Attempt to read the data in the buffer
   /// as a UTF8 string slice
   pub fn as_str(&self) -> &str {
@@ -54,17 +138,17 @@ impl<A: Array<Item = u8>> Writable<A> {
 
   /// Get a slice of the byte buffer
   pub fn as_slice(&self) -> &[u8] {
-    &self.0
+    &self.buf
   }
 
   /// Get a mutable slice of the byte buffer
   pub fn as_mut_slice(&mut self) -> &mut [u8] {
-    &mut self.0
+    &mut self.buf
   }
 
   /// Get the collection wrapped by this `Writable`
   pub fn unwrap(self) -> A {
-    self.0
+    self.buf
   }
 }
 
@@ -77,7 +161,9 @@ impl<A> Display for Writable<A> where A: Array<Item = u8>
 
 impl<A: Array<Item = u8>> From<A> for Writable<A> {
   fn from(a: A) -> Self {
-    Self(a)
+    Self { buf: a,
+           truncating: false,
+           overflowed: false }
   }
 }
 
@@ -85,13 +171,13 @@ impl<A: Array<Item = u8>> Deref for Writable<A> {
   type Target = A;
 
   fn deref(&self) -> &A {
-    &self.0
+    &self.buf
   }
 }
 
 impl<A: Array<Item = u8>> DerefMut for Writable<A> {
   fn deref_mut(&mut self) -> &mut A {
-    &mut self.0
+    &mut self.buf
   }
 }
 
@@ -104,7 +190,14 @@ impl<A: Array<Item = u8>> AsRef<str> for Writable<A> {
 impl<A: Array<Item = u8>> core::fmt::Write for Writable<A> {
   fn write_str(&mut self, s: &str) -> core::fmt::Result {
     match A::CAPACITY {
-      | Some(max) if max < self.len() + s.len() => Err(core::fmt::Error),
+      | _ if self.truncating => {
+        self.try_write_str(s);
+        Ok(())
+      },
+      | Some(max) if max < self.len() + s.len() => {
+        self.overflowed = true;
+        Err(core::fmt::Error)
+      },
       | _ => {
         self.extend(s.bytes());
         Ok(())
@@ -0,0 +1,44 @@
+//! Cost of logging a message summary when the log level is filtered out,
+//! compared against eagerly formatting the summary first.
+
+use core::fmt::Write;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use toad::logging::msg_summary;
+use toad::platform::{Effect, Message};
+use toad::req::Req;
+use toad::std::{dtls, PlatformTypes};
+use toad::todo::String;
+
+type P = PlatformTypes<dtls::N>;
+
+fn logging(c: &mut Criterion) {
+  // no logger is installed, so nothing is enabled
+  log::set_max_level(log::LevelFilter::Off);
+
+  let msg = Message::<P>::from(Req::<P>::get("bench"));
+  let mut effects: Vec<Effect<P>> = vec![];
+
+  let mut group = c.benchmark_group("logging/filtered");
+
+  group.bench_function("eager", |b| {
+         b.iter(|| {
+            let mut summary = String::<100>::default();
+            write!(summary, "{:?} {:?} {:?}", msg.ty, msg.code, msg.token).ok();
+            toad::log!(bench, effects, log::Level::Trace, "{}", summary.as_str());
+            black_box(&effects);
+          })
+       });
+
+  group.bench_function("lazy", |b| {
+         b.iter(|| {
+            toad::log!(bench, effects, log::Level::Trace, "{}", msg_summary(black_box(&msg)));
+            black_box(&effects);
+          })
+       });
+
+  group.finish();
+}
+
+criterion_group!(benches, logging);
+criterion_main!(benches);
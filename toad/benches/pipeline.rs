@@ -0,0 +1,89 @@
+//! Per-message cost of the default step pipeline, compared against
+//! handling the same messages with a plain socket.
+//!
+//! Profile a single benchmark with `cargo make flame <name>`,
+//! e.g. `cargo make flame runtime`.
+
+use std::net::UdpSocket;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use toad::config::Config;
+use toad::net::{Addrd, Socket};
+use toad::platform::{Message, Platform as _};
+use toad::req::Req;
+use toad::resp::Resp;
+use toad::std::{dtls, Platform, PlatformTypes};
+use toad::step::runtime;
+use toad_msg::{Id, Token, TryFromBytes, TryIntoBytes};
+
+type P = PlatformTypes<dtls::N>;
+type Server = Platform<dtls::N, runtime::std::Runtime<dtls::N>>;
+
+/// A NON GET request with a unique id & token
+fn request(n: u64) -> Vec<u8> {
+  let mut req = Req::<P>::get("bench");
+  req.non();
+  req.msg_mut().id = Id((n % u16::MAX as u64) as u16 + 1);
+  req.msg_mut().token = Token(n.to_be_bytes().into_iter().collect());
+
+  Message::<P>::from(req).try_into_bytes::<Vec<u8>>().unwrap()
+}
+
+/// Parse a request & serialize a response to it, without touching the network
+fn parse_serialize(dgram: &[u8]) -> Vec<u8> {
+  let req = Req::<P>::from(Message::<P>::try_from_bytes(dgram).unwrap());
+  let resp = Resp::for_request(&req).unwrap();
+  Message::<P>::from(resp).try_into_bytes::<Vec<u8>>().unwrap()
+}
+
+fn bind_loopback() -> UdpSocket {
+  UdpSocket::bind("127.0.0.1:0").unwrap()
+}
+
+fn pipeline(c: &mut Criterion) {
+  let mut group = c.benchmark_group("runtime/per_message");
+  let mut buf = [0u8; 1152];
+  let mut n = 0u64;
+
+  group.bench_function("parse_serialize", |b| {
+         b.iter(|| {
+            n += 1;
+            parse_serialize(&request(n))
+          })
+       });
+
+  let (server, client) = (bind_loopback(), bind_loopback());
+  let server_addr = server.local_addr().unwrap();
+  group.bench_function("raw_socket", |b| {
+         b.iter(|| {
+            n += 1;
+            client.send_to(&request(n), server_addr).unwrap();
+
+            let (len, addr) = server.recv_from(&mut buf).unwrap();
+            server.send_to(&parse_serialize(&buf[..len]), addr).unwrap();
+
+            client.recv_from(&mut buf).unwrap()
+          })
+       });
+
+  let server = Server::try_new("127.0.0.1:0", Config::default()).unwrap();
+  let server_addr: std::net::SocketAddr =
+    server.socket().local_addr().to_string().parse().unwrap();
+  group.bench_function("runtime", |b| {
+         b.iter(|| {
+            n += 1;
+            client.send_to(&request(n), server_addr).unwrap();
+
+            let req = nb::block!(server.poll_req()).unwrap();
+            let resp = Resp::for_request(req.data()).unwrap();
+            nb::block!(server.send_msg(Addrd(resp.into(), req.addr()))).unwrap();
+
+            client.recv_from(&mut buf).unwrap()
+          })
+       });
+
+  group.finish();
+}
+
+criterion_group!(benches, pipeline);
+criterion_main!(benches);
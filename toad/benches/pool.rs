@@ -0,0 +1,137 @@
+//! Allocations made per message by a minimal step pipeline,
+//! with and without reusing messages from a [`MessagePool`].
+//!
+//! Allocation counts are printed before each benchmark runs, and
+//! the pool is asserted to reduce them.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use toad::config::Config;
+use toad::net::{Addrd, Socket};
+use toad::platform::{Message, Platform as _};
+use toad::req::Req;
+use toad::resp::Resp;
+use toad::std::{dtls, Platform, PlatformTypes};
+use toad::step::ack::Ack;
+use toad::step::parse::Parse;
+use toad::step::pool::Pool;
+use toad::step::Step;
+use toad::steps;
+use toad_msg::{Id, Token, TryIntoBytes};
+
+type P = PlatformTypes<dtls::N>;
+type WithoutPool = steps![Parse, Ack];
+type WithPool = steps![Pool<Vec<Message<P>>>, Parse, Ack];
+
+/// Counts the allocations made by the global allocator
+struct Counting;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout)
+  }
+
+  unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    System.realloc(ptr, layout, new_size)
+  }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// A NON POST request with a unique id & token, and a 256 byte payload
+fn request(n: u64) -> Vec<u8> {
+  let mut req = Req::<P>::post("bench");
+  req.non();
+  req.set_payload(&[0u8; 256][..]);
+  req.msg_mut().id = Id((n % u16::MAX as u64) as u16 + 1);
+  req.msg_mut().token = Token(n.to_be_bytes().into_iter().collect());
+
+  Message::<P>::from(req).try_into_bytes::<Vec<u8>>().unwrap()
+}
+
+/// Receive a request from `client` & echo its payload back
+fn roundtrip<S>(server: &Platform<dtls::N, S>,
+                server_addr: std::net::SocketAddr,
+                client: &UdpSocket,
+                buf: &mut [u8],
+                n: u64)
+  where S: Step<P, PollReq = Addrd<Req<P>>, PollResp = Addrd<Resp<P>>>
+{
+  client.send_to(&request(n), server_addr).unwrap();
+
+  let req = nb::block!(server.poll_req()).unwrap();
+  let mut resp = Resp::for_request(req.data()).unwrap();
+  resp.set_payload(req.data().payload().iter().copied());
+  nb::block!(server.send_msg(Addrd(resp.into(), req.addr()))).unwrap();
+
+  client.recv_from(buf).unwrap();
+}
+
+/// Benchmark round trips through `server`, yielding the number of
+/// allocations made per message before benchmarking
+fn bench_stack<S>(c: &mut Criterion, name: &str, server: &Platform<dtls::N, S>) -> f64
+  where S: Step<P, PollReq = Addrd<Req<P>>, PollResp = Addrd<Resp<P>>>
+{
+  const MESSAGES: u64 = 1000;
+
+  let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+  let server_addr: std::net::SocketAddr =
+    server.socket().local_addr().to_string().parse().unwrap();
+  let mut buf = [0u8; 1152];
+  let mut n = 0u64;
+
+  // warm up, so that the pool has messages in it
+  for _ in 0..10 {
+    n += 1;
+    roundtrip(server, server_addr, &client, &mut buf, n);
+  }
+
+  let before = ALLOCATIONS.load(Ordering::Relaxed);
+  for _ in 0..MESSAGES {
+    n += 1;
+    roundtrip(server, server_addr, &client, &mut buf, n);
+  }
+  let allocs = (ALLOCATIONS.load(Ordering::Relaxed) - before) as f64 / MESSAGES as f64;
+  println!("pool/{}: {:.1} allocations per message", name, allocs);
+
+  c.bench_function(&format!("pool/{}", name), |b| {
+     b.iter(|| {
+        n += 1;
+        roundtrip(server, server_addr, &client, &mut buf, n)
+      })
+   });
+
+  allocs
+}
+
+fn pool(c: &mut Criterion) {
+  let server = Platform::<dtls::N, WithoutPool>::try_new("127.0.0.1:0", Config::default()).unwrap();
+  let without_pool = bench_stack(c, "without_pool", &server);
+
+  let server = Platform::<dtls::N, WithPool>::try_new("127.0.0.1:0", Config::default()).unwrap();
+  let with_pool = bench_stack(c, "with_pool", &server);
+
+  // steps![Pool, Parse, Ack] is Ack<Parse<Pool>>
+  let stats = Step::<P>::inner(Step::<P>::inner(server.steps())).pool()
+                                                              .stats();
+  assert!(stats.reused > 0, "no messages were reused: {:?}", stats);
+  assert!(with_pool < without_pool,
+          "pool did not reduce allocations per message ({:.1} with, {:.1} without)",
+          with_pool,
+          without_pool);
+}
+
+criterion_group!(benches, pool);
+criterion_main!(benches);
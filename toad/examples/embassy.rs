@@ -0,0 +1,76 @@
+//! Runs a minimal toad server on top of `embassy-net`.
+//!
+//! This uses a linux TAP interface so that it can be run
+//! on a workstation; on real firmware `TunTapDevice` would
+//! be swapped out for the driver for your network peripheral.
+//!
+//! ```text
+//! sudo ip tuntap add name tap99 mode tap user $USER
+//! sudo ip link set tap99 up
+//! sudo ip addr add 192.168.69.100/24 dev tap99
+//! cargo run --example embassy --features embassy
+//! ```
+
+use embassy_executor::Spawner;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{Config as NetConfig, Ipv4Address, Ipv4Cidr, Stack, StackResources, StaticConfigV4};
+use embassy_net_tuntap::TunTapDevice;
+use static_cell::StaticCell;
+use toad::config::Config;
+use toad::platform::embassy::{block_async, Platform, PlatformTypes, Socket};
+use toad::platform::Platform as _;
+use toad::resp::Resp;
+use toad::step::ack::Ack;
+use toad::step::parse::Parse;
+
+type Steps = Ack<Parse<()>>;
+
+#[embassy_executor::task]
+async fn net_task(stack: &'static Stack<TunTapDevice>) -> ! {
+  stack.run().await
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+  simple_logger::init().unwrap();
+
+  let device = TunTapDevice::new("tap99").unwrap();
+  let config =
+    NetConfig::ipv4_static(StaticConfigV4 { address: Ipv4Cidr::new(Ipv4Address::new(192, 168, 69, 2), 24),
+                                            dns_servers: Default::default(),
+                                            gateway: Some(Ipv4Address::new(192, 168, 69, 100)) });
+
+  static STACK: StaticCell<Stack<TunTapDevice>> = StaticCell::new();
+  static RESOURCES: StaticCell<StackResources<3>> = StaticCell::new();
+  let stack = &*STACK.init(Stack::new(device,
+                                      config,
+                                      RESOURCES.init(StackResources::<3>::new()),
+                                      0));
+
+  spawner.spawn(net_task(stack)).unwrap();
+
+  static RX_META: StaticCell<[PacketMetadata; 16]> = StaticCell::new();
+  static TX_META: StaticCell<[PacketMetadata; 16]> = StaticCell::new();
+  static RX_BUF: StaticCell<[u8; 4096]> = StaticCell::new();
+  static TX_BUF: StaticCell<[u8; 4096]> = StaticCell::new();
+
+  let sock = UdpSocket::new(stack,
+                            RX_META.init([PacketMetadata::EMPTY; 16]),
+                            RX_BUF.init([0; 4096]),
+                            TX_META.init([PacketMetadata::EMPTY; 16]),
+                            TX_BUF.init([0; 4096]));
+
+  let platform = Platform::<Steps>::new(Socket::new(sock, 5683).unwrap(), Config::default());
+
+  loop {
+    let req = block_async(|| platform.poll_req()).await.unwrap();
+    log::info!("got {:?}", req.data().path());
+
+    let mut resp = Resp::<PlatformTypes>::for_request(req.data()).unwrap();
+    resp.set_payload("hello, world!".bytes());
+
+    block_async(|| platform.send_msg(req.as_ref().map(|_| resp.clone().into())))
+      .await
+      .unwrap();
+  }
+}
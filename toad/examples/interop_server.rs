@@ -0,0 +1,133 @@
+//! A server implementing the resources exercised by the
+//! ETSI CoAP plugtests, for running toad against standard
+//! interop test suites.
+//!
+//! ```text
+//! cargo run --example interop_server -- 0.0.0.0:5683
+//! ```
+//!
+//! | resource           | behavior                                                     |
+//! | ------------------ | ------------------------------------------------------------ |
+//! | `/test`            | GET 2.05, POST 2.01, PUT 2.04, DELETE 2.02                   |
+//! | `/seg1/seg2/seg3`  | GET 2.05                                                     |
+//! | `/query`           | GET 2.05, echoing the Uri-Query options of the request       |
+//! | `/large`           | GET 2.05, a representation larger than one block (Block2)    |
+//! | `/obs`             | GET 2.05, observable; changes every 5 seconds                |
+
+use std::io;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use lazycell::AtomicLazyCell;
+use toad::config::Config;
+use toad::platform::Platform as _;
+use toad::req::Method;
+use toad::resp::code;
+use toad::server::ap::state::{Complete, Hydrated};
+use toad::server::{method, path, respond, Ap, BlockingServer, Init};
+use toad::std::{dtls, Platform, PlatformTypes as T};
+use toad::step::runtime;
+
+type P = Platform<dtls::N, runtime::std::Runtime<dtls::N>>;
+
+/// Number of times `/obs` has changed
+static OBS: AtomicU32 = AtomicU32::new(0);
+
+mod route {
+  use toad::server::respond::Chunk;
+  use toad_msg::MessageOptions;
+
+  use super::*;
+
+  pub fn test(ap: Ap<Hydrated, T<dtls::N>, (), io::Error>)
+              -> Ap<Complete, T<dtls::N>, (), io::Error> {
+    ap.pipe(path::check::rest_equals("test"))
+      .bind_hydrated(|_, req| match req.data().method() {
+        | m if m == Method::GET => respond::ok("Type: CON/NON, Code: GET".into()),
+        | m if m == Method::POST => respond::respond(code::CREATED, Default::default()),
+        | m if m == Method::PUT => respond::respond(code::CHANGED, Default::default()),
+        | m if m == Method::DELETE => respond::respond(code::DELETED, Default::default()),
+        | _ => respond::respond(code::METHOD_NOT_ALLOWED, Default::default()),
+      })
+  }
+
+  pub fn seg(ap: Ap<Hydrated, T<dtls::N>, (), io::Error>)
+             -> Ap<Complete, T<dtls::N>, (), io::Error> {
+    ap.pipe(method::get)
+      .pipe(path::check::rest_equals("seg1/seg2/seg3"))
+      .bind(|_| respond::ok("seg1/seg2/seg3".into()))
+  }
+
+  pub fn query(ap: Ap<Hydrated, T<dtls::N>, (), io::Error>)
+               -> Ap<Complete, T<dtls::N>, (), io::Error> {
+    ap.pipe(method::get)
+      .pipe(path::check::rest_equals("query"))
+      .bind_hydrated(|_, req| {
+        let query = req.data().msg().query::<Vec<_>>().unwrap_or_default();
+        respond::ok(query.join("&").into())
+      })
+  }
+
+  pub fn large(ap: Ap<Hydrated, T<dtls::N>, (), io::Error>)
+               -> Ap<Complete, T<dtls::N>, (), io::Error> {
+    ap.pipe(method::get)
+      .pipe(path::check::rest_equals("large"))
+      .pipe(respond::stream(|num, size| {
+              let large = large();
+              let start = num as usize * size as usize;
+              let end = (start + size as usize).min(large.len());
+              match large.get(start..end) {
+                | Some(block) if end < large.len() => Chunk::More(block.to_vec()),
+                | Some(block) => Chunk::Last(block.to_vec()),
+                | None => Chunk::OutOfRange,
+              }
+            }))
+  }
+
+  pub fn obs(ap: Ap<Hydrated, T<dtls::N>, (), io::Error>)
+             -> Ap<Complete, T<dtls::N>, (), io::Error> {
+    ap.pipe(method::get)
+      .pipe(path::check::rest_equals("obs"))
+      .bind(|_| respond::ok(format!("{}", OBS.load(Ordering::SeqCst)).into()))
+  }
+
+  pub fn not_found(ap: Ap<Hydrated, T<dtls::N>, (), io::Error>)
+                   -> Ap<Complete, T<dtls::N>, (), io::Error> {
+    ap.pipe(path::rest(|_, r| Ap::ok(r.to_string())))
+      .bind(|path| respond::not_found(format!("resource {path} not found").into()))
+  }
+}
+
+/// The representation of `/large`; 2048 bytes of text
+fn large() -> Vec<u8> {
+  (0..2048).map(|n| b'a' + (n % 26) as u8).collect()
+}
+
+pub fn main() {
+  simple_logger::init_with_level(log::Level::Info).unwrap();
+
+  let addr = std::env::args().nth(1)
+                             .unwrap_or_else(|| "0.0.0.0:5683".to_string());
+
+  static SERVER: AtomicLazyCell<P> = AtomicLazyCell::NONE;
+  SERVER.fill(P::try_new(addr, Config::default()).unwrap())
+        .unwrap();
+
+  std::thread::spawn(|| loop {
+    std::thread::sleep(Duration::from_secs(5));
+    OBS.fetch_add(1, Ordering::SeqCst);
+    SERVER.borrow().unwrap().notify("obs").unwrap();
+  });
+
+  SERVER.borrow()
+        .unwrap()
+        .run(Init::none(), |run| {
+          run.maybe(route::test)
+             .maybe(route::seg)
+             .maybe(route::query)
+             .maybe(route::large)
+             .maybe(route::obs)
+             .maybe(route::not_found)
+        })
+        .unwrap();
+}
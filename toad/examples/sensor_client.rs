@@ -0,0 +1,33 @@
+//! A sensor that only ever acts as a CoAP client, reporting
+//! readings to a collector with `POST /readings/temperature`.
+//!
+//! Builds with `--no-default-features --features std,client`,
+//! and is the binary measured by `cargo make size-check`.
+
+use toad::config::Config;
+use toad::net::Addrd;
+use toad::platform::Platform as _;
+use toad::req::Req;
+use toad::std::{dtls, Platform, PlatformTypes as T};
+use toad::step::runtime;
+
+type P = Platform<dtls::N, runtime::std::Runtime<dtls::N>>;
+
+pub fn main() {
+  simple_logger::init_with_level(log::Level::Info).unwrap();
+
+  let collector = std::env::args().nth(1)
+                                  .unwrap_or_else(|| "127.0.0.1:5683".to_string())
+                                  .parse()
+                                  .unwrap();
+
+  let sensor = P::try_new("0.0.0.0:0", Config::default()).unwrap();
+
+  let mut req = Req::<T<dtls::N>>::post("readings/temperature");
+  req.set_payload("21.5");
+
+  let (_, token) = sensor.send_msg(Addrd(req.into(), collector)).unwrap();
+  let resp = nb::block!(sensor.poll_resp(token, collector)).unwrap();
+
+  log::info!("collector responded {:?}", resp.data().code());
+}
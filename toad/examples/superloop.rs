@@ -0,0 +1,46 @@
+//! A server sharing a single thread with a control loop,
+//! the way it would on a bare-metal device without an RTOS.
+//!
+//! Every 10 milliseconds toad is given up to 2 milliseconds
+//! to handle requests, and the control loop gets the rest.
+//!
+//! ```text
+//! cargo run --example superloop -- 0.0.0.0:5683
+//! ```
+
+use std::cell::Cell;
+use std::time::Duration;
+
+use toad::config::Config;
+use toad::exec::{self, Budget, Control};
+use toad::net::Addrd;
+use toad::platform::Platform as _;
+use toad::resp::Resp;
+use toad::std::{dtls, Platform};
+use toad::step::runtime;
+
+type P = Platform<dtls::N, runtime::std::Runtime<dtls::N>>;
+
+pub fn main() {
+  simple_logger::init_with_level(log::Level::Info).unwrap();
+
+  let addr = std::env::args().nth(1)
+                             .unwrap_or_else(|| "0.0.0.0:5683".to_string());
+  let server = P::try_new(addr, Config::default()).unwrap();
+
+  let ticks = Cell::new(0u64);
+
+  exec::superloop(&server,
+                  Budget::default(),
+                  |req| {
+                    let mut resp = Resp::for_request(req.data()).unwrap();
+                    resp.set_payload(format!("ticks: {}", ticks.get()).bytes());
+                    nb::block!(server.send_msg(Addrd(resp.into(), req.addr()))).map(|_| ())
+                  },
+                  |remaining| {
+                    // stand-in for reading sensors & driving actuators
+                    ticks.set(ticks.get() + 1);
+                    std::thread::sleep(Duration::from_millis(remaining.0));
+                    Control::Continue
+                  }).unwrap();
+}
@@ -0,0 +1,99 @@
+use no_std_net::SocketAddr;
+
+pub use observe::{Overflow, Subscription};
+
+use crate::net::Addrd;
+use crate::platform::Platform;
+use crate::req::Req;
+use crate::resp::Resp;
+use crate::step::Step;
+
+/// Observe subscriptions
+///
+/// See [`Client::observe`]
+pub mod observe;
+
+/// Use a CoAP [`Platform`] as a client
+///
+/// This trait is implemented for all [`Platform`]s, and provides
+/// high-level client flows on top of [`Platform::send_msg`] and
+/// [`Platform::poll_resp`].
+pub trait Client<S>: Sized + Platform<S>
+  where S: Step<Self::Types, PollReq = Addrd<Req<Self::Types>>, PollResp = Addrd<Resp<Self::Types>>>
+{
+  /// Register as an observer of the resource at `coap://{addr}/{path}`
+  ///
+  /// This sends a `GET` request with [Observe](toad_msg::opt::known::no_repeat::OBSERVE)
+  /// set to [register](toad_msg::opt::known::observe::Action::Register), and yields a
+  /// [`Subscription`] that can be polled for notifications.
+  ///
+  /// ```no_run
+  /// use toad::client::Client;
+  /// use toad::net::ipv4_socketaddr;
+  /// use toad::std::{dtls, Platform};
+  /// use toad::step::runtime::std::Runtime;
+  ///
+  /// let client = Platform::<dtls::N, Runtime<dtls::N>>::try_new("0.0.0.0:0", Default::default()).unwrap();
+  ///
+  /// let mut sub = client.observe(ipv4_socketaddr([127, 0, 0, 1], 5683), "temperature")
+  ///                     .unwrap();
+  ///
+  /// for _ in 0..3 {
+  ///   let notification = nb::block!(sub.next()).unwrap();
+  ///   println!("temperature is now {:?}", notification.data().payload_string());
+  /// }
+  ///
+  /// sub.cancel().unwrap();
+  /// ```
+  fn observe<P>(&self, addr: SocketAddr, path: P) -> Result<Subscription<'_, S, Self>, Self::Error>
+    where P: AsRef<str>
+  {
+    Subscription::register(self, addr, path, Overflow::default())
+  }
+
+  /// Register as an observer of the resource at `coap://{addr}/{path}`,
+  /// queueing up to `N` notifications that have been received but not yet consumed.
+  ///
+  /// When a notification arrives while the queue is full, `overflow`
+  /// decides which notification is dropped (see [`Overflow`]) and
+  /// [`Subscription::dropped`] is incremented, or passes the new notification
+  /// to a handler and increments [`Subscription::handed_off`].
+  ///
+  /// ```no_run
+  /// use toad::client::{Client, Overflow};
+  /// use toad::net::ipv4_socketaddr;
+  /// use toad::std::{dtls, Platform};
+  /// use toad::step::runtime::std::Runtime;
+  ///
+  /// let client = Platform::<dtls::N, Runtime<dtls::N>>::try_new("0.0.0.0:0", Default::default()).unwrap();
+  ///
+  /// let mut sub = client.observe_queued::<_, 16>(ipv4_socketaddr([127, 0, 0, 1], 5683),
+  ///                                              "temperature",
+  ///                                              Overflow::DropOldest)
+  ///                     .unwrap();
+  ///
+  /// loop {
+  ///   sub.receive().unwrap();
+  ///
+  ///   if let Ok(notification) = sub.next() {
+  ///     println!("temperature is now {:?}", notification.data().payload_string());
+  ///     println!("{} notifications dropped so far", sub.dropped());
+  ///   }
+  /// }
+  /// ```
+  fn observe_queued<P, const N: usize>(&self,
+                                       addr: SocketAddr,
+                                       path: P,
+                                       overflow: Overflow<Addrd<Resp<Self::Types>>>)
+                                       -> Result<Subscription<'_, S, Self, N>, Self::Error>
+    where P: AsRef<str>
+  {
+    Subscription::register(self, addr, path, overflow)
+  }
+}
+
+impl<S, T> Client<S> for T
+  where S: Step<Self::Types, PollReq = Addrd<Req<Self::Types>>, PollResp = Addrd<Resp<Self::Types>>>,
+        T: Sized + Platform<S>
+{
+}
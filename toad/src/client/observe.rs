@@ -0,0 +1,440 @@
+use core::marker::PhantomData;
+
+use embedded_time::duration::Milliseconds;
+use embedded_time::Instant;
+use no_std_net::SocketAddr;
+use toad_msg::opt::known::observe::Action::{Deregister, Register};
+use toad_msg::{Id, MessageOptions, Token, Type};
+
+use crate::net::Addrd;
+use crate::platform::{Platform, PlatformError, PlatformTypes};
+use crate::req::Req;
+use crate::resp::Resp;
+use crate::observe::{is_fresh, notification_seq};
+use crate::step::Step;
+use crate::time::Millis;
+
+/// Max-Age assumed for notifications that do not specify one
+///
+/// <https://www.rfc-editor.org/rfc/rfc7252#section-5.10.5>
+pub const DEFAULT_MAX_AGE: Millis = Milliseconds(60_000);
+
+/// What a [`Subscription`] does with a notification that arrives
+/// while its queue is full
+///
+/// See [`Client::observe_queued`](super::Client::observe_queued).
+pub enum Overflow<T> {
+  /// Drop the oldest queued notification to make room for the new one
+  DropOldest,
+  /// Drop the new notification, keeping the queued ones
+  DropNewest,
+  /// Pass the new notification to a handler instead of queueing it
+  ///
+  /// Notifications passed to the handler are counted by
+  /// [`Subscription::handed_off`], not [`Subscription::dropped`].
+  Call(fn(T)),
+}
+
+impl<T> Clone for Overflow<T> {
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<T> Copy for Overflow<T> {}
+
+impl<T> core::fmt::Debug for Overflow<T> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    match self {
+      | Self::DropOldest => f.write_str("DropOldest"),
+      | Self::DropNewest => f.write_str("DropNewest"),
+      | Self::Call(_) => f.write_str("Call(..)"),
+    }
+  }
+}
+
+impl<T> Default for Overflow<T> {
+  fn default() -> Self {
+    Self::DropOldest
+  }
+}
+
+/// What [`Queue::push`] did with a notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pushed {
+  /// The notification was queued
+  Queued,
+  /// A notification (the new one or the oldest queued one) was dropped
+  Dropped,
+  /// The new notification was passed to the [`Overflow::Call`] handler
+  HandedOff,
+}
+
+/// Fixed-capacity FIFO of notifications received by a [`Subscription`]
+/// but not yet yielded by [`Subscription::next`]
+struct Queue<T, const N: usize> {
+  items: [Option<T>; N],
+  head: usize,
+  len: usize,
+}
+
+impl<T, const N: usize> Queue<T, N> {
+  fn new() -> Self {
+    Self { items: core::array::from_fn(|_| None),
+           head: 0,
+           len: 0 }
+  }
+
+  fn len(&self) -> usize {
+    self.len
+  }
+
+  fn pop(&mut self) -> Option<T> {
+    if self.len == 0 {
+      return None;
+    }
+
+    let t = self.items[self.head].take();
+    self.head = (self.head + 1) % N;
+    self.len -= 1;
+    t
+  }
+
+  /// Enqueue `t`, applying `overflow` if the queue is full.
+  fn push(&mut self, t: T, overflow: Overflow<T>) -> Pushed {
+    let pushed = if self.len == N {
+      match overflow {
+        | Overflow::DropOldest if N > 0 => {
+          self.pop();
+          Pushed::Dropped
+        },
+        | Overflow::DropOldest | Overflow::DropNewest => return Pushed::Dropped,
+        | Overflow::Call(f) => {
+          f(t);
+          return Pushed::HandedOff;
+        },
+      }
+    } else {
+      Pushed::Queued
+    };
+
+    self.items[(self.head + self.len) % N] = Some(t);
+    self.len += 1;
+    pushed
+  }
+}
+
+/// How long after a notification's Max-Age has elapsed that
+/// a [`Subscription`] will wait before re-registering
+pub const REREGISTER_GRACE: Millis = Milliseconds(5_000);
+
+/// An active registration as an observer of a resource on a remote server
+///
+/// Created by [`Client::observe`](super::Client::observe).
+///
+/// ## Ordering
+/// Notifications that arrive out-of-order (according to the
+/// rules in [RFC7641 Section 3.4](https://www.rfc-editor.org/rfc/rfc7641#section-3.4))
+/// (see [`is_fresh`]) are silently dropped, so [`Subscription::next`] will only ever yield
+/// notifications that are fresher than the last.
+///
+/// ## Queueing
+/// A subscription created with [`Client::observe`](super::Client::observe) has no queue;
+/// each call to [`Subscription::next`] polls the platform for one notification,
+/// so notifications that arrive faster than they are consumed may be dropped
+/// by the runtime.
+///
+/// A subscription created with [`Client::observe_queued`](super::Client::observe_queued)
+/// instead drains every available notification into a queue of `N` notifications
+/// whenever it is polled (see [`Subscription::receive`]), and yields them in the order they
+/// were received. When the queue is full, the subscription's [`Overflow`] policy decides
+/// what to do with a new notification, and either [`Subscription::dropped`] or
+/// (for [`Overflow::Call`]) [`Subscription::handed_off`] is incremented.
+///
+/// ## Re-registration
+/// The registration request will be re-sent when:
+/// * no notification has been received within the Max-Age of the last notification (plus [`REREGISTER_GRACE`])
+/// * the server resets the subscription
+///
+/// ## Deregistration
+/// [`Subscription::cancel`] explicitly deregisters with the server.
+///
+/// Dropping a `Subscription` without cancelling it will leave the
+/// server thinking we are still interested; the server will
+/// eventually remove us when it receives a RST in response to a notification.
+pub struct Subscription<'a, S, P, const N: usize = 0>
+  where P: Platform<S>,
+        S: Step<P::Types, PollReq = Addrd<Req<P::Types>>, PollResp = Addrd<Resp<P::Types>>>
+{
+  platform: &'a P,
+  req: Addrd<Req<P::Types>>,
+  token: Token,
+  last_seq: Option<(u32, Instant<<P::Types as PlatformTypes>::Clock>)>,
+  last_heard: Instant<<P::Types as PlatformTypes>::Clock>,
+  max_age: Millis,
+  queue: Queue<Addrd<Resp<P::Types>>, N>,
+  overflow: Overflow<Addrd<Resp<P::Types>>>,
+  dropped: u32,
+  handed_off: u32,
+  __steps: PhantomData<S>,
+}
+
+impl<'a, S, P, const N: usize> core::fmt::Debug for Subscription<'a, S, P, N>
+  where P: Platform<S>,
+        S: Step<P::Types, PollReq = Addrd<Req<P::Types>>, PollResp = Addrd<Resp<P::Types>>>
+{
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("Subscription")
+     .field("req", &self.req)
+     .field("token", &self.token)
+     .field("last_seq", &self.last_seq.map(|(seq, _)| seq))
+     .field("max_age", &self.max_age)
+     .field("queued", &self.queue.len())
+     .field("overflow", &self.overflow)
+     .field("dropped", &self.dropped)
+     .field("handed_off", &self.handed_off)
+     .finish()
+  }
+}
+
+impl<'a, S, P, const N: usize> Subscription<'a, S, P, N>
+  where P: Platform<S>,
+        S: Step<P::Types, PollReq = Addrd<Req<P::Types>>, PollResp = Addrd<Resp<P::Types>>>
+{
+  pub(super) fn register<Path>(platform: &'a P,
+                               addr: SocketAddr,
+                               path: Path,
+                               overflow: Overflow<Addrd<Resp<P::Types>>>)
+                               -> Result<Self, P::Error>
+    where Path: AsRef<str>
+  {
+    let mut req = Req::<P::Types>::get(path);
+    req.msg_mut().set_observe(Register).ok();
+
+    let now = Self::now(platform)?;
+    let mut sub = Self { platform,
+                         req: Addrd(req, addr),
+                         token: Token(Default::default()),
+                         last_seq: None,
+                         last_heard: now,
+                         max_age: DEFAULT_MAX_AGE,
+                         queue: Queue::new(),
+                         overflow,
+                         dropped: 0,
+                         handed_off: 0,
+                         __steps: PhantomData };
+
+    sub.reregister()?;
+    Ok(sub)
+  }
+
+  fn now(platform: &P) -> Result<Instant<<P::Types as PlatformTypes>::Clock>, P::Error> {
+    use embedded_time::Clock;
+
+    platform.clock().try_now().map_err(P::Error::clock)
+  }
+
+  /// The address of the server we're observing
+  pub fn addr(&self) -> SocketAddr {
+    self.req.addr()
+  }
+
+  /// The token used to correlate notifications with this subscription
+  pub fn token(&self) -> Token {
+    self.token
+  }
+
+  /// The number of notifications received but not yet yielded by [`Subscription::next`]
+  pub fn queued(&self) -> usize {
+    self.queue.len()
+  }
+
+  /// The number of notifications that were dropped because they did not
+  /// fit in this subscription's queue.
+  ///
+  /// Notifications passed to an [`Overflow::Call`] handler are not dropped,
+  /// and are counted by [`Subscription::handed_off`] instead.
+  ///
+  /// Always 0 for subscriptions without a queue.
+  pub fn dropped(&self) -> u32 {
+    self.dropped
+  }
+
+  /// The number of notifications that did not fit in this subscription's
+  /// queue and were passed to its [`Overflow::Call`] handler.
+  ///
+  /// Always 0 for subscriptions without a queue.
+  pub fn handed_off(&self) -> u32 {
+    self.handed_off
+  }
+
+  /// (Re-)send the registration request.
+  ///
+  /// The same token is used for every registration, so that
+  /// notifications in response to any of them are yielded by [`Subscription::next`].
+  pub fn reregister(&mut self) -> Result<(), P::Error> {
+    let mut msg = self.req.data().msg().clone();
+    msg.id = Id(0);
+    msg.token = self.token;
+
+    let (_, token) = nb::block!(self.platform.send_msg(Addrd(msg, self.addr())))?;
+    self.token = token;
+    self.last_heard = Self::now(self.platform)?;
+
+    Ok(())
+  }
+
+  /// Poll for the next fresh notification
+  ///
+  /// This will yield [`nb::Error::WouldBlock`] when no notification is available,
+  /// when a stale notification was received and dropped, or when the
+  /// subscription was re-registered.
+  ///
+  /// If this subscription has a queue, all available notifications
+  /// are [received](Subscription::receive) first and the oldest queued notification
+  /// is yielded.
+  pub fn next(&mut self) -> nb::Result<Addrd<Resp<P::Types>>, P::Error> {
+    if N == 0 {
+      return self.poll().and_then(|resp| resp.ok_or(nb::Error::WouldBlock));
+    }
+
+    self.receive().map_err(nb::Error::Other)?;
+    self.queue.pop().ok_or(nb::Error::WouldBlock)
+  }
+
+  /// Move all notifications that are available now into this subscription's queue,
+  /// without yielding any of them.
+  ///
+  /// Applications that consume notifications slowly may invoke this
+  /// more often than [`Subscription::next`] so that notifications are
+  /// not dropped by the runtime before they reach the queue.
+  ///
+  /// Notifications that do not fit in the queue are handled according to
+  /// this subscription's [`Overflow`] policy.
+  pub fn receive(&mut self) -> Result<(), P::Error> {
+    loop {
+      match self.poll() {
+        | Ok(Some(resp)) => {
+          match self.queue.push(resp, self.overflow) {
+            | Pushed::Queued => (),
+            | Pushed::Dropped => self.dropped = self.dropped.saturating_add(1),
+            | Pushed::HandedOff => self.handed_off = self.handed_off.saturating_add(1),
+          }
+        },
+        | Ok(None) => continue,
+        | Err(nb::Error::WouldBlock) => return Ok(()),
+        | Err(nb::Error::Other(e)) => return Err(e),
+      }
+    }
+  }
+
+  /// Poll the platform for a notification
+  ///
+  /// Yields `Ok(None)` when a notification was received but should not be
+  /// yielded (it was stale, or the server reset the subscription).
+  fn poll(&mut self) -> nb::Result<Option<Addrd<Resp<P::Types>>>, P::Error> {
+    let now = Self::now(self.platform).map_err(nb::Error::Other)?;
+
+    match self.platform.poll_resp(self.token, self.addr()) {
+      | Ok(resp) if resp.data().msg_type() == Type::Reset => {
+        self.reregister().map_err(nb::Error::Other)?;
+        Ok(None)
+      },
+      | Ok(resp) => {
+        let seq = notification_seq(resp.data().msg());
+
+        match (self.last_seq, seq) {
+          | (Some((v1, t1)), Some(v2)) if !is_fresh((v1, t1), (v2, now)) => return Ok(None),
+          | _ => (),
+        }
+
+        self.last_seq = seq.map(|seq| (seq, now)).or(self.last_seq);
+        self.last_heard = now;
+        self.max_age = resp.data()
+                           .msg()
+                           .max_age_seconds()
+                           .map(|s| crate::time::from_secs(s as u64))
+                           .unwrap_or(DEFAULT_MAX_AGE);
+
+        Ok(Some(resp))
+      },
+      | Err(nb::Error::WouldBlock) => {
+        let elapsed = crate::time::elapsed(self.last_heard, now);
+
+        match elapsed {
+          | Some(elapsed) if elapsed > crate::time::saturating_add(self.max_age, REREGISTER_GRACE) => {
+            self.reregister().map_err(nb::Error::Other)?;
+            Err(nb::Error::WouldBlock)
+          },
+          | _ => Err(nb::Error::WouldBlock),
+        }
+      },
+      | Err(e) => Err(e),
+    }
+  }
+
+  /// Deregister with the server, consuming the subscription
+  pub fn cancel(self) -> Result<(), P::Error> {
+    let mut msg = self.req.data().msg().clone();
+    msg.id = Id(0);
+    msg.token = self.token;
+    msg.set_observe(Deregister).ok();
+
+    nb::block!(self.platform.send_msg(Addrd(msg, self.addr()))).map(|_| ())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn drain<const N: usize>(q: &mut Queue<u8, N>) -> Vec<u8> {
+    core::iter::from_fn(|| q.pop()).collect()
+  }
+
+  #[test]
+  fn queue_yields_in_order_received() {
+    let mut q = Queue::<u8, 3>::new();
+    assert_eq!(q.push(1, Overflow::DropOldest), Pushed::Queued);
+    assert_eq!(q.push(2, Overflow::DropOldest), Pushed::Queued);
+    assert_eq!(q.pop(), Some(1));
+    assert_eq!(q.push(3, Overflow::DropOldest), Pushed::Queued);
+    assert_eq!(q.push(4, Overflow::DropOldest), Pushed::Queued);
+    assert_eq!(q.len(), 3);
+    assert_eq!(drain(&mut q), vec![2, 3, 4]);
+  }
+
+  #[test]
+  fn queue_overflow_drop_oldest() {
+    let mut q = Queue::<u8, 2>::new();
+    q.push(1, Overflow::DropOldest);
+    q.push(2, Overflow::DropOldest);
+    assert_eq!(q.push(3, Overflow::DropOldest), Pushed::Dropped);
+    assert_eq!(drain(&mut q), vec![2, 3]);
+  }
+
+  #[test]
+  fn queue_overflow_drop_newest() {
+    let mut q = Queue::<u8, 2>::new();
+    q.push(1, Overflow::DropNewest);
+    q.push(2, Overflow::DropNewest);
+    assert_eq!(q.push(3, Overflow::DropNewest), Pushed::Dropped);
+    assert_eq!(drain(&mut q), vec![1, 2]);
+  }
+
+  #[test]
+  fn queue_overflow_call() {
+    use std::sync::atomic::{AtomicU8, Ordering};
+
+    static OVERFLOWED: AtomicU8 = AtomicU8::new(0);
+    fn handle(n: u8) {
+      OVERFLOWED.store(n, Ordering::SeqCst);
+    }
+
+    let mut q = Queue::<u8, 1>::new();
+    q.push(1, Overflow::Call(handle));
+    assert_eq!(q.push(2, Overflow::Call(handle)), Pushed::HandedOff);
+    assert_eq!(OVERFLOWED.load(Ordering::SeqCst), 2);
+    assert_eq!(drain(&mut q), vec![1]);
+  }
+}
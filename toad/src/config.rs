@@ -1,8 +1,11 @@
 #![allow(dead_code)]
 
 use embedded_time::duration::Milliseconds;
+use no_std_net::{IpAddr, SocketAddr};
+use toad_msg::opt::known::{no_repeat, repeat};
+use toad_msg::OptNumber;
 
-use crate::retry::{Attempts, Strategy};
+use crate::retry::{Attempts, Jitter, Strategy};
 use crate::time::Millis;
 
 /// Bytes / Second
@@ -44,6 +47,17 @@ pub struct Con {
   ///                                    init_max: Milliseconds(2_000) });
   /// ```
   pub acked_retry_strategy: Strategy,
+  /// Retry strategy for CON responses that have not yet been ACKed.
+  ///
+  /// When `None`, CON responses are retried using `unacked_retry_strategy`.
+  ///
+  /// Defaults to `None`.
+  /// ```
+  /// use toad::config::Con;
+  ///
+  /// assert_eq!(Con::default().unacked_response_retry_strategy, None);
+  /// ```
+  pub unacked_response_retry_strategy: Option<Strategy>,
   /// Number of times we are allowed to resend a CON request
   /// before erroring.
   //
@@ -137,6 +151,23 @@ pub struct Msg {
   /// See [`Non`]
   pub non: Non,
 
+  /// Randomly stretch every retry delay (of CON and NON messages)
+  /// by up to this much, so that devices that started retrying
+  /// at the same time don't keep retrying in lockstep.
+  ///
+  /// The random number generator is seeded with the message's
+  /// token, so devices should be given distinct [`token_seed`](Msg#structfield.token_seed)s.
+  ///
+  /// Defaults to [`Jitter::NONE`].
+  ///
+  /// ```
+  /// use toad::config::Msg;
+  /// use toad::retry::Jitter;
+  ///
+  /// assert_eq!(Msg::default().retry_jitter, Jitter::NONE);
+  /// ```
+  pub retry_jitter: Jitter,
+
   /// Set the maximum amount of time we should delay
   /// our response to multicast requests.
   ///
@@ -153,14 +184,230 @@ pub struct Msg {
   ///            Milliseconds(5000u64));
   /// ```
   pub multicast_response_leisure: Millis,
+
+  /// Options that this application knows how to process.
+  ///
+  /// Used by [`step::option_policy`](crate::step::option_policy)
+  /// to reject incoming messages that contain
+  /// [critical](toad_msg::OptNumber::must_be_processed) options
+  /// not in this list.
+  ///
+  /// Defaults to [`KNOWN_OPTIONS`].
+  ///
+  /// ```
+  /// use toad::config::{Msg, KNOWN_OPTIONS};
+  /// use toad_msg::opt::known::no_repeat::IF_NONE_MATCH;
+  ///
+  /// assert_eq!(Msg::default().understood_options, KNOWN_OPTIONS);
+  /// assert!(Msg::default().understood_options.contains(&IF_NONE_MATCH));
+  /// ```
+  pub understood_options: &'static [OptNumber],
+
+  /// What to do with incoming requests whose payload
+  /// does not fit in the platform's payload capacity.
+  ///
+  /// Used by [`step::parse`](crate::step::parse).
+  ///
+  /// Defaults to [`Oversized::Reject`].
+  ///
+  /// ```
+  /// use toad::config::{Msg, Oversized};
+  ///
+  /// assert_eq!(Msg::default().oversized, Oversized::Reject);
+  /// ```
+  pub oversized: Oversized,
+
+  /// What to do with incoming messages containing options whose
+  /// values do not conform to their definitions,
+  /// e.g. a 3-byte Content-Format or a Uri-Path that isn't UTF-8.
+  ///
+  /// Used by [`step::parse`](crate::step::parse).
+  ///
+  /// Defaults to [`MalformedOptions::Lenient`].
+  ///
+  /// ```
+  /// use toad::config::{MalformedOptions, Msg};
+  ///
+  /// assert_eq!(Msg::default().malformed_options, MalformedOptions::Lenient);
+  /// ```
+  pub malformed_options: MalformedOptions,
+
+  /// The most responses [`step::buffer_responses`](crate::step::buffer_responses)
+  /// will hold on to for a single peer while polling for other responses.
+  ///
+  /// When a peer exceeds this, its least recently buffered response
+  /// is dropped, so that one peer (hostile or just chatty) cannot fill
+  /// the buffer and starve responses from others.
+  ///
+  /// `None` means that a peer may fill the buffer.
+  ///
+  /// Defaults to `Some(8)`.
+  ///
+  /// ```
+  /// use toad::config::Msg;
+  ///
+  /// assert_eq!(Msg::default().max_buffered_responses_per_peer, Some(8));
+  /// ```
+  pub max_buffered_responses_per_peer: Option<u16>,
+  /// How many times a request is re-sent after the server
+  /// responds 5.03 SERVICE UNAVAILABLE, waiting for the response's Max-Age
+  /// before each attempt.
+  ///
+  /// When `0`, the 5.03 response is yielded like any other response.
+  /// Otherwise, once the request has been re-sent this many times, the next 5.03 response
+  /// is surfaced as [`backoff::Error::ServiceUnavailable`](crate::step::backoff::Error::ServiceUnavailable).
+  ///
+  /// See [`step::backoff`](crate::step::backoff) for more.
+  ///
+  /// Defaults to `0`.
+  ///
+  /// ```
+  /// use toad::config::Msg;
+  ///
+  /// assert_eq!(Msg::default().service_unavailable_retries, 0);
+  /// ```
+  pub service_unavailable_retries: u8,
+
+  /// Whether responses to CON requests may be piggybacked
+  /// on the request's ACK (RFC 7252 §5.2.1).
+  ///
+  /// Some constrained peers mis-handle piggybacked responses; when `false`,
+  /// [`step::ack`](crate::step::ack) sends responses that would have been piggybacked
+  /// separately instead (RFC 7252 §5.2.2), as a CON with a new Id and the request's Token.
+  /// When `true`, CON requests are not ACKed until a response is sent
+  /// (see [`step::ack`](crate::step::ack)); when `false` they are ACKed with
+  /// an Empty message as soon as they are received.
+  ///
+  /// Can be overridden for specific peers with [`piggyback_overrides`](Msg#structfield.piggyback_overrides).
+  ///
+  /// Defaults to `true`.
+  ///
+  /// ```
+  /// use toad::config::Msg;
+  ///
+  /// assert!(Msg::default().piggyback);
+  /// ```
+  pub piggyback: bool,
+
+  /// Peers for which [`piggyback`](Msg#structfield.piggyback) is overridden,
+  /// e.g. `&[(IpAddr::V4(Ipv4Addr::new(192, 168, 0, 7)), false)]` to never piggyback
+  /// responses to a device known to mis-handle them.
+  ///
+  /// See [`Msg::piggyback_to`].
+  ///
+  /// Defaults to `&[]`.
+  ///
+  /// ```
+  /// use toad::config::Msg;
+  ///
+  /// assert!(Msg::default().piggyback_overrides.is_empty());
+  /// ```
+  pub piggyback_overrides: &'static [(IpAddr, bool)],
+}
+
+impl Msg {
+  /// Whether responses to `peer` may be piggybacked,
+  /// according to [`piggyback`](Msg#structfield.piggyback) and
+  /// [`piggyback_overrides`](Msg#structfield.piggyback_overrides)
+  ///
+  /// ```
+  /// use no_std_net::{IpAddr, Ipv4Addr};
+  /// use toad::config::Msg;
+  /// use toad::net::ipv4_socketaddr;
+  ///
+  /// static OVERRIDES: [(IpAddr, bool); 1] = [(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 7)), false)];
+  ///
+  /// let msg = Msg { piggyback_overrides: &OVERRIDES,
+  ///                 ..Msg::default() };
+  ///
+  /// assert!(!msg.piggyback_to(ipv4_socketaddr([10, 0, 0, 7], 5683)));
+  /// assert!(msg.piggyback_to(ipv4_socketaddr([10, 0, 0, 8], 5683)));
+  /// ```
+  pub fn piggyback_to(&self, peer: SocketAddr) -> bool {
+    self.piggyback_overrides
+        .iter()
+        .find(|(ip, _)| *ip == peer.ip())
+        .map(|(_, piggyback)| *piggyback)
+        .unwrap_or(self.piggyback)
+  }
+}
+
+/// Policy for incoming requests with a payload larger than
+/// the platform's payload capacity
+///
+/// See [`Msg.oversized`](Msg#structfield.oversized)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Oversized {
+  /// Respond with 4.13 Request Entity Too Large, with Size1
+  /// set to the platform's payload capacity.
+  Reject,
+  /// Truncate the payload to the platform's payload capacity
+  /// and handle the request as normal.
+  ///
+  /// Truncated requests can be identified with [`Req::truncated`](crate::req::Req::truncated).
+  Truncate,
+}
+
+impl Default for Oversized {
+  fn default() -> Self {
+    Self::Reject
+  }
+}
+
+/// Policy for incoming messages containing malformed options
+///
+/// See [`Msg.malformed_options`](Msg#structfield.malformed_options)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MalformedOptions {
+  /// Fail to parse the whole message, dropping it
+  /// without a response.
+  Strict,
+  /// Remove malformed options from the message and handle it as normal,
+  /// treating them as if they were unrecognized (RFC7252 section 5.4.3):
+  ///
+  /// * Malformed elective options are ignored
+  /// * Requests with a malformed critical option are responded to with 4.02 Bad Option
+  ///
+  /// The latter is done by [`step::option_policy`](crate::step::option_policy),
+  /// using the option numbers [recorded](crate::step::opt::MALFORMED) by [`step::parse`](crate::step::parse).
+  Lenient,
 }
 
+impl Default for MalformedOptions {
+  fn default() -> Self {
+    Self::Lenient
+  }
+}
+
+/// Options defined by RFC7252 (CoAP), RFC7641 (Observe)
+/// and RFC7959 (Block-wise transfers)
+pub const KNOWN_OPTIONS: &[OptNumber] = &[repeat::IF_MATCH,
+                                          no_repeat::HOST,
+                                          repeat::ETAG,
+                                          no_repeat::IF_NONE_MATCH,
+                                          no_repeat::OBSERVE,
+                                          no_repeat::PORT,
+                                          repeat::LOCATION_PATH,
+                                          repeat::PATH,
+                                          no_repeat::CONTENT_FORMAT,
+                                          no_repeat::MAX_AGE,
+                                          repeat::QUERY,
+                                          no_repeat::ACCEPT,
+                                          repeat::LOCATION_QUERY,
+                                          no_repeat::BLOCK2,
+                                          no_repeat::BLOCK1,
+                                          no_repeat::SIZE2,
+                                          no_repeat::PROXY_URI,
+                                          no_repeat::PROXY_SCHEME,
+                                          no_repeat::SIZE1];
+
 impl Default for Con {
   fn default() -> Self {
     Con { unacked_retry_strategy: Strategy::Exponential { init_min: Milliseconds(500),
                                                           init_max: Milliseconds(1_000) },
           acked_retry_strategy: Strategy::Exponential { init_min: Milliseconds(1_000),
                                                         init_max: Milliseconds(2_000) },
+          unacked_response_retry_strategy: None,
           max_attempts: Attempts(4) }
   }
 }
@@ -179,7 +426,74 @@ impl Default for Msg {
           probing_rate: BytesPerSecond(1000),
           con: Con::default(),
           non: Non::default(),
-          multicast_response_leisure: Milliseconds(5000) }
+          retry_jitter: Jitter::NONE,
+          multicast_response_leisure: Milliseconds(5000),
+          understood_options: KNOWN_OPTIONS,
+          oversized: Oversized::default(),
+          malformed_options: MalformedOptions::default(),
+          max_buffered_responses_per_peer: Some(8),
+          service_unavailable_retries: 0,
+          piggyback: true,
+          piggyback_overrides: &[] }
+  }
+}
+
+/// Configuration options related to multicast group membership
+/// and responding to multicast requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Multicast {
+  /// Should servers join the "All CoAP Nodes" multicast groups
+  /// ([`crate::multicast::all_coap_nodes_groups`]) when they start?
+  ///
+  /// Defaults to `false`.
+  ///
+  /// ```
+  /// use toad::config::Multicast;
+  ///
+  /// assert_eq!(Multicast::default().join_all_coap_nodes, false);
+  /// ```
+  pub join_all_coap_nodes: bool,
+
+  /// TTL (hop limit) that servers should set on their socket when they start,
+  /// see [`Socket::set_ttl`](crate::net::Socket::set_ttl).
+  ///
+  /// Multicast datagrams with a TTL of 1 do not leave the local link;
+  /// increase this to reach devices on other subnets of the site.
+  ///
+  /// Defaults to `None` (use the socket's default).
+  ///
+  /// ```
+  /// use toad::config::Multicast;
+  ///
+  /// assert_eq!(Multicast::default().ttl, None);
+  /// ```
+  pub ttl: Option<u32>,
+
+  /// Should servers drop 4.xx and 5.xx responses to requests
+  /// that were sent to a multicast group, rather than sending them?
+  ///
+  /// [RFC7252 section 8.2](https://datatracker.ietf.org/doc/html/rfc7252#section-8.2)
+  /// asks servers not to flood the group with errors (e.g. 4.04 Not Found from
+  /// every device that doesn't have the resource).
+  ///
+  /// Responses to multicast requests that are not suppressed are delayed
+  /// by a random [leisure](Msg#structfield.multicast_response_leisure).
+  ///
+  /// Defaults to `true`.
+  ///
+  /// ```
+  /// use toad::config::Multicast;
+  ///
+  /// assert_eq!(Multicast::default().suppress_error_responses, true);
+  /// ```
+  pub suppress_error_responses: bool,
+}
+
+impl Default for Multicast {
+  fn default() -> Self {
+    Multicast { join_all_coap_nodes: false,
+                ttl: None,
+                suppress_error_responses: true }
   }
 }
 
@@ -188,6 +502,8 @@ impl Default for Msg {
 pub struct Config {
   /// See [`Msg`]
   pub msg: Msg,
+  /// See [`Multicast`]
+  pub multicast: Multicast,
   /// Maximum number of requests that
   /// can be in flight at a given moment
   ///
@@ -199,58 +515,250 @@ pub struct Config {
   /// assert_eq!(Config::default().max_concurrent_requests, 1);
   /// ```
   pub max_concurrent_requests: u8,
+  /// Maximum number of datagrams that may be read from the socket
+  /// by a single [`Platform::poll_req`](crate::platform::Platform::poll_req)
+  /// or [`Platform::poll_resp`](crate::platform::Platform::poll_resp).
+  ///
+  /// Datagrams that do not yield what is being polled for
+  /// (e.g. ACKs, duplicates, responses to other requests) no longer
+  /// end the poll, so bursts are drained without waiting for the next one.
+  /// The effects caused by each datagram are performed before the next is read,
+  /// and the poll returns as soon as a datagram yields what is being polled for.
+  ///
+  /// Default value is `16`
+  ///
+  /// ```
+  /// use toad::config::Config;
+  ///
+  /// assert_eq!(Config::default().max_dgrams_per_poll, 16);
+  /// ```
+  pub max_dgrams_per_poll: u16,
 }
 
 impl Default for Config {
   fn default() -> Self {
     Config { msg: Msg::default(),
-             max_concurrent_requests: 1 }
+             multicast: Multicast::default(),
+             max_concurrent_requests: 1,
+             max_dgrams_per_poll: 16 }
   }
 }
 
+/// A retry strategy in [`Config`], used to say which one is invalid
+/// in [`Error`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StrategyField {
+  /// [`Con.unacked_retry_strategy`](Con#structfield.unacked_retry_strategy)
+  ConUnacked,
+  /// [`Con.acked_retry_strategy`](Con#structfield.acked_retry_strategy)
+  ConAcked,
+  /// [`Con.unacked_response_retry_strategy`](Con#structfield.unacked_response_retry_strategy)
+  ConUnackedResponse,
+  /// [`Non.retry_strategy`](Non#structfield.retry_strategy)
+  Non,
+}
+
+/// Ways a [`Config`] can be invalid, see [`Config::validate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Error {
+  /// [`Con.max_attempts`](Con#structfield.max_attempts) is zero,
+  /// so CON messages would never be sent.
+  ConMaxAttemptsZero,
+  /// [`Non.max_attempts`](Non#structfield.max_attempts) is zero,
+  /// so NON messages would never be sent.
+  NonMaxAttemptsZero,
+  /// A retry strategy's minimum delay is greater than its maximum
+  RetryDelayRange(StrategyField),
+  /// A retry strategy's maximum delay is zero, so retries
+  /// would be sent as fast as the runtime is polled
+  RetryDelayZero(StrategyField),
+  /// [`Msg.probing_rate`](Msg#structfield.probing_rate) is zero
+  ProbingRateZero,
+  /// [`Msg.max_buffered_responses_per_peer`](Msg#structfield.max_buffered_responses_per_peer)
+  /// is `Some(0)`, so no responses could be buffered
+  MaxBufferedResponsesPerPeerZero,
+  /// [`Config.max_concurrent_requests`](Config#structfield.max_concurrent_requests) is zero,
+  /// so no requests could ever be sent
+  MaxConcurrentRequestsZero,
+  /// [`Config.max_dgrams_per_poll`](Config#structfield.max_dgrams_per_poll) is zero,
+  /// so no datagrams could ever be received
+  MaxDgramsPerPollZero,
+  /// The retry strategies and attempts are so large that
+  /// the [`Timing`] parameters derived from them do not fit
+  /// in a `u64` of milliseconds.
+  Overflow,
+}
+
+/// Transmission parameters derived from a [`Config`]
+///
+/// These are the equivalents of the parameters defined in
+/// [RFC7252 section 4.8.2](https://datatracker.ietf.org/doc/html/rfc7252#section-4.8.2),
+/// computed once from the configured retry strategies, attempts and jitter.
+///
+/// ```
+/// use embedded_time::duration::Milliseconds;
+/// use toad::config::Config;
+///
+/// let timing = Config::default().timing();
+/// assert_eq!(timing.max_transmit_span, Milliseconds(8_000u64));
+/// assert_eq!(timing.max_transmit_wait, Milliseconds(16_000u64));
+/// assert_eq!(timing.exchange_lifetime, Milliseconds(208_200u64));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timing {
+  /// The longest time between the first transmission of a message
+  /// and its last retransmission
+  pub max_transmit_span: Millis,
+  /// The longest time between the first transmission of a message
+  /// and giving up on receiving an acknowledgement or response
+  pub max_transmit_wait: Millis,
+  /// The longest time that a message Id may be in use
+  /// after the first transmission of the message it identifies.
+  ///
+  /// [`step::provision_ids`](crate::step::provision_ids) will not
+  /// reuse an Id within this time.
+  pub exchange_lifetime: Millis,
+}
+
 impl Config {
-  pub(crate) fn max_transmit_span_millis(&self) -> u64 {
-    let acked_con = self.msg
-                        .con
-                        .acked_retry_strategy
-                        .max_time(self.msg.con.max_attempts - Attempts(1))
-                        .0 as u64;
-
-    let unacked_con = self.msg
-                          .con
-                          .unacked_retry_strategy
-                          .max_time(self.msg.con.max_attempts - Attempts(1))
-                          .0 as u64;
-
-    let non = self.msg
-                  .non
-                  .retry_strategy
-                  .max_time(self.msg.non.max_attempts - Attempts(1))
-                  .0 as u64;
-
-    acked_con.max(unacked_con).max(non)
-  }
-
-  pub(crate) fn max_transmit_wait_millis(&self) -> u64 {
-    let acked_con = self.msg
-                        .con
-                        .acked_retry_strategy
-                        .max_time(self.msg.con.max_attempts)
-                        .0 as u64;
-
-    let unacked_con = self.msg
-                          .con
-                          .unacked_retry_strategy
-                          .max_time(self.msg.con.max_attempts)
-                          .0 as u64;
-
-    let non = self.msg
-                  .non
-                  .retry_strategy
-                  .max_time(self.msg.non.max_attempts)
-                  .0 as u64;
-
-    acked_con.max(unacked_con).max(non)
+  /// Start building a [`Config`] from the defaults,
+  /// checking it with [`Config::validate`] when it is built.
+  ///
+  /// ```
+  /// use toad::config::{Config, Error};
+  /// use toad::retry::Attempts;
+  ///
+  /// let config = Config::builder().con_max_attempts(Attempts(2))
+  ///                               .max_concurrent_requests(4)
+  ///                               .build()
+  ///                               .unwrap();
+  /// assert_eq!(config.msg.con.max_attempts, Attempts(2));
+  ///
+  /// assert_eq!(Config::builder().non_max_attempts(Attempts(0)).build(),
+  ///            Err(Error::NonMaxAttemptsZero));
+  /// ```
+  pub fn builder() -> ConfigBuilder {
+    ConfigBuilder::default()
+  }
+
+  /// Check that the fields of this config are consistent with one another,
+  /// yielding the [`Timing`] parameters derived from them.
+  ///
+  /// ```
+  /// use embedded_time::duration::Milliseconds;
+  /// use toad::config::{Config, Error, StrategyField};
+  /// use toad::retry::Strategy;
+  ///
+  /// assert!(Config::default().validate().is_ok());
+  ///
+  /// let mut config = Config::default();
+  /// config.msg.non.retry_strategy = Strategy::Delay { min: Milliseconds(10),
+  ///                                                   max: Milliseconds(5) };
+  /// assert_eq!(config.validate(),
+  ///            Err(Error::RetryDelayRange(StrategyField::Non)));
+  /// ```
+  pub fn validate(&self) -> Result<Timing, Error> {
+    if self.msg.con.max_attempts.0 == 0 {
+      return Err(Error::ConMaxAttemptsZero);
+    }
+
+    if self.msg.non.max_attempts.0 == 0 {
+      return Err(Error::NonMaxAttemptsZero);
+    }
+
+    if self.msg.probing_rate.0 == 0 {
+      return Err(Error::ProbingRateZero);
+    }
+
+    if self.msg.max_buffered_responses_per_peer == Some(0) {
+      return Err(Error::MaxBufferedResponsesPerPeerZero);
+    }
+
+    if self.max_concurrent_requests == 0 {
+      return Err(Error::MaxConcurrentRequestsZero);
+    }
+
+    if self.max_dgrams_per_poll == 0 {
+      return Err(Error::MaxDgramsPerPollZero);
+    }
+
+    self.strategies()
+        .into_iter()
+        .filter_map(|(field, strat)| strat.map(|s| (field, s)))
+        .try_for_each(|(field, strategy)| {
+          let (Milliseconds(min), Milliseconds(max)) = match strategy {
+            | Strategy::Delay { min, max } => (min, max),
+            | Strategy::Exponential { init_min, init_max } => (init_min, init_max),
+          };
+
+          if min > max {
+            Err(Error::RetryDelayRange(field))
+          } else if max == 0 {
+            Err(Error::RetryDelayZero(field))
+          } else {
+            Ok(())
+          }
+        })?;
+
+    self.checked_timing().ok_or(Error::Overflow)
+  }
+
+  /// Get the [`Timing`] parameters derived from this config.
+  ///
+  /// Parameters that would overflow (see [`Error::Overflow`])
+  /// saturate at `u64::MAX` milliseconds.
+  pub fn timing(&self) -> Timing {
+    self.checked_timing().unwrap_or(Timing { max_transmit_span: Milliseconds(u64::MAX),
+                                             max_transmit_wait: Milliseconds(u64::MAX),
+                                             exchange_lifetime: Milliseconds(u64::MAX) })
+  }
+
+  fn strategies(&self) -> [(StrategyField, Option<Strategy>); 4] {
+    let Con { unacked_retry_strategy,
+              acked_retry_strategy,
+              unacked_response_retry_strategy,
+              .. } = self.msg.con;
+
+    [(StrategyField::ConUnacked, Some(unacked_retry_strategy)),
+     (StrategyField::ConAcked, Some(acked_retry_strategy)),
+     (StrategyField::ConUnackedResponse, unacked_response_retry_strategy),
+     (StrategyField::Non, Some(self.msg.non.retry_strategy))]
+  }
+
+  /// The longest any retry strategy could take to exhaust its attempts
+  /// (less `less_attempts`), accounting for jitter
+  fn checked_max_retry_time(&self, less_attempts: u16) -> Option<u64> {
+    let con_attempts = Attempts(self.msg.con.max_attempts.0.saturating_sub(less_attempts));
+    let non_attempts = Attempts(self.msg.non.max_attempts.0.saturating_sub(less_attempts));
+
+    self.strategies()
+        .into_iter()
+        .filter_map(|(field, strat)| strat.map(|s| (field, s)))
+        .try_fold(0u64, |longest, (field, strategy)| {
+          let attempts = match field {
+            | StrategyField::Non => non_attempts,
+            | _ => con_attempts,
+          };
+
+          let Milliseconds(time) = strategy.checked_max_time(attempts)?;
+          let jittered = time.checked_mul(self.msg.retry_jitter.0 as u64)?
+                             .checked_div(100)?
+                             .checked_add(time)?;
+
+          Some(longest.max(jittered))
+        })
+  }
+
+  fn checked_timing(&self) -> Option<Timing> {
+    let max_transmit_span = self.checked_max_retry_time(1)?;
+    let max_transmit_wait = self.checked_max_retry_time(0)?;
+    let exchange_lifetime =
+      max_transmit_span.checked_add(2 * self.max_latency_millis())?
+                       .checked_add(self.expected_processing_delay_millis())?;
+
+    Some(Timing { max_transmit_span: Milliseconds(max_transmit_span),
+                  max_transmit_wait: Milliseconds(max_transmit_wait),
+                  exchange_lifetime: Milliseconds(exchange_lifetime) })
   }
 
   // TODO: adjust these on the fly based on actual timings?
@@ -261,10 +769,123 @@ impl Config {
   pub(crate) fn expected_processing_delay_millis(&self) -> u64 {
     200
   }
+}
+
+/// Build a [`Config`], see [`Config::builder`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ConfigBuilder(Config);
+
+impl ConfigBuilder {
+  /// Set [`Msg.token_seed`](Msg#structfield.token_seed)
+  pub fn token_seed(mut self, seed: u16) -> Self {
+    self.0.msg.token_seed = seed;
+    self
+  }
+
+  /// Set [`Msg.probing_rate`](Msg#structfield.probing_rate)
+  pub fn probing_rate(mut self, rate: BytesPerSecond) -> Self {
+    self.0.msg.probing_rate = rate;
+    self
+  }
+
+  /// Set [`Msg.con`](Msg#structfield.con)
+  pub fn con(mut self, con: Con) -> Self {
+    self.0.msg.con = con;
+    self
+  }
+
+  /// Set [`Msg.non`](Msg#structfield.non)
+  pub fn non(mut self, non: Non) -> Self {
+    self.0.msg.non = non;
+    self
+  }
+
+  /// Set [`Con.max_attempts`](Con#structfield.max_attempts)
+  pub fn con_max_attempts(mut self, attempts: Attempts) -> Self {
+    self.0.msg.con.max_attempts = attempts;
+    self
+  }
+
+  /// Set [`Non.max_attempts`](Non#structfield.max_attempts)
+  pub fn non_max_attempts(mut self, attempts: Attempts) -> Self {
+    self.0.msg.non.max_attempts = attempts;
+    self
+  }
+
+  /// Set [`Msg.retry_jitter`](Msg#structfield.retry_jitter)
+  pub fn retry_jitter(mut self, jitter: Jitter) -> Self {
+    self.0.msg.retry_jitter = jitter;
+    self
+  }
+
+  /// Set [`Msg.multicast_response_leisure`](Msg#structfield.multicast_response_leisure)
+  pub fn multicast_response_leisure(mut self, leisure: Millis) -> Self {
+    self.0.msg.multicast_response_leisure = leisure;
+    self
+  }
+
+  /// Set [`Msg.understood_options`](Msg#structfield.understood_options)
+  pub fn understood_options(mut self, opts: &'static [OptNumber]) -> Self {
+    self.0.msg.understood_options = opts;
+    self
+  }
+
+  /// Set [`Msg.oversized`](Msg#structfield.oversized)
+  pub fn oversized(mut self, oversized: Oversized) -> Self {
+    self.0.msg.oversized = oversized;
+    self
+  }
+
+  /// Set [`Msg.malformed_options`](Msg#structfield.malformed_options)
+  pub fn malformed_options(mut self, policy: MalformedOptions) -> Self {
+    self.0.msg.malformed_options = policy;
+    self
+  }
+
+  /// Set [`Msg.max_buffered_responses_per_peer`](Msg#structfield.max_buffered_responses_per_peer)
+  pub fn max_buffered_responses_per_peer(mut self, max: Option<u16>) -> Self {
+    self.0.msg.max_buffered_responses_per_peer = max;
+    self
+  }
+
+  /// Set [`Msg.service_unavailable_retries`](Msg#structfield.service_unavailable_retries)
+  pub fn service_unavailable_retries(mut self, retries: u8) -> Self {
+    self.0.msg.service_unavailable_retries = retries;
+    self
+  }
+
+  /// Set [`Msg.piggyback`](Msg#structfield.piggyback)
+  pub fn piggyback(mut self, piggyback: bool) -> Self {
+    self.0.msg.piggyback = piggyback;
+    self
+  }
+
+  /// Set [`Msg.piggyback_overrides`](Msg#structfield.piggyback_overrides)
+  pub fn piggyback_overrides(mut self, overrides: &'static [(IpAddr, bool)]) -> Self {
+    self.0.msg.piggyback_overrides = overrides;
+    self
+  }
+
+  /// Set [`Config.multicast`](Config#structfield.multicast)
+  pub fn multicast(mut self, multicast: Multicast) -> Self {
+    self.0.multicast = multicast;
+    self
+  }
+
+  /// Set [`Config.max_concurrent_requests`](Config#structfield.max_concurrent_requests)
+  pub fn max_concurrent_requests(mut self, n: u8) -> Self {
+    self.0.max_concurrent_requests = n;
+    self
+  }
+
+  /// Set [`Config.max_dgrams_per_poll`](Config#structfield.max_dgrams_per_poll)
+  pub fn max_dgrams_per_poll(mut self, n: u16) -> Self {
+    self.0.max_dgrams_per_poll = n;
+    self
+  }
 
-  pub(crate) fn exchange_lifetime_millis(&self) -> u64 {
-    self.max_transmit_span_millis()
-    + (2 * self.max_latency_millis())
-    + self.expected_processing_delay_millis()
+  /// [Validate](Config::validate) and yield the config
+  pub fn build(self) -> Result<Config, Error> {
+    self.0.validate().map(|_| self.0)
   }
 }
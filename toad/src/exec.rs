@@ -0,0 +1,112 @@
+use embedded_time::duration::Milliseconds;
+use embedded_time::Clock as _;
+
+use crate::net::Addrd;
+use crate::platform::{Platform, PlatformError, Poll};
+use crate::req::Req;
+use crate::resp::Resp;
+use crate::step::Step;
+use crate::time::{self, Millis};
+
+/// How the time in each slice of a [`superloop`] is shared
+/// between toad and the user's task
+///
+/// ```
+/// use embedded_time::duration::Milliseconds;
+/// use toad::exec::Budget;
+///
+/// let budget = Budget::default();
+/// assert_eq!(budget.runtime, Milliseconds(2u64));
+/// assert_eq!(budget.slice, Milliseconds(10u64));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+  /// The most time that may be spent polling the runtime
+  /// (and handling the requests it yields) in one slice.
+  ///
+  /// The runtime stops being polled as soon as this has elapsed,
+  /// but a request being handled is never interrupted, so a slow
+  /// request handler can overrun it.
+  pub runtime: Millis,
+  /// The length of a slice; whatever the runtime did not use
+  /// is given to the user's task.
+  pub slice: Millis,
+}
+
+impl Default for Budget {
+  fn default() -> Self {
+    Self { runtime: Milliseconds(2),
+           slice: Milliseconds(10) }
+  }
+}
+
+/// Whether a [`superloop`] should keep running, returned
+/// by the user's task at the end of each slice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+  /// Run another slice
+  Continue,
+  /// Stop the superloop
+  Break,
+}
+
+/// Run one slice of a [`superloop`]
+///
+/// Polls `platform` for requests (passing each to `on_request`) until there
+/// are no datagrams left or [`Budget::runtime`] has elapsed, then invokes `task` with
+/// the time remaining in the slice.
+pub fn slice<Pf, S>(platform: &Pf,
+                    budget: Budget,
+                    mut on_request: impl FnMut(Addrd<Req<Pf::Types>>) -> Result<(), Pf::Error>,
+                    task: impl FnOnce(Millis) -> Control)
+                    -> Result<Control, Pf::Error>
+  where Pf: Platform<S>,
+        S: Step<Pf::Types, PollReq = Addrd<Req<Pf::Types>>, PollResp = Addrd<Resp<Pf::Types>>>
+{
+  let start = platform.clock().try_now().map_err(Pf::Error::clock)?;
+  let spent = || {
+    platform.clock()
+            .try_now()
+            .map_err(Pf::Error::clock)
+            .map(|now| time::elapsed(start, now).unwrap_or(Milliseconds(0)))
+  };
+
+  while spent()? < budget.runtime {
+    match platform.try_poll_req()? {
+      | Poll::Ready(req) => on_request(req)?,
+      | Poll::Busy => continue,
+      | Poll::NoData => break,
+    }
+  }
+
+  let Milliseconds(used) = spent()?;
+  Ok(task(Milliseconds(budget.slice.0.saturating_sub(used))))
+}
+
+/// Interleave the toad runtime with a user task on platforms
+/// without an RTOS
+///
+/// Time is divided into slices of [`Budget::slice`] measured with the platform's
+/// [`Clock`](crate::platform::PlatformTypes::Clock). At the start of every slice, the runtime is polled
+/// (for at most [`Budget::runtime`]) and requests it yields are passed to `on_request`.
+/// The rest of the slice is given to `task`, which is passed the time it may run for
+/// before it should return so that toad is not starved (nor the task, by toad).
+///
+/// Runs until `task` returns [`Control::Break`] or an error is encountered.
+///
+/// See `examples/superloop.rs` for a complete example.
+pub fn superloop<Pf, S>(platform: &Pf,
+                        budget: Budget,
+                        mut on_request: impl FnMut(Addrd<Req<Pf::Types>>) -> Result<(), Pf::Error>,
+                        mut task: impl FnMut(Millis) -> Control)
+                        -> Result<(), Pf::Error>
+  where Pf: Platform<S>,
+        S: Step<Pf::Types, PollReq = Addrd<Req<Pf::Types>>, PollResp = Addrd<Resp<Pf::Types>>>
+{
+  loop {
+    match slice(platform, budget, &mut on_request, &mut task)? {
+      | Control::Continue => continue,
+      | Control::Break => return Ok(()),
+    }
+  }
+}
@@ -22,6 +22,16 @@
 //! - CoAP customarily sits on top of UDP (however the standard is [in the process of being adapted](https://tools.ietf.org/id/draft-ietf-core-coap-tcp-tls-11.html) to also run on TCP, like HTTP)
 //! - Because UDP is a "connectionless" protocol, it offers no guarantee of "conversation" between traditional client and server roles. All the UDP transport layer gives you is a method to listen for messages thrown at you, and to throw messages at someone. Owing to this, CoAP machines are expected to perform both client and server roles (or more accurately, _sender_ and _receiver_ roles)
 //! - While _classes_ of status codes are the same (Success 2xx -> 2.xx, Client error 4xx -> 4.xx, Server error 5xx -> 5.xx), the semantics of the individual response codes differ.
+//!
+//! ## Client-only & server-only builds
+//! The `client` and `server` features (both enabled by default) control
+//! which roles toad is compiled for. Devices that only ever make requests,
+//! like sensors reporting readings, can disable `server` to compile out
+//! the [`server`](crate::server) module and Observe step:
+//!
+//! ```toml
+//! toad = { version = "*", default-features = false, features = ["std", "client"] }
+//! ```
 
 // x-release-please-start-version
 #![doc(html_root_url = "https://docs.rs/toad/0.19.1")]
@@ -42,6 +52,7 @@
 // -
 // features
 #![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
 
 #[cfg(feature = "alloc")]
 extern crate alloc as std_alloc;
@@ -111,6 +122,9 @@ pub mod req;
 /// [`log::Level::Error`] messages are not ignored.
 pub mod step;
 
+/// Allocation-free helpers for log messages
+pub mod logging;
+
 /// platform configuration
 pub mod platform;
 
@@ -123,6 +137,10 @@ pub mod time;
 /// configuring runtime behavior
 pub mod config;
 
+/// cooperative scheduling of the runtime alongside user code,
+/// for platforms without an RTOS
+pub mod exec;
+
 /// `std`-only toad stuff
 #[cfg(feature = "std")]
 #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
@@ -131,13 +149,30 @@ pub mod std;
 mod option;
 
 /// Server functionality
+#[cfg(feature = "server")]
+#[cfg_attr(docsrs, doc(cfg(feature = "server")))]
 pub mod server;
 
+/// Observe (RFC 7641) helpers shared by clients & servers
+pub mod observe;
+
+/// Client functionality
+#[cfg(feature = "client")]
+#[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+pub mod client;
+
+/// Firmware / over-the-air update helpers, built on block-wise transfer (RFC 7959)
+///
+/// * [`download()`](ota::download) - download an image into a [`Sink`](ota::Sink), resuming from [`Progress`](ota::Progress) (requires the `client` feature)
+/// * [`serve()`](ota::serve) - serve an image from a function that reads blocks of it (requires the `server` feature)
+#[cfg(any(feature = "client", feature = "server"))]
+pub mod ota;
+
 pub use option::{ContentFormat, ToCoapValue};
 
 /// Helper constants and functions for creating multicast addresses
 pub mod multicast {
-  use no_std_net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+  use no_std_net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4};
 
   /// IPv4 "All CoAP devices" multicast address.
   ///
@@ -145,6 +180,34 @@ pub mod multicast {
   /// that you use this address with a port specific to your application.
   pub const ALL_COAP_DEVICES_IP: Ipv4Addr = Ipv4Addr::new(224, 0, 1, 187);
 
+  /// IPv6 link-local "All CoAP Nodes" multicast address (`ff02::fd`)
+  pub const ALL_COAP_NODES_IPV6_LINK_LOCAL: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfd);
+
+  /// IPv6 site-local "All CoAP Nodes" multicast address (`ff05::fd`)
+  pub const ALL_COAP_NODES_IPV6_SITE_LOCAL: Ipv6Addr = Ipv6Addr::new(0xff05, 0, 0, 0, 0, 0, 0, 0xfd);
+
+  /// The "All CoAP Nodes" multicast groups that a socket bound to `local`
+  /// should join, based on the address family of `local`.
+  ///
+  /// ```
+  /// use no_std_net::{IpAddr, Ipv4Addr};
+  /// use toad::multicast::{all_coap_nodes_groups, ALL_COAP_DEVICES_IP};
+  /// use toad::net::ipv4_socketaddr;
+  ///
+  /// assert_eq!(all_coap_nodes_groups(ipv4_socketaddr([0, 0, 0, 0], 5683)),
+  ///            &[IpAddr::V4(ALL_COAP_DEVICES_IP)]);
+  /// ```
+  pub const fn all_coap_nodes_groups(local: SocketAddr) -> &'static [IpAddr] {
+    const V4: [IpAddr; 1] = [IpAddr::V4(ALL_COAP_DEVICES_IP)];
+    const V6: [IpAddr; 2] = [IpAddr::V6(ALL_COAP_NODES_IPV6_LINK_LOCAL),
+                             IpAddr::V6(ALL_COAP_NODES_IPV6_SITE_LOCAL)];
+
+    match local {
+      | SocketAddr::V4(_) => &V4,
+      | SocketAddr::V6(_) => &V6,
+    }
+  }
+
   /// Create a SocketAddr (IP + port) with the [`ALL_COAP_DEVICES_IP`] address
   ///
   /// If using multicast to discover devices, it's recommended
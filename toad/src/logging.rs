@@ -0,0 +1,100 @@
+use core::fmt;
+
+use crate::platform::{Message, PlatformTypes};
+
+/// Lazily formats a short summary of a message
+/// (type, code & token) when displayed.
+///
+/// See [`msg_summary`]
+pub struct Summary<'a, P>(&'a Message<P>) where P: PlatformTypes;
+
+impl<'a, P> Clone for Summary<'a, P> where P: PlatformTypes
+{
+  fn clone(&self) -> Self {
+    Self(self.0)
+  }
+}
+
+impl<'a, P> Copy for Summary<'a, P> where P: PlatformTypes {}
+
+impl<'a, P> fmt::Debug for Summary<'a, P> where P: PlatformTypes
+{
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt::Display::fmt(self, f)
+  }
+}
+
+impl<'a, P> fmt::Display for Summary<'a, P> where P: PlatformTypes
+{
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?} {:?} {:?}", self.0.ty, self.0.code, self.0.token)
+  }
+}
+
+/// Summarize a message for logging
+///
+/// This does not format anything until the summary is displayed,
+/// so it costs nothing when used in a log message that is filtered out.
+///
+/// ```
+/// use toad::logging::msg_summary;
+/// use toad::platform::Message;
+/// use toad::req::Req;
+/// use toad::std::{dtls, PlatformTypes as Std};
+///
+/// let msg = Message::<Std<dtls::N>>::from(Req::get("hello"));
+/// assert_eq!(msg_summary(&msg).to_string(),
+///            format!("{:?} {:?} {:?}", msg.ty, msg.code, msg.token));
+/// ```
+pub fn msg_summary<P>(msg: &Message<P>) -> Summary<'_, P>
+  where P: PlatformTypes
+{
+  Summary(msg)
+}
+
+/// Lazily formats the items of an iterator as `[a,b,c]` when displayed.
+///
+/// See [`list`]
+#[derive(Clone, Copy)]
+pub struct List<I>(I);
+
+impl<I> fmt::Debug for List<I>
+  where I: Iterator + Clone,
+        I::Item: fmt::Debug
+{
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt::Display::fmt(self, f)
+  }
+}
+
+impl<I> fmt::Display for List<I>
+  where I: Iterator + Clone,
+        I::Item: fmt::Debug
+{
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "[")?;
+    self.0.clone().enumerate().try_for_each(|(ix, item)| {
+                                if ix > 0 {
+                                  write!(f, ",")?;
+                                }
+                                write!(f, "{:?}", item)
+                              })?;
+    write!(f, "]")
+  }
+}
+
+/// Format the items of an iterator for logging, without
+/// collecting them into an intermediate string.
+///
+/// ```
+/// use toad::logging::list;
+///
+/// assert_eq!(list([1, 2, 3].iter()).to_string(), "[1,2,3]");
+/// assert_eq!(list(core::iter::empty::<u8>()).to_string(), "[]");
+/// ```
+pub fn list<I>(items: I) -> List<I::IntoIter>
+  where I: IntoIterator,
+        I::IntoIter: Clone
+{
+  List(items.into_iter())
+}
@@ -1,7 +1,19 @@
 use naan::prelude::MonadOnce;
 use no_std_net::{Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs};
+use tinyvec::ArrayVec;
 use toad_array::Array;
 
+/// Run the runtime over arbitrary framed byte pipes (e.g. serial or LoRa links)
+pub mod framed;
+
+/// Track how reliably peers respond & stop sending requests to ones that don't
+pub mod health;
+
+/// [`Socket`] implementation for [`smoltcp`](https://docs.rs/smoltcp) udp sockets
+#[cfg(feature = "smoltcp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "smoltcp")))]
+pub mod smoltcp;
+
 /// Creates a [`SocketAddr::V4`] from an ipv4 address and port
 pub fn ipv4_socketaddr([a, b, c, d]: [u8; 4], port: u16) -> SocketAddr {
   SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(a, b, c, d), port))
@@ -65,6 +77,74 @@ impl<T> AsMut<T> for Addrd<T> {
   }
 }
 
+/// A change in the lifecycle of a session with a peer
+/// on a connection-oriented transport (e.g. DTLS, TCP)
+///
+/// These are yielded by [`Socket::poll_session`], and made available
+/// to steps in [`Snapshot.session`](crate::platform::Snapshot#structfield.session)
+/// so that they can discard state (e.g. Observe subscriptions or
+/// messages awaiting retry) belonging to peers that are no longer reachable.
+///
+/// Connectionless transports (e.g. plain UDP) never yield these.
+#[derive(PartialEq, PartialOrd, Eq, Ord, Hash, Debug, Clone, Copy)]
+pub enum Session {
+  /// A session with the peer was established
+  Connected,
+  /// The session with the peer was closed, or was lost
+  Disconnected,
+  /// Establishing a session with the peer failed
+  HandshakeFailed,
+}
+
+impl Session {
+  /// Is the peer unreachable after this event?
+  ///
+  /// ```
+  /// use toad::net::Session;
+  ///
+  /// assert!(!Session::Connected.is_closed());
+  /// assert!(Session::Disconnected.is_closed());
+  /// assert!(Session::HandshakeFailed.is_closed());
+  /// ```
+  pub fn is_closed(&self) -> bool {
+    !matches!(self, Self::Connected)
+  }
+}
+
+/// The authenticated identity of a peer on a secure transport (e.g. DTLS)
+///
+/// This is obtained with [`Socket::peer_identity`], and is made available
+/// to servers with [`Req::identity`](crate::req::Req::identity) for use in
+/// authorization decisions.
+#[derive(PartialEq, PartialOrd, Eq, Ord, Hash, Debug, Clone)]
+pub enum Identity {
+  /// The identity a peer provided during a
+  /// [Pre-Shared Key](https://www.rfc-editor.org/rfc/rfc4279) handshake
+  Psk(ArrayVec<[u8; 128]>),
+  /// SHA-256 fingerprint of the DER-encoded public key
+  /// ([SubjectPublicKeyInfo](https://www.rfc-editor.org/rfc/rfc7250#section-3))
+  /// presented by the peer, either as a
+  /// [Raw Public Key](https://www.rfc-editor.org/rfc/rfc7250) or within an X.509 certificate
+  RawPublicKey([u8; 32]),
+}
+
+impl Identity {
+  /// Create a [`Identity::Psk`], yielding `None` if `identity` is longer than 128 bytes
+  ///
+  /// ```
+  /// use toad::net::Identity;
+  ///
+  /// assert!(Identity::psk(b"client-1").is_some());
+  /// assert!(Identity::psk(&[0u8; 129]).is_none());
+  /// ```
+  pub fn psk(identity: &[u8]) -> Option<Self> {
+    let mut bytes = ArrayVec::new();
+    bytes.try_extend_from_slice(identity)
+         .is_none()
+         .then(|| Self::Psk(bytes))
+  }
+}
+
 /// A CoAP network socket
 ///
 /// This mirrors the Udp socket traits in embedded-nal, but allows us to implement them for foreign types (like `std::net::UdpSocket`).
@@ -125,6 +205,18 @@ pub trait Socket: Sized {
     self.send(msg)
   }
 
+  /// Invoke `f`, allowing datagrams [sent](Socket::send) while it runs to be
+  /// held by the socket and written all at once when it returns.
+  ///
+  /// Calls may be nested, in which case datagrams are written when the
+  /// outermost call returns.
+  ///
+  /// # Default Implementation
+  /// Invokes `f`; datagrams are written as soon as they are sent.
+  fn batch<R>(&self, f: impl FnOnce() -> R) -> Result<R, Self::Error> {
+    Ok(f())
+  }
+
   /// Pull a buffered datagram from the socket, along with the address to the sender.
   ///
   /// This clears the internal reciever queue, meaning that subsequent calls
@@ -170,6 +262,93 @@ pub trait Socket: Sized {
     }
   }
 
+  /// Get the authenticated [`Identity`] of the peer at `addr`, if any.
+  ///
+  /// # Default Implementation
+  /// Sockets that do not authenticate peers (e.g. plain UDP)
+  /// should not override this, and will always yield `None`.
+  fn peer_identity(&self, addr: SocketAddr) -> Option<Identity> {
+    let _ = addr;
+    None
+  }
+
+  /// Get the local address that the most recent datagram from `addr`
+  /// was sent to, e.g. to tell unicast requests apart from multicast ones.
+  ///
+  /// # Default Implementation
+  /// Yields [`Socket::local_addr`]. Sockets that can see the destination
+  /// of each datagram (e.g. via `IP_PKTINFO`, like `toad::std::net::BatchUdpSocket`)
+  /// should override this.
+  fn recvd_dest(&self, addr: SocketAddr) -> SocketAddr {
+    let _ = addr;
+    self.local_addr()
+  }
+
+  /// Pull the next [`Session`] lifecycle event from the socket, if any,
+  /// along with the address of the peer it concerns.
+  ///
+  /// # Default Implementation
+  /// Connectionless sockets (e.g. plain UDP) should not override this,
+  /// and will always yield `None`.
+  fn poll_session(&self) -> Option<Addrd<Session>> {
+    None
+  }
+
   /// Join a multicast group
+  ///
+  /// `addr` may be an IPv4 or IPv6 multicast address, although
+  /// implementations may only support groups matching the
+  /// address family of [`Socket::local_addr`].
+  ///
+  /// Joining a group that this socket is already a member of
+  /// should not error.
   fn join_multicast(&self, addr: no_std_net::IpAddr) -> Result<(), Self::Error>;
+
+  /// Leave a multicast group previously joined with [`Socket::join_multicast`]
+  fn leave_multicast(&self, addr: no_std_net::IpAddr) -> Result<(), Self::Error>;
+
+  /// Set the time-to-live (hop limit) of datagrams sent by this socket,
+  /// including multicast datagrams where supported.
+  ///
+  /// A TTL of 1 keeps multicast traffic on the local link,
+  /// larger values allow it to reach site-local subnets.
+  ///
+  /// # Default Implementation
+  /// Sockets that do not support configuring TTL
+  /// should not override this, and will do nothing.
+  fn set_ttl(&self, ttl: u32) -> Result<(), Self::Error> {
+    let _ = ttl;
+    Ok(())
+  }
+
+  /// Get the time-to-live (hop limit) of datagrams sent by this socket
+  ///
+  /// # Default Implementation
+  /// Sockets that do not support configuring TTL
+  /// should not override this, and will always yield `None`.
+  fn ttl(&self) -> Result<Option<u32>, Self::Error> {
+    Ok(None)
+  }
+
+  /// Hint at the size (in bytes) of the OS receive buffer for this socket.
+  ///
+  /// Implementations may round or clamp `bytes`, or ignore it entirely.
+  ///
+  /// # Default Implementation
+  /// Does nothing.
+  fn set_recv_buffer_size(&self, bytes: usize) -> Result<(), Self::Error> {
+    let _ = bytes;
+    Ok(())
+  }
+
+  /// Hint at the size (in bytes) of the OS send buffer for this socket.
+  ///
+  /// Implementations may round or clamp `bytes`, or ignore it entirely.
+  ///
+  /// # Default Implementation
+  /// Does nothing.
+  fn set_send_buffer_size(&self, bytes: usize) -> Result<(), Self::Error> {
+    let _ = bytes;
+    Ok(())
+  }
 }
@@ -0,0 +1,230 @@
+use no_std_net::{IpAddr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use tinyvec::ArrayVec;
+use toad_stem::Stem;
+
+use super::Addrd;
+
+/// A transport that can send & receive opaque frames to & from peers
+///
+/// This is the minimum the runtime needs from a transport, and is much easier
+/// to implement than [`super::Socket`] for links that aren't IP at all
+/// (e.g. LoRa radios, serial lines or SMS gateways).
+///
+/// Peers are identified by a [`SocketAddr`] even when the link has no notion of one;
+/// implementors should map their native addressing to a stable pseudo-address
+/// (see [`pseudo_addr`]).
+///
+/// Wrap a pipe in [`Framed`] to use it as a [`super::Socket`].
+pub trait FramedPipe {
+  /// Errors yielded by the pipe
+  type Error: core::fmt::Debug;
+
+  /// The (pseudo-)address of this end of the pipe
+  fn local_addr(&self) -> SocketAddr;
+
+  /// Send a frame to a peer
+  fn send_frame(&mut self, frame: Addrd<&[u8]>) -> nb::Result<(), Self::Error>;
+
+  /// Receive a frame, copying it into `buffer`
+  ///
+  /// Like [`super::Socket::recv`], bytes that do not fit in `buffer`
+  /// should be dropped and not considered an error condition.
+  fn recv_frame(&mut self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error>;
+}
+
+/// Create a pseudo-address for a node on a link without IP addressing
+///
+/// Node ids are embedded in the interface identifier of an IPv6 unique local address
+/// (`fd00::<node>`), paired with the default CoAP port.
+///
+/// ```
+/// use toad::net::framed::{pseudo_addr, pseudo_node};
+///
+/// let addr = pseudo_addr(0xBEEF);
+/// assert_eq!(addr.to_string(), "[fd00::beef]:5683");
+/// assert_eq!(pseudo_node(addr), Some(0xBEEF));
+/// ```
+pub fn pseudo_addr(node: u64) -> SocketAddr {
+  let [a, b, c, d] = [(node >> 48) as u16, (node >> 32) as u16, (node >> 16) as u16, node as u16];
+  SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0xfd00, 0, 0, 0, a, b, c, d)), 5683)
+}
+
+/// Get the node id from an address created with [`pseudo_addr`]
+pub fn pseudo_node(addr: SocketAddr) -> Option<u64> {
+  match addr.ip() {
+    | IpAddr::V6(ip) if ip.segments()[..4] == [0xfd00, 0, 0, 0] => {
+      Some(ip.segments()[4..].iter()
+                             .fold(0u64, |node, seg| (node << 16) | *seg as u64))
+    },
+    | _ => None,
+  }
+}
+
+/// Errors yielded by [`Framed`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+  /// The pipe failed
+  Pipe(E),
+  /// Pipes are created by the caller,
+  /// so they can't be created from just an address.
+  ///
+  /// Use [`Framed::new`] instead.
+  BindRawUnsupported,
+  /// Pipes have no notion of multicast groups
+  MulticastUnsupported,
+}
+
+type Frame = ArrayVec<[u8; 1152]>;
+
+struct Inner<P> {
+  pipe: P,
+  peeked: Option<Addrd<Frame>>,
+}
+
+/// [`super::Socket`] implementation backed by any [`FramedPipe`]
+///
+/// This lets the step pipeline run over links that aren't UDP.
+/// A frame peeked by the runtime is buffered until it is received.
+pub struct Framed<P> {
+  inner: Stem<Inner<P>>,
+}
+
+impl<P> core::fmt::Debug for Framed<P> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    write!(f, "Framed {{ /* fields hidden */ }}")
+  }
+}
+
+impl<P: FramedPipe> Framed<P> {
+  /// Use a [`FramedPipe`] as a [`super::Socket`]
+  pub fn new(pipe: P) -> Self {
+    Self { inner: Stem::new(Inner { pipe, peeked: None }) }
+  }
+}
+
+fn copy(frame: &Addrd<Frame>, buffer: &mut [u8]) -> Addrd<usize> {
+  let n = frame.data().len().min(buffer.len());
+  buffer[..n].copy_from_slice(&frame.data()[..n]);
+  Addrd(n, frame.addr())
+}
+
+impl<P: FramedPipe> super::Socket for Framed<P> {
+  type Error = Error<P::Error>;
+  type Dgram = Frame;
+
+  fn local_addr(&self) -> SocketAddr {
+    self.inner.map_ref(|Inner { pipe, .. }| pipe.local_addr())
+  }
+
+  fn empty_dgram() -> Self::Dgram {
+    ArrayVec::from([0u8; 1152])
+  }
+
+  fn bind_raw<A: ToSocketAddrs>(_: A) -> Result<Self, Self::Error> {
+    Err(Error::BindRawUnsupported)
+  }
+
+  fn send(&self, msg: Addrd<&[u8]>) -> nb::Result<(), Self::Error> {
+    self.inner.map_mut(|Inner { pipe, .. }| {
+                pipe.send_frame(msg)
+                    .map_err(|e| e.map(Error::Pipe))
+              })
+  }
+
+  fn recv(&self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
+    self.inner.map_mut(|Inner { pipe, peeked }| match peeked.take() {
+                | Some(frame) => Ok(copy(&frame, buffer)),
+                | None => pipe.recv_frame(buffer).map_err(|e| e.map(Error::Pipe)),
+              })
+  }
+
+  fn peek(&self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
+    self.inner.map_mut(|Inner { pipe, peeked }| {
+                if peeked.is_none() {
+                  let mut frame = Self::empty_dgram();
+                  let Addrd(n, addr) = pipe.recv_frame(&mut frame)
+                                           .map_err(|e| e.map(Error::Pipe))?;
+                  frame.truncate(n);
+                  *peeked = Some(Addrd(frame, addr));
+                }
+
+                Ok(copy(peeked.as_ref().unwrap(), buffer))
+              })
+  }
+
+  fn join_multicast(&self, _: IpAddr) -> Result<(), Self::Error> {
+    Err(Error::MulticastUnsupported)
+  }
+
+  fn leave_multicast(&self, _: IpAddr) -> Result<(), Self::Error> {
+    Err(Error::MulticastUnsupported)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::VecDeque;
+
+  use super::*;
+  use crate::net::Socket;
+
+  /// A pipe that delivers everything sent on it back to itself
+  #[derive(Default)]
+  struct Loopback(VecDeque<Addrd<Vec<u8>>>);
+
+  impl FramedPipe for Loopback {
+    type Error = ();
+
+    fn local_addr(&self) -> SocketAddr {
+      pseudo_addr(1)
+    }
+
+    fn send_frame(&mut self, frame: Addrd<&[u8]>) -> nb::Result<(), ()> {
+      self.0.push_back(frame.map(|f| f.to_vec()));
+      Ok(())
+    }
+
+    fn recv_frame(&mut self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, ()> {
+      let Addrd(frame, addr) = self.0.pop_front().ok_or(nb::Error::WouldBlock)?;
+      let n = frame.len().min(buffer.len());
+      buffer[..n].copy_from_slice(&frame[..n]);
+      Ok(Addrd(n, addr))
+    }
+  }
+
+  #[test]
+  fn pseudo_addr_roundtrip() {
+    assert_eq!(pseudo_node(pseudo_addr(0)), Some(0));
+    assert_eq!(pseudo_node(pseudo_addr(u64::MAX)), Some(u64::MAX));
+    assert_eq!(pseudo_node(crate::net::ipv4_socketaddr([127, 0, 0, 1], 5683)),
+               None);
+  }
+
+  #[test]
+  fn peek_then_recv_yields_same_frame() {
+    let sock = Framed::new(Loopback::default());
+    let addr = pseudo_addr(2);
+
+    let mut buf = [0u8; 8];
+    assert_eq!(sock.recv(&mut buf), Err(nb::Error::WouldBlock));
+
+    sock.send(Addrd(&[1, 2, 3], addr)).unwrap();
+    sock.send(Addrd(&[4], addr)).unwrap();
+
+    assert_eq!(sock.peek_addr(), Ok(addr));
+    assert_eq!(sock.peek(&mut buf), Ok(Addrd(3, addr)));
+    assert_eq!(sock.recv(&mut buf), Ok(Addrd(3, addr)));
+    assert_eq!(&buf[..3], &[1, 2, 3]);
+
+    assert_eq!(sock.recv(&mut buf), Ok(Addrd(1, addr)));
+    assert_eq!(buf[0], 4);
+    assert_eq!(sock.recv(&mut buf), Err(nb::Error::WouldBlock));
+  }
+
+  #[test]
+  fn multicast_unsupported() {
+    let sock = Framed::new(Loopback::default());
+    assert_eq!(sock.join_multicast(IpAddr::V4(crate::multicast::ALL_COAP_DEVICES_IP)),
+               Err(Error::MulticastUnsupported));
+  }
+}
@@ -0,0 +1,303 @@
+use embedded_time::duration::Milliseconds;
+use no_std_net::SocketAddr;
+
+use crate::time::Millis;
+
+/// How much a single [`Outcome`] moves a peer's [failure rate](PeerHealth::failure_rate);
+/// each outcome contributes `1 / EWMA_WEIGHT` of the new rate.
+pub const EWMA_WEIGHT: i32 = 8;
+
+/// How an exchange with a peer went
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Outcome {
+  /// The peer acknowledged or responded to the request
+  Success,
+  /// The peer did not acknowledge or respond to the request
+  /// before its retransmissions were exhausted
+  Timeout,
+  /// The peer rejected the request with a RESET
+  Reset,
+}
+
+impl Outcome {
+  /// Is this a [`Outcome::Timeout`] or [`Outcome::Reset`]?
+  pub fn is_failure(&self) -> bool {
+    !matches!(self, Outcome::Success)
+  }
+}
+
+/// When to stop sending requests to a peer
+///
+/// ```
+/// use embedded_time::duration::Milliseconds;
+/// use toad::net::health::Breaker;
+///
+/// assert_eq!(Breaker::default(),
+///            Breaker { trip_after: 5,
+///                      cool_down: Milliseconds(30_000) });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Breaker {
+  /// Number of consecutive failed exchanges after which
+  /// the peer is considered unhealthy
+  pub trip_after: u16,
+  /// How long to refuse to send requests to an unhealthy peer.
+  ///
+  /// Once this has elapsed a single request is let through;
+  /// if it fails the breaker trips again immediately, and if it
+  /// succeeds the peer is healthy again.
+  pub cool_down: Millis,
+}
+
+impl Default for Breaker {
+  fn default() -> Self {
+    Self { trip_after: 5,
+           cool_down: Milliseconds(30_000) }
+  }
+}
+
+/// Health of a single peer
+///
+/// ```
+/// use embedded_time::duration::Milliseconds;
+/// use toad::net::health::{Breaker, Outcome, PeerHealth};
+///
+/// let breaker = Breaker { trip_after: 2,
+///                         cool_down: Milliseconds(1_000) };
+/// let mut peer = PeerHealth::default();
+///
+/// peer.record(Milliseconds(0), Outcome::Timeout, &breaker);
+/// assert!(!peer.is_unhealthy(Milliseconds(0)));
+///
+/// peer.record(Milliseconds(10), Outcome::Reset, &breaker);
+/// assert!(peer.is_unhealthy(Milliseconds(10)));
+/// assert!(!peer.is_unhealthy(Milliseconds(1_010)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PeerHealth {
+  failure_rate: u16,
+  consecutive_failures: u16,
+  unhealthy_until: Option<Millis>,
+}
+
+impl PeerHealth {
+  /// Update the peer's health with the outcome of an exchange
+  /// that concluded at `now`
+  pub fn record(&mut self, now: Millis, outcome: Outcome, breaker: &Breaker) {
+    let sample = if outcome.is_failure() { 1000 } else { 0 };
+    let rate = self.failure_rate as i32;
+    self.failure_rate = (rate + (sample - rate) / EWMA_WEIGHT) as u16;
+
+    if outcome.is_failure() {
+      self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+    } else {
+      self.consecutive_failures = 0;
+      self.unhealthy_until = None;
+    }
+
+    if self.consecutive_failures >= breaker.trip_after {
+      self.unhealthy_until = Some(Milliseconds(now.0.saturating_add(breaker.cool_down.0)));
+    }
+  }
+
+  /// Exponential moving average of the portion of exchanges
+  /// that failed, in thousandths (`0..=1000`)
+  pub fn failure_rate(&self) -> u16 {
+    self.failure_rate
+  }
+
+  /// Number of exchanges that failed since the last one that succeeded
+  pub fn consecutive_failures(&self) -> u16 {
+    self.consecutive_failures
+  }
+
+  /// Should requests to this peer fail fast instead of being sent?
+  pub fn is_unhealthy(&self, now: Millis) -> bool {
+    self.unhealthy_until
+        .map(|until| now < until)
+        .unwrap_or(false)
+  }
+
+  /// When the peer's cool-down ends, if its breaker has tripped
+  pub fn unhealthy_until(&self) -> Option<Millis> {
+    self.unhealthy_until
+  }
+}
+
+/// A request was not sent because the peer is unhealthy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Unhealthy {
+  /// The peer
+  pub addr: SocketAddr,
+  /// When requests to the peer will be let through again
+  pub until: Millis,
+}
+
+/// [`PeerHealth`] of up to `N` peers
+///
+/// When a peer is seen for the first time and `N` peers are already
+/// tracked, the healthy peer with the lowest failure rate is forgotten
+/// to make room. Unhealthy peers are never forgotten before their
+/// cool-down ends.
+///
+/// ```
+/// use embedded_time::duration::Milliseconds;
+/// use toad::net::health::{Breaker, Health, Outcome};
+/// use toad::net::ipv4_socketaddr;
+///
+/// let peer = ipv4_socketaddr([10, 0, 0, 1], 5683);
+/// let mut health = Health::<4>::new(Breaker { trip_after: 1,
+///                                             cool_down: Milliseconds(500) });
+///
+/// assert_eq!(health.check(peer, Milliseconds(0)), Ok(()));
+///
+/// health.record(peer, Milliseconds(0), Outcome::Timeout);
+/// assert!(health.check(peer, Milliseconds(100)).is_err());
+/// assert_eq!(health.check(peer, Milliseconds(500)), Ok(()));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Health<const N: usize> {
+  breaker: Breaker,
+  peers: [Option<(SocketAddr, PeerHealth)>; N],
+}
+
+impl<const N: usize> Default for Health<N> {
+  fn default() -> Self {
+    Self::new(Breaker::default())
+  }
+}
+
+impl<const N: usize> Health<N> {
+  /// Create a table of peer health using `breaker`
+  pub fn new(breaker: Breaker) -> Self {
+    Self { breaker,
+           peers: [None; N] }
+  }
+
+  /// The breaker settings
+  pub fn breaker(&self) -> Breaker {
+    self.breaker
+  }
+
+  /// Health of peer `addr`, if it has been seen
+  pub fn get(&self, addr: SocketAddr) -> Option<PeerHealth> {
+    self.peers
+        .iter()
+        .flatten()
+        .find(|(a, _)| *a == addr)
+        .map(|(_, h)| *h)
+  }
+
+  /// Iterate over the peers being tracked
+  pub fn iter(&self) -> impl Iterator<Item = &(SocketAddr, PeerHealth)> {
+    self.peers.iter().flatten()
+  }
+
+  /// Record the outcome of an exchange with `addr` that concluded at `now`
+  pub fn record(&mut self, addr: SocketAddr, now: Millis, outcome: Outcome) {
+    let breaker = self.breaker;
+    if let Some(health) = self.entry(addr, now) {
+      health.record(now, outcome, &breaker);
+    }
+  }
+
+  /// Fail if requests to `addr` should not be sent at `now`
+  pub fn check(&self, addr: SocketAddr, now: Millis) -> Result<(), Unhealthy> {
+    match self.get(addr).and_then(|h| h.unhealthy_until()) {
+      | Some(until) if now < until => Err(Unhealthy { addr, until }),
+      | _ => Ok(()),
+    }
+  }
+
+  fn entry(&mut self, addr: SocketAddr, now: Millis) -> Option<&mut PeerHealth> {
+    let ix = self.peers
+                 .iter()
+                 .position(|p| matches!(p, Some((a, _)) if *a == addr))
+                 .or_else(|| self.peers.iter().position(Option::is_none))
+                 .or_else(|| {
+                   self.peers
+                       .iter()
+                       .enumerate()
+                       .filter_map(|(ix, p)| p.map(|(_, h)| (ix, h)))
+                       .filter(|(_, h)| !h.is_unhealthy(now))
+                       .min_by_key(|(_, h)| h.failure_rate())
+                       .map(|(ix, _)| ix)
+                 })?;
+
+    let slot = &mut self.peers[ix];
+    if !matches!(slot, Some((a, _)) if *a == addr) {
+      *slot = Some((addr, PeerHealth::default()));
+    }
+
+    slot.as_mut().map(|(_, h)| h)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::net::ipv4_socketaddr;
+
+  fn addr(n: u8) -> SocketAddr {
+    ipv4_socketaddr([10, 0, 0, n], 5683)
+  }
+
+  #[test]
+  fn failure_rate_is_moving_average() {
+    let breaker = Breaker::default();
+    let mut peer = PeerHealth::default();
+
+    peer.record(Milliseconds(0), Outcome::Timeout, &breaker);
+    assert_eq!(peer.failure_rate(), 125);
+
+    peer.record(Milliseconds(0), Outcome::Reset, &breaker);
+    assert_eq!(peer.failure_rate(), 234);
+
+    peer.record(Milliseconds(0), Outcome::Success, &breaker);
+    assert_eq!(peer.failure_rate(), 205);
+    assert_eq!(peer.consecutive_failures(), 0);
+  }
+
+  #[test]
+  fn trips_after_consecutive_failures() {
+    let breaker = Breaker { trip_after: 3,
+                            cool_down: Milliseconds(1_000) };
+    let mut peer = PeerHealth::default();
+
+    peer.record(Milliseconds(0), Outcome::Timeout, &breaker);
+    peer.record(Milliseconds(0), Outcome::Timeout, &breaker);
+    peer.record(Milliseconds(0), Outcome::Success, &breaker);
+    peer.record(Milliseconds(0), Outcome::Timeout, &breaker);
+    peer.record(Milliseconds(0), Outcome::Timeout, &breaker);
+    assert!(!peer.is_unhealthy(Milliseconds(0)));
+
+    peer.record(Milliseconds(100), Outcome::Timeout, &breaker);
+    assert!(peer.is_unhealthy(Milliseconds(1_099)));
+    assert!(!peer.is_unhealthy(Milliseconds(1_100)));
+
+    // the first request after cooling down fails, so the breaker trips again
+    peer.record(Milliseconds(1_200), Outcome::Timeout, &breaker);
+    assert!(peer.is_unhealthy(Milliseconds(1_200)));
+
+    // until a request succeeds
+    peer.record(Milliseconds(2_200), Outcome::Success, &breaker);
+    assert!(!peer.is_unhealthy(Milliseconds(2_200)));
+  }
+
+  #[test]
+  fn full_table_forgets_healthiest_peer() {
+    let mut health = Health::<2>::new(Breaker { trip_after: 1,
+                                                cool_down: Milliseconds(1_000) });
+
+    health.record(addr(1), Milliseconds(0), Outcome::Timeout);
+    health.record(addr(2), Milliseconds(0), Outcome::Success);
+    health.record(addr(3), Milliseconds(0), Outcome::Success);
+
+    assert!(health.get(addr(1)).is_some());
+    assert!(health.get(addr(2)).is_none());
+    assert!(health.get(addr(3)).is_some());
+    assert_eq!(health.check(addr(1), Milliseconds(10)),
+               Err(Unhealthy { addr: addr(1),
+                               until: Milliseconds(1_000) }));
+  }
+}
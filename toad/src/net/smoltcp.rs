@@ -0,0 +1,250 @@
+use no_std_net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+use smoltcp::iface::{Interface, MulticastError, SocketHandle, SocketSet};
+use smoltcp::phy::Device;
+use smoltcp::socket::udp;
+use smoltcp::time::Instant;
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address};
+use tinyvec::ArrayVec;
+use toad_stem::Stem;
+
+use super::Addrd;
+
+/// Errors yielded by [`Socket`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+  /// Binding the smoltcp socket failed
+  Bind(udp::BindError),
+  /// Sending a datagram failed for a reason other than a full tx buffer
+  Send(udp::SendError),
+  /// Joining a multicast group failed
+  Multicast(MulticastError),
+  /// smoltcp sockets live in a [`SocketSet`] owned by the caller,
+  /// so they can't be created from just an address.
+  ///
+  /// Use [`Socket::new`] instead.
+  BindRawUnsupported,
+}
+
+struct Inner<D: Device> {
+  iface: Interface,
+  device: D,
+  sockets: SocketSet<'static>,
+}
+
+/// [`super::Socket`] implementation backed by a smoltcp [`udp::Socket`]
+///
+/// This owns the [`Interface`], [`Device`] and [`SocketSet`] that
+/// the udp socket lives in, and will poll the interface before every
+/// operation. When smoltcp has no datagram buffered (or no room in its
+/// tx buffer), operations yield [`nb::Error::WouldBlock`].
+pub struct Socket<D: Device> {
+  inner: Stem<Inner<D>>,
+  handle: SocketHandle,
+  now: fn() -> Instant,
+}
+
+impl<D: Device> core::fmt::Debug for Socket<D> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("Socket")
+     .field("handle", &self.handle)
+     .finish()
+  }
+}
+
+impl<D: Device> Socket<D> {
+  /// Bind the udp socket at `handle` in `sockets` to `port` and use it
+  /// as a [`super::Socket`]
+  ///
+  /// `now` is used to get timestamps when polling the interface.
+  pub fn new(iface: Interface,
+             device: D,
+             mut sockets: SocketSet<'static>,
+             handle: SocketHandle,
+             port: u16,
+             now: fn() -> Instant)
+             -> Result<Self, Error> {
+    sockets.get_mut::<udp::Socket>(handle)
+           .bind(port)
+           .map_err(Error::Bind)?;
+
+    Ok(Self { inner: Stem::new(Inner { iface,
+                                       device,
+                                       sockets }),
+              handle,
+              now })
+  }
+
+  /// Poll the interface and then do something with the udp socket
+  fn with_sock<R>(&self, f: impl FnOnce(&mut udp::Socket<'static>) -> R) -> R {
+    let now = (self.now)();
+    self.inner.map_mut(|Inner { iface,
+                                device,
+                                sockets }| {
+                        iface.poll(now, device, sockets);
+                        let r = f(sockets.get_mut::<udp::Socket>(self.handle));
+                        iface.poll(now, device, sockets);
+                        r
+                      })
+  }
+}
+
+impl<D: Device> super::Socket for Socket<D> {
+  type Error = Error;
+  type Dgram = ArrayVec<[u8; 1152]>;
+
+  fn local_addr(&self) -> SocketAddr {
+    self.with_sock(|s| {
+          let ep = s.endpoint();
+          let ip = ep.addr
+                     .map(ip_from_smoltcp)
+                     .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+          SocketAddr::new(ip, ep.port)
+        })
+  }
+
+  fn empty_dgram() -> Self::Dgram {
+    ArrayVec::from([0u8; 1152])
+  }
+
+  fn bind_raw<A: ToSocketAddrs>(_: A) -> Result<Self, Self::Error> {
+    Err(Error::BindRawUnsupported)
+  }
+
+  fn send(&self, msg: Addrd<&[u8]>) -> nb::Result<(), Self::Error> {
+    self.with_sock(|s| match s.send_slice(msg.data(), addr_to_endpoint(msg.addr())) {
+          | Ok(()) => Ok(()),
+          | Err(udp::SendError::BufferFull) => Err(nb::Error::WouldBlock),
+          | Err(e) => Err(nb::Error::Other(Error::Send(e))),
+        })
+  }
+
+  fn recv(&self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
+    self.with_sock(|s| {
+          let addrd = copy_peeked(s, buffer)?;
+          s.recv().ok();
+          Ok(addrd)
+        })
+  }
+
+  fn peek(&self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
+    self.with_sock(|s| copy_peeked(s, buffer))
+  }
+
+  fn join_multicast(&self, addr: IpAddr) -> Result<(), Self::Error> {
+    let now = (self.now)();
+    self.inner.map_mut(|Inner { iface, device, .. }| {
+                iface.join_multicast_group(device, ip_to_smoltcp(addr), now)
+                     .map(|_| ())
+                     .map_err(Error::Multicast)
+              })
+  }
+
+  fn leave_multicast(&self, addr: IpAddr) -> Result<(), Self::Error> {
+    let now = (self.now)();
+    self.inner.map_mut(|Inner { iface, device, .. }| {
+                iface.leave_multicast_group(device, ip_to_smoltcp(addr), now)
+                     .map(|_| ())
+                     .map_err(Error::Multicast)
+              })
+  }
+}
+
+/// Copy the datagram at the front of the socket's rx queue into `buffer`,
+/// dropping any bytes that don't fit.
+fn copy_peeked(s: &mut udp::Socket<'static>,
+               buffer: &mut [u8])
+               -> nb::Result<Addrd<usize>, Error> {
+  match s.peek() {
+    | Ok((bytes, meta)) => {
+      let n = bytes.len().min(buffer.len());
+      buffer[..n].copy_from_slice(&bytes[..n]);
+      Ok(Addrd(n, endpoint_to_addr(meta.endpoint)))
+    },
+    | Err(_) => Err(nb::Error::WouldBlock),
+  }
+}
+
+fn ip_from_smoltcp(ip: IpAddress) -> IpAddr {
+  match ip {
+    | IpAddress::Ipv4(Ipv4Address(bytes)) => IpAddr::V4(bytes.into()),
+    | IpAddress::Ipv6(Ipv6Address(bytes)) => IpAddr::V6(bytes.into()),
+  }
+}
+
+fn ip_to_smoltcp(ip: IpAddr) -> IpAddress {
+  match ip {
+    | IpAddr::V4(ip) => IpAddress::Ipv4(Ipv4Address(ip.octets())),
+    | IpAddr::V6(ip) => IpAddress::Ipv6(Ipv6Address(ip.octets())),
+  }
+}
+
+fn endpoint_to_addr(ep: IpEndpoint) -> SocketAddr {
+  SocketAddr::new(ip_from_smoltcp(ep.addr), ep.port)
+}
+
+fn addr_to_endpoint(addr: SocketAddr) -> IpEndpoint {
+  IpEndpoint::new(ip_to_smoltcp(addr.ip()), addr.port())
+}
+
+#[cfg(test)]
+mod tests {
+  use smoltcp::iface::{Config, Interface, SocketSet};
+  use smoltcp::phy::{Loopback, Medium};
+  use smoltcp::socket::udp;
+  use smoltcp::time::Instant;
+  use smoltcp::wire::{HardwareAddress, IpAddress, IpCidr};
+
+  use super::Socket;
+  use crate::net::{ipv4_socketaddr, Addrd, Socket as _};
+
+  fn socket() -> Socket<Loopback> {
+    let mut device = Loopback::new(Medium::Ip);
+    let mut iface = Interface::new(Config::new(HardwareAddress::Ip), &mut device, Instant::ZERO);
+    iface.update_ip_addrs(|addrs| {
+           addrs.push(IpCidr::new(IpAddress::v4(127, 0, 0, 1), 8))
+                .unwrap()
+         });
+
+    let udp = udp::Socket::new(udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 4],
+                                                      vec![0; 4096]),
+                               udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 4],
+                                                      vec![0; 4096]));
+    let mut sockets = SocketSet::new(vec![]);
+    let handle = sockets.add(udp);
+
+    Socket::new(iface, device, sockets, handle, 5683, || Instant::ZERO).unwrap()
+  }
+
+  #[test]
+  fn recv_would_block_when_nothing_sent() {
+    let sock = socket();
+    let mut buf = [0u8; 8];
+    assert_eq!(sock.recv(&mut buf), Err(nb::Error::WouldBlock));
+  }
+
+  #[test]
+  fn send_then_recv_over_loopback() {
+    let sock = socket();
+    let addr = ipv4_socketaddr([127, 0, 0, 1], 5683);
+
+    sock.send(Addrd(&[1, 2, 3], addr)).unwrap();
+
+    let mut buf = [0u8; 8];
+    assert_eq!(sock.peek(&mut buf), Ok(Addrd(3, addr)));
+    assert_eq!(sock.recv(&mut buf), Ok(Addrd(3, addr)));
+    assert_eq!(&buf[..3], &[1, 2, 3]);
+    assert_eq!(sock.recv(&mut buf), Err(nb::Error::WouldBlock));
+  }
+
+  #[test]
+  fn recv_truncates_into_small_buffer() {
+    let sock = socket();
+    let addr = ipv4_socketaddr([127, 0, 0, 1], 5683);
+
+    sock.send(Addrd(&[1, 2, 3, 4], addr)).unwrap();
+
+    let mut buf = [0u8; 2];
+    assert_eq!(sock.recv(&mut buf), Ok(Addrd(2, addr)));
+    assert_eq!(buf, [1, 2]);
+  }
+}
@@ -0,0 +1,118 @@
+use embedded_time::duration::Milliseconds;
+use embedded_time::Instant;
+use toad_msg::opt::known::no_repeat::OBSERVE;
+use toad_msg::MessageOptions;
+
+use crate::platform::PlatformTypes;
+use crate::time::Millis;
+
+/// A notification received more than this long after the previous
+/// notification is always considered fresh, regardless of its sequence number.
+///
+/// <https://www.rfc-editor.org/rfc/rfc7641#section-3.4>
+pub const FRESHNESS_WINDOW: Millis = Milliseconds(128_000);
+
+/// Observe sequence numbers are 24 bits wide; two sequence numbers
+/// that are more than 2^23 apart are assumed to have wrapped around.
+const SEQ_HALF: u32 = 1 << 23;
+
+/// Is a notification with sequence number `v2` received at `t2`
+/// fresher than the last one we saw (`v1` received at `t1`)?
+///
+/// <https://www.rfc-editor.org/rfc/rfc7641#section-3.4>
+///
+/// ```
+/// use toad::observe::is_fresh;
+/// use toad::std::Clock;
+///
+/// let t = embedded_time::Instant::<Clock>::new(0);
+///
+/// assert!(is_fresh((1, t), (2, t)));
+/// assert!(!is_fresh((2, t), (1, t)));
+///
+/// // sequence numbers are 24 bits, and wrap around
+/// assert!(is_fresh(((1 << 24) - 1, t), (0, t)));
+/// ```
+pub fn is_fresh<C>((v1, t1): (u32, Instant<C>), (v2, t2): (u32, Instant<C>)) -> bool
+  where C: crate::time::Clock
+{
+  let elapsed = crate::time::elapsed(t1, t2);
+
+  seq_is_newer(v1, v2) || matches!(elapsed, Some(elapsed) if elapsed > FRESHNESS_WINDOW)
+}
+
+/// Is sequence number `v2` newer than `v1`, accounting for wraparound?
+pub(crate) fn seq_is_newer(v1: u32, v2: u32) -> bool {
+  (v1 < v2 && v2 - v1 < SEQ_HALF) || (v1 > v2 && v1 - v2 > SEQ_HALF)
+}
+
+/// Read the [Observe](toad_msg::opt::known::no_repeat::OBSERVE) option of a
+/// notification as a sequence number.
+///
+/// Sequence numbers are 0-3 byte unsigned integers; longer values yield `None`.
+pub fn notification_seq<P>(msg: &crate::platform::Message<P>) -> Option<u32>
+  where P: PlatformTypes
+{
+  msg.get_first(OBSERVE)
+     .filter(|v| v.0.len() <= 3)
+     .map(|v| v.0.iter().fold(0u32, |n, b| (n << 8) | *b as u32))
+}
+
+#[cfg(test)]
+mod tests {
+  use ::toad_msg::{Code, Id, Token, Type};
+  use tinyvec::array_vec;
+
+  use super::*;
+  use crate::net::Addrd;
+  use crate::platform::Message;
+  use crate::resp::Resp;
+  use crate::test;
+  use crate::test::ClockMock;
+
+  fn notification(seq: u32) -> Addrd<Resp<test::Platform>> {
+    let mut msg =
+      Message::<test::Platform>::new(Type::Non, Code::new(2, 5), Id(1), Token(array_vec!(1)));
+    msg.set(OBSERVE, ::toad_msg::OptValue(seq.to_be_bytes()[1..].to_vec()))
+       .ok();
+    Addrd(Resp::from(msg), test::x.x.x.x(80))
+  }
+
+  #[test]
+  fn notification_seq_decodes_variable_length_uint() {
+    assert_eq!(notification_seq(notification(0).data().msg()), Some(0));
+    assert_eq!(notification_seq(notification(0x0102).data().msg()), Some(0x0102));
+    assert_eq!(notification_seq(notification(0xFFFFFF).data().msg()), Some(0xFFFFFF));
+  }
+
+  #[test]
+  fn freshness_sequence_numbers() {
+    let t = ClockMock::instant(0);
+
+    assert!(is_fresh((1, t), (2, t)));
+    assert!(!is_fresh((2, t), (1, t)));
+    assert!(!is_fresh((2, t), (2, t)));
+    assert!(is_fresh((0, t), (SEQ_HALF - 1, t)));
+    assert!(!is_fresh((0, t), (SEQ_HALF, t)));
+  }
+
+  #[test]
+  fn freshness_sequence_number_wraparound() {
+    let t = ClockMock::instant(0);
+    let max = (1 << 24) - 1;
+
+    assert!(is_fresh((max, t), (0, t)));
+    assert!(is_fresh((max - 10, t), (5, t)));
+    assert!(!is_fresh((0, t), (max, t)));
+    assert!(!is_fresh((5, t), (max - 10, t)));
+  }
+
+  #[test]
+  fn freshness_after_128_seconds() {
+    let secs = |n: u64| ClockMock::instant(n * 1_000_000);
+
+    assert!(!is_fresh((10, secs(0)), (1, secs(128))));
+    assert!(is_fresh((10, secs(0)), (1, secs(129))));
+    assert!(!is_fresh((10, secs(129)), (1, secs(0))));
+  }
+}
@@ -0,0 +1,313 @@
+#[cfg(feature = "client")]
+use no_std_net::SocketAddr;
+use tinyvec::ArrayVec;
+use toad_msg::Code;
+#[cfg(feature = "client")]
+use toad_msg::{opt::known::repeat::ETAG, Id, MessageOptions, Token};
+
+#[cfg(feature = "client")]
+use crate::net::Addrd;
+#[cfg(feature = "client")]
+use crate::platform::Platform;
+#[cfg(feature = "server")]
+use crate::platform::PlatformTypes;
+#[cfg(feature = "client")]
+use crate::req::Req;
+#[cfg(feature = "client")]
+use crate::resp::{code, Resp};
+#[cfg(feature = "server")]
+use crate::server::ap::state::{Complete, Hydrated};
+#[cfg(feature = "server")]
+use crate::server::ap::Ap;
+#[cfg(feature = "server")]
+use crate::server::respond::{self, Chunk};
+#[cfg(feature = "client")]
+use crate::step::Step;
+
+/// An ETag identifying a version of an image
+pub type ETag = ArrayVec<[u8; 8]>;
+
+/// Destination of a [`download`]ed image
+pub trait Sink {
+  /// Error yielded when a block can't be written
+  type Error: core::fmt::Debug;
+
+  /// Write a block of the image, starting at byte `offset`
+  fn write(&mut self, offset: u64, block: &[u8]) -> Result<(), Self::Error>;
+}
+
+#[cfg(feature = "alloc")]
+impl Sink for std_alloc::vec::Vec<u8> {
+  type Error = core::convert::Infallible;
+
+  fn write(&mut self, offset: u64, block: &[u8]) -> Result<(), Self::Error> {
+    let offset = offset as usize;
+    let end = offset + block.len();
+
+    if self.len() < end {
+      self.resize(end, 0);
+    }
+
+    self[offset..end].copy_from_slice(block);
+    Ok(())
+  }
+}
+
+#[cfg(feature = "std")]
+impl Sink for std::fs::File {
+  type Error = std::io::Error;
+
+  fn write(&mut self, offset: u64, block: &[u8]) -> Result<(), Self::Error> {
+    use std::io::{Seek, SeekFrom, Write};
+
+    self.seek(SeekFrom::Start(offset))?;
+    self.write_all(block)
+  }
+}
+
+/// How much of an image has been [`download`]ed
+///
+/// Persist this alongside the [`Sink`] to resume
+/// an interrupted download.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Progress {
+  /// The number of the next block to request
+  pub next_block: u32,
+  /// The size of blocks being requested
+  ///
+  /// `None` before the first block is requested.
+  pub block_size: Option<u16>,
+  /// The ETag of the image being downloaded
+  ///
+  /// `None` before the first block is received.
+  pub etag: Option<ETag>,
+  /// Whether the last block has been received
+  pub complete: bool,
+}
+
+/// Errors encountered during a [`download`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E, S> {
+  /// The platform encountered an error
+  Platform(E),
+  /// The sink failed to write a block
+  Sink(S),
+  /// The server responded with a code other than 2.05 CONTENT
+  Status(Code),
+  /// The image's ETag is not the same as the ETag in [`Progress`],
+  /// meaning the image changed on the server since the download began.
+  ///
+  /// The download must be restarted with fresh [`Progress`].
+  Changed,
+}
+
+/// Download the resource at `coap://{addr}/{path}` into `sink`
+/// block-by-block, using Block2 (RFC 7959).
+///
+/// The download picks up from `progress`, which is updated after
+/// every block is written to `sink`. Pass `Progress::default()`
+/// to start a new download.
+///
+/// Every block's ETag is checked against the ETag of the first block,
+/// so that an image that changes during a download (or between a download
+/// being interrupted and resumed) is not corrupted.
+///
+/// `block_size` is the preferred block size, which the server may lower.
+///
+/// ```no_run
+/// use toad::net::ipv4_socketaddr;
+/// use toad::ota::{self, Progress};
+/// use toad::std::{dtls, Platform};
+/// use toad::step::runtime::std::Runtime;
+///
+/// let client = Platform::<dtls::N, Runtime<dtls::N>>::try_new("0.0.0.0:0", Default::default()).unwrap();
+/// let mut image = std::fs::File::create("firmware.bin").unwrap();
+/// let mut progress = Progress::default();
+///
+/// ota::download(&client,
+///               ipv4_socketaddr([192, 168, 0, 2], 5683),
+///               "firmware",
+///               1024,
+///               &mut progress,
+///               &mut image).unwrap();
+/// ```
+#[cfg(feature = "client")]
+#[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+pub fn download<S, P, K>(platform: &P,
+                         addr: SocketAddr,
+                         path: &str,
+                         block_size: u16,
+                         progress: &mut Progress,
+                         sink: &mut K)
+                         -> Result<(), Error<P::Error, K::Error>>
+  where P: Platform<S>,
+        S: Step<P::Types, PollReq = Addrd<Req<P::Types>>, PollResp = Addrd<Resp<P::Types>>>,
+        K: Sink
+{
+  while !progress.complete {
+    let size = *progress.block_size.get_or_insert(block_size);
+
+    let mut req = Req::<P::Types>::get(path);
+    req.msg_mut()
+       .set_block2(size, progress.next_block, false)
+       .ok();
+
+    let mut msg = req.msg().clone();
+    msg.id = Id(0);
+    msg.token = Token(Default::default());
+
+    let (_, token) = nb::block!(platform.send_msg(Addrd(msg.clone(), addr)))
+                       .map_err(Error::Platform)?;
+    let resp = nb::block!(platform.poll_resp(token, addr)).map_err(Error::Platform)?;
+    let resp = resp.data();
+
+    if resp.code() != code::CONTENT {
+      return Err(Error::Status(resp.code()));
+    }
+
+    let etag = resp.msg()
+                   .get_first(ETAG)
+                   .map(|etag| etag.as_bytes().iter().copied().take(8).collect::<ETag>());
+
+    match (&progress.etag, etag) {
+      | (Some(expected), Some(actual)) if *expected != actual => return Err(Error::Changed),
+      | (None, Some(actual)) => progress.etag = Some(actual),
+      | _ => (),
+    }
+
+    let payload = &resp.msg().payload.0;
+
+    match resp.msg().block2() {
+      | Some(block) => {
+        let offset = u64::from(block.num()) * u64::from(block.size());
+        sink.write(offset, payload).map_err(Error::Sink)?;
+
+        // the server may have chosen a smaller block size than we asked for,
+        // which we should use for the rest of the download
+        progress.block_size = Some(block.size());
+        progress.next_block = block.num() + 1;
+        progress.complete = !block.more();
+      },
+      | None => {
+        // the server sent the whole image in one response
+        sink.write(0, payload).map_err(Error::Sink)?;
+        progress.complete = true;
+      },
+    }
+  }
+
+  Ok(())
+}
+
+/// Serve an image of `len` bytes block-by-block (see [`respond::stream`]),
+/// reading each requested block with `read`.
+///
+/// `read` is invoked with the offset and length of the block to read.
+///
+/// The image's `etag` is included with every block so that clients can
+/// detect when the image changes during a [`download`].
+///
+/// ```
+/// use toad::net::Addrd;
+/// use toad::ota;
+/// use toad::req::Req;
+/// use toad::server::Run;
+/// use toad::std::{dtls, PlatformTypes as Std};
+/// use toad_msg::MessageOptions;
+///
+/// static IMAGE: [u8; 2000] = [0xAA; 2000];
+///
+/// let handle = |req: Req<Std<dtls::Y>>| {
+///   Run::<_, ()>::Unmatched(Addrd(req, "127.0.0.1:5683".parse().unwrap())).maybe(|ap| {
+///     ap.pipe(ota::serve(IMAGE.len() as u64, vec![1], |offset, len| {
+///         IMAGE[offset as usize..offset as usize + len as usize].to_vec()
+///       }))
+///   })
+/// };
+///
+/// let mut req = Req::get("firmware");
+/// req.msg_mut().set_block2(1024, 1, false).unwrap();
+///
+/// match handle(req) {
+///   | Run::Matched(rep) => {
+///     assert_eq!(rep.data().payload.0.len(), 2000 - 1024);
+///     assert!(!rep.data().block2().unwrap().more());
+///   },
+///   | _ => unreachable!(),
+/// }
+/// ```
+#[cfg(feature = "server")]
+#[cfg_attr(docsrs, doc(cfg(feature = "server")))]
+pub fn serve<P, T, E, F>(len: u64,
+                         etag: P::MessageOptionBytes,
+                         read: F)
+                         -> impl FnOnce(Ap<Hydrated, P, T, E>) -> Ap<Complete, P, (), E>
+  where P: PlatformTypes,
+        E: core::fmt::Debug,
+        F: FnOnce(u64, u16) -> P::MessagePayload
+{
+  move |ap| {
+    ap.pipe(respond::stream(|num, size| {
+        let offset = u64::from(num) * u64::from(size);
+
+        if offset > len || (offset == len && num > 0) {
+          Chunk::OutOfRange
+        } else {
+          let block_len = (len - offset).min(u64::from(size));
+          let block = read(offset, block_len as u16);
+
+          if offset + block_len < len {
+            Chunk::More(block)
+          } else {
+            Chunk::Last(block)
+          }
+        }
+      }))
+      .etag(etag)
+  }
+}
+
+#[cfg(all(test, feature = "client", feature = "server"))]
+mod tests {
+  use super::*;
+  use crate::server::Run;
+  use crate::test::Platform;
+
+  fn serve_block(num: u32, size: u16) -> Option<Addrd<crate::platform::Message<Platform>>> {
+    let mut req = Req::get("firmware");
+    req.msg_mut().set_block2(size, num, false).unwrap();
+
+    let run = Run::<Platform, ()>::Unmatched(Addrd(req, crate::test::dummy_addr()));
+    match run.maybe(|ap| {
+               ap.pipe(serve(40, [1u8].into_iter().collect(), |offset, len| {
+                             (offset as u8..offset as u8 + len as u8).collect()
+                           }))
+             }) {
+      | Run::Matched(rep) => Some(rep),
+      | _ => None,
+    }
+  }
+
+  #[test]
+  fn serve_yields_requested_block() {
+    let rep = serve_block(1, 16).unwrap();
+    assert_eq!(rep.data().payload.0, (16..32).collect::<Vec<u8>>());
+    assert!(rep.data().block2().unwrap().more());
+    assert_eq!(rep.data().get_first(ETAG).unwrap().as_bytes(), &[1u8]);
+
+    let rep = serve_block(2, 16).unwrap();
+    assert_eq!(rep.data().payload.0, (32..40).collect::<Vec<u8>>());
+    assert!(!rep.data().block2().unwrap().more());
+
+    let rep = serve_block(3, 16).unwrap();
+    assert_eq!(rep.data().code, code::BAD_OPTION);
+  }
+
+  #[test]
+  fn vec_sink_writes_at_offset() {
+    let mut image = vec![];
+    Sink::write(&mut image, 2, &[1, 2]).unwrap();
+    Sink::write(&mut image, 0, &[3]).unwrap();
+    assert_eq!(image, vec![3, 0, 1, 2]);
+  }
+}
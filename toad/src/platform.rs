@@ -9,13 +9,48 @@ use std_alloc::vec::Vec;
 use toad_array::{AppendCopy, Array};
 
 use crate::config::Config;
-use crate::net::{Addrd, Socket};
+use crate::net::{Addrd, Identity, Session, Socket};
 use crate::req::Req;
 use crate::resp::Resp;
 use crate::step::Step;
-use crate::time::Clock;
+use crate::time::{self, Clock};
 use crate::todo::String;
 
+/// [`Platform`] implementation for async `no_std` targets
+/// using [`embassy-net`](https://docs.rs/embassy-net) and
+/// [`embassy-time`](https://docs.rs/embassy-time)
+#[cfg(feature = "embassy")]
+#[cfg_attr(docsrs, doc(cfg(feature = "embassy")))]
+pub mod embassy;
+
+/// Message & effect storage allocated from a dedicated [`Allocator`](core::alloc::Allocator)
+///
+/// Requires a nightly compiler, since it relies on the unstable `allocator_api`.
+///
+/// * [`Buf`](allocator::Buf) - an [`Array`] backed by `Vec<T, A>`
+/// * [`PlatformTypes`](allocator::PlatformTypes) - storage for a platform that keeps CoAP traffic off the global heap
+#[cfg(feature = "allocator-api")]
+#[cfg_attr(docsrs, doc(cfg(feature = "allocator-api")))]
+pub mod allocator;
+
+/// Quality-of-service classes for outbound messages
+///
+/// * [`Qos`](qos::Qos) - how urgently a message should be sent (ACKs first, Observe notifications last)
+/// * [`PriorityQueue`](qos::PriorityQueue) - a queue yielding the most urgent items first, usable without an allocator
+///
+/// [`batch`] uses these to send ACKs ahead of e.g. a burst of notifications.
+pub mod qos;
+
+/// Reusing messages instead of allocating new ones
+///
+/// * [`MessagePool`] - messages that have been used & can be used again
+/// * [`Stats`](pool::Stats) - how often checkouts were satisfied by the pool
+///
+/// See [`step::pool`](crate::step::pool) to use a pool in a runtime.
+pub mod pool;
+
+pub use pool::MessagePool;
+
 /// Default [`PlatformError`] implementation
 #[derive(Debug)]
 #[allow(missing_docs)]
@@ -95,10 +130,19 @@ pub trait Platform<Steps>
         .poll()
         .map_err(Self::Error::socket)
         .and_then(|recvd_dgram| {
+          let recvd_identity = recvd_dgram.as_ref()
+                                          .and_then(|d| self.socket().peer_identity(d.addr()));
+          let recvd_dest = recvd_dgram.as_ref()
+                                      .map(|d| self.socket().recvd_dest(d.addr()));
+          let session = self.socket().poll_session();
+
           self.clock()
               .try_now()
               .map_err(Self::Error::clock)
               .map(|time| Snapshot { recvd_dgram,
+                                     recvd_identity,
+                                     recvd_dest,
+                                     session,
                                      config: self.config(),
                                      time })
         })
@@ -106,23 +150,21 @@ pub trait Platform<Steps>
 
   /// Poll for an incoming request, and pass it through `Steps`
   /// for processing.
+  ///
+  /// Reads up to [`Config.max_dgrams_per_poll`](Config#structfield.max_dgrams_per_poll)
+  /// datagrams, stopping at the first that yields a request.
+  ///
+  /// See [`Platform::try_poll_req`] to tell apart the reasons this may yield `WouldBlock`.
   fn poll_req(&self) -> nb::Result<Addrd<Req<Self::Types>>, Self::Error> {
-    let mut effects = <Self::Types as PlatformTypes>::Effects::default();
-    let res = self.snapshot()
-                  .map_err(nb::Error::Other)
-                  .and_then(|snapshot| {
-                    self.steps()
-                        .poll_req(&snapshot, &mut effects)
-                        .unwrap_or(Err(nb::Error::WouldBlock))
-                        .map_err(|e: nb::Error<_>| e.map(Self::Error::step))
-                  });
-
-    // NOTE: exec effects even if the above blocks
-    self.exec_many(effects)
-        .map_err(|(_, e)| e)
-        .map_err(nb::Error::Other)?;
+    self.try_poll_req()
+        .map_err(nb::Error::Other)
+        .and_then(Poll::into_nb)
+  }
 
-    res
+  /// [`Platform::poll_req`], yielding [`Poll::NoData`] when there were no datagrams
+  /// to read, and [`Poll::Busy`] when datagrams were left unread.
+  fn try_poll_req(&self) -> Result<Poll<Addrd<Req<Self::Types>>>, Self::Error> {
+    poll_dgrams(self, |steps, snapshot, effects| steps.poll_req(snapshot, effects))
   }
 
   /// Notify Observe subscribers that a new representation of the resource
@@ -138,28 +180,55 @@ pub trait Platform<Steps>
     self.exec_many(effects).map_err(|(_, e)| e)
   }
 
+  /// Cancel the exchange with token `token`
+  ///
+  /// Pending retransmissions of the request and responses buffered
+  /// for it are dropped; see [`Step::cancel`](crate::step::Step::cancel).
+  fn cancel(&self, token: Token) -> Result<(), Self::Error> {
+    use embedded_time::Clock;
+
+    // Not `Platform::snapshot`, because we don't want to pull a datagram off the socket
+    let time = self.clock().try_now().map_err(Self::Error::clock)?;
+    let snapshot = Snapshot { recvd_dgram: None,
+                              recvd_identity: None,
+                              recvd_dest: None,
+                              session: None,
+                              config: self.config(),
+                              time };
+
+    let mut effects = <Self::Types as PlatformTypes>::Effects::default();
+    self.steps()
+        .cancel(&snapshot, &mut effects, token)
+        .map_err(Self::Error::step)?;
+
+    self.exec_many(effects).map_err(|(_, e)| e)
+  }
+
   /// Poll for a response to a sent request, and pass it through `Steps`
   /// for processing.
+  ///
+  /// Reads up to [`Config.max_dgrams_per_poll`](Config#structfield.max_dgrams_per_poll)
+  /// datagrams, stopping at the first that yields the response.
+  ///
+  /// See [`Platform::try_poll_resp`] to tell apart the reasons this may yield `WouldBlock`.
   fn poll_resp(&self,
                token: Token,
                addr: SocketAddr)
                -> nb::Result<Addrd<Resp<Self::Types>>, Self::Error> {
-    let mut effects = <Self::Types as PlatformTypes>::Effects::default();
-    let res = self.snapshot()
-                  .map_err(nb::Error::Other)
-                  .and_then(|snapshot| {
-                    self.steps()
-                        .poll_resp(&snapshot, &mut effects, token, addr)
-                        .unwrap_or(Err(nb::Error::WouldBlock))
-                        .map_err(|e: nb::Error<_>| e.map(Self::Error::step))
-                  });
-
-    // NOTE: exec effects even if the above blocks
-    self.exec_many(effects)
-        .map_err(|(_, e)| e)
-        .map_err(nb::Error::Other)?;
+    self.try_poll_resp(token, addr)
+        .map_err(nb::Error::Other)
+        .and_then(Poll::into_nb)
+  }
 
-    res
+  /// [`Platform::poll_resp`], yielding [`Poll::NoData`] when there were no datagrams
+  /// to read, and [`Poll::Busy`] when datagrams were left unread.
+  fn try_poll_resp(&self,
+                   token: Token,
+                   addr: SocketAddr)
+                   -> Result<Poll<Addrd<Resp<Self::Types>>>, Self::Error> {
+    poll_dgrams(self, |steps, snapshot, effects| {
+      steps.poll_resp(snapshot, effects, token, addr)
+    })
   }
 
   /// `toad` may occasionally emit tracing and logs by invoking this method.
@@ -167,6 +236,40 @@ pub trait Platform<Steps>
   /// It's completely up to the Platform to handle them meaningfully (e.g. `println!`)
   fn log(&self, level: log::Level, msg: String<1000>) -> Result<(), Self::Error>;
 
+  /// Record a datagram that was sent or received by this platform,
+  /// e.g. by writing it to a packet capture file.
+  ///
+  /// [`Effect::Capture`]s are emitted by the [`capture`](crate::step::capture) step,
+  /// which is not part of the default runtime.
+  ///
+  /// The default implementation discards the datagram.
+  fn capture(&self, dgram: Addrd<&[u8]>, direction: Direction) -> Result<(), Self::Error> {
+    let _ = (dgram, direction);
+    Ok(())
+  }
+
+  /// Record that a step began or finished handling an event.
+  ///
+  /// [`Effect::Trace`]s are emitted by the [`trace`](crate::step::trace) step,
+  /// which is not part of the default runtime.
+  ///
+  /// The default implementation logs the span at [`log::Level::Trace`].
+  #[cfg(feature = "trace-steps")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "trace-steps")))]
+  fn trace(&self, span: crate::step::trace::Span) -> Result<(), Self::Error> {
+    self.log(log::Level::Trace, String::fmt(format_args!("{}", span)))
+  }
+
+  /// Perform an [`Effect::Custom`] requested by a custom [`Step`](crate::step::Step)
+  ///
+  /// The default implementation does nothing.
+  fn exec_custom(&self,
+                 effect: &<Self::Types as PlatformTypes>::CustomEffect)
+                 -> nb::Result<(), Self::Error> {
+    let _ = effect;
+    Ok(())
+  }
+
   /// Send a [`toad_msg::Message`]
   fn send_msg(&self,
               mut addrd_msg: Addrd<self::toad_msg::Message<Self::Types>>)
@@ -175,71 +278,226 @@ pub trait Platform<Steps>
 
     let mut effs = <Self::Types as PlatformTypes>::Effects::default();
     let mut on_message_sent_effs = <Self::Types as PlatformTypes>::Effects::default();
+    let mut discarded = false;
 
-    self.snapshot()
-        .discard(|snapshot: &Snapshot<Self::Types>| {
-          self.steps()
-              .before_message_sent(snapshot, &mut effs, &mut addrd_msg)
-              .map_err(Self::Error::step)
-        })
-        .discard(|_: &Snapshot<Self::Types>| self.exec_many(effs).map_err(|(_, e)| e))
-        .and_then(|snapshot| {
-          addrd_msg.clone().fold(|msg, addr| {
-                             let (id, token) = (msg.id, msg.token);
-                             msg.try_into_bytes::<Dgram<Self::Types>>()
-                                .map_err(Self::Error::msg_to_bytes)
-                                .map(|bytes| (id, token, snapshot, Addrd(bytes, addr)))
-                           })
-        })
-        .map_err(nb::Error::Other)
-        .discard(|(_, _, _, addrd_bytes): &(_, _, _, Addrd<<<Self::Types as PlatformTypes>::Socket as Socket>::Dgram>)| {
-          self.socket()
-              .send(addrd_bytes.as_ref().map(|s| s.as_ref()))
-              .map_err(|e: nb::Error<_>| e.map(Self::Error::socket))
-        })
-        .discard(|(_, _, snapshot, _): &(_, _, Snapshot<<Self as Platform<Steps>>::Types>, _)| {
-          self.steps()
-              .on_message_sent(snapshot, &mut on_message_sent_effs, &addrd_msg)
-              .map_err(Self::Error::step)
-              .map_err(nb::Error::Other)
-        })
-        .discard(|_: &(_, _, _, _)| self.exec_many(on_message_sent_effs).map_err(|(_, e)| e).map_err(nb::Error::Other))
-        .map(|(id, token, _, _)| (id, token))
+    let snapshot = self.snapshot()
+                       .discard(|snapshot: &Snapshot<Self::Types>| {
+                         self.steps()
+                             .before_message_sent(snapshot, &mut effs, &mut addrd_msg)
+                             .map_err(Self::Error::step)
+                       })
+                       .map(|snapshot| {
+                         discarded = effs.iter().any(|e| matches!(e, Effect::Discard));
+                         snapshot
+                       })
+                       .discard(|_: &Snapshot<Self::Types>| {
+                         self.exec_many(effs).map_err(|(_, e)| e)
+                       })
+                       .map_err(nb::Error::Other)?;
+
+    if discarded {
+      let (id, token) = (addrd_msg.data().id, addrd_msg.data().token);
+      self.steps().recycle(addrd_msg.unwrap());
+      return Ok((id, token));
+    }
+
+    let sent = addrd_msg.as_ref()
+             .map(|msg| copy_msg(self.steps(), msg))
+             .fold(|msg, addr| {
+               let (id, token) = (msg.id, msg.token);
+               msg.try_into_bytes::<Dgram<Self::Types>>()
+                  .map_err(Self::Error::msg_to_bytes)
+                  .map(|bytes| (id, token, snapshot, Addrd(bytes, addr)))
+             })
+             .map_err(nb::Error::Other)
+             .discard(|(_, _, _, addrd_bytes): &(_, _, _, Addrd<<<Self::Types as PlatformTypes>::Socket as Socket>::Dgram>)| {
+               self.socket()
+                   .send(addrd_bytes.as_ref().map(|s| s.as_ref()))
+                   .map_err(|e: nb::Error<_>| e.map(Self::Error::socket))
+             })
+             .discard(|(_, _, snapshot, _): &(_, _, Snapshot<<Self as Platform<Steps>>::Types>, _)| {
+               self.steps()
+                   .on_message_sent(snapshot, &mut on_message_sent_effs, &addrd_msg)
+                   .map_err(Self::Error::step)
+                   .map_err(nb::Error::Other)
+             })
+             .discard(|_: &(_, _, _, _)| self.exec_many(on_message_sent_effs).map_err(|(_, e)| e).map_err(nb::Error::Other))
+             .map(|(id, token, _, _)| (id, token));
+
+    self.steps().recycle(addrd_msg.unwrap());
+    sent
+  }
+
+  /// Send a request, honoring its [retry policy](Req::set_retry_policy)
+  fn send_req(&self, req: Addrd<Req<Self::Types>>) -> nb::Result<(Id, Token), Self::Error> {
+    use embedded_time::Clock;
+
+    let (addr, policy) = (req.addr(), req.data().retry_policy());
+    let (id, token) = self.send_msg(req.map(Into::into))?;
+
+    if policy == crate::retry::Policy::Default {
+      return Ok((id, token));
+    }
+
+    // Not `Platform::snapshot`, because we don't want to pull a datagram off the socket
+    let time = self.clock()
+                   .try_now()
+                   .map_err(Self::Error::clock)
+                   .map_err(nb::Error::Other)?;
+    let snapshot = Snapshot { recvd_dgram: None,
+                              recvd_identity: None,
+                              recvd_dest: None,
+                              session: None,
+                              config: self.config(),
+                              time };
+
+    let mut effects = <Self::Types as PlatformTypes>::Effects::default();
+    self.steps()
+        .set_retry_policy(&snapshot, &mut effects, Addrd(token, addr), policy)
+        .map_err(Self::Error::step)
+        .map_err(nb::Error::Other)?;
+
+    self.exec_many(effects)
+        .map_err(|(_, e)| nb::Error::Other(e))
+        .map(|_| (id, token))
+  }
+
+  /// Send a request on behalf of another endpoint, e.g. as a forward proxy
+  ///
+  /// Unlike [`Platform::send_msg`], the request's token was chosen by the
+  /// endpoint that originated the request, so it is sent as-is even when it
+  /// is empty (see [`step::provision_tokens`](crate::step::provision_tokens)).
+  fn forward_msg(&self,
+                 msg: Addrd<self::toad_msg::Message<Self::Types>>)
+                 -> nb::Result<(Id, Token), Self::Error> {
+    use embedded_time::Clock;
+
+    let token = Addrd(msg.data().token, msg.addr());
+
+    // Not `Platform::snapshot`, because we don't want to pull a datagram off the socket
+    let time = self.clock()
+                   .try_now()
+                   .map_err(Self::Error::clock)
+                   .map_err(nb::Error::Other)?;
+    let snapshot = Snapshot { recvd_dgram: None,
+                              recvd_identity: None,
+                              recvd_dest: None,
+                              session: None,
+                              config: self.config(),
+                              time };
+
+    let set_forwarding = |forwarding: bool| {
+      let mut effects = <Self::Types as PlatformTypes>::Effects::default();
+      self.steps()
+          .set_forwarding(&snapshot, &mut effects, token, forwarding)
+          .map_err(Self::Error::step)?;
+      self.exec_many(effects).map_err(|(_, e)| e)
+    };
+
+    set_forwarding(true).map_err(nb::Error::Other)?;
+    let sent = self.send_msg(msg);
+    set_forwarding(false).map_err(nb::Error::Other)?;
+
+    sent
+  }
+
+  /// [`Platform::send_msg`], yielding [`Poll::Busy`] when the socket
+  /// could not accept the message right now.
+  fn try_send_msg(&self,
+                  msg: Addrd<self::toad_msg::Message<Self::Types>>)
+                  -> Result<Poll<(Id, Token)>, Self::Error> {
+    match self.send_msg(msg) {
+      | Ok(sent) => Ok(Poll::Ready(sent)),
+      | Err(nb::Error::WouldBlock) => Ok(Poll::Busy),
+      | Err(nb::Error::Other(e)) => Err(e),
+    }
+  }
+
+  /// Send a request to a multicast address, and block until all
+  /// responses received within the [multicast response leisure](crate::config::Msg::multicast_response_leisure)
+  /// have been collected.
+  ///
+  /// Duplicate responses from the same server are discarded by the
+  /// [`BufferResponses`](crate::step::buffer_responses) step.
+  fn multicast<A>(&self, req: Addrd<Req<Self::Types>>) -> Result<A, Self::Error>
+    where A: Array<Item = Addrd<Resp<Self::Types>>>
+  {
+    use embedded_time::Clock;
+
+    let addr = req.addr();
+    let (_, token) = nb::block!(self.send_req(req))?;
+    let sent_at = self.clock().try_now().map_err(Self::Error::clock)?;
+    let leisure = self.config().msg.multicast_response_leisure;
+
+    let mut resps = A::default();
+
+    loop {
+      match self.poll_resp(token, addr) {
+        | Ok(resp) => resps.push(resp),
+        | Err(nb::Error::WouldBlock) => {
+          let now = self.clock().try_now().map_err(Self::Error::clock)?;
+          let elapsed = time::elapsed(sent_at, now);
+
+          match elapsed {
+            | Some(elapsed) if elapsed > leisure => break Ok(resps),
+            | _ => continue,
+          }
+        },
+        | Err(nb::Error::Other(e)) => break Err(e),
+      }
+    }
   }
 
   /// Execute an [`Effect`]
   fn exec_1(&self, effect: &Effect<Self::Types>) -> nb::Result<(), Self::Error> {
     match effect {
       | &Effect::Log(level, msg) => self.log(level, msg).map_err(nb::Error::Other),
-      // TODO(orion): remove this clone as soon as `TryIntoBytes`
+      // TODO(orion): remove this copy as soon as `TryIntoBytes`
       // requires &msg not owned msg
-      | &Effect::Send(ref msg) => self.send_msg(msg.clone()).map(|_| ()),
+      | &Effect::Send(ref msg) => {
+        self.send_msg(msg.as_ref().map(|msg| copy_msg(self.steps(), msg)))
+            .map(|_| ())
+      },
+      | &Effect::Capture(ref dgram, direction) => {
+        self.capture(dgram.as_ref().map(|d| d.as_ref()), direction)
+            .map_err(nb::Error::Other)
+      },
+      | &Effect::Custom(ref eff) => self.exec_custom(eff),
+      // only meaningful to `send_msg`
+      | &Effect::Discard => Ok(()),
+      #[cfg(feature = "trace-steps")]
+      | &Effect::Trace(span) => self.trace(span).map_err(nb::Error::Other),
       | &Effect::Nop => Ok(()),
     }
   }
 
-  /// Execute many [`Effect`]s
+  /// Execute many [`Effect`]s, [batched](batch)
   ///
   /// Blocks on effects that yield `nb::WouldBlock`.
   ///
   /// If executing an effect errors, the erroring effect and all remaining effects are
   /// returned along with the error.
+  ///
+  /// Messages that were sent are [recycled](Step::recycle), and datagrams are
+  /// written within a single [`Socket::batch`].
   fn exec_many(&self,
                effects: <Self::Types as PlatformTypes>::Effects)
                -> Result<(), (<Self::Types as PlatformTypes>::Effects, Self::Error)> {
-    effects.into_iter()
-           .fold(Ok(()), |so_far, eff| match so_far {
-             | Ok(()) => nb::block!(self.exec_1(&eff)).map_err(|e| {
-                           let mut effs: <Self::Types as PlatformTypes>::Effects =
-                             Default::default();
-                           effs.push(eff);
-                           (effs, e)
-                         }),
-             | Err((mut effs, e)) => {
-               effs.push(eff);
-               Err((effs, e))
-             },
-           })
+    // an effect's error (along with the effects that were not executed)
+    // takes priority over failing to write the batch, since datagrams
+    // that could not be written stay queued in the socket.
+    let mut res = Ok(());
+    let written = self.socket().batch(|| {
+                                 res = exec_each::<Self::Types, _>(batch::<Self::Types>(effects),
+                                                                   |eff| self.exec_1(eff),
+                                                                   |eff| {
+                                                                     if let Effect::Send(msg) = eff {
+                                                                       self.steps().recycle(msg.unwrap());
+                                                                     }
+                                                                   })
+                               });
+
+    res.and_then(|_| written.map_err(|e| (Default::default(), Self::Error::socket(e))))
   }
 
   /// Copy of runtime behavior [`Config`] to be used
@@ -291,6 +549,69 @@ pub trait PlatformTypes: Sized + 'static + core::fmt::Debug {
 
   /// How will we store a sequence of effects to perform?
   type Effects: Array<Item = Effect<Self>> + core::fmt::Debug;
+
+  /// Effects specific to this platform that custom [`Step`](crate::step::Step)s
+  /// may request with [`Effect::Custom`] (e.g. blinking an LED or persisting state),
+  /// performed by [`Platform::exec_custom`].
+  ///
+  /// Platforms without any should use [`core::convert::Infallible`].
+  type CustomEffect: Clone + PartialEq + Debug;
+}
+
+/// The outcome of polling the runtime, telling apart
+/// "there is nothing to do" from "try again later"
+///
+/// The `nb` entry points (e.g. [`Platform::poll_req`]) yield `WouldBlock`
+/// for both [`Poll::NoData`] and [`Poll::Busy`]; the `try_` entry points
+/// (e.g. [`Platform::try_poll_req`]) yield a `Poll`, so that callers can sleep until
+/// the socket is readable instead of spinning.
+///
+/// ```
+/// use toad::platform::Poll;
+///
+/// assert_eq!(Poll::Ready(1).into_nb::<()>(), Ok(1));
+/// assert_eq!(Poll::<u8>::NoData.into_nb::<()>(), Err(nb::Error::WouldBlock));
+/// assert_eq!(Poll::Ready(1).map(|n| n + 1), Poll::Ready(2));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Poll<T> {
+  /// The operation completed, yielding `T`
+  Ready(T),
+  /// There was nothing to yield, and there are no more datagrams on the socket.
+  ///
+  /// Poll again once the socket is readable, or when a timer
+  /// (e.g. a retransmission) may be due.
+  NoData,
+  /// The operation could not make progress right now, e.g. the socket
+  /// could not accept a datagram, or datagrams were left unread.
+  ///
+  /// Try again as soon as possible.
+  Busy,
+}
+
+impl<T> Poll<T> {
+  /// Whether this is [`Poll::Ready`]
+  pub fn is_ready(&self) -> bool {
+    matches!(self, Poll::Ready(_))
+  }
+
+  /// Transform the value of [`Poll::Ready`]
+  pub fn map<R>(self, f: impl FnOnce(T) -> R) -> Poll<R> {
+    match self {
+      | Poll::Ready(t) => Poll::Ready(f(t)),
+      | Poll::NoData => Poll::NoData,
+      | Poll::Busy => Poll::Busy,
+    }
+  }
+
+  /// Convert to an [`nb::Result`], where [`Poll::NoData`] and
+  /// [`Poll::Busy`] are [`nb::Error::WouldBlock`].
+  pub fn into_nb<E>(self) -> nb::Result<T, E> {
+    match self {
+      | Poll::Ready(t) => Ok(t),
+      | Poll::NoData | Poll::Busy => Err(nb::Error::WouldBlock),
+    }
+  }
 }
 
 /// A snapshot of the system's state at a given moment
@@ -307,6 +628,18 @@ pub struct Snapshot<P: PlatformTypes> {
   /// A UDP datagram received from somewhere
   pub recvd_dgram: Option<Addrd<<P::Socket as Socket>::Dgram>>,
 
+  /// The authenticated identity of the sender of `recvd_dgram`,
+  /// see [`Socket::peer_identity`]
+  pub recvd_identity: Option<Identity>,
+
+  /// The local address `recvd_dgram` was sent to,
+  /// see [`Socket::recvd_dest`]
+  pub recvd_dest: Option<SocketAddr>,
+
+  /// A session lifecycle event on a connection-oriented transport,
+  /// see [`Socket::poll_session`]
+  pub session: Option<Addrd<Session>>,
+
   /// Runtime config, includes many useful timings
   pub config: Config,
 }
@@ -316,6 +649,9 @@ impl<P: PlatformTypes> core::fmt::Debug for Snapshot<P> {
     f.debug_struct("Snapshot")
      .field("time", &self.time)
      .field("recvd_dgram", &self.recvd_dgram)
+     .field("recvd_identity", &self.recvd_identity)
+     .field("recvd_dest", &self.recvd_dest)
+     .field("session", &self.session)
      .field("config", &self.config)
      .finish()
   }
@@ -325,6 +661,9 @@ impl<P: PlatformTypes> Clone for Snapshot<P> {
   fn clone(&self) -> Self {
     Self { time: self.time,
            recvd_dgram: self.recvd_dgram.clone(),
+           recvd_identity: self.recvd_identity.clone(),
+           recvd_dest: self.recvd_dest,
+           session: self.session,
            config: self.config }
   }
 }
@@ -332,15 +671,247 @@ impl<P: PlatformTypes> Clone for Snapshot<P> {
 /// Used by [`Step`]s to deterministically communicate
 /// to [`Platform`]s side-effects that they would like
 /// to perform.
+///
+/// Variants may be added by crate features (e.g. `trace-steps`),
+/// so matches on `Effect` outside of toad must have a wildcard arm.
 #[allow(missing_docs)]
+#[non_exhaustive]
 pub enum Effect<P>
   where P: PlatformTypes
 {
   Send(Addrd<self::toad_msg::Message<P>>),
   Log(log::Level, String<1000>),
+  /// Record a datagram that was sent or received,
+  /// see [`Platform::capture`]
+  Capture(Addrd<<P::Socket as Socket>::Dgram>, Direction),
+  /// An effect specific to the platform,
+  /// see [`PlatformTypes::CustomEffect`]
+  Custom(P::CustomEffect),
+  /// Issued in [`Step::before_message_sent`](crate::step::Step::before_message_sent)
+  /// to prevent the message from being sent.
+  ///
+  /// [`Platform::send_msg`] still succeeds, but
+  /// [`Step::on_message_sent`](crate::step::Step::on_message_sent) is not invoked.
+  Discard,
+  /// A step began or finished handling an event,
+  /// see [`trace`](crate::step::trace) and [`Platform::trace`]
+  #[cfg(feature = "trace-steps")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "trace-steps")))]
+  Trace(crate::step::trace::Span),
   Nop,
 }
 
+/// Something that can perform [`Effect`]s
+///
+/// [`Platform`]s perform effects with [`Platform::exec_1`];
+/// this allows effects to be performed by something other
+/// than a `Platform`, e.g. a test harness or an effect queue
+/// drained by another task.
+pub trait Exec<P>
+  where P: PlatformTypes
+{
+  /// Error yielded when an effect could not be performed
+  type Error;
+
+  /// Perform an effect
+  fn exec(&mut self, effect: &Effect<P>) -> nb::Result<(), Self::Error>;
+
+  /// [`batch`] and then perform many effects
+  ///
+  /// Blocks on effects that yield `nb::WouldBlock`.
+  ///
+  /// If performing an effect errors, the erroring effect and all remaining effects are
+  /// returned along with the error.
+  fn exec_batch(&mut self, effects: P::Effects) -> Result<(), (P::Effects, Self::Error)> {
+    exec_each::<P, _>(batch::<P>(effects), |eff| self.exec(eff), drop)
+  }
+}
+
+/// Combine and reorder effects where that does not change their outcome
+///
+///  * Adjacent [`Effect::Log`]s of the same level are joined by newlines into one, as long as the result fits
+///  * Within a run of adjacent [`Effect::Send`]s, more urgent messages are sent first (see [`qos::Qos`]),
+///    and sends of the same class to the same address are grouped together.
+///    Addresses keep the order they were first sent to in, and sends to each address keep their order within a class.
+///
+/// ```
+/// use toad::net::Addrd;
+/// use toad::platform::{batch, Effect};
+/// use toad::std::{dtls, PlatformTypes as Std};
+///
+/// type P = Std<dtls::N>;
+///
+/// let effects: Vec<Effect<P>> = vec![Effect::Log(log::Level::Info, "a".into()),
+///                                    Effect::Log(log::Level::Info, "b".into()),
+///                                    Effect::Log(log::Level::Warn, "c".into())];
+///
+/// assert_eq!(batch::<P>(effects),
+///            vec![Effect::Log(log::Level::Info, "a\nb".into()),
+///                 Effect::Log(log::Level::Warn, "c".into())]);
+/// ```
+pub fn batch<P>(effects: P::Effects) -> P::Effects
+  where P: PlatformTypes
+{
+  use core::fmt::Write;
+
+  let mut out = P::Effects::default();
+  let mut sends = P::Effects::default();
+
+  for eff in effects {
+    if let Effect::Send(_) = eff {
+      sends.append(eff);
+      continue;
+    }
+
+    group_sends::<P>(&mut out, &mut sends);
+
+    let joined = match (out.last_mut(), &eff) {
+      | (Some(Effect::Log(level, msg)), Effect::Log(next_level, next))
+        if level == next_level
+           && msg.as_str().len() + 1 + next.as_str().len() <= LOG_CAPACITY =>
+      {
+        write!(msg, "\n{}", next.as_str()).is_ok()
+      },
+      | _ => false,
+    };
+
+    if !joined {
+      out.append(eff);
+    }
+  }
+
+  group_sends::<P>(&mut out, &mut sends);
+  out
+}
+
+/// Move a run of [`Effect::Send`]s to `out`, most urgent first & grouped by address
+fn group_sends<P>(out: &mut P::Effects, sends: &mut P::Effects)
+  where P: PlatformTypes
+{
+  use qos::Prioritized;
+
+  let mut queue = qos::PriorityQueue::<P::Effects>::new();
+  core::mem::take(sends).into_iter().for_each(|eff| {
+                                      // `sends` has the same capacity, so this never fails
+                                      queue.push(eff).ok();
+                                    });
+  let mut sends = queue.into_iter().collect::<P::Effects>();
+
+  for i in 0..sends.len() {
+    let (addr, qos) = match &sends[i] {
+      | Effect::Send(msg) => (msg.addr(), msg.qos()),
+      // already moved to `out`
+      | _ => continue,
+    };
+
+    for j in i..sends.len() {
+      if matches!(&sends[j], Effect::Send(msg) if msg.addr() == addr && msg.qos() == qos) {
+        out.append(core::mem::take(&mut sends[j]));
+      }
+    }
+  }
+}
+
+/// Take [snapshots](Platform::snapshot) and pass them to `poll` until it yields
+/// something other than `WouldBlock`, the socket has no more datagrams, or
+/// [`Config.max_dgrams_per_poll`](Config#structfield.max_dgrams_per_poll)
+/// datagrams have been read.
+///
+/// The effects of each snapshot are performed before the next is taken,
+/// even if `poll` blocked.
+fn poll_dgrams<Pf, S, T>(platform: &Pf,
+                         mut poll: impl FnMut(&S,
+                                              &Snapshot<Pf::Types>,
+                                              &mut <Pf::Types as PlatformTypes>::Effects)
+                                              -> Option<nb::Result<T, S::Error>>)
+                         -> Result<Poll<T>, Pf::Error>
+  where Pf: Platform<S> + ?Sized,
+        S: Step<Pf::Types, PollReq = Addrd<Req<Pf::Types>>, PollResp = Addrd<Resp<Pf::Types>>>
+{
+  for _ in 0..platform.config().max_dgrams_per_poll.max(1) {
+    let mut effects = <Pf::Types as PlatformTypes>::Effects::default();
+    let snapshot = platform.snapshot()?;
+    let drained = snapshot.recvd_dgram.is_none();
+
+    let res = poll(platform.steps(), &snapshot, &mut effects).unwrap_or(Err(nb::Error::WouldBlock));
+
+    // NOTE: exec effects even if the above blocks
+    platform.exec_many(effects).map_err(|(_, e)| e)?;
+
+    match res {
+      | Ok(t) => return Ok(Poll::Ready(t)),
+      | Err(nb::Error::Other(e)) => return Err(Pf::Error::step(e)),
+      | Err(nb::Error::WouldBlock) if drained => return Ok(Poll::NoData),
+      | Err(nb::Error::WouldBlock) => continue,
+    }
+  }
+
+  Ok(Poll::Busy)
+}
+
+/// Perform effects in order with `exec`, stopping at the first that errors
+///
+/// Effects that were performed are passed to `done`.
+fn exec_each<P, E>(effects: P::Effects,
+                   mut exec: impl FnMut(&Effect<P>) -> nb::Result<(), E>,
+                   mut done: impl FnMut(Effect<P>))
+                   -> Result<(), (P::Effects, E)>
+  where P: PlatformTypes
+{
+  effects.into_iter()
+         .fold(Ok(()), |so_far, eff| match so_far {
+           | Ok(()) => match nb::block!(exec(&eff)) {
+             | Ok(()) => {
+               done(eff);
+               Ok(())
+             },
+             | Err(e) => {
+               let mut effs = P::Effects::default();
+               effs.append(eff);
+               Err((effs, e))
+             },
+           },
+           | Err((mut effs, e)) => {
+             effs.append(eff);
+             Err((effs, e))
+           },
+         })
+}
+
+/// Copy a message into one [checked out](Step::checkout) of `steps`,
+/// or clone it if there are none to reuse
+fn copy_msg<P, S>(steps: &S, msg: &self::toad_msg::Message<P>) -> self::toad_msg::Message<P>
+  where P: PlatformTypes,
+        S: Step<P>
+{
+  match steps.checkout() {
+    | Some(mut copy) => {
+      copy.ver = msg.ver;
+      copy.ty = msg.ty;
+      copy.code = msg.code;
+      copy.id = msg.id;
+      copy.token = msg.token;
+      pool::copy_opts::<P>(&mut copy.opts, &msg.opts);
+      copy.payload.0.append_copy(&msg.payload.0);
+      copy
+    },
+    | None => msg.clone(),
+  }
+}
+
+/// Capacity of the message in an [`Effect::Log`]
+const LOG_CAPACITY: usize = 1000;
+
+/// Whether a [captured](Effect::Capture) datagram was
+/// sent or received by this platform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Direction {
+  /// The datagram was received from a remote socket
+  Inbound,
+  /// The datagram was sent to a remote socket
+  Outbound,
+}
+
 impl<P> Default for Effect<P> where P: PlatformTypes
 {
   fn default() -> Self {
@@ -353,6 +924,11 @@ impl<P: PlatformTypes> Clone for Effect<P> {
     match self {
       | Effect::Send(m) => Effect::Send(m.clone()),
       | Effect::Log(l, m) => Effect::Log(*l, *m),
+      | Effect::Capture(d, dir) => Effect::Capture(d.clone(), *dir),
+      | Effect::Custom(e) => Effect::Custom(e.clone()),
+      | Effect::Discard => Effect::Discard,
+      #[cfg(feature = "trace-steps")]
+      | Effect::Trace(s) => Effect::Trace(*s),
       | Effect::Nop => Effect::Nop,
     }
   }
@@ -363,6 +939,11 @@ impl<P: PlatformTypes> core::fmt::Debug for Effect<P> {
     match self {
       | Self::Send(m) => f.debug_tuple("Send").field(m).finish(),
       | Self::Log(l, s) => f.debug_tuple("Log").field(l).field(s).finish(),
+      | Self::Capture(d, dir) => f.debug_tuple("Capture").field(d).field(dir).finish(),
+      | Self::Custom(e) => f.debug_tuple("Custom").field(e).finish(),
+      | Self::Discard => f.debug_tuple("Discard").finish(),
+      #[cfg(feature = "trace-steps")]
+      | Self::Trace(s) => f.debug_tuple("Trace").field(s).finish(),
       | Self::Nop => f.debug_tuple("Nop").finish(),
     }
   }
@@ -373,6 +954,11 @@ impl<P: PlatformTypes> PartialEq for Effect<P> {
     match (self, other) {
       | (Self::Send(a), Self::Send(b)) => a == b,
       | (Self::Log(al, am), Self::Log(bl, bm)) => al == bl && am == bm,
+      | (Self::Capture(ad, adir), Self::Capture(bd, bdir)) => ad == bd && adir == bdir,
+      | (Self::Custom(a), Self::Custom(b)) => a == b,
+      | (Self::Discard, Self::Discard) => true,
+      #[cfg(feature = "trace-steps")]
+      | (Self::Trace(a), Self::Trace(b)) => a == b,
       | _ => false,
     }
   }
@@ -428,6 +1014,7 @@ impl<Clk: Clock + Debug + 'static, Sock: Socket + 'static> PlatformTypes for All
   type Clock = Clk;
   type Socket = Sock;
   type Effects = Vec<Effect<Self>>;
+  type CustomEffect = core::convert::Infallible;
 }
 
 #[deprecated = "use `toad::platform::toad_msg::Message`"]
@@ -453,3 +1040,63 @@ pub mod toad_msg {
       ::toad_msg::SetOptionError<::toad_msg::OptValue<Bytes<P>>, <Map<P> as OptionMap>::OptValues>;
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use ::toad_msg::{Code, Type};
+
+  use super::*;
+  use crate::test::{self, msg};
+
+  #[test]
+  fn batch_groups_adjacent_sends_by_address() {
+    let send = |port: u16, id: u16| {
+      Effect::Send(msg!(CON GET x.x.x.x:0).map(|mut m| {
+                                              m.id = Id(id);
+                                              m
+                                            })
+                                          .with_addr(test::x.x.x.x(port)))
+    };
+
+    let effects: Vec<test::Effect> = vec![send(1, 1),
+                                          send(2, 2),
+                                          send(1, 3),
+                                          Effect::Log(log::Level::Info, "sent".into()),
+                                          send(2, 4),
+                                          send(1, 5)];
+
+    assert_eq!(batch::<test::Platform>(effects),
+               vec![send(1, 1),
+                    send(1, 3),
+                    send(2, 2),
+                    Effect::Log(log::Level::Info, "sent".into()),
+                    send(2, 4),
+                    send(1, 5)]);
+  }
+
+  #[test]
+  fn batch_sends_urgent_messages_first() {
+    let send = |port: u16, ty: Type, id: u16| {
+      let mut msg =
+        Message::<test::Platform>::new(ty, Code::GET, Id(id), Token(Default::default()));
+      if ty == Type::Ack {
+        msg.code = Code::EMPTY;
+      }
+
+      Effect::Send(Addrd(msg, test::x.x.x.x(port)))
+    };
+
+    let effects: Vec<test::Effect> = vec![send(1, Type::Con, 1),
+                                          send(2, Type::Con, 2),
+                                          send(1, Type::Ack, 3),
+                                          send(2, Type::Ack, 4),
+                                          send(1, Type::Con, 5)];
+
+    assert_eq!(batch::<test::Platform>(effects),
+               vec![send(1, Type::Ack, 3),
+                    send(2, Type::Ack, 4),
+                    send(1, Type::Con, 1),
+                    send(1, Type::Con, 5),
+                    send(2, Type::Con, 2)]);
+  }
+}
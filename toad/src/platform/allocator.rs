@@ -0,0 +1,295 @@
+use core::alloc::Allocator;
+use core::fmt::Debug;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+use ::toad_msg::{OptNumber, OptValue};
+use std_alloc::vec::Vec;
+use tinyvec::ArrayVec;
+use toad_array::{AppendCopy, Array, Filled, Indexed, Reserve, Trunc};
+use toad_len::Len;
+
+use crate::net::Socket;
+use crate::platform::{self, Effect};
+use crate::time::Clock;
+
+/// A [`Vec`] that allocates from `A` rather than the global heap,
+/// usable wherever toad needs an [`Array`].
+///
+/// `Vec<T, A>` itself can't be used, since it only implements
+/// [`Default`] & [`FromIterator`] when allocating from the global heap.
+/// `Buf` creates new vecs with `A::default()` instead, so `A` is
+/// expected to be a cheap handle to an allocator (e.g. a ZST
+/// wrapping a `static` pool).
+pub struct Buf<T, A>(Vec<T, A>) where A: Allocator;
+
+impl<T, A> Buf<T, A> where A: Allocator
+{
+  /// Get the vec backing this buffer
+  pub fn into_inner(self) -> Vec<T, A> {
+    self.0
+  }
+}
+
+impl<T, A> From<Vec<T, A>> for Buf<T, A> where A: Allocator
+{
+  fn from(v: Vec<T, A>) -> Self {
+    Self(v)
+  }
+}
+
+impl<T, A> Default for Buf<T, A> where A: Allocator + Default
+{
+  fn default() -> Self {
+    Self(Vec::new_in(A::default()))
+  }
+}
+
+impl<T, A> Clone for Buf<T, A>
+  where T: Clone,
+        A: Allocator + Clone
+{
+  fn clone(&self) -> Self {
+    Self(self.0.clone())
+  }
+}
+
+impl<T, A> Debug for Buf<T, A>
+  where T: Debug,
+        A: Allocator
+{
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    self.0.fmt(f)
+  }
+}
+
+impl<T, A> PartialEq for Buf<T, A>
+  where T: PartialEq,
+        A: Allocator
+{
+  fn eq(&self, other: &Self) -> bool {
+    self.0 == other.0
+  }
+}
+
+impl<T, A> Eq for Buf<T, A>
+  where T: Eq,
+        A: Allocator
+{
+}
+
+impl<T, A> Deref for Buf<T, A> where A: Allocator
+{
+  type Target = [T];
+
+  fn deref(&self) -> &[T] {
+    &self.0
+  }
+}
+
+impl<T, A> DerefMut for Buf<T, A> where A: Allocator
+{
+  fn deref_mut(&mut self) -> &mut [T] {
+    &mut self.0
+  }
+}
+
+impl<T, A> Len for Buf<T, A> where A: Allocator
+{
+  const CAPACITY: Option<usize> = None;
+
+  fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  fn is_full(&self) -> bool {
+    false
+  }
+}
+
+impl<T, A> Reserve for Buf<T, A> where A: Allocator + Default
+{
+  fn reserve(n: usize) -> Self {
+    Self(Vec::with_capacity_in(n, A::default()))
+  }
+}
+
+impl<T, A> Filled<T> for Buf<T, A> where A: Allocator
+{
+  fn filled_using<F>(_: F) -> Option<Self>
+    where F: Fn() -> T
+  {
+    None
+  }
+}
+
+impl<T, A> Trunc for Buf<T, A> where A: Allocator
+{
+  fn trunc(&mut self, len: usize) {
+    self.0.truncate(len)
+  }
+}
+
+impl<T, A> Indexed<T> for Buf<T, A> where A: Allocator
+{
+  fn insert(&mut self, ix: usize, t: T) {
+    self.0.insert(ix, t)
+  }
+
+  fn remove(&mut self, ix: usize) -> Option<T> {
+    if ix < self.0.len() {
+      Some(self.0.remove(ix))
+    } else {
+      None
+    }
+  }
+}
+
+impl<T, A> Extend<T> for Buf<T, A> where A: Allocator
+{
+  fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+    self.0.extend(iter)
+  }
+}
+
+impl<T, A> FromIterator<T> for Buf<T, A> where A: Allocator + Default
+{
+  fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+    let mut buf = Self::default();
+    buf.extend(iter);
+    buf
+  }
+}
+
+impl<T, A> IntoIterator for Buf<T, A> where A: Allocator
+{
+  type Item = T;
+  type IntoIter = std_alloc::vec::IntoIter<T, A>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.0.into_iter()
+  }
+}
+
+impl<T, A> AppendCopy<T> for Buf<T, A>
+  where T: Copy,
+        A: Allocator
+{
+  fn append_copy(&mut self, i: &[T]) {
+    self.0.extend_from_slice(i)
+  }
+}
+
+impl<T, A> Array for Buf<T, A> where A: Allocator + Default
+{
+  type Item = T;
+}
+
+/// implementor of [`crate::platform::PlatformTypes`] storing
+/// message payloads and effects in [`Buf`]s allocated from `A`.
+///
+/// Options are stored inline in fixed-capacity collections
+/// (up to 16 options of up to 4 values of up to 64 bytes each, like
+/// [`embassy::PlatformTypes`](crate::platform::embassy::PlatformTypes)),
+/// since `toad_msg` only supports heap-allocated options in global-heap
+/// collections. Neither touches the global heap.
+///
+/// ```
+/// #![feature(allocator_api)]
+///
+/// use std::alloc::{AllocError, Allocator, Layout, System};
+/// use std::ptr::NonNull;
+///
+/// use toad::platform::allocator::PlatformTypes;
+///
+/// /// Handle to a dedicated pool for CoAP messages
+/// /// (here just the system allocator, for brevity)
+/// #[derive(Debug, Default, Clone, Copy)]
+/// struct Pool;
+///
+/// unsafe impl Allocator for Pool {
+///   fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+///     System.allocate(layout)
+///   }
+///
+///   unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+///     System.deallocate(ptr, layout)
+///   }
+/// }
+///
+/// type P = PlatformTypes<toad::std::Clock, std::net::UdpSocket, Pool>;
+/// type Message = toad::platform::Message<P>;
+/// ```
+pub struct PlatformTypes<C, S, A, E = core::convert::Infallible>(PhantomData<(C, S, A, E)>);
+
+impl<C, S, A, E> Clone for PlatformTypes<C, S, A, E> {
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<C, S, A, E> Copy for PlatformTypes<C, S, A, E> {}
+
+impl<C, S, A, E> Debug for PlatformTypes<C, S, A, E> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_tuple("PlatformTypes").finish()
+  }
+}
+
+impl<C, S, A, E> platform::PlatformTypes for PlatformTypes<C, S, A, E>
+  where C: Clock + 'static,
+        S: Socket + 'static,
+        A: Allocator + Default + Clone + 'static,
+        E: Clone + PartialEq + Debug + 'static
+{
+  type MessagePayload = Buf<u8, A>;
+  type MessageOptionBytes = ArrayVec<[u8; 64]>;
+  type MessageOptionMapOptionValues = ArrayVec<[OptValue<Self::MessageOptionBytes>; 4]>;
+  type MessageOptions = ArrayVec<[(OptNumber, Self::MessageOptionMapOptionValues); 16]>;
+  type Clock = C;
+  type Socket = S;
+  type Effects = Buf<Effect<Self>, A>;
+  type CustomEffect = E;
+}
+
+#[cfg(test)]
+mod tests {
+  use std_alloc::alloc::Global;
+  use toad_msg::{Code, Id, Payload, Token, TryFromBytes, TryIntoBytes, Type};
+
+  use super::*;
+  use crate::test::{ClockMock, SockMock};
+
+  type P = PlatformTypes<ClockMock, SockMock, Global>;
+
+  #[test]
+  fn buf_is_array() {
+    let mut buf = Buf::<u8, Global>::reserve(4);
+    buf.extend([2, 4]);
+    buf.insert(0, 1);
+    buf.append_copy(&[5, 6]);
+
+    assert_eq!(&*buf, &[1, 2, 4, 5, 6]);
+    assert_eq!(buf.remove(2), Some(4));
+    assert_eq!(buf.remove(9), None);
+
+    buf.trunc(2);
+    assert_eq!(buf.clone().into_iter().collect::<Vec<_>>(), vec![1, 2]);
+    assert!(!buf.is_full());
+    assert!(Buf::<u8, Global>::filled(0).is_none());
+  }
+
+  #[test]
+  fn message_roundtrips() {
+    let msg = platform::Message::<P> { ver: Default::default(),
+                                       ty: Type::Con,
+                                       code: Code::GET,
+                                       id: Id(1),
+                                       token: Token(Default::default()),
+                                       opts: Default::default(),
+                                       payload: Payload(b"hello".iter().copied().collect()) };
+
+    let bytes: Vec<u8> = msg.clone().try_into_bytes().unwrap();
+    assert_eq!(platform::Message::<P>::try_from_bytes(bytes).unwrap(), msg);
+  }
+}
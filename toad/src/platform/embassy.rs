@@ -0,0 +1,312 @@
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use embassy_net::udp::{BindError, RecvError, SendError, UdpSocket};
+use embassy_net::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address};
+use embedded_time::rate::Fraction;
+use no_std_net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+use tinyvec::ArrayVec;
+use toad_msg::{OptNumber, OptValue};
+use toad_stem::Stem;
+
+use crate::config::Config;
+use crate::net::{self, Addrd};
+use crate::platform::{self, Effect};
+use crate::req::Req;
+use crate::resp::Resp;
+use crate::step::Step;
+use crate::todo::String;
+
+/// Errors yielded by [`Socket`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+  /// Binding the underlying `embassy_net` socket failed
+  Bind(BindError),
+  /// Sending a datagram failed
+  Send(SendError),
+  /// Receiving a datagram failed
+  Recv(RecvError),
+  /// `embassy_net` sockets borrow their buffers and network stack,
+  /// so they can't be created from just an address.
+  ///
+  /// Create an [`embassy_net::udp::UdpSocket`] yourself and
+  /// wrap it with [`Socket::new`].
+  BindRawUnsupported,
+  /// Multicast group membership is managed by the
+  /// `embassy_net::Stack`, not individual sockets.
+  ///
+  /// Use `Stack::join_multicast_group` and `Stack::leave_multicast_group` instead.
+  MulticastUnsupported,
+}
+
+/// Datagram buffer used by [`Socket`]
+pub type Dgram = ArrayVec<[u8; 1152]>;
+
+/// [`net::Socket`] implementation backed by an [`embassy_net::udp::UdpSocket`]
+///
+/// The embassy socket is async, and this socket polls it with a no-op
+/// waker; when no datagram is ready [`nb::Error::WouldBlock`] is yielded.
+///
+/// This means the task driving `toad` should yield to the executor
+/// between polls, see [`block_async`].
+pub struct Socket {
+  sock: UdpSocket<'static>,
+  peeked: Stem<Option<Addrd<Dgram>>>,
+}
+
+impl core::fmt::Debug for Socket {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("Socket")
+     .field("endpoint", &self.sock.endpoint())
+     .field("peeked", &self.peeked.map_ref(|p| p.is_some()))
+     .finish()
+  }
+}
+
+impl Socket {
+  /// Bind an [`embassy_net::udp::UdpSocket`] to `port` and use it
+  /// as a [`net::Socket`]
+  pub fn new(mut sock: UdpSocket<'static>, port: u16) -> Result<Self, Error> {
+    sock.bind(port).map_err(Error::Bind)?;
+    Ok(Self::from_bound(sock))
+  }
+
+  /// Use an [`embassy_net::udp::UdpSocket`] that has already been bound
+  /// as a [`net::Socket`]
+  pub fn from_bound(sock: UdpSocket<'static>) -> Self {
+    Self { sock,
+           peeked: Stem::new(None) }
+  }
+
+  fn poll_recv(&self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, Error> {
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    match self.sock.poll_recv_from(buffer, &mut cx) {
+      | Poll::Ready(Ok((n, meta))) => Ok(Addrd(n, endpoint_to_addr(meta.endpoint))),
+      | Poll::Ready(Err(e)) => Err(nb::Error::Other(Error::Recv(e))),
+      | Poll::Pending => Err(nb::Error::WouldBlock),
+    }
+  }
+}
+
+impl net::Socket for Socket {
+  type Error = Error;
+  type Dgram = Dgram;
+
+  fn local_addr(&self) -> SocketAddr {
+    let ep = self.sock.endpoint();
+    let ip = ep.addr
+               .map(ip_from_embassy)
+               .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    SocketAddr::new(ip, ep.port)
+  }
+
+  fn empty_dgram() -> Self::Dgram {
+    ArrayVec::from([0u8; 1152])
+  }
+
+  fn bind_raw<A: ToSocketAddrs>(_: A) -> Result<Self, Self::Error> {
+    Err(Error::BindRawUnsupported)
+  }
+
+  fn send(&self, msg: Addrd<&[u8]>) -> nb::Result<(), Self::Error> {
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    match self.sock
+              .poll_send_to(msg.data(), addr_to_endpoint(msg.addr()), &mut cx)
+    {
+      | Poll::Ready(Ok(())) => Ok(()),
+      | Poll::Ready(Err(e)) => Err(nb::Error::Other(Error::Send(e))),
+      | Poll::Pending => Err(nb::Error::WouldBlock),
+    }
+  }
+
+  fn recv(&self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
+    match self.peeked.map_mut(Option::take) {
+      | Some(Addrd(dgram, addr)) => {
+        let n = dgram.len().min(buffer.len());
+        buffer[..n].copy_from_slice(&dgram[..n]);
+        Ok(Addrd(n, addr))
+      },
+      | None => self.poll_recv(buffer),
+    }
+  }
+
+  fn peek(&self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
+    if self.peeked.map_ref(Option::is_none) {
+      let mut dgram = Self::empty_dgram();
+      let Addrd(n, addr) = self.poll_recv(&mut dgram)?;
+      dgram.truncate(n);
+      self.peeked.map_mut(|p| *p = Some(Addrd(dgram, addr)));
+    }
+
+    self.peeked.map_ref(|p| {
+                 let Addrd(dgram, addr) = p.as_ref().unwrap();
+                 let n = dgram.len().min(buffer.len());
+                 buffer[..n].copy_from_slice(&dgram[..n]);
+                 Ok(Addrd(n, *addr))
+               })
+  }
+
+  fn join_multicast(&self, _: IpAddr) -> Result<(), Self::Error> {
+    Err(Error::MulticastUnsupported)
+  }
+
+  fn leave_multicast(&self, _: IpAddr) -> Result<(), Self::Error> {
+    Err(Error::MulticastUnsupported)
+  }
+}
+
+fn ip_from_embassy(ip: IpAddress) -> IpAddr {
+  match ip {
+    | IpAddress::Ipv4(Ipv4Address(bytes)) => IpAddr::V4(bytes.into()),
+    | IpAddress::Ipv6(Ipv6Address(bytes)) => IpAddr::V6(bytes.into()),
+  }
+}
+
+fn ip_to_embassy(ip: IpAddr) -> IpAddress {
+  match ip {
+    | IpAddr::V4(ip) => IpAddress::Ipv4(Ipv4Address(ip.octets())),
+    | IpAddr::V6(ip) => IpAddress::Ipv6(Ipv6Address(ip.octets())),
+  }
+}
+
+fn endpoint_to_addr(ep: IpEndpoint) -> SocketAddr {
+  SocketAddr::new(ip_from_embassy(ep.addr), ep.port)
+}
+
+fn addr_to_endpoint(addr: SocketAddr) -> IpEndpoint {
+  IpEndpoint::new(ip_to_embassy(addr.ip()), addr.port())
+}
+
+fn noop_waker() -> Waker {
+  const VTABLE: RawWakerVTable = RawWakerVTable::new(|_| RawWaker::new(core::ptr::null(), &VTABLE),
+                                                     |_| (),
+                                                     |_| (),
+                                                     |_| ());
+
+  // SAFETY: every function in the vtable is a no-op that never
+  // dereferences the data pointer.
+  #[allow(unsafe_code)]
+  unsafe {
+    Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE))
+  }
+}
+
+/// Implement [`embedded_time::Clock`] using [`embassy_time::Instant`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Clock;
+
+impl Clock {
+  /// Create a new clock
+  pub fn new() -> Self {
+    Self
+  }
+}
+
+impl embedded_time::Clock for Clock {
+  type T = u64;
+
+  // microseconds
+  const SCALING_FACTOR: Fraction = Fraction::new(1, 1_000_000);
+
+  fn try_now(&self) -> Result<embedded_time::Instant<Self>, embedded_time::clock::Error> {
+    Ok(embedded_time::Instant::new(embassy_time::Instant::now().as_micros()))
+  }
+}
+
+/// implementor of [`crate::platform::PlatformTypes`] for
+/// `embassy` targets, using fixed-capacity collections.
+///
+/// A single event can make every step in the runtime emit
+/// effects (logs especially), so up to 32 effects can be
+/// collected at once. Since these collections can't grow,
+/// collecting more than that panics.
+#[derive(Clone, Copy, Debug)]
+pub struct PlatformTypes;
+
+impl platform::PlatformTypes for PlatformTypes {
+  type MessagePayload = ArrayVec<[u8; 1024]>;
+  type MessageOptionBytes = ArrayVec<[u8; 64]>;
+  type MessageOptionMapOptionValues = ArrayVec<[OptValue<Self::MessageOptionBytes>; 4]>;
+  type MessageOptions = ArrayVec<[(OptNumber, Self::MessageOptionMapOptionValues); 16]>;
+  type Clock = Clock;
+  type Socket = Socket;
+  type Effects = ArrayVec<[Effect<Self>; 32]>;
+  type CustomEffect = core::convert::Infallible;
+}
+
+/// implementor of [`crate::platform::Platform`] for `embassy`
+///
+/// Logs are forwarded to the [`log`] crate.
+#[derive(Debug)]
+pub struct Platform<Steps> {
+  steps: Steps,
+  config: Config,
+  socket: Socket,
+  clock: Clock,
+}
+
+impl<Steps> Platform<Steps>
+  where Steps: Step<PlatformTypes,
+                    PollReq = Addrd<Req<PlatformTypes>>,
+                    PollResp = Addrd<Resp<PlatformTypes>>>
+{
+  /// Create a new embassy runtime
+  pub fn new(socket: Socket, config: Config) -> Self
+    where Steps: Default
+  {
+    Self { steps: Steps::default(),
+           config,
+           socket,
+           clock: Clock::new() }
+  }
+}
+
+impl<Steps> platform::Platform<Steps> for Platform<Steps>
+  where Steps: Step<PlatformTypes,
+                    PollReq = Addrd<Req<PlatformTypes>>,
+                    PollResp = Addrd<Resp<PlatformTypes>>>
+{
+  type Types = PlatformTypes;
+  type Error = platform::Error<Steps::Error, Error>;
+
+  fn log(&self, level: log::Level, msg: String<1000>) -> Result<(), Self::Error> {
+    log::log!(target: "toad", level, "{}", msg.as_str());
+    Ok(())
+  }
+
+  fn config(&self) -> Config {
+    self.config
+  }
+
+  fn steps(&self) -> &Steps {
+    &self.steps
+  }
+
+  fn socket(&self) -> &Socket {
+    &self.socket
+  }
+
+  fn clock(&self) -> &Clock {
+    &self.clock
+  }
+}
+
+/// Drive a non-blocking operation (e.g. [`Platform::poll_req`](crate::platform::Platform::poll_req))
+/// to completion from an async task, yielding to the executor
+/// whenever it would block.
+///
+/// ```ignore
+/// let req = toad::platform::embassy::block_async(|| platform.poll_req()).await?;
+/// ```
+pub async fn block_async<T, E>(mut f: impl FnMut() -> nb::Result<T, E>) -> Result<T, E> {
+  loop {
+    match f() {
+      | Ok(t) => break Ok(t),
+      | Err(nb::Error::Other(e)) => break Err(e),
+      | Err(nb::Error::WouldBlock) => embassy_futures::yield_now().await,
+    }
+  }
+}
@@ -0,0 +1,230 @@
+use toad_array::{AppendCopy, Array, Trunc};
+use toad_map::Map;
+use toad_msg::OptValue;
+use toad_stem::Stem;
+
+use crate::platform::{Message, PlatformTypes};
+
+/// Number of messages a [`MessagePool`] keeps when the collection
+/// it stores them in has no fixed capacity (e.g. `Vec`)
+pub const DEFAULT_CAPACITY: usize = 32;
+
+/// Counters kept by a [`MessagePool`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+  /// Messages requested from the pool
+  pub checkouts: u64,
+  /// Checkouts that were satisfied by a message in the pool
+  /// (the rest found the pool empty)
+  pub reused: u64,
+  /// Messages given back & kept for reuse
+  pub returned: u64,
+  /// Messages given back & dropped because the pool was full
+  pub dropped: u64,
+}
+
+#[derive(Debug, Default)]
+struct State<B> {
+  free: B,
+  stats: Stats,
+}
+
+/// Messages that have been used & can be used again
+/// without allocating new storage for them.
+///
+/// A message [given back](MessagePool::give_back) to the pool keeps its
+/// payload & option storage, so when they are stored on the heap a
+/// message [checked out](MessagePool::checkout) of the pool can hold a payload
+/// of the same size & similar options without allocating. Platforms storing messages in
+/// fixed-capacity collections have nothing to gain from a pool.
+///
+/// Messages are stored in `B`; the pool holds up to
+/// `B`'s capacity or [`DEFAULT_CAPACITY`] messages.
+///
+/// See [`step::pool`](crate::step::pool) to use a pool in a runtime.
+///
+/// ```
+/// use toad::platform::{Message, MessagePool};
+/// use toad::std::{dtls, PlatformTypes};
+/// use toad_msg::{Code, Id, Payload, Token, Type};
+///
+/// type P = PlatformTypes<dtls::N>;
+///
+/// let pool = MessagePool::<Vec<Message<P>>>::default();
+/// assert!(pool.checkout().is_none());
+///
+/// let mut msg = Message::<P>::new(Type::Con, Code::POST, Id(1), Token(Default::default()));
+/// msg.payload = Payload(b"hello".to_vec());
+/// pool.give_back(msg);
+///
+/// let msg = pool.checkout().unwrap();
+/// assert!(msg.payload.0.is_empty());
+/// assert!(msg.payload.0.capacity() >= 5);
+///
+/// assert_eq!(pool.stats().reused, 1);
+/// ```
+#[derive(Debug)]
+pub struct MessagePool<B> {
+  state: Stem<State<B>>,
+  capacity: usize,
+}
+
+impl<P, B> Default for MessagePool<B>
+  where P: PlatformTypes,
+        B: Array<Item = Message<P>>
+{
+  fn default() -> Self {
+    Self::with_capacity(B::CAPACITY.unwrap_or(DEFAULT_CAPACITY))
+  }
+}
+
+impl<P, B> MessagePool<B>
+  where P: PlatformTypes,
+        B: Array<Item = Message<P>>
+{
+  /// Create a pool holding up to `capacity` messages
+  /// (or `B`'s capacity, if it is smaller)
+  pub fn with_capacity(capacity: usize) -> Self {
+    Self { state: Stem::new(State { free: B::default(),
+                                    stats: Stats::default() }),
+           capacity: B::CAPACITY.map(|cap| cap.min(capacity))
+                                .unwrap_or(capacity) }
+  }
+
+  /// Take a message out of the pool, if there are any.
+  ///
+  /// The message has an empty payload; everything else (including its options,
+  /// whose storage can be reused) is left as it was and should be overwritten.
+  pub fn checkout(&self) -> Option<Message<P>> {
+    self.state.map_mut(|s| {
+                let msg = match s.free.len() {
+                  | 0 => None,
+                  | len => s.free.remove(len - 1),
+                };
+                s.stats.checkouts += 1;
+                if msg.is_some() {
+                  s.stats.reused += 1;
+                }
+                msg
+              })
+  }
+
+  /// Put a message that is no longer needed in the pool,
+  /// or drop it if the pool is full.
+  pub fn give_back(&self, mut msg: Message<P>) {
+    let capacity = self.capacity;
+    self.state.map_mut(|s| {
+                if s.free.len() >= capacity {
+                  s.stats.dropped += 1;
+                  return;
+                }
+
+                msg.payload.0.trunc(0);
+                s.free.append(msg);
+                s.stats.returned += 1;
+              })
+  }
+
+  /// Number of messages in the pool
+  pub fn len(&self) -> usize {
+    self.state.map_ref(|s| s.free.len())
+  }
+
+  /// Is the pool empty?
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Get the counters as of right now
+  pub fn stats(&self) -> Stats {
+    self.state.map_ref(|s| s.stats)
+  }
+}
+
+/// Overwrite the options in `dst` with the options in `src`,
+/// reusing the storage of values already in `dst`
+pub(crate) fn copy_opts<P>(dst: &mut P::MessageOptions, src: &P::MessageOptions)
+  where P: PlatformTypes
+{
+  while let Some(n) = dst.iter().map(|(n, _)| *n).find(|n| src.get(n).is_none()) {
+    dst.remove(&n);
+  }
+
+  src.iter().for_each(|(n, vals)| match dst.get_mut(n) {
+              | Some(dst_vals) => {
+                dst_vals.trunc(vals.len());
+                dst_vals.iter_mut().zip(vals.iter()).for_each(|(d, s)| {
+                                                        d.0.trunc(0);
+                                                        d.0.append_copy(&s.0);
+                                                      });
+                vals.iter()
+                    .skip(dst_vals.len())
+                    .for_each(|v| dst_vals.append(OptValue(v.0.iter().copied().collect())));
+              },
+              | None => {
+                let vals = vals.iter()
+                               .map(|v| OptValue(v.0.iter().copied().collect()))
+                               .collect();
+                dst.insert(*n, vals).ok();
+              },
+            });
+}
+
+#[cfg(test)]
+mod tests {
+  use toad_msg::{Code, Id, MessageOptions, Payload, Token, Type};
+
+  use super::*;
+  use crate::test;
+
+  fn msg(payload: &[u8]) -> test::Message {
+    let mut msg = test::Message::new(Type::Con, Code::POST, Id(1), Token(Default::default()));
+    msg.payload = Payload(payload.to_vec());
+    msg
+  }
+
+  #[test]
+  fn checkout_yields_the_most_recently_given_back() {
+    let pool = MessagePool::<Vec<test::Message>>::default();
+
+    pool.give_back(msg(b"a"));
+    pool.give_back(msg(b"bb"));
+
+    assert!(pool.checkout().unwrap().payload.0.capacity() >= 2);
+  }
+
+  #[test]
+  fn copy_opts_reuses_option_storage() {
+    let mut dst = msg(b"");
+    dst.set_path("a/b/c").unwrap();
+    dst.set_content_format(toad_msg::ContentFormat::Json).unwrap();
+    let ptr = dst.opts.get(&toad_msg::repeat::PATH).unwrap()[0].0.as_ptr();
+
+    let mut src = msg(b"");
+    src.set_path("d/e").unwrap();
+    src.set_accept(toad_msg::ContentFormat::Text).unwrap();
+
+    copy_opts::<test::Platform>(&mut dst.opts, &src.opts);
+
+    assert_eq!(dst.opts, src.opts);
+    assert_eq!(dst.opts.get(&toad_msg::repeat::PATH).unwrap()[0].0.as_ptr(), ptr);
+  }
+
+  #[test]
+  fn drops_messages_when_full() {
+    let pool = MessagePool::<Vec<test::Message>>::with_capacity(1);
+
+    pool.give_back(msg(b"a"));
+    pool.give_back(msg(b"b"));
+    assert_eq!(pool.len(), 1);
+
+    assert!(pool.checkout().is_some());
+    assert!(pool.checkout().is_none());
+
+    assert_eq!(pool.stats(),
+               Stats { checkouts: 2,
+                       reused: 1,
+                       returned: 1,
+                       dropped: 1 });
+  }
+}
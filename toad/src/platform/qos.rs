@@ -0,0 +1,236 @@
+use toad_array::Array;
+use toad_len::Len;
+use toad_msg::{CodeKind, Type};
+
+use crate::net::Addrd;
+use crate::platform::{Effect, Message, PlatformTypes};
+
+/// Quality-of-service class of an outbound message
+///
+/// Classes are ordered from most to least urgent, so
+/// `Qos::Ack < Qos::Notification`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Qos {
+  /// ACKs & RESETs, which peers are waiting on to stop retransmitting
+  Ack,
+  /// Responses to requests
+  Response,
+  /// Requests & pings
+  Request,
+  /// Responses with an Observe option, i.e. Observe notifications
+  Notification,
+}
+
+impl Qos {
+  /// Get the class of a message
+  ///
+  /// ```
+  /// use toad::platform::qos::Qos;
+  /// use toad::std::{dtls, PlatformTypes as Std};
+  /// use toad_msg::{Code, Id, Token, Type};
+  ///
+  /// type Message = toad::platform::Message<Std<dtls::N>>;
+  ///
+  /// let con = Message::new(Type::Con, Code::GET, Id(1), Token(Default::default()));
+  /// assert_eq!(Qos::of(&con), Qos::Request);
+  /// assert_eq!(Qos::of(&con.ack(con.id)), Qos::Ack);
+  /// ```
+  pub fn of<P>(msg: &Message<P>) -> Self
+    where P: PlatformTypes
+  {
+    match (msg.ty, msg.code.kind()) {
+      | (Type::Ack | Type::Reset, _) => Qos::Ack,
+      | (_, CodeKind::Response) if crate::observe::notification_seq(msg).is_some() => {
+        Qos::Notification
+      },
+      | (_, CodeKind::Response) => Qos::Response,
+      | _ => Qos::Request,
+    }
+  }
+}
+
+/// Things that can be ordered by [`Qos`] in a [`PriorityQueue`]
+pub trait Prioritized {
+  /// The class of this item
+  fn qos(&self) -> Qos;
+}
+
+impl<P> Prioritized for Message<P> where P: PlatformTypes
+{
+  fn qos(&self) -> Qos {
+    Qos::of(self)
+  }
+}
+
+impl<T> Prioritized for Addrd<T> where T: Prioritized
+{
+  fn qos(&self) -> Qos {
+    self.data().qos()
+  }
+}
+
+impl<P> Prioritized for Effect<P> where P: PlatformTypes
+{
+  /// The class of the message for [`Effect::Send`].
+  ///
+  /// Other effects don't put anything on the network, so
+  /// there is no reason to hold them back & they are [`Qos::Ack`].
+  fn qos(&self) -> Qos {
+    match self {
+      | Effect::Send(msg) => msg.qos(),
+      | _ => Qos::Ack,
+    }
+  }
+}
+
+/// A queue that yields its most urgent items first,
+/// and items of the same [`Qos`] class in the order they were pushed.
+///
+/// The queue is backed by any [`Array`], so a fixed-capacity
+/// `tinyvec::ArrayVec` may be used on platforms without an allocator.
+///
+/// ```
+/// use toad::platform::qos::PriorityQueue;
+/// use toad::std::{dtls, PlatformTypes as Std};
+/// use toad_msg::{Code, Id, Token, Type};
+///
+/// type Message = toad::platform::Message<Std<dtls::N>>;
+///
+/// let req = Message::new(Type::Con, Code::GET, Id(1), Token(Default::default()));
+/// let ack = req.ack(Id(2));
+///
+/// let mut q = PriorityQueue::<Vec<Message>>::new();
+/// q.push(req.clone()).unwrap();
+/// q.push(ack.clone()).unwrap();
+///
+/// assert_eq!(q.pop(), Some(ack));
+/// assert_eq!(q.pop(), Some(req));
+/// assert_eq!(q.pop(), None);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PriorityQueue<A>(A);
+
+impl<A> PriorityQueue<A>
+  where A: Array,
+        A::Item: Prioritized
+{
+  /// Create an empty queue
+  pub fn new() -> Self {
+    Self(A::default())
+  }
+
+  /// Add an item to the queue, after all items that are at least as urgent.
+  ///
+  /// If the queue is full, the item is given back.
+  pub fn push(&mut self, item: A::Item) -> Result<(), A::Item> {
+    if self.0.is_full() {
+      return Err(item);
+    }
+
+    let qos = item.qos();
+    let ix = self.0
+                 .iter()
+                 .position(|queued| queued.qos() > qos)
+                 .unwrap_or(self.0.len());
+    self.0.insert(ix, item);
+    Ok(())
+  }
+
+  /// Remove and return the most urgent item
+  pub fn pop(&mut self) -> Option<A::Item> {
+    if self.0.is_empty() {
+      None
+    } else {
+      self.0.remove(0)
+    }
+  }
+
+  /// Get the most urgent item without removing it
+  pub fn peek(&self) -> Option<&A::Item> {
+    self.0.first()
+  }
+
+  /// The number of items in the queue
+  pub fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  /// Whether the queue is empty
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+
+  /// Whether the queue has no room for more items
+  pub fn is_full(&self) -> bool {
+    self.0.is_full()
+  }
+}
+
+impl<A> IntoIterator for PriorityQueue<A> where A: Array
+{
+  type Item = A::Item;
+  type IntoIter = A::IntoIter;
+
+  /// Iterate over the items, most urgent first
+  fn into_iter(self) -> Self::IntoIter {
+    self.0.into_iter()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use tinyvec::ArrayVec;
+  use toad_msg::{Code, Id, Token};
+
+  use super::*;
+  use crate::test::{self, Platform as P};
+
+  fn msg(ty: Type, code: Code) -> Message<P> {
+    Message::<P>::new(ty, code, Id(1), Token(Default::default()))
+  }
+
+  #[test]
+  fn classifies_messages() {
+    use toad_msg::opt::known::no_repeat::OBSERVE;
+    use toad_msg::{MessageOptions, OptValue};
+
+    let mut notification = msg(Type::Non, Code::new(2, 5));
+    notification.set(OBSERVE, OptValue(vec![1])).ok();
+
+    assert_eq!(Qos::of(&msg(Type::Ack, Code::EMPTY)), Qos::Ack);
+    assert_eq!(Qos::of(&msg(Type::Reset, Code::EMPTY)), Qos::Ack);
+    assert_eq!(Qos::of(&msg(Type::Ack, Code::new(2, 5))), Qos::Ack);
+    assert_eq!(Qos::of(&msg(Type::Con, Code::new(2, 5))), Qos::Response);
+    assert_eq!(Qos::of(&msg(Type::Non, Code::new(4, 4))), Qos::Response);
+    assert_eq!(Qos::of(&msg(Type::Con, Code::GET)), Qos::Request);
+    assert_eq!(Qos::of(&msg(Type::Con, Code::EMPTY)), Qos::Request);
+    assert_eq!(Qos::of(&notification), Qos::Notification);
+  }
+
+  #[test]
+  fn pops_most_urgent_first_and_fifo_within_class() {
+    let send = |ty: Type, code: Code, id: u16| {
+      let mut m = msg(ty, code);
+      m.id = Id(id);
+      Effect::Send(Addrd(m, test::x.x.x.x(80)))
+    };
+
+    let mut q = PriorityQueue::<ArrayVec<[test::Effect; 4]>>::new();
+    q.push(send(Type::Con, Code::GET, 1)).unwrap();
+    q.push(send(Type::Non, Code::new(2, 5), 2)).unwrap();
+    q.push(send(Type::Con, Code::GET, 3)).unwrap();
+    q.push(send(Type::Ack, Code::EMPTY, 4)).unwrap();
+
+    assert!(q.is_full());
+    assert_eq!(q.push(send(Type::Ack, Code::EMPTY, 5)),
+               Err(send(Type::Ack, Code::EMPTY, 5)));
+
+    assert_eq!(q.peek(), Some(&send(Type::Ack, Code::EMPTY, 4)));
+    assert_eq!(core::iter::from_fn(|| q.pop()).collect::<Vec<_>>(),
+               vec![send(Type::Ack, Code::EMPTY, 4),
+                    send(Type::Non, Code::new(2, 5), 2),
+                    send(Type::Con, Code::GET, 1),
+                    send(Type::Con, Code::GET, 3)]);
+    assert!(q.is_empty());
+  }
+}
@@ -1,12 +1,17 @@
+use embedded_time::{Clock, Instant};
+use no_std_net::SocketAddr;
 use toad_array::Array;
 use toad_map::Map;
-use toad_msg::{Id,
+use toad_msg::{CodeKind,
+               Id,
                Message,
+               MessageParseError,
                MessageOptions,
                OptNumber,
                OptionMap,
                Payload,
                Token,
+               TryFromBytes,
                TryIntoBytes,
                Type};
 
@@ -24,6 +29,7 @@ pub mod builder;
 #[doc(inline)]
 pub use builder::*;
 
+use crate::net::{Addrd, Identity};
 use crate::platform::{self, PlatformTypes};
 
 /// A CoAP request
@@ -65,17 +71,96 @@ use crate::platform::{self, PlatformTypes};
 /// }
 /// ```
 #[derive(Debug)]
-pub struct Req<P: PlatformTypes>(platform::Message<P>);
+pub struct Req<P: PlatformTypes>(platform::Message<P>,
+                                 Option<Identity>,
+                                 Option<Received>,
+                                 Option<Meta<P::Clock>>,
+                                 crate::retry::Policy);
 
+/// Diagnostic information about the datagram a request was parsed from
+///
+/// See [`Req::received`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Received {
+  /// Length of the datagram as it was received, in bytes
+  pub dgram_len: usize,
+  /// Was the payload truncated to fit the platform's payload capacity?
+  ///
+  /// See [`Oversized::Truncate`](crate::config::Oversized::Truncate)
+  pub truncated: bool,
+}
+
+/// Transport metadata about how a request arrived
+///
+/// See [`Req::meta`]
+pub struct Meta<C: Clock> {
+  /// The time the datagram containing the request was received
+  pub arrived_at: Instant<C>,
+  /// The local address the request was sent to
+  ///
+  /// This is a multicast group address when the request was multicast,
+  /// see [`Socket::recvd_dest`](crate::net::Socket::recvd_dest)
+  pub local_addr: SocketAddr,
+}
+
+impl<C: Clock> Meta<C> {
+  /// Was the request sent to a multicast group address?
+  ///
+  /// ```
+  /// use embedded_time::Instant;
+  /// use toad::net::ipv4_socketaddr;
+  /// use toad::req::Meta;
+  /// use toad::std::Clock;
+  ///
+  /// let meta = Meta::<Clock> { arrived_at: Instant::new(0),
+  ///                            local_addr: ipv4_socketaddr([224, 0, 1, 187], 5683) };
+  /// assert!(meta.is_multicast());
+  ///
+  /// let meta = Meta::<Clock> { arrived_at: Instant::new(0),
+  ///                            local_addr: ipv4_socketaddr([192, 168, 0, 1], 5683) };
+  /// assert!(!meta.is_multicast());
+  /// ```
+  pub fn is_multicast(&self) -> bool {
+    self.local_addr.ip().is_multicast()
+  }
+}
+
+impl<C: Clock> Clone for Meta<C> {
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<C: Clock> Copy for Meta<C> {}
+
+impl<C: Clock> PartialEq for Meta<C> {
+  fn eq(&self, other: &Self) -> bool {
+    self.arrived_at == other.arrived_at && self.local_addr == other.local_addr
+  }
+}
+
+impl<C: Clock> Eq for Meta<C> {}
+
+impl<C: Clock> core::fmt::Debug for Meta<C> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("Meta")
+     .field("arrived_at", &self.arrived_at.duration_since_epoch().integer())
+     .field("local_addr", &self.local_addr)
+     .finish()
+  }
+}
+
+/// Note that [`Req::received`] and [`Req::meta`] are diagnostic information,
+/// and are not considered when comparing requests.
 impl<P: PlatformTypes> PartialEq for Req<P> {
   fn eq(&self, other: &Self) -> bool {
-    self.0 == other.0
+    self.0 == other.0 && self.1 == other.1 && self.4 == other.4
   }
 }
 
 impl<P: PlatformTypes> Clone for Req<P> {
   fn clone(&self) -> Self {
-    Self(self.0.clone())
+    Self(self.0.clone(), self.1.clone(), self.2, self.3, self.4)
   }
 }
 
@@ -90,7 +175,7 @@ impl<P: PlatformTypes> Req<P> {
                         payload: Payload(Default::default()),
                         token: Token(Default::default()) };
 
-    let mut self_ = Self(msg);
+    let mut self_ = Self(msg, None, None, None, Default::default());
 
     self_.as_mut().set_path(path.as_ref()).ok();
     self_
@@ -106,11 +191,157 @@ impl<P: PlatformTypes> Req<P> {
     &self.0
   }
 
+  /// Obtain a reference to the inner message
+  ///
+  /// Same as [`Req::msg`], named consistently with [`Req::into_msg`].
+  pub fn as_msg(&self) -> &platform::Message<P> {
+    &self.0
+  }
+
   /// Obtain a mutable reference to the inner message
   pub fn msg_mut(&mut self) -> &mut platform::Message<P> {
     &mut self.0
   }
 
+  /// Unwrap the inner message.
+  ///
+  /// Every field and option of the message is preserved (including options
+  /// that toad does not understand), so converting a message to a request and back
+  /// yields the same message. Information about the request that is not part of the message
+  /// ([`Req::identity`], [`Req::received`], [`Req::meta`], [`Req::retry_policy`]) is discarded.
+  ///
+  /// ```
+  /// use toad::platform::Message;
+  /// use toad::req::Req;
+  /// use toad::std::{dtls, PlatformTypes as Std};
+  /// use toad_msg::{MessageOptions, OptNumber};
+  ///
+  /// let mut req = Req::<Std<dtls::Y>>::get("hello");
+  /// req.msg_mut().add(OptNumber(65100), [1u8, 2, 3].into_iter().collect()).unwrap();
+  ///
+  /// let msg: Message<Std<dtls::Y>> = req.clone().into_msg();
+  /// assert_eq!(&msg, req.as_msg());
+  /// assert_eq!(Req::from(msg), req);
+  /// ```
+  pub fn into_msg(self) -> platform::Message<P> {
+    self.0
+  }
+
+  /// Override how this request is retried by the [`Retry`](crate::step::retry) step,
+  /// see [`retry::Policy`](crate::retry::Policy)
+  ///
+  /// The policy is not part of the message, and is only honored when the request
+  /// is sent with [`Platform::send_req`](crate::platform::Platform::send_req).
+  pub fn set_retry_policy(&mut self, policy: crate::retry::Policy) {
+    self.4 = policy;
+  }
+
+  /// How this request will be retried, see [`Req::set_retry_policy`]
+  pub fn retry_policy(&self) -> crate::retry::Policy {
+    self.4
+  }
+
+  /// Get the authenticated identity of the peer that sent this request
+  ///
+  /// This is only present for requests received over a secure transport
+  /// that authenticates peers (e.g. [`std::dtls`](crate::std::dtls)),
+  /// see [`Socket::peer_identity`](crate::net::Socket::peer_identity).
+  ///
+  /// ```
+  /// use toad::net::Identity;
+  /// use toad::req::Req;
+  /// use toad::std::{dtls, PlatformTypes as Std};
+  ///
+  /// let mut req = Req::<Std<dtls::Y>>::get("hello");
+  /// assert_eq!(req.identity(), None);
+  ///
+  /// let id = Identity::psk(b"sensor-12").unwrap();
+  /// req.set_identity(Some(id.clone()));
+  /// assert_eq!(req.identity(), Some(&id));
+  /// ```
+  pub fn identity(&self) -> Option<&Identity> {
+    self.1.as_ref()
+  }
+
+  /// Set the authenticated identity of the peer that sent this request
+  ///
+  /// This is done by the [`Parse`](crate::step::parse::Parse) step
+  /// for incoming requests, and should not typically be needed
+  /// by applications.
+  pub fn set_identity(&mut self, identity: Option<Identity>) {
+    self.1 = identity;
+  }
+
+  /// Get information about the datagram this request was parsed from
+  ///
+  /// This is only present for requests received from the network.
+  ///
+  /// ```
+  /// use toad::req::{Received, Req};
+  /// use toad::std::{dtls, PlatformTypes as Std};
+  ///
+  /// let mut req = Req::<Std<dtls::Y>>::get("hello");
+  /// assert_eq!(req.received(), None);
+  /// assert!(!req.truncated());
+  ///
+  /// req.set_received(Some(Received { dgram_len: 2048,
+  ///                                  truncated: true }));
+  /// assert_eq!(req.received().map(|r| r.dgram_len), Some(2048));
+  /// assert!(req.truncated());
+  /// ```
+  pub fn received(&self) -> Option<Received> {
+    self.2
+  }
+
+  /// Was this request's payload truncated to fit in the platform's
+  /// payload capacity?
+  ///
+  /// See [`Req::received`]
+  pub fn truncated(&self) -> bool {
+    self.2.map(|r| r.truncated).unwrap_or(false)
+  }
+
+  /// Set information about the datagram this request was parsed from
+  ///
+  /// This is done by the [`Parse`](crate::step::parse::Parse) step
+  /// for incoming requests, and should not typically be needed
+  /// by applications.
+  pub fn set_received(&mut self, received: Option<Received>) {
+    self.2 = received;
+  }
+
+  /// Get the time this request arrived and the local address it was sent to
+  ///
+  /// This is only present for requests received from the network,
+  /// and is useful for e.g. choosing a leisure period before responding
+  /// to multicast requests.
+  ///
+  /// ```
+  /// use embedded_time::Instant;
+  /// use toad::net::ipv4_socketaddr;
+  /// use toad::req::{Meta, Req};
+  /// use toad::std::{dtls, PlatformTypes as Std};
+  ///
+  /// let mut req = Req::<Std<dtls::Y>>::get("hello");
+  /// assert_eq!(req.meta(), None);
+  ///
+  /// req.set_meta(Some(Meta { arrived_at: Instant::new(0),
+  ///                          local_addr: ipv4_socketaddr([224, 0, 1, 187], 5683) }));
+  /// assert!(req.meta().unwrap().is_multicast());
+  /// ```
+  pub fn meta(&self) -> Option<Meta<P::Clock>> {
+    self.3
+  }
+
+  /// Set the transport metadata of this request
+  ///
+  /// This is done by the [`Parse`](crate::step::parse::Parse) step
+  /// for incoming requests, and should not typically be needed
+  /// by applications.
+  pub fn set_meta(&mut self, meta: Option<Meta<P::Clock>>) {
+    self.3 = meta;
+  }
+
   /// Get the request path (Uri-Path option)
   pub fn path(&self) -> Result<Option<&str>, core::str::Utf8Error> {
     self.get_option(toad_msg::opt::known::repeat::PATH)
@@ -270,7 +501,7 @@ impl<P> AsMut<platform::Message<P>> for Req<P> where P: platform::PlatformTypes
 
 impl<P: PlatformTypes> From<Req<P>> for platform::Message<P> {
   fn from(req: Req<P>) -> Self {
-    req.0
+    req.into_msg()
   }
 }
 
@@ -284,6 +515,100 @@ impl<P: PlatformTypes> TryIntoBytes for Req<P> {
 
 impl<P: PlatformTypes> From<platform::Message<P>> for Req<P> {
   fn from(msg: platform::Message<P>) -> Self {
-    Self(msg)
+    Self(msg, None, None, None, Default::default())
+  }
+}
+
+/// Errors encounterable while parsing a [`Req`] or [`Resp`](crate::resp::Resp)
+/// from bytes, see [`Req::try_from_bytes`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+  /// The bytes are not a CoAP message
+  Parsing(MessageParseError),
+  /// The bytes are a CoAP message, but not of the kind that was expected
+  /// (e.g. a response was parsed as a request)
+  #[allow(missing_docs)]
+  UnexpectedCodeKind { expected: CodeKind, actual: CodeKind },
+}
+
+/// Parse a message from `bytes`, checking that its code is of kind `expected`
+pub(crate) fn parse<P>(bytes: &[u8], expected: CodeKind) -> Result<platform::Message<P>, ParseError>
+  where P: PlatformTypes
+{
+  let msg = platform::Message::<P>::try_from_bytes(bytes).map_err(ParseError::Parsing)?;
+
+  match msg.code.kind() {
+    | actual if actual == expected => Ok(msg),
+    | actual => Err(ParseError::UnexpectedCodeKind { expected, actual }),
+  }
+}
+
+impl<P: PlatformTypes> Req<P> {
+  /// Parse a request from bytes, e.g. a datagram that was captured
+  /// from the network.
+  ///
+  /// This does not require a [`Platform`](crate::platform::Platform)
+  /// or socket; the [`Parse`](crate::step::parse::Parse) step
+  /// does the same for requests received by a running platform.
+  ///
+  /// ```
+  /// use toad::req::{ParseError, Req};
+  /// use toad::resp::Resp;
+  /// use toad::std::{dtls, PlatformTypes as Std};
+  /// use toad_msg::{CodeKind, TryIntoBytes};
+  ///
+  /// type P = Std<dtls::N>;
+  ///
+  /// let bytes: Vec<u8> = Req::<P>::get("hello").try_into_bytes().unwrap();
+  ///
+  /// let req = Req::<P>::try_from_bytes(&bytes).unwrap();
+  /// assert_eq!(req.path(), Ok(Some("hello")));
+  /// assert_eq!(req.received().map(|r| r.dgram_len), Some(bytes.len()));
+  ///
+  /// assert_eq!(Resp::<P>::try_from_bytes(&bytes),
+  ///            Err(ParseError::UnexpectedCodeKind { expected: CodeKind::Response,
+  ///                                                 actual: CodeKind::Request }));
+  /// ```
+  pub fn try_from_bytes(bytes: impl AsRef<[u8]>) -> Result<Self, ParseError> {
+    let bytes = bytes.as_ref();
+
+    parse::<P>(bytes, CodeKind::Request).map(|msg| {
+                                          let mut req = Req::from(msg);
+                                          req.set_received(Some(Received { dgram_len: bytes.len(),
+                                                                           truncated: false }));
+                                          req
+                                        })
+  }
+
+  /// Parse a request from an addressed datagram,
+  /// see [`Req::try_from_bytes`]
+  pub fn try_from_dgram<B>(dgram: Addrd<B>) -> Result<Addrd<Self>, ParseError>
+    where B: AsRef<[u8]>
+  {
+    let addr = dgram.addr();
+    Self::try_from_bytes(dgram.unwrap()).map(|req| Addrd(req, addr))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use proptest::prelude::*;
+
+  use super::*;
+  use crate::test::{self, Platform};
+
+  proptest! {
+    #[test]
+    fn message_round_trips_through_req(msg in test::arbitrary_message(0..=0)) {
+      let req = Req::<Platform>::from(msg.clone());
+      prop_assert_eq!(req.as_msg(), &msg);
+      prop_assert_eq!(req.into_msg(), msg);
+    }
+
+    #[test]
+    fn req_round_trips_through_bytes(msg in test::arbitrary_message(0..=0)) {
+      let bytes = Req::<Platform>::from(msg.clone()).try_into_bytes::<Vec<u8>>().unwrap();
+      prop_assert_eq!(platform::Message::<Platform>::try_from_bytes(bytes).unwrap(), msg);
+    }
   }
 }
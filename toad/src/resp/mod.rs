@@ -1,10 +1,11 @@
 #[cfg(feature = "alloc")]
 use std_alloc::string::{FromUtf8Error, String};
 use toad_array::Array;
-use toad_msg::{Id, Message, Payload, TryIntoBytes, Type};
+use toad_msg::{CodeKind, Id, Message, Payload, TryIntoBytes, Type};
 
+use crate::net::Addrd;
 use crate::platform::{self, PlatformTypes};
-use crate::req::Req;
+use crate::req::{ParseError, Req};
 
 /// Response codes
 pub mod code;
@@ -83,11 +84,27 @@ impl<P: PlatformTypes> Resp<P> {
     &self.0
   }
 
+  /// Obtain a reference to the inner message
+  ///
+  /// Same as [`Resp::msg`], named consistently with [`Resp::into_msg`].
+  pub fn as_msg(&self) -> &platform::Message<P> {
+    &self.0
+  }
+
   /// Obtain a mutable reference to the inner message
   pub fn msg_mut(&mut self) -> &mut platform::Message<P> {
     &mut self.0
   }
 
+  /// Unwrap the inner message.
+  ///
+  /// Every field and option of the message is preserved (including options
+  /// that toad does not understand), so converting a message to a response and back
+  /// yields the same message.
+  pub fn into_msg(self) -> platform::Message<P> {
+    self.0
+  }
+
   /// Create a new response for a given request.
   ///
   /// If the request is CONfirmable, this will return Some(ACK).
@@ -96,6 +113,10 @@ impl<P: PlatformTypes> Resp<P> {
   ///
   /// If the request is EMPTY or RESET, this will return None.
   ///
+  /// The response carries the request's version and token (and its id, when
+  /// the response is an ACK). The request's options and payload are not
+  /// copied, since they describe the request rather than the response.
+  ///
   /// ```
   /// use toad::platform::Message;
   /// use toad::req::Req;
@@ -141,7 +162,7 @@ impl<P: PlatformTypes> Resp<P> {
                         id: req.msg().id,
                         opts: P::MessageOptions::default(),
                         code: code::CONTENT,
-                        ver: Default::default(),
+                        ver: req.msg().ver,
                         payload: Payload(Default::default()),
                         token: req.msg().token };
 
@@ -154,9 +175,9 @@ impl<P: PlatformTypes> Resp<P> {
   /// you receive a NON request and want to ensure
   /// the client receives your response
   ///
-  /// Note that it would be odd to respond to a CON request
-  /// with an ACK followed by a CON response, because the client
-  /// will keep resending the request until they receive the ACK.
+  /// This is also how a CON request is responded to when the response
+  /// isn't ready yet; the request is ACKed with an Empty message
+  /// and later responded to with a CON (see [`Respond::deferred`](crate::server::ap::Respond::deferred)).
   ///
   /// The `toad` runtime will continually retry sending this until
   /// an ACKnowledgement from the client is received.
@@ -165,7 +186,7 @@ impl<P: PlatformTypes> Resp<P> {
                         id: Id(Default::default()),
                         opts: P::MessageOptions::default(),
                         code: code::CONTENT,
-                        ver: Default::default(),
+                        ver: req.msg().ver,
                         payload: Payload(Default::default()),
                         token: req.msg().token };
 
@@ -182,7 +203,7 @@ impl<P: PlatformTypes> Resp<P> {
                         id: Id(Default::default()),
                         opts: P::MessageOptions::default(),
                         code: code::CONTENT,
-                        ver: Default::default(),
+                        ver: req.msg().ver,
                         payload: Payload(Default::default()),
                         token: req.msg().token };
 
@@ -248,6 +269,61 @@ impl<P: PlatformTypes> Resp<P> {
     String::from_utf8(self.payload().copied().collect())
   }
 
+  /// Get the payload and attempt to interpret it as a UTF-8 string
+  /// without copying it
+  ///
+  /// ```
+  /// use toad::req::Req;
+  /// use toad::resp::Resp;
+  /// use toad::std::{dtls, PlatformTypes as Std};
+  ///
+  /// let req = Req::<Std<dtls::Y>>::get("/hello");
+  ///
+  /// // pretend this is an incoming response
+  /// let mut resp = Resp::<Std<dtls::Y>>::for_request(&req).unwrap();
+  /// resp.set_payload("hello!".bytes());
+  ///
+  /// assert_eq!(resp.payload_str().unwrap(), "hello!");
+  /// ```
+  pub fn payload_str(&self) -> Result<&str, core::str::Utf8Error> {
+    core::str::from_utf8(&self.0.payload.0)
+  }
+
+  /// Get the payload and attempt to deserialize it as JSON
+  ///
+  /// ```
+  /// use toad::req::Req;
+  /// use toad::resp::Resp;
+  /// use toad::std::{dtls, PlatformTypes as Std};
+  ///
+  /// let req = Req::<Std<dtls::Y>>::get("/hello");
+  ///
+  /// // pretend this is an incoming response
+  /// let mut resp = Resp::<Std<dtls::Y>>::for_request(&req).unwrap();
+  /// resp.set_payload(r#"{"temp": 21}"#.bytes());
+  ///
+  /// #[derive(serde::Deserialize)]
+  /// struct Reading {
+  ///   temp: u8,
+  /// }
+  ///
+  /// assert_eq!(resp.payload_json::<Reading>().unwrap().temp, 21);
+  /// ```
+  #[cfg(feature = "std_serde_json")]
+  pub fn payload_json<'a, T>(&'a self) -> Result<T, serde_json::Error>
+    where T: serde::Deserialize<'a>
+  {
+    serde_json::from_slice(&self.0.payload.0)
+  }
+
+  /// Get the payload and attempt to deserialize it as JSON
+  #[cfg(all(not(feature = "std_serde_json"), feature = "unstable_serde_json"))]
+  pub fn payload_json<'a, T>(&'a self) -> Result<T, serde_json_core::de::Error>
+    where T: serde::Deserialize<'a>
+  {
+    serde_json_core::from_slice(&self.0.payload.0).map(|(t, _)| t)
+  }
+
   /// Get the response code
   ///
   /// ```
@@ -265,6 +341,83 @@ impl<P: PlatformTypes> Resp<P> {
     self.0.code
   }
 
+  /// Get the class of the response code (the `2` in `2.05 Content`)
+  ///
+  /// ```
+  /// use toad::req::Req;
+  /// use toad::resp::{code, Resp};
+  /// use toad::std::{dtls, PlatformTypes as Std};
+  ///
+  /// // pretend this is an incoming request
+  /// let req = Req::<Std<dtls::Y>>::get("/hello");
+  /// let mut resp = Resp::<Std<dtls::Y>>::for_request(&req).unwrap();
+  ///
+  /// assert_eq!(resp.code_class(), 2);
+  ///
+  /// resp.set_code(code::NOT_FOUND);
+  /// assert_eq!(resp.code_class(), 4);
+  /// ```
+  pub fn code_class(&self) -> u8 {
+    self.0.code.class
+  }
+
+  /// Is the response code a `2.xx` Success code?
+  ///
+  /// ```
+  /// use toad::req::Req;
+  /// use toad::resp::{code, Resp};
+  /// use toad::std::{dtls, PlatformTypes as Std};
+  ///
+  /// // pretend this is an incoming request
+  /// let req = Req::<Std<dtls::Y>>::get("/hello");
+  /// let mut resp = Resp::<Std<dtls::Y>>::for_request(&req).unwrap();
+  /// assert!(resp.is_success());
+  ///
+  /// resp.set_code(code::INTERNAL_SERVER_ERROR);
+  /// assert!(!resp.is_success());
+  /// ```
+  pub fn is_success(&self) -> bool {
+    self.code_class() == 2
+  }
+
+  /// Is the response code a `4.xx` Client Error code?
+  ///
+  /// ```
+  /// use toad::req::Req;
+  /// use toad::resp::{code, Resp};
+  /// use toad::std::{dtls, PlatformTypes as Std};
+  ///
+  /// // pretend this is an incoming request
+  /// let req = Req::<Std<dtls::Y>>::get("/hello");
+  /// let mut resp = Resp::<Std<dtls::Y>>::for_request(&req).unwrap();
+  ///
+  /// resp.set_code(code::NOT_FOUND);
+  /// assert!(resp.is_client_error());
+  /// assert!(!resp.is_server_error());
+  /// ```
+  pub fn is_client_error(&self) -> bool {
+    self.code_class() == 4
+  }
+
+  /// Is the response code a `5.xx` Server Error code?
+  ///
+  /// ```
+  /// use toad::req::Req;
+  /// use toad::resp::{code, Resp};
+  /// use toad::std::{dtls, PlatformTypes as Std};
+  ///
+  /// // pretend this is an incoming request
+  /// let req = Req::<Std<dtls::Y>>::get("/hello");
+  /// let mut resp = Resp::<Std<dtls::Y>>::for_request(&req).unwrap();
+  ///
+  /// resp.set_code(code::INTERNAL_SERVER_ERROR);
+  /// assert!(resp.is_server_error());
+  /// assert!(!resp.is_client_error());
+  /// ```
+  pub fn is_server_error(&self) -> bool {
+    self.code_class() == 5
+  }
+
   /// Change the response code
   ///
   /// ```
@@ -306,7 +459,7 @@ impl<P: PlatformTypes> Resp<P> {
 
 impl<P: PlatformTypes> From<Resp<P>> for platform::Message<P> {
   fn from(rep: Resp<P>) -> Self {
-    rep.0
+    rep.into_msg()
   }
 }
 
@@ -316,6 +469,41 @@ impl<P: PlatformTypes> From<platform::Message<P>> for Resp<P> {
   }
 }
 
+impl<P: PlatformTypes> Resp<P> {
+  /// Parse a response from bytes, e.g. a datagram that was captured
+  /// from the network.
+  ///
+  /// See [`Req::try_from_bytes`]
+  ///
+  /// ```
+  /// use toad::req::Req;
+  /// use toad::resp::{code, Resp};
+  /// use toad::std::{dtls, PlatformTypes as Std};
+  /// use toad_msg::TryIntoBytes;
+  ///
+  /// type P = Std<dtls::N>;
+  ///
+  /// let mut resp = Resp::<P>::for_request(&Req::get("hello")).unwrap();
+  /// resp.set_code(code::CONTENT);
+  /// let bytes: Vec<u8> = resp.try_into_bytes().unwrap();
+  ///
+  /// assert_eq!(Resp::<P>::try_from_bytes(&bytes).map(|r| r.code()),
+  ///            Ok(code::CONTENT));
+  /// ```
+  pub fn try_from_bytes(bytes: impl AsRef<[u8]>) -> Result<Self, ParseError> {
+    crate::req::parse::<P>(bytes.as_ref(), CodeKind::Response).map(Resp::from)
+  }
+
+  /// Parse a response from an addressed datagram,
+  /// see [`Resp::try_from_bytes`]
+  pub fn try_from_dgram<B>(dgram: Addrd<B>) -> Result<Addrd<Self>, ParseError>
+    where B: AsRef<[u8]>
+  {
+    let addr = dgram.addr();
+    Self::try_from_bytes(dgram.unwrap()).map(|resp| Addrd(resp, addr))
+  }
+}
+
 impl<P: PlatformTypes> TryIntoBytes for Resp<P> {
   type Error = <platform::Message<P> as TryIntoBytes>::Error;
 
@@ -323,3 +511,41 @@ impl<P: PlatformTypes> TryIntoBytes for Resp<P> {
     platform::Message::<P>::from(self).try_into_bytes()
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use proptest::prelude::*;
+
+  use super::*;
+  use crate::test::{self, Platform};
+
+  proptest! {
+    #[test]
+    fn message_round_trips_through_resp(msg in test::arbitrary_message(2..=5)) {
+      let resp = Resp::<Platform>::from(msg.clone());
+      prop_assert_eq!(resp.as_msg(), &msg);
+      prop_assert_eq!(resp.into_msg(), msg);
+    }
+
+    #[test]
+    fn resp_round_trips_through_bytes(msg in test::arbitrary_message(2..=5)) {
+      let bytes = Resp::<Platform>::from(msg.clone()).try_into_bytes::<Vec<u8>>().unwrap();
+      prop_assert_eq!(Resp::<Platform>::try_from_bytes(bytes).unwrap().into_msg(), msg);
+    }
+
+    #[test]
+    fn for_request_keeps_request_fields(msg in test::arbitrary_message(0..=2)) {
+      let req = Req::<Platform>::from(msg.clone());
+      match Resp::for_request(&req).map(Resp::into_msg) {
+        | Some(rep) => {
+          prop_assert_eq!(rep.ver, msg.ver);
+          prop_assert_eq!(rep.token, msg.token);
+          if msg.ty == Type::Con {
+            prop_assert_eq!(rep.id, msg.id);
+          }
+        },
+        | None => prop_assert!(matches!(msg.ty, Type::Ack | Type::Reset)),
+      }
+    }
+  }
+}
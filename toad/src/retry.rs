@@ -4,6 +4,7 @@ use embedded_time::duration::Milliseconds;
 use embedded_time::Instant;
 use naan::prelude::Monad;
 use rand::{Rng, SeedableRng};
+use tinyvec::ArrayVec;
 
 use crate::time::{Clock, Millis};
 
@@ -54,29 +55,64 @@ pub struct RetryTimer<C: Clock> {
   strategy: Strategy,
   attempts: Attempts,
   max_attempts: Attempts,
+  jitter: Jitter,
+  seed: u64,
 }
 
 impl<C> RetryTimer<C> where C: Clock
 {
   /// Create a new retrier
   pub fn new(start: Instant<C>, strategy: Strategy, max_attempts: Attempts) -> Self {
+    let seed = Ok(start.duration_since_epoch()).bind(Millis::try_from)
+                                               .map(|Milliseconds(ms)| ms)
+                                               .unwrap_or_else(|_| {
+                                                 start.duration_since_epoch().integer()
+                                               });
+
     Self { start,
            strategy,
            last_attempted_at: None,
            init: if strategy.has_jitter() {
-             let mut rand =
-               Ok(start.duration_since_epoch()).bind(Millis::try_from)
-                                               .map(|Milliseconds(ms)| {
-                                                 rand_chacha::ChaCha8Rng::seed_from_u64(ms)
-                                               })
-                                               .unwrap();
-
+             let mut rand = rand_chacha::ChaCha8Rng::seed_from_u64(seed);
              Milliseconds(rand.gen_range(strategy.range()))
            } else {
              Milliseconds(*strategy.range().start())
            },
            max_attempts,
-           attempts: Attempts(1) }
+           attempts: Attempts(1),
+           jitter: Jitter::NONE,
+           seed }
+  }
+
+  /// Randomly stretch every delay of this timer by up to `jitter`.
+  ///
+  /// `seed` is mixed with the start time to seed the random number
+  /// generator, so timers started at the same instant on different
+  /// devices (or for different messages) should be given different seeds.
+  ///
+  /// ```
+  /// use embedded_time::clock::Clock;
+  /// use embedded_time::duration::Milliseconds;
+  /// use toad::retry::{Attempts, Jitter, RetryTimer, Strategy};
+  ///
+  /// let clock = toad::std::Clock::new();
+  /// let now = clock.try_now().unwrap();
+  /// let strategy = Strategy::Delay { min: Milliseconds(1000),
+  ///                                  max: Milliseconds(1000) };
+  ///
+  /// let timer = RetryTimer::new(now, strategy, Attempts(2)).with_jitter(Jitter(50), 1234);
+  /// assert!(timer.next_attempt_at() >= now + Milliseconds(1000u64));
+  /// assert!(timer.next_attempt_at() <= now + Milliseconds(1500u64));
+  /// ```
+  pub fn with_jitter(mut self, jitter: Jitter, seed: u64) -> Self {
+    self.jitter = jitter;
+    self.seed ^= seed;
+    self
+  }
+
+  /// Get the jitter applied to this timer's delays
+  pub fn jitter(&self) -> Jitter {
+    self.jitter
   }
 
   /// When the thing we keep trying fails, invoke this to
@@ -114,13 +150,29 @@ impl<C> RetryTimer<C> where C: Clock
   /// Get the next time at which this should be retried
   pub fn next_attempt_at(&self) -> Instant<C> {
     let after_start = match self.strategy {
-      | Strategy::Delay { .. } => Milliseconds(self.init.0 * (self.attempts.0 as u64)),
+      | Strategy::Delay { .. } => Milliseconds(self.init.0.saturating_mul(self.attempts.0 as u64)),
       | Strategy::Exponential { .. } => {
         Milliseconds(Strategy::total_delay_exp(self.init, self.attempts.0))
       },
     };
 
-    self.start + after_start
+    crate::time::after(self.start, self.jittered(after_start))
+  }
+
+  /// Stretch a delay by a random factor in `1.0..=jitter`.
+  ///
+  /// The factor is derived from the seed and the attempt number,
+  /// so it is stable across repeated calls for the same attempt.
+  fn jittered(&self, Milliseconds(ms): Millis) -> Millis {
+    if self.jitter == Jitter::NONE {
+      return Milliseconds(ms);
+    }
+
+    let attempt = (self.attempts.0 as u64).rotate_right(16);
+    let mut rand = rand_chacha::ChaCha8Rng::seed_from_u64(self.seed ^ attempt);
+    let percent = rand.gen_range(0..=self.jitter.0 as u64);
+
+    Milliseconds(ms.saturating_add(ms.saturating_mul(percent) / 100))
   }
 }
 
@@ -133,7 +185,9 @@ impl<C> Clone for RetryTimer<C> where C: Clock
            last_attempted_at: self.last_attempted_at,
            strategy: self.strategy,
            attempts: self.attempts,
-           max_attempts: self.max_attempts }
+           max_attempts: self.max_attempts,
+           jitter: self.jitter,
+           seed: self.seed }
   }
 }
 
@@ -146,6 +200,8 @@ impl<C> PartialEq for RetryTimer<C> where C: Clock
     && self.strategy == other.strategy
     && self.attempts == other.attempts
     && self.max_attempts == other.max_attempts
+    && self.jitter == other.jitter
+    && self.seed == other.seed
   }
 }
 
@@ -179,6 +235,31 @@ impl Mul for Attempts {
   }
 }
 
+/// Random multiplier applied to every delay of a [`RetryTimer`],
+/// as a percentage.
+///
+/// `Jitter(n)` stretches each delay by a random factor between
+/// `1.0` and `1.0 + n / 100`.
+///
+/// Without it, a fleet of devices that lost a server at the same moment
+/// will retry in lockstep when it comes back (a "retry storm").
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Jitter(pub u16);
+
+impl Jitter {
+  /// Delays are not randomized
+  pub const NONE: Self = Jitter(0);
+
+  /// Jitter equivalent to [RFC7252](https://datatracker.ietf.org/doc/html/rfc7252#section-4.8)'s
+  /// default `ACK_RANDOM_FACTOR` of 1.5
+  pub const ACK_RANDOM_FACTOR: Self = Jitter(50);
+
+  /// The longest `delay` could be after being stretched by this jitter
+  pub fn max_of(&self, Milliseconds(delay): Millis) -> Millis {
+    Milliseconds(delay.saturating_add(delay.saturating_mul(self.0 as u64) / 100))
+  }
+}
+
 /// Result of [`RetryTimer.what_should_i_do`].
 ///
 /// This tells you if a retry should be attempted or not.
@@ -233,26 +314,103 @@ impl Strategy {
   }
 
   /// Get the amount of time this strategy will take if all attempts fail
+  ///
+  /// This saturates at `u64::MAX` milliseconds, see [`Strategy::checked_max_time`].
   pub fn max_time(&self, max_attempts: Attempts) -> Millis {
-    Milliseconds(match self {
-                   | Self::Exponential { init_max, .. } => {
-                     Self::total_delay_exp(*init_max, max_attempts.0)
-                   },
-                   | Self::Delay { max: Milliseconds(max),
-                                   .. } => max * max_attempts.0 as u64,
-                 })
+    self.checked_max_time(max_attempts)
+        .unwrap_or(Milliseconds(u64::MAX))
+  }
+
+  /// Get the amount of time this strategy will take if all attempts fail,
+  /// or `None` if it would not fit in a `u64` of milliseconds.
+  ///
+  /// ```
+  /// use embedded_time::duration::Milliseconds;
+  /// use toad::retry::{Attempts, Strategy};
+  ///
+  /// let exp = Strategy::Exponential { init_min: Milliseconds(100),
+  ///                                   init_max: Milliseconds(100) };
+  /// assert_eq!(exp.checked_max_time(Attempts(3)), Some(Milliseconds(400)));
+  /// assert_eq!(exp.checked_max_time(Attempts(100)), None);
+  /// ```
+  pub fn checked_max_time(&self, max_attempts: Attempts) -> Option<Millis> {
+    match self {
+      | Self::Exponential { init_max, .. } => {
+        Self::checked_total_delay_exp(*init_max, max_attempts.0)
+      },
+      | Self::Delay { max: Milliseconds(max),
+                      .. } => max.checked_mul(max_attempts.0 as u64),
+    }.map(Milliseconds)
   }
 
   /// Given the initial delay and number of attempts that have been performed,
   /// yields the delay until the next retry should be attempted.
-  const fn total_delay_exp(Milliseconds(init): Millis, attempt: u16) -> u64 {
+  ///
+  /// Saturates at `u64::MAX`.
+  const fn total_delay_exp(init: Millis, attempt: u16) -> u64 {
+    match Self::checked_total_delay_exp(init, attempt) {
+      | Some(delay) => delay,
+      | None => u64::MAX,
+    }
+  }
+
+  const fn checked_total_delay_exp(Milliseconds(init): Millis, attempt: u16) -> Option<u64> {
     // | attempt | total delay      |
+    // | 0       | 0                |
     // | 1       | init             |
     // | 2       | init * 2         |
     // | 3       | init * 4         |
     // | ...     | ...              |
     // | n       | init * 2^n       |
-    init * 2u64.pow((attempt - 1) as u32)
+    if attempt == 0 {
+      return Some(0);
+    }
+
+    match 2u64.checked_pow((attempt - 1) as u32) {
+      | Some(factor) => init.checked_mul(factor),
+      | None if init == 0 => Some(0),
+      | None => None,
+    }
+  }
+}
+
+/// How a message is retried, overriding the [`Config`](crate::config::Config)
+///
+/// Set on a request with [`Req::set_retry_policy`](crate::req::Req::set_retry_policy),
+/// and honored by the [`Retry`](crate::step::retry) step when the request is sent with
+/// [`Platform::send_req`](crate::platform::Platform::send_req).
+///
+/// ```
+/// use embedded_time::duration::Milliseconds;
+/// use toad::req::Req;
+/// use toad::retry::{Policy, Strategy};
+/// use toad::std::{dtls, PlatformTypes as Std};
+///
+/// // charging a credit card twice would be bad
+/// let mut req = Req::<Std<dtls::Y>>::post("payments");
+/// req.set_retry_policy(Policy::NoRetry);
+/// assert_eq!(req.retry_policy(), Policy::NoRetry);
+///
+/// let fast = Strategy::Delay { min: Milliseconds(50),
+///                              max: Milliseconds(100) };
+/// let mut req = Req::<Std<dtls::Y>>::get("temperature");
+/// req.set_retry_policy(Policy::Custom(fast));
+/// assert_eq!(req.retry_policy(), Policy::Custom(fast));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Policy {
+  /// Retry according to the [`Config`](crate::config::Config)
+  Default,
+  /// Never retry, e.g. for requests that are not idempotent
+  NoRetry,
+  /// Retry with this strategy instead of the one in the config
+  /// (the config's max attempts still apply)
+  Custom(Strategy),
+}
+
+impl Default for Policy {
+  fn default() -> Self {
+    Self::Default
   }
 }
 
@@ -378,6 +536,37 @@ mod test {
     assert_eq!(retry.what_should_i_do(now()).unwrap(), YouShould::Cry);
   }
 
+  #[test]
+  fn jittered_retrier() {
+    let time_millis = 0u64;
+    let clock = FakeClock::new(&time_millis as *const _);
+    let now = || clock.try_now().unwrap();
+    let strategy = Strategy::Delay { min: Milliseconds(1000),
+                                     max: Milliseconds(1000) };
+    let timer = |seed| {
+      RetryTimer::new(now(), strategy, Attempts(5)).with_jitter(Jitter::ACK_RANDOM_FACTOR, seed)
+    };
+
+    let delays = (0..32u64).map(|seed| {
+                             let at = timer(seed).next_attempt_at();
+                             assert_eq!(at, timer(seed).next_attempt_at());
+                             at
+                           })
+                           .collect::<Vec<_>>();
+
+    assert!(delays.iter()
+                  .all(|at| *at >= now() + Milliseconds(1000u64)
+                            && *at <= now() + Milliseconds(1500u64)));
+    assert!(delays.iter().any(|at| *at != delays[0]));
+  }
+
+  #[test]
+  fn jitter_max_of() {
+    assert_eq!(Jitter::NONE.max_of(Milliseconds(1000)), Milliseconds(1000));
+    assert_eq!(Jitter::ACK_RANDOM_FACTOR.max_of(Milliseconds(1000)),
+               Milliseconds(1500));
+  }
+
   #[test]
   fn exp_calculation() {
     let init = Milliseconds(100);
@@ -25,6 +25,7 @@ pub(crate) enum ApInner<S, P, T, Error>
   Err(Error),
   RejectHydrated(Addrd<Req<P>>),
   RespondHydrated(Respond<P>, Addrd<Req<P>>),
+  Deferred(Addrd<Req<P>>),
 }
 
 impl<S, P, T, E> core::fmt::Debug for ApInner<S, P, T, E>
@@ -45,6 +46,7 @@ impl<S, P, T, E> core::fmt::Debug for ApInner<S, P, T, E>
       | ApInner::Respond(r) => f.debug_tuple("ApInner::Respond").field(&r).finish(),
       | ApInner::Err(e) => f.debug_tuple("ApInner::Err").field(&e).finish(),
       | ApInner::RejectHydrated(r) => f.debug_tuple("ApInner::RejectHydrated").field(&r).finish(),
+      | ApInner::Deferred(r) => f.debug_tuple("ApInner::Deferred").field(&r).finish(),
       | ApInner::RespondHydrated(req, rep) => f.debug_tuple("ApInner::RespondHydrated")
                                                .field(&req)
                                                .field(&rep)
@@ -70,6 +72,7 @@ impl<S, P, T, E> PartialEq for ApInner<S, P, T, E>
       },
       | (ApInner::Err(a), ApInner::Err(b)) => a == b,
       | (ApInner::RejectHydrated(a), ApInner::RejectHydrated(b)) => a == b,
+      | (ApInner::Deferred(a), ApInner::Deferred(b)) => a == b,
       | _ => false,
     }
   }
@@ -91,6 +94,7 @@ impl<S, P, T, E> Clone for ApInner<S, P, T, E>
       | ApInner::Respond(r) => ApInner::Respond(r.clone()),
       | ApInner::RespondHydrated(req, rep) => ApInner::RespondHydrated(req.clone(), rep.clone()),
       | ApInner::Err(e) => ApInner::Err(e.clone()),
+      | ApInner::Deferred(r) => ApInner::Deferred(r.clone()),
     }
   }
 }
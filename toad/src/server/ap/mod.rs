@@ -4,7 +4,8 @@ use toad_msg::{Code, MessageOptions};
 
 use crate::net::Addrd;
 use crate::platform::PlatformTypes;
-use crate::req::Req;
+use crate::req::{Method, Req};
+use crate::ContentFormat;
 
 mod inner;
 /// Compile-time encoding of "completeness" of Aps
@@ -21,6 +22,9 @@ pub struct Respond<P>
   pub code: Code,
   pub payload: P::MessagePayload,
   pub etag: Option<P::MessageOptionBytes>,
+  pub block2: Option<toad_msg::Block>,
+  pub content_format: Option<toad_msg::ContentFormat>,
+  pub deferred: bool,
 }
 
 impl<P> Clone for Respond<P> where P: PlatformTypes
@@ -28,14 +32,22 @@ impl<P> Clone for Respond<P> where P: PlatformTypes
   fn clone(&self) -> Self {
     Respond { code: self.code,
               payload: self.payload.clone(),
-              etag: self.etag.clone() }
+              etag: self.etag.clone(),
+              block2: self.block2,
+              content_format: self.content_format,
+              deferred: self.deferred }
   }
 }
 
 impl<P> PartialEq for Respond<P> where P: PlatformTypes
 {
   fn eq(&self, other: &Self) -> bool {
-    self.code == other.code && self.payload == other.payload && self.etag == other.etag
+    self.code == other.code
+    && self.payload == other.payload
+    && self.etag == other.etag
+    && self.block2 == other.block2
+    && self.content_format == other.content_format
+    && self.deferred == other.deferred
   }
 }
 
@@ -46,10 +58,30 @@ impl<P> core::fmt::Debug for Respond<P> where P: PlatformTypes
      .field("code", &self.code)
      .field("payload", &self.payload)
      .field("etag", &self.etag)
+     .field("block2", &self.block2)
+     .field("content_format", &self.content_format)
+     .field("deferred", &self.deferred)
      .finish()
   }
 }
 
+impl<P> Respond<P> where P: PlatformTypes
+{
+  /// Send this as a separate response, for handlers that take
+  /// a while to produce one.
+  ///
+  /// The [`Ack`](crate::step::ack) step acknowledges CON requests with an Empty ACK
+  /// as soon as they are received, so that the client stops retransmitting them.
+  /// A deferred response is then sent as a CON with a new Id (and the request's Token),
+  /// which the [`Retry`](crate::step::retry) step retransmits until the client ACKs it.
+  ///
+  /// Responses that are not deferred are sent as NON.
+  pub fn deferred(self) -> Self {
+    Self { deferred: true,
+           ..self }
+  }
+}
+
 /// Record used to share "hydration" across Ap states
 #[non_exhaustive]
 #[allow(missing_docs)]
@@ -105,6 +137,7 @@ impl<P> core::fmt::Debug for Hydrate<P> where P: PlatformTypes
 ///  * `OkHydrated` - this is [`Result::Ok`] with a CoAP request and partially consumed request path
 ///  * `Reject`, `RejectHydrated` - this has been rejected by the endpoint because a filter failed. This behaves just like `Err` but is separate because it should always be recovered. The unhydrated variant (constructed with [`Ap::reject()`] is useful for writing helper functions that exist outside of a specific server context)
 ///  * `Respond`, `RespondHydrated` - this request has been matched with a resource and has a response to send. This implies no other endpoints or resources will see the original request.
+///  * `Deferred` - this request has been matched with a resource that will respond to it later (see `Ap::bind_async`, requires the `tokio` feature)
 ///
 /// ## States
 ///  * [`Unhydrated`] - `Ap` that is just some data; is not a result that the server can act on
@@ -305,6 +338,63 @@ impl<T, P, E> Ap<Hydrated, P, T, E>
     }
   }
 
+  /// Reject the request if its method is not `method`
+  ///
+  /// Shorthand for [`method::is`](super::method::is).
+  pub fn method(self, method: Method) -> Self {
+    self.pipe(super::method::is(method))
+  }
+
+  /// Reject the request if its Content-Format is not `format`
+  ///
+  /// Shorthand for [`option::content_format`](super::option::content_format).
+  pub fn content_format(self, format: ContentFormat) -> Self {
+    self.pipe(super::option::content_format(format))
+  }
+
+  /// Parse the query parameter `name` with [`FromStr`](core::str::FromStr),
+  /// rejecting the request if it is missing or fails to parse.
+  ///
+  /// Shorthand for [`option::query_param`](super::option::query_param).
+  ///
+  /// ```
+  /// use toad::net::Addrd;
+  /// use toad::req::{Method, Req};
+  /// use toad::resp::code;
+  /// use toad::server::{respond, Run};
+  /// use toad::std::{dtls, PlatformTypes as Std};
+  /// use toad_msg::MessageOptions;
+  ///
+  /// let addr = "127.0.0.1:5683".parse().unwrap();
+  /// let handle = |req: Req<Std<dtls::Y>>| {
+  ///   Run::<_, ()>::Unmatched(Addrd(req, addr)).maybe(|ap| {
+  ///                                               ap.method(Method::GET)
+  ///                                                 .query_param::<u32>("page")
+  ///                                                 .bind(|(_, page)| {
+  ///                                                   respond::ok(format!("page {page}").into())
+  ///                                                 })
+  ///                                                 .or_else_respond(code::BAD_REQUEST)
+  ///                                             })
+  /// };
+  ///
+  /// let mut req = Req::get("users");
+  /// req.msg_mut().add_query("page=2").unwrap();
+  /// match handle(req) {
+  ///   | Run::Matched(rep) => assert_eq!(rep.data().payload.0, b"page 2".to_vec()),
+  ///   | _ => unreachable!(),
+  /// }
+  ///
+  /// match handle(Req::get("users")) {
+  ///   | Run::Matched(rep) => assert_eq!(rep.data().code, code::BAD_REQUEST),
+  ///   | _ => unreachable!(),
+  /// }
+  /// ```
+  pub fn query_param<V>(self, name: &str) -> Ap<Hydrated, P, (T, V), E>
+    where V: core::str::FromStr
+  {
+    self.pipe(super::option::query_param(name))
+  }
+
   /// [`Ap::ok`] with a [`Hydrate`] request context
   pub fn ok_hydrated(t: T, hy: Hydrate<P>) -> Ap<Hydrated, P, T, E> {
     Ap(ApInner::OkHydrated(t, hy))
@@ -375,6 +465,7 @@ impl<S, P, T, E> Ap<S, P, T, E>
       | ApInner::Respond(r) => Ap::respond_hydrated(req, r).coerce_state(),
       | ApInner::Err(e) => Ap::err(e).coerce_state(),
       | ApInner::RejectHydrated(r) => Ap::reject_hydrated(r).coerce_state(),
+      | ApInner::Deferred(r) => Ap(ApInner::Deferred(r)).coerce_state(),
       | ApInner::RespondHydrated(rep, req) => Ap::respond_hydrated(req, rep).coerce_state(),
     }
   }
@@ -473,21 +564,69 @@ impl<S, P, T, E> Ap<S, P, T, E>
   /// set the `etag` option for the response before sending.
   pub fn etag(self, etag: P::MessageOptionBytes) -> Self {
     match self.0 {
-      | ApInner::Respond(Respond { code, payload, .. }) => {
-        Ap::respond(Respond { code,
-                              payload,
-                              etag: Some(etag) }).coerce_state()
+      | ApInner::Respond(rep) => Ap::respond(Respond { etag: Some(etag),
+                                                        ..rep }).coerce_state(),
+      | ApInner::RespondHydrated(rep, req) => {
+        Ap::respond_hydrated(req,
+                             Respond { etag: Some(etag),
+                                       ..rep }).coerce_state()
       },
-      | ApInner::RespondHydrated(Respond { code, payload, .. }, req) => {
+      | other => Self(other),
+    }
+  }
+
+  /// If this is [`Ap::respond`] or [`Ap::respond_hydrated`],
+  /// set the `Content-Format` option for the response before sending.
+  ///
+  /// (Not to be confused with [`Ap::content_format`], which filters
+  /// requests by _their_ `Content-Format`)
+  pub fn format(self, format: toad_msg::ContentFormat) -> Self {
+    match self.0 {
+      | ApInner::Respond(rep) => Ap::respond(Respond { content_format: Some(format),
+                                                        ..rep }).coerce_state(),
+      | ApInner::RespondHydrated(rep, req) => {
         Ap::respond_hydrated(req,
-                             Respond { code,
-                                       payload,
-                                       etag: Some(etag) }).coerce_state()
+                             Respond { content_format: Some(format),
+                                       ..rep }).coerce_state()
+      },
+      | other => Self(other),
+    }
+  }
+
+  /// If this is [`Ap::respond`] or [`Ap::respond_hydrated`],
+  /// send the response [separately](Respond::deferred), as a CON
+  /// that is retransmitted until the client ACKs it.
+  pub fn deferred(self) -> Self {
+    match self.0 {
+      | ApInner::Respond(rep) => Ap::respond(rep.deferred()).coerce_state(),
+      | ApInner::RespondHydrated(rep, req) => {
+        Ap::respond_hydrated(req, rep.deferred()).coerce_state()
       },
       | other => Self(other),
     }
   }
 
+  /// If this is [`Ap::reject`] or [`Ap::reject_hydrated`],
+  /// respond to the request with `code` and an empty payload instead.
+  ///
+  /// This is useful at the end of a route that should answer
+  /// requests it rejected (e.g. with 4.00 BAD REQUEST)
+  /// rather than letting later routes try them.
+  pub fn or_else_respond(self, code: Code) -> Self {
+    let rep = || Respond { code,
+                           payload: Default::default(),
+                           etag: None,
+                           block2: None,
+                           content_format: None,
+                           deferred: false };
+
+    match self.0 {
+      | ApInner::Reject => Ap::respond(rep()).coerce_state(),
+      | ApInner::RejectHydrated(req) => Ap::respond_hydrated(req, rep()).coerce_state(),
+      | other => Self(other),
+    }
+  }
+
   pub(crate) fn coerce_state<S2>(self) -> Ap<S2, P, T, E>
     where S2: ApState
   {
@@ -498,6 +637,7 @@ impl<S, P, T, E> Ap<S, P, T, E>
       | ApInner::Ok(t) => ApInner::Ok(t),
       | ApInner::Reject => ApInner::Reject,
       | ApInner::RejectHydrated(req) => ApInner::RejectHydrated(req),
+      | ApInner::Deferred(req) => ApInner::Deferred(req),
       | ApInner::Respond(r) => ApInner::Respond(r),
       | ApInner::RespondHydrated(a, b) => ApInner::RespondHydrated(a, b),
     };
@@ -518,6 +658,7 @@ impl<S, P, T, E> Ap<S, P, T, E>
       | ApInner::Err(e) => ApInner::Err(e),
       | ApInner::Reject => ApInner::Reject,
       | ApInner::RejectHydrated(req) => ApInner::RejectHydrated(req),
+      | ApInner::Deferred(req) => ApInner::Deferred(req),
       | ApInner::Respond(r) => ApInner::Respond(r),
       | ApInner::RespondHydrated(a, b) => ApInner::RespondHydrated(a, b),
     };
@@ -537,6 +678,7 @@ impl<S, P, T, E> Ap<S, P, T, E>
       | ApInner::OkHydrated(t, hy) => ApInner::OkHydrated(t, hy),
       | ApInner::Ok(t) => ApInner::Ok(t),
       | ApInner::RejectHydrated(req) => ApInner::RejectHydrated(req),
+      | ApInner::Deferred(req) => ApInner::Deferred(req),
       | ApInner::Reject => ApInner::Reject,
       | ApInner::Respond(r) => ApInner::Respond(r),
       | ApInner::RespondHydrated(a, b) => ApInner::RespondHydrated(a, b),
@@ -566,6 +708,7 @@ impl<S, P, T, E> Ap<S, P, T, E>
       | ApInner::Err(e) => ApInner::Err(e),
       | ApInner::Reject => ApInner::Reject,
       | ApInner::RejectHydrated(req) => ApInner::RejectHydrated(req),
+      | ApInner::Deferred(req) => ApInner::Deferred(req),
       | ApInner::Respond(r) => ApInner::Respond(r),
       | ApInner::RespondHydrated(req, rep) => ApInner::RespondHydrated(req, rep),
     };
@@ -599,6 +742,7 @@ impl<S, P, T, E> Ap<S, P, T, E>
       | ApInner::Ok(t) => ApInner::Ok(t),
       | ApInner::Reject => ApInner::Reject,
       | ApInner::RejectHydrated(r) => ApInner::RejectHydrated(r),
+      | ApInner::Deferred(r) => ApInner::Deferred(r),
       | ApInner::Respond(r) => ApInner::Respond(r),
       | ApInner::RespondHydrated(a, b) => ApInner::RespondHydrated(a, b),
     };
@@ -613,6 +757,25 @@ mod tests {
   use crate::req::Req;
   use crate::resp::code;
 
+  #[test]
+  fn or_else_respond_only_replaces_rejections() {
+    type Ap<S> = super::Ap<S, crate::test::Platform, (), ()>;
+
+    let req = || Addrd(Req::<crate::test::Platform>::get("foo"), crate::test::x.x.x.x(80));
+    let bad_request = Respond { code: code::BAD_REQUEST,
+                                payload: Default::default(),
+                                etag: None,
+                                block2: None,
+                                content_format: None,
+                                deferred: false };
+
+    assert_eq!(Ap::reject().or_else_respond(code::BAD_REQUEST),
+               Ap::respond(bad_request.clone()));
+    assert_eq!(Ap::reject_hydrated(req()).or_else_respond(code::BAD_REQUEST),
+               Ap::respond_hydrated(req(), bad_request));
+    assert_eq!(Ap::err(()).or_else_respond(code::BAD_REQUEST), Ap::err(()));
+  }
+
   #[test]
   fn ap_variant_precedence() {
     type Ap<S> = super::Ap<S, crate::test::Platform, (), ()>;
@@ -632,14 +795,20 @@ mod tests {
     let respond = || {
       Ap::respond(Respond { code: code::CONTENT,
                             payload: "".into(),
-                            etag: None })
+                            etag: None,
+                            block2: None,
+                            content_format: None,
+                            deferred: false })
     };
     let reject_hy = || Ap::reject_hydrated(Addrd(req(), addr));
     let respond_hy = || {
       Ap::respond_hydrated(Addrd(req(), addr),
                            Respond { code: code::CONTENT,
                                      payload: "".into(),
-                                     etag: None })
+                                     etag: None,
+                                     block2: None,
+                                     content_format: None,
+                                     deferred: false })
     };
 
     macro_rules! case {
@@ -0,0 +1,290 @@
+use toad_msg::repeat::PATH;
+use toad_msg::{Code, MessageOptions};
+
+use super::ap::state::Hydrated;
+use super::ap::{Ap, Hydrate, Respond};
+use super::Run;
+use crate::net::Identity;
+use crate::platform::PlatformTypes;
+use crate::req::{Method, Req};
+use crate::resp::code;
+
+/// The peers that a [`Rule`] applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Principal<'a> {
+  /// All peers, authenticated or not
+  Anyone,
+  /// All peers that have an authenticated [`Identity`]
+  Authenticated,
+  /// Peers authenticated with a specific PSK identity
+  ///
+  /// See [`Identity::Psk`]
+  Psk(&'a [u8]),
+  /// Peers authenticated with a public key with a specific fingerprint
+  ///
+  /// See [`Identity::RawPublicKey`]
+  RawPublicKey([u8; 32]),
+}
+
+impl<'a> Principal<'a> {
+  /// Does this principal include a peer with (optional) identity `identity`?
+  pub fn includes(&self, identity: Option<&Identity>) -> bool {
+    match (self, identity) {
+      | (Self::Anyone, _) => true,
+      | (Self::Authenticated, id) => id.is_some(),
+      | (Self::Psk(a), Some(Identity::Psk(b))) => *a == b.as_slice(),
+      | (Self::RawPublicKey(a), Some(Identity::RawPublicKey(b))) => a == b,
+      | _ => false,
+    }
+  }
+}
+
+/// Grants a [`Principal`] permission to make requests
+/// using some [`Method`]s to all paths starting with `path`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rule<'a> {
+  /// Who this rule applies to
+  pub principal: Principal<'a>,
+  /// Path prefix that this rule applies to.
+  ///
+  /// This is matched against whole segments of the request path,
+  /// so `"sensors"` matches `sensors/temp` but not `sensorsabc`.
+  /// An empty path matches all requests.
+  pub path: &'a str,
+  /// Methods that are allowed
+  pub methods: &'a [Method],
+}
+
+impl<'a> Rule<'a> {
+  /// Create a new rule
+  pub const fn new(principal: Principal<'a>, path: &'a str, methods: &'a [Method]) -> Self {
+    Self { principal,
+           path,
+           methods }
+  }
+
+  fn matches_path<P>(&self, req: &Req<P>) -> bool
+    where P: PlatformTypes
+  {
+    let mut req_path = req.msg().get(PATH).map(|segs| segs.iter()).into_iter().flatten();
+
+    self.path
+        .split('/')
+        .filter(|seg| !seg.is_empty())
+        .all(|seg| req_path.next().map(|s| s.as_bytes() == seg.as_bytes()) == Some(true))
+  }
+
+  /// Does this rule allow `req`?
+  pub fn allows<P>(&self, req: &Req<P>) -> bool
+    where P: PlatformTypes
+  {
+    self.principal.includes(req.identity())
+    && self.methods.contains(&req.method())
+    && self.matches_path(req)
+  }
+}
+
+/// The outcome of evaluating an [`Acl`] against a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Decision {
+  /// The request may be handled
+  Allow,
+  /// The request was not allowed, and the peer is not authenticated.
+  ///
+  /// Responded to with 4.01 Unauthorized.
+  Unauthorized,
+  /// The request was not allowed, and the peer is authenticated.
+  ///
+  /// Responded to with 4.03 Forbidden.
+  Forbidden,
+}
+
+impl Decision {
+  /// The response code for a request that was not allowed
+  pub fn code(&self) -> Option<Code> {
+    match self {
+      | Self::Allow => None,
+      | Self::Unauthorized => Some(code::UNAUTHORIZED),
+      | Self::Forbidden => Some(code::FORBIDDEN),
+    }
+  }
+}
+
+/// A declarative Access Control List, mapping peer [`Identity`]s
+/// to the methods they may use on paths
+///
+/// Requests are denied unless at least one [`Rule`] allows them.
+/// Denied requests from unauthenticated peers are responded to
+/// with 4.01 Unauthorized, and denied requests from authenticated
+/// peers with 4.03 Forbidden.
+///
+/// Typically an `Acl` is evaluated before all handlers with [`Run::authorize`],
+/// but it may also be used as a filter on individual routes with [`check`].
+///
+/// ```
+/// use toad::net::{Addrd, Identity};
+/// use toad::req::{Method, Req};
+/// use toad::resp::code;
+/// use toad::server::auth::{Acl, Principal, Rule};
+/// use toad::server::{respond, Run};
+/// use toad::std::{dtls, PlatformTypes as Std};
+///
+/// static ACL: Acl = Acl::new(&[Rule::new(Principal::Anyone, "public", &[Method::GET]),
+///                              Rule::new(Principal::Psk(b"admin"),
+///                                        "",
+///                                        &[Method::GET, Method::PUT])]);
+///
+/// # let addr: no_std_net::SocketAddr = "192.168.0.1:8080".parse().unwrap();
+/// let handle = |req: Req<Std<dtls::Y>>| {
+///   Run::<_, ()>::Unmatched(Addrd(req, addr)).authorize(&ACL)
+///                                            .maybe(|ap| ap.bind(|_| respond::ok([].into())))
+/// };
+///
+/// let code_of = |run: Run<Std<dtls::Y>, ()>| match run {
+///   | Run::Matched(rep) => rep.data().code,
+///   | _ => panic!(),
+/// };
+///
+/// assert_eq!(code_of(handle(Req::get("public/time"))), code::CONTENT);
+/// assert_eq!(code_of(handle(Req::put("public/time"))), code::UNAUTHORIZED);
+///
+/// let mut req = Req::put("config");
+/// req.set_identity(Identity::psk(b"guest"));
+/// assert_eq!(code_of(handle(req)), code::FORBIDDEN);
+///
+/// let mut req = Req::put("config");
+/// req.set_identity(Identity::psk(b"admin"));
+/// assert_eq!(code_of(handle(req)), code::CONTENT);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Acl<'a> {
+  rules: &'a [Rule<'a>],
+}
+
+impl<'a> Acl<'a> {
+  /// Create an ACL from a set of rules
+  pub const fn new(rules: &'a [Rule<'a>]) -> Self {
+    Self { rules }
+  }
+
+  /// The rules in this ACL
+  pub fn rules(&self) -> &'a [Rule<'a>] {
+    self.rules
+  }
+
+  /// Decide whether `req` may be handled
+  pub fn decide<P>(&self, req: &Req<P>) -> Decision
+    where P: PlatformTypes
+  {
+    match (self.rules.iter().any(|r| r.allows(req)), req.identity()) {
+      | (true, _) => Decision::Allow,
+      | (false, None) => Decision::Unauthorized,
+      | (false, Some(_)) => Decision::Forbidden,
+    }
+  }
+}
+
+fn enforce<P, T, E>(acl: &Acl, ap: Ap<Hydrated, P, T, E>) -> Ap<Hydrated, P, T, E>
+  where P: PlatformTypes,
+        E: core::fmt::Debug
+{
+  match ap.try_unwrap_ok_hydrated() {
+    | Ok((t, h)) => match acl.decide(h.req.data()).code() {
+      | None => Ap::ok_hydrated(t, h),
+      | Some(code) => {
+        let Hydrate { req, .. } = h;
+        Ap::respond(Respond { code,
+                              payload: Default::default(),
+                              etag: None,
+                              block2: None,
+                              content_format: None,
+                              deferred: false }).hydrate(req)
+                                                   .pretend()
+      },
+    },
+    | Err(e) => e,
+  }
+}
+
+/// Respond with 4.01 / 4.03 if the request is not allowed by `acl`
+///
+/// The full request path is checked, regardless of
+/// how many segments have been consumed.
+pub fn check<'a, P, T, E>(acl: &'a Acl<'a>)
+                          -> impl Fn(Ap<Hydrated, P, T, E>) -> Ap<Hydrated, P, T, E> + 'a
+  where P: PlatformTypes + 'a,
+        T: 'a,
+        E: core::fmt::Debug + 'a
+{
+  move |ap| enforce(acl, ap)
+}
+
+impl<P, E> Run<P, E>
+  where P: PlatformTypes,
+        E: core::fmt::Debug
+{
+  /// Respond with 4.01 / 4.03 to requests that are not allowed by `acl`,
+  /// before any subsequent handlers are invoked.
+  ///
+  /// See [`Acl`]
+  pub fn authorize(self, acl: &Acl) -> Self {
+    self.maybe(|ap| enforce(acl, ap).bind(|_| Ap::reject()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::net::Addrd;
+  use crate::test::Platform;
+
+  type Req = crate::req::Req<Platform>;
+
+  static ACL: Acl = Acl::new(&[Rule::new(Principal::Anyone, "a", &[Method::GET]),
+                               Rule::new(Principal::Authenticated, "b/c", &[Method::GET]),
+                               Rule::new(Principal::RawPublicKey([1; 32]),
+                                         "",
+                                         &[Method::GET, Method::POST])]);
+
+  fn req(method: Method, path: &str, identity: Option<Identity>) -> Req {
+    let mut req = Req::new(method, path);
+    req.set_identity(identity);
+    req
+  }
+
+  #[test]
+  fn path_prefix_matches_whole_segments() {
+    assert_eq!(ACL.decide(&req(Method::GET, "a", None)), Decision::Allow);
+    assert_eq!(ACL.decide(&req(Method::GET, "a/b", None)), Decision::Allow);
+    assert_eq!(ACL.decide(&req(Method::GET, "ab", None)), Decision::Unauthorized);
+    assert_eq!(ACL.decide(&req(Method::GET, "b", Identity::psk(b"x"))),
+               Decision::Forbidden);
+    assert_eq!(ACL.decide(&req(Method::GET, "b/c/d", Identity::psk(b"x"))),
+               Decision::Allow);
+  }
+
+  #[test]
+  fn methods_and_identities() {
+    let key = Some(Identity::RawPublicKey([1; 32]));
+    let other_key = Some(Identity::RawPublicKey([2; 32]));
+
+    assert_eq!(ACL.decide(&req(Method::POST, "a", None)), Decision::Unauthorized);
+    assert_eq!(ACL.decide(&req(Method::POST, "a", other_key.clone())),
+               Decision::Forbidden);
+    assert_eq!(ACL.decide(&req(Method::POST, "a", key.clone())), Decision::Allow);
+    assert_eq!(ACL.decide(&req(Method::DELETE, "a", key)), Decision::Forbidden);
+  }
+
+  #[test]
+  fn authorize_does_not_match_allowed_requests() {
+    let r = Addrd(req(Method::GET, "a", None), crate::test::dummy_addr());
+    assert_eq!(Run::<Platform, ()>::Unmatched(r.clone()).authorize(&ACL),
+               Run::Unmatched(r));
+
+    let r = Addrd(req(Method::GET, "b/c", None), crate::test::dummy_addr());
+    match Run::<Platform, ()>::Unmatched(r).authorize(&ACL) {
+      | Run::Matched(rep) => assert_eq!(rep.data().code, code::UNAUTHORIZED),
+      | _ => panic!(),
+    }
+  }
+}
@@ -0,0 +1,201 @@
+use core::hash::Hasher;
+
+use toad_hash::Blake2Hasher;
+use toad_msg::opt::known::repeat::ETAG;
+use toad_msg::MessageOptions;
+
+use super::ap::state::ApState;
+use super::ap::{Ap, ApInner, Respond};
+use crate::platform::PlatformTypes;
+use crate::req::{Method, Req};
+use crate::resp::code;
+
+/// Generate an ETag for a representation by hashing it
+///
+/// ```
+/// use toad::server::etag;
+///
+/// assert_eq!(etag::generate(b"hello"), etag::generate(b"hello"));
+/// assert_ne!(etag::generate(b"hello"), etag::generate(b"goodbye"));
+/// ```
+pub fn generate(representation: &[u8]) -> [u8; 8] {
+  let mut hasher = Blake2Hasher::new();
+  hasher.write(representation);
+  hasher.finish().to_be_bytes()
+}
+
+/// Version 2.05 CONTENT responses with an ETag, and let clients
+/// revalidate representations they have cached.
+///
+/// * If the response doesn't have an ETag (see [`Ap::etag`]),
+///   one is [generated](generate) from the payload.
+///   [Streamed](super::respond::stream) responses only contain part of the representation,
+///   so they must set their own ETag.
+/// * If the request is a GET with an ETag option matching the response's ETag,
+///   the client's cached representation is still current
+///   and the response is replaced with 2.03 VALID and no payload
+///
+/// Other responses are left alone.
+///
+/// ```
+/// use toad::net::Addrd;
+/// use toad::req::Req;
+/// use toad::resp::code;
+/// use toad::server::{etag, respond, Run};
+/// use toad::std::{dtls, PlatformTypes as Std};
+/// use toad_msg::MessageOptions;
+///
+/// let addr = "127.0.0.1:5683".parse().unwrap();
+/// let handle = |req: Req<Std<dtls::Y>>| {
+///   Run::<_, ()>::Unmatched(Addrd(req, addr)).maybe(|ap| {
+///                                               ap.bind(|_| respond::ok(b"hello".to_vec()))
+///                                                 .pipe(etag::auto)
+///                                             })
+/// };
+///
+/// let mut req = Req::get("hello");
+/// let rep = match handle(req.clone()) {
+///   | Run::Matched(rep) => rep,
+///   | _ => unreachable!(),
+/// };
+/// let tag = etag::generate(b"hello");
+/// assert_eq!(rep.data().get_first(toad_msg::repeat::ETAG).unwrap().as_bytes(),
+///            &tag);
+///
+/// req.msg_mut().add_etag(&tag).unwrap();
+/// match handle(req) {
+///   | Run::Matched(rep) => assert_eq!(rep.data().code, code::VALID),
+///   | _ => unreachable!(),
+/// };
+/// ```
+pub fn auto<S, P, T, E>(ap: Ap<S, P, T, E>) -> Ap<S, P, T, E>
+  where S: ApState,
+        P: PlatformTypes
+{
+  match ap.0 {
+    | ApInner::Respond(rep) => Ap(ApInner::Respond(with_etag(rep))),
+    | ApInner::RespondHydrated(rep, req) => {
+      let rep = validate(req.data(), with_etag(rep));
+      Ap(ApInner::RespondHydrated(rep, req))
+    },
+    | other => Ap(other),
+  }
+}
+
+fn with_etag<P>(rep: Respond<P>) -> Respond<P>
+  where P: PlatformTypes
+{
+  match rep {
+    | Respond { code,
+                payload,
+                etag: None,
+                block2: None,
+                content_format,
+                deferred, }
+      if code == code::CONTENT =>
+    {
+      let etag = generate(&payload).into_iter().collect();
+      Respond { code,
+                payload,
+                etag: Some(etag),
+                block2: None,
+                content_format,
+                deferred }
+    },
+    | rep => rep,
+  }
+}
+
+fn validate<P>(req: &Req<P>, rep: Respond<P>) -> Respond<P>
+  where P: PlatformTypes
+{
+  let cached = |etag: &P::MessageOptionBytes| {
+    req.method() == Method::GET
+    && req.msg()
+          .get(ETAG)
+          .map(|tags| tags.iter().any(|tag| tag.as_bytes() == &etag[..]))
+          .unwrap_or(false)
+  };
+
+  match rep {
+    | Respond { code,
+                etag: Some(etag),
+                deferred,
+                .. }
+      if code == code::CONTENT && cached(&etag) =>
+    {
+      Respond { code: code::VALID,
+                payload: Default::default(),
+                etag: Some(etag),
+                block2: None,
+                content_format: None,
+                deferred }
+    },
+    | rep => rep,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::net::Addrd;
+  use crate::server::{respond, Run};
+  use crate::test::Platform;
+
+  fn handle(req: Req<Platform>) -> Addrd<crate::platform::Message<Platform>> {
+    let run = Run::<Platform, ()>::Unmatched(Addrd(req, crate::test::dummy_addr()));
+    match run.maybe(|ap| {
+               ap.bind(|_| respond::ok(b"hello".iter().copied().collect()))
+                 .pipe(auto)
+             }) {
+      | Run::Matched(rep) => rep,
+      | _ => panic!(),
+    }
+  }
+
+  #[test]
+  fn etag_generated_when_missing() {
+    let rep = handle(Req::get("a"));
+    assert_eq!(rep.data().code, code::CONTENT);
+    assert_eq!(rep.data().get_first(ETAG).unwrap().as_bytes(),
+               &generate(b"hello"));
+  }
+
+  #[test]
+  fn etag_set_by_handler_is_kept() {
+    let run = Run::<Platform, ()>::Unmatched(Addrd(Req::get("a"), crate::test::dummy_addr()));
+    let rep = match run.maybe(|ap| {
+                         ap.bind(|_| {
+                             let tag = [1, 2].into_iter().collect();
+                             respond::ok(b"hello".iter().copied().collect()).etag(tag)
+                           })
+                           .pipe(auto)
+                       }) {
+      | Run::Matched(rep) => rep,
+      | _ => panic!(),
+    };
+
+    assert_eq!(rep.data().get_first(ETAG).unwrap().as_bytes(), &[1u8, 2]);
+  }
+
+  #[test]
+  fn matching_etag_responds_valid() {
+    let mut req = Req::get("a");
+    req.msg_mut().add_etag([0u8]).unwrap();
+    req.msg_mut().add_etag(&generate(b"hello")).unwrap();
+
+    let rep = handle(req);
+    assert_eq!(rep.data().code, code::VALID);
+    assert!(rep.data().payload.0.is_empty());
+    assert_eq!(rep.data().get_first(ETAG).unwrap().as_bytes(),
+               &generate(b"hello"));
+  }
+
+  #[test]
+  fn stale_etag_responds_content() {
+    let mut req = Req::get("a");
+    req.msg_mut().add_etag([0u8]).unwrap();
+
+    assert_eq!(handle(req).data().code, code::CONTENT);
+  }
+}
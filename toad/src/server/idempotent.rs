@@ -0,0 +1,380 @@
+use core::marker::PhantomData;
+
+use embedded_time::Instant;
+use no_std_net::SocketAddr;
+use tinyvec::ArrayVec;
+use toad_map::Map;
+use toad_msg::{MessageOptions, OptNumber, Token};
+use toad_stem::Stem;
+
+use super::ap::state::{Complete, Hydrated};
+use super::ap::{Ap, ApInner, Respond};
+use super::respond;
+use crate::net::Addrd;
+use crate::platform::PlatformTypes;
+use crate::req::{Method, Req};
+use crate::resp::code;
+use crate::time::{Millis, Stamped};
+
+/// The longest idempotency key accepted, in bytes
+///
+/// This is also the longest token allowed by RFC7252.
+pub const MAX_KEY_LEN: usize = 8;
+
+/// Identifies a POST in an [`Idempotent`] cache
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Key {
+  /// The client's socket address
+  pub addr: SocketAddr,
+  /// The request's token, or the value of the
+  /// [option](Idempotent::with_option) the client supplied
+  pub key: ArrayVec<[u8; MAX_KEY_LEN]>,
+}
+
+/// Deduplicate POST requests that carry a client-supplied idempotency key
+///
+/// POSTs are not idempotent; when the ACK or response to a POST is lost,
+/// the client retransmits it and the server would normally perform the action again.
+///
+/// By default, a POST is identified by the peer's address and the request's token,
+/// which stays the same when a client retries a request. Applications whose clients
+/// send a different token with each retry may instead read keys from an
+/// [option of their choosing](Idempotent::with_option).
+/// The first time a key is seen from a peer, the request is handled & the response is cached
+/// for `window`; POSTs from the same peer with the same key are answered with
+/// the cached response without being handled again.
+///
+/// Requests that aren't POSTs, POSTs without a key (an empty token, or no value for the
+/// chosen option) and responses that were never sent (the handler rejected the request
+/// or errored) are not cached.
+/// Keys longer than [`MAX_KEY_LEN`] are answered with 4.00 BAD REQUEST.
+///
+/// If the cache is full, expired responses are removed. If none have expired,
+/// the oldest response is removed.
+///
+/// `M` may be any [`Map`] from [`Key`] to [`Stamped`] responses, e.g.
+/// `BTreeMap<Key, Stamped<Clock, Respond<P>>>` or `ArrayVec<[(Key, Stamped<Clock, Respond<P>>); 16]>`.
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use std::sync::atomic::{AtomicU32, Ordering};
+///
+/// use embedded_time::duration::Milliseconds;
+/// use embedded_time::Clock as _;
+/// use toad::net::Addrd;
+/// use toad::req::Req;
+/// use toad::resp::code;
+/// use toad::server::ap::Respond;
+/// use toad::server::idempotent::{Idempotent, Key};
+/// use toad::server::{method, path, respond, Run};
+/// use toad::std::{dtls, Clock, PlatformTypes as Std};
+/// use toad::time::Stamped;
+///
+/// type Orders =
+///   Idempotent<Std<dtls::Y>, BTreeMap<Key, Stamped<Clock, Respond<Std<dtls::Y>>>>>;
+///
+/// static PLACED: AtomicU32 = AtomicU32::new(0);
+///
+/// let clock = Clock::new();
+/// let orders = Orders::new(Milliseconds(60_000));
+///
+/// let handle = |req: Req<Std<dtls::Y>>| {
+///   let now = clock.try_now().unwrap();
+///   let run = Run::<_, ()>::Unmatched(Addrd(req, "127.0.0.1:5683".parse().unwrap())).maybe(|ap| {
+///     ap.pipe(path::check::rest_equals("orders"))
+///       .pipe(orders.handle(now, |ap| {
+///                     ap.pipe(method::post).bind(|_| {
+///                                             let n = PLACED.fetch_add(1, Ordering::SeqCst) + 1;
+///                                             respond::respond(code::CREATED, n.to_string().into())
+///                                           })
+///                   }))
+///   });
+///
+///   match run {
+///     | Run::Matched(rep) => rep.unwrap(),
+///     | _ => unreachable!(),
+///   }
+/// };
+///
+/// let mut order = Req::post("orders");
+/// order.msg_mut().token = toad_msg::Token([1, 2, 3].into_iter().collect());
+///
+/// assert_eq!(handle(order.clone()).payload().0, b"1".to_vec());
+///
+/// // the response was lost & the client retried
+/// assert_eq!(handle(order).payload().0, b"1".to_vec());
+/// assert_eq!(PLACED.load(Ordering::SeqCst), 1);
+/// ```
+pub struct Idempotent<P, M>
+  where P: PlatformTypes
+{
+  option: Option<OptNumber>,
+  window: Millis,
+  responses: Stem<M>,
+  __p: PhantomData<P>,
+}
+
+impl<P, M> core::fmt::Debug for Idempotent<P, M>
+  where P: PlatformTypes,
+        M: core::fmt::Debug
+{
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("Idempotent")
+     .field("option", &self.option)
+     .field("window", &self.window)
+     .field("responses", &self.responses)
+     .finish()
+  }
+}
+
+impl<P, M> Idempotent<P, M>
+  where P: PlatformTypes,
+        M: Map<Key, Stamped<P::Clock, Respond<P>>>
+{
+  /// Create an empty cache, where responses to POSTs
+  /// keyed by their token are kept for `window`
+  pub fn new(window: Millis) -> Self {
+    Self { option: None,
+           window,
+           responses: Stem::new(M::default()),
+           __p: PhantomData }
+  }
+
+  /// Read idempotency keys from `option` instead of the request's token
+  pub fn with_option(self, option: OptNumber) -> Self {
+    Self { option: Some(option),
+           ..self }
+  }
+
+  fn expired(&self, now: Instant<P::Clock>, cached: Instant<P::Clock>) -> bool {
+    crate::time::elapsed(cached, now).map(|d| d > self.window)
+                                     .unwrap_or(false)
+  }
+
+  /// Get the key identifying `req`, if it is a POST with
+  /// an idempotency key.
+  ///
+  /// `Some(Err(()))` means the key is too long.
+  fn key(&self, req: &Addrd<Req<P>>) -> Option<Result<Key, ()>> {
+    if req.data().method() != Method::POST {
+      return None;
+    }
+
+    let msg = req.data().msg();
+    let key = match self.option {
+      | Some(option) => msg.get_first(option).map(|v| v.as_bytes()),
+      | None if msg.token.0.is_empty() => None,
+      | None => Some(&msg.token.0[..]),
+    };
+
+    key.map(|key| match key {
+         | key if key.len() > MAX_KEY_LEN => Err(()),
+         | key => Ok(Key { addr: req.addr(),
+                           key: key.iter().copied().collect() }),
+       })
+  }
+
+  /// Get the cached response to the POST identified by `key`, if it has not expired
+  pub fn get(&self, now: Instant<P::Clock>, key: &Key) -> Option<Respond<P>> {
+    self.responses.map_ref(|rs| {
+                    rs.get(key)
+                      .filter(|s| !self.expired(now, s.time()))
+                      .map(|s| s.data().clone())
+                  })
+  }
+
+  /// Cache the response to the POST identified by `key`
+  ///
+  /// If there is no room (e.g. a cache with capacity 0),
+  /// the response is not cached.
+  pub fn insert(&self, now: Instant<P::Clock>, key: Key, rep: Respond<P>) {
+    self.remove_expired(now);
+
+    let mut entry = Some((key, rep));
+    self.responses.map_mut(|rs| {
+                    let (key, rep) = entry.take().expect("map_mut only invokes its closure once");
+                    rs.remove(&key);
+
+                    if rs.is_full() {
+                      let oldest = rs.iter()
+                                     .min_by_key(|(_, s)| s.time())
+                                     .map(|(k, _)| k.clone());
+                      if let Some(oldest) = oldest {
+                        rs.remove(&oldest);
+                      }
+                    }
+
+                    if !rs.is_full() {
+                      rs.insert(key, Stamped(rep, now)).ok();
+                    }
+                  })
+  }
+
+  /// Remove all responses that were cached longer than `window` ago
+  pub fn remove_expired(&self, now: Instant<P::Clock>) {
+    self.responses.map_mut(|rs| loop {
+                    let expired = rs.iter()
+                                    .find(|(_, s)| self.expired(now, s.time()))
+                                    .map(|(k, _)| k.clone());
+
+                    match expired {
+                      | Some(key) => {
+                        rs.remove(&key);
+                      },
+                      | None => break,
+                    }
+                  })
+  }
+
+  /// Number of cached responses (including expired responses that have not been removed yet)
+  pub fn len(&self) -> usize {
+    self.responses.map_ref(|rs| rs.len())
+  }
+
+  /// Is the cache empty?
+  pub fn is_empty(&self) -> bool {
+    self.responses.map_ref(|rs| rs.is_empty())
+  }
+
+  /// Handle requests with `handler`, answering POSTs whose key
+  /// was seen within the `window` with the response that was sent the first time.
+  pub fn handle<'a, X, E, F>(&'a self,
+                             now: Instant<P::Clock>,
+                             handler: F)
+                             -> impl FnOnce(Ap<Hydrated, P, X, E>) -> Ap<Complete, P, (), E> + 'a
+    where E: core::fmt::Debug,
+          F: FnOnce(Ap<Hydrated, P, X, E>) -> Ap<Complete, P, (), E> + 'a
+  {
+    move |ap| {
+      let key = match &ap.0 {
+        | ApInner::OkHydrated(_, hy) => self.key(&hy.req),
+        | _ => None,
+      };
+
+      match key {
+        | None => handler(ap),
+        | Some(Err(())) => ap.bind(|_| respond::respond(code::BAD_REQUEST, Default::default())),
+        | Some(Ok(key)) => match self.get(now, &key) {
+          | Some(rep) => ap.bind(|_| Ap::respond(rep)),
+          | None => {
+            let ap = handler(ap);
+            match &ap.0 {
+              | ApInner::Respond(rep) | ApInner::RespondHydrated(rep, _) => {
+                self.insert(now, key, rep.clone())
+              },
+              | _ => (),
+            }
+            ap
+          },
+        },
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use core::cell::Cell;
+
+  use std_alloc::collections::BTreeMap;
+  use toad_msg::OptValue;
+
+  use super::*;
+  use crate::server::ap::Hydrate;
+  use crate::test::{dummy_addr, dummy_addr_2, ClockMock, Platform};
+
+  type Cache = Idempotent<Platform, BTreeMap<Key, Stamped<ClockMock, Respond<Platform>>>>;
+
+  fn now(ms: u64) -> Instant<ClockMock> {
+    ClockMock::instant(ms * 1000)
+  }
+
+  const IDEMPOTENCY_KEY: OptNumber = OptNumber(65100);
+
+  fn post(token: &[u8]) -> crate::test::Req {
+    let mut req = crate::test::Req::post("orders");
+    req.msg_mut().token = Token(token.iter().copied().collect());
+    req
+  }
+
+  fn post_keyed(key: &[u8]) -> crate::test::Req {
+    let mut req = crate::test::Req::post("orders");
+    req.msg_mut()
+       .set(IDEMPOTENCY_KEY, OptValue(key.iter().copied().collect()))
+       .unwrap();
+    req
+  }
+
+  fn handle(cache: &Cache,
+            handled: &Cell<u8>,
+            ms: u64,
+            addr: SocketAddr,
+            req: crate::test::Req)
+            -> Respond<Platform> {
+    Ap::<_, Platform, (), ()>::ok_hydrated((), Hydrate::from_request(Addrd(req, addr)))
+      .pipe(cache.handle(now(ms), |ap| {
+                   ap.bind(|_| {
+                       handled.set(handled.get() + 1);
+                       respond::respond(code::CREATED, Default::default())
+                     })
+                 }))
+      .try_unwrap_respond()
+      .unwrap()
+  }
+
+  #[test]
+  fn retried_post_is_handled_once() {
+    let cache = Cache::new(Millis::new(100));
+    let handled = Cell::new(0);
+
+    handle(&cache, &handled, 0, dummy_addr(), post(&[1]));
+    let rep = handle(&cache, &handled, 50, dummy_addr(), post(&[1]));
+
+    assert_eq!(rep.code, code::CREATED);
+    assert_eq!(handled.get(), 1);
+    assert_eq!(cache.len(), 1);
+  }
+
+  #[test]
+  fn posts_are_handled_again_when_key_peer_or_window_differ() {
+    let cache = Cache::new(Millis::new(100));
+    let handled = Cell::new(0);
+
+    handle(&cache, &handled, 0, dummy_addr(), post(&[1]));
+    handle(&cache, &handled, 0, dummy_addr(), post(&[2]));
+    handle(&cache, &handled, 0, dummy_addr_2(), post(&[1]));
+    handle(&cache, &handled, 101, dummy_addr(), post(&[1]));
+    assert_eq!(handled.get(), 4);
+
+    handle(&cache, &handled, 0, dummy_addr(), crate::test::Req::post("orders"));
+    handle(&cache, &handled, 0, dummy_addr(), crate::test::Req::post("orders"));
+    assert_eq!(handled.get(), 6);
+  }
+
+  #[test]
+  fn post_keyed_with_option_ignores_token() {
+    let cache = Cache::new(Millis::new(100)).with_option(IDEMPOTENCY_KEY);
+    let handled = Cell::new(0);
+
+    let mut retry = post_keyed(&[1]);
+    retry.msg_mut().token = Token([2].into_iter().collect());
+
+    handle(&cache, &handled, 0, dummy_addr(), post_keyed(&[1]));
+    handle(&cache, &handled, 50, dummy_addr(), retry);
+    assert_eq!(handled.get(), 1);
+
+    handle(&cache, &handled, 50, dummy_addr(), post(&[1]));
+    handle(&cache, &handled, 50, dummy_addr(), post(&[1]));
+    assert_eq!(handled.get(), 3);
+  }
+
+  #[test]
+  fn long_key_is_bad_request() {
+    let cache = Cache::new(Millis::new(100)).with_option(IDEMPOTENCY_KEY);
+    let handled = Cell::new(0);
+
+    let rep = handle(&cache, &handled, 0, dummy_addr(), post_keyed(&[0; MAX_KEY_LEN + 1]));
+    assert_eq!(rep.code, code::BAD_REQUEST);
+    assert_eq!(handled.get(), 0);
+  }
+}
@@ -1,15 +1,19 @@
 use core::fmt::Write;
 
 pub use ap::Ap;
-use toad_msg::MessageOptions;
+use embedded_time::duration::Milliseconds;
+use embedded_time::Instant;
+use rand::{Rng, SeedableRng};
+use toad_msg::{MessageOptions, Token};
 
 use self::ap::state::{Complete, Hydrated};
 use self::ap::{ApInner, Hydrate, Respond};
 use crate::net::{Addrd, Socket};
-use crate::platform::{Message, Platform, PlatformTypes};
+use crate::platform::{Message, Platform, PlatformError, PlatformTypes};
 use crate::req::Req;
 use crate::resp::Resp;
 use crate::step::Step;
+use crate::time::{self, Clock, Millis};
 use crate::todo::String;
 
 /// Server flow applicative
@@ -24,7 +28,11 @@ pub mod ap;
 ///       * [`next_equals()`](path::segment::check::next_is) - assert that the next route segment equals a string
 ///    * [`param`](path::segment::param)
 ///       * [`u32()`](path::segment::param::u32) - consume the next route segment and parse as u32, rejecting the request if parsing fails.
+///       * [`parse()`](path::segment::param::parse) - consume the next route segment and parse with [`FromStr`](core::str::FromStr), rejecting the request if parsing fails.
+///    * [`wildcard()`](path::segment::wildcard) - consume the next route segment without inspecting it, rejecting the request if there are no segments left.
 /// * [`rest()`](path::rest) - extract the full route, skipping consumed segments & combine it with data in the `Ap`
+/// * [`param`](path::param)
+///    * [`rest()`](path::param::rest) - consume the rest of the route, skipping consumed segments, and capture it alongside the data in the `Ap`
 /// * [`check`](path::check)
 ///    * [`rest_is()`](path::check::rest_is) - assert that the rest of the route matches a predicate
 ///    * [`rest_equals()`](path::check::rest_equals) - assert that the rest of the route matches a string
@@ -37,6 +45,62 @@ pub mod method;
 /// Respond to requests
 pub mod respond;
 
+/// Request option filters & extractors
+///
+/// * [`content_format()`](option::content_format) - reject requests whose Content-Format doesn't match
+/// * [`query_param()`](option::query_param) - find a query parameter and parse it with [`FromStr`](core::str::FromStr), rejecting the request if it's missing or parsing fails.
+pub mod option;
+
+/// Generate ETags for responses, and respond 2.03 VALID
+/// to clients whose cached representation is current
+///
+/// * [`auto()`](etag::auto) - add ETags to 2.05 CONTENT responses that don't have one & validate the request's ETags
+/// * [`generate()`](etag::generate) - hash a representation to get its ETag
+pub mod etag;
+
+/// Ready-made resources serving constant or filesystem content
+///
+/// * [`StaticBytes`](resources::StaticBytes) - constant bytes, `application/octet-stream` by default
+/// * [`StaticStr`](resources::StaticStr) - constant text, `text/plain` by default
+/// * [`Dir`](resources::Dir) - files in a directory, with Content-Format [inferred](resources::infer_format) from their extensions (requires `std`)
+///
+/// Each responds to GET requests with the representation's Content-Format
+/// and an ETag, sending large representations block-by-block (Block2, RFC 7959).
+///
+/// * [`DeviceInfo`](resources::DeviceInfo) - version, uptime & message counts from the [`Metrics`](crate::step::metrics::Metrics) step, as JSON or CBOR
+/// * [`Logs`](resources::Logs) - recent runtime log entries kept in a ring buffer, for Observing devices without a serial console
+/// * [`Versioned`](resources::Versioned) - a value updated with conditional PUTs (If-Match / If-None-Match), answering conflicts with 4.12 PRECONDITION FAILED
+pub mod resources;
+
+/// Per-peer session state
+pub mod peer;
+pub use peer::{PeerKey, PeerStore};
+
+/// Deduplicate retried POSTs
+///
+/// * [`Idempotent`](idempotent::Idempotent) - answer POSTs carrying an idempotency key (the request's token, by default) that was already seen with the cached response, rather than performing the action again
+pub mod idempotent;
+
+/// Authorization of requests based on the peer's [`Identity`](crate::net::Identity)
+///
+/// * [`Acl`](auth::Acl) - declarative table of [`Rule`](auth::Rule)s granting identities access to methods on paths
+/// * [`Run::authorize`] - respond with 4.01 / 4.03 to requests not allowed by an `Acl`, before any routes are tried
+/// * [`check()`](auth::check) - the same as `Run::authorize`, as a filter on a single route
+pub mod auth;
+
+/// Asynchronous request handlers
+///
+/// * [`Ap::bind_async`] - respond to a request with a future, without blocking the server
+/// * [`Spawner`](spawn::Spawner) - runs the futures on a tokio runtime & limits how many may be in flight
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+pub mod spawn;
+
+/// Routes registered at runtime
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod route_table;
+
 /// [`Run`] errors
 #[derive(Clone, Debug, Copy, PartialEq, Eq)]
 pub enum Error<E> {
@@ -77,6 +141,9 @@ pub enum Run<P, E>
   Unmatched(Addrd<Req<P>>),
   /// Request has a response
   Matched(Addrd<Message<P>>),
+  /// Request was matched, and will be responded to
+  /// once an asynchronous handler completes
+  Deferred(Addrd<Req<P>>),
   /// An Error occurred
   Error(Error<E>),
 }
@@ -89,6 +156,7 @@ impl<P, E> PartialEq for Run<P, E>
     match (self, other) {
       | (Self::Unmatched(a), Self::Unmatched(b)) => a == b,
       | (Self::Matched(a), Self::Matched(b)) => a == b,
+      | (Self::Deferred(a), Self::Deferred(b)) => a == b,
       | (Self::Error(a), Self::Error(b)) => a == b,
       | _ => false,
     }
@@ -105,9 +173,16 @@ impl<P, E> Run<P, E>
       | ApInner::Err(e) => Self::Error(Error::Other(e)),
       | ApInner::RespondHydrated(Respond { code,
                                            payload,
-                                           etag, },
+                                           etag,
+                                           block2,
+                                           content_format,
+                                           deferred, },
                                  Addrd(req, addr)) => {
-        let mut resp = Resp::non(&req);
+        let mut resp = if deferred {
+          Resp::con(&req)
+        } else {
+          Resp::non(&req)
+        };
         resp.set_code(code);
         resp.set_payload(payload);
 
@@ -115,9 +190,20 @@ impl<P, E> Run<P, E>
           resp.msg_mut().add_etag(etag.as_ref()).ok();
         }
 
+        if let Some(block) = block2 {
+          resp.msg_mut()
+              .set_block2(block.size(), block.num(), block.more())
+              .ok();
+        }
+
+        if let Some(format) = content_format {
+          resp.msg_mut().set_content_format(format).ok();
+        }
+
         Self::Matched(Addrd(resp.into(), addr))
       },
       | ApInner::RejectHydrated(req) => Self::Unmatched(req),
+      | ApInner::Deferred(req) => Self::Deferred(req),
       | a @ ApInner::Respond { .. }
       | a @ ApInner::Reject
       | a @ ApInner::Phantom(_)
@@ -134,10 +220,38 @@ impl<P, E> Run<P, E>
   {
     match self {
       | Run::Matched(m) => Run::Matched(m),
+      | Run::Deferred(req) => Run::Deferred(req),
       | Run::Error(e) => Run::Error(e),
       | Run::Unmatched(req) => Self::handle(f(Ap::ok_hydrated((), Hydrate::from_request(req)))),
     }
   }
+
+  /// Try the routes in a [`RouteTable`](route_table::RouteTable)
+  ///
+  /// This may be mixed with static routes declared with [`Run::maybe`].
+  #[cfg(feature = "alloc")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+  pub fn routes(self, table: &mut route_table::RouteTable<P, E>) -> Self {
+    table.handle(self)
+  }
+}
+
+/// Maximum number of responses to multicast requests that
+/// [`BlockingServer`]s will hold while they wait out their leisure.
+///
+/// If more responses than this are waiting, the next one is sent immediately.
+pub const MAX_LEISURELY_RESPONSES: usize = 8;
+
+/// Pick a random point within `leisure` after `now` to respond
+/// to a multicast request ([RFC7252 section 8.2](https://datatracker.ietf.org/doc/html/rfc7252#section-8.2))
+fn leisure_end<C: Clock>(now: Instant<C>, leisure: Millis, token: Token) -> Instant<C> {
+  let seed = token.0
+                  .iter()
+                  .fold(now.duration_since_epoch().integer(), |seed, b| {
+                    seed.rotate_left(8) ^ u64::from(*b)
+                  });
+  let delay = rand_chacha::ChaCha8Rng::seed_from_u64(seed).gen_range(0..=leisure.0);
+  time::after(now, Milliseconds(delay))
 }
 
 /// Newtype wrapper of an initialization function
@@ -159,14 +273,41 @@ impl Init<fn()> {
 ///
 /// Servers are thread-safe, meaning that [`run`](BlockingServer::run) may
 /// be invoked concurrently by multiple worker threads.
+///
+/// # Multicast
+/// Responses to requests that were sent to a multicast group
+/// (see [`Meta::is_multicast`](crate::req::Meta::is_multicast)) are handled
+/// as recommended by [RFC7252 section 8.2](https://datatracker.ietf.org/doc/html/rfc7252#section-8.2):
+///  * 4.xx and 5.xx responses are dropped by the [`Multicast`](crate::step::multicast) step of the runtime, unless [`Multicast.suppress_error_responses`](crate::config::Multicast#structfield.suppress_error_responses) is `false`
+///  * responses are sent at a random point within [`Msg.multicast_response_leisure`](crate::config::Msg#structfield.multicast_response_leisure)
 pub trait BlockingServer<S>: Sized + Platform<S>
   where S: Step<Self::Types, PollReq = Addrd<Req<Self::Types>>, PollResp = Addrd<Resp<Self::Types>>>
 {
   #[allow(missing_docs)]
-  fn run<I, R>(&self, init: Init<I>, mut handle_request: R) -> Result<(), Error<Self::Error>>
+  fn run<I, R>(&self, init: Init<I>, handle_request: R) -> Result<(), Error<Self::Error>>
+    where I: FnMut(),
+          R: FnMut(Run<Self::Types, Self::Error>) -> Run<Self::Types, Self::Error>
+  {
+    self.run_deferred(init, || None, handle_request)
+  }
+
+  /// [`run`](BlockingServer::run), and while waiting for requests
+  /// send the responses yielded by `poll_deferred`.
+  ///
+  /// Requests that `handle_request` leaves [`Run::Deferred`] are
+  /// expected to eventually yield a response from `poll_deferred`.
+  /// (e.g. `spawn::Spawner::poll`, requires the `tokio` feature)
+  fn run_deferred<I, D, R>(&self,
+                           init: Init<I>,
+                           mut poll_deferred: D,
+                           mut handle_request: R)
+                           -> Result<(), Error<Self::Error>>
     where I: FnMut(),
+          D: FnMut() -> Option<Addrd<Message<Self::Types>>>,
           R: FnMut(Run<Self::Types, Self::Error>) -> Run<Self::Types, Self::Error>
   {
+    use embedded_time::Clock as _;
+
     let mut startup_msg = String::<1000>::default();
     write!(
            &mut startup_msg,
@@ -192,10 +333,62 @@ pub trait BlockingServer<S>: Sized + Platform<S>
     self.log(log::Level::Info, startup_msg)
         .map_err(Error::Other)?;
 
+    if let Some(ttl) = self.config().multicast.ttl {
+      self.socket()
+          .set_ttl(ttl)
+          .map_err(Self::Error::socket)
+          .map_err(Error::Other)?;
+    }
+
+    if self.config().multicast.join_all_coap_nodes {
+      crate::multicast::all_coap_nodes_groups(self.socket().local_addr())
+        .iter()
+        .try_for_each(|group| {
+          self.socket()
+              .join_multicast(*group)
+              .map_err(Self::Error::socket)
+              .map_err(Error::Other)
+        })?;
+    }
+
     init.0.map(|mut f| f());
 
+    let mut leisurely: [Option<(Instant<<Self::Types as PlatformTypes>::Clock>,
+                                Addrd<Message<Self::Types>>)>;
+                        MAX_LEISURELY_RESPONSES] = Default::default();
+
     loop {
-      let req = nb::block!(self.poll_req()).map_err(Error::Other)?;
+      let req = loop {
+        while let Some(rep) = poll_deferred() {
+          nb::block!(self.send_msg(rep.clone())).map_err(Error::Other)?;
+        }
+
+        if leisurely.iter().any(Option::is_some) {
+          let now = self.clock()
+                        .try_now()
+                        .map_err(Self::Error::clock)
+                        .map_err(Error::Other)?;
+
+          for slot in leisurely.iter_mut() {
+            if !matches!(slot, Some((at, _)) if *at <= now) {
+              continue;
+            }
+
+            if let Some((_, rep)) = slot.take() {
+              nb::block!(self.send_msg(rep.clone())).map_err(Error::Other)?;
+            }
+          }
+        }
+
+        match self.poll_req() {
+          | Ok(req) => break req,
+          | Err(nb::Error::WouldBlock) => continue,
+          | Err(nb::Error::Other(e)) => return Err(Error::Other(e)),
+        }
+      };
+
+      let multicast = req.data().meta().map(|m| m.is_multicast()).unwrap_or(false);
+
       match handle_request(Run::Unmatched(req)) {
         | Run::Unmatched(req) => {
           let mut msg = String::<1000>::default();
@@ -216,8 +409,32 @@ Do you need a fallback?
 )"#
           ).ok();
         },
+        | Run::Matched(rep) if multicast => {
+          let now = self.clock()
+                        .try_now()
+                        .map_err(Self::Error::clock)
+                        .map_err(Error::Other)?;
+          let at = leisure_end(now,
+                               self.config().msg.multicast_response_leisure,
+                               rep.data().token);
+
+          match leisurely.iter_mut().find(|slot| slot.is_none()) {
+            | Some(slot) => *slot = Some((at, rep)),
+            | None => {
+              let mut msg = String::<1000>::default();
+              write!(&mut msg,
+                     "{} responses to multicast requests are already waiting, sending response to {:?} without leisure",
+                     MAX_LEISURELY_RESPONSES,
+                     rep.addr()).ok();
+              self.log(log::Level::Warn, msg).map_err(Error::Other)?;
+
+              nb::block!(self.send_msg(rep.clone())).map_err(Error::Other)?;
+            },
+          }
+        },
         | Run::Matched(rep) => nb::block!(self.send_msg(rep.clone())).map_err(Error::Other)
                                                                      .map(|_| ())?,
+        | Run::Deferred(_) => (),
         | Run::Error(e) => break Err(e),
       }
     }
@@ -232,6 +449,69 @@ impl<S, T> BlockingServer<S> for T
 
 #[cfg(test)]
 mod tests {
+  use embedded_time::duration::Milliseconds;
+  use toad_msg::Token;
+
+  use super::leisure_end;
+  use crate::test::ClockMock;
+
+  #[test]
+  fn leisure_end_is_within_leisure() {
+    let now = ClockMock::instant(1_000_000);
+    let leisure = Milliseconds(5000u64);
+
+    (0..32u8).for_each(|n| {
+               let token = Token(core::iter::once(n).collect());
+               let end = leisure_end(now, leisure, token);
+               assert!(end >= now);
+               assert!(end <= now + leisure);
+               assert_eq!(end, leisure_end(now, leisure, token));
+             });
+
+    assert_eq!(leisure_end(now, Milliseconds(0u64), Token(Default::default())),
+               now);
+  }
+
+  #[test]
+  fn deferred_response_is_con_with_new_id() {
+    use toad_msg::{Id, Type};
+
+    use crate::net::Addrd;
+    use crate::req::Req;
+    use crate::server::{respond, Run};
+    use crate::test::Platform as P;
+
+    let mut req = Req::<P>::get("slow");
+    req.msg_mut().id = Id(12);
+    req.msg_mut().token = Token(core::iter::once(3).collect());
+
+    let run = |deferred: bool| {
+      Run::<P, ()>::Unmatched(Addrd(req.clone(), crate::test::x.x.x.x(80))).maybe(|ap| {
+        let ap = ap.bind(|_| respond::ok("done".into()));
+        if deferred {
+          ap.deferred()
+        } else {
+          ap
+        }
+      })
+    };
+
+    match run(true) {
+      | Run::Matched(rep) => {
+        assert_eq!(rep.data().ty, Type::Con);
+        assert_eq!(rep.data().id, Id(0));
+        assert_eq!(rep.data().token, req.msg().token);
+        assert_eq!(rep.data().payload.0, b"done".to_vec());
+      },
+      | other => panic!("{other:?}"),
+    }
+
+    match run(false) {
+      | Run::Matched(rep) => assert_eq!(rep.data().ty, Type::Non),
+      | other => panic!("{other:?}"),
+    }
+  }
+
   mod compiles {
     use crate::server::{path, respond, Error, Run};
     use crate::std::{dtls, PlatformTypes as Std};
@@ -0,0 +1,175 @@
+use core::str::FromStr;
+
+use toad_msg::opt::known::no_repeat::CONTENT_FORMAT;
+use toad_msg::opt::known::repeat::QUERY;
+use toad_msg::MessageOptions;
+
+use super::ap::state::Hydrated;
+use super::ap::{Ap, Hydrate};
+use crate::platform::PlatformTypes;
+use crate::req::Req;
+use crate::ContentFormat;
+
+fn query_value<'a, P>(req: &'a Req<P>, name: &str) -> Option<&'a str>
+  where P: PlatformTypes
+{
+  req.msg()
+     .get(QUERY)?
+     .iter()
+     .filter_map(|q| core::str::from_utf8(q.as_bytes()).ok())
+     .find_map(|q| match q.split_once('=') {
+       | Some((k, v)) if k == name => Some(v),
+       | None if q == name => Some(""),
+       | _ => None,
+     })
+}
+
+/// Reject the request if its Content-Format option is not `format`
+///
+/// ```
+/// use toad::net::Addrd;
+/// use toad::req::Req;
+/// use toad::server::ap::{Ap, Hydrate};
+/// use toad::server::option;
+/// use toad::std::{dtls, PlatformTypes as Std};
+/// use toad::ContentFormat;
+/// use toad_msg::MessageOptions;
+///
+/// let addr = "192.168.0.1:8080".parse().unwrap();
+/// let ap = |req: Req<Std<dtls::Y>>| -> Ap<_, Std<dtls::Y>, (), ()> {
+///   Ap::ok_hydrated((), Hydrate::from_request(Addrd(req, addr)))
+/// };
+///
+/// let mut json = Req::post("users");
+/// json.msg_mut()
+///     .set_content_format(toad_msg::ContentFormat::Json)
+///     .unwrap();
+///
+/// assert!(ap(json).pipe(option::content_format(ContentFormat::Json))
+///                 .is_ok());
+/// assert!(ap(Req::post("users")).pipe(option::content_format(ContentFormat::Json))
+///                               .is_rejected());
+/// ```
+pub fn content_format<P, T, E>(format: ContentFormat)
+                               -> impl Fn(Ap<Hydrated, P, T, E>) -> Ap<Hydrated, P, T, E>
+  where P: PlatformTypes,
+        E: core::fmt::Debug
+{
+  move |ap| match ap.try_unwrap_ok_hydrated() {
+    | Ok((t, h)) => {
+      // Content-Format is a uint of 0-2 bytes; peers use the shortest encoding
+      let actual = h.req
+                    .data()
+                    .msg()
+                    .get_first(CONTENT_FORMAT)
+                    .map(|f| f.as_bytes().iter().fold(0u16, |n, b| (n << 8) | u16::from(*b)));
+
+      if actual == Some(u16::from(&format)) {
+        Ap::ok_hydrated(t, h)
+      } else {
+        let Hydrate { req, .. } = h;
+        Ap::reject_hydrated(req).pretend()
+      }
+    },
+    | Err(e) => e,
+  }
+}
+
+/// Find the query parameter `name` (`?name=value`) and parse its value
+/// with [`FromStr`], capturing it alongside the data in the `Ap`.
+///
+/// A parameter without a value (`?name`) is parsed from the empty string.
+///
+/// If the parameter is missing or fails to be parsed, the request will be rejected.
+///
+/// ```
+/// use toad::net::Addrd;
+/// use toad::req::Req;
+/// use toad::server::ap::{Ap, Hydrate};
+/// use toad::server::option;
+/// use toad::std::{dtls, PlatformTypes as Std};
+/// use toad_msg::MessageOptions;
+///
+/// let addr = "192.168.0.1:8080".parse().unwrap();
+/// let mut req = Req::<Std<dtls::Y>>::get("users");
+/// req.msg_mut().add_query("page=3").unwrap();
+/// req.msg_mut().add_query("verbose").unwrap();
+///
+/// let ap: Ap<_, Std<dtls::Y>, (), ()> =
+///   Ap::ok_hydrated((), Hydrate::from_request(Addrd(req, addr)));
+///
+/// let ap = ap.pipe(option::query_param::<u32, _, _, _>("page"));
+/// assert_eq!(ap.clone().try_unwrap_ok().unwrap(), ((), 3));
+///
+/// assert!(ap.clone()
+///           .pipe(option::query_param::<String, _, _, _>("verbose"))
+///           .is_ok());
+/// assert!(ap.pipe(option::query_param::<u32, _, _, _>("limit"))
+///           .is_rejected());
+/// ```
+pub fn query_param<V, P, T, E>(
+  name: &str)
+  -> impl FnOnce(Ap<Hydrated, P, T, E>) -> Ap<Hydrated, P, (T, V), E> + '_
+  where P: PlatformTypes,
+        E: core::fmt::Debug,
+        V: FromStr
+{
+  move |ap| {
+    ap.bind_hydrated(|t, req| match query_value(req.data(), name).map(V::from_str) {
+        | Some(Ok(v)) => Ap::ok((t, v)),
+        | _ => Ap::reject().pretend_unhydrated(),
+      })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::net::Addrd;
+  use crate::test::Platform;
+
+  fn req(queries: &[&str]) -> Req<Platform> {
+    let mut req = Req::get("a");
+    queries.iter()
+           .for_each(|q| req.msg_mut().add_query(q).unwrap());
+    req
+  }
+
+  #[test]
+  fn query_value_finds_first_matching_param() {
+    let req = req(&["a=1", "b", "a=2", "c=x=y"]);
+    assert_eq!(query_value(&req, "a"), Some("1"));
+    assert_eq!(query_value(&req, "b"), Some(""));
+    assert_eq!(query_value(&req, "c"), Some("x=y"));
+    assert_eq!(query_value(&req, "d"), None);
+  }
+
+  #[test]
+  fn content_format_accepts_minimal_encodings() {
+    let ap = |value: Vec<u8>| {
+      let mut req = Req::<Platform>::post("a");
+      req.msg_mut()
+         .set(CONTENT_FORMAT, toad_msg::OptValue(value))
+         .unwrap();
+      Ap::<_, Platform, (), ()>::ok_hydrated((),
+                                             Hydrate::from_request(Addrd(req,
+                                                                         crate::test::dummy_addr())))
+    };
+
+    assert!(ap(vec![50]).pipe(content_format(ContentFormat::Json))
+                        .is_ok());
+    assert!(ap(vec![0, 50]).pipe(content_format(ContentFormat::Json))
+                           .is_ok());
+    assert!(ap(vec![]).pipe(content_format(ContentFormat::Text))
+                      .is_ok());
+    assert!(ap(vec![50]).pipe(content_format(ContentFormat::Text))
+                        .is_rejected());
+  }
+
+  #[test]
+  fn query_param_rejects_unparseable() {
+    let req = Addrd(req(&["n=abc"]), crate::test::dummy_addr());
+    let ap = Ap::<_, Platform, (), ()>::ok_hydrated((), Hydrate::from_request(req));
+    assert!(ap.pipe(query_param::<u32, _, _, _>("n")).is_rejected());
+  }
+}
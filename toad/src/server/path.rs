@@ -118,8 +118,47 @@ pub mod segment {
     }
   }
 
+  /// Consume the next path segment, regardless of its value.
+  ///
+  /// If there are no more segments, the request will be rejected.
+  ///
+  /// ```
+  /// use toad::net::Addrd;
+  /// use toad::req::Req;
+  /// use toad::server::ap::{state, Ap, Hydrate};
+  /// use toad::server::path;
+  /// use toad::std::{dtls, PlatformTypes as Std};
+  ///
+  /// # let addr = || {
+  /// #   use no_std_net::*;
+  /// #   SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 1), 8080))
+  /// # };
+  /// let addr = addr(); // 192.168.0.1:8080
+  ///
+  /// // matches `devices/*/status`
+  /// let status = |ap: Ap<_, Std<dtls::Y>, (), ()>| {
+  ///   ap.pipe(path::segment::check::next_equals("devices"))
+  ///     .pipe(path::segment::wildcard)
+  ///     .pipe(path::check::rest_equals("status"))
+  /// };
+  ///
+  /// let req = Req::<Std<dtls::Y>>::get("devices/abc/status");
+  /// assert!(status(Ap::ok_hydrated((), Hydrate::from_request(Addrd(req, addr)))).is_ok());
+  ///
+  /// let req = Req::<Std<dtls::Y>>::get("devices/status");
+  /// assert!(status(Ap::ok_hydrated((), Hydrate::from_request(Addrd(req, addr)))).is_rejected());
+  /// ```
+  pub fn wildcard<P, T, E>(ap: Ap<Hydrated, P, T, E>) -> Ap<Hydrated, P, T, E>
+    where P: PlatformTypes,
+          E: core::fmt::Debug
+  {
+    check::next_is(|_| true)(ap)
+  }
+
   /// Route parameter extraction
   pub mod param {
+    use core::str::FromStr;
+
     use super::*;
 
     /// Consume the next path segment as an integer
@@ -169,6 +208,49 @@ pub mod segment {
          .map(|u| (t, u))
       })(ap)
     }
+
+    /// Consume the next path segment as any type implementing [`FromStr`]
+    ///
+    /// If the segment fails to be parsed, the request will be rejected.
+    ///
+    /// ```
+    /// use toad::net::Addrd;
+    /// use toad::req::Req;
+    /// use toad::server::ap::{state, Ap, Hydrate};
+    /// use toad::server::path;
+    /// use toad::std::{dtls, PlatformTypes as Std};
+    ///
+    /// # let addr = || {
+    /// #   use no_std_net::*;
+    /// #   SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 1), 8080))
+    /// # };
+    /// let addr = addr(); // 192.168.0.1:8080
+    ///
+    /// let req = Req::<Std<dtls::Y>>::get("-12/true");
+    /// let ap: Ap<_, Std<dtls::Y>, (), ()> =
+    ///   Ap::ok_hydrated((), Hydrate::from_request(Addrd(req, addr)));
+    ///
+    /// assert_eq!(ap.pipe(path::segment::param::parse::<i8, _, _, _>)
+    ///              .pipe(path::segment::param::parse::<bool, _, _, _>)
+    ///              .try_unwrap_ok()
+    ///              .unwrap(),
+    ///            (((), -12), true));
+    /// ```
+    pub fn parse<V, P, T, E>(ap: Ap<Hydrated, P, T, E>) -> Ap<Hydrated, P, (T, V), E>
+      where P: PlatformTypes,
+            E: core::fmt::Debug,
+            V: FromStr,
+            V::Err: core::fmt::Debug
+    {
+      next(|t, s| {
+        s.map(Ap::ok)
+         .unwrap_or_else(|| Ap::reject().pretend_unhydrated())
+         .map(V::from_str)
+         .bind(Ap::from_result)
+         .reject_on_err()
+         .map(|v| (t, v))
+      })(ap)
+    }
   }
 }
 
@@ -207,6 +289,50 @@ pub fn rest<T, SOut, R, F, P, E>(
   }
 }
 
+/// Route parameter extraction spanning multiple segments
+pub mod param {
+  use super::*;
+
+  /// Consume the rest of the request path, skipping any
+  /// consumed [`segment`]s, and capture it alongside
+  /// the data in the `Ap`.
+  ///
+  /// ```
+  /// use toad::net::Addrd;
+  /// use toad::req::Req;
+  /// use toad::server::ap::{state, Ap, Hydrate};
+  /// use toad::server::path;
+  /// use toad::std::{dtls, PlatformTypes as Std};
+  ///
+  /// # let addr = || {
+  /// #   use no_std_net::*;
+  /// #   SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 1), 8080))
+  /// # };
+  /// let addr = addr(); // 192.168.0.1:8080
+  ///
+  /// // devices/{id}/files/{path...}
+  /// let req = Req::<Std<dtls::Y>>::get("devices/12/files/etc/hosts");
+  /// let ap: Ap<_, Std<dtls::Y>, (), ()> =
+  ///   Ap::ok_hydrated((), Hydrate::from_request(Addrd(req, addr)));
+  ///
+  /// let (((), id), file) = ap.pipe(path::segment::check::next_equals("devices"))
+  ///                          .pipe(path::segment::param::u32)
+  ///                          .pipe(path::segment::check::next_equals("files"))
+  ///                          .pipe(path::param::rest)
+  ///                          .try_unwrap_ok()
+  ///                          .unwrap();
+  ///
+  /// assert_eq!(id, 12);
+  /// assert_eq!(file.as_str(), "etc/hosts");
+  /// ```
+  pub fn rest<P, T, E>(ap: Ap<Hydrated, P, T, E>) -> Ap<Hydrated, P, (T, String<1000>), E>
+    where P: PlatformTypes,
+          E: core::fmt::Debug
+  {
+    super::rest(|t, s| Ap::ok((t, String::from(s))))(ap)
+  }
+}
+
 /// Helper functions for adding filters against whole paths
 pub mod check {
   use super::*;
@@ -369,4 +495,65 @@ mod tests {
 
     assert_eq!(ap.clone().try_unwrap_ok(), Ok(123));
   }
+
+  #[test]
+  fn wildcard() {
+    let req = |p| {
+      let mut r = crate::test::msg!(CON GET x.x.x.x:1111).map(Req::from);
+      r.as_mut().msg_mut().set_path(p).unwrap();
+      r
+    };
+
+    let check = |p| {
+      Ap::<_, (), ()>::ok_hydrated((), Hydrate::from_request(req(p)))
+        .pipe(path::segment::check::next_equals("users"))
+        .pipe(path::segment::wildcard)
+        .pipe(path::check::rest_equals("name"))
+    };
+
+    assert!(check("users/123/name").is_ok());
+    assert!(check("users/abc/name").is_ok());
+    assert!(check("users/name").is_rejected());
+    assert!(check("users").is_rejected());
+  }
+
+  #[test]
+  fn segment_param_parse() {
+    let req = |p| {
+      let mut r = crate::test::msg!(CON GET x.x.x.x:1111).map(Req::from);
+      r.as_mut().msg_mut().set_path(p).unwrap();
+      r
+    };
+
+    let check = |p| {
+      Ap::<_, (), ()>::ok_hydrated((), Hydrate::from_request(req(p)))
+        .pipe(path::segment::param::parse::<u64, _, _, _>)
+        .map(|(_, n)| n)
+    };
+
+    assert_eq!(check("18446744073709551615").try_unwrap_ok(), Ok(u64::MAX));
+    assert!(check("-1").is_rejected());
+    assert!(check("").is_rejected());
+  }
+
+  #[test]
+  fn param_rest() {
+    let req = |p| {
+      let mut r = crate::test::msg!(CON GET x.x.x.x:1111).map(Req::from);
+      r.as_mut().msg_mut().set_path(p).unwrap();
+      r
+    };
+
+    let hy = Hydrate::from_request(req("devices/12/files/a/b/c.txt"));
+
+    let ap = Ap::<_, (), ()>::ok_hydrated((), hy).pipe(path::segment::check::next_equals("devices"))
+                                                 .pipe(path::segment::param::parse::<u32, _, _, _>)
+                                                 .pipe(path::segment::check::next_equals("files"))
+                                                 .pipe(path::param::rest)
+                                                 .map(|(((), id), file)| {
+                                                   (id, file.as_str().to_string())
+                                                 });
+
+    assert_eq!(ap.try_unwrap_ok(), Ok((12, "a/b/c.txt".to_string())));
+  }
 }
@@ -0,0 +1,346 @@
+use core::marker::PhantomData;
+
+use embedded_time::Instant;
+use no_std_net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use toad_len::Len;
+use toad_map::Map;
+
+use crate::net::Addrd;
+use crate::platform::PlatformTypes;
+use crate::req::Req;
+use crate::time::{Millis, Stamped};
+
+/// Identifies a peer in a [`PeerStore`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PeerKey {
+  /// The peer's socket address
+  pub addr: SocketAddr,
+}
+
+impl Default for PeerKey {
+  fn default() -> Self {
+    Self { addr: SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 0)) }
+  }
+}
+
+impl From<SocketAddr> for PeerKey {
+  fn from(addr: SocketAddr) -> Self {
+    Self { addr }
+  }
+}
+
+impl<'a, P> From<&'a Addrd<Req<P>>> for PeerKey where P: PlatformTypes
+{
+  fn from(req: &'a Addrd<Req<P>>) -> Self {
+    Self::from(req.addr())
+  }
+}
+
+/// Per-peer state (e.g. authentication status, sequence counters)
+/// that routes may read & write.
+///
+/// Peers that haven't been [seen](PeerStore::session) in `ttl`
+/// are considered expired, and will be evicted to make room for new peers.
+/// If the store is full and no peers have expired,
+/// the least recently seen peer is evicted instead.
+///
+/// `M` may be any [`Map`] from [`PeerKey`] to [`Stamped`] data, e.g.
+/// `BTreeMap<PeerKey, Stamped<Clock, T>>` or `ArrayVec<[(PeerKey, Stamped<Clock, T>); 16]>`.
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// use embedded_time::duration::Milliseconds;
+/// use embedded_time::Clock as _;
+/// use toad::net::Addrd;
+/// use toad::req::Req;
+/// use toad::server::ap::{Ap, Hydrate};
+/// use toad::server::{respond, PeerKey, PeerStore};
+/// use toad::std::{dtls, Clock, PlatformTypes as Std};
+/// use toad::time::Stamped;
+///
+/// # let addr: no_std_net::SocketAddr = "192.168.0.1:8080".parse().unwrap();
+/// type Peers = PeerStore<Std<dtls::Y>, u32, BTreeMap<PeerKey, Stamped<Clock, u32>>>;
+///
+/// let clock = Clock::new();
+/// let mut peers = Peers::new(Milliseconds(60_000));
+///
+/// let mut count_visits = |req: Req<Std<dtls::Y>>| {
+///   let now = clock.try_now().unwrap();
+///
+///   Ap::<_, Std<dtls::Y>, (), ()>::ok_hydrated((), Hydrate::from_request(Addrd(req, addr)))
+///     .bind_hydrated(|_, req| {
+///       let mut session = peers.session(now, req);
+///       let visits = session.get().copied().unwrap_or(0) + 1;
+///       session.insert(visits).ok();
+///
+///       respond::ok(visits.to_string().into())
+///     })
+///     .try_unwrap_respond()
+///     .unwrap()
+/// };
+///
+/// assert_eq!(count_visits(Req::get("hello")).payload, b"1".to_vec());
+/// assert_eq!(count_visits(Req::get("hello")).payload, b"2".to_vec());
+/// ```
+pub struct PeerStore<P, T, M> {
+  peers: M,
+  ttl: Millis,
+  __p: PhantomData<(P, T)>,
+}
+
+impl<P, T, M> core::fmt::Debug for PeerStore<P, T, M> where M: core::fmt::Debug
+{
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("PeerStore")
+     .field("peers", &self.peers)
+     .field("ttl", &self.ttl)
+     .finish()
+  }
+}
+
+impl<P, T, M> PeerStore<P, T, M>
+  where P: PlatformTypes,
+        M: Map<PeerKey, Stamped<P::Clock, T>>
+{
+  /// Create an empty store, where peers expire
+  /// after `ttl` has elapsed since they were last seen
+  pub fn new(ttl: Millis) -> Self {
+    Self { peers: M::default(),
+           ttl,
+           __p: PhantomData }
+  }
+
+  fn expired(&self, now: Instant<P::Clock>, seen: Instant<P::Clock>) -> bool {
+    crate::time::elapsed(seen, now).map(|d| d > self.ttl)
+                                   .unwrap_or(false)
+  }
+
+  /// Get the data stored for a peer, if it has not expired
+  pub fn get(&self, now: Instant<P::Clock>, peer: impl Into<PeerKey>) -> Option<&T> {
+    self.peers
+        .get(&peer.into())
+        .filter(|s| !self.expired(now, s.time()))
+        .map(|s| s.data())
+  }
+
+  /// Get a mutable reference to the data stored for a peer,
+  /// if it has not expired.
+  ///
+  /// This marks the peer as seen at `now`.
+  pub fn get_mut(&mut self, now: Instant<P::Clock>, peer: impl Into<PeerKey>) -> Option<&mut T> {
+    let key = peer.into();
+
+    match self.peers.get(&key) {
+      | Some(s) if !self.expired(now, s.time()) => (),
+      | _ => return None,
+    }
+
+    self.peers.get_mut(&key).map(|s| {
+                              s.1 = now;
+                              &mut s.0
+                            })
+  }
+
+  /// Store data for a peer, yielding the data previously stored for them
+  /// (if it had not expired).
+  ///
+  /// If the store is full, expired peers are removed. If none have expired,
+  /// the least recently seen peer is removed.
+  ///
+  /// If there is still no room (e.g. a store with capacity 0),
+  /// the data is yielded back in `Err`.
+  pub fn insert(&mut self,
+                now: Instant<P::Clock>,
+                peer: impl Into<PeerKey>,
+                t: T)
+                -> Result<Option<T>, T> {
+    let key = peer.into();
+
+    let prev = self.remove(now, key);
+
+    if self.peers.is_full() {
+      self.remove_expired(now);
+    }
+
+    if self.peers.is_full() {
+      let lru = self.peers.iter().min_by_key(|(_, s)| s.time()).map(|(k, _)| *k);
+      if let Some(lru) = lru {
+        self.peers.remove(&lru);
+      }
+    }
+
+    if self.peers.is_full() {
+      Err(t)
+    } else {
+      self.peers.insert(key, Stamped(t, now)).ok();
+      Ok(prev)
+    }
+  }
+
+  /// Remove a peer, yielding their data if it had not expired
+  pub fn remove(&mut self, now: Instant<P::Clock>, peer: impl Into<PeerKey>) -> Option<T> {
+    self.peers
+        .remove(&peer.into())
+        .filter(|s| !self.expired(now, s.time()))
+        .map(Stamped::discard_timestamp)
+  }
+
+  /// Remove all peers that have not been seen within the `ttl`
+  pub fn remove_expired(&mut self, now: Instant<P::Clock>) {
+    loop {
+      let expired = self.peers
+                        .iter()
+                        .find(|(_, s)| self.expired(now, s.time()))
+                        .map(|(k, _)| *k);
+
+      match expired {
+        | Some(key) => {
+          self.peers.remove(&key);
+        },
+        | None => break,
+      }
+    }
+  }
+
+  /// Number of peers in the store (including expired peers that have not been removed yet)
+  pub fn len(&self) -> usize {
+    self.peers.len()
+  }
+
+  /// Is the store empty?
+  pub fn is_empty(&self) -> bool {
+    self.peers.is_empty()
+  }
+
+  /// Access the data stored for a single peer
+  ///
+  /// Typically used within [`Ap::bind_hydrated`](crate::server::ap::Ap::bind_hydrated)
+  /// to get the session data for the peer that sent the request.
+  pub fn session(&mut self,
+                 now: Instant<P::Clock>,
+                 peer: impl Into<PeerKey>)
+                 -> Session<'_, P, T, M> {
+    Session { store: self,
+              key: peer.into(),
+              now }
+  }
+}
+
+/// The data stored for a single peer in a [`PeerStore`]
+///
+/// See [`PeerStore::session`]
+pub struct Session<'a, P, T, M>
+  where P: PlatformTypes
+{
+  store: &'a mut PeerStore<P, T, M>,
+  key: PeerKey,
+  now: Instant<P::Clock>,
+}
+
+impl<'a, P, T, M> core::fmt::Debug for Session<'a, P, T, M>
+  where P: PlatformTypes,
+        M: core::fmt::Debug
+{
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("Session")
+     .field("store", &self.store)
+     .field("key", &self.key)
+     .finish()
+  }
+}
+
+impl<'a, P, T, M> Session<'a, P, T, M>
+  where P: PlatformTypes,
+        M: Map<PeerKey, Stamped<P::Clock, T>>
+{
+  /// The peer this session belongs to
+  pub fn key(&self) -> PeerKey {
+    self.key
+  }
+
+  /// See [`PeerStore::get`]
+  pub fn get(&self) -> Option<&T> {
+    self.store.get(self.now, self.key)
+  }
+
+  /// See [`PeerStore::get_mut`]
+  pub fn get_mut(&mut self) -> Option<&mut T> {
+    self.store.get_mut(self.now, self.key)
+  }
+
+  /// See [`PeerStore::insert`]
+  pub fn insert(&mut self, t: T) -> Result<Option<T>, T> {
+    self.store.insert(self.now, self.key, t)
+  }
+
+  /// See [`PeerStore::remove`]
+  pub fn remove(&mut self) -> Option<T> {
+    self.store.remove(self.now, self.key)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std_alloc::collections::BTreeMap;
+  use tinyvec::ArrayVec;
+
+  use super::*;
+  use crate::test::{dummy_addr, dummy_addr_2, dummy_addr_3, ClockMock, Platform};
+
+  type Clock = ClockMock;
+
+  fn now(ms: u64) -> Instant<Clock> {
+    ClockMock::instant(ms * 1000)
+  }
+
+  #[test]
+  fn expiry() {
+    let mut store =
+      PeerStore::<Platform, u8, BTreeMap<PeerKey, Stamped<Clock, u8>>>::new(Millis::new(100));
+
+    store.insert(now(0), dummy_addr(), 1).unwrap();
+    assert_eq!(store.get(now(100), dummy_addr()), Some(&1));
+
+    // get_mut refreshes
+    *store.get_mut(now(100), dummy_addr()).unwrap() += 1;
+    assert_eq!(store.get(now(200), dummy_addr()), Some(&2));
+
+    assert_eq!(store.get(now(201), dummy_addr()), None);
+    assert_eq!(store.get_mut(now(201), dummy_addr()), None);
+    assert_eq!(store.insert(now(201), dummy_addr(), 3), Ok(None));
+
+    store.insert(now(201), dummy_addr_2(), 4).unwrap();
+    store.remove_expired(now(400));
+    assert!(store.is_empty());
+  }
+
+  #[test]
+  fn full_store_evicts_least_recently_seen() {
+    let mut store =
+      PeerStore::<Platform, u8, ArrayVec<[(PeerKey, Stamped<Clock, u8>); 2]>>::new(Millis::new(1000));
+
+    store.insert(now(0), dummy_addr(), 1).unwrap();
+    store.insert(now(1), dummy_addr_2(), 2).unwrap();
+    store.get_mut(now(2), dummy_addr()).unwrap();
+
+    assert_eq!(store.insert(now(3), dummy_addr_3(), 3), Ok(None));
+    assert_eq!(store.len(), 2);
+    assert_eq!(store.get(now(3), dummy_addr()), Some(&1));
+    assert_eq!(store.get(now(3), dummy_addr_2()), None);
+    assert_eq!(store.get(now(3), dummy_addr_3()), Some(&3));
+  }
+
+  #[test]
+  fn session() {
+    let mut store =
+      PeerStore::<Platform, u8, BTreeMap<PeerKey, Stamped<Clock, u8>>>::new(Millis::new(100));
+
+    let mut s = store.session(now(0), dummy_addr());
+    assert_eq!(s.get(), None);
+    assert_eq!(s.insert(1), Ok(None));
+    assert_eq!(s.insert(2), Ok(Some(1)));
+    assert_eq!(s.remove(), Some(2));
+    assert_eq!(s.get(), None);
+  }
+}
@@ -0,0 +1,1226 @@
+use core::fmt::Write;
+
+use tinyvec::ArrayVec;
+use toad_msg::{Block, ContentFormat, MessageOptions};
+use toad_stem::Stem;
+
+use super::ap::state::{Complete, CompleteWhenHydrated, Hydrated};
+use super::ap::{Ap, Respond};
+use super::{etag, method, path, respond};
+use crate::platform::PlatformTypes;
+use crate::req::{Method, Req};
+use crate::resp::code;
+use crate::step::metrics::Stats;
+use crate::todo::String;
+
+/// Block size used when the request doesn't have a Block2 option
+const DEFAULT_BLOCK_SIZE: u16 = 1024;
+
+/// Guess the Content-Format of a file from the extension in its name,
+/// falling back to `application/octet-stream`.
+///
+/// ```
+/// use toad::server::resources::infer_format;
+/// use toad_msg::ContentFormat;
+///
+/// assert_eq!(infer_format("config.json"), ContentFormat::Json);
+/// assert_eq!(infer_format("www/README.TXT"), ContentFormat::Text);
+/// assert_eq!(infer_format("firmware.bin"), ContentFormat::OctetStream);
+/// assert_eq!(infer_format("Makefile"), ContentFormat::OctetStream);
+/// ```
+pub fn infer_format(name: &str) -> ContentFormat {
+  let ext = match name.rsplit_once('.') {
+    | Some((_, ext)) if !ext.contains('/') => ext,
+    | _ => return ContentFormat::OctetStream,
+  };
+
+  let is = |s: &str| ext.eq_ignore_ascii_case(s);
+
+  if is("txt") || is("text") {
+    ContentFormat::Text
+  } else if is("wlnk") {
+    ContentFormat::LinkFormat
+  } else if is("xml") {
+    ContentFormat::Xml
+  } else if is("exi") {
+    ContentFormat::Exi
+  } else if is("json") {
+    ContentFormat::Json
+  } else if is("cbor") {
+    ContentFormat::Other(60)
+  } else {
+    ContentFormat::OctetStream
+  }
+}
+
+/// Respond to GET requests with `bytes`
+///
+/// * The response has the Content-Format `format` and an ETag [generated](etag::generate) from `bytes`,
+///   and clients whose cached representation is current are answered with 2.03 VALID (see [`etag::auto`])
+/// * If the request has a Block2 option or `bytes` do not fit in a single 1024 byte block,
+///   only the requested block is sent. Blocks past the end of `bytes` are answered with 4.02 BAD OPTION.
+fn serve<'a, P, T, E>(bytes: &'a [u8],
+                      format: ContentFormat)
+                      -> impl FnOnce(Ap<Hydrated, P, T, E>) -> Ap<Complete, P, (), E> + 'a
+  where P: PlatformTypes,
+        E: core::fmt::Debug
+{
+  move |ap| {
+    ap.pipe(method::get)
+      .bind_hydrated(|_, req| {
+        let requested = req.data().msg().block2();
+        let (num, size) = requested.map(|b| (b.num(), b.size()))
+                                   .unwrap_or((0, DEFAULT_BLOCK_SIZE));
+
+        let start = num as usize * size as usize;
+        let end = (start + size as usize).min(bytes.len());
+
+        if start >= bytes.len() && num > 0 {
+          return respond::respond(code::BAD_OPTION, Default::default());
+        }
+
+        let block2 = if requested.is_some() || bytes.len() > size as usize {
+          Some(Block::new(size, num, end < bytes.len()))
+        } else {
+          None
+        };
+
+        Ap::respond(Respond { code: code::CONTENT,
+                              payload: bytes[start..end].iter().copied().collect(),
+                              etag: Some(etag::generate(bytes).into_iter().collect()),
+                              block2,
+                              content_format: Some(format),
+                              deferred: false })
+      })
+      .pipe(etag::auto)
+  }
+}
+
+/// A constant binary representation
+///
+/// Responds to GET requests with the bytes, an ETag and the
+/// Content-Format (`application/octet-stream` unless [set](StaticBytes::format)),
+/// splitting the bytes into blocks when they are too large
+/// to send at once.
+///
+/// ```
+/// use toad::net::Addrd;
+/// use toad::req::Req;
+/// use toad::server::resources::StaticBytes;
+/// use toad::server::{path, Run};
+/// use toad::std::{dtls, PlatformTypes as Std};
+/// use toad_msg::{ContentFormat, MessageOptions};
+///
+/// static LOGO: StaticBytes = StaticBytes::new(&[0x89, 0x50, 0x4E, 0x47]);
+///
+/// let handle = |req: Req<Std<dtls::Y>>| {
+///   Run::<_, ()>::Unmatched(Addrd(req, "127.0.0.1:5683".parse().unwrap())).maybe(|ap| {
+///     ap.pipe(path::check::rest_equals("logo.png"))
+///       .pipe(LOGO.serve())
+///   })
+/// };
+///
+/// match handle(Req::get("logo.png")) {
+///   | Run::Matched(rep) => {
+///     assert_eq!(rep.data().payload.0, vec![0x89, 0x50, 0x4E, 0x47]);
+///     assert_eq!(rep.data().content_format(), Some(ContentFormat::OctetStream));
+///   },
+///   | _ => unreachable!(),
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticBytes {
+  bytes: &'static [u8],
+  format: ContentFormat,
+}
+
+impl StaticBytes {
+  /// Create a resource serving `bytes` as `application/octet-stream`
+  pub const fn new(bytes: &'static [u8]) -> Self {
+    Self { bytes,
+           format: ContentFormat::OctetStream }
+  }
+
+  /// Set the Content-Format of the bytes
+  pub const fn format(self, format: ContentFormat) -> Self {
+    Self { format, ..self }
+  }
+
+  /// Respond to GET requests with the bytes
+  pub fn serve<P, T, E>(self) -> impl FnOnce(Ap<Hydrated, P, T, E>) -> Ap<Complete, P, (), E>
+    where P: PlatformTypes,
+          E: core::fmt::Debug
+  {
+    serve(self.bytes, self.format)
+  }
+}
+
+/// A constant text representation
+///
+/// The same as [`StaticBytes`], defaulting to the Content-Format
+/// `text/plain; charset=utf-8`.
+///
+/// ```
+/// use toad::net::Addrd;
+/// use toad::req::Req;
+/// use toad::server::resources::StaticStr;
+/// use toad::server::{path, Run};
+/// use toad::std::{dtls, PlatformTypes as Std};
+/// use toad_msg::{ContentFormat, MessageOptions};
+///
+/// static MODEL: StaticStr = StaticStr::new(r#"{"model": "frog-3000"}"#).format(ContentFormat::Json);
+///
+/// let handle = |req: Req<Std<dtls::Y>>| {
+///   Run::<_, ()>::Unmatched(Addrd(req, "127.0.0.1:5683".parse().unwrap())).maybe(|ap| {
+///     ap.pipe(path::check::rest_equals("device/model"))
+///       .pipe(MODEL.serve())
+///   })
+/// };
+///
+/// match handle(Req::get("device/model")) {
+///   | Run::Matched(rep) => {
+///     assert_eq!(rep.data().payload.0, br#"{"model": "frog-3000"}"#.to_vec());
+///     assert_eq!(rep.data().content_format(), Some(ContentFormat::Json));
+///   },
+///   | _ => unreachable!(),
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticStr {
+  text: &'static str,
+  format: ContentFormat,
+}
+
+impl StaticStr {
+  /// Create a resource serving `text` as `text/plain; charset=utf-8`
+  pub const fn new(text: &'static str) -> Self {
+    Self { text,
+           format: ContentFormat::Text }
+  }
+
+  /// Set the Content-Format of the text (e.g. [`ContentFormat::Json`])
+  pub const fn format(self, format: ContentFormat) -> Self {
+    Self { format, ..self }
+  }
+
+  /// Respond to GET requests with the text
+  pub fn serve<P, T, E>(self) -> impl FnOnce(Ap<Hydrated, P, T, E>) -> Ap<Complete, P, (), E>
+    where P: PlatformTypes,
+          E: core::fmt::Debug
+  {
+    serve(self.text.as_bytes(), self.format)
+  }
+}
+
+/// Version of toad reported by [`DeviceInfo`]
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// `application/cbor`
+const CBOR: ContentFormat = ContentFormat::Other(60);
+
+/// Device health for fleet monitoring: the version of toad, uptime, and
+/// message counts kept by the [`Metrics`](crate::step::metrics::Metrics) step.
+///
+/// Responds to GET requests for its path (`device` unless [set](DeviceInfo::path))
+/// with JSON, or CBOR when the request's Accept option is `application/cbor` (60).
+/// Requests that accept neither are responded to with 4.06 NOT ACCEPTABLE.
+///
+/// ```json
+/// {"version":"0.19.1","uptime_ms":1200,"sent":12,"received":10,"retransmitted":1}
+/// ```
+///
+/// ```
+/// use toad::net::Addrd;
+/// use toad::req::Req;
+/// use toad::server::resources::DeviceInfo;
+/// use toad::server::Run;
+/// use toad::std::{dtls, PlatformTypes as Std};
+/// use toad::step::metrics::Stats;
+/// use toad_msg::{ContentFormat, MessageOptions};
+///
+/// // typically `server.steps().stats()`, see `toad::step::metrics`
+/// let stats = Stats { sent: 12,
+///                     ..Stats::default() };
+///
+/// let handle = |req: Req<Std<dtls::Y>>| {
+///   Run::<_, ()>::Unmatched(Addrd(req, "127.0.0.1:5683".parse().unwrap())).maybe(|ap| {
+///     ap.pipe(DeviceInfo::new().path(".well-known/device").serve(stats))
+///   })
+/// };
+///
+/// match handle(Req::get(".well-known/device")) {
+///   | Run::Matched(rep) => {
+///     assert_eq!(rep.data().content_format(), Some(ContentFormat::Json));
+///     let json = String::from_utf8(rep.data().payload.0.clone()).unwrap();
+///     assert!(json.contains(r#""sent":12"#));
+///   },
+///   | _ => unreachable!(),
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceInfo {
+  path: &'static str,
+}
+
+impl Default for DeviceInfo {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl DeviceInfo {
+  /// Create a resource at the path `device`
+  pub const fn new() -> Self {
+    Self { path: "device" }
+  }
+
+  /// Set the path of the resource (without a leading `/`)
+  pub const fn path(self, path: &'static str) -> Self {
+    Self { path }
+  }
+
+  /// Respond to GET requests for the resource with `stats`
+  pub fn serve<P, T, E>(self,
+                        stats: Stats)
+                        -> impl FnOnce(Ap<Hydrated, P, T, E>) -> Ap<Complete, P, (), E>
+    where P: PlatformTypes,
+          E: core::fmt::Debug
+  {
+    move |ap| {
+      ap.pipe(path::check::rest_equals(self.path))
+        .pipe(method::get)
+        .bind_hydrated(move |_, req| match req.data().msg().accept() {
+          | None | Some(ContentFormat::Json) => {
+            respond::ok(device_info_json(stats).as_bytes().iter().copied().collect())
+              .format(ContentFormat::Json)
+          },
+          | Some(CBOR) => {
+            respond::ok(device_info_cbor(stats).into_iter().collect()).format(CBOR)
+          },
+          | Some(_) => respond::respond(code::NOT_ACCEPTABLE, Default::default()),
+        })
+    }
+  }
+}
+
+fn device_info_json(stats: Stats) -> String<256> {
+  let mut json = String::<256>::default();
+  write!(json,
+         r#"{{"version":"{}","uptime_ms":{},"sent":{},"received":{},"retransmitted":{}}}"#,
+         VERSION, stats.uptime.0, stats.sent, stats.received, stats.retransmitted).ok();
+  json
+}
+
+/// Write the head of a CBOR data item
+fn cbor_head<const N: usize>(cbor: &mut ArrayVec<[u8; N]>, major: u8, n: u64) {
+  let major = major << 5;
+  match n {
+    | 0..=23 => cbor.push(major | n as u8),
+    | 24..=0xFF => cbor.extend_from_slice(&[major | 24, n as u8]),
+    | 0x100..=0xFFFF => {
+      cbor.push(major | 25);
+      cbor.extend_from_slice(&(n as u16).to_be_bytes());
+    },
+    | 0x10000..=0xFFFF_FFFF => {
+      cbor.push(major | 26);
+      cbor.extend_from_slice(&(n as u32).to_be_bytes());
+    },
+    | _ => {
+      cbor.push(major | 27);
+      cbor.extend_from_slice(&n.to_be_bytes());
+    },
+  }
+}
+
+/// Write a CBOR text string
+fn cbor_text<const N: usize>(cbor: &mut ArrayVec<[u8; N]>, s: &str) {
+  cbor_head(cbor, 3, s.len() as u64);
+  cbor.extend_from_slice(s.as_bytes());
+}
+
+fn device_info_cbor(stats: Stats) -> ArrayVec<[u8; 128]> {
+  let mut cbor = ArrayVec::new();
+  cbor_head(&mut cbor, 5, 5);
+
+  cbor_text(&mut cbor, "version");
+  cbor_text(&mut cbor, VERSION);
+
+  [("uptime_ms", stats.uptime.0),
+   ("sent", stats.sent),
+   ("received", stats.received),
+   ("retransmitted", stats.retransmitted)].into_iter()
+                                          .for_each(|(k, v)| {
+                                            cbor_text(&mut cbor, k);
+                                            cbor_head(&mut cbor, 0, v);
+                                          });
+
+  cbor
+}
+
+/// Longest log message kept by [`Logs`]; longer messages are truncated.
+pub const MAX_LOG_LEN: usize = 256;
+
+/// Longest encoding of a single [`LogEntry`]
+///
+/// The CBOR encoding is the longest: besides the message it holds
+/// the map head (1), `"seq"` (4), a 64-bit seq (9), `"level"` (6),
+/// the level (6), `"msg"` (4) and the message's head (3).
+const MAX_LOG_ENTRY_LEN: usize = MAX_LOG_LEN + 33;
+
+/// Largest payload sent by [`Logs`]; only the most recent
+/// entries that fit are sent.
+const MAX_LOGS_PAYLOAD: usize = DEFAULT_BLOCK_SIZE as usize;
+
+/// A log entry kept by [`Logs`]
+#[derive(Debug, Clone)]
+struct LogEntry {
+  seq: u64,
+  level: log::Level,
+  msg: String<MAX_LOG_LEN>,
+}
+
+impl LogEntry {
+  /// `{seq} {level} {msg}\n`
+  fn text(&self) -> ArrayVec<[u8; MAX_LOG_ENTRY_LEN]> {
+    let mut line = String::<MAX_LOG_ENTRY_LEN>::default();
+    writeln!(line, "{} {} {}", self.seq, self.level, self.msg.as_str()).ok();
+    line.as_bytes().iter().copied().collect()
+  }
+
+  /// `{"seq": seq, "level": level, "msg": msg}`
+  fn cbor(&self) -> ArrayVec<[u8; MAX_LOG_ENTRY_LEN]> {
+    let mut cbor = ArrayVec::new();
+    cbor_head(&mut cbor, 5, 3);
+    cbor_text(&mut cbor, "seq");
+    cbor_head(&mut cbor, 0, self.seq);
+    cbor_text(&mut cbor, "level");
+    cbor_text(&mut cbor, self.level.as_str());
+    cbor_text(&mut cbor, "msg");
+    cbor_text(&mut cbor, self.msg.as_str());
+    cbor
+  }
+}
+
+#[derive(Debug)]
+struct LogEntries<const N: usize> {
+  entries: [Option<LogEntry>; N],
+  seq: u64,
+  changed: bool,
+}
+
+impl<const N: usize> LogEntries<N> {
+  const NONE: Option<LogEntry> = None;
+
+  /// Entries from oldest to newest
+  fn iter(&self) -> impl DoubleEndedIterator<Item = &LogEntry> {
+    (self.seq.saturating_sub(N as u64)..self.seq).filter_map(|seq| {
+                                                   self.entries[(seq % N as u64) as usize].as_ref()
+                                                 })
+  }
+}
+
+/// Recent runtime log entries, kept in a ring buffer of `N` entries
+/// and served at `toad/logs` (unless [set](Logs::path)) as they change.
+///
+/// Handy for watching devices in the field that have no serial console:
+/// a client can Observe the resource (e.g. `coap-client -s 60 coap://device/toad/logs`)
+/// and will be sent the latest entries whenever the server is
+/// [notified](crate::platform::Platform::notify) that the resource changed.
+///
+/// ## Recording entries
+/// With feature `std`, `Logs` is a [`log::Log`] that records entries from the `toad`
+/// log target (all log messages emitted by the runtime), so it can be installed with
+/// [`log::set_logger`]. Platforms that handle [`Effect::Log`](crate::platform::Effect::Log)
+/// themselves can [`record`](Logs::record) entries directly instead.
+///
+/// Entries less severe than the [level](Logs::level) (`Info` by default) are ignored.
+///
+/// ## Notifications
+/// Recording entries does not notify observers by itself; the application should
+/// periodically notify observers when there are new entries:
+/// ```ignore
+/// if LOGS.take_changed() {
+///   server.notify("toad/logs")?;
+/// }
+/// ```
+///
+/// ## Representation
+/// GET requests are responded to with the most recent entries that fit in 1024 bytes,
+/// oldest first, as `text/plain` (one `{seq} {level} {message}` per line), or as a CBOR
+/// array of `{"seq", "level", "msg"}` maps when the request's Accept option is
+/// `application/cbor` (60). Requests that accept neither are responded to with 4.06 NOT ACCEPTABLE.
+///
+/// ```
+/// use toad::net::Addrd;
+/// use toad::req::Req;
+/// use toad::server::resources::Logs;
+/// use toad::server::Run;
+/// use toad::std::{dtls, PlatformTypes as Std};
+/// use toad_msg::{ContentFormat, MessageOptions};
+///
+/// static LOGS: Logs<32> = Logs::new();
+///
+/// LOGS.record(log::Level::Warn, "peer 10.0.0.2:5683 is unreachable");
+/// LOGS.record(log::Level::Debug, "ignored; less severe than Info");
+/// assert!(LOGS.take_changed());
+///
+/// let handle = |req: Req<Std<dtls::Y>>| {
+///   Run::<_, ()>::Unmatched(Addrd(req, "127.0.0.1:5683".parse().unwrap())).maybe(|ap| {
+///     ap.pipe(LOGS.serve())
+///   })
+/// };
+///
+/// match handle(Req::get("toad/logs")) {
+///   | Run::Matched(rep) => {
+///     assert_eq!(rep.data().content_format(), Some(ContentFormat::Text));
+///     assert_eq!(rep.data().payload.0,
+///                b"0 WARN peer 10.0.0.2:5683 is unreachable\n".to_vec());
+///   },
+///   | _ => unreachable!(),
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Logs<const N: usize> {
+  path: &'static str,
+  level: log::LevelFilter,
+  entries: Stem<LogEntries<N>>,
+}
+
+impl<const N: usize> Default for Logs<N> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<const N: usize> Logs<N> {
+  /// Create a resource at the path `toad/logs`, keeping `Info` entries and above
+  pub const fn new() -> Self {
+    Self { path: "toad/logs",
+           level: log::LevelFilter::Info,
+           entries: Stem::new(LogEntries { entries: [LogEntries::<N>::NONE; N],
+                                           seq: 0,
+                                           changed: false }) }
+  }
+
+  /// Set the path of the resource (without a leading `/`)
+  pub const fn path(self, path: &'static str) -> Self {
+    Self { path, ..self }
+  }
+
+  /// Set the least severe level of entries to keep
+  pub const fn level(self, level: log::LevelFilter) -> Self {
+    Self { level, ..self }
+  }
+
+  /// Record a log entry, evicting the oldest entry if there are already `N`.
+  ///
+  /// Messages longer than [`MAX_LOG_LEN`] bytes are truncated.
+  pub fn record(&self, level: log::Level, msg: &str) {
+    if N == 0 || level > self.level {
+      return;
+    }
+
+    let mut end = msg.len().min(MAX_LOG_LEN);
+    while !msg.is_char_boundary(end) {
+      end -= 1;
+    }
+
+    self.entries.map_mut(|e| {
+                  let seq = e.seq;
+                  let entry = LogEntry { seq,
+                                         level,
+                                         msg: String::from(&msg[..end]) };
+                  e.entries[(seq % N as u64) as usize] = Some(entry);
+                  e.seq += 1;
+                  e.changed = true;
+                });
+  }
+
+  /// Whether any entries have been recorded since this was last invoked;
+  /// when this yields `true` observers of the resource should be notified.
+  pub fn take_changed(&self) -> bool {
+    self.entries
+        .map_mut(|e| core::mem::replace(&mut e.changed, false))
+  }
+
+  /// Respond to GET requests for the resource with the most recent entries
+  pub fn serve<P, T, E>(&self)
+                        -> impl FnOnce(Ap<Hydrated, P, T, E>) -> Ap<Complete, P, (), E> + '_
+    where P: PlatformTypes,
+          E: core::fmt::Debug
+  {
+    move |ap| {
+      ap.pipe(path::check::rest_equals(self.path))
+        .pipe(method::get)
+        .bind_hydrated(move |_, req| match req.data().msg().accept() {
+          | None | Some(ContentFormat::Text) => {
+            respond::ok(self.text().into_iter().collect()).format(ContentFormat::Text)
+          },
+          | Some(CBOR) => respond::ok(self.cbor().into_iter().collect()).format(CBOR),
+          | Some(_) => respond::respond(code::NOT_ACCEPTABLE, Default::default()),
+        })
+    }
+  }
+
+  /// The number of most recent entries whose encodings fit in `limit` bytes
+  fn fitting(&self,
+             limit: usize,
+             encode: fn(&LogEntry) -> ArrayVec<[u8; MAX_LOG_ENTRY_LEN]>)
+             -> usize {
+    self.entries.map_ref(|e| {
+                  e.iter()
+                   .rev()
+                   .scan(0usize, |len, entry| {
+                     *len += encode(entry).len();
+                     Some(*len)
+                   })
+                   .take_while(|len| *len <= limit)
+                   .count()
+                })
+  }
+
+  fn text(&self) -> ArrayVec<[u8; MAX_LOGS_PAYLOAD]> {
+    let n = self.fitting(MAX_LOGS_PAYLOAD, LogEntry::text);
+    self.entries.map_ref(|e| {
+                  let skip = e.iter().count() - n;
+                  e.iter()
+                   .skip(skip)
+                   .flat_map(LogEntry::text)
+                   .collect()
+                })
+  }
+
+  fn cbor(&self) -> ArrayVec<[u8; MAX_LOGS_PAYLOAD]> {
+    // leave room for the array head
+    let n = self.fitting(MAX_LOGS_PAYLOAD - 9, LogEntry::cbor);
+    self.entries.map_ref(|e| {
+                  let mut cbor = ArrayVec::new();
+                  cbor_head(&mut cbor, 4, n as u64);
+
+                  let skip = e.iter().count() - n;
+                  e.iter()
+                   .skip(skip)
+                   .for_each(|entry| cbor.extend_from_slice(&entry.cbor()));
+                  cbor
+                })
+  }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl<const N: usize> log::Log for Logs<N> {
+  fn enabled(&self, metadata: &log::Metadata) -> bool {
+    metadata.target().starts_with("toad") && metadata.level() <= self.level
+  }
+
+  fn log(&self, record: &log::Record) {
+    if !self.enabled(record.metadata()) {
+      return;
+    }
+
+    let mut msg = String::<1000>::default();
+    write!(msg, "{}", record.args()).ok();
+    self.record(record.level(), msg.as_str());
+  }
+
+  fn flush(&self) {}
+}
+
+/// A value that clients can update safely, without losing
+/// concurrent updates made by other clients
+///
+/// The value is versioned with an ETag, which changes every time the value does.
+/// Clients update the value with a conditional PUT (RFC 7252 §5.10.8):
+///
+/// * **If-Match** - the value is only replaced if its ETag is one of the request's
+///   (i.e. nobody has changed it since the client last read it), or if the
+///   If-Match option is empty and there is a value.
+/// * **If-None-Match** - the value is only set if there is not one yet.
+///
+/// When a precondition is not met, the request is responded to with 4.12 PRECONDITION FAILED
+/// and the value is left alone; checking and replacing the value happen atomically.
+///
+/// ## ETags across reboots
+/// ETags are a counter that starts at 0 (or 1 for a resource [with a value](Versioned::new)),
+/// so after a reboot the device would hand out ETags that clients may still hold
+/// from before, for a different value. To avoid this, [`seed`](Versioned::seed) the counter
+/// at startup with boot-unique entropy (e.g. from a hardware RNG), or with a persisted
+/// value greater than any ETag handed out before the reboot.
+///
+/// ```
+/// use toad::net::Addrd;
+/// use toad::req::Req;
+/// use toad::resp::code;
+/// use toad::server::resources::Versioned;
+/// use toad::server::{path, Run};
+/// use toad::std::{dtls, PlatformTypes as Std};
+/// use toad_msg::MessageOptions;
+///
+/// static INTERVAL: Versioned<u32> = Versioned::new(60);
+///
+/// let boot = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+///                                        .unwrap();
+/// INTERVAL.seed(boot.as_nanos() as u64);
+///
+/// let handle = |req: Req<Std<dtls::Y>>| {
+///   let run = Run::<_, ()>::Unmatched(Addrd(req, "127.0.0.1:5683".parse().unwrap())).maybe(|ap| {
+///     ap.pipe(path::check::rest_equals("config/interval"))
+///       .pipe(INTERVAL.serve(|n| n.to_string().into_bytes(),
+///                            |bytes| core::str::from_utf8(bytes).ok()?.parse().ok()))
+///   });
+///
+///   match run {
+///     | Run::Matched(rep) => rep.unwrap(),
+///     | _ => unreachable!(),
+///   }
+/// };
+///
+/// let tag = INTERVAL.etag().unwrap();
+///
+/// let mut put = Req::put("config/interval");
+/// put.msg_mut().add_if_match(tag).unwrap();
+/// put.set_payload("30");
+///
+/// assert_eq!(handle(put.clone()).code, code::CHANGED);
+/// assert_eq!(INTERVAL.get(), Some(30));
+///
+/// // the value changed since `tag` was read
+/// assert_eq!(handle(put).code, code::PRECONDITION_FAILED);
+/// ```
+#[derive(Debug, Default)]
+pub struct Versioned<T> {
+  state: Stem<Version<T>>,
+}
+
+#[derive(Debug, Default)]
+struct Version<T> {
+  value: Option<T>,
+  version: u64,
+}
+
+impl<T> Version<T> {
+  fn etag(&self) -> Option<[u8; 8]> {
+    self.value.as_ref().map(|_| self.version.to_be_bytes())
+  }
+
+  fn replace(&mut self, value: T) -> [u8; 8] {
+    self.value = Some(value);
+    self.version = self.version.wrapping_add(1);
+    self.version.to_be_bytes()
+  }
+}
+
+impl<T> Versioned<T> {
+  /// Create a resource with an initial value
+  pub const fn new(value: T) -> Self {
+    Self { state: Stem::new(Version { value: Some(value),
+                                      version: 1 }) }
+  }
+
+  /// Create a resource without a value, which clients
+  /// may create with a PUT
+  pub const fn empty() -> Self {
+    Self { state: Stem::new(Version { value: None,
+                                      version: 0 }) }
+  }
+
+  /// Continue versioning from `seed`, so that ETags handed out
+  /// before a reboot are not handed out again for different values.
+  ///
+  /// The current value's ETag (if there is one) becomes `seed`.
+  /// This should be invoked once at startup, before the resource is served.
+  pub fn seed(&self, seed: u64) {
+    self.state.map_mut(|s| s.version = seed)
+  }
+
+  /// Get the current ETag of the value, if there is one
+  pub fn etag(&self) -> Option<[u8; 8]> {
+    self.state.map_ref(|s| s.etag())
+  }
+
+  /// Get a copy of the value, if there is one
+  pub fn get(&self) -> Option<T>
+    where T: Clone
+  {
+    self.state.map_ref(|s| s.value.clone())
+  }
+
+  /// Replace the value unconditionally, yielding its new ETag
+  pub fn set(&self, value: T) -> [u8; 8] {
+    let mut value = Some(value);
+    self.state
+        .map_mut(|s| s.replace(value.take().expect("map_mut only invokes its closure once")))
+  }
+
+  /// Replace the value only if its ETag is `etag`, yielding its new ETag.
+  ///
+  /// If the value has changed (or there is no value), `value`
+  /// is given back.
+  pub fn compare_and_swap(&self, etag: &[u8], value: T) -> Result<[u8; 8], T> {
+    let mut value = Some(value);
+    let swapped = self.state.map_mut(|s| {
+                                    s.etag()
+                                     .filter(|current| current[..] == *etag)
+                                     .and_then(|_| value.take())
+                                     .map(|value| s.replace(value))
+                                  });
+
+    swapped.ok_or_else(|| value.expect("value is only taken when swapped"))
+  }
+
+  /// Respond to GET requests with the value & its ETag,
+  /// and replace it when a PUT request's preconditions are met.
+  ///
+  /// * `encode` converts the value to its representation
+  /// * `decode` parses the payload of a PUT request, yielding `None` when it is invalid (4.00 BAD REQUEST)
+  ///
+  /// * GET - 2.05 CONTENT (or 2.03 VALID, see [`etag::auto`]), or 4.04 NOT FOUND when there is no value
+  /// * PUT - 2.04 CHANGED, or 2.01 CREATED when there was no value
+  ///
+  /// Other methods are rejected.
+  pub fn serve<'a, P, X, E, Enc, Dec>(&'a self,
+                                      encode: Enc,
+                                      decode: Dec)
+                                      -> impl FnOnce(Ap<Hydrated, P, X, E>) -> Ap<Complete, P, (), E> + 'a
+    where P: PlatformTypes,
+          E: core::fmt::Debug,
+          Enc: Fn(&T) -> P::MessagePayload + 'a,
+          Dec: Fn(&[u8]) -> Option<T> + 'a
+  {
+    move |ap| {
+      ap.bind_hydrated(|_, req| match req.data().method() {
+          | m if m == Method::GET => match self.state.map_ref(|s| s.value.as_ref().map(&encode).zip(s.etag())) {
+            | Some((payload, tag)) => Ap::respond(Respond { code: code::CONTENT,
+                                                            payload,
+                                                            etag: Some(tag.into_iter().collect()),
+                                                            block2: None,
+                                                            content_format: None,
+                                                            deferred: false }),
+            | None => respond::not_found(Default::default()),
+          },
+          | m if m == Method::PUT => match decode(req.data().payload()) {
+            | Some(value) => self.put(req.data(), value),
+            | None => respond::respond(code::BAD_REQUEST, Default::default()),
+          },
+          | _ => Ap::reject(),
+        })
+        .pipe(etag::auto)
+    }
+  }
+
+  /// Replace the value if the preconditions of `req` are met
+  fn put<P, E>(&self, req: &Req<P>, value: T) -> Ap<CompleteWhenHydrated, P, (), E>
+    where P: PlatformTypes,
+          E: core::fmt::Debug
+  {
+    let mut value = Some(value);
+    self.state.map_mut(|s| {
+                let current = s.etag();
+
+                let if_match = req.msg().if_match().map(|tags| {
+                                                     tags.iter().any(|tag| match current {
+                                                                  | Some(_) if tag.0.is_empty() => true,
+                                                                  | Some(current) => tag.as_bytes() == &current[..],
+                                                                  | None => false,
+                                                                })
+                                                   });
+                let if_none_match = req.msg().if_not_exists_flag_enabled();
+
+                if if_match == Some(false) || (if_none_match && current.is_some()) {
+                  return respond::respond(code::PRECONDITION_FAILED, Default::default());
+                }
+
+                let created = current.is_none();
+                let tag = s.replace(value.take().expect("map_mut only invokes its closure once"));
+                let code = if created { code::CREATED } else { code::CHANGED };
+
+                Ap::respond(Respond { code,
+                                      payload: Default::default(),
+                                      etag: Some(tag.into_iter().collect()),
+                                      block2: None,
+                                      content_format: None,
+                                      deferred: false })
+              })
+  }
+}
+
+#[cfg(feature = "std")]
+pub use dir::Dir;
+
+#[cfg(feature = "std")]
+mod dir {
+  use std::fs;
+  use std::path::{Component, Path, PathBuf};
+
+  use super::*;
+
+  /// Files in a directory on the filesystem
+  ///
+  /// Responds to GET requests whose (remaining) path names a file
+  /// under the directory with the contents of the file, like [`StaticBytes`],
+  /// [inferring](infer_format) the Content-Format from the file's extension.
+  ///
+  /// Requests for paths that are not files in the directory
+  /// (including paths like `../secret` that would escape it)
+  /// are rejected, so that a fallback route can respond 4.04 NOT FOUND.
+  ///
+  /// ```no_run
+  /// use toad::config::Config;
+  /// use toad::server::resources::Dir;
+  /// use toad::server::{path, respond, BlockingServer, Init};
+  /// use toad::std::{dtls, Platform};
+  /// use toad::step::runtime;
+  ///
+  /// type Server = Platform<dtls::N, runtime::std::Runtime<dtls::N>>;
+  ///
+  /// let server = Server::try_new("0.0.0.0:5683", Config::default()).unwrap();
+  /// let public = Dir::new("./public");
+  ///
+  /// server.run(Init::none(), |run| {
+  ///         // GET coap://localhost/public/index.json -> ./public/index.json
+  ///         run.maybe(|ap| {
+  ///              ap.pipe(path::segment::check::next_equals("public"))
+  ///                .pipe(public.serve())
+  ///            })
+  ///            .maybe(|ap| ap.bind(|_| respond::not_found("Not found!".into())))
+  ///       })
+  ///       .unwrap();
+  /// ```
+  #[derive(Debug, Clone, PartialEq, Eq)]
+  pub struct Dir {
+    root: PathBuf,
+  }
+
+  impl Dir {
+    /// Create a resource serving the files in `root`
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+      Self { root: root.into() }
+    }
+
+    /// Get the file at `path` relative to the directory,
+    /// if it exists and `path` does not escape the directory.
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+      let rel = Path::new(path);
+      let safe = !path.is_empty() && rel.components().all(|c| matches!(c, Component::Normal(_)));
+
+      Some(self.root.join(rel)).filter(|file| safe && file.is_file())
+    }
+
+    /// Read the file at `path` & infer its Content-Format
+    fn read(&self, path: &str) -> Option<(Vec<u8>, ContentFormat)> {
+      self.resolve(path)
+          .and_then(|file| fs::read(file).ok())
+          .map(|bytes| (bytes, infer_format(path)))
+    }
+
+    /// Respond to GET requests with the file at the rest of the request path
+    pub fn serve<P, T, E>(&self) -> impl FnOnce(Ap<Hydrated, P, T, E>) -> Ap<Complete, P, (), E> + '_
+      where P: PlatformTypes,
+            E: core::fmt::Debug
+    {
+      move |ap| {
+        let file = ap.pipe(method::get).pipe(path::rest(|_, p| match self.read(p) {
+                                                          | Some(file) => Ap::ok(file),
+                                                          | None => Ap::reject().pretend_unhydrated(),
+                                                        }));
+
+        match file.try_unwrap_ok_hydrated() {
+          | Ok(((bytes, format), hy)) => Ap::ok_hydrated((), hy).pipe(serve(&bytes, format)),
+          | Err(other) => other.map(|_| ()).coerce_state(),
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use toad_msg::opt::known::repeat::ETAG;
+
+  use super::*;
+  use crate::net::Addrd;
+  use crate::req::Req;
+  use crate::server::Run;
+  use crate::test::Platform;
+
+  static SMALL: StaticStr = StaticStr::new("hello");
+  static LARGE: StaticBytes = StaticBytes::new(&[7; 40]);
+
+  fn handle<F>(req: Req<Platform>, f: F) -> Option<Addrd<crate::platform::Message<Platform>>>
+    where F: FnMut(Ap<Hydrated, Platform, (), ()>) -> Ap<Complete, Platform, (), ()>
+  {
+    match Run::<Platform, ()>::Unmatched(Addrd(req, crate::test::dummy_addr())).maybe(f) {
+      | Run::Matched(rep) => Some(rep),
+      | _ => None,
+    }
+  }
+
+  #[test]
+  fn small_representation_is_sent_whole() {
+    let rep = handle(Req::get("a"), |ap| ap.pipe(SMALL.serve())).unwrap();
+
+    assert_eq!(rep.data().code, code::CONTENT);
+    assert_eq!(rep.data().payload.0, b"hello".to_vec());
+    assert_eq!(rep.data().content_format(), Some(ContentFormat::Text));
+    assert_eq!(rep.data().get_first(ETAG).unwrap().as_bytes(),
+               &etag::generate(b"hello"));
+    assert_eq!(rep.data().block2(), None);
+  }
+
+  #[test]
+  fn requested_block_is_sent() {
+    let get_block = |num| {
+      let mut req = Req::get("a");
+      req.msg_mut().set_block2(16, num, false).unwrap();
+      handle(req, |ap| ap.pipe(LARGE.serve())).unwrap()
+    };
+
+    let rep = get_block(1);
+    assert_eq!(rep.data().payload.0, vec![7; 16]);
+    assert!(rep.data().block2().unwrap().more());
+    assert_eq!(rep.data().get_first(ETAG).unwrap().as_bytes(),
+               &etag::generate(&[7; 40]));
+
+    let rep = get_block(2);
+    assert_eq!(rep.data().payload.0, vec![7; 8]);
+    assert!(!rep.data().block2().unwrap().more());
+
+    assert_eq!(get_block(3).data().code, code::BAD_OPTION);
+  }
+
+  #[test]
+  fn cached_representation_is_validated() {
+    let mut req = Req::get("a");
+    req.msg_mut().add_etag(&etag::generate(b"hello")).unwrap();
+
+    let rep = handle(req, |ap| ap.pipe(SMALL.serve())).unwrap();
+    assert_eq!(rep.data().code, code::VALID);
+    assert!(rep.data().payload.0.is_empty());
+  }
+
+  #[test]
+  fn non_get_requests_are_rejected() {
+    assert!(handle(Req::post("a"), |ap| ap.pipe(SMALL.serve())).is_none());
+  }
+
+  #[test]
+  fn dir_serves_files_under_root() {
+    let root = std::env::temp_dir().join("toad-server-resources-test");
+    std::fs::create_dir_all(root.join("a")).unwrap();
+    std::fs::write(root.join("a/b.json"), "{}").unwrap();
+
+    let dir = Dir::new(&root);
+    let get = |path: &str| handle(Req::get(path), |ap| ap.pipe(dir.serve()));
+
+    let rep = get("a/b.json").unwrap();
+    assert_eq!(rep.data().payload.0, b"{}".to_vec());
+    assert_eq!(rep.data().content_format(), Some(ContentFormat::Json));
+
+    assert!(get("a").is_none());
+    assert!(get("a/missing.txt").is_none());
+    assert!(get("a/../a/b.json").is_none());
+  }
+
+  #[test]
+  fn device_info_responds_with_stats() {
+    let stats = Stats { uptime: crate::time::Millis::new(1200),
+                        sent: 12,
+                        received: 300,
+                        retransmitted: 1 };
+    let get = |accept: Option<ContentFormat>| {
+      let mut req = Req::get("device");
+      if let Some(accept) = accept {
+        req.msg_mut().set_accept(accept).unwrap();
+      }
+      handle(req, |ap| ap.pipe(DeviceInfo::new().serve(stats))).unwrap()
+    };
+
+    let rep = get(None);
+    assert_eq!(rep.data().content_format(), Some(ContentFormat::Json));
+    assert_eq!(rep.data().payload.0,
+               format!(r#"{{"version":"{VERSION}","uptime_ms":1200,"sent":12,"received":300,"retransmitted":1}}"#).into_bytes());
+
+    let rep = get(Some(CBOR));
+    assert_eq!(rep.data().content_format(), Some(CBOR));
+
+    let mut cbor = vec![0xA5, 0x67];
+    cbor.extend_from_slice(b"version");
+    cbor.push(0x60 | VERSION.len() as u8);
+    cbor.extend_from_slice(VERSION.as_bytes());
+    cbor.push(0x69);
+    cbor.extend_from_slice(b"uptime_ms");
+    cbor.extend_from_slice(&[0x19, 0x04, 0xB0]);
+    cbor.push(0x64);
+    cbor.extend_from_slice(b"sent");
+    cbor.push(0x0C);
+    cbor.push(0x68);
+    cbor.extend_from_slice(b"received");
+    cbor.extend_from_slice(&[0x19, 0x01, 0x2C]);
+    cbor.push(0x6D);
+    cbor.extend_from_slice(b"retransmitted");
+    cbor.push(0x01);
+    assert_eq!(rep.data().payload.0, cbor);
+
+    assert_eq!(get(Some(ContentFormat::Xml)).data().code,
+               code::NOT_ACCEPTABLE);
+    assert!(handle(Req::get("other"), |ap| ap.pipe(DeviceInfo::new().serve(stats))).is_none());
+  }
+
+  #[test]
+  fn logs_keep_most_recent_entries() {
+    let logs = Logs::<2>::new();
+    assert!(!logs.take_changed());
+
+    logs.record(log::Level::Info, "a");
+    logs.record(log::Level::Trace, "ignored");
+    logs.record(log::Level::Warn, "b");
+    logs.record(log::Level::Error, "c");
+    assert!(logs.take_changed());
+    assert!(!logs.take_changed());
+
+    let get = |accept: Option<ContentFormat>| {
+      let mut req = Req::get("toad/logs");
+      if let Some(accept) = accept {
+        req.msg_mut().set_accept(accept).unwrap();
+      }
+      handle(req, |ap| ap.pipe(logs.serve())).unwrap()
+    };
+
+    let rep = get(None);
+    assert_eq!(rep.data().content_format(), Some(ContentFormat::Text));
+    assert_eq!(rep.data().payload.0, b"1 WARN b\n2 ERROR c\n".to_vec());
+
+    let rep = get(Some(CBOR));
+    assert_eq!(rep.data().content_format(), Some(CBOR));
+
+    let mut cbor = vec![0x82];
+    [(1, "WARN", "b"), (2, "ERROR", "c")].into_iter()
+                                         .for_each(|(seq, level, msg)| {
+                                           cbor.extend_from_slice(&[0xA3, 0x63]);
+                                           cbor.extend_from_slice(b"seq");
+                                           cbor.push(seq);
+                                           cbor.push(0x65);
+                                           cbor.extend_from_slice(b"level");
+                                           cbor.push(0x60 | level.len() as u8);
+                                           cbor.extend_from_slice(level.as_bytes());
+                                           cbor.push(0x63);
+                                           cbor.extend_from_slice(b"msg");
+                                           cbor.push(0x61);
+                                           cbor.extend_from_slice(msg.as_bytes());
+                                         });
+    assert_eq!(rep.data().payload.0, cbor);
+
+    assert_eq!(get(Some(ContentFormat::Json)).data().code,
+               code::NOT_ACCEPTABLE);
+  }
+
+  #[test]
+  fn logs_payload_fits_in_one_block() {
+    let logs = Logs::<16>::new().path("logs").level(log::LevelFilter::Trace);
+    let long = "é".repeat(MAX_LOG_LEN);
+    (0..16).for_each(|_| logs.record(log::Level::Trace, &long));
+
+    let rep = handle(Req::get("logs"), |ap| ap.pipe(logs.serve())).unwrap();
+    let text = core::str::from_utf8(&rep.data().payload.0).unwrap();
+    assert!(text.len() <= DEFAULT_BLOCK_SIZE as usize);
+
+    let lines = text.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[2].starts_with("15 TRACE é"));
+    assert_eq!(lines[2].len(), "15 TRACE ".len() + MAX_LOG_LEN);
+  }
+
+  #[test]
+  fn longest_log_entry_fits() {
+    let entry = LogEntry { seq: u64::MAX,
+                           level: log::Level::Trace,
+                           msg: String::from("a".repeat(MAX_LOG_LEN).as_str()) };
+
+    assert_eq!(entry.cbor().len(), MAX_LOG_ENTRY_LEN);
+    assert!(entry.text().ends_with(b"\n"));
+  }
+
+  fn serve_versioned(versioned: &Versioned<u8>,
+                     req: Req<Platform>)
+                     -> Option<Addrd<crate::platform::Message<Platform>>> {
+    handle(req, |ap| {
+      ap.pipe(versioned.serve(|n| core::iter::once(*n).collect(), |bytes| bytes.first().copied()))
+    })
+  }
+
+  #[test]
+  fn versioned_put_if_none_match_only_creates() {
+    let versioned = Versioned::<u8>::empty();
+    let put = |n: u8| {
+      let mut req = Req::put("a");
+      req.msg_mut().set_if_not_exists().unwrap();
+      req.set_payload(&[n][..]);
+      serve_versioned(&versioned, req).unwrap()
+    };
+
+    assert_eq!(serve_versioned(&versioned, Req::get("a")).unwrap().data().code,
+               code::NOT_FOUND);
+
+    let rep = put(1);
+    assert_eq!(rep.data().code, code::CREATED);
+    assert_eq!(rep.data().get_first(ETAG).unwrap().as_bytes(),
+               &versioned.etag().unwrap());
+
+    assert_eq!(put(2).data().code, code::PRECONDITION_FAILED);
+    assert_eq!(versioned.get(), Some(1));
+  }
+
+  #[test]
+  fn versioned_put_if_match_detects_conflicts() {
+    let versioned = Versioned::new(1u8);
+    let tag = versioned.etag().unwrap();
+    let put = |n: u8, tag: &[u8]| {
+      let mut req = Req::put("a");
+      req.msg_mut().add_if_match(tag).unwrap();
+      req.set_payload(&[n][..]);
+      serve_versioned(&versioned, req).unwrap().data().code
+    };
+
+    let rep = serve_versioned(&versioned, Req::get("a")).unwrap();
+    assert_eq!(rep.data().payload.0, vec![1]);
+    assert_eq!(rep.data().get_first(ETAG).unwrap().as_bytes(), &tag);
+
+    assert_eq!(put(2, &tag), code::CHANGED);
+    assert_eq!(put(3, &tag), code::PRECONDITION_FAILED);
+    assert_eq!(versioned.get(), Some(2));
+
+    // empty If-Match only requires that there is a value
+    assert_eq!(put(4, &[]), code::CHANGED);
+    assert_eq!(versioned.get(), Some(4));
+
+    let mut req = Req::put("a");
+    req.msg_mut().add_if_match(versioned.etag().unwrap()).unwrap();
+    assert_eq!(serve_versioned(&versioned, req).unwrap().data().code,
+               code::BAD_REQUEST);
+    assert!(serve_versioned(&versioned, Req::post("a")).is_none());
+  }
+
+  #[test]
+  fn versioned_compare_and_swap() {
+    let versioned = Versioned::new(1u8);
+    let tag = versioned.etag().unwrap();
+
+    let new_tag = versioned.compare_and_swap(&tag, 2).unwrap();
+    assert_ne!(new_tag, tag);
+    assert_eq!(versioned.compare_and_swap(&tag, 3), Err(3));
+    assert_eq!(versioned.get(), Some(2));
+
+    assert_eq!(Versioned::empty().compare_and_swap(&tag, 1u8), Err(1));
+  }
+
+  #[test]
+  fn versioned_seed_continues_from_seed() {
+    let versioned = Versioned::new(1u8);
+    let unseeded = versioned.etag().unwrap();
+
+    versioned.seed(0x5eed);
+    assert_eq!(versioned.etag(), Some(0x5eedu64.to_be_bytes()));
+    assert_ne!(versioned.etag(), Some(unseeded));
+    assert_eq!(versioned.set(2), 0x5eeeu64.to_be_bytes());
+  }
+}
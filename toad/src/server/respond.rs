@@ -1,8 +1,9 @@
-use toad_msg::Code;
+use toad_msg::{Block, Code, MessageOptions};
 
-use super::ap::state::CompleteWhenHydrated;
+use super::ap::state::{Complete, CompleteWhenHydrated, Hydrated};
 use super::ap::{Ap, Respond};
 use crate::platform::PlatformTypes;
+use crate::resp::code;
 
 /// Respond to the incoming request, with a custom code and payload.
 pub fn respond<P, E>(code: Code, payload: P::MessagePayload) -> Ap<CompleteWhenHydrated, P, (), E>
@@ -11,7 +12,10 @@ pub fn respond<P, E>(code: Code, payload: P::MessagePayload) -> Ap<CompleteWhenH
 {
   Ap::respond(Respond { code,
                         payload,
-                        etag: None })
+                        etag: None,
+                        block2: None,
+                        content_format: None,
+                        deferred: false })
 }
 
 /// [`respond`] with 2.05 CONTENT
@@ -30,6 +34,96 @@ pub fn not_found<P, E>(payload: P::MessagePayload) -> Ap<CompleteWhenHydrated, P
   respond(crate::resp::code::NOT_FOUND, payload)
 }
 
+/// A block of a [streamed](stream) representation
+#[derive(Debug)]
+pub enum Chunk<P>
+  where P: PlatformTypes
+{
+  /// A block that is followed by more blocks.
+  ///
+  /// This must be exactly as long as the requested block size.
+  More(P::MessagePayload),
+  /// The final block of the representation
+  Last(P::MessagePayload),
+  /// The requested block is past the end of the representation
+  OutOfRange,
+}
+
+/// Respond 2.05 CONTENT with one block of a representation
+/// that is too large to hold in memory (e.g. a firmware image),
+/// using Block2 (RFC 7959).
+///
+/// The client requests each block of the representation separately,
+/// so `f` is invoked with the block number & block size from the
+/// request's Block2 option (or block 0 of 1024 bytes when it has none),
+/// and only needs to produce that block.
+///
+/// If `f` yields [`Chunk::OutOfRange`], the request is responded to with 4.02 BAD OPTION.
+///
+/// ```
+/// use toad::net::Addrd;
+/// use toad::req::Req;
+/// use toad::server::respond::{self, Chunk};
+/// use toad::server::Run;
+/// use toad::std::{dtls, PlatformTypes as Std};
+/// use toad_msg::MessageOptions;
+///
+/// static IMAGE: [u8; 40] = [7; 40];
+///
+/// let handle = |req: Req<Std<dtls::Y>>| {
+///   Run::<_, ()>::Unmatched(Addrd(req, "127.0.0.1:5683".parse().unwrap())).maybe(|ap| {
+///     ap.pipe(respond::stream(|num, size| {
+///         let start = num as usize * size as usize;
+///         let end = (start + size as usize).min(IMAGE.len());
+///         match IMAGE.get(start..end) {
+///           | Some(block) if end < IMAGE.len() => Chunk::More(block.to_vec()),
+///           | Some(block) => Chunk::Last(block.to_vec()),
+///           | None => Chunk::OutOfRange,
+///         }
+///       }))
+///   })
+/// };
+///
+/// let mut req = Req::get("firmware");
+/// req.msg_mut().set_block2(16, 2, false).unwrap();
+///
+/// match handle(req) {
+///   | Run::Matched(rep) => {
+///     assert_eq!(rep.data().payload.0, vec![7; 8]);
+///     assert!(!rep.data().block2().unwrap().more());
+///   },
+///   | _ => unreachable!(),
+/// }
+/// ```
+pub fn stream<P, T, E, F>(f: F) -> impl FnOnce(Ap<Hydrated, P, T, E>) -> Ap<Complete, P, (), E>
+  where P: PlatformTypes,
+        E: core::fmt::Debug,
+        F: FnOnce(u32, u16) -> Chunk<P>
+{
+  |ap| {
+    ap.bind_hydrated(|_, req| {
+        let (num, size) = req.data()
+                             .msg()
+                             .block2()
+                             .map(|b| (b.num(), b.size()))
+                             .unwrap_or((0, 1024));
+
+        let (payload, more) = match f(num, size) {
+          | Chunk::More(payload) => (payload, true),
+          | Chunk::Last(payload) => (payload, false),
+          | Chunk::OutOfRange => return respond(code::BAD_OPTION, Default::default()),
+        };
+
+        Ap::respond(Respond { code: code::CONTENT,
+                              payload,
+                              etag: None,
+                              block2: Some(Block::new(size, num, more)),
+                              content_format: None,
+                              deferred: false })
+      })
+  }
+}
+
 /// Respond with JSON
 #[cfg(any(feature = "std_serde_json", feature = "unstable_serde_json"))]
 pub mod json {
@@ -173,6 +267,7 @@ pub mod json {
       type Clock = crate::test::ClockMock;
       type Socket = crate::test::SockMock;
       type Effects = ArrayVec<[Effect<Self>; 4]>;
+      type CustomEffect = core::convert::Infallible;
     }
 
     #[derive(Debug, Clone, Serialize)]
@@ -0,0 +1,225 @@
+use std_alloc::boxed::Box;
+use std_alloc::string::{String, ToString};
+use std_alloc::vec::Vec;
+
+use super::ap::state::{Complete, Hydrated};
+use super::{path, Ap, Run};
+use crate::platform::PlatformTypes;
+
+/// A route registered in a [`RouteTable`]
+pub type Handler<P, E> = Box<dyn FnMut(Ap<Hydrated, P, (), E>) -> Ap<Complete, P, (), E> + Send>;
+
+/// Identifies a route registered in a [`RouteTable`],
+/// used to [`unregister`](RouteTable::unregister) it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RouteId(u64);
+
+struct Route<P, E>
+  where P: PlatformTypes
+{
+  id: RouteId,
+  path: String,
+  handler: Handler<P, E>,
+}
+
+/// Routes that can be registered & unregistered at runtime
+///
+/// Routes declared statically with [`Run::maybe`] are resolved at compile time,
+/// which doesn't work for handlers that come and go while the server
+/// is running (e.g. plugin-style firmware modules).
+///
+/// A `RouteTable` stores boxed handlers, each mounted at a path. When
+/// a request's path starts with the segments of the route's path, those
+/// segments are consumed and the handler is invoked with the rest.
+/// Routes are tried in the order they were registered.
+///
+/// `RouteTable`s can be freely mixed with static routes using [`Run::routes`].
+///
+/// ```
+/// use std::sync::Mutex;
+///
+/// use toad::net::Addrd;
+/// use toad::req::Req;
+/// use toad::server::route_table::RouteTable;
+/// use toad::server::{path, respond, Run};
+/// use toad::std::{dtls, PlatformTypes as Std};
+///
+/// # let addr: no_std_net::SocketAddr = "192.168.0.1:8080".parse().unwrap();
+/// let table = Mutex::new(RouteTable::<Std<dtls::Y>, ()>::new());
+///
+/// let plugin = table.lock().unwrap().register("plugins/greeter", |ap| {
+///                                      ap.pipe(path::rest(|_, name| {
+///                                          respond::ok(format!("hello, {name}!").into())
+///                                        }))
+///                                    });
+///
+/// let handle = |req: Req<Std<dtls::Y>>| {
+///   Run::Unmatched(Addrd(req, addr)).routes(&mut table.lock().unwrap())
+///                                   .maybe(|ap| ap.bind(|_| respond::not_found("".into())))
+/// };
+///
+/// match handle(Req::get("plugins/greeter/frog")) {
+///   | Run::Matched(rep) => assert_eq!(rep.data().payload.0, b"hello, frog!".to_vec()),
+///   | _ => panic!(),
+/// }
+///
+/// assert!(table.lock().unwrap().unregister(plugin));
+///
+/// match handle(Req::get("plugins/greeter/frog")) {
+///   | Run::Matched(rep) => assert_eq!(rep.data().code, toad::resp::code::NOT_FOUND),
+///   | _ => panic!(),
+/// }
+/// ```
+pub struct RouteTable<P, E>
+  where P: PlatformTypes
+{
+  routes: Vec<Route<P, E>>,
+  next_id: u64,
+}
+
+impl<P, E> Default for RouteTable<P, E> where P: PlatformTypes
+{
+  fn default() -> Self {
+    Self { routes: Vec::new(),
+           next_id: 0 }
+  }
+}
+
+impl<P, E> core::fmt::Debug for RouteTable<P, E> where P: PlatformTypes
+{
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_map()
+     .entries(self.routes.iter().map(|r| (r.id, &r.path)))
+     .finish()
+  }
+}
+
+impl<P, E> RouteTable<P, E>
+  where P: PlatformTypes,
+        E: core::fmt::Debug
+{
+  /// Create an empty `RouteTable`
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Register a handler for requests whose path starts with `path`.
+  ///
+  /// Segments of `path` are consumed before invoking the handler,
+  /// so [`path::rest`] within the handler will only see the remainder
+  /// of the request path. An empty `path` matches all requests.
+  pub fn register<S, F>(&mut self, path: S, handler: F) -> RouteId
+    where S: AsRef<str>,
+          F: FnMut(Ap<Hydrated, P, (), E>) -> Ap<Complete, P, (), E> + Send + 'static
+  {
+    let id = RouteId(self.next_id);
+    self.next_id += 1;
+
+    self.routes.push(Route { id,
+                             path: path.as_ref().trim_matches('/').to_string(),
+                             handler: Box::new(handler) });
+    id
+  }
+
+  /// Remove a route, returning `false` if it had already been removed.
+  pub fn unregister(&mut self, id: RouteId) -> bool {
+    let len = self.routes.len();
+    self.routes.retain(|r| r.id != id);
+    self.routes.len() != len
+  }
+
+  /// Iterate over the registered routes & the paths they are mounted at,
+  /// in the order they will be tried.
+  pub fn iter(&self) -> impl Iterator<Item = (RouteId, &str)> {
+    self.routes.iter().map(|r| (r.id, r.path.as_str()))
+  }
+
+  /// Number of registered routes
+  pub fn len(&self) -> usize {
+    self.routes.len()
+  }
+
+  /// Are there no registered routes?
+  pub fn is_empty(&self) -> bool {
+    self.routes.is_empty()
+  }
+
+  /// Try each registered route in order, see [`Run::maybe`].
+  pub fn handle(&mut self, run: Run<P, E>) -> Run<P, E> {
+    self.routes.iter_mut().fold(run, |run, route| {
+                              run.maybe(|ap| {
+                                   let mount = route.path.split('/').filter(|seg| !seg.is_empty());
+                                   let ap = mount.fold(ap, |ap, seg| {
+                                                   let seg = seg.to_string();
+                                                   ap.pipe(path::segment::check::next_equals(seg))
+                                                 });
+
+                                   (route.handler)(ap)
+                                 })
+                            })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use toad_msg::MessageOptions;
+
+  use super::*;
+  use crate::net::Addrd;
+  use crate::req::Req;
+  use crate::resp::code;
+  use crate::server::respond;
+
+  type Table = RouteTable<crate::test::Platform, ()>;
+
+  fn req(path: &str) -> Run<crate::test::Platform, ()> {
+    let mut r = crate::test::msg!(CON GET x.x.x.x:1111).map(Req::from);
+    r.as_mut().msg_mut().set_path(path).unwrap();
+    Run::Unmatched(r)
+  }
+
+  fn code_of(run: Run<crate::test::Platform, ()>) -> Option<toad_msg::Code> {
+    match run {
+      | Run::Matched(Addrd(m, _)) => Some(m.code),
+      | _ => None,
+    }
+  }
+
+  #[test]
+  fn routes_match_by_prefix() {
+    let mut table = Table::new();
+    table.register("a/b", |ap| ap.bind(|_| respond::ok(Default::default())));
+
+    assert_eq!(code_of(table.handle(req("a/b"))), Some(code::CONTENT));
+    assert_eq!(code_of(table.handle(req("a/b/c"))), Some(code::CONTENT));
+    assert_eq!(code_of(table.handle(req("a"))), None);
+    assert_eq!(code_of(table.handle(req("a/c"))), None);
+  }
+
+  #[test]
+  fn routes_tried_in_order() {
+    let mut table = Table::new();
+    let first = table.register("", |ap| {
+                       ap.pipe(path::check::rest_equals("x"))
+                         .bind(|_| respond::respond(code::CREATED, Default::default()))
+                     });
+    table.register("", |ap| ap.bind(|_| respond::ok(Default::default())));
+
+    assert_eq!(code_of(table.handle(req("x"))), Some(code::CREATED));
+    assert_eq!(code_of(table.handle(req("y"))), Some(code::CONTENT));
+
+    assert!(table.unregister(first));
+    assert!(!table.unregister(first));
+    assert_eq!(code_of(table.handle(req("x"))), Some(code::CONTENT));
+  }
+
+  #[test]
+  fn introspection() {
+    let mut table = Table::new();
+    let a = table.register("/a/", |ap| ap.bind(|_| respond::ok(Default::default())));
+    let b = table.register("b/c", |ap| ap.bind(|_| respond::ok(Default::default())));
+
+    assert_eq!(table.iter().collect::<Vec<_>>(), vec![(a, "a"), (b, "b/c")]);
+    assert_eq!(table.len(), 2);
+  }
+}
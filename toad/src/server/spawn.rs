@@ -0,0 +1,191 @@
+use std::future::Future;
+use std::sync::{mpsc, Arc, Mutex};
+
+use tokio::runtime::Handle;
+use tokio::sync::Semaphore;
+
+use super::ap::state::{Complete, CompleteWhenHydrated, Hydrated};
+use super::ap::{Ap, ApInner, Hydrate, Respond};
+use super::Run;
+use crate::net::Addrd;
+use crate::platform::{Message, PlatformTypes};
+use crate::req::Req;
+use crate::resp::code;
+
+/// Runs asynchronous request handlers (see [`Ap::bind_async`])
+/// on a tokio runtime, and collects their responses for the server
+/// to send.
+///
+/// At most `max_in_flight` handlers may be running at once; requests
+/// handled while this limit is reached are responded to
+/// with 5.03 SERVICE UNAVAILABLE.
+///
+/// ```no_run
+/// use toad::req::Method;
+/// use toad::server::spawn::Spawner;
+/// use toad::server::{respond, BlockingServer, Init};
+/// use toad::std::{dtls, Platform as Std};
+/// use toad::step::runtime;
+///
+/// let tokio = tokio::runtime::Runtime::new().unwrap();
+/// let spawner = Spawner::new(tokio.handle().clone(), 64);
+///
+/// type Steps = runtime::std::Runtime<dtls::N>;
+/// let server = Std::<dtls::N, Steps>::try_new("0.0.0.0:5683", Default::default()).unwrap();
+///
+/// server.run_deferred(Init::none(), || spawner.poll(), |run| {
+///         run.maybe(|ap| {
+///              ap.method(Method::GET).bind_async(&spawner, |_| async {
+///                                                  // let user = db.get_user(..).await;
+///                                                  respond::ok("hello!".into())
+///                                                })
+///            })
+///       })
+///       .unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Spawner<P>
+  where P: PlatformTypes
+{
+  runtime: Handle,
+  max_in_flight: usize,
+  permits: Arc<Semaphore>,
+  tx: mpsc::Sender<Addrd<Message<P>>>,
+  rx: Mutex<mpsc::Receiver<Addrd<Message<P>>>>,
+}
+
+impl<P> Spawner<P> where P: PlatformTypes
+{
+  /// Create a `Spawner` that runs handlers on `runtime`,
+  /// allowing at most `max_in_flight` to run at once.
+  pub fn new(runtime: Handle, max_in_flight: usize) -> Self {
+    let (tx, rx) = mpsc::channel();
+    Self { runtime,
+           max_in_flight,
+           permits: Arc::new(Semaphore::new(max_in_flight)),
+           tx,
+           rx: Mutex::new(rx) }
+  }
+
+  /// The number of handlers that have not completed yet
+  pub fn in_flight(&self) -> usize {
+    self.max_in_flight - self.permits.available_permits()
+  }
+
+  /// Get a response from a handler that has completed, if any.
+  ///
+  /// Intended to be passed to [`BlockingServer::run_deferred`](super::BlockingServer::run_deferred).
+  pub fn poll(&self) -> Option<Addrd<Message<P>>> {
+    self.rx.lock().unwrap().try_recv().ok()
+  }
+}
+
+impl<P, T, E> Ap<Hydrated, P, T, E>
+  where P: PlatformTypes,
+        E: core::fmt::Debug
+{
+  /// Use an asynchronous function `F` (`T -> Future<Ap>`) to respond to the request.
+  ///
+  /// The future is spawned with `spawner` and this returns immediately
+  /// with the request marked [`Run::Deferred`], so that
+  /// the server may continue to receive requests while the handler runs.
+  ///
+  /// Once the future resolves, its response is yielded by [`Spawner::poll`].
+  /// Since the request has long since been ACKed, responses that must reach
+  /// the client should be [deferred](Ap::deferred) so that they are retransmitted
+  /// until the client ACKs them.
+  /// If the future rejects the request or errors, no response is sent.
+  ///
+  /// If the spawner already has its maximum number of handlers in flight,
+  /// the request is responded to with 5.03 SERVICE UNAVAILABLE.
+  ///
+  /// The function will only be called if this is [`Ap::ok_hydrated`].
+  pub fn bind_async<F, Fut>(self, spawner: &Spawner<P>, f: F) -> Ap<Complete, P, (), E>
+    where F: FnOnce(T) -> Fut,
+          Fut: Future<Output = Ap<CompleteWhenHydrated, P, (), E>> + Send + 'static,
+          E: Send + 'static,
+          Req<P>: Send,
+          Message<P>: Send
+  {
+    match self.try_unwrap_ok_hydrated() {
+      | Ok((t, Hydrate { req, .. })) => {
+        let permit = match spawner.permits.clone().try_acquire_owned() {
+          | Ok(permit) => permit,
+          | Err(_) => {
+            let rep = Respond { code: code::SERVICE_UNAVAILABLE,
+                                payload: Default::default(),
+                                etag: None,
+                                block2: None,
+                                content_format: None,
+                                deferred: false };
+            return Ap::respond_hydrated(req, rep).coerce_state();
+          },
+        };
+
+        let fut = f(t);
+        let tx = spawner.tx.clone();
+        let deferred = req.clone();
+
+        spawner.runtime.spawn(async move {
+                         if let Run::Matched(rep) = Run::handle(fut.await.hydrate(req)) {
+                           tx.send(rep).ok();
+                         }
+
+                         drop(permit);
+                       });
+
+        Ap(ApInner::Deferred(deferred))
+      },
+      | Err(other) => other.map(|_| ()).coerce_state(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::server::respond;
+  use crate::test::Platform;
+
+  fn run(spawner: &Spawner<Platform>) -> Run<Platform, ()> {
+    let req = Addrd(Req::get("a"), crate::test::dummy_addr());
+    Run::Unmatched(req).maybe(|ap| {
+                         ap.bind_async(spawner, |_| async {
+                             tokio::task::yield_now().await;
+                             respond::ok(Default::default())
+                           })
+                       })
+  }
+
+  #[test]
+  fn deferred_response_is_polled_once_complete() {
+    let tokio = tokio::runtime::Builder::new_current_thread().build()
+                                                             .unwrap();
+    let spawner = Spawner::<Platform>::new(tokio.handle().clone(), 1);
+
+    assert!(matches!(run(&spawner), Run::Deferred(_)));
+    assert_eq!(spawner.in_flight(), 1);
+    assert!(spawner.poll().is_none());
+
+    tokio.block_on(async {
+           while spawner.in_flight() > 0 {
+             tokio::task::yield_now().await;
+           }
+         });
+
+    assert_eq!(spawner.poll().unwrap().data().code, code::CONTENT);
+  }
+
+  #[test]
+  fn too_many_in_flight_responds_service_unavailable() {
+    let tokio = tokio::runtime::Builder::new_current_thread().build()
+                                                             .unwrap();
+    let spawner = Spawner::<Platform>::new(tokio.handle().clone(), 1);
+
+    assert!(matches!(run(&spawner), Run::Deferred(_)));
+    match run(&spawner) {
+      | Run::Matched(rep) => assert_eq!(rep.data().code, code::SERVICE_UNAVAILABLE),
+      | _ => panic!(),
+    }
+  }
+}
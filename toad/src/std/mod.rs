@@ -4,17 +4,26 @@ use embedded_time::rate::Fraction;
 
 /// Networking! woohoo!
 pub mod net;
+
+/// Write captured dgrams to pcapng files
+pub mod pcap;
+
+/// Save server-side Observe registrations to disk
+#[cfg(feature = "server")]
+#[cfg_attr(docsrs, doc(cfg(feature = "server")))]
+pub mod observe;
 use core::marker::PhantomData;
 use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::io;
+use std::sync::Mutex;
 
 use dtls::sealed::Security;
 pub use net::*;
 use toad_msg::{OptNumber, OptValue};
 
 use crate::net::{Addrd, Socket};
-use crate::platform::{Effect, PlatformError};
+use crate::platform::{Direction, Effect, PlatformError};
 use crate::req::Req;
 use crate::resp::Resp;
 use crate::step::Step;
@@ -47,6 +56,14 @@ pub mod dtls {
   #[derive(Debug, Clone, Copy)]
   pub struct N;
 
+  /// ZST marker for disabling DTLS, and reading & writing
+  /// bursts of datagrams with single syscalls
+  /// (see [`BatchUdpSocket`](super::BatchUdpSocket))
+  #[cfg(feature = "linux-batch")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "linux-batch")))]
+  #[derive(Debug, Clone, Copy)]
+  pub struct Batched;
+
   impl Security for Y {
     type Socket = SecureUdpSocket;
   }
@@ -54,6 +71,11 @@ pub mod dtls {
   impl Security for N {
     type Socket = UdpSocket;
   }
+
+  #[cfg(feature = "linux-batch")]
+  impl Security for Batched {
+    type Socket = super::BatchUdpSocket;
+  }
 }
 
 /// implementor of [`crate::platform::PlatformTypes`] for
@@ -70,6 +92,7 @@ impl<Sec> crate::platform::PlatformTypes for PlatformTypes<Sec> where Sec: Secur
   type Clock = Clock;
   type Socket = Sec::Socket;
   type Effects = Vec<Effect<Self>>;
+  type CustomEffect = core::convert::Infallible;
 }
 
 impl<StepError, SocketError> PlatformError<StepError, SocketError> for io::Error
@@ -102,6 +125,7 @@ pub struct Platform<Sec, Steps>
   config: crate::config::Config,
   socket: Sec::Socket,
   clock: Clock,
+  capture: Mutex<Option<pcap::PcapNg<Box<dyn io::Write + Send>>>>,
 }
 
 impl<Sec, Steps> Platform<Sec, Steps>
@@ -116,6 +140,15 @@ impl<Sec, Steps> Platform<Sec, Steps>
                                              -> io::Result<Self>
     where Steps: Default
   {
+    Self::try_new_with_steps(addr, cfg, Steps::default())
+  }
+
+  /// Create a new std runtime using steps that were already constructed,
+  /// e.g. a [`DynStep`](crate::step::dyn_step::DynStep)
+  pub fn try_new_with_steps<A: std::net::ToSocketAddrs>(addr: A,
+                                                        cfg: crate::config::Config,
+                                                        steps: Steps)
+                                                        -> io::Result<Self> {
     fn first_addr<A_: std::net::ToSocketAddrs>(a: A_) -> io::Result<std::net::SocketAddr> {
       let yielded_no_addrs = || {
         io::Error::new(io::ErrorKind::InvalidInput,
@@ -134,10 +167,77 @@ impl<Sec, Steps> Platform<Sec, Steps>
                       no_std::SockAddr::from(std::SockAddr(a)).0
                     })
                     .and_then(|a| Sec::Socket::bind(a).map_err(socket_error))
-                    .map(|socket| Self { steps: Steps::default(),
+                    .map(|socket| Self { steps,
                                          config: cfg,
                                          socket,
-                                         clock: Clock::new() })
+                                         clock: Clock::new(),
+                                         capture: Mutex::new(None) })
+  }
+
+  /// Write all datagrams sent & received to a [pcapng](pcap::PcapNg) capture
+  ///
+  /// Note that datagrams are only captured when the [`capture`](crate::step::capture)
+  /// step is included in `Steps`.
+  ///
+  /// ```no_run
+  /// use std::fs::File;
+  ///
+  /// use toad::config::Config;
+  /// use toad::std::{dtls, Platform};
+  /// use toad::step::capture::Capture;
+  /// use toad::step::runtime;
+  ///
+  /// type Steps = Capture<runtime::std::Runtime<dtls::N>>;
+  ///
+  /// let toad = Platform::<dtls::N, Steps>::try_new("0.0.0.0:5683", Config::default()).unwrap();
+  /// toad.capture_to(File::create("toad.pcapng").unwrap())
+  ///     .unwrap();
+  /// ```
+  pub fn capture_to<W>(&self, writer: W) -> io::Result<()>
+    where W: io::Write + Send + 'static
+  {
+    let pcap = pcap::PcapNg::new(Box::new(writer) as Box<dyn io::Write + Send>)?;
+    *self.capture.lock().unwrap() = Some(pcap);
+    Ok(())
+  }
+}
+
+impl<Sec> crate::step::runtime::std::Runtime<Sec> where Sec: Security
+{
+  /// Describe the state held by every step in the runtime
+  /// (pending retries, buffered responses, observers, etc.)
+  ///
+  /// See [`Step::snapshot_state`]
+  ///
+  /// ```
+  /// use toad::config::Config;
+  /// use toad::platform::Platform as _;
+  /// use toad::std::{dtls, Platform};
+  /// use toad::step::runtime;
+  ///
+  /// let toad = Platform::<dtls::N, runtime::std::Runtime<dtls::N>>::try_new("127.0.0.1:0",
+  ///                                                                          Config::default()).unwrap();
+  ///
+  /// let dump = toad.steps().debug_dump();
+  /// assert!(dump.contains("Retry: 0 message(s) pending"));
+  /// ```
+  pub fn debug_dump(&self) -> std::string::String {
+    let mut dump = std::string::String::new();
+    Step::<PlatformTypes<Sec>>::snapshot_state(self, &mut dump).ok();
+    dump
+  }
+}
+
+impl<Sec, Steps> crate::platform::Exec<PlatformTypes<Sec>> for Platform<Sec, Steps>
+  where Sec: Security,
+        Steps: Step<PlatformTypes<Sec>,
+                    PollReq = Addrd<Req<PlatformTypes<Sec>>>,
+                    PollResp = Addrd<Resp<PlatformTypes<Sec>>>>
+{
+  type Error = io::Error;
+
+  fn exec(&mut self, effect: &Effect<PlatformTypes<Sec>>) -> nb::Result<(), io::Error> {
+    crate::platform::Platform::exec_1(self, effect)
   }
 }
 
@@ -155,6 +255,32 @@ impl<Sec, Steps> crate::platform::Platform<Steps> for Platform<Sec, Steps>
     Ok(())
   }
 
+  fn capture(&self, dgram: Addrd<&[u8]>, direction: Direction) -> Result<(), Self::Error> {
+    match self.capture.lock().unwrap().as_mut() {
+      | Some(pcap) => pcap.write(self.socket.local_addr(), dgram, direction),
+      | None => Ok(()),
+    }
+  }
+
+  /// Emits the span as a `tracing` event on the `toad` target
+  #[cfg(feature = "std_tracing")]
+  fn trace(&self, span: crate::step::trace::Span) -> Result<(), Self::Error> {
+    use crate::step::trace::Edge;
+
+    let edge = match span.edge {
+      | Edge::Enter => "enter",
+      | Edge::Exit => "exit",
+    };
+
+    tracing::trace!(target: "toad",
+                    step = span.step,
+                    hook = span.hook.as_str(),
+                    correlation = span.correlation.map(|c| c.0),
+                    "{}",
+                    edge);
+    Ok(())
+  }
+
   fn config(&self) -> crate::config::Config {
     self.config
   }
@@ -201,3 +327,15 @@ impl embedded_time::Clock for Clock {
     Ok(embedded_time::Instant::new(elapsed.as_micros() as u64))
   }
 }
+
+/// Wall clock time from [`std::time::SystemTime`]
+///
+/// Note that `embedded_time::Clock` for this type is monotonic,
+/// and is unaffected by changes to the system time.
+impl crate::time::WallClock for Clock {
+  fn try_unix_now(&self) -> Result<crate::time::Seconds, embedded_time::clock::Error> {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+                                .map(|d| embedded_time::duration::Seconds(d.as_secs()))
+                                .map_err(|_| embedded_time::clock::Error::Unspecified)
+  }
+}
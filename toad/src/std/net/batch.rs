@@ -0,0 +1,553 @@
+use std::collections::VecDeque;
+use std::io;
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use tinyvec::ArrayVec;
+
+use super::convert;
+use crate::net::{Addrd, Socket};
+
+/// Maximum number of datagrams read or written by a single syscall
+pub const BATCH_SIZE: usize = 16;
+
+type Dgram = ArrayVec<[u8; 1152]>;
+
+/// A received datagram, along with the local address it was sent to (if known)
+type Recvd = (Addrd<Dgram>, Option<no_std_net::SocketAddr>);
+
+/// [`UdpSocket`] that reads and writes bursts of datagrams
+/// with single syscalls.
+///
+/// On Linux, receiving a datagram reads up to [`BATCH_SIZE`] datagrams
+/// with `recvmmsg`, queueing the rest for subsequent calls to [`Socket::recv`].
+/// Datagrams sent within [`Socket::batch`] (e.g. all messages sent by
+/// [`Platform::exec_many`](crate::platform::Platform::exec_many)) are written
+/// together with `sendmmsg` when the batch ends.
+///
+/// On Linux, the local address each datagram was sent to is read with
+/// `IP_PKTINFO` / `IPV6_RECVPKTINFO` and yielded by [`Socket::recvd_dest`],
+/// so servers can tell multicast requests apart from unicast ones.
+///
+/// On other platforms this behaves exactly like [`UdpSocket`].
+///
+/// ```no_run
+/// use toad::std::{dtls, Platform as Std};
+/// use toad::step::runtime;
+///
+/// type Steps = runtime::std::Runtime<dtls::Batched>;
+/// let server = Std::<dtls::Batched, Steps>::try_new("0.0.0.0:5683", Default::default()).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct BatchUdpSocket {
+  sock: UdpSocket,
+  rx: Mutex<VecDeque<Recvd>>,
+  tx: Mutex<Vec<Addrd<Dgram>>>,
+  batching: AtomicUsize,
+  last_dest: Mutex<Option<Addrd<no_std_net::SocketAddr>>>,
+}
+
+impl BatchUdpSocket {
+  /// Wrap a [`UdpSocket`]
+  ///
+  /// This enables `IP_PKTINFO` / `IPV6_RECVPKTINFO` on the socket where
+  /// supported; if they can't be enabled, [`Socket::recvd_dest`] falls back to
+  /// [`Socket::local_addr`].
+  pub fn new(sock: UdpSocket) -> Self {
+    mmsg::recv_pktinfo(&sock).ok();
+
+    Self { sock,
+           rx: Mutex::new(VecDeque::new()),
+           tx: Mutex::new(Vec::new()),
+           batching: AtomicUsize::new(0),
+           last_dest: Mutex::new(None) }
+  }
+
+  /// Get the wrapped [`UdpSocket`]
+  pub fn inner(&self) -> &UdpSocket {
+    &self.sock
+  }
+
+  /// Read datagrams into the receive queue if it is empty
+  fn fill(&self) -> nb::Result<(), io::Error> {
+    let mut rx = self.rx.lock().unwrap();
+    if rx.is_empty() {
+      let port = Socket::local_addr(&self.sock).port();
+      mmsg::recv(&self.sock, port, &mut rx).map_err(convert::io_to_nb)?;
+    }
+
+    Ok(())
+  }
+
+  /// Copy the datagram at the front of the receive queue into `buffer`
+  fn copy(&self, buffer: &mut [u8], remove: bool) -> nb::Result<Addrd<usize>, io::Error> {
+    if !cfg!(target_os = "linux") {
+      return match remove {
+        | true => Socket::recv(&self.sock, buffer),
+        | false => Socket::peek(&self.sock, buffer),
+      };
+    }
+
+    self.fill()?;
+
+    let mut rx = self.rx.lock().unwrap();
+    let dgram = match remove {
+      | true => rx.pop_front().map(|(dgram, dest)| {
+                                 let src = dgram.addr();
+                                 *self.last_dest.lock().unwrap() =
+                                   dest.map(|dest| Addrd(dest, src));
+                                 dgram
+                               }),
+      | false => rx.front().map(|(dgram, _)| dgram.clone()),
+    };
+
+    dgram.map(|Addrd(dgram, addr)| {
+           let n = dgram.len().min(buffer.len());
+           buffer[..n].copy_from_slice(&dgram[..n]);
+           Addrd(n, addr)
+         })
+         .ok_or(nb::Error::WouldBlock)
+  }
+}
+
+impl Socket for BatchUdpSocket {
+  type Error = io::Error;
+  type Dgram = Dgram;
+
+  fn local_addr(&self) -> no_std_net::SocketAddr {
+    Socket::local_addr(&self.sock)
+  }
+
+  fn empty_dgram() -> Self::Dgram {
+    <UdpSocket as Socket>::empty_dgram()
+  }
+
+  fn bind_raw<A: no_std_net::ToSocketAddrs>(addr: A) -> Result<Self, Self::Error> {
+    <UdpSocket as Socket>::bind_raw(addr).map(Self::new)
+  }
+
+  fn send(&self, msg: Addrd<&[u8]>) -> nb::Result<(), Self::Error> {
+    if !cfg!(target_os = "linux") {
+      return Socket::send(&self.sock, msg);
+    }
+
+    let batching = self.batching.load(Ordering::SeqCst) > 0;
+    let mut tx = self.tx.lock().unwrap();
+
+    // datagrams left over from a write that would have blocked
+    // must go out before this one
+    if !batching && tx.is_empty() {
+      return Socket::send(&self.sock, msg);
+    }
+
+    tx.push(msg.map(|bytes| bytes.iter().copied().collect()));
+    if !batching || tx.len() >= BATCH_SIZE {
+      flush(&self.sock, &mut tx).map_err(nb::Error::Other)?;
+    }
+
+    Ok(())
+  }
+
+  fn recv(&self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
+    self.copy(buffer, true)
+  }
+
+  fn peek(&self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
+    self.copy(buffer, false)
+  }
+
+  fn batch<R>(&self, f: impl FnOnce() -> R) -> Result<R, Self::Error> {
+    let batch = Batch::start(&self.batching);
+    let r = f();
+
+    match batch.end() {
+      | 0 => flush(&self.sock, &mut self.tx.lock().unwrap()).map(|_| r),
+      | _ => Ok(r),
+    }
+  }
+
+  fn recvd_dest(&self, addr: no_std_net::SocketAddr) -> no_std_net::SocketAddr {
+    match *self.last_dest.lock().unwrap() {
+      | Some(Addrd(dest, src)) if src == addr => dest,
+      | _ => Socket::local_addr(self),
+    }
+  }
+
+  fn join_multicast(&self, addr: no_std_net::IpAddr) -> Result<(), Self::Error> {
+    Socket::join_multicast(&self.sock, addr)
+  }
+
+  fn leave_multicast(&self, addr: no_std_net::IpAddr) -> Result<(), Self::Error> {
+    Socket::leave_multicast(&self.sock, addr)
+  }
+
+  fn set_ttl(&self, ttl: u32) -> Result<(), Self::Error> {
+    Socket::set_ttl(&self.sock, ttl)
+  }
+
+  fn ttl(&self) -> Result<Option<u32>, Self::Error> {
+    Socket::ttl(&self.sock)
+  }
+
+  fn set_recv_buffer_size(&self, bytes: usize) -> Result<(), Self::Error> {
+    Socket::set_recv_buffer_size(&self.sock, bytes)
+  }
+
+  fn set_send_buffer_size(&self, bytes: usize) -> Result<(), Self::Error> {
+    Socket::set_send_buffer_size(&self.sock, bytes)
+  }
+}
+
+/// Marks a socket as [batching](Socket::batch) until dropped,
+/// so that a panic in the batch does not leave the socket batching forever.
+struct Batch<'a>(&'a AtomicUsize);
+
+impl<'a> Batch<'a> {
+  fn start(batching: &'a AtomicUsize) -> Self {
+    batching.fetch_add(1, Ordering::SeqCst);
+    Self(batching)
+  }
+
+  /// End the batch, yielding the number of batches still in progress
+  fn end(self) -> usize {
+    let batching = self.0.fetch_sub(1, Ordering::SeqCst) - 1;
+    core::mem::forget(self);
+    batching
+  }
+}
+
+impl<'a> Drop for Batch<'a> {
+  fn drop(&mut self) {
+    self.0.fetch_sub(1, Ordering::SeqCst);
+  }
+}
+
+/// Write the datagrams in `tx`
+///
+/// If the socket's send buffer is full (or the write was interrupted), the datagrams
+/// that were not written stay queued until the next time the socket writes.
+fn flush(sock: &UdpSocket, tx: &mut Vec<Addrd<Dgram>>) -> io::Result<()> {
+  match mmsg::send(sock, tx) {
+    | Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted) => {
+      Ok(())
+    },
+    | r => r,
+  }
+}
+
+/// `recvmmsg` & `sendmmsg`
+#[cfg(target_os = "linux")]
+mod mmsg {
+  use core::mem;
+  use std::collections::VecDeque;
+  use std::io;
+  use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket};
+  use std::os::unix::io::AsRawFd;
+
+  use super::{convert, Dgram, Recvd, BATCH_SIZE};
+  use crate::net::Addrd;
+
+  /// Room for one `IP_PKTINFO` or `IPV6_PKTINFO` control message,
+  /// as `u64`s so that it is suitably aligned for `cmsghdr`
+  type Control = [u64; 8];
+
+  /// Ask the kernel to report the destination address of each datagram
+  pub fn recv_pktinfo(sock: &UdpSocket) -> io::Result<()> {
+    let (level, opt) = match sock.local_addr()? {
+      | SocketAddr::V4(_) => (libc::IPPROTO_IP, libc::IP_PKTINFO),
+      | SocketAddr::V6(_) => (libc::IPPROTO_IPV6, libc::IPV6_RECVPKTINFO),
+    };
+    let on: libc::c_int = 1;
+
+    // SAFETY: the fd is owned by `sock` and open for the duration of this call,
+    // and `on` is a live `c_int` whose size is passed as the option length.
+    #[allow(unsafe_code)]
+    let res = unsafe {
+      libc::setsockopt(sock.as_raw_fd(),
+                       level,
+                       opt,
+                       &on as *const libc::c_int as *const libc::c_void,
+                       mem::size_of::<libc::c_int>() as libc::socklen_t)
+    };
+
+    match res {
+      | 0 => Ok(()),
+      | _ => Err(io::Error::last_os_error()),
+    }
+  }
+
+  /// Read up to [`BATCH_SIZE`] datagrams into `rx`, along with the
+  /// local address (on `port`) they were sent to, if the kernel reported it.
+  pub fn recv(sock: &UdpSocket, port: u16, rx: &mut VecDeque<Recvd>) -> io::Result<()> {
+    let mut bufs = [[0u8; 1152]; BATCH_SIZE];
+    let mut controls: [Control; BATCH_SIZE] = [[0; 8]; BATCH_SIZE];
+
+    // SAFETY: these are plain C structs, for which all zeroes is a valid value
+    #[allow(unsafe_code)]
+    let (mut addrs, mut iovs, mut hdrs): ([libc::sockaddr_storage; BATCH_SIZE],
+                                          [libc::iovec; BATCH_SIZE],
+                                          [libc::mmsghdr; BATCH_SIZE]) =
+      unsafe { (mem::zeroed(), mem::zeroed(), mem::zeroed()) };
+
+    let slots = bufs.iter_mut()
+                    .zip(iovs.iter_mut())
+                    .zip(addrs.iter_mut().zip(hdrs.iter_mut()))
+                    .zip(controls.iter_mut());
+    for (((buf, iov), (addr, hdr)), control) in slots {
+      iov.iov_base = buf.as_mut_ptr() as *mut libc::c_void;
+      iov.iov_len = buf.len();
+      hdr.msg_hdr.msg_name = addr as *mut libc::sockaddr_storage as *mut libc::c_void;
+      hdr.msg_hdr.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+      hdr.msg_hdr.msg_iov = iov;
+      hdr.msg_hdr.msg_iovlen = 1;
+      hdr.msg_hdr.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+      hdr.msg_hdr.msg_controllen = mem::size_of::<Control>() as _;
+    }
+
+    // SAFETY: every header points to a live buffer & address of the advertised lengths,
+    // none of which move until the call returns.
+    #[allow(unsafe_code)]
+    let n = unsafe {
+      libc::recvmmsg(sock.as_raw_fd(),
+                     hdrs.as_mut_ptr(),
+                     BATCH_SIZE as libc::c_uint,
+                     libc::MSG_DONTWAIT as _,
+                     core::ptr::null_mut())
+    };
+
+    if n < 0 {
+      return Err(io::Error::last_os_error());
+    }
+
+    (0..n as usize).try_for_each(|i| {
+                     // datagrams larger than the buffer are truncated, like `UdpSocket::recv_from`
+                     let len = (hdrs[i].msg_len as usize).min(bufs[i].len());
+                     let addr = from_sockaddr(&addrs[i])?;
+                     let dest = pktinfo(&hdrs[i].msg_hdr).map(|ip| SocketAddr::new(ip, port));
+                     rx.push_back((Addrd(bufs[i][..len].iter().copied().collect(),
+                                         convert::std::SockAddr(addr).into()),
+                                   dest.map(|dest| convert::std::SockAddr(dest).into())));
+                     Ok(())
+                   })
+  }
+
+  /// Write all datagrams in `tx`, [`BATCH_SIZE`] at a time
+  ///
+  /// Datagrams that were not written (because of an error, including
+  /// [`WouldBlock`](io::ErrorKind::WouldBlock) & [`Interrupted`](io::ErrorKind::Interrupted))
+  /// are left in `tx`.
+  pub fn send(sock: &UdpSocket, tx: &mut Vec<Addrd<Dgram>>) -> io::Result<()> {
+    while !tx.is_empty() {
+      let count = tx.len().min(BATCH_SIZE);
+
+      // SAFETY: these are plain C structs, for which all zeroes is a valid value
+      #[allow(unsafe_code)]
+      let (mut addrs, mut iovs, mut hdrs): ([libc::sockaddr_storage; BATCH_SIZE],
+                                            [libc::iovec; BATCH_SIZE],
+                                            [libc::mmsghdr; BATCH_SIZE]) =
+        unsafe { (mem::zeroed(), mem::zeroed(), mem::zeroed()) };
+
+      for (i, Addrd(dgram, addr)) in tx.iter().take(count).enumerate() {
+        let addr = SocketAddr::from(convert::no_std::SockAddr(*addr));
+        let namelen = to_sockaddr(addr, &mut addrs[i]);
+
+        iovs[i].iov_base = dgram.as_ptr() as *mut libc::c_void;
+        iovs[i].iov_len = dgram.len();
+        hdrs[i].msg_hdr.msg_name =
+          &mut addrs[i] as *mut libc::sockaddr_storage as *mut libc::c_void;
+        hdrs[i].msg_hdr.msg_namelen = namelen;
+        hdrs[i].msg_hdr.msg_iov = &mut iovs[i];
+        hdrs[i].msg_hdr.msg_iovlen = 1;
+      }
+
+      // SAFETY: every header points to a live datagram & address of the advertised lengths,
+      // none of which move until the call returns. `sendmmsg` does not write to the datagrams.
+      #[allow(unsafe_code)]
+      let n = unsafe { libc::sendmmsg(sock.as_raw_fd(), hdrs.as_mut_ptr(), count as libc::c_uint, 0) };
+
+      if n < 0 {
+        return Err(io::Error::last_os_error());
+      }
+
+      tx.drain(..n as usize);
+    }
+
+    Ok(())
+  }
+
+  /// Get the destination address from the `IP_PKTINFO` or `IPV6_PKTINFO`
+  /// control message of a received datagram, if there is one
+  fn pktinfo(hdr: &libc::msghdr) -> Option<IpAddr> {
+    // SAFETY: `hdr` was filled in by `recvmmsg`, so its control buffer holds
+    // `msg_controllen` bytes of well-formed control messages, and each message's
+    // data is the struct its level & type say it is.
+    #[allow(unsafe_code)]
+    unsafe {
+      let mut cmsg = libc::CMSG_FIRSTHDR(hdr);
+      while !cmsg.is_null() {
+        match ((*cmsg).cmsg_level, (*cmsg).cmsg_type) {
+          | (libc::IPPROTO_IP, libc::IP_PKTINFO) => {
+            let info = (libc::CMSG_DATA(cmsg) as *const libc::in_pktinfo).read_unaligned();
+            return Some(Ipv4Addr::from(info.ipi_addr.s_addr.to_ne_bytes()).into());
+          },
+          | (libc::IPPROTO_IPV6, libc::IPV6_PKTINFO) => {
+            let info = (libc::CMSG_DATA(cmsg) as *const libc::in6_pktinfo).read_unaligned();
+            let ip = Ipv6Addr::from(info.ipi6_addr.s6_addr);
+
+            // IPv4 datagrams received by a dual-stack socket
+            return Some(match ip.octets() {
+                          | [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, a, b, c, d] => {
+                            Ipv4Addr::new(a, b, c, d).into()
+                          },
+                          | _ => ip.into(),
+                        });
+          },
+          | _ => cmsg = libc::CMSG_NXTHDR(hdr, cmsg),
+        }
+      }
+    }
+
+    None
+  }
+
+  fn from_sockaddr(addr: &libc::sockaddr_storage) -> io::Result<SocketAddr> {
+    match addr.ss_family as libc::c_int {
+      | libc::AF_INET => {
+        // SAFETY: `ss_family` says this is a `sockaddr_in`, and `sockaddr_storage` is large
+        // enough & suitably aligned for any socket address.
+        #[allow(unsafe_code)]
+        let addr = unsafe { &*(addr as *const libc::sockaddr_storage as *const libc::sockaddr_in) };
+        let ip = Ipv4Addr::from(addr.sin_addr.s_addr.to_ne_bytes());
+        Ok(SocketAddrV4::new(ip, u16::from_be(addr.sin_port)).into())
+      },
+      | libc::AF_INET6 => {
+        // SAFETY: see above
+        #[allow(unsafe_code)]
+        let addr =
+          unsafe { &*(addr as *const libc::sockaddr_storage as *const libc::sockaddr_in6) };
+        let ip = Ipv6Addr::from(addr.sin6_addr.s6_addr);
+        Ok(SocketAddrV6::new(ip,
+                             u16::from_be(addr.sin6_port),
+                             addr.sin6_flowinfo,
+                             addr.sin6_scope_id).into())
+      },
+      | family => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                     format!("unexpected address family {}", family))),
+    }
+  }
+
+  fn to_sockaddr(addr: SocketAddr, storage: &mut libc::sockaddr_storage) -> libc::socklen_t {
+    match addr {
+      | SocketAddr::V4(addr) => {
+        // SAFETY: `sockaddr_storage` is large enough & suitably aligned for any socket address
+        #[allow(unsafe_code)]
+        let sin = unsafe { &mut *(storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in) };
+        sin.sin_family = libc::AF_INET as libc::sa_family_t;
+        sin.sin_port = addr.port().to_be();
+        sin.sin_addr = libc::in_addr { s_addr: u32::from_ne_bytes(addr.ip().octets()) };
+        mem::size_of::<libc::sockaddr_in>() as libc::socklen_t
+      },
+      | SocketAddr::V6(addr) => {
+        // SAFETY: see above
+        #[allow(unsafe_code)]
+        let sin6 =
+          unsafe { &mut *(storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in6) };
+        sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+        sin6.sin6_port = addr.port().to_be();
+        sin6.sin6_flowinfo = addr.flowinfo();
+        sin6.sin6_addr = libc::in6_addr { s6_addr: addr.ip().octets() };
+        sin6.sin6_scope_id = addr.scope_id();
+        mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t
+      },
+    }
+  }
+}
+
+/// `recvmmsg` & `sendmmsg` are only used on linux
+#[cfg(not(target_os = "linux"))]
+mod mmsg {
+  use std::collections::VecDeque;
+  use std::io;
+  use std::net::UdpSocket;
+
+  use super::{Dgram, Recvd};
+  use crate::net::Addrd;
+
+  pub fn recv_pktinfo(_: &UdpSocket) -> io::Result<()> {
+    Ok(())
+  }
+
+  pub fn recv(_: &UdpSocket, _: u16, _: &mut VecDeque<Recvd>) -> io::Result<()> {
+    Ok(())
+  }
+
+  pub fn send(_: &UdpSocket, _: &mut Vec<Addrd<Dgram>>) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn burst_is_received_in_order() {
+    let a = BatchUdpSocket::bind_raw("127.0.0.1:0").unwrap();
+    let b = BatchUdpSocket::bind_raw("127.0.0.1:0").unwrap();
+
+    a.batch(|| {
+       (0..BATCH_SIZE as u8 + 2).for_each(|n| {
+                                    a.send(Addrd(&[n, n, n], b.local_addr())).unwrap()
+                                  })
+     })
+     .unwrap();
+
+    let mut buf = [0u8; 8];
+    for n in 0..BATCH_SIZE as u8 + 2 {
+      let Addrd(len, addr) = nb::block!(b.peek(&mut buf)).unwrap();
+      assert_eq!((addr, &buf[..len]), (a.local_addr(), &[n, n, n][..]));
+
+      let Addrd(len, addr) = nb::block!(b.recv(&mut buf)).unwrap();
+      assert_eq!((addr, &buf[..len]), (a.local_addr(), &[n, n, n][..]));
+    }
+
+    assert!(matches!(b.recv(&mut buf), Err(nb::Error::WouldBlock)));
+  }
+
+  #[test]
+  fn recvd_dest_is_the_destination_of_the_last_datagram() {
+    let a = BatchUdpSocket::bind_raw("127.0.0.1:0").unwrap();
+    let b = BatchUdpSocket::bind_raw("0.0.0.0:0").unwrap();
+    let b_port = b.local_addr().port();
+
+    a.send(Addrd(&[1], crate::net::ipv4_socketaddr([127, 0, 0, 1], b_port)))
+     .unwrap();
+
+    let mut buf = [0u8; 8];
+    let Addrd(_, from) = nb::block!(b.recv(&mut buf)).unwrap();
+    assert_eq!(from, a.local_addr());
+
+    let dest = b.recvd_dest(from);
+    if cfg!(target_os = "linux") {
+      assert_eq!(dest, crate::net::ipv4_socketaddr([127, 0, 0, 1], b_port));
+    } else {
+      assert_eq!(dest, b.local_addr());
+    }
+  }
+
+  #[test]
+  fn panic_in_batch_ends_batch() {
+    let a = BatchUdpSocket::bind_raw("127.0.0.1:0").unwrap();
+    let b = BatchUdpSocket::bind_raw("127.0.0.1:0").unwrap();
+
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| a.batch(|| panic!("oops")))).unwrap_err();
+    assert_eq!(a.batching.load(Ordering::SeqCst), 0);
+
+    a.send(Addrd(&[1, 2, 3], b.local_addr())).unwrap();
+
+    let mut buf = [0u8; 8];
+    let Addrd(len, addr) = nb::block!(b.recv(&mut buf)).unwrap();
+    assert_eq!((addr, &buf[..len]), (a.local_addr(), &[1, 2, 3][..]));
+  }
+}
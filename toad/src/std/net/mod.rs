@@ -4,6 +4,7 @@ use std::net::UdpSocket;
 use naan::prelude::{Monad, MonadOnce};
 use tinyvec::ArrayVec;
 
+use crate::net::framed::FramedPipe;
 use crate::net::{Addrd, Socket};
 
 pub(super) mod convert;
@@ -12,6 +13,20 @@ pub(super) mod convert;
 pub mod secure;
 pub use secure::{Error as SecureSocketError, SecureUdpSocket};
 
+/// [`UdpSocket`] that reads & writes bursts of datagrams with single syscalls
+#[cfg(feature = "linux-batch")]
+#[cfg_attr(docsrs, doc(cfg(feature = "linux-batch")))]
+pub mod batch;
+#[cfg(feature = "linux-batch")]
+pub use batch::BatchUdpSocket;
+
+/// CoAP over WebSockets
+#[cfg(feature = "std_ws")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std_ws")))]
+pub mod ws;
+#[cfg(feature = "std_ws")]
+pub use ws::WsSocket;
+
 impl Socket for UdpSocket {
   type Error = io::Error;
   type Dgram = ArrayVec<[u8; 1152]>;
@@ -56,6 +71,15 @@ impl Socket for UdpSocket {
     }
   }
 
+  fn leave_multicast(&self, addr: no_std_net::IpAddr) -> Result<(), Self::Error> {
+    match convert::std::Ip::from(convert::no_std::Ip(addr)).0 {
+      | std::net::IpAddr::V4(addr) => {
+        self.leave_multicast_v4(&addr, &std::net::Ipv4Addr::UNSPECIFIED)
+      },
+      | std::net::IpAddr::V6(addr) => self.leave_multicast_v6(&addr, 0),
+    }
+  }
+
   fn peek(&self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
     std::net::UdpSocket::peek_from(self, buffer).map(|(n, addr)| {
                                                   Addrd(n,
@@ -67,4 +91,122 @@ impl Socket for UdpSocket {
   fn empty_dgram() -> Self::Dgram {
     ArrayVec::from([0u8; 1152])
   }
+
+  fn set_ttl(&self, ttl: u32) -> Result<(), Self::Error> {
+    UdpSocket::set_ttl(self, ttl)?;
+
+    // std does not expose the IPv6 multicast hop limit
+    match UdpSocket::local_addr(self)? {
+      | std::net::SocketAddr::V4(_) => self.set_multicast_ttl_v4(ttl),
+      | std::net::SocketAddr::V6(_) => Ok(()),
+    }
+  }
+
+  fn ttl(&self) -> Result<Option<u32>, Self::Error> {
+    UdpSocket::ttl(self).map(Some)
+  }
+
+  fn set_recv_buffer_size(&self, bytes: usize) -> Result<(), Self::Error> {
+    sockopt::set_buffer_size(self, sockopt::Buffer::Recv, bytes)
+  }
+
+  fn set_send_buffer_size(&self, bytes: usize) -> Result<(), Self::Error> {
+    sockopt::set_buffer_size(self, sockopt::Buffer::Send, bytes)
+  }
+}
+
+/// Socket options not exposed by [`std::net::UdpSocket`]
+mod sockopt {
+  use std::io;
+  use std::net::UdpSocket;
+
+  pub enum Buffer {
+    Recv,
+    Send,
+  }
+
+  #[cfg(unix)]
+  pub fn set_buffer_size(sock: &UdpSocket, buf: Buffer, bytes: usize) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let opt = match buf {
+      | Buffer::Recv => libc::SO_RCVBUF,
+      | Buffer::Send => libc::SO_SNDBUF,
+    };
+    let bytes = libc::c_int::try_from(bytes).unwrap_or(libc::c_int::MAX);
+
+    // SAFETY: the fd is owned by `sock` and open for the duration of this call,
+    // and `bytes` is a live `c_int` whose size is passed as the option length.
+    #[allow(unsafe_code)]
+    let res = unsafe {
+      libc::setsockopt(sock.as_raw_fd(),
+                       libc::SOL_SOCKET,
+                       opt,
+                       &bytes as *const libc::c_int as *const libc::c_void,
+                       core::mem::size_of::<libc::c_int>() as libc::socklen_t)
+    };
+
+    match res {
+      | 0 => Ok(()),
+      | _ => Err(io::Error::last_os_error()),
+    }
+  }
+
+  /// Buffer sizes are only hints, and are ignored on platforms
+  /// we do not know how to set them on.
+  #[cfg(not(unix))]
+  pub fn set_buffer_size(_: &UdpSocket, _: Buffer, _: usize) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+/// UDP adapter for [`FramedPipe`], where each datagram is a frame
+///
+/// This is mostly useful for exercising pipe-based deployments
+/// (e.g. serial gateways) on a development machine.
+impl FramedPipe for UdpSocket {
+  type Error = io::Error;
+
+  fn local_addr(&self) -> no_std_net::SocketAddr {
+    Socket::local_addr(self)
+  }
+
+  fn send_frame(&mut self, frame: Addrd<&[u8]>) -> nb::Result<(), Self::Error> {
+    Socket::send(self, frame)
+  }
+
+  fn recv_frame(&mut self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
+    Socket::recv(self, buffer)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::net::framed::Framed;
+
+  #[test]
+  fn udp_framed_pipe() {
+    let a = Framed::new(<UdpSocket as Socket>::bind_raw("127.0.0.1:0").unwrap());
+    let b = Framed::new(<UdpSocket as Socket>::bind_raw("127.0.0.1:0").unwrap());
+
+    a.send(Addrd(&[1, 2, 3], b.local_addr())).unwrap();
+
+    let mut buf = [0u8; 8];
+    let Addrd(n, addr) = nb::block!(b.recv(&mut buf)).unwrap();
+    assert_eq!(addr, a.local_addr());
+    assert_eq!(&buf[..n], &[1, 2, 3]);
+  }
+
+  #[test]
+  fn ttl_and_buffer_sizes() {
+    let sock = <UdpSocket as Socket>::bind_raw("127.0.0.1:0").unwrap();
+
+    Socket::set_ttl(&sock, 5).unwrap();
+    assert_eq!(Socket::ttl(&sock).unwrap(), Some(5));
+    assert_eq!(sock.multicast_ttl_v4().unwrap(), 5);
+
+    Socket::set_recv_buffer_size(&sock, 64 * 1024).unwrap();
+    Socket::set_send_buffer_size(&sock, 64 * 1024).unwrap();
+  }
 }
@@ -1,5 +1,5 @@
 use core::time::Duration;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 #[allow(unused_imports)]
 use std::io::{self, Read, Write};
 use std::net::UdpSocket;
@@ -12,6 +12,7 @@ use openssl::ssl::{ConnectConfiguration,
                    Ssl,
                    SslAcceptor,
                    SslAcceptorBuilder,
+                   ErrorCode,
                    SslConnector,
                    SslContext,
                    SslMethod,
@@ -22,6 +23,7 @@ use toad_array::Array;
 use self::conn::{SecureUdpConn, SslStream};
 use super::convert::nb_to_io;
 use super::{convert, Addrd, Socket};
+use crate::net::{Identity, Session};
 use crate::todo::{self, NbResultExt, ResultExt2};
 
 /// Secure socket result
@@ -237,6 +239,7 @@ pub struct SecureUdpSocket {
   sock: Arc<UdpSocket>,
   ssl: SslRole,
   conns: Mutex<Connections>,
+  sessions: Mutex<VecDeque<Addrd<Session>>>,
 }
 
 impl core::fmt::Debug for SecureUdpSocket {
@@ -284,7 +287,8 @@ impl SecureUdpSocket {
     sock.set_nonblocking(true).unwrap();
     Self { sock: Arc::new(sock),
            ssl: SslRole::Server(ssl.into_context()),
-           conns: Default::default() }
+           conns: Default::default(),
+           sessions: Default::default() }
   }
 
   /// Create a new secure socket for a client
@@ -298,7 +302,8 @@ impl SecureUdpSocket {
     sock.set_nonblocking(true).unwrap();
     Self { sock: Arc::new(sock),
            ssl: SslRole::Client(ssl),
-           conns: Default::default() }
+           conns: Default::default(),
+           sessions: Default::default() }
   }
 
   /// Create a new secure socket for a server
@@ -422,10 +427,14 @@ impl SecureUdpSocket {
                                     -> Result<Shared<conn::SecureUdpConn>> {
     match self.get_conn(addr) {
       | Some(conn) => Ok(conn),
-      | None => Self::connect(&self.ssl,
-                              self.sock.clone(),
-                              &mut self.conns.lock().unwrap(),
-                              addr).map_err(Error::from),
+      | None => {
+        let conn = Self::connect(&self.ssl,
+                                 self.sock.clone(),
+                                 &mut self.conns.lock().unwrap(),
+                                 addr).map_err(Error::from);
+        self.session_started(addr, &conn);
+        conn
+      },
     }
   }
 
@@ -434,10 +443,35 @@ impl SecureUdpSocket {
                                    -> Result<Shared<conn::SecureUdpConn>> {
     match self.get_conn(addr) {
       | Some(conn) => Ok(conn),
-      | None => Self::accept(&self.ssl,
-                             self.sock.clone(),
-                             &mut self.conns.lock().unwrap(),
-                             addr).map_err(Error::from),
+      | None => {
+        let conn = Self::accept(&self.ssl,
+                                self.sock.clone(),
+                                &mut self.conns.lock().unwrap(),
+                                addr).map_err(Error::from);
+        self.session_started(addr, &conn);
+        conn
+      },
+    }
+  }
+
+  fn session_event(&self, addr: no_std_net::SocketAddr, session: Session) {
+    log::trace!("{:?} {:?}", addr, session);
+    self.sessions.lock().unwrap().push_back(Addrd(session, addr));
+  }
+
+  /// Record the outcome of a brand new `connect` or `accept`
+  ///
+  /// Handshakes that are still in flight will be reported
+  /// by [`SecureUdpSocket::restart_handshake`] once they complete.
+  fn session_started(&self,
+                     addr: no_std_net::SocketAddr,
+                     conn: &Result<Shared<conn::SecureUdpConn>>) {
+    match conn {
+      | Ok(conn) if conn.lock().unwrap().stream().is_some() => {
+        self.session_event(addr, Session::Connected)
+      },
+      | Ok(_) | Err(Error::WouldBlock) => (),
+      | Err(_) => self.session_event(addr, Session::HandshakeFailed),
     }
   }
 
@@ -471,7 +505,15 @@ impl SecureUdpSocket {
                           | conn::SecureUdpConn::Establishing(e) => e,
                         })
                         .map(|mid| match mid.handshake().map_err(Error::from) {
-                          | Ok(_) => todo!(),
+                          | Ok(mut stream) => {
+                            stream.get_mut().handshake_done();
+                            self.conns
+                  .lock()
+                  .unwrap()
+                  .insert(addr, Arc::new(Mutex::new(conn::SecureUdpConn::Established(stream))));
+                            self.session_event(addr, Session::Connected);
+                            Error::WouldBlock
+                          },
                           | Err(e) => match e {
                             | Error::WouldBlockMidHandshake(e) => {
                               self.conns
@@ -481,7 +523,10 @@ impl SecureUdpSocket {
                           Arc::new(Mutex::new(conn::SecureUdpConn::Establishing(e))));
                               Error::WouldBlock
                             },
-                            | e => e,
+                            | e => {
+                              self.session_event(addr, Session::HandshakeFailed);
+                              e
+                            },
                           },
                         })
                         .unwrap_err_or(|_| Error::WouldBlock)
@@ -504,6 +549,22 @@ impl Socket for SecureUdpSocket {
     todo!()
   }
 
+  fn set_ttl(&self, ttl: u32) -> Result<()> {
+    Socket::set_ttl(self.sock.as_ref(), ttl).map_err(Error::from)
+  }
+
+  fn ttl(&self) -> Result<Option<u32>> {
+    Socket::ttl(self.sock.as_ref()).map_err(Error::from)
+  }
+
+  fn set_recv_buffer_size(&self, bytes: usize) -> Result<()> {
+    Socket::set_recv_buffer_size(self.sock.as_ref(), bytes).map_err(Error::from)
+  }
+
+  fn set_send_buffer_size(&self, bytes: usize) -> Result<()> {
+    Socket::set_send_buffer_size(self.sock.as_ref(), bytes).map_err(Error::from)
+  }
+
   fn send(&self, msg: Addrd<&[u8]>) -> nb::Result<(), Self::Error> {
     self.get_conn_or_connect(msg.addr())
         .bind(|stream: Arc<Mutex<SecureUdpConn>>| {
@@ -521,11 +582,27 @@ impl Socket for SecureUdpSocket {
         .perform_nb_err(|e| log::error!("{:?}", e))
   }
 
+  fn poll_session(&self) -> Option<Addrd<Session>> {
+    self.sessions.lock().unwrap().pop_front()
+  }
+
   fn insecure_send(&self, msg: Addrd<&[u8]>) -> nb::Result<(), Self::Error> {
     Socket::send(self.sock.as_ref(), msg).map_err(|e| e.map(Error::from))
                                          .perform_nb_err(|e| log::error!("{:?}", e))
   }
 
+  fn peer_identity(&self, addr: no_std_net::SocketAddr) -> Option<Identity> {
+    let conn = self.get_conn(addr)?;
+    let mut lock = conn.lock().unwrap();
+    let cert = lock.stream()?.ssl().peer_certificate()?;
+
+    cert.public_key()
+        .and_then(|key| key.public_key_to_der())
+        .map(|der| Identity::RawPublicKey(openssl::sha::sha256(&der)))
+        .map_err(|e| log::error!("{:?}", e))
+        .ok()
+  }
+
   fn recv(&self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
     self.sock
         .peek_addr()
@@ -535,9 +612,17 @@ impl Socket for SecureUdpSocket {
         .bind1(|Addrd(conn, addr): Addrd<Arc<Mutex<SecureUdpConn>>>| {
           let mut lock = conn.lock().unwrap();
           match DerefMut::deref_mut(&mut lock) {
-            | conn::SecureUdpConn::Established(conn) => conn.ssl_read(buffer)
-                                                            .map(|n| Addrd(n, addr))
-                                                            .map_err(Error::from),
+            | conn::SecureUdpConn::Established(conn) => match conn.ssl_read(buffer) {
+              | Ok(n) => Ok(Addrd(n, addr)),
+              // The peer sent close_notify; forget the connection
+              // so that the next datagram from them starts a new handshake.
+              | Err(e) if e.code() == ErrorCode::ZERO_RETURN => {
+                self.conns.lock().unwrap().remove(&addr);
+                self.session_event(addr, Session::Disconnected);
+                Err(Error::WouldBlock)
+              },
+              | Err(e) => Err(Error::from(e)),
+            },
             | conn::SecureUdpConn::Establishing(_) => Err(self.restart_handshake(addr)),
           }
         })
@@ -573,4 +658,11 @@ impl Socket for SecureUdpSocket {
                                                              log::error!("{:?}", e)
                                                            })
   }
+
+  /// Multicast and SSL are incompatible, so this always returns `Err(io::ErrorKind::Unsupported)`.
+  fn leave_multicast(&self, _: no_std_net::IpAddr) -> Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported).into()).discard_err(|e: &Error| {
+                                                             log::error!("{:?}", e)
+                                                           })
+  }
 }
@@ -0,0 +1,520 @@
+use core::sync::atomic::{AtomicU16, Ordering};
+use core::time::Duration;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::sync::Mutex;
+
+use tinyvec::ArrayVec;
+use toad_msg::alloc::Message;
+use toad_msg::{MessageOptions, OptNumber, TryFromBytes};
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::HeaderValue;
+use tungstenite::{Message as WsMessage, WebSocket};
+
+use super::convert;
+use crate::net::{Addrd, Session, Socket};
+
+/// The WebSocket subprotocol name registered for CoAP
+///
+/// See [RFC 8323 Section 4.1](https://www.rfc-editor.org/rfc/rfc8323#section-4.1)
+pub const SUBPROTOCOL: &str = "coap";
+
+/// Resource path that coap+ws servers accept connections on
+///
+/// See [RFC 8323 Section 8.4](https://www.rfc-editor.org/rfc/rfc8323#section-8.4)
+pub const PATH: &str = "/.well-known/coap";
+
+/// The largest message we are willing to receive,
+/// and the assumed limit for peers that have not
+/// told us otherwise in a CSM.
+pub const MAX_MESSAGE_SIZE: u32 = 1152;
+
+/// Codes of the signaling messages defined by RFC 8323
+///
+/// See [RFC 8323 Section 5](https://www.rfc-editor.org/rfc/rfc8323#section-5)
+pub mod signal {
+  /// 7.01 Capabilities and Settings Message
+  pub const CSM: u8 = 0xE1;
+  /// 7.02 Ping
+  pub const PING: u8 = 0xE2;
+  /// 7.03 Pong
+  pub const PONG: u8 = 0xE3;
+  /// 7.04 Release
+  pub const RELEASE: u8 = 0xE4;
+  /// 7.05 Abort
+  pub const ABORT: u8 = 0xE5;
+
+  /// CSM Option: Max-Message-Size
+  pub const OPT_MAX_MESSAGE_SIZE: u32 = 2;
+  /// CSM Option: Block-Wise-Transfer
+  pub const OPT_BLOCK_WISE_TRANSFER: u32 = 4;
+}
+
+/// Capabilities a peer advertised in its CSM
+///
+/// See [RFC 8323 Section 5.3](https://www.rfc-editor.org/rfc/rfc8323#section-5.3)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Csm {
+  /// The largest message the peer will accept
+  pub max_message_size: u32,
+  /// Whether the peer supports Block-wise transfers (RFC 7959)
+  pub block_wise_transfer: bool,
+}
+
+impl Default for Csm {
+  fn default() -> Self {
+    Self { max_message_size: MAX_MESSAGE_SIZE,
+           block_wise_transfer: false }
+  }
+}
+
+/// Rewrite a CoAP-over-UDP datagram as a CoAP-over-WebSockets frame
+///
+/// The WebSocket message format (RFC 8323 Section 4.4) drops the
+/// Version, Type and Message ID fields, and has a length nibble that
+/// is always zero since WebSocket frames are already length-delimited.
+///
+/// Empty messages (ACK, RST and CON pings) have no meaning on a reliable
+/// transport, so this yields `None` for them.
+///
+/// ```
+/// use toad::std::net::ws;
+///
+/// // CON GET, MID 0x1234, token [0xAB], Uri-Path "a"
+/// let udp = [0x41, 0x01, 0x12, 0x34, 0xAB, 0xB1, b'a'];
+/// assert_eq!(ws::frame(&udp), Some(vec![0x01, 0x01, 0xAB, 0xB1, b'a']));
+///
+/// // Empty ACK
+/// assert_eq!(ws::frame(&[0x60, 0x00, 0x12, 0x34]), None);
+/// ```
+pub fn frame(dgram: &[u8]) -> Option<Vec<u8>> {
+  if dgram.len() < 4 || dgram[1] == 0 {
+    return None;
+  }
+
+  let tkl = dgram[0] & 0b1111;
+  let mut frame = Vec::with_capacity(dgram.len() - 2);
+  frame.push(tkl);
+  frame.push(dgram[1]);
+  frame.extend_from_slice(&dgram[4..]);
+  Some(frame)
+}
+
+/// Rewrite a CoAP-over-WebSockets frame as a CoAP-over-UDP datagram
+///
+/// Reliable transports have no notion of message types or IDs, so the datagram
+/// is marked Non-confirmable (so that the runtime will neither acknowledge nor retry it)
+/// and given the Message ID `id`.
+///
+/// Yields `None` if the frame is malformed.
+///
+/// ```
+/// use toad::std::net::ws;
+///
+/// let frame = [0x01, 0x01, 0xAB, 0xB1, b'a'];
+/// assert_eq!(ws::unframe(&frame, 0x1234),
+///            Some(vec![0x51, 0x01, 0x12, 0x34, 0xAB, 0xB1, b'a']));
+///
+/// // Len nibble must be zero
+/// assert_eq!(ws::unframe(&[0x11, 0x01, 0xAB], 0), None);
+/// ```
+pub fn unframe(frame: &[u8], id: u16) -> Option<Vec<u8>> {
+  let tkl = *frame.first()?;
+  if tkl >> 4 != 0 || tkl > 8 || frame.len() < 2 + tkl as usize {
+    return None;
+  }
+
+  let [id_hi, id_lo] = id.to_be_bytes();
+  let mut dgram = Vec::with_capacity(frame.len() + 2);
+  dgram.push(0b01_01_0000 | tkl);
+  dgram.push(frame[1]);
+  dgram.push(id_hi);
+  dgram.push(id_lo);
+  dgram.extend_from_slice(&frame[2..]);
+  Some(dgram)
+}
+
+/// Build the CSM we send when a connection is opened
+///
+/// ```
+/// use toad::std::net::ws;
+///
+/// // 7.01, Max-Message-Size (option 2) = 1152
+/// assert_eq!(ws::csm(1152), vec![0x00, 0xE1, 0x22, 0x04, 0x80]);
+/// ```
+pub fn csm(max_message_size: u32) -> Vec<u8> {
+  let size = max_message_size.to_be_bytes();
+  let size = &size[size.iter().take_while(|b| **b == 0).count()..];
+
+  let mut frame = vec![0x00, signal::CSM];
+  frame.push(((signal::OPT_MAX_MESSAGE_SIZE as u8) << 4) | size.len() as u8);
+  frame.extend_from_slice(size);
+  frame
+}
+
+fn signal(code: u8, token: &[u8]) -> Vec<u8> {
+  let mut frame = vec![token.len() as u8, code];
+  frame.extend_from_slice(token);
+  frame
+}
+
+fn parse_csm(msg: &Message) -> Csm {
+  let max_message_size = msg.get_u32(OptNumber(signal::OPT_MAX_MESSAGE_SIZE))
+                            .unwrap_or(MAX_MESSAGE_SIZE);
+  let block_wise_transfer = msg.get(OptNumber(signal::OPT_BLOCK_WISE_TRANSFER))
+                               .is_some();
+
+  Csm { max_message_size,
+        block_wise_transfer }
+}
+
+fn ws_err(e: tungstenite::Error) -> io::Error {
+  match e {
+    | tungstenite::Error::Io(e) => e,
+    | e => io::Error::new(io::ErrorKind::Other, e.to_string()),
+  }
+}
+
+struct Conn {
+  ws: WebSocket<TcpStream>,
+  csm: Option<Csm>,
+}
+
+/// CoAP over WebSockets ([RFC 8323](https://www.rfc-editor.org/rfc/rfc8323))
+///
+/// Lets toad servers be reached from browsers, typically through a WS gateway.
+///
+/// The runtime speaks CoAP-over-UDP, so this socket translates messages
+/// to and from the WebSocket message format at the edge:
+///  * Outbound: empty messages (ACKs, RSTs) are dropped, and Type / Message ID are stripped
+///  * Inbound: messages are presented to the runtime as Non-confirmable with a fresh Message ID
+///
+/// Signaling messages (CSM, Ping, Pong, Release, Abort) are handled here
+/// and never reach the runtime. A CSM is sent as soon as a connection opens,
+/// and the first message from a peer must be a CSM or the connection is aborted.
+///
+/// Connections are accepted on the address this socket was bound to,
+/// and opened on demand when sending to a peer we are not yet connected to.
+/// Lifecycle changes are reported via [`Socket::poll_session`].
+pub struct WsSocket {
+  listener: TcpListener,
+  conns: Mutex<HashMap<no_std_net::SocketAddr, Conn>>,
+  inbox: Mutex<VecDeque<Addrd<Vec<u8>>>>,
+  sessions: Mutex<VecDeque<Addrd<Session>>>,
+  id: AtomicU16,
+}
+
+impl core::fmt::Debug for WsSocket {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    write!(f, "WsSocket {{ /* fields hidden */ }}")
+  }
+}
+
+impl WsSocket {
+  /// Capabilities advertised by the peer at `addr`, if we have received its CSM
+  pub fn peer_csm(&self, addr: no_std_net::SocketAddr) -> Option<Csm> {
+    self.conns.lock().unwrap().get(&addr).and_then(|c| c.csm)
+  }
+
+  fn session_event(&self, addr: no_std_net::SocketAddr, session: Session) {
+    log::trace!("{:?} {:?}", addr, session);
+    self.sessions.lock().unwrap().push_back(Addrd(session, addr));
+  }
+
+  fn open(&self, addr: no_std_net::SocketAddr, mut ws: WebSocket<TcpStream>) -> io::Result<()> {
+    ws.send(WsMessage::Binary(csm(MAX_MESSAGE_SIZE))).map_err(ws_err)?;
+    ws.get_ref().set_read_timeout(None)?;
+    ws.get_ref().set_nonblocking(true)?;
+
+    self.conns
+        .lock()
+        .unwrap()
+        .insert(addr, Conn { ws, csm: None });
+    self.session_event(addr, Session::Connected);
+    Ok(())
+  }
+
+  fn close(&self, addr: no_std_net::SocketAddr, conn: &mut Conn) {
+    conn.ws.close(None).ok();
+    conn.ws.flush().ok();
+    self.session_event(addr, Session::Disconnected);
+  }
+
+  fn connect(&self, addr: no_std_net::SocketAddr) -> io::Result<()> {
+    use tungstenite::client::IntoClientRequest;
+
+    let std_addr: std::net::SocketAddr = convert::no_std::SockAddr(addr).into();
+    let stream = TcpStream::connect_timeout(&std_addr, Duration::from_secs(5))?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut req = format!("ws://{}{}", std_addr, PATH).into_client_request()
+                                                       .map_err(ws_err)?;
+    req.headers_mut()
+       .insert("Sec-WebSocket-Protocol", HeaderValue::from_static(SUBPROTOCOL));
+
+    tungstenite::client(req, stream).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+                                    .map_err(|e| {
+                                      self.session_event(addr, Session::HandshakeFailed);
+                                      e
+                                    })
+                                    .and_then(|(ws, _)| self.open(addr, ws))
+  }
+
+  fn accept(&self) -> io::Result<()> {
+    loop {
+      let (stream, addr) = match self.listener.accept() {
+        | Ok(conn) => conn,
+        | Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+        | Err(e) => return Err(e),
+      };
+      let addr = convert::std::SockAddr(addr).into();
+
+      // Like the DTLS handshake, we block (with a timeout)
+      // while the upgrade is negotiated.
+      stream.set_nonblocking(false)?;
+      stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+      let negotiate = |req: &Request, mut resp: Response| -> Result<Response, ErrorResponse> {
+        let coap = req.headers()
+                      .get_all("Sec-WebSocket-Protocol")
+                      .iter()
+                      .filter_map(|v| v.to_str().ok())
+                      .flat_map(|v| v.split(','))
+                      .any(|p| p.trim() == SUBPROTOCOL);
+
+        if coap {
+          resp.headers_mut()
+              .insert("Sec-WebSocket-Protocol", HeaderValue::from_static(SUBPROTOCOL));
+        }
+
+        Ok(resp)
+      };
+
+      match tungstenite::accept_hdr(stream, negotiate) {
+        | Ok(ws) => self.open(addr, ws)?,
+        | Err(e) => {
+          log::warn!("{:?} websocket handshake failed: {}", addr, e);
+          self.session_event(addr, Session::HandshakeFailed);
+        },
+      }
+    }
+  }
+
+  /// Handle a single inbound WebSocket message,
+  /// returning `false` if the connection should be dropped.
+  fn handle(&self, addr: no_std_net::SocketAddr, conn: &mut Conn, frame: Vec<u8>) -> bool {
+    let id = self.id.fetch_add(1, Ordering::Relaxed);
+    let msg = match unframe(&frame, id).and_then(|dgram| {
+                                          Message::try_from_bytes(&dgram).ok()
+                                                                         .map(|msg| (dgram, msg))
+                                        }) {
+      | Some(msg) => msg,
+      | None => {
+        log::warn!("{:?} sent a malformed message, aborting connection", addr);
+        conn.ws.send(WsMessage::Binary(signal(signal::ABORT, &[]))).ok();
+        return false;
+      },
+    };
+
+    let (dgram, msg) = msg;
+    let code = frame[1];
+
+    match (conn.csm, code) {
+      | (_, signal::CSM) => {
+        conn.csm = Some(parse_csm(&msg));
+        true
+      },
+      | (None, _) => {
+        log::warn!("{:?} did not send a CSM first, aborting connection", addr);
+        conn.ws.send(WsMessage::Binary(signal(signal::ABORT, &[]))).ok();
+        false
+      },
+      | (Some(_), signal::PING) => {
+        conn.ws
+            .send(WsMessage::Binary(signal(signal::PONG, &msg.token.0)))
+            .ok();
+        true
+      },
+      | (Some(_), signal::PONG) => true,
+      | (Some(_), signal::RELEASE) | (Some(_), signal::ABORT) => false,
+      | (Some(_), _) => {
+        self.inbox.lock().unwrap().push_back(Addrd(dgram, addr));
+        true
+      },
+    }
+  }
+
+  /// Accept pending connections, then move every available message
+  /// from every connection into the inbox.
+  fn pump(&self) -> io::Result<()> {
+    self.accept()?;
+
+    let mut conns = self.conns.lock().unwrap();
+    let mut dead = Vec::new();
+
+    for (addr, conn) in conns.iter_mut() {
+      let alive = loop {
+        match conn.ws.read() {
+          | Ok(WsMessage::Binary(frame)) => {
+            if !self.handle(*addr, conn, frame) {
+              break false;
+            }
+          },
+          | Ok(WsMessage::Close(_)) => break false,
+          // RFC 8323 Section 4.2: CoAP messages are carried in binary frames only
+          | Ok(WsMessage::Text(_)) => break false,
+          | Ok(_) => continue,
+          | Err(tungstenite::Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => break true,
+          | Err(e) => {
+            log::warn!("{:?} {}", addr, e);
+            break false;
+          },
+        }
+      };
+
+      if !alive {
+        dead.push(*addr);
+      }
+    }
+
+    dead.into_iter().for_each(|addr| {
+                      if let Some(mut conn) = conns.remove(&addr) {
+                        self.close(addr, &mut conn);
+                      }
+                    });
+
+    Ok(())
+  }
+}
+
+impl Socket for WsSocket {
+  type Error = io::Error;
+  type Dgram = ArrayVec<[u8; 1152]>;
+
+  fn local_addr(&self) -> no_std_net::SocketAddr {
+    convert::std::SockAddr(self.listener.local_addr().unwrap()).into()
+  }
+
+  fn empty_dgram() -> Self::Dgram {
+    ArrayVec::from([0u8; 1152])
+  }
+
+  fn bind_raw<A: no_std_net::ToSocketAddrs>(addr: A) -> Result<Self, Self::Error> {
+    let addrs = addr.to_socket_addrs()
+                    .unwrap()
+                    .map(|no_std| convert::no_std::SockAddr(no_std).into())
+                    .collect::<Vec<std::net::SocketAddr>>();
+
+    let listener = TcpListener::bind(addrs.as_slice())?;
+    listener.set_nonblocking(true)?;
+
+    Ok(Self { listener,
+              conns: Default::default(),
+              inbox: Default::default(),
+              sessions: Default::default(),
+              id: AtomicU16::new(0) })
+  }
+
+  fn send(&self, msg: Addrd<&[u8]>) -> nb::Result<(), Self::Error> {
+    let frame = match frame(msg.data()) {
+      | Some(frame) => frame,
+      | None => return Ok(()),
+    };
+
+    if !self.conns.lock().unwrap().contains_key(&msg.addr()) {
+      self.connect(msg.addr()).map_err(nb::Error::Other)?;
+    }
+
+    let mut conns = self.conns.lock().unwrap();
+    let conn = conns.get_mut(&msg.addr())
+                    .ok_or_else(|| nb::Error::Other(io::ErrorKind::NotConnected.into()))?;
+
+    let max = conn.csm.unwrap_or_default().max_message_size as usize;
+    if frame.len() > max {
+      let e = io::Error::new(io::ErrorKind::InvalidInput,
+                             format!("message of {} bytes exceeds peer's Max-Message-Size ({})",
+                                     frame.len(),
+                                     max));
+      return Err(nb::Error::Other(e));
+    }
+
+    conn.ws
+        .send(WsMessage::Binary(frame))
+        .map_err(ws_err)
+        .map_err(convert::io_to_nb)
+  }
+
+  fn recv(&self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
+    self.pump().map_err(nb::Error::Other)?;
+
+    let Addrd(dgram, addr) = self.inbox
+                                 .lock()
+                                 .unwrap()
+                                 .pop_front()
+                                 .ok_or(nb::Error::WouldBlock)?;
+    let n = dgram.len().min(buffer.len());
+    buffer[..n].copy_from_slice(&dgram[..n]);
+    Ok(Addrd(n, addr))
+  }
+
+  fn peek(&self, buffer: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
+    self.pump().map_err(nb::Error::Other)?;
+
+    let inbox = self.inbox.lock().unwrap();
+    let Addrd(dgram, addr) = inbox.front().ok_or(nb::Error::WouldBlock)?;
+    let n = dgram.len().min(buffer.len());
+    buffer[..n].copy_from_slice(&dgram[..n]);
+    Ok(Addrd(n, *addr))
+  }
+
+  fn poll_session(&self) -> Option<Addrd<Session>> {
+    self.sessions.lock().unwrap().pop_front()
+  }
+
+  /// Multicast is not possible over TCP, so this always returns `Err(io::ErrorKind::Unsupported)`.
+  fn join_multicast(&self, _: no_std_net::IpAddr) -> Result<(), Self::Error> {
+    Err(io::ErrorKind::Unsupported.into())
+  }
+
+  /// Multicast is not possible over TCP, so this always returns `Err(io::ErrorKind::Unsupported)`.
+  fn leave_multicast(&self, _: no_std_net::IpAddr) -> Result<(), Self::Error> {
+    Err(io::ErrorKind::Unsupported.into())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn frame_unframe_roundtrip() {
+    let udp = [0x41, 0x45, 0x00, 0x07, 0xAB, 0xFF, b'h', b'i'];
+    let ws = frame(&udp).unwrap();
+    assert_eq!(ws, vec![0x01, 0x45, 0xAB, 0xFF, b'h', b'i']);
+
+    let udp = unframe(&ws, 7).unwrap();
+    // Type is rewritten to NON
+    assert_eq!(udp, vec![0x51, 0x45, 0x00, 0x07, 0xAB, 0xFF, b'h', b'i']);
+  }
+
+  #[test]
+  fn unframe_rejects_bad_token_length() {
+    assert_eq!(unframe(&[0x09, 0x01], 0), None);
+    assert_eq!(unframe(&[0x02, 0x01, 0xAB], 0), None);
+    assert_eq!(unframe(&[], 0), None);
+  }
+
+  #[test]
+  fn parse_csm_reads_options() {
+    let dgram = unframe(&[0x00, signal::CSM, 0x22, 0x04, 0x00, 0x20], 0).unwrap();
+    let msg = Message::try_from_bytes(&dgram).unwrap();
+    assert_eq!(parse_csm(&msg),
+               Csm { max_message_size: 1024,
+                     block_wise_transfer: true });
+
+    let dgram = unframe(&csm(MAX_MESSAGE_SIZE), 0).unwrap();
+    let msg = Message::try_from_bytes(&dgram).unwrap();
+    assert_eq!(parse_csm(&msg), Csm::default());
+  }
+}
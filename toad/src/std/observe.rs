@@ -0,0 +1,165 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use toad_msg::Token;
+
+use crate::step::observe::{Persistence, Registration, RegistrationPath};
+
+/// [`Persistence`] that saves Observe registrations to a file,
+/// one registration per line:
+///
+/// ```text
+/// <addr> <token as hex> <seq or -> <path>
+/// ```
+///
+/// The file is replaced atomically when saving, and a missing file
+/// is treated as having no registrations.
+///
+/// ```no_run
+/// use toad::platform::Platform;
+/// use toad::step::runtime;
+/// use toad::std::observe::FilePersistence;
+/// use toad::std::{dtls, Platform as Std};
+///
+/// type Steps = runtime::std::Runtime<dtls::N, FilePersistence>;
+///
+/// let server = Std::<dtls::N, Steps>::try_new("0.0.0.0:5683", Default::default()).unwrap();
+/// server.steps()
+///       .persist_with(FilePersistence::new("observers.txt"))
+///       .unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilePersistence {
+  path: Option<PathBuf>,
+}
+
+impl FilePersistence {
+  /// Save registrations to the file at `path`
+  pub fn new(path: impl Into<PathBuf>) -> Self {
+    Self { path: Some(path.into()) }
+  }
+}
+
+fn invalid(line: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData,
+                 format!("invalid observe registration {:?}", line))
+}
+
+fn parse(line: &str) -> Option<Registration> {
+  let mut parts = line.splitn(4, ' ');
+  let addr = parts.next()?.parse().ok()?;
+
+  let token_hex = parts.next()?;
+  if token_hex.len() % 2 != 0 || token_hex.len() > 16 {
+    return None;
+  }
+  let token = (0..token_hex.len()).step_by(2)
+                                  .map(|ix| u8::from_str_radix(&token_hex[ix..ix + 2], 16).ok())
+                                  .collect::<Option<_>>()
+                                  .map(Token)?;
+
+  let seq = match parts.next()? {
+    | "-" => None,
+    | seq => Some(seq.parse().ok()?),
+  };
+  let path = RegistrationPath::from(parts.next()?);
+
+  Some(Registration { addr,
+                      token,
+                      path,
+                      seq })
+}
+
+impl Persistence for FilePersistence {
+  type Error = io::Error;
+
+  fn save<I>(&mut self, mut regs: I) -> io::Result<()>
+    where I: Iterator<Item = Registration>
+  {
+    let path = match &self.path {
+      | Some(path) => path,
+      | None => return Ok(()),
+    };
+
+    let tmp = path.with_extension("tmp");
+    let mut file = fs::File::create(&tmp)?;
+    regs.try_for_each(|reg| {
+          let token = reg.token
+                         .0
+                         .iter()
+                         .map(|b| format!("{:02x}", b))
+                         .collect::<String>();
+          let seq = reg.seq
+                       .map(|seq| seq.to_string())
+                       .unwrap_or_else(|| "-".into());
+          writeln!(file, "{} {} {} {}", reg.addr, token, seq, reg.path.as_str())
+        })?;
+    file.sync_all()?;
+
+    fs::rename(tmp, path)
+  }
+
+  fn load<F>(&mut self, mut f: F) -> io::Result<()>
+    where F: FnMut(Registration)
+  {
+    let path = match &self.path {
+      | Some(path) => path,
+      | None => return Ok(()),
+    };
+
+    let contents = match fs::read_to_string(path) {
+      | Ok(contents) => contents,
+      | Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+      | Err(e) => return Err(e),
+    };
+
+    contents.lines()
+            .filter(|line| !line.is_empty())
+            .try_for_each(|line| parse(line).map(&mut f).ok_or_else(|| invalid(line)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use tinyvec::array_vec;
+
+  use super::*;
+
+  fn reg(seq: Option<u32>, path: &str) -> Registration {
+    Registration { addr: "127.0.0.1:5683".parse().unwrap(),
+                   token: Token(array_vec![0x0a, 0xff]),
+                   path: RegistrationPath::from(path),
+                   seq }
+  }
+
+  #[test]
+  fn save_then_load_roundtrips() {
+    let path = std::env::temp_dir().join(format!("toad-observe-{}.txt", std::process::id()));
+    let regs = vec![reg(Some(12), "sensors/temp"), reg(None, "")];
+
+    let mut persist = FilePersistence::new(&path);
+    persist.save(regs.clone().into_iter()).unwrap();
+
+    let mut loaded = vec![];
+    FilePersistence::new(&path).load(|reg| loaded.push(reg))
+                               .unwrap();
+    fs::remove_file(&path).ok();
+
+    assert_eq!(loaded, regs);
+  }
+
+  #[test]
+  fn missing_file_has_no_registrations() {
+    let mut loaded = vec![];
+    FilePersistence::new("/definitely/does/not/exist.txt").load(|reg| loaded.push(reg))
+                                                          .unwrap();
+    assert!(loaded.is_empty());
+  }
+
+  #[test]
+  fn parse_rejects_garbage() {
+    assert_eq!(parse("not a registration"), None);
+    assert_eq!(parse("127.0.0.1:5683 abc - path"), None);
+  }
+}
@@ -0,0 +1,303 @@
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use no_std_net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use crate::net::Addrd;
+use crate::platform::Direction;
+
+/// <https://www.tcpdump.org/linktypes.html>
+///
+/// Packets begin with an IPv4 or IPv6 header,
+/// which lets Wireshark dissect the UDP datagram (and the CoAP
+/// message within) without any additional configuration.
+const LINKTYPE_RAW: u16 = 101;
+
+const BLOCK_SECTION_HEADER: u32 = 0x0A0D0D0A;
+const BLOCK_INTERFACE_DESCRIPTION: u32 = 0x00000001;
+const BLOCK_ENHANCED_PACKET: u32 = 0x00000006;
+
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B3C4D;
+
+const OPT_END: u16 = 0;
+const OPT_EPB_FLAGS: u16 = 2;
+
+const IP_PROTO_UDP: u8 = 17;
+const IP_TTL: u8 = 64;
+
+/// Writes datagrams sent & received by a platform to
+/// a [pcapng](https://www.ietf.org/archive/id/draft-tuexen-opsawg-pcapng-05.html)
+/// capture, so that they can be inspected using Wireshark.
+///
+/// Since the socket only sees UDP payloads, IP and UDP headers
+/// are synthesized for each packet using the local & remote socket addresses.
+///
+/// Inbound and outbound packets are distinguished using the
+/// `epb_flags` direction bits.
+///
+/// ```
+/// use no_std_net::SocketAddr;
+/// use toad::net::Addrd;
+/// use toad::platform::Direction;
+/// use toad::std::pcap::PcapNg;
+///
+/// let local: SocketAddr = "127.0.0.1:5683".parse().unwrap();
+/// let remote: SocketAddr = "127.0.0.1:5684".parse().unwrap();
+///
+/// let mut pcap = PcapNg::new(Vec::<u8>::new()).unwrap();
+/// pcap.write(local, Addrd(&[0x40, 0x01, 0x00, 0x01][..], remote), Direction::Outbound)
+///     .unwrap();
+///
+/// let bytes = pcap.into_inner();
+/// assert_eq!(&bytes[0..4], &[0x0A, 0x0D, 0x0D, 0x0A]);
+/// ```
+pub struct PcapNg<W> {
+  writer: W,
+}
+
+impl<W> core::fmt::Debug for PcapNg<W> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("PcapNg").finish_non_exhaustive()
+  }
+}
+
+impl<W> PcapNg<W> where W: Write
+{
+  /// Create a new capture, writing the pcapng section header
+  /// and interface description to `writer`.
+  pub fn new(mut writer: W) -> io::Result<Self> {
+    let mut shb = Vec::new();
+    shb.extend(BYTE_ORDER_MAGIC.to_le_bytes());
+    shb.extend(1u16.to_le_bytes());
+    shb.extend(0u16.to_le_bytes());
+    // section length not specified
+    shb.extend((-1i64).to_le_bytes());
+    write_block(&mut writer, BLOCK_SECTION_HEADER, &shb)?;
+
+    let mut idb = Vec::new();
+    idb.extend(LINKTYPE_RAW.to_le_bytes());
+    idb.extend(0u16.to_le_bytes());
+    // no snap length
+    idb.extend(0u32.to_le_bytes());
+    write_block(&mut writer, BLOCK_INTERFACE_DESCRIPTION, &idb)?;
+
+    writer.flush()?;
+    Ok(Self { writer })
+  }
+
+  /// Write a datagram sent to or received from `dgram.addr()`
+  /// by a socket bound to `local`.
+  pub fn write(&mut self,
+               local: SocketAddr,
+               dgram: Addrd<&[u8]>,
+               direction: Direction)
+               -> io::Result<()> {
+    let (src, dst) = match direction {
+      | Direction::Inbound => (dgram.addr(), local),
+      | Direction::Outbound => (local, dgram.addr()),
+    };
+
+    let packet = ip_packet(src, dst, dgram.data());
+
+    // microseconds (the default `if_tsresol`)
+    let ts = SystemTime::now().duration_since(UNIX_EPOCH)
+                              .map(|d| d.as_micros() as u64)
+                              .unwrap_or(0);
+
+    let mut epb = Vec::new();
+    epb.extend(0u32.to_le_bytes());
+    epb.extend(((ts >> 32) as u32).to_le_bytes());
+    epb.extend((ts as u32).to_le_bytes());
+    epb.extend((packet.len() as u32).to_le_bytes());
+    epb.extend((packet.len() as u32).to_le_bytes());
+    epb.extend(&packet);
+    pad(&mut epb);
+
+    let flags: u32 = match direction {
+      | Direction::Inbound => 0b01,
+      | Direction::Outbound => 0b10,
+    };
+    epb.extend(OPT_EPB_FLAGS.to_le_bytes());
+    epb.extend(4u16.to_le_bytes());
+    epb.extend(flags.to_le_bytes());
+    epb.extend(OPT_END.to_le_bytes());
+    epb.extend(0u16.to_le_bytes());
+
+    write_block(&mut self.writer, BLOCK_ENHANCED_PACKET, &epb)?;
+    self.writer.flush()
+  }
+
+  /// Get the underlying writer
+  pub fn into_inner(self) -> W {
+    self.writer
+  }
+}
+
+fn pad(bytes: &mut Vec<u8>) {
+  while bytes.len() % 4 != 0 {
+    bytes.push(0);
+  }
+}
+
+fn write_block<W: Write>(w: &mut W, ty: u32, body: &[u8]) -> io::Result<()> {
+  // block type, block length, body, block length
+  let len = (body.len() + 12) as u32;
+  w.write_all(&ty.to_le_bytes())?;
+  w.write_all(&len.to_le_bytes())?;
+  w.write_all(body)?;
+  w.write_all(&len.to_le_bytes())
+}
+
+fn checksum(bytes: impl IntoIterator<Item = u8>) -> u16 {
+  let mut bytes = bytes.into_iter();
+  let mut sum = 0u32;
+
+  while let Some(hi) = bytes.next() {
+    let lo = bytes.next().unwrap_or(0);
+    sum += u16::from_be_bytes([hi, lo]) as u32;
+  }
+
+  while sum > 0xFFFF {
+    sum = (sum & 0xFFFF) + (sum >> 16);
+  }
+
+  !(sum as u16)
+}
+
+fn udp_header(src: SocketAddr, dst: SocketAddr, pseudo: &[u8], payload: &[u8]) -> Vec<u8> {
+  let len = (payload.len() + 8) as u16;
+
+  let mut udp = Vec::with_capacity(8);
+  udp.extend(src.port().to_be_bytes());
+  udp.extend(dst.port().to_be_bytes());
+  udp.extend(len.to_be_bytes());
+  udp.extend([0, 0]);
+
+  let sum = checksum(pseudo.iter().chain(udp.iter()).chain(payload).copied());
+  // a computed checksum of 0 is transmitted as all ones
+  let sum = if sum == 0 { 0xFFFF } else { sum };
+  udp[6..8].copy_from_slice(&sum.to_be_bytes());
+
+  udp
+}
+
+/// The addresses of a packet must belong to the same
+/// family; if the local socket is bound to an address of a
+/// different family than the remote, the unspecified address is used instead.
+fn ip_packet(src: SocketAddr, dst: SocketAddr, payload: &[u8]) -> Vec<u8> {
+  let udp_len = (payload.len() + 8) as u16;
+
+  match (src.ip(), dst.ip()) {
+    | (IpAddr::V4(s), IpAddr::V4(d)) => ipv4_packet(s, d, src, dst, udp_len, payload),
+    | (IpAddr::V6(s), IpAddr::V6(d)) => ipv6_packet(s, d, src, dst, udp_len, payload),
+    | (IpAddr::V4(s), IpAddr::V6(_)) => {
+      ipv4_packet(s, Ipv4Addr::UNSPECIFIED, src, dst, udp_len, payload)
+    },
+    | (IpAddr::V6(_), IpAddr::V4(d)) => {
+      ipv4_packet(Ipv4Addr::UNSPECIFIED, d, src, dst, udp_len, payload)
+    },
+  }
+}
+
+fn ipv4_packet(s: Ipv4Addr,
+               d: Ipv4Addr,
+               src: SocketAddr,
+               dst: SocketAddr,
+               udp_len: u16,
+               payload: &[u8])
+               -> Vec<u8> {
+  let mut ip = Vec::with_capacity(20);
+  ip.push(0x45);
+  ip.push(0);
+  ip.extend((udp_len + 20).to_be_bytes());
+  // id
+  ip.extend([0, 0]);
+  // don't fragment
+  ip.extend([0x40, 0]);
+  ip.push(IP_TTL);
+  ip.push(IP_PROTO_UDP);
+  ip.extend([0, 0]);
+  ip.extend(s.octets());
+  ip.extend(d.octets());
+
+  let sum = checksum(ip.iter().copied());
+  ip[10..12].copy_from_slice(&sum.to_be_bytes());
+
+  let mut pseudo = Vec::with_capacity(12);
+  pseudo.extend(s.octets());
+  pseudo.extend(d.octets());
+  pseudo.extend([0, IP_PROTO_UDP]);
+  pseudo.extend(udp_len.to_be_bytes());
+
+  ip.extend(udp_header(src, dst, &pseudo, payload));
+  ip.extend(payload);
+  ip
+}
+
+fn ipv6_packet(s: Ipv6Addr,
+               d: Ipv6Addr,
+               src: SocketAddr,
+               dst: SocketAddr,
+               udp_len: u16,
+               payload: &[u8])
+               -> Vec<u8> {
+  let mut ip = Vec::with_capacity(40);
+  ip.extend([0x60, 0, 0, 0]);
+  ip.extend(udp_len.to_be_bytes());
+  ip.push(IP_PROTO_UDP);
+  ip.push(IP_TTL);
+  ip.extend(s.octets());
+  ip.extend(d.octets());
+
+  let mut pseudo = Vec::with_capacity(40);
+  pseudo.extend(s.octets());
+  pseudo.extend(d.octets());
+  pseudo.extend((udp_len as u32).to_be_bytes());
+  pseudo.extend([0, 0, 0, IP_PROTO_UDP]);
+
+  ip.extend(udp_header(src, dst, &pseudo, payload));
+  ip.extend(payload);
+  ip
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn checksum_rfc1071_example() {
+    // https://www.rfc-editor.org/rfc/rfc1071#section-3
+    assert_eq!(checksum([0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7]), !0xddf2);
+  }
+
+  #[test]
+  fn ipv4_header_checksum_verifies() {
+    let src: SocketAddr = "192.168.0.1:5683".parse().unwrap();
+    let dst: SocketAddr = "192.168.0.2:1234".parse().unwrap();
+    let pkt = ip_packet(src, dst, &[1, 2, 3]);
+
+    assert_eq!(pkt.len(), 20 + 8 + 3);
+    assert_eq!(checksum(pkt[0..20].iter().copied()), 0);
+    assert_eq!(&pkt[20..22], &5683u16.to_be_bytes());
+    assert_eq!(&pkt[22..24], &1234u16.to_be_bytes());
+  }
+
+  #[test]
+  fn blocks_are_32_bit_aligned() {
+    let local: SocketAddr = "[::1]:5683".parse().unwrap();
+    let remote: SocketAddr = "[::1]:5684".parse().unwrap();
+
+    let mut pcap = PcapNg::new(Vec::<u8>::new()).unwrap();
+    let header_len = pcap.writer.len();
+    pcap.write(local, Addrd(&[0x40, 0x01, 0x00][..], remote), Direction::Inbound)
+        .unwrap();
+
+    let bytes = pcap.into_inner();
+    let epb = &bytes[header_len..];
+    let len = u32::from_le_bytes(epb[4..8].try_into().unwrap()) as usize;
+
+    assert_eq!(len % 4, 0);
+    assert_eq!(epb.len(), len);
+    assert_eq!(&epb[len - 4..], &epb[4..8]);
+  }
+}
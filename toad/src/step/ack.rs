@@ -1,34 +1,220 @@
+use no_std_net::SocketAddr;
 use toad_array::Array;
-use toad_msg::{CodeKind, Type};
+use toad_msg::{Code, CodeKind, Id, Payload, Token, Type};
+use toad_stem::Stem;
 
 use super::{exec_inner_step, Step, StepOutput};
 use crate::net::Addrd;
-use crate::platform::{Effect, PlatformTypes};
+use crate::platform::{self, Effect, Message, PlatformTypes};
 use crate::req::Req;
 use crate::resp::Resp;
+use crate::time::Millis;
+
+/// How many CON requests are remembered, to tell
+/// whether their responses may be piggybacked
+const REQUESTS: usize = 16;
+
+/// A CON request received from a peer
+#[derive(Debug, Clone, PartialEq)]
+struct Con {
+  addr: SocketAddr,
+  id: Id,
+  token: Token,
+  /// Whether an Empty ACK has been sent for the request,
+  /// in which case its response may not be piggybacked
+  acked: bool,
+  /// Until `acked`, when to send an Empty ACK if no response has been sent.
+  ///
+  /// Afterwards, when the peer would have stopped retransmitting the request
+  /// and it can be forgotten.
+  deadline: Millis,
+}
 
 /// ACK incoming Confirmable messages
 ///
 /// See the [module documentation](crate::step::ack) for more
-#[derive(Debug, Clone, Copy)]
-pub struct Ack<S>(S);
-
-impl<S: Default> Default for Ack<S> {
-  fn default() -> Self {
-    Ack(Default::default())
-  }
+#[derive(Debug, Default)]
+pub struct Ack<S> {
+  inner: S,
+  cons: Stem<[Option<Con>; REQUESTS]>,
 }
 
 impl<S> Ack<S> {
   /// Create a new Ack step
   pub fn new(s: S) -> Self {
-    Self(s)
+    Self { inner: s,
+           cons: Default::default() }
+  }
+
+  /// Send Empty ACKs for requests that were not responded to in time,
+  /// and forget ACKed requests that the peer is done retransmitting
+  fn expire<P>(&self, snap: &platform::Snapshot<P>, effects: &mut P::Effects, now: Millis)
+    where P: PlatformTypes
+  {
+    self.cons.map_mut(|cons| {
+               for slot in cons.iter_mut() {
+                 match slot {
+                   | Some(con) if con.deadline <= now && con.acked => *slot = None,
+                   | Some(con) if con.deadline <= now => {
+                     effects.push(Effect::Send(Addrd(empty_ack(con.id), con.addr)));
+                     con.acked = true;
+                     con.deadline = forget_at(snap, now);
+                   },
+                   | _ => (),
+                 }
+               }
+             })
+  }
+
+  /// Handle an incoming CON request: ACK it now, or hold off so
+  /// that its response can be piggybacked
+  fn recvd_con<P>(&self,
+                  snap: &platform::Snapshot<P>,
+                  effects: &mut P::Effects,
+                  req: Addrd<&Message<P>>)
+    where P: PlatformTypes
+  {
+    let (addr, id) = (req.addr(), req.data().id);
+    let ack_now = |effects: &mut P::Effects| {
+      effects.push(Effect::Send(Addrd(empty_ack(id), addr)));
+    };
+
+    let now = match now(snap) {
+      | Some(now) => now,
+      | None => return ack_now(effects),
+    };
+
+    self.cons.map_mut(|cons| {
+               if let Some(con) = cons.iter().flatten().find(|c| c.addr == addr && c.id == id) {
+                 // A retransmission; ACK it again if our ACK was lost
+                 if con.acked {
+                   ack_now(effects);
+                 }
+                 return;
+               }
+
+               let acked = !snap.config.msg.piggyback_to(addr);
+               let deadline = if acked {
+                 ack_now(effects);
+                 forget_at(snap, now)
+               } else {
+                 // Respond before the peer would retransmit, or ACK with an Empty message
+                 let window = snap.config.msg.con.unacked_retry_strategy.range().start() / 2;
+                 Millis::new(now.0.saturating_add(window))
+               };
+
+               // When full, the request is not remembered, so it is ACKed now
+               // and its response will be sent separately.
+               match cons.iter_mut().find(|c| c.is_none()) {
+                 | Some(slot) => {
+                   *slot = Some(Con { addr,
+                                      id,
+                                      token: req.data().token,
+                                      acked,
+                                      deadline })
+                 },
+                 | None if !acked => ack_now(effects),
+                 | None => (),
+               }
+             })
+  }
+
+  /// The response `rep` is about to be sent
+  ///
+  /// Yields whether `rep` may be sent as-is: piggybacked responses
+  /// may only be sent when the request has not been ACKed already.
+  /// Separate responses are preceded by an Empty ACK if the request
+  /// has not been ACKed yet.
+  fn responding<P>(&self,
+                   snap: &platform::Snapshot<P>,
+                   effects: &mut P::Effects,
+                   rep: Addrd<&Message<P>>)
+                   -> bool
+    where P: PlatformTypes
+  {
+    let piggybacked = rep.data().ty == Type::Ack;
+
+    let is_req = |c: &Con| {
+      c.addr == rep.addr()
+      && !c.acked
+      && if piggybacked {
+        c.id == rep.data().id
+      } else {
+        c.token == rep.data().token
+      }
+    };
+
+    self.cons.map_mut(|cons| {
+               let waiting = cons.iter_mut().find(|c| matches!(c, Some(c) if is_req(c)));
+
+               match (piggybacked, waiting) {
+                 | (true, Some(slot)) => {
+                   *slot = None;
+                   true
+                 },
+                 | (true, None) => false,
+                 | (false, Some(Some(con))) => {
+                   effects.push(Effect::Send(Addrd(empty_ack(con.id), con.addr)));
+                   con.acked = true;
+                   con.deadline = now(snap).map(|now| forget_at(snap, now))
+                                           .unwrap_or(con.deadline);
+                   true
+                 },
+                 | (false, _) => true,
+               }
+             })
   }
 }
 
 type InnerPollReq<P> = Addrd<Req<P>>;
 type InnerPollResp<P> = Addrd<Resp<P>>;
 
+fn now<P>(snap: &platform::Snapshot<P>) -> Option<Millis>
+  where P: PlatformTypes
+{
+  Millis::try_from(snap.time.duration_since_epoch()).ok()
+}
+
+/// When an ACKed request can be forgotten, once the peer
+/// would have stopped retransmitting it
+fn forget_at<P>(snap: &platform::Snapshot<P>, now: Millis) -> Millis
+  where P: PlatformTypes
+{
+  let con = snap.config.msg.con;
+  let span = con.unacked_retry_strategy.max_time(con.max_attempts);
+  Millis::new(now.0.saturating_add(span.0))
+}
+
+/// An Empty ACK for the message with Id `id`
+///
+/// Empty messages carry no token, so unlike [`toad_msg::Message::ack`]
+/// the token of the acknowledged message is not echoed.
+#[doc = toad_macros::rfc_7252_doc!("4.1")]
+fn empty_ack<P>(id: Id) -> Message<P>
+  where P: PlatformTypes
+{
+  Message { id,
+            ty: Type::Ack,
+            ver: Default::default(),
+            token: Token(Default::default()),
+            code: Code::EMPTY,
+            opts: Default::default(),
+            payload: Payload(Default::default()) }
+}
+
+/// ACK `msg` if it is a separate (CON) response
+///
+/// Incoming CON responses are ACKed whether they were yielded by `poll_req` or `poll_resp`,
+/// and before any outer step (e.g. [`BufferResponses`](crate::step::buffer_responses::BufferResponses))
+/// buffers or drops them as duplicates, so that the peer stops retransmitting them.
+fn ack_con_response<P>(effects: &mut P::Effects, msg: Addrd<&Message<P>>)
+  where P: PlatformTypes
+{
+  if msg.data().ty == Type::Con && msg.data().code.kind() == CodeKind::Response {
+    effects.push(Effect::Send(Addrd(empty_ack(msg.data().id), msg.addr())));
+  }
+}
+
 impl<Inner: Step<P, PollReq = InnerPollReq<P>, PollResp = InnerPollResp<P>>, P: PlatformTypes>
   Step<P> for Ack<Inner>
 {
@@ -38,24 +224,28 @@ impl<Inner: Step<P, PollReq = InnerPollReq<P>, PollResp = InnerPollResp<P>>, P:
   type Inner = Inner;
 
   fn inner(&self) -> &Inner {
-    &self.0
+    &self.inner
   }
 
   fn poll_req(&self,
               snap: &crate::platform::Snapshot<P>,
               effects: &mut <P as PlatformTypes>::Effects)
               -> StepOutput<Self::PollReq, Inner::Error> {
-    match exec_inner_step!(self.0.poll_req(snap, effects), core::convert::identity) {
-      | Some(req)
-        if req.data().as_ref().ty == Type::Con
-           && req.data().as_ref().code.kind() == CodeKind::Request =>
-      {
-        effects.push(Effect::Send(Addrd(Resp::ack(req.as_ref().data()).into(), req.addr())));
-        Some(Ok(req))
-      },
-      | Some(req) => Some(Ok(req)),
-      | None => None,
+    if let Some(now) = now(snap) {
+      self.expire(snap, effects, now);
     }
+
+    let req = exec_inner_step!(self.inner.poll_req(snap, effects), core::convert::identity);
+    req.map(|req| {
+         let msg = req.as_ref().map(Req::msg);
+         if msg.data().ty == Type::Con && msg.data().code.kind() == CodeKind::Request {
+           self.recvd_con(snap, effects, msg);
+         } else {
+           ack_con_response::<P>(effects, msg);
+         }
+
+         Ok(req)
+       })
   }
 
   fn poll_resp(&self,
@@ -64,8 +254,32 @@ impl<Inner: Step<P, PollReq = InnerPollReq<P>, PollResp = InnerPollResp<P>>, P:
                token: toad_msg::Token,
                addr: no_std_net::SocketAddr)
                -> StepOutput<Self::PollResp, Inner::Error> {
-    exec_inner_step!(self.0.poll_resp(snap, effects, token, addr),
-                     core::convert::identity).map(Ok)
+    if let Some(now) = now(snap) {
+      self.expire(snap, effects, now);
+    }
+
+    let resp = exec_inner_step!(self.inner.poll_resp(snap, effects, token, addr),
+                                core::convert::identity);
+    resp.map(|resp| {
+          ack_con_response::<P>(effects, resp.as_ref().map(Resp::msg));
+          Ok(resp)
+        })
+  }
+
+  fn before_message_sent(&self,
+                         snap: &crate::platform::Snapshot<P>,
+                         effects: &mut <P as PlatformTypes>::Effects,
+                         msg: &mut Addrd<crate::platform::Message<P>>)
+                         -> Result<(), Self::Error> {
+    if msg.data().code.kind() == CodeKind::Response && !self.responding(snap, effects, msg.as_ref()) {
+      // The request was already ACKed with an Empty message (or we can't tell),
+      // and there may only be one ACK per Id (RFC 7252 §5.2.2),
+      // so send the response separately; CON with a new Id (provisioned by an inner step)
+      msg.data_mut().ty = Type::Con;
+      msg.data_mut().id = Id(0);
+    }
+
+    self.inner.before_message_sent(snap, effects, msg)
   }
 }
 
@@ -92,7 +306,7 @@ mod test {
     let msg = Msg { id: Id(1),
                     ty,
                     ver: Default::default(),
-                    token: Token(Default::default()),
+                    token: Token(tinyvec::array_vec!(1, 2)),
                     code,
                     opts: Default::default(),
                     payload: Payload(Default::default()) };
@@ -148,24 +362,46 @@ mod test {
       ]
   );
 
+  /// Assert that `effs` is a single Empty ACK of `msg`
+  fn assert_empty_ack(effs: &Vec<Effect<crate::test::Platform>>,
+                      msg: Addrd<&platform::Message<crate::test::Platform>>) {
+    assert_eq!(effs,
+               &vec![Effect::Send(Addrd(super::empty_ack(msg.data().id), msg.addr()))]);
+
+    match &effs[0] {
+      | Effect::Send(ack) => {
+        assert_eq!(ack.addr(), msg.addr());
+        assert_eq!(ack.data().ty, Type::Ack);
+        assert_eq!(ack.data().code, Code::EMPTY);
+        assert_eq!(ack.data().id, msg.data().id);
+        assert!(ack.data().token.0.is_empty());
+      },
+      | _ => unreachable!(),
+    }
+  }
+
   test::test_step!(
       GIVEN Ack::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
       WHEN inner_yields_con_request [
         (inner.poll_req => { Some(Ok(test_msg(Type::Con, Code::new(0, 01)).0)) })
       ]
-      THEN poll_req_should_ack [
+      THEN poll_req_should_wait_to_piggyback [
         (poll_req(_, _) should satisfy { |out| assert_eq!(out, Some(Ok(test_msg(Type::Con, Code::new(0, 01)).0))) }),
-        (effects == {
-          vec![
-            Effect::Send(
-              Addrd(
-                Resp::ack(&test_msg(Type::Con, Code::new(1, 01)).0.0)
-                  .into(),
-                crate::test::dummy_addr()
-              )
-            )
-          ]
-        })
+        (effects == { vec![] })
+      ]
+  );
+
+  test::test_step!(
+      GIVEN Ack::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+      WHEN inner_yields_con_response_to_poll_req [
+        (inner.poll_req => { Some(Ok(test_msg(Type::Con, Code::new(2, 05)).0)) })
+      ]
+      THEN poll_req_should_ack_response [
+        (poll_req(_, _) should satisfy { |out| assert_eq!(out, Some(Ok(test_msg(Type::Con, Code::new(2, 05)).0))) }),
+        (effects should satisfy { |effs| {
+          let req = test_msg(Type::Con, Code::new(2, 05)).0;
+          assert_empty_ack(effs, req.as_ref().map(Req::msg));
+        }})
       ]
   );
 
@@ -179,4 +415,130 @@ mod test {
         (effects == { vec![] })
       ]
   );
+
+  test::test_step!(
+      GIVEN Ack::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+      WHEN inner_yields_con_response [
+        (inner.poll_resp => { Some(Ok(test_msg(Type::Con, Code::new(2, 05)).1)) })
+      ]
+      THEN poll_resp_should_ack_with_empty_message [
+        (poll_resp(_, _, _, _) should satisfy { |out| assert_eq!(out, Some(Ok(test_msg(Type::Con, Code::new(2, 05)).1))) }),
+        (effects should satisfy { |effs| {
+          let resp = test_msg(Type::Con, Code::new(2, 05)).1;
+          assert_empty_ack(effs, resp.as_ref().map(Resp::msg));
+        }})
+      ]
+  );
+
+  type Mock = crate::test::MockStep<(), InnerPollReq, InnerPollResp, ()>;
+
+  /// Poll `sut` for a CON request
+  fn recv_con(sut: &Ack<Mock>,
+              snap: &crate::test::Snapshot)
+              -> (Addrd<Req<crate::test::Platform>>, Vec<Effect<crate::test::Platform>>) {
+    let req = test_msg(Type::Con, Code::GET).0;
+    let yielded = req.clone();
+    sut.inner()
+       .set_poll_req(move |_, _, _| Some(Ok(yielded.clone())));
+
+    let mut effs = vec![];
+    sut.poll_req(snap, &mut effs).unwrap().unwrap();
+    (req, effs)
+  }
+
+  #[test]
+  fn con_request_is_acked_at_once_when_not_piggybacking() {
+    let sut = Ack::<Mock>::default();
+    let mut snap = crate::test::snapshot();
+    snap.config.msg.piggyback = false;
+
+    let (req, effs) = recv_con(&sut, &snap);
+    assert_empty_ack(&effs, req.as_ref().map(Req::msg));
+  }
+
+  #[test]
+  fn piggybacked_response_is_sent_without_empty_ack() {
+    let sut = Ack::<Mock>::default();
+    let snap = crate::test::snapshot();
+
+    let (req, effs) = recv_con(&sut, &snap);
+    assert!(effs.is_empty());
+
+    let mut rep = req.as_ref().map(|r| Resp::ack(r).into());
+    let mut effs = vec![];
+    sut.before_message_sent(&snap, &mut effs, &mut rep).unwrap();
+    assert!(effs.is_empty());
+    assert_eq!(rep.data().ty, Type::Ack);
+    assert_eq!(rep.data().id, req.data().msg().id);
+  }
+
+  #[test]
+  fn separate_response_is_preceded_by_empty_ack() {
+    let sut = Ack::<Mock>::default();
+    let snap = crate::test::snapshot();
+
+    let (req, _) = recv_con(&sut, &snap);
+
+    let mut rep = req.as_ref().map(|r| Resp::non(r).into());
+    let mut effs = vec![];
+    sut.before_message_sent(&snap, &mut effs, &mut rep).unwrap();
+    assert_empty_ack(&effs, req.as_ref().map(Req::msg));
+    assert_eq!(rep.data().ty, Type::Non);
+  }
+
+  #[test]
+  fn unanswered_con_request_is_acked_before_peer_retransmits() {
+    let sut = Ack::<Mock>::default();
+    let mut snap = crate::test::snapshot();
+
+    let (req, _) = recv_con(&sut, &snap);
+    sut.inner().set_poll_req(|_, _, _| None);
+
+    let window = *snap.config.msg.con.unacked_retry_strategy.range().start() / 2;
+
+    let mut effs = vec![];
+    snap.time = crate::test::ClockMock::instant((window - 1) * 1000);
+    sut.poll_req(&snap, &mut effs);
+    assert!(effs.is_empty());
+
+    snap.time = crate::test::ClockMock::instant(window * 1000);
+    sut.poll_req(&snap, &mut effs);
+    assert_empty_ack(&effs, req.as_ref().map(Req::msg));
+  }
+
+  #[test]
+  fn piggybacked_response_to_acked_request_is_sent_separately() {
+    let sut = Ack::<Mock>::default();
+    let mut snap = crate::test::snapshot();
+
+    let (req, _) = recv_con(&sut, &snap);
+    sut.inner().set_poll_req(|_, _, _| None);
+
+    // no response in time, so the request is ACKed with an Empty message
+    snap.time = crate::test::ClockMock::instant(60_000 * 1000);
+    let mut effs = vec![];
+    sut.poll_req(&snap, &mut effs);
+    assert_empty_ack(&effs, req.as_ref().map(Req::msg));
+
+    let mut rep = req.as_ref().map(|r| Resp::ack(r).into());
+    let mut effs = vec![];
+    sut.before_message_sent(&snap, &mut effs, &mut rep).unwrap();
+    assert!(effs.is_empty());
+    assert_eq!(rep.data().ty, Type::Con);
+    assert_eq!(rep.data().id, toad_msg::Id(0));
+    assert_eq!(rep.data().token, req.data().msg().token);
+  }
+
+  #[test]
+  fn retransmitted_acked_request_is_acked_again() {
+    let sut = Ack::<Mock>::default();
+    let mut snap = crate::test::snapshot();
+    snap.config.msg.piggyback = false;
+
+    let (req, effs) = recv_con(&sut, &snap);
+    assert_empty_ack(&effs, req.as_ref().map(Req::msg));
+
+    let (_, effs) = recv_con(&sut, &snap);
+    assert_empty_ack(&effs, req.as_ref().map(Req::msg));
+  }
 }
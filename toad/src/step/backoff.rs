@@ -0,0 +1,416 @@
+use embedded_time::duration::Milliseconds;
+use no_std_net::SocketAddr;
+use toad_array::Array;
+use toad_len::Len;
+use toad_msg::{CodeKind, Id, MessageOptions, Token};
+use toad_stem::Stem;
+
+use super::{log, Step, StepOutput};
+use crate::net::Addrd;
+use crate::platform::{self, Effect, PlatformTypes};
+use crate::req::Req;
+use crate::resp::{code, Resp};
+use crate::time::Millis;
+
+/// How many requests are remembered so that they can be re-sent,
+/// when [`Msg.service_unavailable_retries`](crate::config::Msg#structfield.service_unavailable_retries)
+/// is nonzero
+const PENDING: usize = 16;
+
+/// How long to back off for when a 5.03 response has no Max-Age option
+///
+/// See [RFC7252 Section 5.10.5](https://www.rfc-editor.org/rfc/rfc7252#section-5.10.5)
+pub const DEFAULT_RETRY_AFTER: Millis = Milliseconds(60_000);
+
+/// Errors that can be encountered by [`Backoff`]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+  /// The inner step failed.
+  ///
+  /// This variant's Debug representation is completely
+  /// replaced by the inner type E's debug representation.
+  Inner(E),
+  /// The server responded 5.03 SERVICE UNAVAILABLE, and
+  /// asked not to be sent the request again for `retry_after`.
+  ServiceUnavailable {
+    /// The response's Max-Age, or [`DEFAULT_RETRY_AFTER`] if it had none
+    retry_after: Millis,
+  },
+}
+
+impl<E: core::fmt::Debug> core::fmt::Debug for Error<E> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    match self {
+      | Self::ServiceUnavailable { retry_after } => f.debug_struct("ServiceUnavailable")
+                                                     .field("retry_after", retry_after)
+                                                     .finish(),
+      | Self::Inner(e) => e.fmt(f),
+    }
+  }
+}
+
+impl<E> super::Error for Error<E> where E: super::Error {}
+
+impl<E> From<E> for Error<E> {
+  fn from(e: E) -> Self {
+    Error::Inner(e)
+  }
+}
+
+/// A request that has been sent, and may be re-sent
+/// if the server is unavailable
+#[derive(Debug)]
+pub struct Pending<P>
+  where P: PlatformTypes
+{
+  req: Addrd<platform::Message<P>>,
+  sent_at: Millis,
+  retries: u8,
+  retry_at: Option<Millis>,
+}
+
+impl<P> Pending<P> where P: PlatformTypes
+{
+  fn is(&self, addr: SocketAddr, token: Token) -> bool {
+    self.req.addr() == addr && self.req.data().token == token
+  }
+}
+
+/// Back off from servers that respond 5.03 SERVICE UNAVAILABLE
+///
+/// See the [module documentation](crate::step::backoff) for more
+#[derive(Debug)]
+pub struct Backoff<Inner, Buffer> {
+  inner: Inner,
+  pending: Stem<Buffer>,
+}
+
+impl<Inner, Buffer> Default for Backoff<Inner, Buffer>
+  where Inner: Default,
+        Buffer: Default
+{
+  fn default() -> Self {
+    Self { inner: Inner::default(),
+           pending: Stem::<Buffer>::default() }
+  }
+}
+
+impl<Inner, Buffer> Backoff<Inner, Buffer> {
+  /// Re-send requests whose back off has elapsed
+  fn resend_due<P>(&self, effects: &mut P::Effects, now: Millis)
+    where P: PlatformTypes,
+          Buffer: Array<Item = Pending<P>>
+  {
+    self.pending.map_mut(|pending| {
+                  pending.iter_mut()
+                         .filter(|p| matches!(p.retry_at, Some(at) if at <= now))
+                         .for_each(|p| {
+                           p.retry_at = None;
+
+                           // re-sent as a new message, with the same token
+                           let mut req = p.req.clone();
+                           req.data_mut().id = Id(0);
+                           effects.push(Effect::Send(req));
+                         })
+                });
+  }
+
+  /// Schedule the request with token `token` to be re-sent at `at`,
+  /// yielding whether it will be.
+  fn retry_at<P>(&self, addr: SocketAddr, token: Token, at: Millis, max_retries: u8) -> bool
+    where P: PlatformTypes,
+          Buffer: Array<Item = Pending<P>>
+  {
+    self.pending.map_mut(|pending| {
+                  match pending.iter().position(|p| p.is(addr, token)) {
+                    | Some(ix) if pending[ix].retries < max_retries => {
+                      pending[ix].retries += 1;
+                      pending[ix].retry_at = Some(at);
+                      true
+                    },
+                    | Some(ix) => {
+                      pending.remove(ix);
+                      false
+                    },
+                    | None => false,
+                  }
+                })
+  }
+
+  fn forget<P>(pending: &mut Buffer, addr: SocketAddr, token: Token)
+    where P: PlatformTypes,
+          Buffer: Array<Item = Pending<P>>
+  {
+    if let Some(ix) = pending.iter().position(|p| p.is(addr, token)) {
+      pending.remove(ix);
+    }
+  }
+}
+
+fn now<P>(snap: &platform::Snapshot<P>) -> Option<Millis>
+  where P: PlatformTypes
+{
+  Millis::try_from(snap.time.duration_since_epoch()).ok()
+}
+
+impl<P, E, Inner, Buffer> Step<P> for Backoff<Inner, Buffer>
+  where P: PlatformTypes,
+        E: super::Error,
+        Inner: Step<P, PollReq = Addrd<Req<P>>, PollResp = Addrd<Resp<P>>, Error = E>,
+        Buffer: Array<Item = Pending<P>>
+{
+  type PollReq = Addrd<Req<P>>;
+  type PollResp = Addrd<Resp<P>>;
+  type Error = Error<E>;
+  type Inner = Inner;
+
+  fn inner(&self) -> &Inner {
+    &self.inner
+  }
+
+  fn poll_req(&self,
+              snap: &platform::Snapshot<P>,
+              effects: &mut P::Effects)
+              -> StepOutput<Self::PollReq, Self::Error> {
+    if let Some(now) = now(snap) {
+      self.resend_due::<P>(effects, now);
+    }
+
+    self.inner
+        .poll_req(snap, effects)
+        .map(|r| r.map_err(|nb| nb.map(Error::Inner)))
+  }
+
+  fn poll_resp(&self,
+               snap: &platform::Snapshot<P>,
+               effects: &mut P::Effects,
+               token: Token,
+               addr: SocketAddr)
+               -> StepOutput<Self::PollResp, Self::Error> {
+    let now = now(snap);
+    if let Some(now) = now {
+      self.resend_due::<P>(effects, now);
+    }
+
+    let max_retries = snap.config.msg.service_unavailable_retries;
+
+    match self.inner.poll_resp(snap, effects, token, addr) {
+      | Some(Ok(resp)) if max_retries > 0 && resp.data().code() == code::SERVICE_UNAVAILABLE => {
+        let retry_after = resp.data()
+                              .msg()
+                              .max_age_seconds()
+                              .map(|s| Milliseconds(u64::from(s) * 1000))
+                              .unwrap_or(DEFAULT_RETRY_AFTER);
+
+        let retrying = now.map(|now| Milliseconds(now.0.saturating_add(retry_after.0)))
+                          .map(|at| self.retry_at::<P>(resp.addr(), token, at, max_retries))
+                          .unwrap_or(false);
+
+        if retrying {
+          log!(Backoff::poll_resp,
+               effects,
+               log::Level::Warn,
+               "{} is unavailable, re-sending {:?} in {}ms",
+               resp.addr(),
+               token,
+               retry_after.0);
+          Some(Err(nb::Error::WouldBlock))
+        } else {
+          Some(Err(nb::Error::Other(Error::ServiceUnavailable { retry_after })))
+        }
+      },
+      | Some(Ok(resp)) => {
+        self.pending
+            .map_mut(|pending| Self::forget::<P>(pending, resp.addr(), token));
+        Some(Ok(resp))
+      },
+      | other => other.map(|r| r.map_err(|nb| nb.map(Error::Inner))),
+    }
+  }
+
+  fn cancel(&self,
+            snap: &platform::Snapshot<P>,
+            effects: &mut P::Effects,
+            token: Token)
+            -> Result<(), Self::Error> {
+    self.inner.cancel(snap, effects, token)?;
+    self.pending.map_mut(|pending| {
+                  while let Some(ix) = pending.iter().position(|p| p.req.data().token == token) {
+                    pending.remove(ix);
+                  }
+                });
+    Ok(())
+  }
+
+  fn on_message_sent(&self,
+                     snap: &platform::Snapshot<P>,
+                     effects: &mut P::Effects,
+                     msg: &Addrd<platform::Message<P>>)
+                     -> Result<(), Self::Error> {
+    self.inner.on_message_sent(snap, effects, msg)?;
+
+    let now = now(snap);
+    let retrying = snap.config.msg.service_unavailable_retries > 0;
+    let request = msg.data().code.kind() == CodeKind::Request;
+
+    if let (Some(now), true, true) = (now, retrying, request) {
+      self.pending.map_mut(|pending| {
+                    // retransmissions & re-sent requests are already pending
+                    if pending.iter().any(|p| p.is(msg.addr(), msg.data().token)) {
+                      return;
+                    }
+
+                    // forget the oldest request to make room
+                    if pending.is_full() || pending.len() >= PENDING {
+                      let oldest = pending.iter()
+                                          .enumerate()
+                                          .min_by_key(|(_, p)| p.sent_at)
+                                          .map(|(ix, _)| ix);
+                      if let Some(ix) = oldest {
+                        pending.remove(ix);
+                      }
+                    }
+
+                    pending.push(Pending { req: msg.clone(),
+                                           sent_at: now,
+                                           retries: 0,
+                                           retry_at: None });
+                  });
+    }
+
+    Ok(())
+  }
+
+  fn snapshot_state<W>(&self, w: &mut W) -> core::fmt::Result
+    where W: core::fmt::Write
+  {
+    self.pending.map_ref(|pending| {
+                  writeln!(w, "Backoff: {} request(s) pending", pending.len())?;
+                  pending.iter()
+                         .filter_map(|p| p.retry_at.map(|at| (p, at)))
+                         .try_for_each(|(p, at)| {
+                           writeln!(w,
+                                    "  {} {:?} (re-sending at {}ms)",
+                                    p.req.addr(),
+                                    p.req.data().token,
+                                    at.0)
+                         })
+                })?;
+
+    self.inner.snapshot_state(w)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use tinyvec::array_vec;
+  use toad_msg::{Code, Payload, Type};
+
+  use super::*;
+  use crate::config::Config;
+  use crate::test::{self, Platform as P};
+
+  type InnerPollReq = Addrd<Req<P>>;
+  type InnerPollResp = Addrd<Resp<P>>;
+  type Mock = test::MockStep<(), InnerPollReq, InnerPollResp, ()>;
+  type Backoff = super::Backoff<Mock, Vec<Pending<P>>>;
+
+  fn msg(ty: Type, code: Code, id: u16) -> test::Message {
+    test::Message { ver: Default::default(),
+                    token: Token(array_vec!([u8; 8] => 1)),
+                    ty,
+                    code,
+                    id: Id(id),
+                    opts: Default::default(),
+                    payload: Payload(vec![]) }
+  }
+
+  fn snapshot(ms: u64, retries: u8) -> platform::Snapshot<P> {
+    let mut config = Config::default();
+    config.msg.service_unavailable_retries = retries;
+
+    platform::Snapshot { time: test::ClockMock::instant(ms * 1_000),
+                         recvd_dgram: None,
+                         recvd_identity: None,
+                         recvd_dest: None,
+                         session: None,
+                         config }
+  }
+
+  fn step(max_age: Option<u32>) -> Backoff {
+    let s = Backoff::default();
+    s.inner()
+     .set_poll_req(|_, _, _| None)
+     .set_poll_resp(move |_, _, _, _, _| {
+       let mut resp = msg(Type::Ack, code::SERVICE_UNAVAILABLE, 1);
+       if let Some(max_age) = max_age {
+         resp.set_max_age(max_age).unwrap();
+       }
+       Some(Ok(Addrd(Resp::from(resp), test::x.x.x.x(80))))
+     })
+     .set_on_message_sent(|_, _, _, _| Ok(()));
+    s
+  }
+
+  fn poll(s: &Backoff, snap: &platform::Snapshot<P>, effects: &mut Vec<test::Effect>)
+          -> StepOutput<InnerPollResp, Error<()>> {
+    Step::<P>::poll_resp(s,
+                         snap,
+                         effects,
+                         Token(array_vec!([u8; 8] => 1)),
+                         test::x.x.x.x(80))
+  }
+
+  #[test]
+  fn service_unavailable_is_yielded_without_retries() {
+    let s = step(Some(30));
+    let mut effects = vec![];
+    let req = Addrd(msg(Type::Con, Code::GET, 1), test::x.x.x.x(80));
+
+    Step::<P>::on_message_sent(&s, &snapshot(0, 0), &mut effects, &req).unwrap();
+    let resp = poll(&s, &snapshot(10, 0), &mut effects).unwrap().unwrap();
+    assert_eq!(resp.data().code(), code::SERVICE_UNAVAILABLE);
+    assert!(effects.iter()
+                   .all(|e| !matches!(e, test::Effect::Send(_))));
+  }
+
+  #[test]
+  fn service_unavailable_resends_after_max_age() {
+    let s = step(Some(30));
+    let mut effects = vec![];
+    let req = Addrd(msg(Type::Con, Code::GET, 1), test::x.x.x.x(80));
+
+    Step::<P>::on_message_sent(&s, &snapshot(0, 1), &mut effects, &req).unwrap();
+    assert_eq!(poll(&s, &snapshot(10, 1), &mut effects),
+               Some(Err(nb::Error::WouldBlock)));
+
+    effects.clear();
+    s.inner().set_poll_resp(|_, _, _, _, _| None);
+    poll(&s, &snapshot(30_009, 1), &mut effects);
+    assert!(effects.iter()
+                   .all(|e| !matches!(e, test::Effect::Send(_))));
+
+    poll(&s, &snapshot(30_010, 1), &mut effects);
+    let sent = effects.iter()
+                      .filter_map(|e| match e {
+                        | test::Effect::Send(m) => Some(m),
+                        | _ => None,
+                      })
+                      .collect::<Vec<_>>();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].data().code, Code::GET);
+    assert_eq!(sent[0].data().token, req.data().token);
+    assert_eq!(sent[0].data().id, Id(0));
+
+    // out of retries
+    let resent = sent[0].clone();
+    Step::<P>::on_message_sent(&s, &snapshot(30_010, 1), &mut effects, &resent).unwrap();
+    s.inner().set_poll_resp(|_, _, _, _, _| {
+               Some(Ok(Addrd(Resp::from(msg(Type::Ack, code::SERVICE_UNAVAILABLE, 2)),
+                             test::x.x.x.x(80))))
+             });
+    assert_eq!(poll(&s, &snapshot(30_020, 1), &mut effects),
+               Some(Err(nb::Error::Other(Error::ServiceUnavailable { retry_after:
+                                                                       DEFAULT_RETRY_AFTER }))));
+  }
+}
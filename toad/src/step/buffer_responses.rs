@@ -1,49 +1,299 @@
-use core::fmt::Write;
-
+use embedded_time::Instant;
 use no_std_net::SocketAddr;
 use toad_array::Array;
 use toad_len::Len;
-use toad_map::Map;
-use toad_msg::{Token, Type};
+use toad_map::{InsertError, Map};
+use toad_msg::{CodeKind, Token, Type};
+use tinyvec::ArrayVec;
 use toad_stem::Stem;
 
-use super::{Step, StepOutput};
+use super::{_try, log, Step, StepOutput};
 use crate::exec_inner_step;
+use crate::logging::list;
 use crate::net::Addrd;
-use crate::platform::{Effect, PlatformTypes};
+use crate::observe::notification_seq;
+use crate::platform::{self, PlatformTypes, Snapshot};
 use crate::req::Req;
 use crate::resp::Resp;
-use crate::todo::String;
+use crate::time::{self, Stamped};
 
 /// Struct responsible for buffering and yielding responses to the request
 /// we're polling for.
 ///
 /// For more information, see the [module documentation](crate::step::buffer_responses).
 #[derive(Debug)]
-pub struct BufferResponses<S, B> {
+pub struct BufferResponses<S, B, M, R> {
   buffer: Stem<B>,
+  multicast_reqs: Stem<M>,
+  requests: Stem<R>,
   inner: S,
 }
 
-impl<S: Default, B: Default> Default for BufferResponses<S, B> {
+impl<S: Default, B: Default, M: Default, R: Default> Default for BufferResponses<S, B, M, R> {
   fn default() -> Self {
     Self { buffer: Default::default(),
+           multicast_reqs: Default::default(),
+           requests: Default::default(),
            inner: S::default() }
   }
 }
 
-impl<S, B> BufferResponses<S, B> {
-  fn store<P>(&self, resp: Addrd<Resp<P>>)
+/// A response was received from a peer with a token
+/// that matches none of the requests we sent to that peer.
+///
+/// This usually means that the peer echoed the wrong token,
+/// and the request it was responding to will eventually time out.
+///
+/// Logged by [`BufferResponses`] with [`log::Level::Warn`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenMismatch {
+  /// The peer that sent the response
+  pub peer: SocketAddr,
+  /// Tokens of (up to 4 of) the requests sent to `peer`
+  /// that have not yet been responded to
+  pub expected: ArrayVec<[Token; 4]>,
+  /// Token of the response
+  pub received: Token,
+}
+
+impl core::fmt::Display for TokenMismatch {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    write!(f,
+           "response from {} has token {:?}, which matches none of the requests sent to it (expected one of {})",
+           self.peer,
+           self.received,
+           list(self.expected.iter()))
+  }
+}
+
+/// Key of the least recently buffered response (of `peer`, if specified)
+fn least_recent<P, B>(buf: &B, peer: Option<SocketAddr>) -> Option<(SocketAddr, Token, Type)>
+  where P: PlatformTypes,
+        B: Map<(SocketAddr, Token, Type), Stamped<P::Clock, Addrd<Resp<P>>>>
+{
+  buf.iter()
+     .filter(|((addr, _, _), _)| peer.map(|peer| *addr == peer).unwrap_or(true))
+     .min_by_key(|(_, resp)| resp.time())
+     .map(|(k, _)| *k)
+}
+
+impl<S, B, M, R> BufferResponses<S, B, M, R> {
+  /// Remember that a request was sent to a (unicast) peer,
+  /// forgetting the least recently sent request if there are too many.
+  fn track_request<P>(&self, msg: &Addrd<platform::Message<P>>, now: Instant<P::Clock>)
     where P: PlatformTypes,
-          B: Map<(SocketAddr, Token, Type), Addrd<Resp<P>>>
+          R: Map<Addrd<Token>, Instant<P::Clock>>
   {
-    let mut resp_removable = Some(resp);
-    self.buffer.map_mut(|buf| {
-                 let resp = Option::take(&mut resp_removable).unwrap();
-                 buf.insert((resp.addr(), resp.data().as_ref().token, resp.data().as_ref().ty),
-                            resp)
-                    .ok()
-               });
+    let key = Addrd(msg.data().token, msg.addr());
+
+    self.requests.map_mut(|reqs| {
+                   if !reqs.has(&key) && reqs.is_full() {
+                     let oldest = reqs.iter().min_by_key(|(_, sent)| **sent).map(|(k, _)| *k);
+                     oldest.map(|k| reqs.remove(&k));
+                   }
+
+                   reqs.insert(key, now).ok();
+                 });
+  }
+
+  /// A response was yielded; forget the request it responds to,
+  /// unless it is an Observe notification (more will follow with the same token).
+  fn forget_request<P>(&self, resp: &Addrd<Resp<P>>)
+    where P: PlatformTypes,
+          R: Map<Addrd<Token>, Instant<P::Clock>>
+  {
+    if notification_seq(resp.data().msg()).is_none() {
+      self.requests
+          .map_mut(|reqs| reqs.remove(&Addrd(resp.data().token(), resp.addr())));
+    }
+  }
+
+  /// Warn if `resp` was sent by a peer we sent requests to,
+  /// but its token matches none of them.
+  fn detect_token_mismatch<P>(&self, effects: &mut P::Effects, resp: &Addrd<Resp<P>>)
+    where P: PlatformTypes,
+          R: Map<Addrd<Token>, Instant<P::Clock>>
+  {
+    if resp.data().msg().code.kind() != CodeKind::Response {
+      return;
+    }
+
+    let peer = resp.addr();
+    let received = resp.data().token();
+
+    let mismatch = self.requests.map_ref(|reqs| {
+                                  let mut to_peer =
+                                    reqs.iter()
+                                        .filter(|(Addrd(_, addr), _)| *addr == peer)
+                                        .map(|(Addrd(token, _), _)| *token)
+                                        .peekable();
+
+                                  if to_peer.peek().is_none() {
+                                    return None;
+                                  }
+
+                                  let mut expected = ArrayVec::new();
+                                  for token in to_peer {
+                                    if token == received {
+                                      return None;
+                                    }
+
+                                    if expected.len() < expected.capacity() {
+                                      expected.push(token);
+                                    }
+                                  }
+
+                                  Some(TokenMismatch { peer,
+                                                       expected,
+                                                       received })
+                                });
+
+    if let Some(mismatch) = mismatch {
+      log!(BufferResponses::detect_token_mismatch,
+           effects,
+           log::Level::Warn,
+           "{}",
+           mismatch);
+    }
+  }
+
+  /// Buffer a response.
+  ///
+  /// If the peer that sent it already has
+  /// [`max_buffered_responses_per_peer`](crate::config::Msg#structfield.max_buffered_responses_per_peer)
+  /// responses buffered, the least recently buffered of them is dropped
+  /// (with a warning) to make room for this one.
+  ///
+  /// Otherwise if the buffer is full, the least recently buffered response
+  /// of any peer is dropped.
+  fn store<P, E>(&self,
+                 snap: &Snapshot<P>,
+                 effects: &mut P::Effects,
+                 resp: Addrd<Resp<P>>)
+                 -> Result<(), Error<E>>
+    where P: PlatformTypes,
+          B: Map<(SocketAddr, Token, Type), Stamped<P::Clock, Addrd<Resp<P>>>>
+  {
+    let key = (resp.addr(), resp.data().as_ref().token, resp.data().as_ref().ty);
+    let peer = resp.addr();
+    let per_peer = snap.config.msg.max_buffered_responses_per_peer;
+    let mut resp_removable = Some(Stamped(resp, snap.time));
+
+    let evicted = self.buffer.map_mut(|buf| {
+                                 if buf.has(&key) {
+                                   return None;
+                                 }
+
+                                 let peer_is_full = per_peer.map(|max| {
+                                                              buf.iter()
+                                                                 .filter(|((addr, _, _), _)| *addr == peer)
+                                                                 .count()
+                                                              >= max as usize
+                                                            })
+                                                            .unwrap_or(false);
+
+                                 let evict = if peer_is_full {
+                                   least_recent::<P, B>(buf, Some(peer))
+                                 } else if buf.is_full() {
+                                   least_recent::<P, B>(buf, None)
+                                 } else {
+                                   None
+                                 };
+
+                                 evict.and_then(|k| buf.remove(&k))
+                                      .map(|r| (peer_is_full, r.discard_timestamp()))
+                               });
+
+    match evicted {
+      | Some((true, evicted)) => log!(BufferResponses::store,
+                                      effects,
+                                      log::Level::Warn,
+                                      "{:?} has {} responses buffered; dropping its least recent ({:?}) to make room",
+                                      peer,
+                                      per_peer.unwrap_or(0),
+                                      evicted.data().token()),
+      | Some((false, evicted)) => log!(BufferResponses::store,
+                                       effects,
+                                       log::Level::Warn,
+                                       "response buffer {} is full; dropping least recent response from {:?} ({:?}) to make room",
+                                       core::any::type_name::<B>(),
+                                       evicted.addr(),
+                                       evicted.data().token()),
+      | None => (),
+    }
+
+    self.buffer.map_mut(|buf| match Option::take(&mut resp_removable) {
+                 | Some(resp) => match buf.insert(key, resp) {
+                   | Ok(()) | Err(InsertError::Exists(_)) => Ok(()),
+                   | Err(InsertError::CapacityExhausted) => Err(Error::BufferResponsesFull),
+                 },
+                 | None => Ok(()),
+               })
+  }
+
+  /// Poll for responses to a request sent to a multicast address.
+  ///
+  /// Responses may come from any number of peers, so they are matched only by token.
+  ///
+  /// All responses are buffered (discarding duplicates from the same peer) until
+  /// [`multicast_response_leisure`](crate::config::Msg.multicast_response_leisure)
+  /// has elapsed since the request was sent, at which point they will be yielded
+  /// one at a time until there are none left.
+  fn poll_multicast_resp<P, E>(&self,
+                               snap: &Snapshot<P>,
+                               effects: &mut P::Effects,
+                               resp: Option<Addrd<Resp<P>>>,
+                               token: Token)
+                               -> StepOutput<Addrd<Resp<P>>, Error<E>>
+    where P: PlatformTypes,
+          B: Map<(SocketAddr, Token, Type), Stamped<P::Clock, Addrd<Resp<P>>>>,
+          M: Map<Token, Instant<P::Clock>>
+  {
+    match resp {
+      | Some(resp)
+        if self.buffer.map_ref(|buf| {
+                        buf.has(&(resp.addr(), resp.data().as_ref().token, resp.data().as_ref().ty))
+                      }) =>
+      {
+        log!(BufferResponses::poll_multicast_resp,
+             effects,
+             log::Level::Trace,
+             "ignoring duplicate response from {:?} {:?}",
+             resp.addr(),
+             resp.data().token());
+      },
+      | Some(resp) => _try!(Result; self.store(snap, effects, resp)),
+      | None => (),
+    }
+
+    let leisure_elapsed = match self.multicast_reqs.map_ref(|m| m.get(&token).copied()) {
+      | Some(sent_at) => {
+        time::elapsed(sent_at, snap.time)
+          .map(|elapsed| elapsed >= snap.config.msg.multicast_response_leisure)
+          .unwrap_or(false)
+      },
+      | None => true,
+    };
+
+    if !leisure_elapsed {
+      return Some(Err(nb::Error::WouldBlock));
+    }
+
+    let next = self.buffer.map_mut(|buf| {
+                            let key = buf.iter()
+                                         .find(|((_, t, _), _)| *t == token)
+                                         .map(|(k, _)| *k);
+                            key.and_then(|k| buf.remove(&k))
+                               .map(Stamped::discard_timestamp)
+                          });
+
+    match next {
+      | Some(resp) => Some(Ok(resp)),
+      | None => {
+        self.multicast_reqs.map_mut(|m| m.remove(&token));
+        Some(Err(nb::Error::WouldBlock))
+      },
+    }
   }
 }
 
@@ -55,11 +305,12 @@ pub enum Error<E> {
   /// This variant's Debug representation is completely
   /// replaced by the inner type E's debug representation
   Inner(E),
-  /// Storing this response would exceed a hard capacity for the
-  /// response buffer.
+  /// The response buffer could not fit this response,
+  /// even after dropping a buffered response to make room.
   ///
-  /// Only applicable to [`BufferResponses`] that uses `ArrayVec` or
-  /// similar heapless backing structure.
+  /// When the buffer is full, buffered responses are dropped
+  /// to make room for new ones, so this is only yielded by a
+  /// [`BufferResponses`] whose backing structure has no capacity at all.
   BufferResponsesFull,
 }
 
@@ -81,10 +332,12 @@ impl<E: core::fmt::Debug> core::fmt::Debug for Error<E> {
 impl<E: super::Error> super::Error for Error<E> {}
 
 impl<P: PlatformTypes,
-      B: Map<(SocketAddr, Token, Type), Addrd<Resp<P>>>,
+      B: Map<(SocketAddr, Token, Type), Stamped<P::Clock, Addrd<Resp<P>>>>,
+      M: Map<Token, Instant<P::Clock>>,
+      R: Map<Addrd<Token>, Instant<P::Clock>>,
       E: super::Error,
       S: Step<P, PollReq = Addrd<Req<P>>, PollResp = Addrd<Resp<P>>, Error = E>> Step<P>
-  for BufferResponses<S, B>
+  for BufferResponses<S, B, M, R>
 {
   type PollReq = Addrd<Req<P>>;
   type PollResp = Addrd<Resp<P>>;
@@ -95,6 +348,39 @@ impl<P: PlatformTypes,
     &self.inner
   }
 
+  fn snapshot_state<W>(&self, w: &mut W) -> core::fmt::Result
+    where W: core::fmt::Write
+  {
+    self.buffer.map_ref(|buf| {
+                 writeln!(w, "BufferResponses: {} response(s) buffered", buf.len())?;
+                 buf.iter().try_for_each(|((addr, token, ty), resp)| {
+                              writeln!(w,
+                                       "  {} {:?} {:?} {:?}",
+                                       addr,
+                                       ty,
+                                       token,
+                                       resp.data().data().msg().code)
+                            })
+               })?;
+
+    self.multicast_reqs.map_ref(|reqs| {
+                         writeln!(w,
+                                  "BufferResponses: {} multicast request(s) awaiting responses",
+                                  reqs.len())?;
+                         reqs.iter()
+                             .try_for_each(|(token, sent)| writeln!(w, "  {:?} (sent at {:?})", token, sent))
+                       })?;
+
+    self.requests.map_ref(|reqs| {
+                   writeln!(w, "BufferResponses: {} request(s) awaiting responses", reqs.len())?;
+                   reqs.iter().try_for_each(|(Addrd(token, addr), sent)| {
+                                writeln!(w, "  {} {:?} (sent at {:?})", addr, token, sent)
+                              })
+                 })?;
+
+    self.inner.snapshot_state(w)
+  }
+
   fn poll_req(&self,
               snap: &crate::platform::Snapshot<P>,
               effects: &mut <P as PlatformTypes>::Effects)
@@ -110,41 +396,135 @@ impl<P: PlatformTypes,
                token: toad_msg::Token,
                addr: no_std_net::SocketAddr)
                -> StepOutput<Self::PollResp, Self::Error> {
+    if addr.ip().is_multicast() {
+      let resp = exec_inner_step!(run_anyway_when_would_block = true,
+                                  self.inner.poll_resp(snap, effects, token, addr),
+                                  Error::Inner);
+      return self.poll_multicast_resp(snap, effects, resp, token);
+    }
+
     let resp = exec_inner_step!(self.inner.poll_resp(snap, effects, token, addr),
                                 Error::Inner);
 
-    if self.buffer.map_ref(Len::is_full) {
-      return Some(Err(nb::Error::Other(Error::BufferResponsesFull)));
-    }
-
     let try_remove_from_buffer =
-      |ty: Type| self.buffer.map_mut(|buf| buf.remove(&(addr, token, ty)));
+      |ty: Type| {
+        self.buffer
+            .map_mut(|buf| buf.remove(&(addr, token, ty)).map(Stamped::discard_timestamp))
+      };
 
     let is_what_we_polled_for =
       |resp: &Addrd<Resp<_>>| resp.addr() == addr && resp.data().as_ref().token == token;
 
+    if let Some(resp) = resp.as_ref() {
+      self.detect_token_mismatch(effects, resp);
+    }
+
+    let yielded = |resp: Addrd<Resp<P>>| {
+      self.forget_request(&resp);
+      Some(Ok(resp))
+    };
+
     match resp {
-      | Some(resp) if is_what_we_polled_for(&resp) => Some(Ok(resp)),
+      | Some(resp) if is_what_we_polled_for(&resp) => yielded(resp),
       | Some(resp) => {
-        let mut msg = String::<1000>::default();
-        write!(&mut msg,
-               "polled for response to {:?}, got response with token {:?}",
-               token,
-               resp.data().token()).ok();
-        effects.push(Effect::Log(log::Level::Info, msg));
-        self.store(resp);
+        log!(BufferResponses::poll_resp,
+             effects,
+             log::Level::Info,
+             "polled for response to {:?}, got response with token {:?}",
+             token,
+             resp.data().token());
+        _try!(Result; self.store(snap, effects, resp));
 
         match try_remove_from_buffer(Type::Ack).or_else(|| try_remove_from_buffer(Type::Con))
                                                .or_else(|| try_remove_from_buffer(Type::Non))
                                                .or_else(|| try_remove_from_buffer(Type::Reset))
         {
-          | Some(resp) => Some(Ok(resp)),
+          | Some(resp) => yielded(resp),
           | None => Some(Err(nb::Error::WouldBlock)),
         }
       },
       | None => None,
     }
   }
+
+  fn cancel(&self,
+            snap: &Snapshot<P>,
+            effects: &mut P::Effects,
+            token: Token)
+            -> Result<(), Self::Error> {
+    self.inner
+        .cancel(snap, effects, token)
+        .map_err(Error::Inner)?;
+
+    self.buffer.map_mut(|buf| {
+                 while let Some(key) = buf.iter()
+                                          .map(|(key, _)| *key)
+                                          .find(|(_, t, _)| *t == token)
+                 {
+                   buf.remove(&key);
+                 }
+               });
+    self.multicast_reqs.map_mut(|reqs| reqs.remove(&token));
+    self.requests.map_mut(|reqs| {
+                   while let Some(key) = reqs.iter()
+                                             .map(|(key, _)| *key)
+                                             .find(|Addrd(t, _)| *t == token)
+                   {
+                     reqs.remove(&key);
+                   }
+                 });
+
+    log!(BufferResponses::cancel,
+         effects,
+         log::Level::Debug,
+         "{:?} cancelled, dropped buffered responses",
+         token);
+
+    Ok(())
+  }
+
+  fn before_message_sent(&self,
+                         snap: &Snapshot<P>,
+                         effects: &mut P::Effects,
+                         msg: &mut Addrd<platform::Message<P>>)
+                         -> Result<(), Self::Error> {
+    self.inner
+        .before_message_sent(snap, effects, msg)
+        .map_err(Error::Inner)?;
+
+    if msg.addr().ip().is_multicast()
+       && msg.data().code.kind() == CodeKind::Request
+       && msg.data().ty == Type::Con
+    {
+      log!(BufferResponses::before_message_sent,
+           effects,
+           log::Level::Warn,
+           "multicast requests must not be confirmable; sending {:?} as NON",
+           msg.data().token);
+      msg.as_mut().ty = Type::Non;
+    }
+
+    Ok(())
+  }
+
+  fn on_message_sent(&self,
+                     snap: &Snapshot<P>,
+                     effects: &mut P::Effects,
+                     msg: &Addrd<platform::Message<P>>)
+                     -> Result<(), Self::Error> {
+    self.inner
+        .on_message_sent(snap, effects, msg)
+        .map_err(Error::Inner)?;
+
+    if msg.addr().ip().is_multicast() && msg.data().code.kind() == CodeKind::Request {
+      self.multicast_reqs
+          .map_mut(|m| m.insert(msg.data().token, snap.time).ok());
+    } else if msg.data().code.kind() == CodeKind::Request {
+      self.track_request(msg, snap.time);
+    }
+
+    Ok(())
+  }
 }
 
 #[cfg(test)]
@@ -156,12 +536,15 @@ mod test {
 
   use super::*;
   use crate::step::test::test_step;
-  use crate::test::Platform as P;
+  use crate::test::{ClockMock, Platform as P};
 
   type InnerPollReq = Addrd<Req<P>>;
   type InnerPollResp = Addrd<Resp<P>>;
   type BufferResponses<S> =
-    super::BufferResponses<S, BTreeMap<(SocketAddr, Token, Type), Addrd<Resp<P>>>>;
+    super::BufferResponses<S,
+                           BTreeMap<(SocketAddr, Token, Type), Stamped<ClockMock, Addrd<Resp<P>>>>,
+                           BTreeMap<Token, Instant<ClockMock>>,
+                           BTreeMap<Addrd<Token>, Instant<ClockMock>>>;
 
   test_step!(
     GIVEN BufferResponses::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
@@ -362,4 +745,253 @@ mod test {
       )
     ]
   );
+
+  fn snapshot_at(millis: u64) -> Snapshot<P> {
+    Snapshot { time: ClockMock::instant(millis * 1000),
+               recvd_dgram: None,
+               recvd_identity: None,
+               recvd_dest: None,
+               session: None,
+               config: Default::default() }
+  }
+
+  fn multicast_msg(ty: Type) -> Addrd<platform::Message<P>> {
+    use toad_msg::*;
+
+    let msg = platform::Message::<P> { ver: Default::default(),
+                                       token: Token(array_vec!([u8; 8] => 1)),
+                                       ty,
+                                       code: Code::GET,
+                                       id: Id(1),
+                                       opts: Default::default(),
+                                       payload: Payload(vec![]) };
+
+    Addrd(msg, crate::multicast::all_coap_devices(5683))
+  }
+
+  fn resp_from(addr: SocketAddr) -> Option<nb::Result<InnerPollResp, ()>> {
+    resp_with_token(addr, 1)
+  }
+
+  fn resp_with_token(addr: SocketAddr, token: u8) -> Option<nb::Result<InnerPollResp, ()>> {
+    use toad_msg::*;
+
+    let msg = platform::Message::<P> { ver: Default::default(),
+                                       token: Token(array_vec!([u8; 8] => token)),
+                                       ty: Type::Non,
+                                       code: Code::new(2, 05),
+                                       id: Id(2),
+                                       opts: Default::default(),
+                                       payload: Payload(vec![]) };
+
+    Some(Ok(Addrd(msg.into(), addr)))
+  }
+
+  test_step!(
+    GIVEN BufferResponses::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+    WHEN buffered_response_cancelled [
+      (inner.poll_resp => { resp_from(crate::test::dummy_addr()) }),
+      ({|step: &BufferResponses<Dummy>| step.poll_resp(&snapshot_at(0), &mut vec![], Token(array_vec!([u8; 8] => 2)), crate::test::dummy_addr())}),
+      ({|step: &BufferResponses<Dummy>| step.cancel(&snapshot_at(0), &mut vec![], Token(array_vec!([u8; 8] => 1))).unwrap()}),
+      (inner.poll_resp => { resp_with_token(crate::test::dummy_addr(), 3) })
+    ]
+    THEN buffered_response_should_be_dropped [
+      (
+        poll_resp(
+          _,
+          _,
+          Token(array_vec!([u8; 8] => 1)),
+          crate::test::dummy_addr()
+        ) should satisfy { |out| assert_eq!(out, Some(Err(nb::Error::WouldBlock))) }
+      )
+    ]
+  );
+
+  test_step!(
+    GIVEN BufferResponses::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+    WHEN multicast_con_request_sent [
+      (inner.before_message_sent = { |_, _, _| Ok(()) })
+    ]
+    THEN request_should_be_non [
+      (before_message_sent(_, _, multicast_msg(Type::Con)) should be ok with { |msg| assert_eq!(msg.data().ty, Type::Non) })
+    ]
+  );
+
+  test_step!(
+    GIVEN BufferResponses::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+    WHEN multicast_response_received_during_leisure [
+      (inner.on_message_sent = { |_, _, _| Ok(()) }),
+      ({|step: &BufferResponses<Dummy>| step.on_message_sent(&snapshot_at(0), &mut vec![], &multicast_msg(Type::Non)).unwrap()}),
+      (inner.poll_resp => { resp_from(crate::test::dummy_addr()) }),
+      (snapshot = { snapshot_at(1000) })
+    ]
+    THEN response_should_be_held [
+      (
+        poll_resp(
+          _,
+          _,
+          Token(array_vec!([u8; 8] => 1)),
+          crate::multicast::all_coap_devices(5683)
+        ) should satisfy { |out| assert_eq!(out, Some(Err(nb::Error::WouldBlock))) }
+      )
+    ]
+  );
+
+  test_step!(
+    GIVEN BufferResponses::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+    WHEN multicast_leisure_elapses [
+      (inner.on_message_sent = { |_, _, _| Ok(()) }),
+      ({|step: &BufferResponses<Dummy>| step.on_message_sent(&snapshot_at(0), &mut vec![], &multicast_msg(Type::Non)).unwrap()}),
+      (inner.poll_resp => { resp_from(crate::test::dummy_addr()) }),
+      ({|step: &BufferResponses<Dummy>| step.poll_resp(&snapshot_at(1000), &mut vec![], Token(array_vec!([u8; 8] => 1)), crate::multicast::all_coap_devices(5683))}),
+      ({|step: &BufferResponses<Dummy>| step.poll_resp(&snapshot_at(1500), &mut vec![], Token(array_vec!([u8; 8] => 1)), crate::multicast::all_coap_devices(5683))}),
+      (inner.poll_resp => { resp_from(crate::test::dummy_addr_2()) }),
+      ({|step: &BufferResponses<Dummy>| step.poll_resp(&snapshot_at(2000), &mut vec![], Token(array_vec!([u8; 8] => 1)), crate::multicast::all_coap_devices(5683))}),
+      (inner.poll_resp => { Some(Err(nb::Error::WouldBlock)) }),
+      (snapshot = { snapshot_at(6000) })
+    ]
+    THEN responses_from_each_peer_should_be_yielded_once [
+      (
+        poll_resp(
+          _,
+          _,
+          Token(array_vec!([u8; 8] => 1)),
+          crate::multicast::all_coap_devices(5683)
+        ) should satisfy { |out| assert!(matches!(out, Some(Ok(_)))) }
+      ),
+      (
+        poll_resp(
+          _,
+          _,
+          Token(array_vec!([u8; 8] => 1)),
+          crate::multicast::all_coap_devices(5683)
+        ) should satisfy { |out| assert!(matches!(out, Some(Ok(_)))) }
+      ),
+      (
+        poll_resp(
+          _,
+          _,
+          Token(array_vec!([u8; 8] => 1)),
+          crate::multicast::all_coap_devices(5683)
+        ) should satisfy { |out| assert_eq!(out, Some(Err(nb::Error::WouldBlock))) }
+      )
+    ]
+  );
+
+  #[test]
+  fn chatty_peer_cannot_starve_others() {
+    crate::test::log_level(log::LevelFilter::Warn);
+
+    type Mock = crate::test::MockStep<(), InnerPollReq, InnerPollResp, ()>;
+
+    let s = BufferResponses::<Mock>::default();
+    let mut effects = vec![];
+
+    let snap = |millis: u64| {
+      let mut snap = snapshot_at(millis);
+      snap.config.msg.max_buffered_responses_per_peer = Some(2);
+      snap
+    };
+
+    let mut recv = |millis: u64, addr: SocketAddr, token: u8| {
+      s.inner()
+       .set_poll_resp(move |_, _, _, _, _| resp_with_token(addr, token));
+
+      // none of these are what we're polling for, so all are buffered
+      let out = s.poll_resp(&snap(millis),
+                            &mut effects,
+                            Token(array_vec!([u8; 8] => 99)),
+                            crate::test::dummy_addr_3());
+      assert_eq!(out, Some(Err(nb::Error::WouldBlock)));
+    };
+
+    recv(0, crate::test::dummy_addr_2(), 1);
+    (10..16).for_each(|token| recv(token as u64, crate::test::dummy_addr(), token));
+    recv(20, crate::test::dummy_addr_2(), 2);
+
+    let buffered = |addr: SocketAddr| {
+      s.buffer.map_ref(|buf| {
+                buf.iter()
+                   .filter(|((a, _, _), _)| *a == addr)
+                   .map(|((_, t, _), _)| *t)
+                   .collect::<Vec<_>>()
+              })
+    };
+
+    // only the 2 most recent responses of the chatty peer are kept
+    assert_eq!(buffered(crate::test::dummy_addr()),
+               vec![Token(array_vec!([u8; 8] => 14)), Token(array_vec!([u8; 8] => 15))]);
+
+    // and the other peer's responses are untouched
+    assert_eq!(buffered(crate::test::dummy_addr_2()),
+               vec![Token(array_vec!([u8; 8] => 1)), Token(array_vec!([u8; 8] => 2))]);
+
+    let warnings = effects.iter()
+                          .filter(|e| matches!(e, crate::platform::Effect::Log(log::Level::Warn, _)))
+                          .count();
+    assert_eq!(warnings, 4);
+  }
+
+  #[test]
+  fn response_with_unknown_token_is_diagnosed() {
+    crate::test::log_level(log::LevelFilter::Warn);
+
+    type Mock = crate::test::MockStep<(), InnerPollReq, InnerPollResp, ()>;
+
+    let s = BufferResponses::<Mock>::default();
+    let token = |t: u8| Token(array_vec!([u8; 8] => t));
+    let req_to = |addr: SocketAddr, t: u8| {
+      multicast_msg(Type::Con).map(|mut msg| {
+                                msg.token = token(t);
+                                msg
+                              })
+                              .with_addr(addr)
+    };
+
+    s.on_message_sent(&snapshot_at(0), &mut vec![], &req_to(crate::test::dummy_addr(), 1))
+     .unwrap();
+    s.on_message_sent(&snapshot_at(0), &mut vec![], &req_to(crate::test::dummy_addr(), 2))
+     .unwrap();
+
+    let warnings = |effects: &Vec<crate::test::Effect>| {
+      effects.iter()
+             .filter_map(|e| match e {
+               | crate::platform::Effect::Log(log::Level::Warn, msg) => Some(msg.as_str().to_string()),
+               | _ => None,
+             })
+             .collect::<Vec<_>>()
+    };
+
+    // the peer echoed a token we never sent it
+    let mut effects = vec![];
+    s.inner()
+     .set_poll_resp(|_, _, _, _, _| resp_with_token(crate::test::dummy_addr(), 3));
+    let out = s.poll_resp(&snapshot_at(1), &mut effects, token(1), crate::test::dummy_addr());
+    assert_eq!(out, Some(Err(nb::Error::WouldBlock)));
+
+    let expected = TokenMismatch { peer: crate::test::dummy_addr(),
+                                   expected: [token(1), token(2)].into_iter().collect(),
+                                   received: token(3) };
+    assert_eq!(warnings(&effects),
+               vec![format!("[BufferResponses::detect_token_mismatch] {}", expected)]);
+
+    // responses to requests we sent (and from peers we sent nothing to) are fine
+    let mut effects = vec![];
+    s.inner()
+     .set_poll_resp(|_, _, _, _, _| resp_with_token(crate::test::dummy_addr(), 2));
+    s.poll_resp(&snapshot_at(2), &mut effects, token(2), crate::test::dummy_addr())
+     .unwrap()
+     .unwrap();
+    s.inner()
+     .set_poll_resp(|_, _, _, _, _| resp_with_token(crate::test::dummy_addr_2(), 3));
+    s.poll_resp(&snapshot_at(3), &mut effects, token(1), crate::test::dummy_addr());
+    assert!(warnings(&effects).is_empty());
+
+    // once responded to, a request is no longer expected
+    let mut effects = vec![];
+    s.inner()
+     .set_poll_resp(|_, _, _, _, _| resp_with_token(crate::test::dummy_addr(), 2));
+    s.poll_resp(&snapshot_at(4), &mut effects, token(1), crate::test::dummy_addr());
+    assert_eq!(warnings(&effects).len(), 1);
+  }
 }
@@ -0,0 +1,150 @@
+use toad_array::Array;
+use toad_msg::TryIntoBytes;
+
+use super::{Step, StepOutput};
+use crate::net::{Addrd, Socket};
+use crate::platform::{self, Direction, Effect, PlatformTypes};
+use crate::req::Req;
+use crate::resp::Resp;
+
+type Dgram<P> = <<P as PlatformTypes>::Socket as Socket>::Dgram;
+
+/// Emit an [`Effect::Capture`] for every datagram sent or received
+///
+/// See the [module documentation](crate::step::capture) for more
+#[derive(Default, Debug, Clone, Copy)]
+pub struct Capture<S>(S);
+
+impl<S> Capture<S> {
+  /// Create a new Capture step
+  pub fn new(s: S) -> Self {
+    Self(s)
+  }
+
+  fn capture_recvd<P>(snap: &platform::Snapshot<P>, effects: &mut P::Effects)
+    where P: PlatformTypes
+  {
+    if let Some(dgram) = snap.recvd_dgram.as_ref() {
+      effects.push(Effect::Capture(dgram.clone(), Direction::Inbound));
+    }
+  }
+}
+
+impl<P, E, S> Step<P> for Capture<S>
+  where P: PlatformTypes,
+        E: super::Error,
+        S: Step<P, PollReq = Addrd<Req<P>>, PollResp = Addrd<Resp<P>>, Error = E>
+{
+  type PollReq = Addrd<Req<P>>;
+  type PollResp = Addrd<Resp<P>>;
+  type Error = E;
+  type Inner = S;
+
+  fn inner(&self) -> &S {
+    &self.0
+  }
+
+  fn poll_req(&self,
+              snap: &platform::Snapshot<P>,
+              effects: &mut P::Effects)
+              -> StepOutput<Self::PollReq, Self::Error> {
+    Self::capture_recvd(snap, effects);
+    self.0.poll_req(snap, effects)
+  }
+
+  fn poll_resp(&self,
+               snap: &platform::Snapshot<P>,
+               effects: &mut P::Effects,
+               token: toad_msg::Token,
+               addr: no_std_net::SocketAddr)
+               -> StepOutput<Self::PollResp, Self::Error> {
+    Self::capture_recvd(snap, effects);
+    self.0.poll_resp(snap, effects, token, addr)
+  }
+
+  fn on_message_sent(&self,
+                     snap: &platform::Snapshot<P>,
+                     effects: &mut P::Effects,
+                     msg: &Addrd<platform::Message<P>>)
+                     -> Result<(), Self::Error> {
+    self.0.on_message_sent(snap, effects, msg)?;
+
+    // The platform has already serialized this message
+    // successfully, so this should never fail.
+    if let Ok(dgram) = msg.data().clone().try_into_bytes::<Dgram<P>>() {
+      effects.push(Effect::Capture(Addrd(dgram, msg.addr()), Direction::Outbound));
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use tinyvec::{array_vec, ArrayVec};
+  use toad_msg::Token;
+
+  use super::*;
+  use crate::step::test::test_step;
+  use crate::test::{self, Platform as P};
+
+  type InnerPollReq = Addrd<Req<P>>;
+  type InnerPollResp = Addrd<Resp<P>>;
+
+  fn test_msg() -> Addrd<test::Message> {
+    use toad_msg::*;
+
+    let msg = test::Message { ver: Default::default(),
+                              token: Token(array_vec!([u8; 8] => 1)),
+                              ty: Type::Con,
+                              code: Code::GET,
+                              id: Id(1),
+                              opts: Default::default(),
+                              payload: Payload(vec![]) };
+
+    Addrd(msg, test::x.x.x.x(80))
+  }
+
+  fn test_dgram() -> Addrd<ArrayVec<[u8; 1024]>> {
+    test_msg().map(|msg| msg.try_into_bytes().unwrap())
+  }
+
+  test_step!(
+    GIVEN Capture::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+    WHEN dgram_received [
+      (inner.poll_req => { None }),
+      (snapshot = { platform::Snapshot { time: test::ClockMock::instant(0),
+                                         recvd_dgram: Some(test_dgram()),
+                                         recvd_identity: None,
+                                         recvd_dest: None,
+                                         session: None,
+                                         config: Default::default() } })
+    ]
+    THEN capture_inbound [
+      (poll_req(_, _) should satisfy { |out| assert!(out.is_none()) }),
+      (effects should satisfy { |effs| assert_eq!(effs, &vec![Effect::Capture(test_dgram(), Direction::Inbound)]) })
+    ]
+  );
+
+  test_step!(
+    GIVEN Capture::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+    WHEN nothing_received [
+      (inner.poll_resp => { None })
+    ]
+    THEN capture_nothing [
+      (poll_resp(_, _, Token(Default::default()), test::x.x.x.x(80)) should satisfy { |out| assert!(out.is_none()) }),
+      (effects should satisfy { |effs| assert!(effs.is_empty()) })
+    ]
+  );
+
+  test_step!(
+    GIVEN Capture::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+    WHEN message_sent [
+      (inner.on_message_sent = { |_, _, _| Ok(()) })
+    ]
+    THEN capture_outbound [
+      (on_message_sent(_, test_msg()) should satisfy { |r| assert!(r.is_ok()) }),
+      (effects should satisfy { |effs| assert_eq!(effs, &vec![Effect::Capture(test_dgram(), Direction::Outbound)]) })
+    ]
+  );
+}
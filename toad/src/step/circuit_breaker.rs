@@ -0,0 +1,513 @@
+use no_std_net::SocketAddr;
+use toad_msg::{Code, CodeKind, Id, Token, Type};
+use toad_stem::Stem;
+
+use super::{log, Step, StepOutput};
+use crate::net::health::{Breaker, Health, Outcome, PeerHealth, Unhealthy};
+use crate::net::Addrd;
+use crate::platform::{self, PlatformTypes};
+use crate::req::Req;
+use crate::resp::Resp;
+use crate::time::Millis;
+
+/// How many peers' health is tracked
+const PEERS: usize = 16;
+
+/// How many outstanding requests are watched for a response
+const PENDING: usize = 16;
+
+/// Errors that can be encountered by [`CircuitBreaker`]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+  /// The inner step failed.
+  ///
+  /// This variant's Debug representation is completely
+  /// replaced by the inner type E's debug representation.
+  Inner(E),
+  /// The request was not sent because too many of the
+  /// recent requests to this peer failed.
+  PeerUnhealthy(Unhealthy),
+}
+
+impl<E: core::fmt::Debug> core::fmt::Debug for Error<E> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    match self {
+      | Self::PeerUnhealthy(u) => f.debug_tuple("PeerUnhealthy").field(u).finish(),
+      | Self::Inner(e) => e.fmt(f),
+    }
+  }
+}
+
+impl<E> super::Error for Error<E> where E: super::Error {}
+
+impl<E> From<E> for Error<E> {
+  fn from(e: E) -> Self {
+    Error::Inner(e)
+  }
+}
+
+/// A request that has been sent and not yet responded to
+#[derive(Debug, Clone, PartialEq)]
+struct Pending {
+  addr: SocketAddr,
+  id: Id,
+  token: Token,
+  deadline: Millis,
+}
+
+#[derive(Debug, Default)]
+struct State {
+  health: Health<PEERS>,
+  pending: [Option<Pending>; PENDING],
+}
+
+impl State {
+  fn record<P>(&mut self, effects: &mut P::Effects, addr: SocketAddr, now: Millis, outcome: Outcome)
+    where P: PlatformTypes
+  {
+    let was_healthy = self.health.check(addr, now).is_ok();
+    self.health.record(addr, now, outcome);
+
+    if let (true, Err(Unhealthy { until, .. })) = (was_healthy, self.health.check(addr, now)) {
+      log!(CircuitBreaker::record,
+           effects,
+           log::Level::Warn,
+           "{} failed {} times in a row, not sending requests to it until {}ms",
+           addr,
+           self.health.breaker().trip_after,
+           until.0);
+    }
+  }
+
+  /// Count requests that outlived their deadline as timed out
+  fn expire<P>(&mut self, effects: &mut P::Effects, now: Millis)
+    where P: PlatformTypes
+  {
+    for ix in 0..PENDING {
+      match &self.pending[ix] {
+        | Some(Pending { addr, deadline, .. }) if *deadline <= now => {
+          let addr = *addr;
+          self.pending[ix] = None;
+          self.record::<P>(effects, addr, now, Outcome::Timeout);
+        },
+        | _ => (),
+      }
+    }
+  }
+
+  /// Conclude the request that the dgram `bytes` received from `addr` is a reply to, if any
+  fn recvd<P>(&mut self, effects: &mut P::Effects, now: Millis, addr: SocketAddr, bytes: &[u8])
+    where P: PlatformTypes
+  {
+    let (ty, code, id, token) = match header(bytes) {
+      | Some(h) => h,
+      | None => return,
+    };
+
+    // Empty ACKs & RESETs are matched to requests by Id,
+    // responses by token.
+    let (outcome, by_id) = match (ty, code.kind()) {
+      | (Type::Reset, _) => (Outcome::Reset, true),
+      | (Type::Ack, _) if code == Code::EMPTY => (Outcome::Success, true),
+      | (_, CodeKind::Response) => (Outcome::Success, false),
+      | _ => return,
+    };
+    let is_reply =
+      |p: &Pending| p.addr == addr && if by_id { p.id == id } else { p.token == token };
+
+    let ix = self.pending
+                 .iter()
+                 .position(|p| matches!(p, Some(p) if is_reply(p)));
+
+    if let Some(ix) = ix {
+      self.pending[ix] = None;
+      self.record::<P>(effects, addr, now, outcome);
+    }
+  }
+
+  fn sent(&mut self, addr: SocketAddr, id: Id, token: Token, deadline: Millis) {
+    let retransmission = self.pending
+                             .iter()
+                             .flatten()
+                             .any(|p| p.addr == addr && p.id == id);
+
+    if !retransmission {
+      // When full, the request is not watched and will not count
+      // towards the peer's health.
+      if let Some(slot) = self.pending.iter_mut().find(|p| p.is_none()) {
+        *slot = Some(Pending { addr,
+                               id,
+                               token,
+                               deadline });
+      }
+    }
+  }
+}
+
+/// Read the type, code, id & token of a message without parsing
+/// the rest of it
+fn header(bytes: &[u8]) -> Option<(Type, Code, Id, Token)> {
+  let tkl = (*bytes.first()? & 0b1111) as usize;
+  if tkl > 8 || bytes.len() < 4 + tkl {
+    return None;
+  }
+
+  let ty = Type::try_from((bytes[0] >> 4) & 0b11).ok()?;
+  let code = Code::from(bytes[1]);
+  let id = Id(u16::from_be_bytes([bytes[2], bytes[3]]));
+  let token = Token(bytes[4..4 + tkl].iter().copied().collect());
+
+  Some((ty, code, id, token))
+}
+
+fn now<P>(snap: &platform::Snapshot<P>) -> Option<Millis>
+  where P: PlatformTypes
+{
+  Millis::try_from(snap.time.duration_since_epoch()).ok()
+}
+
+/// Stop sending requests to peers that keep failing to respond
+///
+/// See the [module documentation](crate::step::circuit_breaker) for more
+#[derive(Debug, Default)]
+pub struct CircuitBreaker<S> {
+  inner: S,
+  state: Stem<State>,
+}
+
+impl<S> CircuitBreaker<S> {
+  /// Create a new CircuitBreaker step using the default [`Breaker`]
+  pub fn new(inner: S) -> Self {
+    Self { inner,
+           state: Default::default() }
+  }
+
+  /// Use `breaker` to decide when peers are unhealthy
+  pub fn with_breaker(self, breaker: Breaker) -> Self {
+    self.state.map_mut(|s| s.health = Health::new(breaker));
+    self
+  }
+
+  /// Get the health of a peer, if any requests have been sent to it
+  pub fn health(&self, addr: SocketAddr) -> Option<PeerHealth> {
+    self.state.map_ref(|s| s.health.get(addr))
+  }
+
+  fn on_poll<P>(&self, snap: &platform::Snapshot<P>, effects: &mut P::Effects)
+    where P: PlatformTypes
+  {
+    let now = match now(snap) {
+      | Some(now) => now,
+      | None => return,
+    };
+
+    self.state.map_mut(|s| {
+                if let Some(dgram) = snap.recvd_dgram.as_ref() {
+                  s.recvd::<P>(effects, now, dgram.addr(), &dgram.data()[..]);
+                }
+
+                s.expire::<P>(effects, now);
+              });
+  }
+}
+
+impl<P, E, S> Step<P> for CircuitBreaker<S>
+  where P: PlatformTypes,
+        E: super::Error,
+        S: Step<P, PollReq = Addrd<Req<P>>, PollResp = Addrd<Resp<P>>, Error = E>
+{
+  type PollReq = Addrd<Req<P>>;
+  type PollResp = Addrd<Resp<P>>;
+  type Error = Error<E>;
+  type Inner = S;
+
+  fn inner(&self) -> &S {
+    &self.inner
+  }
+
+  fn poll_req(&self,
+              snap: &platform::Snapshot<P>,
+              effects: &mut P::Effects)
+              -> StepOutput<Self::PollReq, Self::Error> {
+    self.on_poll(snap, effects);
+    self.inner
+        .poll_req(snap, effects)
+        .map(|r| r.map_err(|nb| nb.map(Error::Inner)))
+  }
+
+  fn poll_resp(&self,
+               snap: &platform::Snapshot<P>,
+               effects: &mut P::Effects,
+               token: Token,
+               addr: SocketAddr)
+               -> StepOutput<Self::PollResp, Self::Error> {
+    self.on_poll(snap, effects);
+    self.inner
+        .poll_resp(snap, effects, token, addr)
+        .map(|r| r.map_err(|nb| nb.map(Error::Inner)))
+  }
+
+  fn cancel(&self,
+            snap: &platform::Snapshot<P>,
+            effects: &mut P::Effects,
+            token: Token)
+            -> Result<(), Self::Error> {
+    self.inner.cancel(snap, effects, token)?;
+    self.state.map_mut(|s| {
+                s.pending
+                 .iter_mut()
+                 .filter(|p| matches!(p, Some(p) if p.token == token))
+                 .for_each(|p| *p = None)
+              });
+    Ok(())
+  }
+
+  fn before_message_sent(&self,
+                         snap: &platform::Snapshot<P>,
+                         effects: &mut P::Effects,
+                         msg: &mut Addrd<platform::Message<P>>)
+                         -> Result<(), Self::Error> {
+    self.inner.before_message_sent(snap, effects, msg)?;
+
+    let now = match now(snap) {
+      | Some(now) => now,
+      | None => return Ok(()),
+    };
+
+    if msg.data().code.kind() != CodeKind::Request {
+      return Ok(());
+    }
+
+    let unhealthy = self.state.map_ref(|s| s.health.check(msg.addr(), now));
+    unhealthy.map_err(|u| {
+               log!(CircuitBreaker::before_message_sent,
+                    effects,
+                    log::Level::Warn,
+                    "not sending {} {:?} to unhealthy peer {} (until {}ms)",
+                    msg.data().code,
+                    msg.data().token,
+                    u.addr,
+                    u.until.0);
+               Error::PeerUnhealthy(u)
+             })
+  }
+
+  fn on_message_sent(&self,
+                     snap: &platform::Snapshot<P>,
+                     effects: &mut P::Effects,
+                     msg: &Addrd<platform::Message<P>>)
+                     -> Result<(), Self::Error> {
+    self.inner.on_message_sent(snap, effects, msg)?;
+
+    let now = now(snap);
+    let unicast = !msg.addr().ip().is_multicast();
+    // NON requests may legitimately go unanswered, so only CONs
+    // (which are always ACKed or RESET) count towards the peer's health
+    let con_request = msg.data().ty == Type::Con && msg.data().code.kind() == CodeKind::Request;
+
+    if let (Some(now), true, true) = (now, unicast, con_request) {
+      let wait = snap.config.timing().max_transmit_wait;
+      let deadline = Millis::new(now.0.saturating_add(wait.0));
+      self.state
+          .map_mut(|s| s.sent(msg.addr(), msg.data().id, msg.data().token, deadline));
+    }
+
+    Ok(())
+  }
+
+  fn snapshot_state<W>(&self, w: &mut W) -> core::fmt::Result
+    where W: core::fmt::Write
+  {
+    self.state.map_ref(|s| {
+                 let pending = s.pending.iter().flatten().count();
+                 writeln!(w, "CircuitBreaker: {} request(s) pending", pending)?;
+                 s.health.iter().try_for_each(|(addr, h)| {
+                                  writeln!(w,
+                                           "  {} failure rate {}‰, {} consecutive failure(s){}",
+                                           addr,
+                                           h.failure_rate(),
+                                           h.consecutive_failures(),
+                                           if h.unhealthy_until().is_some() {
+                                             ", tripped"
+                                           } else {
+                                             ""
+                                           })
+                                })
+               })?;
+
+    self.inner.snapshot_state(w)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use embedded_time::duration::Milliseconds;
+  use tinyvec::array_vec;
+  use toad_msg::{Payload, TryIntoBytes};
+
+  use super::*;
+  use crate::config::Config;
+  use crate::step::test::test_step;
+  use crate::test::{self, Platform as P};
+
+  type InnerPollReq = Addrd<Req<P>>;
+  type InnerPollResp = Addrd<Resp<P>>;
+  type Mock = test::MockStep<(), InnerPollReq, InnerPollResp, ()>;
+
+  fn msg(ty: Type, code: Code, id: u16, token: u8) -> test::Message {
+    test::Message { ver: Default::default(),
+                    token: Token(array_vec!([u8; 8] => token)),
+                    ty,
+                    code,
+                    id: Id(id),
+                    opts: Default::default(),
+                    payload: Payload(vec![]) }
+  }
+
+  fn req(id: u16, token: u8) -> Addrd<test::Message> {
+    Addrd(msg(Type::Con, Code::GET, id, token), test::x.x.x.x(80))
+  }
+
+  fn snapshot(ms: u64, recvd: Option<test::Message>) -> platform::Snapshot<P> {
+    let dgram = recvd.map(|m| Addrd(m.try_into_bytes().unwrap(), test::x.x.x.x(80)));
+
+    platform::Snapshot { time: test::ClockMock::instant(ms * 1_000),
+                         recvd_dgram: dgram,
+                         recvd_identity: None,
+                         recvd_dest: None,
+                         session: None,
+                         config: Default::default() }
+  }
+
+  fn step() -> CircuitBreaker<Mock> {
+    let s = CircuitBreaker::<Mock>::default().with_breaker(Breaker { trip_after: 2,
+                                                                     cool_down:
+                                                                       Milliseconds(1_000) });
+    s.inner()
+     .set_poll_req(|_, _, _| None)
+     .set_poll_resp(|_, _, _, _, _| None)
+     .set_before_message_sent(|_, _, _, _| Ok(()))
+     .set_on_message_sent(|_, _, _, _| Ok(()));
+    s
+  }
+
+  test_step!(
+    GIVEN CircuitBreaker::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+    WHEN nothing_received [
+      (inner.poll_resp => { None })
+    ]
+    THEN nothing_happens [
+      (poll_resp(_, _, Token(Default::default()), test::x.x.x.x(80)) should satisfy { |out| assert!(out.is_none()) }),
+      (effects should satisfy { |effs| assert!(effs.is_empty()) })
+    ]
+  );
+
+  #[test]
+  fn header_reads_type_code_id_and_token() {
+    let bytes: Vec<u8> = msg(Type::Ack, Code::new(2, 5), 300, 9).try_into_bytes()
+                                                                .unwrap();
+    assert_eq!(header(&bytes),
+               Some((Type::Ack, Code::new(2, 5), Id(300), Token(array_vec!([u8; 8] => 9)))));
+    assert_eq!(header(&bytes[..4]), None);
+    assert_eq!(header(&[]), None);
+  }
+
+  #[test]
+  fn fails_fast_after_consecutive_timeouts() {
+    test::log_level(log::LevelFilter::Warn);
+
+    let wait = Config::default().timing().max_transmit_wait.0;
+    let s = step();
+    let mut effects = vec![];
+
+    // sent & retransmitted; still one request
+    Step::<P>::on_message_sent(&s, &snapshot(0, None), &mut effects, &req(1, 1)).unwrap();
+    Step::<P>::on_message_sent(&s, &snapshot(10, None), &mut effects, &req(1, 1)).unwrap();
+    Step::<P>::poll_req(&s, &snapshot(wait, None), &mut effects);
+    assert_eq!(s.health(test::x.x.x.x(80)).unwrap().consecutive_failures(),
+               1);
+
+    Step::<P>::on_message_sent(&s, &snapshot(wait, None), &mut effects, &req(2, 2)).unwrap();
+    Step::<P>::poll_req(&s, &snapshot(wait * 2, None), &mut effects);
+    assert_eq!(s.health(test::x.x.x.x(80)).unwrap().consecutive_failures(),
+               2);
+    assert!(effects.iter()
+                   .any(|e| matches!(e, test::Effect::Log(log::Level::Warn, _))));
+
+    let mut next = req(3, 3);
+    assert_eq!(Step::<P>::before_message_sent(&s,
+                                              &snapshot(wait * 2, None),
+                                              &mut effects,
+                                              &mut next),
+               Err(Error::PeerUnhealthy(Unhealthy { addr: test::x.x.x.x(80),
+                                                    until: Milliseconds(wait * 2 + 1_000) })));
+
+    // responses & ACKs may still be sent to the peer
+    let mut ack = Addrd(msg(Type::Ack, Code::EMPTY, 4, 4), test::x.x.x.x(80));
+    assert_eq!(Step::<P>::before_message_sent(&s,
+                                              &snapshot(wait * 2, None),
+                                              &mut effects,
+                                              &mut ack),
+               Ok(()));
+
+    // after cooling down
+    assert_eq!(Step::<P>::before_message_sent(&s,
+                                              &snapshot(wait * 2 + 1_000, None),
+                                              &mut effects,
+                                              &mut next),
+               Ok(()));
+  }
+
+  #[test]
+  fn unanswered_non_requests_are_not_failures() {
+    let s = step();
+    let mut effects = vec![];
+    let wait = Config::default().timing().max_transmit_wait.0;
+    let non = Addrd(msg(Type::Non, Code::GET, 1, 1), test::x.x.x.x(80));
+
+    Step::<P>::on_message_sent(&s, &snapshot(0, None), &mut effects, &non).unwrap();
+    Step::<P>::poll_req(&s, &snapshot(wait * 2, None), &mut effects);
+
+    assert_eq!(s.health(test::x.x.x.x(80)), None);
+  }
+
+  #[test]
+  fn replies_conclude_requests() {
+    let s = step();
+    let mut effects = vec![];
+    let addr = test::x.x.x.x(80);
+
+    Step::<P>::on_message_sent(&s, &snapshot(0, None), &mut effects, &req(1, 1)).unwrap();
+    Step::<P>::on_message_sent(&s, &snapshot(0, None), &mut effects, &req(2, 2)).unwrap();
+    Step::<P>::on_message_sent(&s, &snapshot(0, None), &mut effects, &req(3, 3)).unwrap();
+
+    // empty ACK, matched by Id
+    Step::<P>::poll_resp(&s,
+                         &snapshot(1, Some(msg(Type::Ack, Code::EMPTY, 1, 0))),
+                         &mut effects,
+                         Token(Default::default()),
+                         addr);
+    assert_eq!(s.health(addr).unwrap().failure_rate(), 0);
+
+    // RESET, matched by Id
+    Step::<P>::poll_resp(&s,
+                         &snapshot(2, Some(msg(Type::Reset, Code::EMPTY, 2, 0))),
+                         &mut effects,
+                         Token(Default::default()),
+                         addr);
+    assert_eq!(s.health(addr).unwrap().consecutive_failures(), 1);
+
+    // separate response, matched by token
+    Step::<P>::poll_resp(&s,
+                         &snapshot(3, Some(msg(Type::Non, Code::new(2, 5), 99, 3))),
+                         &mut effects,
+                         Token(Default::default()),
+                         addr);
+    assert_eq!(s.health(addr).unwrap().consecutive_failures(), 0);
+
+    // nothing left to time out
+    let wait = Config::default().timing().max_transmit_wait.0;
+    Step::<P>::poll_req(&s, &snapshot(wait * 2, None), &mut effects);
+    assert_eq!(s.health(addr).unwrap().consecutive_failures(), 0);
+  }
+}
@@ -0,0 +1,304 @@
+use toad_msg::{CodeKind, ContentFormat, MessageOptions, OptNumber, OptValue, Payload, Token};
+
+use super::{exec_inner_step, log, Step, StepOutput};
+use crate::net::Addrd;
+use crate::platform::{self, Effect, Message, PlatformTypes, Snapshot};
+use crate::req::Req;
+use crate::resp::{code, Resp};
+
+/// Content-Coding option, identifying the [`PayloadCodec`]
+/// a payload was encoded with.
+///
+/// This is a critical option in the experimental range,
+/// so it must be added to [`understood_options`](crate::config::Msg.understood_options)
+/// on both ends of a link that uses a [`Codec`].
+pub const CONTENT_CODING: OptNumber = OptNumber(65001);
+
+/// A reversible transformation of message payloads, e.g. compression
+pub trait PayloadCodec<P>
+  where P: PlatformTypes
+{
+  /// The value of the [`CONTENT_CODING`] option for payloads encoded by this codec
+  fn coding(&self) -> u8;
+
+  /// Whether payloads with the Content-Format `format` should be encoded.
+  ///
+  /// `format` is `None` when the message has no Content-Format option.
+  fn applies_to(&self, format: Option<ContentFormat>) -> bool;
+
+  /// Encode a payload.
+  ///
+  /// Returning `None` will send the payload as-is (e.g. when
+  /// the payload could not be compressed).
+  fn encode(&self, payload: &[u8]) -> Option<P::MessagePayload>;
+
+  /// Decode a payload, returning `None` when it is invalid.
+  fn decode(&self, payload: &[u8]) -> Option<P::MessagePayload>;
+}
+
+/// Encodes outbound payloads and decodes inbound payloads
+/// with a [`PayloadCodec`].
+///
+/// See the [module documentation](crate::step::codec) for more
+#[derive(Debug, Clone, Copy)]
+pub struct Codec<S, C>(S, C);
+
+impl<S: Default, C: Default> Default for Codec<S, C> {
+  fn default() -> Self {
+    Codec(Default::default(), Default::default())
+  }
+}
+
+impl<S, C> Codec<S, C> {
+  /// Create a new Codec step
+  pub fn new(s: S, codec: C) -> Self {
+    Self(s, codec)
+  }
+}
+
+/// Why a payload could not be decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecodeError {
+  UnknownCoding(Option<u8>),
+  Invalid,
+}
+
+impl<S, C> Codec<S, C> {
+  fn decode<P>(&self, msg: &mut Message<P>) -> Result<(), DecodeError>
+    where P: PlatformTypes,
+          C: PayloadCodec<P>
+  {
+    let coding = match msg.get_first(CONTENT_CODING) {
+      | Some(coding) => coding.as_bytes().first().copied(),
+      | None => return Ok(()),
+    };
+
+    if coding != Some(self.1.coding()) {
+      return Err(DecodeError::UnknownCoding(coding));
+    }
+
+    let payload = self.1.decode(&msg.payload.0).ok_or(DecodeError::Invalid)?;
+    msg.payload = Payload(payload);
+    msg.remove(CONTENT_CODING);
+
+    Ok(())
+  }
+}
+
+type InnerPollReq<P> = Addrd<Req<P>>;
+type InnerPollResp<P> = Addrd<Resp<P>>;
+
+impl<Inner, C, P> Step<P> for Codec<Inner, C>
+  where Inner: Step<P, PollReq = InnerPollReq<P>, PollResp = InnerPollResp<P>>,
+        C: PayloadCodec<P>,
+        P: PlatformTypes
+{
+  type PollReq = Addrd<Req<P>>;
+  type PollResp = Addrd<Resp<P>>;
+  type Error = Inner::Error;
+  type Inner = Inner;
+
+  fn inner(&self) -> &Inner {
+    &self.0
+  }
+
+  fn poll_req(&self,
+              snap: &Snapshot<P>,
+              effects: &mut <P as PlatformTypes>::Effects)
+              -> StepOutput<Self::PollReq, Inner::Error> {
+    let mut req = match exec_inner_step!(self.0.poll_req(snap, effects), core::convert::identity) {
+      | Some(req) => req,
+      | None => return None,
+    };
+
+    match self.decode(req.data_mut().msg_mut()) {
+      | Ok(()) => Some(Ok(req)),
+      | Err(e) => {
+        log!(Codec::poll_req,
+             effects,
+             log::Level::Warn,
+             "rejecting {:?} {:?}: could not decode payload {:?}",
+             req.addr(),
+             req.data().msg().token,
+             e);
+
+        if let Some(mut resp) = Resp::for_request(req.data()) {
+          resp.set_code(match e {
+                          | DecodeError::UnknownCoding(_) => code::BAD_OPTION,
+                          | DecodeError::Invalid => code::BAD_REQUEST,
+                        });
+          effects.push(Effect::Send(Addrd(resp.into(), req.addr())));
+        }
+
+        Some(Err(nb::Error::WouldBlock))
+      },
+    }
+  }
+
+  fn poll_resp(&self,
+               snap: &Snapshot<P>,
+               effects: &mut <P as PlatformTypes>::Effects,
+               token: Token,
+               addr: no_std_net::SocketAddr)
+               -> StepOutput<Self::PollResp, Inner::Error> {
+    let mut resp = match exec_inner_step!(self.0.poll_resp(snap, effects, token, addr),
+                                          core::convert::identity)
+    {
+      | Some(resp) => resp,
+      | None => return None,
+    };
+
+    match self.decode(resp.data_mut().msg_mut()) {
+      | Ok(()) => Some(Ok(resp)),
+      | Err(e) => {
+        log!(Codec::poll_resp,
+             effects,
+             log::Level::Warn,
+             "dropping response {:?} {:?}: could not decode payload {:?}",
+             resp.addr(),
+             resp.data().msg().token,
+             e);
+
+        Some(Err(nb::Error::WouldBlock))
+      },
+    }
+  }
+
+  fn before_message_sent(&self,
+                         snap: &platform::Snapshot<P>,
+                         effs: &mut P::Effects,
+                         msg: &mut Addrd<platform::Message<P>>)
+                         -> Result<(), Self::Error> {
+    self.0.before_message_sent(snap, effs, msg)?;
+
+    let msg = msg.data_mut();
+    if msg.code.kind() == CodeKind::Empty
+       || msg.payload.0.is_empty()
+       || msg.get(CONTENT_CODING).is_some()
+       || !self.1.applies_to(msg.content_format())
+    {
+      return Ok(());
+    }
+
+    match self.1.encode(&msg.payload.0) {
+      | Some(encoded) if encoded.len() < msg.payload.0.len() => {
+        msg.payload = Payload(encoded);
+        msg.set(CONTENT_CODING,
+                OptValue(core::iter::once(self.1.coding()).collect()))
+           .ok();
+      },
+      | _ => (),
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use tinyvec::array_vec;
+  use toad_msg::{Code, Id, Type};
+
+  use super::*;
+  use crate::step::test::test_step;
+  use crate::test;
+
+  /// Run-length encoding of `(count, byte)` pairs, applied to text payloads
+  #[derive(Debug, Default, Clone, Copy)]
+  struct Rle;
+
+  impl PayloadCodec<test::Platform> for Rle {
+    fn coding(&self) -> u8 {
+      1
+    }
+
+    fn applies_to(&self, format: Option<ContentFormat>) -> bool {
+      format == Some(ContentFormat::Text)
+    }
+
+    fn encode(&self, payload: &[u8]) -> Option<Vec<u8>> {
+      let mut out = Vec::new();
+      payload.iter().for_each(|b| match out.len() {
+                      | n if n >= 2 && out[n - 1] == *b && out[n - 2] < u8::MAX => out[n - 2] += 1,
+                      | _ => out.extend([1, *b]),
+                    });
+      Some(out)
+    }
+
+    fn decode(&self, payload: &[u8]) -> Option<Vec<u8>> {
+      if payload.len() % 2 != 0 {
+        return None;
+      }
+
+      Some(payload.chunks(2)
+                  .flat_map(|pair| core::iter::repeat(pair[1]).take(pair[0] as usize))
+                  .collect())
+    }
+  }
+
+  type InnerPollReq = Addrd<Req<test::Platform>>;
+  type InnerPollResp = Addrd<Resp<test::Platform>>;
+
+  fn msg(payload: &[u8], coding: Option<u8>) -> Addrd<test::Message> {
+    let mut msg = test::Message { ver: Default::default(),
+                                  ty: Type::Non,
+                                  id: Id(1),
+                                  code: Code::new(2, 5),
+                                  token: Token(array_vec!(_ => 1)),
+                                  payload: Payload(payload.to_vec()),
+                                  opts: Default::default() };
+    msg.set_content_format(ContentFormat::Text).unwrap();
+    if let Some(coding) = coding {
+      msg.set(CONTENT_CODING, OptValue(vec![coding])).unwrap();
+    }
+    Addrd(msg, test::dummy_addr())
+  }
+
+  test_step!(
+    GIVEN Codec::<Dummy, Rle> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+    WHEN inner_yields_encoded_response [
+      (inner.poll_resp => { Some(Ok(msg(&[3, b'a', 1, b'b'], Some(1)).map(Resp::from))) })
+    ]
+    THEN payload_should_be_decoded [
+      (poll_resp(_, _, _, _) should satisfy { |out| {
+        let resp = out.unwrap().unwrap();
+        assert_eq!(resp.data().msg().payload.0, b"aaab".to_vec());
+        assert!(resp.data().msg().get(CONTENT_CODING).is_none());
+      }})
+    ]
+  );
+
+  test_step!(
+    GIVEN Codec::<Dummy, Rle> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+    WHEN inner_yields_response_with_unknown_coding [
+      (inner.poll_resp => { Some(Ok(msg(&[3, b'a'], Some(2)).map(Resp::from))) })
+    ]
+    THEN response_should_be_dropped [
+      (poll_resp(_, _, _, _) should satisfy { |out| assert!(matches!(out, Some(Err(nb::Error::WouldBlock)))) })
+    ]
+  );
+
+  test_step!(
+    GIVEN Codec::<Dummy, Rle> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+    WHEN outbound_text_payload_compresses [
+    ]
+    THEN payload_should_be_encoded [
+      (before_message_sent(_, _, msg(b"aaaaaaaa", None)) should be ok with { |msg| {
+        assert_eq!(msg.data().payload.0, vec![8, b'a']);
+        assert_eq!(msg.data().get_first(CONTENT_CODING).unwrap().as_bytes(), &[1u8]);
+      }})
+    ]
+  );
+
+  test_step!(
+    GIVEN Codec::<Dummy, Rle> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+    WHEN outbound_text_payload_does_not_compress [
+    ]
+    THEN payload_should_not_be_encoded [
+      (before_message_sent(_, _, msg(b"ab", None)) should be ok with { |msg| {
+        assert_eq!(msg.data().payload.0, b"ab".to_vec());
+        assert!(msg.data().get(CONTENT_CODING).is_none());
+      }})
+    ]
+  );
+}
@@ -0,0 +1,396 @@
+use core::fmt::{self, Debug};
+
+use no_std_net::SocketAddr;
+use std_alloc::boxed::Box;
+use toad_msg::Token;
+
+use super::{Step, StepOutput};
+use crate::net::Addrd;
+use crate::platform::{self, PlatformTypes};
+use crate::req::Req;
+use crate::resp::Resp;
+use crate::retry::Policy;
+
+/// The object-safe subset of [`Step`] used by [`DynStep`]
+///
+/// This is implemented for every [`Step`] that yields requests & responses
+/// and can't (and shouldn't need to) be implemented manually.
+pub trait ErasedStep<P, E>
+  where P: PlatformTypes
+{
+  /// See [`Step::poll_req`]
+  fn poll_req(&self,
+              snap: &platform::Snapshot<P>,
+              effects: &mut P::Effects)
+              -> StepOutput<Addrd<Req<P>>, E>;
+
+  /// See [`Step::poll_resp`]
+  fn poll_resp(&self,
+               snap: &platform::Snapshot<P>,
+               effects: &mut P::Effects,
+               token: Token,
+               addr: SocketAddr)
+               -> StepOutput<Addrd<Resp<P>>, E>;
+
+  /// See [`Step::notify`]
+  fn notify(&self, path: &str, effects: &mut P::Effects) -> Result<(), E>;
+
+  /// See [`Step::cancel`]
+  fn cancel(&self,
+            snap: &platform::Snapshot<P>,
+            effects: &mut P::Effects,
+            token: Token)
+            -> Result<(), E>;
+
+  /// See [`Step::set_retry_policy`]
+  fn set_retry_policy(&self,
+                      snap: &platform::Snapshot<P>,
+                      effects: &mut P::Effects,
+                      token: Addrd<Token>,
+                      policy: Policy)
+                      -> Result<(), E>;
+
+  /// See [`Step::set_forwarding`]
+  fn set_forwarding(&self,
+                    snap: &platform::Snapshot<P>,
+                    effects: &mut P::Effects,
+                    token: Addrd<Token>,
+                    forwarding: bool)
+                    -> Result<(), E>;
+
+  /// See [`Step::before_message_sent`]
+  fn before_message_sent(&self,
+                         snap: &platform::Snapshot<P>,
+                         effects: &mut P::Effects,
+                         msg: &mut Addrd<platform::Message<P>>)
+                         -> Result<(), E>;
+
+  /// See [`Step::on_message_sent`]
+  fn on_message_sent(&self,
+                     snap: &platform::Snapshot<P>,
+                     effects: &mut P::Effects,
+                     msg: &Addrd<platform::Message<P>>)
+                     -> Result<(), E>;
+
+  /// See [`Step::checkout`]
+  fn checkout(&self) -> Option<platform::Message<P>>;
+
+  /// See [`Step::recycle`]
+  fn recycle(&self, msg: platform::Message<P>);
+
+  /// See [`Step::snapshot_state`]
+  fn snapshot_state(&self, w: &mut dyn fmt::Write) -> fmt::Result;
+}
+
+impl<P, E, S> ErasedStep<P, E> for S
+  where P: PlatformTypes,
+        S: Step<P, PollReq = Addrd<Req<P>>, PollResp = Addrd<Resp<P>>, Error = E>
+{
+  fn poll_req(&self,
+              snap: &platform::Snapshot<P>,
+              effects: &mut P::Effects)
+              -> StepOutput<Addrd<Req<P>>, E> {
+    Step::poll_req(self, snap, effects)
+  }
+
+  fn poll_resp(&self,
+               snap: &platform::Snapshot<P>,
+               effects: &mut P::Effects,
+               token: Token,
+               addr: SocketAddr)
+               -> StepOutput<Addrd<Resp<P>>, E> {
+    Step::poll_resp(self, snap, effects, token, addr)
+  }
+
+  fn notify(&self, path: &str, effects: &mut P::Effects) -> Result<(), E> {
+    Step::notify(self, path, effects)
+  }
+
+  fn cancel(&self,
+            snap: &platform::Snapshot<P>,
+            effects: &mut P::Effects,
+            token: Token)
+            -> Result<(), E> {
+    Step::cancel(self, snap, effects, token)
+  }
+
+  fn set_retry_policy(&self,
+                      snap: &platform::Snapshot<P>,
+                      effects: &mut P::Effects,
+                      token: Addrd<Token>,
+                      policy: Policy)
+                      -> Result<(), E> {
+    Step::set_retry_policy(self, snap, effects, token, policy)
+  }
+
+  fn set_forwarding(&self,
+                    snap: &platform::Snapshot<P>,
+                    effects: &mut P::Effects,
+                    token: Addrd<Token>,
+                    forwarding: bool)
+                    -> Result<(), E> {
+    Step::set_forwarding(self, snap, effects, token, forwarding)
+  }
+
+  fn before_message_sent(&self,
+                         snap: &platform::Snapshot<P>,
+                         effects: &mut P::Effects,
+                         msg: &mut Addrd<platform::Message<P>>)
+                         -> Result<(), E> {
+    Step::before_message_sent(self, snap, effects, msg)
+  }
+
+  fn on_message_sent(&self,
+                     snap: &platform::Snapshot<P>,
+                     effects: &mut P::Effects,
+                     msg: &Addrd<platform::Message<P>>)
+                     -> Result<(), E> {
+    Step::on_message_sent(self, snap, effects, msg)
+  }
+
+  fn checkout(&self) -> Option<platform::Message<P>> {
+    Step::checkout(self)
+  }
+
+  fn recycle(&self, msg: platform::Message<P>) {
+    Step::recycle(self, msg)
+  }
+
+  fn snapshot_state(&self, mut w: &mut dyn fmt::Write) -> fmt::Result {
+    Step::snapshot_state(self, &mut w)
+  }
+}
+
+/// The step used by [`DynStep::default`], which does nothing
+#[derive(Debug, Clone, Copy)]
+struct Nop;
+
+impl<P, E> ErasedStep<P, E> for Nop where P: PlatformTypes
+{
+  fn poll_req(&self,
+              _: &platform::Snapshot<P>,
+              _: &mut P::Effects)
+              -> StepOutput<Addrd<Req<P>>, E> {
+    None
+  }
+
+  fn poll_resp(&self,
+               _: &platform::Snapshot<P>,
+               _: &mut P::Effects,
+               _: Token,
+               _: SocketAddr)
+               -> StepOutput<Addrd<Resp<P>>, E> {
+    None
+  }
+
+  fn notify(&self, _: &str, _: &mut P::Effects) -> Result<(), E> {
+    Ok(())
+  }
+
+  fn cancel(&self, _: &platform::Snapshot<P>, _: &mut P::Effects, _: Token) -> Result<(), E> {
+    Ok(())
+  }
+
+  fn set_retry_policy(&self,
+                      _: &platform::Snapshot<P>,
+                      _: &mut P::Effects,
+                      _: Addrd<Token>,
+                      _: Policy)
+                      -> Result<(), E> {
+    Ok(())
+  }
+
+  fn set_forwarding(&self,
+                    _: &platform::Snapshot<P>,
+                    _: &mut P::Effects,
+                    _: Addrd<Token>,
+                    _: bool)
+                    -> Result<(), E> {
+    Ok(())
+  }
+
+  fn before_message_sent(&self,
+                         _: &platform::Snapshot<P>,
+                         _: &mut P::Effects,
+                         _: &mut Addrd<platform::Message<P>>)
+                         -> Result<(), E> {
+    Ok(())
+  }
+
+  fn on_message_sent(&self,
+                     _: &platform::Snapshot<P>,
+                     _: &mut P::Effects,
+                     _: &Addrd<platform::Message<P>>)
+                     -> Result<(), E> {
+    Ok(())
+  }
+
+  fn checkout(&self) -> Option<platform::Message<P>> {
+    None
+  }
+
+  fn recycle(&self, _: platform::Message<P>) {}
+
+  fn snapshot_state(&self, _: &mut dyn fmt::Write) -> fmt::Result {
+    Ok(())
+  }
+}
+
+/// A type-erased [`Step`], invoking the step it wraps through a vtable
+///
+/// See the [module documentation](crate::step::dyn_step) for more
+pub struct DynStep<P, E>(Box<dyn ErasedStep<P, E>>) where P: PlatformTypes;
+
+impl<P, E> DynStep<P, E> where P: PlatformTypes
+{
+  /// Erase the type of `step`
+  pub fn new<S>(step: S) -> Self
+    where S: Step<P, PollReq = Addrd<Req<P>>, PollResp = Addrd<Resp<P>>, Error = E> + 'static
+  {
+    Self(Box::new(step))
+  }
+}
+
+impl<P, E> Default for DynStep<P, E> where P: PlatformTypes
+{
+  /// A step that does nothing; see [`DynStep::new`]
+  fn default() -> Self {
+    Self(Box::new(Nop))
+  }
+}
+
+impl<P, E> Debug for DynStep<P, E> where P: PlatformTypes
+{
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("DynStep").field(&"..").finish()
+  }
+}
+
+impl<P, E> Step<P> for DynStep<P, E>
+  where P: PlatformTypes,
+        E: super::Error
+{
+  type PollReq = Addrd<Req<P>>;
+  type PollResp = Addrd<Resp<P>>;
+  type Error = E;
+
+  // The erased step is the only inner step, and every handler
+  // is overridden to invoke it directly.
+  type Inner = Self;
+
+  fn inner(&self) -> &Self {
+    self
+  }
+
+  fn poll_req(&self,
+              snap: &platform::Snapshot<P>,
+              effects: &mut P::Effects)
+              -> StepOutput<Self::PollReq, Self::Error> {
+    self.0.poll_req(snap, effects)
+  }
+
+  fn poll_resp(&self,
+               snap: &platform::Snapshot<P>,
+               effects: &mut P::Effects,
+               token: Token,
+               addr: SocketAddr)
+               -> StepOutput<Self::PollResp, Self::Error> {
+    self.0.poll_resp(snap, effects, token, addr)
+  }
+
+  fn notify<Path>(&self, path: Path, effects: &mut P::Effects) -> Result<(), Self::Error>
+    where Path: AsRef<str> + Clone
+  {
+    self.0.notify(path.as_ref(), effects)
+  }
+
+  fn cancel(&self,
+            snap: &platform::Snapshot<P>,
+            effects: &mut P::Effects,
+            token: Token)
+            -> Result<(), Self::Error> {
+    self.0.cancel(snap, effects, token)
+  }
+
+  fn set_retry_policy(&self,
+                      snap: &platform::Snapshot<P>,
+                      effects: &mut P::Effects,
+                      token: Addrd<Token>,
+                      policy: Policy)
+                      -> Result<(), Self::Error> {
+    self.0.set_retry_policy(snap, effects, token, policy)
+  }
+
+  fn set_forwarding(&self,
+                    snap: &platform::Snapshot<P>,
+                    effects: &mut P::Effects,
+                    token: Addrd<Token>,
+                    forwarding: bool)
+                    -> Result<(), Self::Error> {
+    self.0.set_forwarding(snap, effects, token, forwarding)
+  }
+
+  fn before_message_sent(&self,
+                         snap: &platform::Snapshot<P>,
+                         effects: &mut P::Effects,
+                         msg: &mut Addrd<platform::Message<P>>)
+                         -> Result<(), Self::Error> {
+    self.0.before_message_sent(snap, effects, msg)
+  }
+
+  fn on_message_sent(&self,
+                     snap: &platform::Snapshot<P>,
+                     effects: &mut P::Effects,
+                     msg: &Addrd<platform::Message<P>>)
+                     -> Result<(), Self::Error> {
+    self.0.on_message_sent(snap, effects, msg)
+  }
+
+  fn checkout(&self) -> Option<platform::Message<P>> {
+    self.0.checkout()
+  }
+
+  fn recycle(&self, msg: platform::Message<P>) {
+    self.0.recycle(msg)
+  }
+
+  fn snapshot_state<W>(&self, w: &mut W) -> fmt::Result
+    where W: fmt::Write
+  {
+    self.0.snapshot_state(w)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::step::test::default_snapshot;
+  use crate::test::{self, Platform as P};
+
+  #[test]
+  fn default_does_nothing() {
+    let step = DynStep::<P, ()>::default();
+    let mut effects = vec![];
+
+    assert!(step.poll_req(&default_snapshot(), &mut effects).is_none());
+    assert!(step.poll_resp(&default_snapshot(),
+                           &mut effects,
+                           Token(Default::default()),
+                           test::x.x.x.x(80))
+                .is_none());
+    assert_eq!(step.notify("foo", &mut effects), Ok(()));
+    assert!(effects.is_empty());
+  }
+
+  #[test]
+  fn delegates_to_erased_step() {
+    let req = || Addrd(Req::<P>::get("foo"), test::x.x.x.x(80));
+
+    let inner = test::MockStep::<(), Addrd<Req<P>>, Addrd<Resp<P>>, ()>::default();
+    inner.set_poll_req(move |_, _, _| Some(Ok(req())));
+
+    let step = DynStep::<P, ()>::new(inner);
+
+    assert_eq!(step.poll_req(&default_snapshot(), &mut vec![]), Some(Ok(req())));
+  }
+}
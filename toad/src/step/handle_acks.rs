@@ -1,10 +1,8 @@
-use core::fmt::Write;
-
 use naan::prelude::ResultExt;
 use toad_array::Array;
 use toad_len::Len;
 use toad_map::{InsertError, Map};
-use toad_msg::{Token, Type};
+use toad_msg::{Id, Token, Type};
 use toad_stem::Stem;
 
 use super::{log, Step, StepOutput};
@@ -12,7 +10,6 @@ use crate::net::Addrd;
 use crate::platform::{Effect, PlatformTypes};
 use crate::req::Req;
 use crate::resp::Resp;
-use crate::todo::String;
 use crate::{exec_inner_step, platform};
 
 /// Struct responsible for buffering and yielding responses to the request
@@ -65,45 +62,45 @@ impl<E: core::fmt::Debug> core::fmt::Debug for Error<E> {
 
 impl<E: super::Error> super::Error for Error<E> {}
 
+/// Find the CON addressed by an ACK.
+///
+/// ACKs are matched to CONs by Message ID and peer, since Empty ACKs
+/// carry no token.
+#[doc = toad_macros::rfc_7252_doc!("4.4")]
+fn acked<B>(buf: &B, ack: Addrd<Id>) -> Option<Addrd<Token>>
+  where B: Map<Addrd<Token>, Id>
+{
+  buf.iter()
+     .find(|(Addrd(_, addr), id)| *addr == ack.addr() && **id == *ack.data())
+     .map(|(k, _)| *k)
+}
+
 macro_rules! common {
   ($in:expr, $msg:expr, $effects:expr, $buffer:expr) => {{
     let msg: Addrd<&platform::Message<P>> = $msg;
 
     match msg.data().ty {
-      Type::Ack if !$buffer.map_ref(|buf| buf.has(&msg.map(|m| m.token)))
-          => {
-        let (size, sender, token) =
-          (msg.data().len(), msg.addr(), msg.data().token);
-
-        let tokens = $buffer.map_ref(
-          |buf| {
-            let mut tokens = String::<1000>::default();
-            write!(tokens, "[").ok();
-            buf.iter().enumerate().for_each(|(ix, (token, _))| {
-              write!(tokens, "{:?}", token).ok();
-              if ix < buf.len() - 1 {
-                write!(tokens, ",").ok();
-              }
-            });
-            write!(tokens, "]").ok();
-            tokens
-          });
+      Type::Ack => match $buffer.map_ref(|buf| acked(buf, msg.map(|m| m.id))) {
+        | None => {
+          let (size, sender, id) = (msg.data().len(), msg.addr(), msg.data().id);
 
-          let tokens = tokens.as_str();
-
-        log!(HandleAcks, $effects, log::Level::Warn, "Discarding {size}b ACK from {sender} addressing unknown {token:?}. Presently expecting acks for: {tokens}");
-        None
-      },
-      Type::Ack => {
-        let (size, sender, token) = (msg.data().len(), msg.addr(), (msg.data().id, msg.data().token));
-        log!(HandleAcks, $effects, log::Level::Trace, "Got {size}b ACK from {sender} for {token:?}");
-        $buffer.map_mut(|buf| buf.remove(&msg.as_ref().map(|m| m.token)));
-
-        if msg.data().code.kind() == toad_msg::CodeKind::Empty {
+          $buffer.map_ref(|buf| {
+            let tokens = crate::logging::list(buf.iter().map(|(token, _)| token));
+            log!(HandleAcks, $effects, log::Level::Warn, "Discarding {size}b ACK from {sender} addressing unknown {id:?}. Presently expecting acks for: {tokens}");
+          });
           None
-        } else {
-          Some(Ok($in))
-        }
+        },
+        | Some(con) => {
+          let (size, sender, token) = (msg.data().len(), msg.addr(), (msg.data().id, con.data()));
+          log!(HandleAcks, $effects, log::Level::Trace, "Got {size}b ACK from {sender} for {token:?}");
+          $buffer.map_mut(|buf| buf.remove(&con));
+
+          if msg.data().code.kind() == toad_msg::CodeKind::Empty {
+            None
+          } else {
+            Some(Ok($in))
+          }
+        },
       },
       _ => Some(Ok($in))
     }
@@ -111,7 +108,7 @@ macro_rules! common {
 }
 
 impl<P: PlatformTypes,
-      B: Map<Addrd<Token>, ()> + core::fmt::Debug,
+      B: Map<Addrd<Token>, Id> + core::fmt::Debug,
       E: super::Error,
       S: Step<P, PollReq = Addrd<Req<P>>, PollResp = Addrd<Resp<P>>, Error = E>> Step<P>
   for HandleAcks<S, B>
@@ -125,6 +122,18 @@ impl<P: PlatformTypes,
     &self.inner
   }
 
+  fn snapshot_state<W>(&self, w: &mut W) -> core::fmt::Result
+    where W: core::fmt::Write
+  {
+    self.buffer.map_ref(|buf| {
+                 writeln!(w, "HandleAcks: {} CON(s) awaiting ACK", buf.len())?;
+                 buf.iter()
+                    .try_for_each(|(Addrd(token, addr), _)| writeln!(w, "  {} {:?}", addr, token))
+               })?;
+
+    self.inner.snapshot_state(w)
+  }
+
   fn poll_req(&self,
               snap: &crate::platform::Snapshot<P>,
               effects: &mut <P as PlatformTypes>::Effects)
@@ -169,7 +178,7 @@ impl<P: PlatformTypes,
 
     match msg.data().ty {
       | Type::Con => self.buffer
-                         .map_mut(|buf| buf.insert(msg.as_ref().map(|m| m.token), ()))
+                         .map_mut(|buf| buf.insert(msg.as_ref().map(|m| m.token), msg.data().id))
                          .recover(|e| {
                            if matches!(e, InsertError::Exists(_)) {
                              Ok(())
@@ -197,7 +206,7 @@ mod test {
 
   type InnerPollReq = Addrd<Req<test::Platform>>;
   type InnerPollResp = Addrd<Resp<test::Platform>>;
-  type HandleAcks<S> = super::HandleAcks<S, BTreeMap<Addrd<Token>, ()>>;
+  type HandleAcks<S> = super::HandleAcks<S, BTreeMap<Addrd<Token>, Id>>;
 
   fn test_message(ty: Type) -> Addrd<test::Message> {
     use toad_msg::*;
@@ -266,8 +275,29 @@ mod test {
     ]
   );
 
+  #[test]
+  fn snapshot_state_lists_cons_awaiting_ack() {
+    type Mock = test::MockStep<(), InnerPollReq, InnerPollResp, ()>;
+
+    let sut = HandleAcks::<Mock>::default();
+    sut.buffer.map_mut(|b| {
+                b.insert(Addrd(Token(array_vec!(_ => 1)), test::dummy_addr()), Id(1))
+                 .unwrap()
+              });
+
+    let mut dump = std::string::String::new();
+    Step::<test::Platform>::snapshot_state(&sut, &mut dump).unwrap();
+
+    assert_eq!(dump,
+               format!("HandleAcks: 1 CON(s) awaiting ACK\n  {} {:?}\n",
+                       test::dummy_addr(),
+                       Token(array_vec!(_ => 1))));
+  }
+
   #[test]
   fn when_expected_piggybacked_ack_received_it_should_be_processed_and_returned() {
+    test::log_level(log::LevelFilter::Trace);
+
     struct TestState {
       token_last_sent: Option<Token>,
     }
@@ -324,6 +354,8 @@ mod test {
 
   #[test]
   fn when_expected_empty_ack_received_it_should_be_processed_and_ignored() {
+    test::log_level(log::LevelFilter::Trace);
+
     struct TestState {
       token_last_sent: Option<Token>,
     }
@@ -341,14 +373,15 @@ mod test {
          Ok(())
        })
        .set_poll_resp(|mock, _, _, poll_for_token, _| {
-         let mut msg = test::msg!(ACK {0 . 00} x.x.x.x:2222);
+         let msg = test::msg!(ACK {0 . 00} x.x.x.x:2222);
 
          let token = mock.state
                          .map_ref(|s| s.as_ref().unwrap().token_last_sent.unwrap());
-         Addrd::data_mut(&mut msg).token = token;
-
          assert_eq!(token, poll_for_token);
 
+         // Empty ACKs have no token; they are matched to the CON by Id
+         assert!(msg.data().token.0.is_empty());
+
          Some(Ok(msg.map(Resp::from)))
        });
 
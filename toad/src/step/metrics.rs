@@ -0,0 +1,258 @@
+use no_std_net::SocketAddr;
+use toad_msg::{Id, Type};
+use toad_stem::Stem;
+
+use super::{Step, StepOutput};
+use crate::net::Addrd;
+use crate::platform::{self, PlatformTypes};
+use crate::req::Req;
+use crate::resp::Resp;
+use crate::time::Millis;
+
+/// How many recently sent messages are remembered in order
+/// to recognize retransmissions of them
+const RECENT_LEN: usize = 32;
+
+/// Counters kept by the [`Metrics`] step
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+  /// Time elapsed between the first & most recent time the step was polled
+  pub uptime: Millis,
+  /// Messages sent, including retransmissions
+  pub sent: u64,
+  /// Datagrams received
+  pub received: u64,
+  /// CON & NON messages that were sent again after not being
+  /// acknowledged or responded to (see [`retry`](super::retry))
+  pub retransmitted: u64,
+}
+
+#[derive(Debug, Default)]
+struct State {
+  stats: Stats,
+  started_at: Option<Millis>,
+  recent: [Option<(SocketAddr, Id)>; RECENT_LEN],
+  recent_ix: usize,
+}
+
+impl State {
+  fn tick(&mut self, now: Millis) {
+    let started_at = *self.started_at.get_or_insert(now);
+    self.stats.uptime = Millis::new(now.0.saturating_sub(started_at.0));
+  }
+
+  /// Was a message with this Id already sent to this address?
+  ///
+  /// Message Ids are never reused for new messages within the exchange
+  /// lifetime (see [`provision_ids`](super::provision_ids)), so this
+  /// is true only of retransmissions.
+  fn seen(&mut self, key: (SocketAddr, Id)) -> bool {
+    if self.recent.contains(&Some(key)) {
+      true
+    } else {
+      self.recent[self.recent_ix] = Some(key);
+      self.recent_ix = (self.recent_ix + 1) % RECENT_LEN;
+      false
+    }
+  }
+}
+
+/// Count messages sent, received and retransmitted
+///
+/// See the [module documentation](crate::step::metrics) for more
+#[derive(Debug, Default)]
+pub struct Metrics<S> {
+  inner: S,
+  state: Stem<State>,
+}
+
+impl<S> Metrics<S> {
+  /// Create a new Metrics step
+  pub fn new(inner: S) -> Self {
+    Self { inner,
+           state: Default::default() }
+  }
+
+  /// Get the counters as of right now
+  pub fn stats(&self) -> Stats {
+    self.state.map_ref(|s| s.stats)
+  }
+
+  fn on_poll<P>(&self, snap: &platform::Snapshot<P>)
+    where P: PlatformTypes
+  {
+    let now = Millis::try_from(snap.time.duration_since_epoch()).ok();
+    let recvd = snap.recvd_dgram.is_some();
+
+    self.state.map_mut(|s| {
+                if let Some(now) = now {
+                  s.tick(now);
+                }
+
+                if recvd {
+                  s.stats.received += 1;
+                }
+              });
+  }
+}
+
+impl<P, E, S> Step<P> for Metrics<S>
+  where P: PlatformTypes,
+        E: super::Error,
+        S: Step<P, PollReq = Addrd<Req<P>>, PollResp = Addrd<Resp<P>>, Error = E>
+{
+  type PollReq = Addrd<Req<P>>;
+  type PollResp = Addrd<Resp<P>>;
+  type Error = E;
+  type Inner = S;
+
+  fn inner(&self) -> &S {
+    &self.inner
+  }
+
+  fn poll_req(&self,
+              snap: &platform::Snapshot<P>,
+              effects: &mut P::Effects)
+              -> StepOutput<Self::PollReq, Self::Error> {
+    self.on_poll(snap);
+    self.inner.poll_req(snap, effects)
+  }
+
+  fn poll_resp(&self,
+               snap: &platform::Snapshot<P>,
+               effects: &mut P::Effects,
+               token: toad_msg::Token,
+               addr: SocketAddr)
+               -> StepOutput<Self::PollResp, Self::Error> {
+    self.on_poll(snap);
+    self.inner.poll_resp(snap, effects, token, addr)
+  }
+
+  fn on_message_sent(&self,
+                     snap: &platform::Snapshot<P>,
+                     effects: &mut P::Effects,
+                     msg: &Addrd<platform::Message<P>>)
+                     -> Result<(), Self::Error> {
+    self.inner.on_message_sent(snap, effects, msg)?;
+
+    let retryable = matches!(msg.data().ty, Type::Con | Type::Non);
+    let key = (msg.addr(), msg.data().id);
+
+    self.state.map_mut(|s| {
+                s.stats.sent += 1;
+
+                if retryable && s.seen(key) {
+                  s.stats.retransmitted += 1;
+                }
+              });
+
+    Ok(())
+  }
+
+  fn snapshot_state<W>(&self, w: &mut W) -> core::fmt::Result
+    where W: core::fmt::Write
+  {
+    let Stats { uptime,
+                sent,
+                received,
+                retransmitted, } = self.stats();
+    writeln!(w,
+             "Metrics: up {}ms, {} sent, {} received, {} retransmitted",
+             uptime.0, sent, received, retransmitted)?;
+    self.inner.snapshot_state(w)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use tinyvec::array_vec;
+  use toad_msg::Token;
+
+  use super::*;
+  use crate::step::test::test_step;
+  use crate::test::{self, Platform as P};
+
+  type InnerPollReq = Addrd<Req<P>>;
+  type InnerPollResp = Addrd<Resp<P>>;
+
+  fn test_msg(ty: Type, id: u16) -> Addrd<test::Message> {
+    use toad_msg::*;
+
+    let msg = test::Message { ver: Default::default(),
+                              token: Token(array_vec!([u8; 8] => 1)),
+                              ty,
+                              code: Code::GET,
+                              id: Id(id),
+                              opts: Default::default(),
+                              payload: Payload(vec![]) };
+
+    Addrd(msg, test::x.x.x.x(80))
+  }
+
+  fn snapshot(secs: u64, recvd: bool) -> platform::Snapshot<P> {
+    let dgram = Addrd(Default::default(), test::x.x.x.x(80));
+
+    platform::Snapshot { time: test::ClockMock::instant(secs * 1_000_000),
+                         recvd_dgram: Some(dgram).filter(|_| recvd),
+                         recvd_identity: None,
+                         recvd_dest: None,
+                         session: None,
+                         config: Default::default() }
+  }
+
+  test_step!(
+    GIVEN Metrics::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+    WHEN dgram_received [
+      (inner.poll_req => { None }),
+      (snapshot = { snapshot(0, true) })
+    ]
+    THEN inner_output_is_yielded [
+      (poll_req(_, _) should satisfy { |out| assert!(out.is_none()) })
+    ]
+  );
+
+  test_step!(
+    GIVEN Metrics::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+    WHEN nothing_received [
+      (inner.poll_resp => { None })
+    ]
+    THEN nothing_happens [
+      (poll_resp(_, _, Token(Default::default()), test::x.x.x.x(80)) should satisfy { |out| assert!(out.is_none()) }),
+      (effects should satisfy { |effs| assert!(effs.is_empty()) })
+    ]
+  );
+
+  #[test]
+  fn counts_sent_received_and_retransmitted() {
+    type Mock = test::MockStep<(), InnerPollReq, InnerPollResp, ()>;
+
+    let s = Metrics::<Mock>::default();
+    s.inner().set_poll_req(|_, _, _| None)
+             .set_on_message_sent(|_, _, _, _| Ok(()));
+
+    let mut effects = vec![];
+
+    Step::<P>::poll_req(&s, &snapshot(1, true), &mut effects);
+    Step::<P>::poll_req(&s, &snapshot(2, false), &mut effects);
+    Step::<P>::poll_req(&s, &snapshot(4, true), &mut effects);
+
+    // sent once, retransmitted twice
+    for _ in 0..3 {
+      Step::<P>::on_message_sent(&s, &snapshot(4, false), &mut effects, &test_msg(Type::Con, 1)).unwrap();
+    }
+
+    // ACKs are never retransmitted, even if they are sent again
+    // in response to a duplicate CON
+    for _ in 0..2 {
+      Step::<P>::on_message_sent(&s, &snapshot(4, false), &mut effects, &test_msg(Type::Ack, 2)).unwrap();
+    }
+
+    Step::<P>::on_message_sent(&s, &snapshot(4, false), &mut effects, &test_msg(Type::Non, 3)).unwrap();
+
+    assert_eq!(s.stats(),
+               Stats { uptime: Millis::new(3000),
+                       sent: 6,
+                       received: 2,
+                       retransmitted: 2 });
+  }
+}
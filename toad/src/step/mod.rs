@@ -4,20 +4,47 @@ use no_std_net::SocketAddr;
 use crate::net::Addrd;
 use crate::platform::{self, PlatformTypes};
 
+/// Custom metadata options that steps may set on messages
+///
+/// These options are never sent over the wire.
+pub mod opt {
+  use toad_msg::OptNumber;
+
+  /// Numbers of options that were removed from an incoming message
+  /// by the [`Parse`](super::parse::Parse) step because they were malformed,
+  /// one value (the option number as a big-endian u32) per removed option.
+  ///
+  /// See [`MalformedOptions::Lenient`](crate::config::MalformedOptions::Lenient).
+  ///
+  /// Removed by the [`OptionPolicy`](super::option_policy::OptionPolicy) step.
+  pub const MALFORMED: OptNumber = OptNumber(65003);
+}
+
 /// Standard set of Steps
 pub mod runtime {
-  use ::toad_msg::Token;
+  use ::toad_msg::{Id, Token};
+  #[cfg(any(feature = "client", feature = "server"))]
+  use embedded_time::Instant;
   use naan::prelude::{HKT1, HKT2};
+  #[cfg(feature = "client")]
   use no_std_net::SocketAddr;
 
   use super::ack::Ack;
+  #[cfg(feature = "client")]
+  use super::{backoff, buffer_responses};
+  #[cfg(feature = "server")]
+  use super::{multicast, observe};
+  use super::option_policy::OptionPolicy;
   use super::parse::Parse;
   use super::provision_ids::{self, IdWithDefault, SocketAddrWithDefault};
+  #[cfg(feature = "client")]
   use super::provision_tokens::ProvisionTokens;
-  use super::{buffer_responses, handle_acks, observe, retry};
+  use super::{handle_acks, retry};
   use crate::net::Addrd;
   use crate::platform::{Message, PlatformTypes};
+  #[cfg(feature = "server")]
   use crate::req::Req;
+  #[cfg(feature = "client")]
   use crate::resp::Resp;
   use crate::time::Stamped;
 
@@ -30,13 +57,21 @@ pub mod runtime {
   type Clock<P> = <P as PlatformTypes>::Clock;
 
   #[allow(missing_docs)]
-  pub type HandleAcks<M, S> = handle_acks::HandleAcks<S, Map<M, Addrd<Token>, ()>>;
+  pub type HandleAcks<M, S> = handle_acks::HandleAcks<S, Map<M, Addrd<Token>, Id>>;
   #[allow(missing_docs)]
   pub type Retry<P, A, S> = retry::Retry<S, Array<A, (retry::State<Clock<P>>, Addrd<Message<P>>)>>;
   #[allow(missing_docs)]
+  #[cfg(feature = "client")]
   pub type BufferResponses<P, M, S> =
     buffer_responses::BufferResponses<S,
-                                      Map<M, (SocketAddr, Token, toad_msg::Type), Addrd<Resp<P>>>>;
+                                      Map<M,
+                                          (SocketAddr, Token, toad_msg::Type),
+                                          Stamped<Clock<P>, Addrd<Resp<P>>>>,
+                                      Map<M, Token, Instant<Clock<P>>>,
+                                      Map<M, Addrd<Token>, Instant<Clock<P>>>>;
+  #[allow(missing_docs)]
+  #[cfg(feature = "client")]
+  pub type Backoff<P, A, S> = backoff::Backoff<S, Array<A, backoff::Pending<P>>>;
   #[allow(missing_docs)]
   pub type ProvisionIds<P, M, A, S> =
     provision_ids::ProvisionIds<P,
@@ -45,24 +80,72 @@ pub mod runtime {
                                     SocketAddrWithDefault,
                                     Array<A, Stamped<Clock<P>, IdWithDefault>>>>;
   #[allow(missing_docs)]
-  pub type Observe<P, A, S> = observe::Observe<S,
-                                               Array<A, observe::Sub<P>>,
-                                               Array<A, Addrd<Req<P>>>,
-                                               observe::SubHash_TypePathQueryAccept<P>>;
-
-  /// Parse -> ProvisionIds -> ProvisionTokens -> Ack -> Retry -> HandleAcks -> BufferResponses -> Observe
+  #[cfg(feature = "server")]
+  pub type Multicast<P, M, S> = multicast::Multicast<S, Map<M, Addrd<Token>, Instant<Clock<P>>>>;
+  #[allow(missing_docs)]
+  #[cfg(feature = "server")]
+  pub type Observe<P, A, S, Persist = observe::NoPersistence, Filter = observe::NoFilter> =
+    observe::Observe<S,
+                     Array<A, observe::Sub<P>>,
+                     Array<A, Addrd<Req<P>>>,
+                     observe::SubHash_TypePathQueryAccept<P>,
+                     Array<A, observe::LastSeq<P>>,
+                     Persist,
+                     Filter>;
+
+  /// Parse -> ProvisionIds -> ProvisionTokens (`client` only)
+  #[cfg(feature = "client")]
+  type Provisioned<P, Array, Map> = ProvisionTokens<ProvisionIds<P, Map, Array, Parse<()>>>;
+  #[cfg(not(feature = "client"))]
+  type Provisioned<P, Array, Map> = ProvisionIds<P, Map, Array, Parse<()>>;
+
+  /// Steps shared by clients and servers
   #[rustfmt::skip]
-  pub type Runtime<P, Array, Map> =
-    Observe<P, Array,
-    BufferResponses<P, Map,
+  type Core<P, Array, Map> =
     HandleAcks<Map,
     Retry<P, Array,
     Ack<
-    ProvisionTokens<
-    ProvisionIds<P, Map, Array,
-    Parse<
-    ()
-    >>>>>>>>;
+    OptionPolicy<
+    Provisioned<P, Array, Map>
+    >>>>;
+
+  /// Parse -> ProvisionIds -> ProvisionTokens -> OptionPolicy -> Ack -> Retry -> HandleAcks -> BufferResponses -> Backoff -> Multicast -> Observe
+  ///
+  /// `Persist` is the [`observe::Persistence`] used to save Observe registrations,
+  /// and `Filter` is the [`observe::NotificationFilter`] deciding which subscribers are notified.
+  ///
+  /// Without the `client` feature, ProvisionTokens, BufferResponses and Backoff are omitted,
+  /// and without the `server` feature Multicast and Observe are omitted (along with the `Persist` & `Filter` parameters).
+  ///
+  /// To assemble a stack including steps of your own, see [`steps!`](crate::steps).
+  #[cfg(all(feature = "client", feature = "server"))]
+  pub type Runtime<P,
+                   Array,
+                   Map,
+                   Persist = observe::NoPersistence,
+                   Filter = observe::NoFilter> =
+    Observe<P,
+            Array,
+            Multicast<P, Map, Backoff<P, Array, BufferResponses<P, Map, Core<P, Array, Map>>>>,
+            Persist,
+            Filter>;
+
+  #[allow(missing_docs)]
+  #[cfg(all(feature = "server", not(feature = "client")))]
+  pub type Runtime<P,
+                   Array,
+                   Map,
+                   Persist = observe::NoPersistence,
+                   Filter = observe::NoFilter> =
+    Observe<P, Array, Multicast<P, Map, Core<P, Array, Map>>, Persist, Filter>;
+
+  #[allow(missing_docs)]
+  #[cfg(all(feature = "client", not(feature = "server")))]
+  pub type Runtime<P, Array, Map> = Backoff<P, Array, BufferResponses<P, Map, Core<P, Array, Map>>>;
+
+  #[allow(missing_docs)]
+  #[cfg(not(any(feature = "client", feature = "server")))]
+  pub type Runtime<P, Array, Map> = Core<P, Array, Map>;
 
   #[allow(missing_docs)]
   #[cfg(feature = "std")]
@@ -70,8 +153,15 @@ pub mod runtime {
     use crate::std::PlatformTypes;
 
     /// Default steps + step order pre-applied with `Vec` and `BTreeMap`
-    pub type Runtime<Dtls> =
-      super::Runtime<PlatformTypes<Dtls>, naan::hkt::Vec, naan::hkt::BTreeMap>;
+    #[cfg(feature = "server")]
+    pub type Runtime<Dtls,
+                     Persist = crate::step::observe::NoPersistence,
+                     Filter = crate::step::observe::NoFilter> =
+      super::Runtime<PlatformTypes<Dtls>, naan::hkt::Vec, naan::hkt::BTreeMap, Persist, Filter>;
+
+    /// Default steps + step order pre-applied with `Vec` and `BTreeMap`
+    #[cfg(not(feature = "server"))]
+    pub type Runtime<Dtls> = super::Runtime<PlatformTypes<Dtls>, naan::hkt::Vec, naan::hkt::BTreeMap>;
   }
 }
 
@@ -90,6 +180,11 @@ pub mod runtime {
 ///
 /// Outbound non-confirmable responses and ACKs will never be retried.
 ///
+/// Requests may override the config with a [`retry::Policy`](crate::retry::Policy)
+/// (see [`Req::set_retry_policy`](crate::req::Req::set_retry_policy)), e.g. so that
+/// a non-idempotent POST is never retried. Policies are remembered by token (see
+/// [`Step::set_retry_policy`]), so they also apply when a request is re-sent with the same token.
+///
 /// Note that the bandwidth used for retrying will never significantly exceed
 /// [`probing_rate`](crate::config::Config.probing_rate), so retries may be delayed
 /// by a small amount to respect this parameter.
@@ -115,6 +210,10 @@ pub mod retry;
 ///
 /// Based on [`cmp_requests`](observe::Observe::cmp_requests), equivalent requests will be combined.
 ///
+/// The ETag of the last representation sent to each subscriber is remembered, and the re-sent requests
+/// carry it; an application using [`etag::auto`](crate::server::etag::auto) will then notify subscribers
+/// whose representation is still current with a 2.03 VALID and no payload.
+///
 /// # Example
 /// ### Given
 /// * a resource `<coap://server/temperature>`
@@ -132,21 +231,70 @@ pub mod retry;
 ///  - Request 2 `GET coap://server/temperature?above=23deg`
 ///
 /// The response to request 1 will be sent to clients A, B, and C. The response to request 2 will be sent to client D.
+///
+/// ## Persistence
+/// Registrations are lost when the device restarts, unless the step is given
+/// [`Persistence`](observe::Persistence) with [`Observe::persist_with`](observe::Observe::persist_with).
+///
+/// Registrations are saved with the sequence number of the last notification sent to each subscriber;
+/// if a notification's sequence number is not newer than that (e.g. the application's counter started over after a restart)
+/// it is replaced by the next sequence number.
+///
+/// ## Filtering
+/// By default every subscriber is sent every notification. To decide per subscriber
+/// (e.g. only notifying client D above when the temperature is above 23 degrees),
+/// register a [`NotificationFilter`](observe::NotificationFilter) with
+/// [`Observe::filter_with`](observe::Observe::filter_with); it is given each subscriber's
+/// request and the fresh representation.
+#[cfg(feature = "server")]
+#[cfg_attr(docsrs, doc(cfg(feature = "server")))]
 pub mod observe;
 
+/// # Suppress error responses to multicast requests
+/// * Client Flow ✗
+/// * Server Flow ✓
+///
+/// ## Internal State
+/// This step will store the tokens of requests that were sent to a multicast group
+/// (see [`Meta::is_multicast`](crate::req::Meta::is_multicast)), removing them
+/// as they are responded to, or once they are older than the exchange lifetime
+/// (or [`multicast_response_leisure`](crate::config::Msg::multicast_response_leisure),
+/// if longer) and will no longer be responded to.
+///
+/// ## Behavior
+/// 4.xx and 5.xx responses to multicast requests are [discarded](crate::platform::Effect::Discard)
+/// rather than sent ([RFC7252 section 8.2](https://datatracker.ietf.org/doc/html/rfc7252#section-8.2)),
+/// unless [`Multicast.suppress_error_responses`](crate::config::Multicast#structfield.suppress_error_responses)
+/// is `false`.
+///
+/// Requests are only known to be multicast when the socket reports the address
+/// datagrams were sent to (see [`Socket::recvd_dest`](crate::net::Socket::recvd_dest)).
+///
+/// ## Transformation
+/// None
+#[cfg(feature = "server")]
+#[cfg_attr(docsrs, doc(cfg(feature = "server")))]
+pub mod multicast;
+
 /// # Assign message tokens to those with Token(0)
 /// * Client Flow ✓
 /// * Server Flow ✗
 ///
 /// ## Internal State
-/// None
+/// Stores the tokens of up to 4 messages being forwarded
 ///
 /// ## Behavior
-/// Whenever a request is sent with an Token of 0, the Token is replaced
-/// with a new Token that has not been used yet.
+/// Whenever a request is sent with an Token of 0 (empty, or all zero bytes),
+/// the Token is replaced with a new non-zero Token that has not been used yet.
+///
+/// Requests forwarded on behalf of another endpoint with
+/// [`Platform::forward_msg`](crate::platform::Platform::forward_msg) (e.g. by a proxy)
+/// are never modified, since their token was chosen by the endpoint that originated them.
 ///
 /// ## Transformation
 /// None
+#[cfg(feature = "client")]
+#[cfg_attr(docsrs, doc(cfg(feature = "client")))]
 pub mod provision_tokens;
 
 /// # Assign message Ids to those with Id(0)
@@ -189,15 +337,82 @@ pub mod handle_acks;
 /// * Server Flow ✓
 ///
 /// ## Internal State
+/// Stores up to 16 CON requests, and whether each has been ACKed yet
+///
+/// ## Behavior
+/// If a CON request is received by a server and responses to the peer may be
+/// [piggybacked](crate::config::Msg#structfield.piggyback), the request is not ACKed right away,
+/// so that a response sent with [`Resp::ack`](crate::resp::Resp::ack) can be piggybacked on the ACK.
+/// If the response is sent separately instead (as a NON or, for [deferred](crate::server::ap::Respond::deferred)
+/// responses, a CON), or none is sent within half of the shortest
+/// [unacked retry delay](crate::config::Con#structfield.unacked_retry_strategy) (before the
+/// peer would retransmit the request), the request is ACKed with an Empty message with
+/// the request's Id (Empty messages have no Token).
+///
+/// When piggybacking is disabled for the peer, or too many requests are awaiting a response,
+/// CON requests are ACKed with an Empty message as soon as they are received.
+///
+/// CON responses (i.e. separate responses, sent by servers that
+/// ACK requests before responding to them) are ACKed with an Empty message
+/// with the response's Id as soon as they are received, including duplicates and
+/// responses that are buffered until they are polled for.
+///
+/// ## Transformation
+/// Outgoing piggybacked responses (ACKs with a response code) to requests that were already
+/// ACKed with an Empty message (e.g. because [`Msg.piggyback`](crate::config::Msg#structfield.piggyback)
+/// is disabled for the recipient, or the response took too long) are sent as separate CON
+/// responses with a new Id and the same Token, since a request may only be ACKed once (RFC 7252 §5.2.2).
+/// The same goes for piggybacked responses to requests this step does not know about.
+pub mod ack;
+
+/// # Reject messages with unrecognized critical options
+/// * Client Flow ✓
+/// * Server Flow ✓
+///
+/// ## Internal State
 /// None
 ///
 /// ## Behavior
-/// If a CON is received by a client or server,
-/// this step will reply with an ACK.
+/// Options whose number is odd are [critical](toad_msg::OptNumber::must_be_processed),
+/// and must be understood by the recipient. The options this application understands
+/// are listed in [`understood_options`](crate::config::Msg.understood_options).
+///
+/// Options that [`Parse`](parse::Parse) found to be [malformed](crate::config::MalformedOptions::Lenient)
+/// are treated as if they were unrecognized (RFC7252 section 5.4.3).
+///
+///  * Requests with an unrecognized critical option are responded to with 4.02 Bad Option, and WouldBlock is yielded
+///  * CON & NON responses with an unrecognized critical option are rejected with a RESET, and WouldBlock is yielded
+///  * ACK responses with an unrecognized critical option are ignored, and WouldBlock is yielded
 ///
 /// ## Transformation
+/// The [`MALFORMED`](opt::MALFORMED) option is removed from incoming messages
+pub mod option_policy;
+
+/// # Encode & decode payloads on the wire
+/// * Client Flow ✓
+/// * Server Flow ✓
+///
+/// ## Internal State
 /// None
-pub mod ack;
+///
+/// ## Behavior
+/// Outbound payloads whose Content-Format the [`PayloadCodec`](codec::PayloadCodec)
+/// [applies to](codec::PayloadCodec::applies_to) are encoded, and marked with
+/// the [`CONTENT_CODING`](codec::CONTENT_CODING) option. Payloads are only replaced when
+/// encoding makes them smaller.
+///
+/// Inbound messages with a [`CONTENT_CODING`](codec::CONTENT_CODING) option are decoded
+/// and the option is removed.
+///
+///  * Requests that can't be decoded are responded to with 4.00 Bad Request (4.02 Bad Option if the coding is unknown), and WouldBlock is yielded
+///  * Responses that can't be decoded are dropped, and WouldBlock is yielded
+///
+/// This step is not part of the default [`runtime`]; it must wrap the runtime
+/// on both ends of a link, e.g. `Codec<Runtime<..>, Deflate>`.
+///
+/// ## Transformation
+/// Inbound payloads are decoded
+pub mod codec;
 
 /// # Set standard options on outbound messages
 /// * Client Flow ✓
@@ -220,6 +435,8 @@ pub mod set_standard_options;
 ///
 /// ## Internal State
 ///  * Stores all responses received
+///  * Stores the time that multicast requests were sent
+///  * Stores the tokens of requests sent to each peer that have not yet been responded to
 ///
 /// ## Behavior
 ///  * Store incoming response
@@ -231,10 +448,53 @@ pub mod set_standard_options;
 ///      3. NON
 ///      4. RESET
 ///
+/// ### Eviction
+/// Each buffered response is stamped with the time it was received.
+///  * When a peer has [`max_buffered_responses_per_peer`](crate::config::Msg#structfield.max_buffered_responses_per_peer) responses buffered, its least recent is dropped to make room for a new one
+///  * When the buffer is full, the least recent response of any peer is dropped to make room for a new one
+///
+/// Dropping a response issues a [`log::Level::Warn`] log effect.
+///
+/// ### Multicast
+/// When polling for responses to a request sent to a multicast address:
+///  * Responses from any peer with a matching token are buffered, and duplicate responses from the same peer are dropped
+///  * WouldBlock is yielded until [`multicast_response_leisure`](crate::config::Msg.multicast_response_leisure) has elapsed since the request was sent
+///  * Buffered responses are then yielded one at a time
+///
+/// ### Token mismatches
+/// When a response is received from a peer that we've sent requests to, but its token
+/// matches none of them (e.g. the peer echoed the wrong token), a
+/// [`TokenMismatch`](buffer_responses::TokenMismatch) describing the peer, the tokens we expected
+/// and the token received is logged at [`log::Level::Warn`].
+///
 /// ## Transformation
-/// None
+/// Multicast requests are always sent as NON.
+#[cfg(feature = "client")]
+#[cfg_attr(docsrs, doc(cfg(feature = "client")))]
 pub mod buffer_responses;
 
+/// # Back off from servers that are unavailable
+/// * Client Flow ✓
+/// * Server Flow ✗
+///
+/// ## Internal State
+/// When [`Msg.service_unavailable_retries`](crate::config::Msg#structfield.service_unavailable_retries)
+/// is nonzero, copies of up to 16 requests that have been sent and not yet responded to
+///
+/// ## Behavior
+/// When the response being polled for is 5.03 SERVICE UNAVAILABLE, the server is asking
+/// not to be sent the request again until its Max-Age (60 seconds if it has none) has elapsed.
+///  * If `service_unavailable_retries` is `0` (the default), yield the response like any other
+///  * If the request has been re-sent `service_unavailable_retries` times already,
+///    yield [`Error::ServiceUnavailable`](backoff::Error::ServiceUnavailable) with the Max-Age as `retry_after`
+///  * Otherwise yield WouldBlock, and re-send the request with the same token once Max-Age has elapsed
+///
+/// ## Transformation
+/// None
+#[cfg(feature = "client")]
+#[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+pub mod backoff;
+
 /// # Parse messages from dgrams
 /// * Client Flow ✓
 /// * Server Flow ✓
@@ -245,8 +505,232 @@ pub mod buffer_responses;
 /// ## Behavior
 ///  * Parse dgrams from snapshot into Message
 ///  * Wrap Message with Req/Resp (no filtering)
+///  * Requests that do not fit in the platform's fixed message capacity
+///    (too many options, an option value too long, or a payload too long)
+///    are responded to with 4.13 Request Entity Too Large, with Size1 set
+///    to the maximum payload size
+///  * If [`Msg.oversized`](crate::config::Msg#structfield.oversized) is
+///    [`Oversized::Truncate`](crate::config::Oversized::Truncate), requests with
+///    payloads that are too long are instead truncated, and marked with [`Req::truncated`](crate::req::Req::truncated)
+///  * The length of the received datagram is available on requests with [`Req::received`](crate::req::Req::received)
+///  * Options are checked against their definitions in RFC7252, RFC7641 & RFC7959 according to
+///    [`Msg.malformed_options`](crate::config::Msg#structfield.malformed_options); by default, malformed options
+///    are removed and recorded in the [`MALFORMED`](opt::MALFORMED) option for [`option_policy`] to act on
 pub mod parse;
 
+/// # Capture dgrams sent & received
+/// * Client Flow ✓
+/// * Server Flow ✓
+///
+/// This step is not included in the default [`runtime`], and is intended
+/// to be wrapped around it while debugging:
+///
+/// ```
+/// use toad::std::dtls;
+/// use toad::step::capture::Capture;
+/// use toad::step::runtime;
+///
+/// type Steps = Capture<runtime::std::Runtime<dtls::N>>;
+/// ```
+///
+/// ## Internal State
+/// None
+///
+/// ## Behavior
+///  * Emit [`Effect::Capture`](crate::platform::Effect::Capture) for dgrams in the snapshot
+///  * Emit [`Effect::Capture`](crate::platform::Effect::Capture) for all messages sent
+///
+/// It's up to the [`Platform`](crate::platform::Platform::capture) to do something with these,
+/// e.g. [`toad::std::Platform`](crate::std::Platform::capture_to) writes them to a pcapng file
+/// that can be opened in Wireshark.
+///
+/// ## Transformation
+/// None
+pub mod capture;
+
+/// # Count messages sent, received & retransmitted
+/// * Client Flow ✓
+/// * Server Flow ✓
+///
+/// This step is not included in the default [`runtime`]; wrap it around
+/// the runtime to keep [`Stats`](metrics::Stats), e.g. for a
+/// [`DeviceInfo`](crate::server::resources::DeviceInfo) resource:
+///
+/// ```
+/// use toad::std::dtls;
+/// use toad::step::metrics::Metrics;
+/// use toad::step::runtime;
+///
+/// type Steps = Metrics<runtime::std::Runtime<dtls::N>>;
+/// ```
+///
+/// The counters can then be read with `platform.steps().stats()`.
+///
+/// ## Internal State
+///  * Counters
+///  * The addresses & Ids of the last 32 CON / NON messages sent
+///
+/// ## Behavior
+///  * Count dgrams in the snapshot as received
+///  * Count all messages sent
+///  * Count CON / NON messages with the same Id & address as one of the
+///    last 32 sent as retransmitted
+///
+/// ## Transformation
+/// None
+pub mod metrics;
+
+/// # Stop sending requests to unresponsive peers
+/// * Client Flow ✓
+/// * Server Flow ✗
+///
+/// This step is not included in the default [`runtime`]; wrap it around
+/// the runtime so that requests to a peer that has stopped responding
+/// fail fast rather than being retransmitted until they time out:
+///
+/// ```
+/// use toad::net::health::Breaker;
+/// use toad::std::dtls;
+/// use toad::step::circuit_breaker::CircuitBreaker;
+/// use toad::step::runtime;
+///
+/// type Steps = CircuitBreaker<runtime::std::Runtime<dtls::N>>;
+///
+/// let steps = Steps::default().with_breaker(Breaker { trip_after: 3,
+///                                                     ..Breaker::default() });
+/// ```
+///
+/// ## Internal State
+///  * The [`Health`](crate::net::health::Health) of the 16 peers most recently sent requests
+///  * The addresses, Ids, tokens & deadlines of up to 16 CON requests awaiting a reply
+///
+/// ## Behavior
+///  * When a CON request is sent, wait up to [`max_transmit_wait`](crate::config::Timing::max_transmit_wait) for a reply
+///  * Count an Empty ACK or response as a success, and a RESET or no reply as a failure
+///  * NON requests may go unanswered, and do not count towards a peer's health
+///  * After [`Breaker::trip_after`](crate::net::health::Breaker::trip_after) consecutive failures,
+///    refuse to send requests to the peer for [`Breaker::cool_down`](crate::net::health::Breaker::cool_down)
+///    and yield [`Error::PeerUnhealthy`](circuit_breaker::Error::PeerUnhealthy) instead
+///
+/// ## Transformation
+/// None
+pub mod circuit_breaker;
+
+/// # Trace the events handled by each step
+/// * Client Flow ✓
+/// * Server Flow ✓
+///
+/// This step is not included in the default [`runtime`]. [`Trace`](trace::Trace)
+/// wraps the step preceding it, so a stack can be traced step-by-step
+/// by following each step of interest with it:
+///
+/// ```
+/// use toad::step::ack::Ack;
+/// use toad::step::parse::Parse;
+/// use toad::step::trace::Trace;
+/// use toad::steps;
+///
+/// type Stack = steps![Parse, Trace, Ack, Trace];
+///
+/// let _: Trace<Ack<Trace<Parse<()>>>> = Stack::default();
+/// ```
+///
+/// ## Internal State
+/// None
+///
+/// ## Behavior
+///  * Emit an [`Effect::Trace`](crate::platform::Effect::Trace) entering the wrapped step
+///    before it handles an event, and one exiting it afterwards
+///  * Tag each with the [`CorrelationId`](trace::CorrelationId) of the message being handled,
+///    derived from its token & peer address
+///
+/// It's up to the [`Platform`](crate::platform::Platform::trace) to do something with these;
+/// by default they are logged, and with the `std_tracing` feature
+/// [`toad::std::Platform`](crate::std::Platform) emits them as [`tracing`](https://docs.rs/tracing) events.
+///
+/// ## Transformation
+/// None
+#[cfg(feature = "trace-steps")]
+#[cfg_attr(docsrs, doc(cfg(feature = "trace-steps")))]
+pub mod trace;
+
+/// # Erase the type of a stack of steps
+/// * Client Flow ✓
+/// * Server Flow ✓
+///
+/// Every step is generic over the step it wraps, so each distinct stack
+/// of steps gets its own copy of every step's code. This is free for
+/// most applications, but on targets with little flash the duplicated code
+/// (e.g. when an application has more than one [`Platform`](crate::platform::Platform))
+/// can matter.
+///
+/// [`DynStep`](dyn_step::DynStep) boxes a step and invokes it through a vtable,
+/// so code wrapping it is only compiled once, trading a virtual call per
+/// event for a smaller binary.
+///
+/// ```
+/// use toad::config::Config;
+/// use toad::std::{dtls, Platform, PlatformTypes};
+/// use toad::step::dyn_step::DynStep;
+/// use toad::step::{runtime, Step};
+///
+/// type P = PlatformTypes<dtls::N>;
+/// type Runtime = runtime::std::Runtime<dtls::N>;
+/// type Steps = DynStep<P, <Runtime as Step<P>>::Error>;
+///
+/// let steps = DynStep::new(Runtime::default());
+/// let toad =
+///   Platform::<dtls::N, Steps>::try_new_with_steps("127.0.0.1:0", Config::default(), steps).unwrap();
+/// ```
+///
+/// ## Internal State
+/// The boxed step
+///
+/// ## Behavior
+/// Invoke the boxed step. [`DynStep::default`](dyn_step::DynStep) boxes a step that does nothing.
+///
+/// ## Transformation
+/// None
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod dyn_step;
+
+/// # Reuse messages instead of allocating new ones
+/// * Client Flow ✓
+/// * Server Flow ✓
+///
+/// This step is not included in the default [`runtime`]. On platforms
+/// storing payloads on the heap, put [`Pool`](pool::Pool) beneath [`parse`]
+/// so that messages parsed from incoming dgrams reuse the storage of
+/// messages that have already been sent:
+///
+/// ```
+/// use toad::platform::Message;
+/// use toad::std::{dtls, PlatformTypes};
+/// use toad::step::ack::Ack;
+/// use toad::step::parse::Parse;
+/// use toad::step::pool::Pool;
+/// use toad::steps;
+///
+/// type P = PlatformTypes<dtls::N>;
+/// type Stack = steps![Pool<Vec<Message<P>>>, Parse, Ack];
+///
+/// let _ = Stack::default();
+/// ```
+///
+/// ## Internal State
+///  * A [`MessagePool`](crate::platform::MessagePool) of messages that can be reused
+///
+/// ## Behavior
+///  * Yield messages from the pool in [`Step::checkout`], which [`parse`] uses
+///    to store the payloads of incoming messages
+///  * Keep messages given to [`Step::recycle`] in the pool, which the [`Platform`](crate::platform::Platform)
+///    does with messages once they have been sent
+///
+/// ## Transformation
+/// None
+pub mod pool;
+
 /// ```text
 ///             None -> "You may run, the step may have done nothing or just performed some effects"
 ///         Some(Ok) -> "You may run, the step yielded a T that could be transformed or discarded"
@@ -285,14 +769,19 @@ macro_rules! exec_inner_step {
 }
 
 /// Issue an `Effect::Log`
+///
+/// The message is only formatted (and the effect only issued)
+/// if `$lvl` is enabled for the `toad` target in the [`log`] crate.
 #[macro_export]
 macro_rules! log {
   ($at:path, $effs:expr, $lvl:expr, $($arg:tt)*) => {{
-    use toad_array::Array;
-    type S = $crate::todo::String::<1000>;
-    let msg = S::fmt(format_args!($($arg)*));
-    let msg = S::fmt(format_args!("[{}] {}", stringify!($at), msg.as_str()));
-    $effs.push($crate::platform::Effect::Log($lvl, msg));
+    let lvl: ::log::Level = $lvl;
+    if ::log::log_enabled!(target: "toad", lvl) {
+      use toad_array::Array;
+      type S = $crate::todo::String::<1000>;
+      let msg = S::fmt(format_args!("[{}] {}", stringify!($at), format_args!($($arg)*)));
+      $effs.push($crate::platform::Effect::Log(lvl, msg));
+    }
   }};
 }
 
@@ -309,7 +798,63 @@ macro_rules! _try {
   }};
 }
 
-pub use {_try, exec_inner_step, log};
+/// Assemble a stack of steps, innermost first
+///
+/// Each step is given the step before it as its last type argument,
+/// with the first step wrapping `()`. This is the convention followed
+/// by the type aliases in [`step::runtime`](crate::step::runtime),
+/// so those can be used here to avoid spelling out buffer types.
+///
+/// Every step in this crate implements `Default` when its inner step does,
+/// so the resulting stack does too.
+///
+/// ```
+/// use toad::step::ack::Ack;
+/// use toad::step::metrics::Metrics;
+/// use toad::step::parse::Parse;
+/// use toad::steps;
+///
+/// type Stack = steps![Parse, Ack, Metrics];
+///
+/// let stack: Metrics<Ack<Parse<()>>> = Stack::default();
+/// ```
+///
+/// Steps of your own can be inserted anywhere, provided
+/// the step they wrap is their last type argument:
+///
+/// ```
+/// use toad::std::{dtls, PlatformTypes as Std};
+/// use toad::step::parse::Parse;
+/// use toad::step::{ack, runtime};
+/// use toad::steps;
+///
+/// #[derive(Default)]
+/// struct Audit<S>(S);
+///
+/// type P = Std<dtls::N>;
+///
+/// type Stack = steps![Parse,
+///                     runtime::ProvisionIds<P, naan::hkt::BTreeMap, naan::hkt::Vec>,
+///                     Audit,
+///                     ack::Ack];
+///
+/// let _ = Stack::default();
+/// ```
+#[macro_export]
+macro_rules! steps {
+  (@acc [$($acc:tt)*]) => {$($acc)*};
+  (@acc [$($acc:tt)*] $($seg:ident)::+ $(<$($arg:ty),* $(,)?>)? $(, $($rest:tt)*)?) => {
+    $crate::steps!(@acc [$($seg)::+ <$($($arg,)*)? $($acc)*>] $($($rest)*)?)
+  };
+  (@acc [$($acc:tt)*] $($rest:tt)*) => {
+    compile_error!(concat!("expected a step type, like `Parse` or `runtime::Retry<P, A>`, found `",
+                           stringify!($($rest)*),
+                           "`"))
+  };
+  ($($steps:tt)*) => {$crate::steps!(@acc [()] $($steps)*)};
+}
+
+pub use {_try, exec_inner_step, log, steps};
 
 /// An error that can be returned by a [`Step`].
 pub trait Error: core::fmt::Debug {}
@@ -368,6 +913,79 @@ pub trait Step<P: PlatformTypes>: Default {
         .map_err(Self::Error::from)
   }
 
+  /// # Cancel an exchange
+  ///
+  /// Forget all state held for messages with token `token`
+  /// (e.g. requests waiting to be retransmitted, responses buffered
+  /// for a request), so that nothing more is sent or yielded for it.
+  ///
+  /// Used for user-initiated cancellation and for requests that timed out.
+  ///
+  /// # Gotchas
+  /// Make sure you invoke `self.inner().cancel`!
+  ///
+  /// # Default Implementation
+  /// The default implementation will just invoke `self.inner().cancel`
+  fn cancel(&self,
+            snap: &platform::Snapshot<P>,
+            effects: &mut P::Effects,
+            token: Token)
+            -> Result<(), Self::Error> {
+    self.inner()
+        .cancel(snap, effects, token)
+        .map_err(Self::Error::from)
+  }
+
+  /// # Override how an exchange is retried
+  ///
+  /// Retransmissions of messages with token `token` sent to the same address
+  /// should follow `policy` instead of the config.
+  ///
+  /// Invoked by [`Platform::send_req`](crate::platform::Platform::send_req)
+  /// right after a request with a [`retry::Policy`](crate::retry::Policy)
+  /// other than the default was sent.
+  ///
+  /// # Gotchas
+  /// Make sure you invoke `self.inner().set_retry_policy`!
+  ///
+  /// # Default Implementation
+  /// The default implementation will just invoke `self.inner().set_retry_policy`
+  fn set_retry_policy(&self,
+                      snap: &platform::Snapshot<P>,
+                      effects: &mut P::Effects,
+                      token: Addrd<Token>,
+                      policy: crate::retry::Policy)
+                      -> Result<(), Self::Error> {
+    self.inner()
+        .set_retry_policy(snap, effects, token, policy)
+        .map_err(Self::Error::from)
+  }
+
+  /// # Forward a message on behalf of another endpoint
+  ///
+  /// Invoked by [`Platform::forward_msg`](crate::platform::Platform::forward_msg)
+  /// with `forwarding = true` right before a message with token `token` is forwarded
+  /// (e.g. by a proxy), and with `forwarding = false` once it was sent (or failed to be).
+  ///
+  /// The token of a forwarded message belongs to the endpoint that originated it,
+  /// so steps must not rewrite it.
+  ///
+  /// # Gotchas
+  /// Make sure you invoke `self.inner().set_forwarding`!
+  ///
+  /// # Default Implementation
+  /// The default implementation will just invoke `self.inner().set_forwarding`
+  fn set_forwarding(&self,
+                    snap: &platform::Snapshot<P>,
+                    effects: &mut P::Effects,
+                    token: Addrd<Token>,
+                    forwarding: bool)
+                    -> Result<(), Self::Error> {
+    self.inner()
+        .set_forwarding(snap, effects, token, forwarding)
+        .map_err(Self::Error::from)
+  }
+
   /// Invoked before messages are sent, allowing for internal state change & modification.
   ///
   /// # Gotchas
@@ -401,6 +1019,46 @@ pub trait Step<P: PlatformTypes>: Default {
         .on_message_sent(snap, effects, msg)
         .map_err(Self::Error::from)
   }
+
+  /// Get a message that was [recycled](Step::recycle) earlier,
+  /// to be overwritten instead of allocating a new one.
+  ///
+  /// Yields `None` if there are no messages to reuse, in which
+  /// case the caller should create a message the usual way.
+  ///
+  /// See [`pool`] for more.
+  ///
+  /// # Default Implementation
+  /// The default implementation will just invoke `self.inner().checkout`
+  fn checkout(&self) -> Option<platform::Message<P>> {
+    self.inner().checkout()
+  }
+
+  /// Give back a message that is no longer needed,
+  /// so that its storage can be reused by [`Step::checkout`].
+  ///
+  /// See [`pool`] for more.
+  ///
+  /// # Default Implementation
+  /// The default implementation will just invoke `self.inner().recycle`
+  fn recycle(&self, msg: platform::Message<P>) {
+    self.inner().recycle(msg)
+  }
+
+  /// Write a human-readable description of the internal state
+  /// held by this step (e.g. messages waiting to be retried)
+  /// for debugging.
+  ///
+  /// # Gotchas
+  /// Make sure you invoke `self.inner().snapshot_state`!
+  ///
+  /// # Default Implementation
+  /// The default implementation writes nothing & invokes `self.inner().snapshot_state`
+  fn snapshot_state<W>(&self, w: &mut W) -> core::fmt::Result
+    where W: core::fmt::Write
+  {
+    self.inner().snapshot_state(w)
+  }
 }
 
 impl<P: PlatformTypes> Step<P> for () {
@@ -435,6 +1093,32 @@ impl<P: PlatformTypes> Step<P> for () {
     Ok(())
   }
 
+  fn cancel(&self,
+            _: &platform::Snapshot<P>,
+            _: &mut P::Effects,
+            _: Token)
+            -> Result<(), Self::Error> {
+    Ok(())
+  }
+
+  fn set_retry_policy(&self,
+                      _: &platform::Snapshot<P>,
+                      _: &mut P::Effects,
+                      _: Addrd<Token>,
+                      _: crate::retry::Policy)
+                      -> Result<(), Self::Error> {
+    Ok(())
+  }
+  fn set_forwarding(&self,
+                    _: &platform::Snapshot<P>,
+                    _: &mut P::Effects,
+                    _: Addrd<Token>,
+                    _: bool)
+                    -> Result<(), Self::Error> {
+    Ok(())
+  }
+
+
   fn before_message_sent(&self,
                          _: &platform::Snapshot<P>,
                          _: &mut P::Effects,
@@ -450,6 +1134,18 @@ impl<P: PlatformTypes> Step<P> for () {
                      -> Result<(), Self::Error> {
     Ok(())
   }
+
+  fn checkout(&self) -> Option<platform::Message<P>> {
+    None
+  }
+
+  fn recycle(&self, _: platform::Message<P>) {}
+
+  fn snapshot_state<W>(&self, _: &mut W) -> core::fmt::Result
+    where W: core::fmt::Write
+  {
+    Ok(())
+  }
 }
 
 #[cfg(test)]
@@ -464,6 +1160,9 @@ pub mod test {
     platform::Snapshot { time: ClockMock::new().try_now().unwrap(),
                          recvd_dgram: Some(crate::net::Addrd(Default::default(),
                                                              crate::test::dummy_addr())),
+                         recvd_identity: None,
+                         recvd_dest: None,
+                         session: None,
                          config: crate::config::Config::default() }
   }
 
@@ -875,6 +1574,8 @@ pub mod test {
 
           dummy_step!($inner_step);
 
+          test::log_level(::log::LevelFilter::Trace);
+
           let mut effects: <test::Platform as platform::PlatformTypes>::Effects = Default::default();
           let mut snapshot: platform::Snapshot<test::Platform> = $crate::step::test::default_snapshot();
           let mut token = ::toad_msg::Token(Default::default());
@@ -915,4 +1616,38 @@ pub mod test {
   }
 
   pub use {dummy_step, test_step, test_step_when};
+
+  #[test]
+  #[cfg(feature = "server")]
+  fn internal_options_do_not_collide() {
+    let mut numbers = vec![opt::MALFORMED,
+                           codec::CONTENT_CODING,
+                           observe::opt::WAS_CREATED_BY_OBSERVE];
+    let len = numbers.len();
+    numbers.sort();
+    numbers.dedup();
+    assert_eq!(numbers.len(), len);
+  }
+
+  #[test]
+  fn log_skips_disabled_levels() {
+    test::log_level(log::LevelFilter::Warn);
+
+    let mut effects = Vec::<test::Effect>::new();
+    log!(Test, effects, log::Level::Trace, "{}", "trace");
+    log!(Test, effects, log::Level::Debug, "{}", "debug");
+    log!(Test, effects, log::Level::Info, "{}", "info");
+    assert_eq!(effects, vec![]);
+
+    log!(Test, effects, log::Level::Warn, "{}", "warn");
+    log!(Test, effects, log::Level::Error, "{}", "error");
+    assert_eq!(effects.iter()
+                      .map(|e| match e {
+                        | platform::Effect::Log(lvl, msg) => (*lvl, msg.as_str().to_string()),
+                        | _ => panic!("{:?}", e),
+                      })
+                      .collect::<Vec<_>>(),
+               vec![(log::Level::Warn, "[Test] warn".to_string()),
+                    (log::Level::Error, "[Test] error".to_string())]);
+  }
 }
@@ -0,0 +1,242 @@
+use embedded_time::Instant;
+use naan::prelude::ResultExt;
+use toad_array::Array;
+use toad_len::Len;
+use toad_map::{InsertError, Map};
+use toad_msg::{CodeKind, Token};
+use toad_stem::Stem;
+
+use super::{log, Step, StepOutput};
+use crate::net::Addrd;
+use crate::platform::PlatformTypes;
+use crate::req::Req;
+use crate::resp::Resp;
+use crate::{exec_inner_step, platform};
+
+/// Struct responsible for suppressing error responses to multicast requests
+///
+/// `B` maps multicast requests awaiting a response to when they were received.
+///
+/// For more information, see the [module documentation](crate::step::multicast).
+#[derive(Debug)]
+pub struct Multicast<S, B> {
+  requests: Stem<B>,
+  inner: S,
+}
+
+impl<S: Default, B: Default> Default for Multicast<S, B> {
+  fn default() -> Self {
+    Self { requests: Default::default(),
+           inner: S::default() }
+  }
+}
+
+impl<S, B> Multicast<S, B> {
+  /// Forget requests that were received longer ago than `lifetime`;
+  /// they will not be responded to anymore.
+  fn remove_expired<P>(&self, now: Instant<P::Clock>, lifetime: crate::time::Millis)
+    where P: PlatformTypes,
+          B: Map<Addrd<Token>, Instant<P::Clock>>
+  {
+    let expired = |at: &Instant<P::Clock>| {
+      crate::time::elapsed(*at, now).map(|d| d > lifetime)
+                                    .unwrap_or(false)
+    };
+
+    self.requests.map_mut(|reqs| loop {
+                   let key = reqs.iter().find(|(_, at)| expired(at)).map(|(k, _)| *k);
+
+                   match key {
+                     | Some(key) => {
+                       reqs.remove(&key);
+                     },
+                     | None => break,
+                   }
+                 })
+  }
+}
+
+impl<P: PlatformTypes,
+      B: Map<Addrd<Token>, Instant<P::Clock>> + core::fmt::Debug,
+      E: super::Error,
+      S: Step<P, PollReq = Addrd<Req<P>>, PollResp = Addrd<Resp<P>>, Error = E>> Step<P>
+  for Multicast<S, B>
+{
+  type PollReq = Addrd<Req<P>>;
+  type PollResp = Addrd<Resp<P>>;
+  type Error = E;
+  type Inner = S;
+
+  fn inner(&self) -> &S {
+    &self.inner
+  }
+
+  fn snapshot_state<W>(&self, w: &mut W) -> core::fmt::Result
+    where W: core::fmt::Write
+  {
+    self.requests.map_ref(|reqs| {
+                   writeln!(w, "Multicast: {} request(s) awaiting response", reqs.len())?;
+                   reqs.iter()
+                       .try_for_each(|(Addrd(token, addr), _)| writeln!(w, "  {} {:?}", addr, token))
+                 })?;
+
+    self.inner.snapshot_state(w)
+  }
+
+  fn poll_req(&self,
+              snap: &platform::Snapshot<P>,
+              effects: &mut P::Effects)
+              -> StepOutput<Self::PollReq, Self::Error> {
+    // Requests that were never responded to (e.g. no handler matched them)
+    // would otherwise never be removed
+    let lifetime = snap.config
+                       .timing()
+                       .exchange_lifetime
+                       .max(snap.config.msg.multicast_response_leisure);
+    self.remove_expired::<P>(snap.time, lifetime);
+
+    let req = exec_inner_step!(self.inner.poll_req(snap, effects), core::convert::identity);
+
+    req.map(|req| {
+         if req.data().meta().map(|m| m.is_multicast()).unwrap_or(false) {
+           let key = Addrd(req.data().msg().token, req.addr());
+           let inserted = self.requests
+                              .map_mut(|reqs| reqs.insert(key, snap.time))
+                              .recover(|e| match e {
+                                | InsertError::Exists(_) => Ok(()),
+                                | e => Err(e),
+                              });
+
+           if inserted.is_err() {
+             log!(Multicast::poll_req,
+                  effects,
+                  log::Level::Warn,
+                  "Too many multicast requests awaiting responses; an error response to {:?} will not be suppressed",
+                  key);
+           }
+         }
+
+         Ok(req)
+       })
+  }
+
+  fn poll_resp(&self,
+               snap: &platform::Snapshot<P>,
+               effects: &mut P::Effects,
+               token: Token,
+               addr: no_std_net::SocketAddr)
+               -> StepOutput<Self::PollResp, Self::Error> {
+    self.inner.poll_resp(snap, effects, token, addr)
+  }
+
+  fn before_message_sent(&self,
+                         snap: &platform::Snapshot<P>,
+                         effects: &mut P::Effects,
+                         msg: &mut Addrd<platform::Message<P>>)
+                         -> Result<(), Self::Error> {
+    self.inner.before_message_sent(snap, effects, msg)?;
+
+    if msg.data().code.kind() != CodeKind::Response {
+      return Ok(());
+    }
+
+    let key = Addrd(msg.data().token, msg.addr());
+    let was_multicast = self.requests
+                            .map_mut(|reqs| reqs.remove(&key))
+                            .is_some();
+
+    if was_multicast
+       && snap.config.multicast.suppress_error_responses
+       && msg.data().code.class >= 4
+    {
+      log!(Multicast::before_message_sent,
+           effects,
+           log::Level::Debug,
+           "Not sending {:?} response to multicast request from {:?}",
+           msg.data().code,
+           msg.addr());
+      effects.push(platform::Effect::Discard);
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::BTreeMap;
+
+  use toad_msg::Code;
+
+  use super::*;
+  use crate::req::Meta;
+  use crate::test;
+
+  type Mock = test::MockStep<(), Addrd<Req<test::Platform>>, Addrd<Resp<test::Platform>>, ()>;
+  type Multicast =
+    super::Multicast<Mock, BTreeMap<Addrd<Token>, Instant<test::ClockMock>>>;
+
+  /// Receive a request sent to `dest`, then respond to it with `code`,
+  /// yielding whether the response was discarded
+  fn discards(dest: no_std_net::SocketAddr, code: Code) -> bool {
+    let sut = Multicast::default();
+
+    let mut req = test::msg!(NON GET x.x.x.x:1111).map(Req::from);
+    req.as_mut().set_meta(Some(Meta { arrived_at: test::ClockMock::instant(0),
+                                      local_addr: dest }));
+
+    let mut rep = test::msg!(NON {2 . 05} x.x.x.x:1111);
+    rep.as_mut().code = code;
+    rep.as_mut().token = req.data().msg().token;
+
+    sut.inner()
+       .set_poll_req(move |_, _, _| Some(Ok(req.clone())));
+
+    let (snap, mut effs) = (test::snapshot(), Vec::<test::Effect>::new());
+    sut.poll_req(&snap, &mut effs).unwrap().unwrap();
+    sut.before_message_sent(&snap, &mut effs, &mut rep).unwrap();
+
+    effs.contains(&test::Effect::Discard)
+  }
+
+  #[test]
+  fn error_responses_to_multicast_requests_are_discarded() {
+    let group = crate::net::ipv4_socketaddr([224, 0, 1, 187], 5683);
+
+    assert!(discards(group, Code::new(4, 4)));
+    assert!(discards(group, Code::new(5, 0)));
+    assert!(!discards(group, Code::new(2, 5)));
+  }
+
+  #[test]
+  fn error_responses_to_unicast_requests_are_sent() {
+    assert!(!discards(test::dummy_addr(), Code::new(4, 4)));
+  }
+
+  #[test]
+  fn unanswered_multicast_requests_are_forgotten() {
+    let sut = Multicast::default();
+    let group = crate::net::ipv4_socketaddr([224, 0, 1, 187], 5683);
+
+    let mut req = test::msg!(NON GET x.x.x.x:1111).map(Req::from);
+    req.as_mut().set_meta(Some(Meta { arrived_at: test::ClockMock::instant(0),
+                                      local_addr: group }));
+    sut.inner()
+       .set_poll_req(move |_, _, _| Some(Ok(req.clone())));
+
+    let (mut snap, mut effs) = (test::snapshot(), Vec::<test::Effect>::new());
+    sut.poll_req(&snap, &mut effs).unwrap().unwrap();
+    assert_eq!(sut.requests.map_ref(|r| r.len()), 1);
+
+    sut.inner().set_poll_req(|_, _, _| None);
+    let lifetime = snap.config.timing().exchange_lifetime.0;
+
+    snap.time = test::ClockMock::instant(lifetime * 1000);
+    assert!(sut.poll_req(&snap, &mut effs).is_none());
+    assert_eq!(sut.requests.map_ref(|r| r.len()), 1);
+
+    snap.time = test::ClockMock::instant((lifetime + 1) * 1000);
+    assert!(sut.poll_req(&snap, &mut effs).is_none());
+    assert_eq!(sut.requests.map_ref(|r| r.len()), 0);
+  }
+}
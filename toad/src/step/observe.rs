@@ -2,21 +2,26 @@ use core::fmt::{Debug, Write};
 use core::hash::{Hash, Hasher};
 use core::marker::PhantomData;
 
+use embedded_time::Instant;
 use no_std_net::SocketAddr;
 use toad_array::Array;
 use toad_hash::Blake2Hasher;
+use toad_msg::opt::known::no_repeat::OBSERVE;
 use toad_msg::opt::known::observe::Action::{Deregister, Register};
-use toad_msg::opt::known::repeat::QUERY;
+use tinyvec::ArrayVec;
+use toad_msg::opt::known::repeat::{ETAG, QUERY};
 use toad_msg::repeat::PATH;
 use toad_msg::{CodeKind, Id, MessageOptions, Token};
 use toad_stem::Stem;
 
 use super::{log, Step};
+use crate::logging::list;
 use crate::net::Addrd;
+use crate::observe::seq_is_newer;
+pub use crate::observe::{is_fresh, notification_seq, FRESHNESS_WINDOW};
 use crate::platform::{self, Effect, PlatformTypes};
 use crate::req::Req;
-use crate::resp::Resp;
-use crate::todo::String;
+use crate::resp::{code, Resp};
 
 /// Custom metadata options used to track messages created by this step.
 ///
@@ -150,17 +155,29 @@ impl<P, T> SubscriptionHash<P> for &mut T
   }
 }
 
+/// An ETag of up to 8 bytes
+#[doc = toad_macros::rfc_7252_doc!("5.10.6")]
+pub type Etag = ArrayVec<[u8; 8]>;
+
 /// An Observe subscription
 pub struct Sub<P>
   where P: PlatformTypes
 {
   req: Addrd<Req<P>>,
+  seq: Option<u32>,
+  etag: Option<Etag>,
+  pending_notification: bool,
 }
 
 impl<P> core::fmt::Debug for Sub<P> where P: PlatformTypes
 {
   fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-    f.debug_struct("Sub").field("req", &self.req).finish()
+    f.debug_struct("Sub")
+     .field("req", &self.req)
+     .field("seq", &self.seq)
+     .field("etag", &self.etag)
+     .field("pending_notification", &self.pending_notification)
+     .finish()
   }
 }
 
@@ -168,7 +185,55 @@ impl<P> Sub<P> where P: PlatformTypes
 {
   #[allow(missing_docs)]
   pub fn new(req: Addrd<Req<P>>) -> Self {
-    Self { req }
+    Self { req,
+           seq: None,
+           etag: None,
+           pending_notification: false }
+  }
+
+  /// Re-create a subscription from a saved [`Registration`]
+  pub fn from_registration(reg: &Registration) -> Self {
+    let mut req = Req::get(reg.path.as_str());
+    req.msg_mut().token = reg.token;
+    req.msg_mut().set_observe(Register).ok();
+
+    Self { req: Addrd(req, reg.addr),
+           seq: reg.seq,
+           etag: None,
+           pending_notification: false }
+  }
+
+  /// Get the [`Registration`] to save for this subscription
+  pub fn registration(&self) -> Registration {
+    let mut path = RegistrationPath::default();
+    self.msg()
+        .get(PATH)
+        .map(|segs| segs.iter())
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .for_each(|(ix, seg)| {
+          let sep = if ix == 0 { "" } else { "/" };
+          write!(path,
+                 "{}{}",
+                 sep,
+                 core::str::from_utf8(seg.as_bytes()).unwrap_or_default()).ok();
+        });
+
+    Registration { addr: self.addr(),
+                   token: self.token(),
+                   path,
+                   seq: self.seq }
+  }
+
+  /// The sequence number of the last notification sent to this subscriber
+  pub fn seq(&self) -> Option<u32> {
+    self.seq
+  }
+
+  /// The ETag of the representation last sent to this subscriber, if it had one
+  pub fn etag(&self) -> Option<&[u8]> {
+    self.etag.as_ref().map(|tag| tag.as_slice())
   }
 
   #[allow(missing_docs)]
@@ -203,29 +268,419 @@ impl<P> Sub<P> where P: PlatformTypes
   }
 }
 
+/// Path of a persisted [`Registration`]
+pub type RegistrationPath = crate::todo::String<256>;
+
+/// A server-side Observe registration, as saved by [`Persistence`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registration {
+  /// Address of the subscriber
+  pub addr: SocketAddr,
+  /// Token of the subscriber's register request
+  pub token: Token,
+  /// Path of the observed resource, e.g. `"sensors/temp"`
+  pub path: RegistrationPath,
+  /// Sequence number of the last notification sent to the subscriber (if any)
+  pub seq: Option<u32>,
+}
+
+/// Storage for server-side Observe registrations,
+/// so that subscribers keep receiving notifications
+/// after the device restarts.
+///
+/// Registrations are saved whenever they change, and loaded
+/// when storage is attached to the step with [`Observe::persist_with`].
+///
+/// [`NoPersistence`] is used by default; [`crate::std::observe::FilePersistence`]
+/// saves registrations to a file.
+pub trait Persistence {
+  /// Errors encountered when saving or loading
+  type Error: Debug;
+
+  /// Overwrite all saved registrations with `regs`
+  fn save<I>(&mut self, regs: I) -> Result<(), Self::Error>
+    where I: Iterator<Item = Registration>;
+
+  /// Invoke `f` with each saved registration
+  fn load<F>(&mut self, f: F) -> Result<(), Self::Error>
+    where F: FnMut(Registration);
+}
+
+/// [`Persistence`] that doesn't save anything
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NoPersistence;
+
+impl Persistence for NoPersistence {
+  type Error = core::convert::Infallible;
+
+  fn save<I>(&mut self, _: I) -> Result<(), Self::Error>
+    where I: Iterator<Item = Registration>
+  {
+    Ok(())
+  }
+
+  fn load<F>(&mut self, _: F) -> Result<(), Self::Error>
+    where F: FnMut(Registration)
+  {
+    Ok(())
+  }
+}
+
+/// Decides whether each subscriber should be sent a new notification,
+/// e.g. to implement conditional observation like `GET /temp?above=23`.
+///
+/// When the application responds to a request re-sent by [`Observe`] after
+/// [`notify`](super::Step::notify), the filter is consulted once for each subscriber
+/// with the request they registered with and the fresh representation.
+/// Subscribers for which [`should_notify`](NotificationFilter::should_notify)
+/// is `false` are skipped.
+///
+/// The first response to a register request is always sent.
+///
+/// [`NoFilter`] is used by default, and filter functions can be registered
+/// using [`FilterFn`] with [`Observe::filter_with`].
+pub trait NotificationFilter<P>
+  where P: PlatformTypes
+{
+  /// Should the subscriber who registered with `sub` be sent `rep`?
+  fn should_notify(&self, sub: &Addrd<Req<P>>, rep: &platform::Message<P>) -> bool;
+}
+
+/// [`NotificationFilter`] that notifies every subscriber
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NoFilter;
+
+impl<P> NotificationFilter<P> for NoFilter where P: PlatformTypes
+{
+  fn should_notify(&self, _: &Addrd<Req<P>>, _: &platform::Message<P>) -> bool {
+    true
+  }
+}
+
+/// A [`NotificationFilter`] function, notifying every subscriber when `None`
+///
+/// ```
+/// use toad::net::Addrd;
+/// use toad::platform::Message;
+/// use toad::req::Req;
+/// use toad::step::observe::FilterFn;
+/// use toad_msg::MessageOptions;
+///
+/// type P = toad::std::PlatformTypes<toad::std::dtls::N>;
+///
+/// /// Only notify subscribers to `temp?above=<n>` when the temperature exceeds `n`
+/// fn above(sub: &Addrd<Req<P>>, rep: &Message<P>) -> bool {
+///   let temp = |s: &str| s.parse::<f32>().ok();
+///   let threshold = sub.data()
+///                      .msg()
+///                      .query::<Vec<_>>()
+///                      .ok()
+///                      .and_then(|q| q.into_iter().find_map(|q| q.strip_prefix("above=")))
+///                      .and_then(temp);
+///
+///   let reading = core::str::from_utf8(&rep.payload.0).ok().and_then(temp);
+///
+///   match (threshold, reading) {
+///     | (Some(threshold), Some(temp)) => temp > threshold,
+///     | _ => true,
+///   }
+/// }
+///
+/// let filter: FilterFn<P> = Some(above);
+/// ```
+pub type FilterFn<P> = Option<fn(&Addrd<Req<P>>, &platform::Message<P>) -> bool>;
+
+impl<P> NotificationFilter<P> for FilterFn<P> where P: PlatformTypes
+{
+  fn should_notify(&self, sub: &Addrd<Req<P>>, rep: &platform::Message<P>) -> bool {
+    self.map(|f| f(sub, rep)).unwrap_or(true)
+  }
+}
+
+/// The sequence number of the freshest notification
+/// received for a subscription we registered as a client
+pub struct LastSeq<P>
+  where P: PlatformTypes
+{
+  sub: Addrd<Token>,
+  seq: u32,
+  received_at: Instant<P::Clock>,
+}
+
+impl<P> core::fmt::Debug for LastSeq<P> where P: PlatformTypes
+{
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("LastSeq")
+     .field("sub", &self.sub)
+     .field("seq", &self.seq)
+     .field("received_at", &self.received_at)
+     .finish()
+  }
+}
+
+impl<P> LastSeq<P> where P: PlatformTypes
+{
+  #[allow(missing_docs)]
+  pub fn new(sub: Addrd<Token>, seq: u32, received_at: Instant<P::Clock>) -> Self {
+    Self { sub,
+           seq,
+           received_at }
+  }
+
+  /// The address & token of the subscription
+  pub fn sub(&self) -> Addrd<Token> {
+    self.sub
+  }
+
+  /// The sequence number of the freshest notification
+  pub fn seq(&self) -> u32 {
+    self.seq
+  }
+
+  /// When the freshest notification was received
+  pub fn received_at(&self) -> Instant<P::Clock> {
+    self.received_at
+  }
+}
+
 /// See [the module documentation](self)
 #[derive(Debug)]
-pub struct Observe<S, Subs, RequestQueue, Hasher> {
+pub struct Observe<S, Subs, RequestQueue, Hasher, Seqs, Persist = NoPersistence, Filter = NoFilter>
+{
   inner: S,
   subs: Stem<Subs>,
   request_queue: Stem<RequestQueue>,
+  seqs: Stem<Seqs>,
+  persist: Stem<Persist>,
+  filter: Stem<Filter>,
   __hasher: PhantomData<Hasher>,
 }
 
-impl<I, S, RQ, H> Default for Observe<I, S, RQ, H>
+impl<I, S, RQ, H, SQ, PS, F> Default for Observe<I, S, RQ, H, SQ, PS, F>
   where I: Default,
         S: Default,
-        RQ: Default
+        RQ: Default,
+        SQ: Default,
+        PS: Default,
+        F: Default
 {
   fn default() -> Self {
     Observe { inner: I::default(),
               subs: Stem::new(S::default()),
               request_queue: Stem::new(RQ::default()),
+              seqs: Stem::new(SQ::default()),
+              persist: Stem::new(PS::default()),
+              filter: Stem::new(F::default()),
               __hasher: PhantomData }
   }
 }
 
-impl<S, Subs, RequestQueue, Hasher> Observe<S, Subs, RequestQueue, Hasher> {
+impl<S, Subs, RequestQueue, Hasher, Seqs, Persist, Filter>
+  Observe<S, Subs, RequestQueue, Hasher, Seqs, Persist, Filter>
+{
+  /// Decide which subscribers are sent each notification with `filter`
+  ///
+  /// See [`NotificationFilter`]
+  pub fn filter_with(&self, filter: Filter) {
+    let mut filter = Some(filter);
+    self.filter
+        .map_mut(|f| *f = Option::take(&mut filter).expect("closure only invoked once"));
+  }
+
+  /// If `msg` is a notification for a subscriber that the [`NotificationFilter`]
+  /// rejects, [discard](Effect::Discard) it.
+  ///
+  /// Yields whether `msg` was discarded.
+  fn filter_notification<P>(&self,
+                            effs: &mut P::Effects,
+                            msg: &Addrd<platform::Message<P>>)
+                            -> bool
+    where P: PlatformTypes,
+          Subs: Array<Item = Sub<P>>,
+          Filter: NotificationFilter<P>
+  {
+    let notify = self.subs.map_mut(|subs| {
+                            match subs.iter_mut()
+                                      .find(|s| {
+                                        s.addr() == msg.addr() && s.token() == msg.data().token
+                                      }) {
+                              | Some(sub) if sub.pending_notification => {
+                                sub.pending_notification = false;
+                                self.filter
+                                    .map_ref(|f| f.should_notify(sub.req(), msg.data()))
+                              },
+                              | _ => true,
+                            }
+                          });
+
+    if !notify {
+      log!(Observe::filter_notification,
+           effs,
+           log::Level::Trace,
+           "filtered: {:?} {:?}",
+           msg.addr(),
+           msg.data().token);
+      effs.push(Effect::Discard);
+    }
+
+    !notify
+  }
+
+  /// Save registrations to `persist` whenever they change,
+  /// restoring any registrations it already contains.
+  pub fn persist_with<P>(&self, persist: Persist) -> Result<(), Persist::Error>
+    where P: PlatformTypes,
+          Subs: Array<Item = Sub<P>>,
+          Persist: Persistence
+  {
+    let mut persist = Some(persist);
+    self.persist
+        .map_mut(|p| *p = Option::take(&mut persist).expect("closure only invoked once"));
+
+    self.persist.map_mut(|p| {
+                  p.load(|reg| {
+                     self.subs.map_mut(|subs| {
+                                if Self::get(subs, reg.addr, reg.token).is_none() {
+                                  subs.push(Sub::from_registration(&reg));
+                                }
+                              })
+                   })
+                })
+  }
+
+  /// Save all registrations, logging failures
+  fn save<P>(&self, effs: &mut P::Effects)
+    where P: PlatformTypes,
+          Subs: Array<Item = Sub<P>>,
+          Persist: Persistence
+  {
+    let res = self.subs.map_ref(|subs| {
+                         self.persist
+                             .map_mut(|p| p.save(subs.iter().map(Sub::registration)))
+                       });
+
+    if let Err(e) = res {
+      log!(Observe::save,
+           effs,
+           log::Level::Error,
+           "failed to save observe registrations: {:?}",
+           e);
+    }
+  }
+
+  /// Remember the sequence number of a notification sent to a subscriber.
+  ///
+  /// If it is not newer than the last one sent (e.g. the application's
+  /// counter was reset by a restart) it is replaced by the next sequence number,
+  /// so that the subscriber doesn't discard it as stale.
+  fn track_notification_seq<P>(&self,
+                               effs: &mut P::Effects,
+                               msg: &mut Addrd<platform::Message<P>>)
+    where P: PlatformTypes,
+          Subs: Array<Item = Sub<P>>,
+          Persist: Persistence
+  {
+    let seq = match notification_seq(msg.data()) {
+      | Some(seq) => seq,
+      | None => return,
+    };
+
+    let tracked = self.subs.map_mut(|subs| {
+                             match subs.iter_mut().find(|s| {
+                                                    s.addr() == msg.addr()
+                                                    && s.token() == msg.data().token
+                                                  }) {
+                               | Some(sub) => {
+                                 let seq = match sub.seq {
+                                   | Some(last) if !seq_is_newer(last, seq) => {
+                                     let next = (last + 1) % (1 << 24);
+                                     msg.as_mut()
+                                        .set(OBSERVE,
+                                             next.to_be_bytes()
+                                                 .into_iter()
+                                                 .skip_while(|b| *b == 0)
+                                                 .collect())
+                                        .ok();
+                                     next
+                                   },
+                                   | _ => seq,
+                                 };
+
+                                 sub.seq = Some(seq);
+                                 true
+                               },
+                               | None => false,
+                             }
+                           });
+
+    if tracked {
+      self.save::<P>(effs);
+    }
+  }
+
+  /// Remember the ETag of the representation sent to a subscriber,
+  /// so that the next notification can be a 2.03 VALID if it hasn't changed.
+  fn track_notification_etag<P>(&self, msg: &Addrd<platform::Message<P>>)
+    where P: PlatformTypes,
+          Subs: Array<Item = Sub<P>>
+  {
+    if ![code::CONTENT, code::VALID].contains(&msg.data().code) {
+      return;
+    }
+
+    let etag = msg.data()
+                  .get_first(ETAG)
+                  .filter(|tag| tag.as_bytes().len() <= 8)
+                  .map(|tag| tag.as_bytes().iter().copied().collect::<Etag>());
+
+    self.subs.map_mut(|subs| {
+               if let Some(sub) = subs.iter_mut().find(|s| {
+                                                   s.addr() == msg.addr()
+                                                   && s.token() == msg.data().token
+                                                 })
+               {
+                 sub.etag = etag;
+               }
+             });
+  }
+
+  /// Set the ETag of a request re-sent for a notification
+  /// to that of the representation its subscribers already hold,
+  /// so that the application can respond 2.03 VALID if it's still current
+  /// (see [`etag::auto`](crate::server::etag::auto)).
+  ///
+  /// Every subscriber similar to `req` is sent the response, so this is only
+  /// done when they all hold the same representation; when they don't,
+  /// ETags are removed from the request so that the full representation is sent.
+  /// Before any subscriber has been sent a representation with an ETag,
+  /// the ETags they registered with are kept.
+  fn resync_etag<P>(subs: &Subs, req: &mut Addrd<Req<P>>)
+    where P: PlatformTypes,
+          Subs: Array<Item = Sub<P>>,
+          Hasher: SubscriptionHash<P> + Default
+  {
+    let h = Self::hash_req(req);
+    let mut etags = subs.iter()
+                        .filter(|s| Self::hash_req(s.req()) == h)
+                        .map(|s| s.etag.as_ref());
+
+    let first = match etags.next() {
+      | Some(etag) => etag,
+      | None => return,
+    };
+
+    match (first, etags.all(|etag| etag == first)) {
+      | (None, true) => (),
+      | (Some(etag), true) => {
+        req.as_mut().msg_mut().remove(ETAG);
+        req.as_mut().msg_mut().add_etag(etag).ok();
+      },
+      | (_, false) => {
+        req.as_mut().msg_mut().remove(ETAG);
+      },
+    }
+  }
+
   fn hash<'a, P>(sub: &'a Sub<P>) -> (&'a Sub<P>, u64)
     where P: PlatformTypes,
           Hasher: SubscriptionHash<P> + Default
@@ -259,26 +714,6 @@ impl<S, Subs, RequestQueue, Hasher> Observe<S, Subs, RequestQueue, Hasher> {
         .map(|(ix, _)| ix)
   }
 
-  fn fmt_subs<'a, P>(&self) -> String<1000>
-    where Subs: Array<Item = Sub<P>>,
-          P: PlatformTypes
-  {
-    self.subs.map_ref(|subs| {
-               let mut msg = String::<1000>::from("[");
-               subs.iter().enumerate().for_each(|(n, s)| {
-                                        write!(&mut msg,
-                                               "\"{:?} {:?}\"",
-                                               s.req.addr(),
-                                               s.req.data().msg().token).ok();
-                                        if n < subs.len() - 1 {
-                                          write!(&mut msg, ",").ok();
-                                        }
-                                      });
-               write!(&mut msg, "]").ok();
-               msg
-             })
-  }
-
   fn similar_to<'a, P>(subs: &'a Subs,
                        addr: SocketAddr,
                        t: Token)
@@ -290,12 +725,29 @@ impl<S, Subs, RequestQueue, Hasher> Observe<S, Subs, RequestQueue, Hasher> {
     subs.iter()
         .filter(move |s| match Self::get(subs, addr, t).map(Self::hash) {
           | Some((sub, h)) => {
-            s.addr() != sub.addr() && s.token() != sub.token() && Self::hash(sub).1 == h
+            !(s.addr() == sub.addr() && s.token() == sub.token()) && Self::hash(s).1 == h
           },
           | None => false,
         })
   }
 
+  /// Subscribers similar to the one that was just notified
+  /// have been sent a copy of the notification (or filtered out)
+  fn clear_pending_notifications<P>(subs: &mut Subs, addr: SocketAddr, t: Token)
+    where Subs: Array<Item = Sub<P>>,
+          P: PlatformTypes,
+          Hasher: SubscriptionHash<P> + Default
+  {
+    let h = match Self::get(subs, addr, t).map(Self::hash) {
+      | Some((_, h)) => h,
+      | None => return,
+    };
+
+    subs.iter_mut()
+        .filter(|s| !(s.addr() == addr && s.token() == t) && Self::hash_req(s.req()) == h)
+        .for_each(|s| s.pending_notification = false);
+  }
+
   fn subs_matching_path<'a, 'b, P>(subs: &'a Subs,
                                    p: &'b str)
                                    -> impl 'a + Iterator<Item = &'a Sub<P>>
@@ -303,16 +755,20 @@ impl<S, Subs, RequestQueue, Hasher> Observe<S, Subs, RequestQueue, Hasher> {
           P: PlatformTypes,
           'b: 'a
   {
-    subs.iter().filter(move |s| {
-                 s.msg()
-                  .get(PATH)
-                  .map(|segs| {
-                    segs.iter()
-                        .map(|val| -> &[u8] { &val.0 })
-                        .eq(p.split("/").map(|s| s.as_bytes()))
-                  })
-                  .unwrap_or_else(|| p.is_empty())
-               })
+    subs.iter().filter(move |s| Self::matches_path(s, p))
+  }
+
+  fn matches_path<P>(sub: &Sub<P>, p: &str) -> bool
+    where P: PlatformTypes
+  {
+    sub.msg()
+       .get(PATH)
+       .map(|segs| {
+         segs.iter()
+             .map(|val| -> &[u8] { &val.0 })
+             .eq(p.split("/").map(|s| s.as_bytes()))
+       })
+       .unwrap_or_else(|| p.is_empty())
   }
 
   fn remove_queued_requests_matching_path<P>(rq: &mut RequestQueue, path: &str) -> ()
@@ -368,7 +824,8 @@ impl<S, Subs, RequestQueue, Hasher> Observe<S, Subs, RequestQueue, Hasher> {
                                    effs: &mut <P as PlatformTypes>::Effects)
                                    -> super::StepOutput<Addrd<Req<P>>, E>
     where P: PlatformTypes,
-          Subs: Array<Item = Sub<P>>
+          Subs: Array<Item = Sub<P>>,
+          Persist: Persistence
   {
     match req.data().msg().observe() {
       | Some(Register) => {
@@ -381,6 +838,7 @@ impl<S, Subs, RequestQueue, Hasher> Observe<S, Subs, RequestQueue, Hasher> {
         let mut sub = Some(Sub::new(req.clone()));
         self.subs
             .map_mut(move |s| s.push(Option::take(&mut sub).expect("closure only invoked once")));
+        self.save::<P>(effs);
       },
       | Some(Deregister) => {
         log!(Observe::handle_incoming_request,
@@ -395,7 +853,8 @@ impl<S, Subs, RequestQueue, Hasher> Observe<S, Subs, RequestQueue, Hasher> {
                 s.remove(ix);
               },
               | None => (),
-            })
+            });
+        self.save::<P>(effs);
       },
       | _ => {
         log!(Observe::handle_incoming_request,
@@ -410,12 +869,125 @@ impl<S, Subs, RequestQueue, Hasher> Observe<S, Subs, RequestQueue, Hasher> {
     Some(Ok(req))
   }
 
-  fn clone_and_enqueue_sub_requests<P>(subs: &Subs, rq: &mut RequestQueue, path: &str)
+  /// Drop notifications that are older than the freshest
+  /// notification we've seen for the same subscription.
+  fn handle_incoming_notification<P, E>(&self,
+                                        resp: Addrd<Resp<P>>,
+                                        snap: &platform::Snapshot<P>,
+                                        effs: &mut <P as PlatformTypes>::Effects)
+                                        -> super::StepOutput<Addrd<Resp<P>>, E>
+    where P: PlatformTypes,
+          Seqs: Array<Item = LastSeq<P>>
+  {
+    let seq = match notification_seq(resp.data().msg()) {
+      | Some(seq) => seq,
+      | None => return Some(Ok(resp)),
+    };
+
+    let sub = Addrd(resp.data().token(), resp.addr());
+    let now = snap.time;
+
+    self.seqs.map_mut(|seqs| {
+               // Sequence numbers received more than 128 seconds ago
+               // have no bearing on freshness; forget them.
+               while let Some(ix) =
+                 seqs.iter().position(|last| {
+                               crate::time::elapsed(last.received_at, now)
+                                 .map(|elapsed| elapsed > FRESHNESS_WINDOW)
+                                 .unwrap_or(false)
+                             })
+               {
+                 seqs.remove(ix);
+               }
+
+               match seqs.iter().position(|last| last.sub == sub) {
+                 | Some(ix) if !is_fresh((seqs[ix].seq, seqs[ix].received_at), (seq, now)) => {
+                   log!(Observe::handle_incoming_notification,
+                        effs,
+                        log::Level::Trace,
+                        "dropping stale notification {} (last seen {}): {:?}",
+                        seq,
+                        seqs[ix].seq,
+                        sub);
+                   Some(Err(nb::Error::WouldBlock))
+                 },
+                 | Some(ix) => {
+                   seqs[ix] = LastSeq::new(sub, seq, now);
+                   Some(Ok(resp))
+                 },
+                 | None => {
+                   seqs.push(LastSeq::new(sub, seq, now));
+                   Some(Ok(resp))
+                 },
+               }
+             })
+  }
+
+  fn forget_seq<P>(&self, sub: Addrd<Token>)
+    where P: PlatformTypes,
+          Seqs: Array<Item = LastSeq<P>>
+  {
+    self.seqs
+        .map_mut(|seqs| match seqs.iter().position(|last| last.sub == sub) {
+          | Some(ix) => {
+            seqs.remove(ix);
+          },
+          | None => (),
+        })
+  }
+
+  /// When the session with a peer closes (e.g. a DTLS session dies),
+  /// forget all of its subscriptions, queued notifications
+  /// and notification sequence numbers.
+  fn forget_closed_session<P>(&self,
+                              snap: &platform::Snapshot<P>,
+                              effs: &mut <P as PlatformTypes>::Effects)
+    where P: PlatformTypes,
+          Subs: Array<Item = Sub<P>>,
+          RequestQueue: Array<Item = Addrd<Req<P>>>,
+          Seqs: Array<Item = LastSeq<P>>,
+          Persist: Persistence
+  {
+    let addr = match snap.session {
+      | Some(Addrd(session, addr)) if session.is_closed() => addr,
+      | _ => return,
+    };
+
+    log!(Observe::forget_closed_session,
+         effs,
+         log::Level::Debug,
+         "session with {} closed, dropping its subscriptions",
+         addr);
+
+    self.subs.map_mut(|subs| {
+               while let Some(ix) = subs.iter().position(|s| s.addr() == addr) {
+                 subs.remove(ix);
+               }
+             });
+    self.request_queue.map_mut(|rq| {
+                        while let Some(ix) = rq.iter().position(|r| r.addr() == addr) {
+                          rq.remove(ix);
+                        }
+                      });
+    self.seqs.map_mut(|seqs| {
+               while let Some(ix) = seqs.iter().position(|last| last.sub.addr() == addr) {
+                 seqs.remove(ix);
+               }
+             });
+    self.save::<P>(effs);
+  }
+
+  fn clone_and_enqueue_sub_requests<P>(subs: &mut Subs, rq: &mut RequestQueue, path: &str)
     where P: PlatformTypes,
           Subs: Array<Item = Sub<P>>,
           RequestQueue: Array<Item = Addrd<Req<P>>>,
           Hasher: SubscriptionHash<P> + Default
   {
+    subs.iter_mut()
+        .filter(|s| Self::matches_path(s, path))
+        .for_each(|s| s.pending_notification = true);
+
+    let subs: &Subs = subs;
     Self::subs_matching_path(subs, path).for_each(|sub| {
                                           // TODO: handle option capacity
                                           let mut req = sub.req().clone();
@@ -423,6 +995,7 @@ impl<S, Subs, RequestQueue, Hasher> Observe<S, Subs, RequestQueue, Hasher> {
                                              .msg_mut()
                                              .set(opt::WAS_CREATED_BY_OBSERVE, Default::default())
                                              .ok();
+                                          Self::resync_etag(subs, &mut req);
 
                                           if rq.iter().all(|req2| {
                                                         Self::hash_req(&req) != Self::hash_req(req2)
@@ -434,12 +1007,15 @@ impl<S, Subs, RequestQueue, Hasher> Observe<S, Subs, RequestQueue, Hasher> {
   }
 }
 
-impl<P, S, B, RQ, H> Step<P> for Observe<S, B, RQ, H>
+impl<P, S, B, RQ, H, SQ, PS, F> Step<P> for Observe<S, B, RQ, H, SQ, PS, F>
   where P: PlatformTypes,
         S: Step<P, PollReq = Addrd<Req<P>>, PollResp = Addrd<Resp<P>>>,
         B: Default + Array<Item = Sub<P>>,
         RQ: Default + Array<Item = Addrd<Req<P>>>,
-        H: SubscriptionHash<P> + Default
+        H: SubscriptionHash<P> + Default,
+        SQ: Default + Array<Item = LastSeq<P>>,
+        PS: Default + Persistence,
+        F: Default + NotificationFilter<P>
 {
   type PollReq = Addrd<Req<P>>;
   type PollResp = Addrd<Resp<P>>;
@@ -451,6 +1027,46 @@ impl<P, S, B, RQ, H> Step<P> for Observe<S, B, RQ, H>
     &self.inner
   }
 
+  fn snapshot_state<W>(&self, w: &mut W) -> core::fmt::Result
+    where W: core::fmt::Write
+  {
+    self.subs.map_ref(|subs| {
+               writeln!(w, "Observe: {} subscription(s)", subs.len())?;
+               subs.iter().try_for_each(|sub| {
+                            write!(w, "  {} {:?} /", sub.addr(), sub.msg().token)?;
+                            sub.msg()
+                               .get(PATH)
+                               .map(|segs| segs.iter())
+                               .into_iter()
+                               .flatten()
+                               .try_for_each(|seg| {
+                                 write!(w,
+                                        "{}/",
+                                        core::str::from_utf8(seg.as_bytes()).unwrap_or("<invalid utf8>"))
+                               })?;
+                            writeln!(w)
+                          })
+             })?;
+
+    self.request_queue.map_ref(|reqs| {
+                        writeln!(w, "Observe: {} request(s) queued for notification", reqs.len())
+                      })?;
+
+    self.seqs.map_ref(|seqs| {
+               writeln!(w, "Observe: {} notification sequence number(s)", seqs.len())?;
+               seqs.iter().try_for_each(|seq| {
+                            writeln!(w,
+                                     "  {} {:?} seq {} (received at {:?})",
+                                     seq.sub.addr(),
+                                     seq.sub.data(),
+                                     seq.seq,
+                                     seq.received_at)
+                          })
+             })?;
+
+    self.inner.snapshot_state(w)
+  }
+
   fn poll_req(&self,
               snap: &platform::Snapshot<P>,
               effects: &mut <P as PlatformTypes>::Effects)
@@ -458,6 +1074,8 @@ impl<P, S, B, RQ, H> Step<P> for Observe<S, B, RQ, H>
     // TODO(orion): if throughput so high that there is always a request on the wire,
     // we will never fully flush the queue.
     // maybe add a timestamp or TTL check so that we can prioritize old outbound subscription updates
+    self.forget_closed_session(snap, effects);
+
     match self.inner.poll_req(snap, effects) {
       | Some(Ok(req)) => self.handle_incoming_request(req, snap, effects),
       | None | Some(Err(nb::Error::WouldBlock)) => self.get_queued_request::<P>().map(Ok),
@@ -471,7 +1089,12 @@ impl<P, S, B, RQ, H> Step<P> for Observe<S, B, RQ, H>
                token: ::toad_msg::Token,
                addr: no_std_net::SocketAddr)
                -> super::StepOutput<Self::PollResp, Self::Error> {
-    self.inner.poll_resp(snap, effects, token, addr)
+    self.forget_closed_session(snap, effects);
+
+    match self.inner.poll_resp(snap, effects, token, addr) {
+      | Some(Ok(resp)) => self.handle_incoming_notification(resp, snap, effects),
+      | other => other,
+    }
   }
 
   fn notify<Path>(&self,
@@ -495,7 +1118,7 @@ impl<P, S, B, RQ, H> Step<P> for Observe<S, B, RQ, H>
                              rq.len());
 
                         Self::remove_queued_requests_matching_path(rq, path.as_ref());
-                        self.subs.map_ref(|subs| {
+                        self.subs.map_mut(|subs| {
                                    Self::clone_and_enqueue_sub_requests(subs, rq, path.as_ref())
                                  });
 
@@ -516,14 +1139,33 @@ impl<P, S, B, RQ, H> Step<P> for Observe<S, B, RQ, H>
                          -> Result<(), Self::Error> {
     self.inner().before_message_sent(snap, effs, msg)?;
 
+    if msg.data().code.kind() == CodeKind::Request && msg.data().observe() == Some(Deregister) {
+      self.forget_seq::<P>(Addrd(msg.data().token, msg.addr()));
+    }
+
+    let discarded =
+      msg.data().code.kind() == CodeKind::Response && self.filter_notification(effs, msg);
+
+    if msg.data().code.kind() == CodeKind::Response && !discarded {
+      self.track_notification_seq(effs, msg);
+      self.track_notification_etag(msg);
+    }
+
     if let Some(_) = msg.data().get(opt::WAS_CREATED_BY_OBSERVE) {
       msg.as_mut().remove(opt::WAS_CREATED_BY_OBSERVE);
     } else if msg.data().code.kind() == CodeKind::Response
               && self.subs
                      .map_ref(|subs| Self::get(subs, msg.addr(), msg.data().token).is_some())
     {
+      let should_notify = |sub: &Sub<P>| {
+        !sub.pending_notification
+        || self.filter
+               .map_ref(|f| f.should_notify(sub.req(), msg.data()))
+      };
+
       self.subs.map_ref(|subs| {
-                 Self::similar_to(subs, msg.addr(), msg.data().token).for_each(|sub| {
+                 let similar = Self::similar_to(subs, msg.addr(), msg.data().token);
+                 similar.filter(|sub| should_notify(sub)).for_each(|sub| {
                    let mut msg = msg.clone();
                    msg.as_mut()
                       .set(opt::WAS_CREATED_BY_OBSERVE, Default::default())
@@ -538,6 +1180,9 @@ impl<P, S, B, RQ, H> Step<P> for Observe<S, B, RQ, H>
                    effs.push(Effect::Send(msg.with_addr(sub.addr())));
                  })
                });
+
+      self.subs
+          .map_mut(|subs| Self::clear_pending_notifications(subs, msg.addr(), msg.data().token));
     } else {
       log!(Observe::before_message_sent,
            effs,
@@ -545,11 +1190,14 @@ impl<P, S, B, RQ, H> Step<P> for Observe<S, B, RQ, H>
            "ignoring {:?} {:?}",
            msg.addr(),
            msg.data().token);
-      log!(Observe::before_message_sent,
-           effs,
-           log::Level::Trace,
-           "subscriptions: {}",
-           self.fmt_subs().as_str());
+      self.subs.map_ref(|subs| {
+                 let subs = list(subs.iter().map(|s| (s.req.addr(), &s.req.data().msg().token)));
+                 log!(Observe::before_message_sent,
+                      effs,
+                      log::Level::Trace,
+                      "subscriptions: {}",
+                      subs);
+               });
     }
 
     Ok(())
@@ -579,7 +1227,8 @@ mod tests {
   type Observe<S> = super::Observe<S,
                                    Vec<Sub>,
                                    Vec<Addrd<Req<test::Platform>>>,
-                                   SubHash_TypePathQueryAccept<test::Platform>>;
+                                   SubHash_TypePathQueryAccept<test::Platform>,
+                                   Vec<LastSeq<test::Platform>>>;
   type PollReq = Addrd<Req<test::Platform>>;
   type PollResp = Addrd<Resp<test::Platform>>;
 
@@ -648,6 +1297,9 @@ mod tests {
           // this should add it to subscribtions list
           step.poll_req(&Snapshot { time: ClockMock::new().try_now().unwrap(),
                          recvd_dgram: None,
+                         recvd_identity: None,
+                         recvd_dest: None,
+                         session: None,
                          config: Default::default() }, &mut Default::default()).unwrap().unwrap()
         }}),
         // We have a new version available
@@ -670,10 +1322,16 @@ mod tests {
         (inner.poll_req = { poll_req_emitting_single_register_request(21) }),
         ({|step: &Observe<Dummy>| step.poll_req(&Snapshot { time: ClockMock::new().try_now().unwrap(),
                          recvd_dgram: None,
+                         recvd_identity: None,
+                         recvd_dest: None,
+                         session: None,
                          config: Default::default() }, &mut Default::default()).unwrap().unwrap()}),
         (inner.poll_req = { poll_req_emitting_single_register_request(22) }),
         ({|step: &Observe<Dummy>| step.poll_req(&Snapshot { time: ClockMock::new().try_now().unwrap(),
                          recvd_dgram: None,
+                         recvd_identity: None,
+                         recvd_dest: None,
+                         session: None,
                          config: Default::default() }, &mut Default::default()).unwrap().unwrap()})
       ]
       THEN response_is_copied_and_sent_to_subscriber [
@@ -699,6 +1357,9 @@ mod tests {
         ({|step: &Observe<Dummy>| {
           step.poll_req(&Snapshot { time: test::ClockMock::new().try_now().unwrap(),
                          recvd_dgram: None,
+                         recvd_identity: None,
+                         recvd_dest: None,
+                         session: None,
                          config: crate::config::Config::default() }, &mut Default::default()).unwrap().unwrap()
         }}),
         ({|step: &Observe<Dummy>| step.notify("foot/bart", &mut vec![]).unwrap()})
@@ -715,12 +1376,18 @@ mod tests {
         ({|step: &Observe<Dummy>| {
           step.poll_req(&Snapshot { time: test::ClockMock::new().try_now().unwrap(),
                          recvd_dgram: None,
+                         recvd_identity: None,
+                         recvd_dest: None,
+                         session: None,
                          config: crate::config::Config::default() }, &mut Default::default()).unwrap().unwrap()
         }}),
         ({|step: &Observe<Dummy>| step.notify("foo/bar", &mut vec![]).unwrap()}),
         ({|step: &Observe<Dummy>| {
           step.poll_req(&Snapshot { time: test::ClockMock::new().try_now().unwrap(),
                          recvd_dgram: None,
+                         recvd_identity: None,
+                         recvd_dest: None,
+                         session: None,
                          config: crate::config::Config::default() }, &mut Default::default()).unwrap().unwrap()
         }}),
         ({|step: &Observe<Dummy>| step.notify("foo/bar", &mut vec![]).unwrap()})
@@ -734,6 +1401,46 @@ mod tests {
       ]
   );
 
+  fn notification(seq: u32) -> Addrd<Resp<test::Platform>> {
+    let mut msg = Message::new(Type::Non, Code::new(2, 5), Id(1), Token(array_vec!(1)));
+    msg.set(OBSERVE, ::toad_msg::OptValue(seq.to_be_bytes()[1..].to_vec()))
+       .ok();
+    Addrd(Resp::from(msg), test::x.x.x.x(80))
+  }
+
+  fn snapshot_at(micros: u64) -> Snapshot {
+    Snapshot { time: ClockMock::instant(micros),
+               recvd_dgram: None,
+               recvd_identity: None,
+               recvd_dest: None,
+               session: None,
+               config: Default::default() }
+  }
+
+  test_step!(
+      GIVEN Observe::<Dummy> where Dummy: {Step<PollReq = PollReq, PollResp = PollResp, Error = ()>};
+      WHEN notifications_arrive_out_of_order [
+        (inner.poll_resp => { Some(Ok(notification(5))) }),
+        ({|step: &Observe<Dummy>| step.poll_resp(&snapshot_at(0), &mut vec![], Token(array_vec!(1)), test::x.x.x.x(80)).unwrap().unwrap()}),
+        (inner.poll_resp => { Some(Ok(notification(4))) })
+      ]
+      THEN stale_notification_is_dropped [
+        (poll_resp(_, _, _, _) should satisfy { |out| assert_eq!(out, Some(Err(nb::Error::WouldBlock))) })
+      ]
+  );
+
+  test_step!(
+      GIVEN Observe::<Dummy> where Dummy: {Step<PollReq = PollReq, PollResp = PollResp, Error = ()>};
+      WHEN notifications_arrive_in_order [
+        (inner.poll_resp => { Some(Ok(notification(5))) }),
+        ({|step: &Observe<Dummy>| step.poll_resp(&snapshot_at(0), &mut vec![], Token(array_vec!(1)), test::x.x.x.x(80)).unwrap().unwrap()}),
+        (inner.poll_resp => { Some(Ok(notification(6))) })
+      ]
+      THEN fresh_notification_is_yielded [
+        (poll_resp(_, _, _, _) should satisfy { |out| assert_eq!(out, Some(Ok(notification(6)))) })
+      ]
+  );
+
   #[test]
   pub fn sub_hash() {
     fn req<F>(stuff: F) -> u64
@@ -795,4 +1502,275 @@ mod tests {
                  r.set_accept(ContentFormat::Json).ok();
                }));
   }
+
+  #[derive(Default, Clone)]
+  struct MemPersistence(std::sync::Arc<Mutex<Vec<Registration>>>);
+
+  impl Persistence for MemPersistence {
+    type Error = ();
+
+    fn save<I>(&mut self, regs: I) -> Result<(), ()>
+      where I: Iterator<Item = Registration>
+    {
+      *self.0.lock().unwrap() = regs.collect();
+      Ok(())
+    }
+
+    fn load<F>(&mut self, f: F) -> Result<(), ()>
+      where F: FnMut(Registration)
+    {
+      self.0.lock().unwrap().clone().into_iter().for_each(f);
+      Ok(())
+    }
+  }
+
+  type PersistentObserve = super::Observe<test::MockStep<(), PollReq, PollResp, ()>,
+                                          Vec<Sub>,
+                                          Vec<Addrd<Req<test::Platform>>>,
+                                          SubHash_TypePathQueryAccept<test::Platform>,
+                                          Vec<LastSeq<test::Platform>>,
+                                          MemPersistence>;
+
+  fn saved_registration(seq: Option<u32>) -> Registration {
+    Registration { addr: test::x.x.x.x(80),
+                   token: Token(array_vec!(1)),
+                   path: RegistrationPath::from("foo/bar"),
+                   seq }
+  }
+
+  #[test]
+  fn registrations_are_saved() {
+    let step = PersistentObserve::default();
+    let mem = MemPersistence::default();
+    step.persist_with::<test::Platform>(mem.clone()).unwrap();
+
+    step.inner()
+        .set_poll_req(|_, _, _| {
+          let mut msg = test::msg!(CON GET x.x.x.x:80).unwrap();
+          msg.token = Token(array_vec!(1));
+          msg.set_path("foo/bar").ok();
+          msg.set_observe(Register).ok();
+          Some(Ok(Addrd(Req::from(msg), test::x.x.x.x(80))))
+        });
+    step.poll_req(&snapshot_at(0), &mut vec![]).unwrap().unwrap();
+
+    assert_eq!(*mem.0.lock().unwrap(), vec![saved_registration(None)]);
+  }
+
+  #[test]
+  fn saved_registrations_are_notified_after_restart() {
+    let mem = MemPersistence::default();
+    *mem.0.lock().unwrap() = vec![saved_registration(Some(10))];
+
+    let step = PersistentObserve::default();
+    step.persist_with::<test::Platform>(mem.clone()).unwrap();
+    step.inner().set_poll_req(|_, _, _| None);
+
+    step.notify("foo/bar", &mut vec![]).unwrap();
+    let req = step.poll_req(&snapshot_at(0), &mut vec![]).unwrap().unwrap();
+    assert_eq!(req.addr(), test::x.x.x.x(80));
+    assert_eq!(req.data().msg().token, Token(array_vec!(1)));
+
+    // the application's sequence numbers started over after the restart
+    let mut resp = notification(0).map(|r| r.msg().clone());
+    step.before_message_sent(&snapshot_at(0), &mut vec![], &mut resp)
+        .unwrap();
+
+    assert_eq!(notification_seq(resp.data()), Some(11));
+    assert_eq!(*mem.0.lock().unwrap(), vec![saved_registration(Some(11))]);
+  }
+
+  type FilteredObserve = super::Observe<test::MockStep<(), PollReq, PollResp, ()>,
+                                        Vec<Sub>,
+                                        Vec<Addrd<Req<test::Platform>>>,
+                                        SubHash_TypePathQueryAccept<test::Platform>,
+                                        Vec<LastSeq<test::Platform>>,
+                                        NoPersistence,
+                                        FilterFn<test::Platform>>;
+
+  fn filtered_observe(filter: fn(&Addrd<Req<test::Platform>>, &Message) -> bool)
+                      -> FilteredObserve {
+    let step = FilteredObserve::default();
+    step.filter_with(Some(filter));
+
+    (1..=3u8).for_each(|n| {
+               step.inner().set_poll_req(move |_, _, _| {
+                             let mut msg = test::msg!(CON GET x.x.x.x:80).unwrap();
+                             msg.token = Token(array_vec!(n));
+                             msg.set_path("foo/bar").ok();
+                             msg.set_observe(Register).ok();
+                             Some(Ok(Addrd(Req::from(msg), test::x.x.x.x(n as u16))))
+                           });
+               step.poll_req(&snapshot_at(0), &mut vec![]).unwrap().unwrap();
+             });
+    step.inner().set_poll_req(|_, _, _| None);
+
+    step
+  }
+
+  fn response_to(n: u8) -> Addrd<Message> {
+    Addrd(Message::new(Type::Con, Code::new(2, 5), Id(1), Token(array_vec!(n))),
+          test::x.x.x.x(n as u16))
+  }
+
+  fn notified(effs: &[Effect<test::Platform>]) -> Vec<SocketAddr> {
+    effs.iter()
+        .filter_map(|e| match e {
+          | Effect::Send(m) => Some(m.addr()),
+          | _ => None,
+        })
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect()
+  }
+
+  #[test]
+  fn filtered_subscribers_are_not_notified() {
+    let step = filtered_observe(|sub, _| sub.addr() != test::x.x.x.x(2));
+
+    step.notify("foo/bar", &mut vec![]).unwrap();
+    let req = step.poll_req(&snapshot_at(0), &mut vec![]).unwrap().unwrap();
+    assert_eq!(req.addr(), test::x.x.x.x(1));
+
+    let mut effs = vec![];
+    let mut resp = response_to(1);
+    step.before_message_sent(&snapshot_at(0), &mut effs, &mut resp)
+        .unwrap();
+
+    assert!(!effs.contains(&Effect::Discard));
+    assert_eq!(notified(&effs), vec![test::x.x.x.x(3)]);
+  }
+
+  #[test]
+  fn filtered_subscriber_whose_request_was_notified_is_discarded() {
+    let step = filtered_observe(|sub, _| sub.addr() != test::x.x.x.x(1));
+
+    step.notify("foo/bar", &mut vec![]).unwrap();
+    step.poll_req(&snapshot_at(0), &mut vec![]).unwrap().unwrap();
+
+    let mut effs = vec![];
+    let mut resp = response_to(1);
+    step.before_message_sent(&snapshot_at(0), &mut effs, &mut resp)
+        .unwrap();
+
+    assert!(effs.contains(&Effect::Discard));
+    assert_eq!(notified(&effs), vec![test::x.x.x.x(2), test::x.x.x.x(3)]);
+  }
+
+  /// Send `resp` and the copies fanned out to similar subscribers
+  fn send_with_copies(step: &FilteredObserve, resp: Addrd<Message>) {
+    let mut effs = vec![];
+    let mut resp = resp;
+    step.before_message_sent(&snapshot_at(0), &mut effs, &mut resp)
+        .unwrap();
+
+    effs.into_iter().for_each(|e| match e {
+                      | Effect::Send(mut m) => {
+                        step.before_message_sent(&snapshot_at(0), &mut vec![], &mut m)
+                            .unwrap()
+                      },
+                      | _ => (),
+                    });
+  }
+
+  fn with_etag(mut resp: Addrd<Message>, etag: &[u8]) -> Addrd<Message> {
+    resp.as_mut().add_etag(etag).unwrap();
+    resp
+  }
+
+  #[test]
+  fn notification_requests_carry_etag_subscribers_hold() {
+    let step = filtered_observe(|_, _| true);
+
+    send_with_copies(&step, with_etag(response_to(1), &[1, 2]));
+
+    step.notify("foo/bar", &mut vec![]).unwrap();
+    let req = step.poll_req(&snapshot_at(0), &mut vec![]).unwrap().unwrap();
+
+    assert_eq!(req.data().msg().etags().map(|tags| tags.len()), Some(1));
+    assert_eq!(req.data().msg().get_first(ETAG).unwrap().as_bytes(), &[1, 2]);
+  }
+
+  #[test]
+  fn valid_notification_is_fanned_out() {
+    let step = filtered_observe(|_, _| true);
+    send_with_copies(&step, with_etag(response_to(1), &[1, 2]));
+
+    step.notify("foo/bar", &mut vec![]).unwrap();
+    step.poll_req(&snapshot_at(0), &mut vec![]).unwrap().unwrap();
+
+    let mut valid = with_etag(response_to(1), &[1, 2]);
+    valid.as_mut().code = code::VALID;
+
+    let mut effs = vec![];
+    step.before_message_sent(&snapshot_at(0), &mut effs, &mut valid)
+        .unwrap();
+    assert_eq!(notified(&effs), vec![test::x.x.x.x(2), test::x.x.x.x(3)]);
+
+    step.subs.map_ref(|subs| {
+               subs.iter()
+                   .for_each(|s| assert_eq!(s.etag(), Some(&[1u8, 2][..])))
+             });
+  }
+
+  #[test]
+  fn notification_requests_drop_etag_when_subscribers_differ() {
+    let step = filtered_observe(|_, _| true);
+
+    send_with_copies(&step, with_etag(response_to(1), &[1, 2]));
+    let mut resp = with_etag(response_to(2), &[3, 4]);
+    step.before_message_sent(&snapshot_at(0), &mut vec![], &mut resp)
+        .unwrap();
+
+    step.notify("foo/bar", &mut vec![]).unwrap();
+    let req = step.poll_req(&snapshot_at(0), &mut vec![]).unwrap().unwrap();
+
+    assert!(req.data().msg().get(ETAG).is_none());
+  }
+
+  #[test]
+  fn notifying_one_subscription_clears_similar_ones_from_the_same_peer() {
+    let step = FilteredObserve::default();
+    step.filter_with(Some(|_, _| true));
+
+    (1..=2u8).for_each(|n| {
+               step.inner().set_poll_req(move |_, _, _| {
+                             let mut msg = test::msg!(CON GET x.x.x.x:80).unwrap();
+                             msg.token = Token(array_vec!(n));
+                             msg.set_path("foo/bar").ok();
+                             msg.set_observe(Register).ok();
+                             Some(Ok(Addrd(Req::from(msg), test::x.x.x.x(1))))
+                           });
+               step.poll_req(&snapshot_at(0), &mut vec![]).unwrap().unwrap();
+             });
+    step.inner().set_poll_req(|_, _, _| None);
+
+    step.notify("foo/bar", &mut vec![]).unwrap();
+    let req = step.poll_req(&snapshot_at(0), &mut vec![]).unwrap().unwrap();
+
+    let mut effs = vec![];
+    let mut resp = Addrd(Message::new(Type::Con, Code::new(2, 5), Id(1), req.data().msg().token),
+                         req.addr());
+    step.before_message_sent(&snapshot_at(0), &mut effs, &mut resp)
+        .unwrap();
+
+    assert_eq!(notified(&effs), vec![test::x.x.x.x(1)]);
+    step.subs.map_ref(|subs| {
+               assert_eq!(subs.len(), 2);
+               assert!(subs.iter().all(|s| !s.pending_notification));
+             });
+    assert!(step.poll_req(&snapshot_at(0), &mut vec![]).is_none());
+  }
+
+  #[test]
+  fn first_response_to_register_is_never_filtered() {
+    let step = filtered_observe(|_, _| false);
+
+    let mut effs = vec![];
+    let mut resp = response_to(1);
+    step.before_message_sent(&snapshot_at(0), &mut effs, &mut resp)
+        .unwrap();
+
+    assert!(!effs.contains(&Effect::Discard));
+  }
 }
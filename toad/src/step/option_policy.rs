@@ -0,0 +1,337 @@
+use toad_map::Map;
+use toad_msg::{Code, CodeKind, MessageOptions, OptNumber, OptionMustBeProcessed, Payload, Token,
+               Type};
+
+use super::{exec_inner_step, log, opt, Step, StepOutput};
+use crate::config::Config;
+use crate::net::Addrd;
+use crate::platform::{Effect, Message, PlatformTypes, Snapshot};
+use crate::req::Req;
+use crate::resp::{code, Resp};
+
+/// Reject messages containing critical options that
+/// the application does not understand
+///
+/// See the [module documentation](crate::step::option_policy) for more
+#[derive(Debug, Clone, Copy)]
+pub struct OptionPolicy<S>(S);
+
+impl<S: Default> Default for OptionPolicy<S> {
+  fn default() -> Self {
+    OptionPolicy(Default::default())
+  }
+}
+
+impl<S> OptionPolicy<S> {
+  /// Create a new OptionPolicy step
+  pub fn new(s: S) -> Self {
+    Self(s)
+  }
+}
+
+/// Find the first option in `msg` that must be processed
+/// and is not in [`understood_options`](crate::config::Msg.understood_options)
+fn unrecognized_critical_option<P>(config: &Config, msg: &Message<P>) -> Option<OptNumber>
+  where P: PlatformTypes
+{
+  msg.opts
+     .iter()
+     .map(|(n, _)| *n)
+     .find(|n| {
+       n.must_be_processed() == OptionMustBeProcessed::Yes
+       && !config.msg.understood_options.contains(n)
+     })
+}
+
+/// Remove the [`MALFORMED`](opt::MALFORMED) option from `msg`,
+/// yielding the first malformed option that must be processed
+fn malformed_critical_option<P>(msg: &mut Message<P>) -> Option<OptNumber>
+  where P: PlatformTypes
+{
+  msg.remove(opt::MALFORMED)?
+     .iter()
+     .filter_map(|v| <[u8; 4]>::try_from(v.as_bytes()).ok())
+     .map(|n| OptNumber(u32::from_be_bytes(n)))
+     .find(|n| n.must_be_processed() == OptionMustBeProcessed::Yes)
+}
+
+/// Find the first critical option in `msg` that is
+/// [malformed](malformed_critical_option) or [unrecognized](unrecognized_critical_option)
+fn rejected_option<P>(config: &Config, msg: &mut Message<P>) -> Option<(OptNumber, &'static str)>
+  where P: PlatformTypes
+{
+  malformed_critical_option(msg).map(|n| (n, "malformed"))
+                                .or_else(|| {
+                                  unrecognized_critical_option(config, msg).map(|n| (n, "unrecognized"))
+                                })
+}
+
+type InnerPollReq<P> = Addrd<Req<P>>;
+type InnerPollResp<P> = Addrd<Resp<P>>;
+
+impl<Inner: Step<P, PollReq = InnerPollReq<P>, PollResp = InnerPollResp<P>>, P: PlatformTypes>
+  Step<P> for OptionPolicy<Inner>
+{
+  type PollReq = Addrd<Req<P>>;
+  type PollResp = Addrd<Resp<P>>;
+  type Error = Inner::Error;
+  type Inner = Inner;
+
+  fn inner(&self) -> &Inner {
+    &self.0
+  }
+
+  fn poll_req(&self,
+              snap: &Snapshot<P>,
+              effects: &mut <P as PlatformTypes>::Effects)
+              -> StepOutput<Self::PollReq, Inner::Error> {
+    let mut req = match exec_inner_step!(self.0.poll_req(snap, effects), core::convert::identity) {
+      | Some(req) => req,
+      | None => return None,
+    };
+
+    match rejected_option(&snap.config, req.data_mut().msg_mut()) {
+      | Some((num, why)) if req.data().msg().code.kind() == CodeKind::Request => {
+        log!(OptionPolicy::poll_req,
+             effects,
+             log::Level::Warn,
+             "rejecting {:?} {:?}: {} critical option {:?}",
+             req.addr(),
+             req.data().msg().token,
+             why,
+             num);
+
+        if let Some(mut resp) = Resp::for_request(req.data()) {
+          resp.set_code(code::BAD_OPTION);
+          effects.push(Effect::Send(Addrd(resp.into(), req.addr())));
+        }
+
+        Some(Err(nb::Error::WouldBlock))
+      },
+      | _ => Some(Ok(req)),
+    }
+  }
+
+  fn poll_resp(&self,
+               snap: &Snapshot<P>,
+               effects: &mut <P as PlatformTypes>::Effects,
+               token: Token,
+               addr: no_std_net::SocketAddr)
+               -> StepOutput<Self::PollResp, Inner::Error> {
+    let mut resp = match exec_inner_step!(self.0.poll_resp(snap, effects, token, addr),
+                                          core::convert::identity)
+    {
+      | Some(resp) => resp,
+      | None => return None,
+    };
+
+    match rejected_option(&snap.config, resp.data_mut().msg_mut()) {
+      | Some((num, why)) => {
+        log!(OptionPolicy::poll_resp,
+             effects,
+             log::Level::Warn,
+             "rejecting response {:?} {:?}: {} critical option {:?}",
+             resp.addr(),
+             resp.data().msg().token,
+             why,
+             num);
+
+        // Rejecting an ACK means silently ignoring it,
+        // rejecting a CON or NON is done by sending a matching RST.
+        //
+        // <https://www.rfc-editor.org/rfc/rfc7252#section-4.2>
+        // <https://www.rfc-editor.org/rfc/rfc7252#section-4.3>
+        match resp.data().msg().ty {
+          | Type::Con | Type::Non => {
+            let rst = Message::<P> { ty: Type::Reset,
+                                     id: resp.data().msg().id,
+                                     ver: Default::default(),
+                                     token: Token(Default::default()),
+                                     code: Code::EMPTY,
+                                     opts: Default::default(),
+                                     payload: Payload(Default::default()) };
+            effects.push(Effect::Send(Addrd(rst, resp.addr())));
+          },
+          | _ => (),
+        }
+
+        Some(Err(nb::Error::WouldBlock))
+      },
+      | None => Some(Ok(resp)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use toad_msg::{Code, Id, MessageOptions, OptNumber, OptValue, Payload, Token, Type};
+
+  use super::super::test;
+  use super::{Effect, OptionPolicy, Step};
+  use crate::net::Addrd;
+  use crate::resp::code;
+
+  type InnerPollReq = super::InnerPollReq<crate::test::Platform>;
+  type InnerPollResp = super::InnerPollResp<crate::test::Platform>;
+
+  fn test_msg(ty: Type, code: Code, opt: Option<OptNumber>) -> Addrd<crate::test::Message> {
+    let mut msg = crate::test::Message { id: Id(1),
+                                         ty,
+                                         ver: Default::default(),
+                                         token: Token(Default::default()),
+                                         code,
+                                         opts: Default::default(),
+                                         payload: Payload(Default::default()) };
+
+    if let Some(n) = opt {
+      msg.set(n, OptValue(vec![1])).unwrap();
+    }
+
+    Addrd(msg, crate::test::dummy_addr())
+  }
+
+  fn malformed(ty: Type, code: Code, n: OptNumber) -> Addrd<crate::test::Message> {
+    let mut msg = test_msg(ty, code, None);
+    msg.as_mut()
+       .add(crate::step::opt::MALFORMED, OptValue(n.0.to_be_bytes().to_vec()))
+       .unwrap();
+    msg
+  }
+
+  fn sent(effs: &[crate::test::Effect]) -> Vec<&Addrd<crate::test::Message>> {
+    effs.iter()
+        .filter_map(|e| match e {
+          | Effect::Send(m) => Some(m),
+          | _ => None,
+        })
+        .collect()
+  }
+
+  test::test_step!(
+      GIVEN OptionPolicy::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+      WHEN inner_errors [
+        (inner.poll_req => { Some(Err(nb::Error::Other(()))) }),
+        (inner.poll_resp => { Some(Err(nb::Error::Other(()))) })
+      ]
+      THEN this_should_error [
+        (poll_req(_, _) should satisfy { |out| assert_eq!(out, Some(Err(nb::Error::Other(())))) }),
+        (poll_resp(_, _, _, _) should satisfy { |out| assert_eq!(out, Some(Err(nb::Error::Other(())))) })
+      ]
+  );
+
+  test::test_step!(
+      GIVEN OptionPolicy::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+      WHEN inner_yields_messages_with_understood_options [
+        (inner.poll_req => { Some(Ok(test_msg(Type::Con, Code::GET, Some(OptNumber(11))).map(Into::into))) }),
+        (inner.poll_resp => { Some(Ok(test_msg(Type::Con, code::CONTENT, Some(OptNumber(6))).map(Into::into))) })
+      ]
+      THEN messages_should_pass_through [
+        (poll_req(_, _) should satisfy { |out| assert_eq!(out, Some(Ok(test_msg(Type::Con, Code::GET, Some(OptNumber(11))).map(Into::into)))) }),
+        (poll_resp(_, _, _, _) should satisfy { |out| assert_eq!(out, Some(Ok(test_msg(Type::Con, code::CONTENT, Some(OptNumber(6))).map(Into::into)))) }),
+        (effects == { vec![] })
+      ]
+  );
+
+  test::test_step!(
+      GIVEN OptionPolicy::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+      WHEN inner_yields_messages_with_unrecognized_elective_option [
+        (inner.poll_req => { Some(Ok(test_msg(Type::Con, Code::GET, Some(OptNumber(2))).map(Into::into))) }),
+        (inner.poll_resp => { Some(Ok(test_msg(Type::Non, code::CONTENT, Some(OptNumber(2))).map(Into::into))) })
+      ]
+      THEN messages_should_pass_through [
+        (poll_req(_, _) should satisfy { |out| assert_eq!(out, Some(Ok(test_msg(Type::Con, Code::GET, Some(OptNumber(2))).map(Into::into)))) }),
+        (poll_resp(_, _, _, _) should satisfy { |out| assert_eq!(out, Some(Ok(test_msg(Type::Non, code::CONTENT, Some(OptNumber(2))).map(Into::into)))) }),
+        (effects == { vec![] })
+      ]
+  );
+
+  test::test_step!(
+      GIVEN OptionPolicy::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+      WHEN inner_yields_con_request_with_unrecognized_critical_option [
+        (inner.poll_req => { Some(Ok(test_msg(Type::Con, Code::GET, Some(OptNumber(9))).map(Into::into))) })
+      ]
+      THEN request_should_be_rejected_with_bad_option [
+        (poll_req(_, _) should satisfy { |out| assert_eq!(out, Some(Err(nb::Error::WouldBlock))) }),
+        (effects should satisfy { |effs: &Vec<crate::test::Effect>| {
+          let sent = sent(effs);
+          assert_eq!(sent.len(), 1);
+          assert_eq!(sent[0].addr(), crate::test::dummy_addr());
+          assert_eq!(sent[0].data().ty, Type::Ack);
+          assert_eq!(sent[0].data().id, Id(1));
+          assert_eq!(sent[0].data().code, code::BAD_OPTION);
+        }})
+      ]
+  );
+
+  test::test_step!(
+      GIVEN OptionPolicy::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+      WHEN inner_yields_non_request_with_unrecognized_critical_option [
+        (inner.poll_req => { Some(Ok(test_msg(Type::Non, Code::GET, Some(OptNumber(9))).map(Into::into))) })
+      ]
+      THEN request_should_be_rejected_with_bad_option [
+        (poll_req(_, _) should satisfy { |out| assert_eq!(out, Some(Err(nb::Error::WouldBlock))) }),
+        (effects should satisfy { |effs: &Vec<crate::test::Effect>| {
+          let sent = sent(effs);
+          assert_eq!(sent.len(), 1);
+          assert_eq!(sent[0].data().ty, Type::Non);
+          assert_eq!(sent[0].data().code, code::BAD_OPTION);
+        }})
+      ]
+  );
+
+  test::test_step!(
+      GIVEN OptionPolicy::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+      WHEN inner_yields_con_response_with_unrecognized_critical_option [
+        (inner.poll_resp => { Some(Ok(test_msg(Type::Con, code::CONTENT, Some(OptNumber(9))).map(Into::into))) })
+      ]
+      THEN response_should_be_reset [
+        (poll_resp(_, _, _, _) should satisfy { |out| assert_eq!(out, Some(Err(nb::Error::WouldBlock))) }),
+        (effects should satisfy { |effs: &Vec<crate::test::Effect>| {
+          let sent = sent(effs);
+          assert_eq!(sent.len(), 1);
+          assert_eq!(sent[0].data().ty, Type::Reset);
+          assert_eq!(sent[0].data().id, Id(1));
+          assert_eq!(sent[0].data().code, Code::EMPTY);
+        }})
+      ]
+  );
+
+  test::test_step!(
+      GIVEN OptionPolicy::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+      WHEN inner_yields_ack_response_with_unrecognized_critical_option [
+        (inner.poll_resp => { Some(Ok(test_msg(Type::Ack, code::CONTENT, Some(OptNumber(9))).map(Into::into))) })
+      ]
+      THEN response_should_be_ignored [
+        (poll_resp(_, _, _, _) should satisfy { |out| assert_eq!(out, Some(Err(nb::Error::WouldBlock))) }),
+        (effects should satisfy { |effs: &Vec<crate::test::Effect>| assert!(sent(effs).is_empty()) })
+      ]
+  );
+
+  test::test_step!(
+      GIVEN OptionPolicy::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+      WHEN inner_yields_messages_with_malformed_elective_option [
+        (inner.poll_req => { Some(Ok(malformed(Type::Con, Code::GET, OptNumber(12)).map(Into::into))) }),
+        (inner.poll_resp => { Some(Ok(malformed(Type::Non, code::CONTENT, OptNumber(12)).map(Into::into))) })
+      ]
+      THEN messages_should_pass_through_without_malformed_option [
+        (poll_req(_, _) should satisfy { |out| assert_eq!(out, Some(Ok(test_msg(Type::Con, Code::GET, None).map(Into::into)))) }),
+        (poll_resp(_, _, _, _) should satisfy { |out| assert_eq!(out, Some(Ok(test_msg(Type::Non, code::CONTENT, None).map(Into::into)))) }),
+        (effects == { vec![] })
+      ]
+  );
+
+  test::test_step!(
+      GIVEN OptionPolicy::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+      WHEN inner_yields_request_with_malformed_critical_option [
+        (inner.poll_req => { Some(Ok(malformed(Type::Con, Code::GET, OptNumber(11)).map(Into::into))) })
+      ]
+      THEN request_should_be_rejected_with_bad_option [
+        (poll_req(_, _) should satisfy { |out| assert_eq!(out, Some(Err(nb::Error::WouldBlock))) }),
+        (effects should satisfy { |effs: &Vec<crate::test::Effect>| {
+          let sent = sent(effs);
+          assert_eq!(sent.len(), 1);
+          assert_eq!(sent[0].data().code, code::BAD_OPTION);
+        }})
+      ]
+  );
+}
@@ -1,10 +1,25 @@
-use toad_msg::TryFromBytes;
+use toad_array::AppendCopy;
+use toad_len::Len;
+use toad_msg::opt::parse_error::OptParseError;
+use toad_msg::opt::known::{no_repeat, repeat};
+use toad_msg::{Code,
+               CodeKind,
+               Id,
+               MessageOptions,
+               MessageParseError,
+               OptNumber,
+               OptValue,
+               OptionMap,
+               Token,
+               TryFromBytes,
+               Type};
 
-use super::{exec_inner_step, Step, StepOutput};
+use super::{exec_inner_step, log, opt, Step, StepOutput};
+use crate::config::{Config, MalformedOptions, Oversized};
 use crate::net::Addrd;
-use crate::platform::{self, PlatformTypes};
-use crate::req::Req;
-use crate::resp::Resp;
+use crate::platform::{self, Effect, PlatformTypes};
+use crate::req::{Meta, Received, Req};
+use crate::resp::{code, Resp};
 
 /// Parse messages from dgrams on the socket
 ///
@@ -24,6 +39,10 @@ impl<S> Parse<S> {
 pub enum Error<E> {
   /// Datagram failed to parse as a CoAP message
   Parsing(toad_msg::MessageParseError),
+  /// An option of the message is malformed, and
+  /// [`Msg.malformed_options`](crate::config::Msg#structfield.malformed_options)
+  /// is [`Strict`](MalformedOptions::Strict)
+  MalformedOption(InvalidOption),
   /// The inner step failed.
   ///
   /// This variant's Debug representation is completely
@@ -53,6 +72,7 @@ impl<E: core::fmt::Debug> core::fmt::Debug for Error<E> {
   fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
     match self {
       | Self::Parsing(e) => f.debug_tuple("Parsing").field(e).finish(),
+      | Self::MalformedOption(e) => f.debug_tuple("MalformedOption").field(e).finish(),
       | Self::Inner(e) => e.fmt(f),
     }
   }
@@ -61,12 +81,10 @@ impl<E: core::fmt::Debug> core::fmt::Debug for Error<E> {
 impl<E: super::Error> super::Error for Error<E> {}
 
 macro_rules! common {
-  ($dgram:expr) => {{
+  ($inner:expr, $dgram:expr) => {{
     $dgram.map(|d| {
             d.as_ref()
-             .fold(|dgram, addr| {
-               platform::Message::<P>::try_from_bytes(dgram).map(|dgram| Addrd(dgram, addr))
-             })
+             .fold(|dgram, addr| parse::<P, _>($inner, dgram.as_ref()).map(|dgram| Addrd(dgram, addr)))
              .map_err(Error::Parsing)
              .map_err(nb::Error::Other)
           })
@@ -74,6 +92,256 @@ macro_rules! common {
   }};
 }
 
+/// Did parsing fail because the message did not fit in
+/// the platform's fixed-capacity message collections?
+fn exceeded_capacity(e: &MessageParseError) -> bool {
+  matches!(e,
+           MessageParseError::PayloadTooLong(_)
+           | MessageParseError::OptParseError(OptParseError::TooManyOptions(_)
+                                              | OptParseError::OptionValueTooLong { .. }))
+}
+
+/// Parse just the header & token of a message that failed to parse
+fn header<P>(dgram: &[u8]) -> Option<platform::Message<P>>
+  where P: PlatformTypes
+{
+  let tkl = (dgram.first()? & 0b1111) as usize;
+  let ty = Type::try_from((dgram[0] >> 4) & 0b11).ok()?;
+  let code = Code::from(*dgram.get(1)?);
+  let id = Id(u16::from_be_bytes([*dgram.get(2)?, *dgram.get(3)?]));
+  let token = dgram.get(4..4 + tkl).filter(|_| tkl <= 8)?;
+
+  Some(platform::Message::<P>::new(ty, code, id, Token(token.iter().copied().collect())))
+}
+
+/// Find the index of the first payload byte in a serialized message
+/// whose options are known to be well-formed
+fn payload_offset(dgram: &[u8]) -> Option<usize> {
+  let ext_len = |nibble: u8| match nibble {
+    | 13 => 1,
+    | 14 => 2,
+    | _ => 0,
+  };
+
+  let mut ix = 4 + (dgram.first()? & 0b1111) as usize;
+  loop {
+    let head = *dgram.get(ix)?;
+    ix += 1;
+
+    if head == 0b11111111 {
+      break Some(ix);
+    }
+
+    // extended delta bytes come before extended length bytes
+    let len_ix = ix + ext_len(head >> 4);
+    let len = match head & 0b1111 {
+      | 13 => *dgram.get(len_ix)? as usize + 13,
+      | 14 => u16::from_be_bytes([*dgram.get(len_ix)?, *dgram.get(len_ix + 1)?]) as usize + 269,
+      | n => n as usize,
+    };
+
+    ix = len_ix + ext_len(head & 0b1111) + len;
+  }
+}
+
+/// Parse a message, reusing a message [checked out](Step::checkout)
+/// of `inner` if there is one
+fn parse<P, S>(inner: &S, dgram: &[u8]) -> Result<platform::Message<P>, MessageParseError>
+  where P: PlatformTypes,
+        S: Step<P>
+{
+  match inner.checkout() {
+    | Some(pooled) => parse_into(dgram, pooled).or_else(|pooled| {
+                                                  inner.recycle(pooled);
+                                                  platform::Message::<P>::try_from_bytes(dgram)
+                                                }),
+    | None => platform::Message::<P>::try_from_bytes(dgram),
+  }
+}
+
+/// Parse a message, copying its payload into the payload storage of `pooled`
+///
+/// Yields `pooled` back if the message can't be parsed this way,
+/// so that it can be parsed normally (and fail with the usual error, if it's invalid).
+fn parse_into<P>(dgram: &[u8],
+                 mut pooled: platform::Message<P>)
+                 -> Result<platform::Message<P>, platform::Message<P>>
+  where P: PlatformTypes
+{
+  let (head, payload) = match payload_offset(dgram) {
+    | Some(ix) if ix < dgram.len() => (&dgram[..ix - 1], &dgram[ix..]),
+    // a payload marker followed by an empty payload is invalid
+    | Some(_) => return Err(pooled),
+    | None => (dgram, &[][..]),
+  };
+
+  if P::MessagePayload::CAPACITY.map(|cap| payload.len() > cap)
+                                .unwrap_or(false)
+  {
+    return Err(pooled);
+  }
+
+  match platform::Message::<P>::try_from_bytes(head) {
+    | Ok(mut msg) => {
+      pooled.payload.0.append_copy(payload);
+      msg.payload = pooled.payload;
+      Ok(msg)
+    },
+    | Err(_) => Err(pooled),
+  }
+}
+
+/// An option of a message does not conform to its definition
+/// in RFC7252, RFC7641 (Observe) or RFC7959 (Block-wise transfers)
+#[doc = toad_macros::rfc_7252_doc!("5.10")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidOption {
+  /// A non-repeatable option occurred more than once
+  #[allow(missing_docs)]
+  Repeated { number: OptNumber, count: usize },
+  /// An option's value is too short or too long
+  #[allow(missing_docs)]
+  Length {
+    number: OptNumber,
+    len: usize,
+    min: usize,
+    max: usize,
+  },
+  /// A string option's value is not UTF-8
+  Utf8(OptNumber),
+}
+
+impl InvalidOption {
+  /// The number of the invalid option
+  pub fn number(&self) -> OptNumber {
+    match self {
+      | Self::Repeated { number, .. } | Self::Length { number, .. } | Self::Utf8(number) => *number,
+    }
+  }
+}
+
+/// `(number, repeatable, min length, max length, string)`
+type OptionDef = (OptNumber, bool, usize, usize, bool);
+
+/// Definitions of the options checked by [`check_options`]
+const OPTION_DEFS: &[OptionDef] = &[(repeat::IF_MATCH, true, 0, 8, false),
+                                    (no_repeat::HOST, false, 1, 255, true),
+                                    (repeat::ETAG, true, 1, 8, false),
+                                    (no_repeat::IF_NONE_MATCH, false, 0, 0, false),
+                                    (no_repeat::OBSERVE, false, 0, 3, false),
+                                    (no_repeat::PORT, false, 0, 2, false),
+                                    (repeat::LOCATION_PATH, true, 0, 255, true),
+                                    (repeat::PATH, true, 0, 255, true),
+                                    (no_repeat::CONTENT_FORMAT, false, 0, 2, false),
+                                    (no_repeat::MAX_AGE, false, 0, 4, false),
+                                    (repeat::QUERY, true, 0, 255, true),
+                                    (no_repeat::ACCEPT, false, 0, 2, false),
+                                    (repeat::LOCATION_QUERY, true, 0, 255, true),
+                                    (no_repeat::BLOCK2, false, 0, 3, false),
+                                    (no_repeat::BLOCK1, false, 0, 3, false),
+                                    (no_repeat::SIZE2, false, 0, 4, false),
+                                    (no_repeat::PROXY_URI, false, 1, 1034, true),
+                                    (no_repeat::PROXY_SCHEME, false, 1, 255, true),
+                                    (no_repeat::SIZE1, false, 0, 4, false)];
+
+/// The first option in `opts` that does not conform to its definition in [`OPTION_DEFS`]
+///
+/// Options without a definition are not checked.
+fn first_invalid<O>(opts: &O) -> Option<InvalidOption>
+  where O: OptionMap
+{
+  opts.iter().find_map(|(n, values)| {
+               let &(number, repeatable, min, max, string) =
+                 OPTION_DEFS.iter().find(|(number, ..)| number == n)?;
+
+               if !repeatable && values.len() > 1 {
+                 return Some(InvalidOption::Repeated { number,
+                                                       count: values.len() });
+               }
+
+               values.iter().find_map(|v| {
+                              let bytes = v.as_bytes();
+                              if !(min..=max).contains(&bytes.len()) {
+                                Some(InvalidOption::Length { number,
+                                                             len: bytes.len(),
+                                                             min,
+                                                             max })
+                              } else if string && core::str::from_utf8(bytes).is_err() {
+                                Some(InvalidOption::Utf8(number))
+                              } else {
+                                None
+                              }
+                            })
+             })
+}
+
+/// Check the options of a parsed message against their definitions
+///
+/// See [`MalformedOptions`]
+fn check_options<P, E>(config: &Config,
+                       effects: &mut P::Effects,
+                       mut msg: Addrd<platform::Message<P>>)
+                       -> Result<Addrd<platform::Message<P>>, nb::Error<Error<E>>>
+  where P: PlatformTypes
+{
+  // only this step may say which options were malformed
+  msg.data_mut().remove(opt::MALFORMED);
+
+  loop {
+    let e = match first_invalid(&msg.data().opts) {
+      | Some(e) => e,
+      | None => break Ok(msg),
+    };
+
+    log!(Parse::check_options,
+         effects,
+         log::Level::Debug,
+         "{:?} sent a message with a malformed option: {:?}",
+         msg.addr(),
+         e);
+
+    if config.msg.malformed_options == MalformedOptions::Strict {
+      return Err(nb::Error::Other(Error::MalformedOption(e)));
+    }
+
+    let n = e.number();
+    msg.data_mut().remove(n);
+    msg.data_mut()
+       .add(opt::MALFORMED, OptValue(n.0.to_be_bytes().into_iter().collect()))
+       .map_err(|_| nb::Error::Other(Error::MalformedOption(e)))?;
+  }
+}
+
+/// Truncate the payload of a serialized message to `capacity` bytes
+fn truncate(dgram: &[u8], capacity: usize) -> Option<&[u8]> {
+  dgram.get(..payload_offset(dgram)? + capacity)
+}
+
+/// Build a 4.13 Request Entity Too Large response to a request
+/// that did not fit in the platform's message capacity.
+///
+/// Size1 is set to the maximum payload size, so the client
+/// can retry with a smaller payload (or switch to Block1).
+fn entity_too_large<P>(dgram: &[u8], e: &MessageParseError) -> Option<Resp<P>>
+  where P: PlatformTypes
+{
+  let req = header::<P>(dgram).filter(|msg| msg.code.kind() == CodeKind::Request)
+                              .map(Req::from)?;
+  let mut resp = Resp::for_request(&req)?;
+  resp.set_code(code::REQUEST_ENTITY_TOO_LARGE);
+
+  let size1 = match e {
+    | MessageParseError::PayloadTooLong(capacity) => Some(*capacity),
+    | _ => P::MessagePayload::CAPACITY,
+  };
+
+  if let Some(size1) = size1 {
+    resp.msg_mut().set_size1(size1 as u64).ok();
+  }
+
+  Some(resp)
+}
+
 impl<Inner: Step<P>, P: PlatformTypes> Step<P> for Parse<Inner> {
   type PollReq = Addrd<Req<P>>;
   type PollResp = Addrd<Resp<P>>;
@@ -89,7 +357,65 @@ impl<Inner: Step<P>, P: PlatformTypes> Step<P> for Parse<Inner> {
               effects: &mut <P as PlatformTypes>::Effects)
               -> StepOutput<Self::PollReq, Error<Inner::Error>> {
     exec_inner_step!(self.0.poll_req(snap, effects), Error::Inner);
-    Some(common!(snap.recvd_dgram.as_ref()).map(|addrd| addrd.map(Req::from)))
+
+    let dgram = match snap.recvd_dgram.as_ref() {
+      | Some(dgram) => dgram,
+      | None => return Some(Err(nb::Error::WouldBlock)),
+    };
+
+    let (parsed, truncated) = match common!(&self.0, Some(dgram)) {
+      | Err(nb::Error::Other(Error::Parsing(MessageParseError::PayloadTooLong(capacity))))
+        if snap.config.msg.oversized == Oversized::Truncate =>
+      {
+        log!(Parse::poll_req,
+             effects,
+             log::Level::Warn,
+             "truncating payload of {}b message from {:?} to {}b",
+             dgram.data().as_ref().len(),
+             dgram.addr(),
+             capacity);
+
+        let parsed = truncate(dgram.data().as_ref(), capacity)
+          .ok_or(MessageParseError::PayloadTooLong(capacity))
+          .and_then(platform::Message::<P>::try_from_bytes)
+          .map(|msg| Addrd(msg, dgram.addr()))
+          .map_err(|e| nb::Error::Other(Error::Parsing(e)));
+
+        (parsed, true)
+      },
+      | Err(nb::Error::Other(Error::Parsing(e))) if exceeded_capacity(&e) => {
+        log!(Parse::poll_req,
+             effects,
+             log::Level::Warn,
+             "{:?} sent a message that does not fit in this platform's message capacity: {:?}",
+             dgram.addr(),
+             e);
+
+        return match entity_too_large::<P>(dgram.data().as_ref(), &e) {
+          | Some(resp) => {
+            effects.push(Effect::Send(Addrd(resp.into(), dgram.addr())));
+            Some(Err(nb::Error::WouldBlock))
+          },
+          | None => Some(Err(nb::Error::Other(Error::Parsing(e)))),
+        };
+      },
+      | other => (other, false),
+    };
+
+    let parsed = parsed.and_then(|msg| check_options::<P, _>(&snap.config, effects, msg));
+    let received = Received { dgram_len: dgram.data().as_ref().len(),
+                              truncated };
+
+    Some(parsed.map(|addrd| {
+                 addrd.map(|msg| {
+                        let mut req = Req::from(msg);
+                        req.set_identity(snap.recvd_identity.clone());
+                        req.set_received(Some(received));
+                        req.set_meta(snap.recvd_dest.map(|local_addr| Meta { arrived_at: snap.time,
+                                                                             local_addr }));
+                        req
+                      })
+               }))
   }
 
   fn poll_resp(&self,
@@ -99,7 +425,8 @@ impl<Inner: Step<P>, P: PlatformTypes> Step<P> for Parse<Inner> {
                addr: no_std_net::SocketAddr)
                -> StepOutput<Self::PollResp, Error<Inner::Error>> {
     exec_inner_step!(self.0.poll_resp(snap, effects, token, addr), Error::Inner);
-    Some(common!(snap.recvd_dgram.as_ref()).map(|addrd| addrd.map(Resp::from)))
+    Some(common!(&self.0, snap.recvd_dgram.as_ref()).and_then(|msg| check_options::<P, _>(&snap.config, effects, msg))
+                                                    .map(|addrd| addrd.map(Resp::from)))
   }
 }
 
@@ -109,8 +436,8 @@ mod test {
   use toad_msg::{Code, Type};
 
   use super::super::test;
-  use super::{Error, Parse, Step};
-  use crate::net::{Addrd, Socket};
+  use super::{Error, InvalidOption, Parse, Step};
+  use crate::net::{Addrd, Identity, Socket};
   use crate::platform;
   use crate::req::Req;
   use crate::resp::Resp;
@@ -139,6 +466,93 @@ mod test {
      Addrd(Resp::<_>::from(msg), addr))
   }
 
+  #[test]
+  fn truncate_cuts_payload_to_capacity() {
+    use toad_msg::{MessageOptions, TryFromBytes, TryIntoBytes};
+
+    let mut msg = crate::test::Message::new(Type::Con, Code::POST, toad_msg::Id(1), Default::default());
+    msg.set_path("a/long-path-segment-to-exercise-extended-lengths").unwrap();
+    msg.set_payload(toad_msg::Payload(b"hello, world!".to_vec()));
+    let bytes: Vec<u8> = msg.clone().try_into_bytes().unwrap();
+
+    assert_eq!(&bytes[super::payload_offset(&bytes).unwrap()..], b"hello, world!");
+
+    let truncated = crate::test::Message::try_from_bytes(super::truncate(&bytes, 5).unwrap()).unwrap();
+    assert_eq!(truncated.payload().0, b"hello".to_vec());
+    assert_eq!(truncated.opts, msg.opts);
+  }
+
+  #[test]
+  fn requests_exceeding_capacity_get_4_13_with_size1() {
+    use toad_msg::{MessageOptions, MessageParseError};
+
+    let (con, ..) = test_msg(Type::Con, Code::GET);
+    let resp = super::entity_too_large::<crate::test::Platform>(con.data().as_ref(),
+                                                                &MessageParseError::PayloadTooLong(4)).unwrap();
+    assert_eq!(resp.msg().ty, Type::Ack);
+    assert_eq!(resp.msg().id, toad_msg::Id(1));
+    assert_eq!(resp.msg().code, crate::resp::code::REQUEST_ENTITY_TOO_LARGE);
+    assert_eq!(resp.msg().size1(), Some(4));
+
+    let (ack, ..) = test_msg(Type::Ack, Code::new(2, 04));
+    assert!(super::entity_too_large::<crate::test::Platform>(ack.data().as_ref(),
+                                                             &MessageParseError::PayloadTooLong(4)).is_none());
+  }
+
+  #[test]
+  fn malformed_options_are_removed_or_rejected_per_config() {
+    use toad_msg::no_repeat::{CONTENT_FORMAT, MAX_AGE};
+    use toad_msg::{MessageOptions, OptValue, TryIntoBytes};
+
+    use crate::config::{Config, MalformedOptions};
+    use crate::step::opt::MALFORMED;
+
+    let mut msg = crate::test::Message::new(Type::Con, Code::GET, toad_msg::Id(1), Default::default());
+    msg.set_path("a").unwrap();
+    msg.set(CONTENT_FORMAT, OptValue(vec![0, 0, 0])).unwrap();
+    msg.set(MAX_AGE, OptValue(vec![0; 5])).unwrap();
+    let dgram = Addrd(msg.try_into_bytes().unwrap(), crate::test::dummy_addr());
+
+    let snap = |config: Config| platform::Snapshot { time: crate::test::ClockMock::new().try_now().unwrap(),
+                                                     recvd_dgram: Some(dgram.clone()),
+                                                     recvd_identity: None,
+                                                     recvd_dest: None,
+                                                     session: None,
+                                                     config };
+
+    let step = Parse::<crate::test::MockStep<(), (), (), ()>>::default();
+    let mut effs = Vec::<crate::test::Effect>::new();
+
+    let req = step.poll_req(&snap(Config::default()), &mut effs)
+                  .unwrap()
+                  .unwrap();
+    let malformed = req.data()
+                       .msg()
+                       .get(MALFORMED)
+                       .unwrap()
+                       .iter()
+                       .map(|v| v.as_bytes().to_vec())
+                       .collect::<Vec<_>>();
+    assert_eq!(malformed, vec![12u32.to_be_bytes().to_vec(), 14u32.to_be_bytes().to_vec()]);
+    assert!(req.data().msg().get(CONTENT_FORMAT).is_none());
+    assert!(req.data().msg().get(MAX_AGE).is_none());
+    assert_eq!(req.data().msg().path_string().unwrap(), "a");
+
+    let strict = Config { msg: crate::config::Msg { malformed_options: MalformedOptions::Strict,
+                                                    ..Default::default() },
+                          ..Default::default() };
+    match step.poll_req(&snap(strict), &mut effs) {
+      | Some(Err(nb::Error::Other(Error::MalformedOption(e)))) => {
+        assert_eq!(e,
+                   InvalidOption::Length { number: CONTENT_FORMAT,
+                                           len: 3,
+                                           min: 0,
+                                           max: 2 })
+      },
+      | other => panic!("expected MalformedOption, got {:?}", other),
+    }
+  }
+
   test::test_step!(
       GIVEN Parse::<Dummy> where Dummy: {Step<PollReq = (), PollResp = (), Error = ()>};
       WHEN inner_errors [
@@ -172,6 +586,9 @@ mod test {
           platform::Snapshot {
             time: crate::test::ClockMock::new().try_now().unwrap(),
             recvd_dgram: Some(test_msg(Type::Con, Code::new(1, 01)).0),
+            recvd_identity: None,
+            recvd_dest: None,
+            session: None,
             config: Default::default(),
           }
         })
@@ -181,6 +598,55 @@ mod test {
       ]
   );
 
+  test::test_step!(
+      GIVEN Parse::<Dummy> where Dummy: {Step<PollReq = (), PollResp = (), Error = ()>};
+      WHEN request_recvd_from_authenticated_peer [
+        (inner.poll_req => {None}),
+        (snapshot = {
+          platform::Snapshot {
+            time: crate::test::ClockMock::new().try_now().unwrap(),
+            recvd_dgram: Some(test_msg(Type::Con, Code::new(1, 01)).0),
+            recvd_identity: Identity::psk(b"client"),
+            recvd_dest: None,
+            session: None,
+            config: Default::default(),
+          }
+        })
+      ]
+      THEN poll_req_should_attach_identity [
+        (poll_req(_, _) should satisfy { |out| {
+          let req = out.unwrap().unwrap();
+          assert_eq!(req.data().identity(), Identity::psk(b"client").as_ref());
+          assert_eq!(req.data().received().map(|r| r.dgram_len), Some(test_msg(Type::Con, Code::new(1, 01)).0.data().len()));
+          assert!(!req.data().truncated());
+        }})
+      ]
+  );
+
+  test::test_step!(
+      GIVEN Parse::<Dummy> where Dummy: {Step<PollReq = (), PollResp = (), Error = ()>};
+      WHEN multicast_request_recvd [
+        (inner.poll_req => {None}),
+        (snapshot = {
+          platform::Snapshot {
+            time: crate::test::ClockMock::instant(1000),
+            recvd_dgram: Some(test_msg(Type::Non, Code::new(1, 01)).0),
+            recvd_identity: None,
+            recvd_dest: Some(crate::net::ipv4_socketaddr([224, 0, 1, 187], 5683)),
+            session: None,
+            config: Default::default(),
+          }
+        })
+      ]
+      THEN poll_req_should_attach_meta [
+        (poll_req(_, _) should satisfy { |out| {
+          let meta = out.unwrap().unwrap().data().meta().unwrap();
+          assert_eq!(meta.arrived_at, crate::test::ClockMock::instant(1000));
+          assert!(meta.is_multicast());
+        }})
+      ]
+  );
+
   test::test_step!(
       GIVEN Parse::<Dummy> where Dummy: {Step<PollReq = (), PollResp = (), Error = ()>};
       WHEN empty_ack_recvd [
@@ -189,6 +655,9 @@ mod test {
           platform::Snapshot {
             time: crate::test::ClockMock::new().try_now().unwrap(),
             recvd_dgram: Some(test_msg(Type::Ack, Code::new(0, 0)).0),
+            recvd_identity: None,
+            recvd_dest: None,
+            session: None,
             config: Default::default(),
           }
         })
@@ -206,6 +675,9 @@ mod test {
           platform::Snapshot {
             time: crate::test::ClockMock::new().try_now().unwrap(),
             recvd_dgram: Some(test_msg(Type::Ack, Code::new(2, 04)).0),
+            recvd_identity: None,
+            recvd_dest: None,
+            session: None,
             config: Default::default(),
           }
         })
@@ -223,6 +695,9 @@ mod test {
             platform::Snapshot {
               time: crate::test::ClockMock::new().try_now().unwrap(),
               recvd_dgram: Some(test_msg(Type::Ack, Code::new(2, 04)).0),
+              recvd_identity: None,
+              recvd_dest: None,
+              session: None,
               config: Default::default(),
             }
           })
@@ -240,6 +715,9 @@ mod test {
           platform::Snapshot {
            time: crate::test::ClockMock::new().try_now().unwrap(),
            recvd_dgram: Some(test_msg(Type::Con, Code::new(1, 1)).0),
+           recvd_identity: None,
+           recvd_dest: None,
+           session: None,
            config: Default::default(),
           }
         })
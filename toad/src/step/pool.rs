@@ -0,0 +1,142 @@
+use no_std_net::SocketAddr;
+use toad_array::Array;
+use toad_msg::Token;
+
+use super::{Step, StepOutput};
+use crate::platform::{self, MessagePool, PlatformTypes};
+
+/// Keep messages that are no longer needed in a [`MessagePool`],
+/// and hand them out again through [`Step::checkout`].
+///
+/// See the [module documentation](crate::step::pool) for more
+#[derive(Debug)]
+pub struct Pool<B, S> {
+  inner: S,
+  pool: MessagePool<B>,
+}
+
+impl<P, B, S> Default for Pool<B, S>
+  where P: PlatformTypes,
+        B: Array<Item = platform::Message<P>>,
+        S: Default
+{
+  fn default() -> Self {
+    Self { inner: S::default(),
+           pool: MessagePool::default() }
+  }
+}
+
+impl<P, B, S> Pool<B, S>
+  where P: PlatformTypes,
+        B: Array<Item = platform::Message<P>>
+{
+  /// Create a Pool step keeping messages in `pool`
+  pub fn new(inner: S, pool: MessagePool<B>) -> Self {
+    Self { inner, pool }
+  }
+
+  /// The pool messages are kept in
+  pub fn pool(&self) -> &MessagePool<B> {
+    &self.pool
+  }
+}
+
+impl<P, B, S> Step<P> for Pool<B, S>
+  where P: PlatformTypes,
+        B: Array<Item = platform::Message<P>>,
+        S: Step<P>
+{
+  type PollReq = S::PollReq;
+  type PollResp = S::PollResp;
+  type Error = S::Error;
+  type Inner = S;
+
+  fn inner(&self) -> &S {
+    &self.inner
+  }
+
+  fn poll_req(&self,
+              snap: &platform::Snapshot<P>,
+              effects: &mut P::Effects)
+              -> StepOutput<Self::PollReq, Self::Error> {
+    self.inner.poll_req(snap, effects)
+  }
+
+  fn poll_resp(&self,
+               snap: &platform::Snapshot<P>,
+               effects: &mut P::Effects,
+               token: Token,
+               addr: SocketAddr)
+               -> StepOutput<Self::PollResp, Self::Error> {
+    self.inner.poll_resp(snap, effects, token, addr)
+  }
+
+  fn checkout(&self) -> Option<platform::Message<P>> {
+    self.pool.checkout()
+  }
+
+  fn recycle(&self, msg: platform::Message<P>) {
+    self.pool.give_back(msg)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use toad_msg::{Code, Id, Payload, TryIntoBytes, Type};
+
+  use super::*;
+  use crate::net::Addrd;
+  use crate::step::ack::Ack;
+  use crate::step::parse::Parse;
+  use crate::test::{self, Platform as P};
+
+  type Stack = crate::steps![Pool<Vec<test::Message>>, Parse];
+
+  fn dgram(payload: &[u8]) -> Addrd<Vec<u8>> {
+    let mut msg = test::Message::new(Type::Con, Code::POST, Id(1), Token(Default::default()));
+    msg.payload = Payload(payload.to_vec());
+
+    Addrd(msg.try_into_bytes().unwrap(), test::x.x.x.x(80))
+  }
+
+  fn snap(dgram: Addrd<Vec<u8>>) -> platform::Snapshot<P> {
+    platform::Snapshot { time: test::ClockMock::instant(0),
+                         recvd_dgram: Some(dgram.map(|d| d.into_iter().collect())),
+                         recvd_identity: None,
+                         recvd_dest: None,
+                         session: None,
+                         config: Default::default() }
+  }
+
+  #[test]
+  fn parse_reuses_recycled_messages() {
+    let s = Stack::default();
+    let stats = || Step::<P>::inner(&s).pool().stats();
+
+    Step::<P>::recycle(&s, test::Message::new(Type::Non, Code::GET, Id(9), Token(Default::default())));
+    assert_eq!(Step::<P>::inner(&s).pool().len(), 1);
+
+    let req = Step::<P>::poll_req(&s, &snap(dgram(b"hello")), &mut vec![]).unwrap()
+                                                                        .unwrap();
+    assert_eq!(req.data().payload(), b"hello");
+    assert_eq!(req.data().msg().id, Id(1));
+    assert_eq!(stats().reused, 1);
+
+    // the pool is empty now, so the next message is parsed normally
+    let req = Step::<P>::poll_req(&s, &snap(dgram(b"")), &mut vec![]).unwrap()
+                                                                   .unwrap();
+    assert!(req.data().payload().is_empty());
+    assert_eq!(stats().checkouts, 2);
+    assert_eq!(stats().reused, 1);
+  }
+
+  #[test]
+  fn checkout_and_recycle_reach_the_pool() {
+    type Outer = crate::steps![Pool<Vec<test::Message>>, Parse, Ack];
+    let s = Outer::default();
+
+    assert!(Step::<P>::checkout(&s).is_none());
+    Step::<P>::recycle(&s, test::Message::new(Type::Non, Code::GET, Id(9), Token(Default::default())));
+    assert!(Step::<P>::checkout(&s).is_some());
+  }
+}
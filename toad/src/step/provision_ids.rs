@@ -1,7 +1,6 @@
 use core::any::type_name;
 use core::marker::PhantomData;
 
-use embedded_time::duration::Milliseconds;
 use embedded_time::Instant;
 use no_std_net::SocketAddr;
 use tinyvec::ArrayVec;
@@ -18,7 +17,7 @@ use crate::platform;
 use crate::platform::PlatformTypes;
 use crate::req::Req;
 use crate::resp::Resp;
-use crate::time::Stamped;
+use crate::time::{self, Stamped};
 
 /// Supertrait type shenanigans
 ///
@@ -111,13 +110,17 @@ impl<P, Inner, Ids> ProvisionIds<P, Inner, Ids>
         P: PlatformTypes
 {
   fn prune(effs: &mut P::Effects, seen: &mut Ids, now: Instant<P::Clock>, config: Config) {
+    let exchange_lifetime = config.timing().exchange_lifetime;
+
     for (_, ids) in seen.iter_mut() {
       ids.sort_by_key(|t| t.time());
       let ix_of_first_id_to_keep = ids.iter()
                                       .enumerate()
                                       .find(|(_, id)| {
-                                        now.checked_duration_since(&id.time())
-                               < Some(Milliseconds(config.exchange_lifetime_millis()).into())
+                                        time::elapsed(id.time(), now).map(|e| {
+                                                                        e < exchange_lifetime
+                                                                      })
+                                                                      .unwrap_or(true)
                                       })
                                       .map(|(ix, _)| ix);
 
@@ -141,14 +144,20 @@ impl<P, Inner, Ids> ProvisionIds<P, Inner, Ids>
     }
   }
 
-  fn new_addr(effs: &mut P::Effects, seen: &mut Ids, addr: SocketAddr) {
+  /// Start keeping track of the Ids used with `addr`.
+  ///
+  /// If there is no room for another address, the Ids of the address
+  /// that we least recently used are forgotten to make room.
+  ///
+  /// Yields `false` if `addr` could not be tracked even after doing so.
+  fn new_addr(effs: &mut P::Effects, seen: &mut Ids, addr: SocketAddr) -> bool {
     log!(ProvisionIds::new_addr,
          effs,
          log::Level::Trace,
          "haven't seen {:?} before",
          addr);
     match seen.insert(SocketAddrWithDefault(addr), Default::default()) {
-      | Ok(_) => (),
+      | Ok(_) | Err(InsertError::Exists(_)) => true,
       | Err(InsertError::CapacityExhausted) => {
         let mut to_remove: Option<Stamped<P::Clock, SocketAddrWithDefault>> = None;
 
@@ -168,10 +177,22 @@ impl<P, Inner, Ids> ProvisionIds<P, Inner, Ids>
           }
         }
 
-        seen.remove(&to_remove.unwrap().discard_timestamp());
+        if let Some(Stamped(SocketAddrWithDefault(to_remove), _)) = to_remove {
+          log!(ProvisionIds::new_addr,
+               effs,
+               log::Level::Warn,
+               "Id buffer {} has reached capacity of {} addresses. Forgetting Ids used with {:?} to make room for {:?}",
+               type_name::<Ids>(),
+               Ids::CAPACITY.unwrap_or(usize::MAX),
+               to_remove,
+               addr);
+          seen.remove(&SocketAddrWithDefault(to_remove));
+        }
+
+        matches!(seen.insert(SocketAddrWithDefault(addr), Default::default()),
+                 Ok(_) | Err(InsertError::Exists(_)))
       },
-      | Err(InsertError::Exists(_)) => unreachable!(),
-    };
+    }
   }
 
   /// Generate a Message ID that has not been used yet with the connection with this socket
@@ -185,8 +206,17 @@ impl<P, Inner, Ids> ProvisionIds<P, Inner, Ids>
           -> Id {
     match seen.get_mut(&SocketAddrWithDefault(addr)) {
       | None => {
-        Self::new_addr(effs, seen, addr);
-        Self::next(effs, seen, config, time, addr)
+        if Self::new_addr(effs, seen, addr) {
+          Self::next(effs, seen, config, time, addr)
+        } else {
+          log!(ProvisionIds::next,
+               effs,
+               log::Level::Warn,
+               "Id buffer {} has no room to track Ids used with {:?}; Ids sent to it may be reused",
+               type_name::<Ids>(),
+               addr);
+          Id(1)
+        }
       },
       | Some(ids) => {
         // Pessimistically assume clients are sending us non-sequential
@@ -210,8 +240,6 @@ impl<P, Inner, Ids> ProvisionIds<P, Inner, Ids>
           let mut ahead = ids.iter();
           ahead.next();
 
-          // # PANICS
-          //
           // ideally `ids` will always be a sequence of natural numbers
           // (1, 2, 3, 4, ..)
           //
@@ -222,16 +250,34 @@ impl<P, Inner, Ids> ProvisionIds<P, Inner, Ids>
           //
           // if this is the case, we can get a unique ID by finding a gap (2, 4)
           // and adding 1 to the integer at the start of the gap.
-          //
-          // if the set of ids is literally **EVERY** integer in u16 then this will panic.
-          let (Stamped(IdWithDefault(Id(before_gap)), _), _) =
+          let gap =
             ids.iter()
                .zip(ahead)
                .find(|(Stamped(IdWithDefault(Id(cur)), _), Stamped(IdWithDefault(Id(next)), _))| {
                        next - cur > 1
                      })
-               .unwrap();
-          Id(before_gap + 1)
+               .map(|(Stamped(IdWithDefault(Id(before_gap)), _), _)| Id(before_gap + 1));
+
+          match gap {
+            | Some(id) => id,
+            | None => {
+              // if the set of ids is literally **EVERY** integer in u16,
+              // reuse the oldest one.
+              ids.sort_by_key(|s| s.time());
+              let oldest = ids.remove(0)
+                              .map(|Stamped(IdWithDefault(id), _)| id)
+                              .unwrap_or(Id(1));
+              ids.sort();
+
+              log!(ProvisionIds::next,
+                   effs,
+                   log::Level::Warn,
+                   "every Id has been used with {:?}; reusing the oldest, {:?}",
+                   addr,
+                   oldest);
+              oldest
+            },
+          }
         };
 
         log!(ProvisionIds::next,
@@ -256,8 +302,17 @@ impl<P, Inner, Ids> ProvisionIds<P, Inner, Ids>
 
     match seen.get_mut(&SocketAddrWithDefault(addr)) {
       | None => {
-        Self::new_addr(effs, seen, addr);
-        Self::seen(effs, seen, config, now, addr, id)
+        if Self::new_addr(effs, seen, addr) {
+          Self::seen(effs, seen, config, now, addr, id)
+        } else {
+          log!(ProvisionIds::seen,
+               effs,
+               log::Level::Warn,
+               "Id buffer {} has no room to track Ids used with {:?}; forgetting {:?}",
+               type_name::<Ids>(),
+               addr,
+               id);
+        }
       },
       | Some(ids) => {
         if ids.is_full() {
@@ -326,6 +381,19 @@ impl<P, E: super::Error, Inner, Ids> Step<P> for ProvisionIds<P, Inner, Ids>
     &self.inner
   }
 
+  fn snapshot_state<W>(&self, w: &mut W) -> core::fmt::Result
+    where W: core::fmt::Write
+  {
+    self.seen.map_ref(|seen| {
+               writeln!(w, "ProvisionIds: ids seen from {} peer(s)", seen.len())?;
+               seen.iter().try_for_each(|(SocketAddrWithDefault(addr), ids)| {
+                            writeln!(w, "  {} ({} id(s))", addr, ids.len())
+                          })
+             })?;
+
+    self.inner.snapshot_state(w)
+  }
+
   fn poll_req(&self,
               snap: &crate::platform::Snapshot<P>,
               effects: &mut <P as PlatformTypes>::Effects)
@@ -562,7 +630,7 @@ mod test {
     let mut effs = Vec::<test::Effect>::new();
     let step = Step::default();
     let cfg = Config::default();
-    let exchange_lifetime_micros = cfg.exchange_lifetime_millis() * 1_000;
+    let exchange_lifetime_micros = cfg.timing().exchange_lifetime.0 * 1_000;
 
     // This test assumes that the clock considers 1 "tick" to be 1 microsecond.
     assert_eq!(Microseconds::try_from(ClockMock::instant(1).duration_since_epoch()),
@@ -1,6 +1,7 @@
 use embedded_time::Instant;
 use no_std_net::SocketAddr;
 use toad_msg::{CodeKind, Token};
+use toad_stem::Stem;
 
 use super::{log, Step};
 use crate::config::Config;
@@ -54,19 +55,42 @@ impl<E> From<E> for Error<E> {
 /// the message's origin/destination address.
 ///
 /// For more information, see the [module documentation](crate::step::provision_tokens).
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct ProvisionTokens<Inner> {
   inner: Inner,
+  /// Messages being [forwarded](Step::set_forwarding) right now
+  forwarding: Stem<[Option<Addrd<Token>>; FORWARDING]>,
 }
 
+/// How many messages can be [forwarded](Step::set_forwarding) at once
+/// by [`ProvisionTokens`].
+///
+/// When more are, the oldest is forgotten and its token may be provisioned.
+const FORWARDING: usize = 4;
+
 impl<Inner> Default for ProvisionTokens<Inner> where Inner: Default
 {
   fn default() -> Self {
-    Self { inner: Default::default() }
+    Self { inner: Default::default(),
+           forwarding: Stem::new([None; FORWARDING]) }
   }
 }
 
+/// Is `token` "unassigned", i.e. empty or all zeroes?
+fn is_unassigned(token: &Token) -> bool {
+  token.0.iter().all(|b| *b == 0)
+}
+
 impl<Inner> ProvisionTokens<Inner> {
+  /// Is the message with `token` being forwarded on behalf of another endpoint?
+  ///
+  /// The token of a forwarded request belongs to the endpoint
+  /// that originated it, and must never be rewritten.
+  fn is_forwarded(&self, token: Addrd<Token>) -> bool {
+    self.forwarding
+        .map_ref(|fwd| fwd.iter().flatten().any(|t| *t == token))
+  }
+
   fn next<P>(&self,
              effs: &mut P::Effects,
              now: Instant<P::Clock>,
@@ -88,7 +112,13 @@ impl<Inner> ProvisionTokens<Inner> {
       [a, b, c, d, e, f, g, h, i, j]
     };
 
-    let next = Token::opaque(&bytes);
+    let mut next = Token::opaque(&bytes);
+
+    // a generated token must never look unassigned
+    if is_unassigned(&next) {
+      next.0[7] = 1;
+    }
+
     log!(ProvisionTokens::next,
          effs,
          log::Level::Debug,
@@ -118,14 +148,44 @@ impl<P, E: super::Error, Inner> Step<P> for ProvisionTokens<Inner>
                          -> Result<(), Self::Error> {
     self.inner.before_message_sent(snap, effs, msg)?;
 
-    let token = match (msg.data().code.kind(), msg.data().token) {
-      | (CodeKind::Request, t) if t == Token(Default::default()) => {
-        self.next(effs, snap.time, snap.config)?
-      },
-      | (_, t) => t,
-    };
+    if msg.data().code.kind() == CodeKind::Request
+       && is_unassigned(&msg.data().token)
+       && !self.is_forwarded(Addrd(msg.data().token, msg.addr()))
+    {
+      msg.data_mut().token = self.next(effs, snap.time, snap.config)?;
+    }
+
+    Ok(())
+  }
 
-    msg.data_mut().token = token;
+  fn set_forwarding(&self,
+                    snap: &platform::Snapshot<P>,
+                    effects: &mut P::Effects,
+                    token: Addrd<Token>,
+                    forwarding: bool)
+                    -> Result<(), Self::Error> {
+    self.inner
+        .set_forwarding(snap, effects, token, forwarding)
+        .map_err(Error::Inner)?;
+
+    self.forwarding.map_mut(|fwd| {
+                     if let Some(ix) = fwd.iter().position(|t| *t == Some(token)) {
+                       fwd[ix] = None;
+                       fwd[ix..].rotate_left(1);
+                     }
+
+                     if !forwarding {
+                       return;
+                     }
+
+                     match fwd.iter().position(Option::is_none) {
+                       | Some(ix) => fwd[ix] = Some(token),
+                       | None => {
+                         fwd.rotate_left(1);
+                         fwd[FORWARDING - 1] = Some(token);
+                       },
+                     }
+                   });
 
     Ok(())
   }
@@ -153,6 +213,8 @@ impl<P, E: super::Error, Inner> Step<P> for ProvisionTokens<Inner>
 
 #[cfg(test)]
 mod test {
+  use toad_msg::MessageOptions;
+
   use super::*;
   use crate::step::test::test_step;
   use crate::test::{ClockMock, Snapshot};
@@ -193,6 +255,9 @@ mod test {
       (before_message_sent(
           Snapshot { time: ClockMock::instant(0),
                      recvd_dgram: Some(Addrd(Default::default(), crate::test::dummy_addr())),
+                     recvd_identity: None,
+                     recvd_dest: None,
+                     session: None,
                      config: Config::default() },
                      _,
           crate::test::msg!(CON GET x.x.x.x:80)
@@ -209,10 +274,64 @@ mod test {
       (before_message_sent(
           Snapshot { time: ClockMock::instant(0),
                      recvd_dgram: Some(Addrd(Default::default(), crate::test::dummy_addr())),
+                     recvd_identity: None,
+                     recvd_dest: None,
+                     session: None,
                      config: Config::default() },
                      _,
           crate::test::msg!(CON {2 . 04} x.x.x.x:80)
       ) should satisfy { |m| assert_eq!(m.data().token, Token(Default::default())) })
     ]
   );
+
+  test_step!(
+    GIVEN ProvisionTokens::<Dummy> where Dummy: {Step<PollReq = InnerPollReq, PollResp = InnerPollResp, Error = ()>};
+    WHEN we_boutta_send_a_request_with_all_zero_token [
+      (inner.before_message_sent = { |_, _, _| Ok(()) })
+    ]
+    THEN this_should_replace_it [
+      (before_message_sent(
+          Snapshot { time: ClockMock::instant(0),
+                     recvd_dgram: None,
+                     recvd_identity: None,
+                     recvd_dest: None,
+                     session: None,
+                     config: Config::default() },
+                     _,
+          crate::test::msg!(CON {0 . 1} x.x.x.x:80 with |m: &mut crate::test::Message| m.token = Token(tinyvec::array_vec!(0, 0, 0)))
+      ) should satisfy { |m| assert!(!is_unassigned(&m.data().token)) })
+    ]
+  );
+
+  type Mock = crate::test::MockStep<(), InnerPollReq, InnerPollResp, ()>;
+
+  fn send(sut: &ProvisionTokens<Mock>, mut msg: Addrd<crate::test::Message>) -> Token {
+    let snap = crate::test::snapshot();
+    sut.before_message_sent(&snap, &mut vec![], &mut msg).unwrap();
+    msg.data().token
+  }
+
+  #[test]
+  fn request_sent_through_a_proxy_is_given_a_token() {
+    let sut = ProvisionTokens::<Mock>::default();
+    let req = crate::test::msg!(CON {0 . 1} x.x.x.x:80 with |m: &mut crate::test::Message| {
+      m.set_proxy_uri("coap://example.com/a").unwrap();
+    });
+
+    assert!(!is_unassigned(&send(&sut, req)));
+  }
+
+  #[test]
+  fn forwarded_request_keeps_its_token() {
+    let sut = ProvisionTokens::<Mock>::default();
+    let snap = crate::test::snapshot();
+    let req = crate::test::msg!(CON {0 . 1} x.x.x.x:80);
+    let token = Addrd(req.data().token, req.addr());
+
+    sut.set_forwarding(&snap, &mut vec![], token, true).unwrap();
+    assert_eq!(send(&sut, req.clone()), Token(Default::default()));
+
+    sut.set_forwarding(&snap, &mut vec![], token, false).unwrap();
+    assert!(!is_unassigned(&send(&sut, req)));
+  }
 }
@@ -1,25 +1,28 @@
 use embedded_time::duration::Milliseconds;
 use embedded_time::Instant;
+use no_std_net::SocketAddr;
 use toad_array::Array;
-use toad_msg::{CodeKind, Token, Type};
+use toad_msg::{CodeKind, Id, Token, Type};
 use toad_stem::Stem;
-use toad_string::{format, String};
 
 use super::{log, Step, StepOutput, _try};
 use crate::config::Config;
+use crate::logging::{msg_summary, Summary};
 use crate::net::Addrd;
 use crate::platform::{self, Effect, PlatformTypes, Snapshot};
 use crate::req::Req;
 use crate::resp::Resp;
-use crate::retry::{Attempts, RetryTimer, Strategy, YouShould};
-use crate::time::{Clock, Millis};
+use crate::retry::{Attempts, Policy, RetryTimer, Strategy, YouShould};
+use crate::time::{self, Clock, Millis};
 
 #[allow(missing_docs)]
 #[allow(missing_debug_implementations)]
 #[allow(missing_copy_implementations)]
-pub struct Debug {
-  pub msg_short: String<100>,
-  pub msg_should_be: String<16>,
+pub struct Debug<'a, P>
+  where P: PlatformTypes
+{
+  pub msg_short: Summary<'a, P>,
+  pub msg_should_be: &'static str,
   pub since_last_attempt: Millis,
   pub since_first_attempt: Millis,
   pub until_next_attempt: Option<Millis>,
@@ -31,23 +34,21 @@ pub trait Buf<P>
         Self: Array<Item = (State<P::Clock>, Addrd<platform::Message<P>>)>
 {
   /// Data points used by log messaging
-  fn debug(now: Instant<P::Clock>,
-           state: &State<P::Clock>,
-           msg: &Addrd<platform::toad_msg::Message<P>>)
-           -> Debug {
-    let msg_short = format!(100,
-                            "{:?} {:?} {:?}",
-                            msg.data().ty,
-                            msg.data().code,
-                            msg.data().token);
-    let since_first_attempt = Millis::try_from(now - state.retry_timer().first_attempted_at()).expect("duration since first attempt should be less than u64::MAX milliseconds");
-    let since_last_attempt = Millis::try_from(now - state.retry_timer().last_attempted_at()).expect("duration since last attempt should be less than u64::MAX milliseconds");
-    let until_next_attempt = state.retry_timer().next_attempt_at().checked_duration_since(&now).map(|until| Millis::try_from(until).expect("duration until next attempt should be less than u64::MAX milliseconds"));
+  fn debug<'a>(now: Instant<P::Clock>,
+               state: &State<P::Clock>,
+               msg: &'a Addrd<platform::toad_msg::Message<P>>)
+               -> Debug<'a, P> {
+    let msg_short = msg_summary(msg.data());
+    let since_first_attempt =
+      time::elapsed(state.retry_timer().first_attempted_at(), now).unwrap_or(Milliseconds(0));
+    let since_last_attempt =
+      time::elapsed(state.retry_timer().last_attempted_at(), now).unwrap_or(Milliseconds(0));
+    let until_next_attempt = time::elapsed(now, state.retry_timer().next_attempt_at());
     let msg_should_be = if msg.data().ty == Type::Con {
                           "acknowledged"
                         } else {
                           "responded to"
-                        }.into();
+                        };
     Debug { since_first_attempt,
             since_last_attempt,
             until_next_attempt,
@@ -108,16 +109,52 @@ pub trait Buf<P>
     }
   }
 
+  /// The exchange with token `token` was cancelled,
+  /// and messages with that token should no longer be retried
+  fn cancel(&mut self, effects: &mut P::Effects, token: Token) {
+    while let Some(ix) = self.iter().position(|(_, msg)| msg.data().token == token) {
+      log!(retry::Buf::cancel,
+           effects,
+           log::Level::Debug,
+           "{:?} cancelled, no longer retrying {}",
+           token,
+           msg_summary(self[ix].1.data()));
+      self.remove(ix);
+    }
+  }
+
+  /// The session with `addr` closed, and messages to it
+  /// should no longer be retried
+  fn forget_peer(&mut self, effects: &mut P::Effects, addr: SocketAddr) {
+    while let Some(ix) = self.iter().position(|(_, msg)| msg.addr() == addr) {
+      log!(retry::Buf::forget_peer,
+           effects,
+           log::Level::Debug,
+           "session with {} closed, no longer retrying {}",
+           addr,
+           msg_summary(self[ix].1.data()));
+      self.remove(ix);
+    }
+  }
+
   /// We saw an ACK and should transition the retry state for matching outbound
   /// CONs to the "acked" state
-  fn mark_acked(&mut self, now: Instant<P::Clock>, effects: &mut P::Effects, token: Token) {
-    let found = self.iter_mut().find(|(_, msg)| msg.data().token == token);
+  ///
+  /// Empty ACKs carry no token, so outbound CONs are matched by Message ID
+  /// (and by token, if the ACK has one).
+  fn mark_acked(&mut self, now: Instant<P::Clock>, effects: &mut P::Effects, id: Id, token: Token) {
+    let found = self.iter_mut().find(|(_, msg)| {
+                                 msg.data().id == id
+                                 && (token.0.is_empty() || msg.data().token == token)
+                               });
 
     match found {
       | Some((_, msg)) if msg.data().code.kind() == CodeKind::Response => {
+        let token = msg.data().token;
         self.forget(now, effects, token);
       },
       | Some((state, msg)) if matches!(state, State::ConPreAck { .. }) => {
+        let token = msg.data().token;
         let dbg = Self::debug(now, state, msg);
         log!(retry::Buf::mark_acked,
              effects,
@@ -128,10 +165,11 @@ pub trait Buf<P>
              dbg.since_first_attempt);
 
         let timer = match state {
-          | State::ConPreAck { post_ack_strategy,
-                               post_ack_max_attempts,
-                               .. } => {
+          | State::ConPreAck { timer,
+                               post_ack_strategy,
+                               post_ack_max_attempts, } => {
             RetryTimer::new(now, *post_ack_strategy, *post_ack_max_attempts)
+              .with_jitter(timer.jitter(), jitter_seed(token))
           },
           | _ => unreachable!(),
         };
@@ -142,7 +180,8 @@ pub trait Buf<P>
         log!(retry::Buf::mark_acked,
              effects,
              log::Level::Info,
-             "ACK {:?} does not apply to any known messages",
+             "ACK {:?} {:?} does not apply to any known messages",
+             id,
              token);
       },
     };
@@ -188,7 +227,7 @@ pub trait Buf<P>
       },
       | (Type::Ack, CodeKind::Empty) => {
         log!(retry::Buf::maybe_seen_response, effects, log::Level::Trace, "ACK 0.00 {:?} means we should find the corresponding outbound CON and either forget (if CON response) or transition to expecting a response (if CON request). No following logs means the ACK was unexpected.", msg.data().token);
-        self.mark_acked(now, effects, msg.data().token);
+        self.mark_acked(now, effects, msg.data().id, msg.data().token);
         Ok(())
       },
       | (_, CodeKind::Response) => {
@@ -214,14 +253,53 @@ pub trait Buf<P>
                          now: Instant<P::Clock>,
                          effects: &mut P::Effects,
                          msg: &Addrd<platform::Message<P>>,
-                         config: Config)
+                         config: Config,
+                         policy: Policy)
                          -> Result<(), Error<E>> {
     match msg.data().ty {
+      | _ if msg.addr().ip().is_multicast() => {
+        log!(retry::Buf::store_retryables,
+             effects,
+             log::Level::Trace,
+             "{:?} {:?} sent to multicast address {:?} will not be retried",
+             msg.data().ty,
+             msg.data().code,
+             msg.addr());
+        Ok(())
+      },
+      | _ if self.iter().any(|(_, m)| {
+                           m.addr() == msg.addr()
+                           && m.data().id == msg.data().id
+                           && m.data().token == msg.data().token
+                         }) =>
+      {
+        log!(retry::Buf::store_retryables,
+             effects,
+             log::Level::Trace,
+             "{} is a retransmission of a message already being tracked",
+             msg_summary(msg.data()));
+        Ok(())
+      },
+      | _ if policy == Policy::NoRetry => {
+        log!(retry::Buf::store_retryables,
+             effects,
+             log::Level::Trace,
+             "{} will not be retried; its retry policy is NoRetry",
+             msg_summary(msg.data()));
+        Ok(())
+      },
       | Type::Con | Type::Non if self.is_full() => Err(Error::RetryBufferFull),
       | Type::Con => {
-        let timer = RetryTimer::new(now,
-                                    config.msg.con.unacked_retry_strategy,
-                                    config.msg.con.max_attempts);
+        let strategy = match (policy, msg.data().code.kind()) {
+          | (Policy::Custom(strategy), _) => strategy,
+          | (_, CodeKind::Response) => config.msg
+                                             .con
+                                             .unacked_response_retry_strategy
+                                             .unwrap_or(config.msg.con.unacked_retry_strategy),
+          | _ => config.msg.con.unacked_retry_strategy,
+        };
+        let timer = RetryTimer::new(now, strategy, config.msg.con.max_attempts)
+          .with_jitter(config.msg.retry_jitter, jitter_seed(msg.data().token));
         self.push((State::ConPreAck { timer,
                                       post_ack_strategy: config.msg.con.acked_retry_strategy,
                                       post_ack_max_attempts: config.msg.con.max_attempts },
@@ -241,9 +319,12 @@ pub trait Buf<P>
              log::Level::Trace,
              "sent NON request {:?}; will retry if no response",
              msg.data().code);
-        let timer = RetryTimer::new(now,
-                                    config.msg.non.retry_strategy,
-                                    config.msg.non.max_attempts);
+        let strategy = match policy {
+          | Policy::Custom(strategy) => strategy,
+          | _ => config.msg.non.retry_strategy,
+        };
+        let timer = RetryTimer::new(now, strategy, config.msg.non.max_attempts)
+          .with_jitter(config.msg.retry_jitter, jitter_seed(msg.data().token));
         self.push((State::Just(timer), msg.clone()));
 
         Ok(())
@@ -261,6 +342,14 @@ pub trait Buf<P>
   }
 }
 
+/// Seed for the random [`Jitter`](crate::retry::Jitter) of a message's retry timer
+///
+/// Tokens are generated from [`Msg.token_seed`](crate::config::Msg#structfield.token_seed),
+/// so this differs between devices as well as between messages.
+fn jitter_seed(token: Token) -> u64 {
+  token.0.iter().fold(0u64, |seed, b| (seed << 8) | *b as u64)
+}
+
 impl<T, P> Buf<P> for T
   where T: Array<Item = (State<P::Clock>, Addrd<platform::Message<P>>)>,
         P: PlatformTypes
@@ -351,6 +440,58 @@ impl<C> State<C> where C: Clock
 pub struct Retry<Inner, Buffer> {
   inner: Inner,
   buf: Stem<Buffer>,
+  /// Retry policies set with [`Step::set_retry_policy`], oldest first
+  policies: Stem<[Option<(Addrd<Token>, Policy)>; POLICIES]>,
+}
+
+/// How many exchanges' [`Policy`]s are remembered by [`Retry`].
+///
+/// When more are set, the oldest is forgotten and
+/// messages with its token are retried according to the config.
+pub const POLICIES: usize = 16;
+
+impl<Inner, Buffer> Retry<Inner, Buffer> {
+  fn policy(&self, token: Addrd<Token>) -> Policy {
+    self.policies.map_ref(|ps| {
+                   ps.iter()
+                     .flatten()
+                     .find(|(t, _)| *t == token)
+                     .map(|(_, p)| *p)
+                     .unwrap_or_default()
+                 })
+  }
+
+  fn remember_policy(&self, token: Addrd<Token>, policy: Policy) {
+    self.policies.map_mut(|ps| {
+                   if let Some(ix) = ps.iter().position(|p| matches!(p, Some((t, _)) if *t == token)) {
+                     ps[ix] = None;
+                     ps[ix..].rotate_left(1);
+                   }
+
+                   if policy == Policy::Default {
+                     return;
+                   }
+
+                   match ps.iter().position(Option::is_none) {
+                     | Some(ix) => ps[ix] = Some((token, policy)),
+                     | None => {
+                       ps.rotate_left(1);
+                       ps[POLICIES - 1] = Some((token, policy));
+                     },
+                   }
+                 });
+  }
+
+  fn forget_policies(&self, token: Token) {
+    self.policies.map_mut(|ps| {
+                   while let Some(ix) =
+                     ps.iter().position(|p| matches!(p, Some((t, _)) if t.data() == &token))
+                   {
+                     ps[ix] = None;
+                     ps[ix..].rotate_left(1);
+                   }
+                 });
+  }
 }
 
 impl<Inner, Buffer> Default for Retry<Inner, Buffer>
@@ -359,7 +500,8 @@ impl<Inner, Buffer> Default for Retry<Inner, Buffer>
 {
   fn default() -> Self {
     Self { inner: Inner::default(),
-           buf: Stem::<Buffer>::default() }
+           buf: Stem::<Buffer>::default(),
+           policies: Stem::new([None; POLICIES]) }
   }
 }
 
@@ -411,10 +553,35 @@ impl<P, E, Inner, Buffer> Step<P> for Retry<Inner, Buffer>
     &self.inner
   }
 
+  fn snapshot_state<W>(&self, w: &mut W) -> core::fmt::Result
+    where W: core::fmt::Write
+  {
+    self.buf.map_ref(|buf| {
+              writeln!(w, "Retry: {} message(s) pending", buf.len())?;
+              buf.iter().try_for_each(|(state, msg)| {
+                          writeln!(w,
+                                   "  {} {:?} {:?} {:?} (next attempt at {:?})",
+                                   msg.addr(),
+                                   msg.data().ty,
+                                   msg.data().code,
+                                   msg.data().token,
+                                   state.retry_timer().next_attempt_at())
+                        })
+            })?;
+
+    self.inner.snapshot_state(w)
+  }
+
   fn poll_req(&self,
               snap: &Snapshot<P>,
               effects: &mut <P as PlatformTypes>::Effects)
               -> StepOutput<Self::PollReq, Self::Error> {
+    if let Some(Addrd(session, addr)) = snap.session {
+      if session.is_closed() {
+        self.buf.map_mut(|b| b.forget_peer(effects, addr));
+      }
+    }
+
     // SERVER FLOW:
     //  * CON responses WILL     be retried
     //  * NON responses WILL NOT be retried
@@ -436,6 +603,12 @@ impl<P, E, Inner, Buffer> Step<P> for Retry<Inner, Buffer>
                token: toad_msg::Token,
                addr: no_std_net::SocketAddr)
                -> StepOutput<Self::PollResp, Self::Error> {
+    if let Some(Addrd(session, addr)) = snap.session {
+      if session.is_closed() {
+        self.buf.map_mut(|b| b.forget_peer(effects, addr));
+      }
+    }
+
     // CLIENT FLOW:
     //  * CON requests WILL     be retried
     //  * NON requests WILL     be retried
@@ -457,8 +630,50 @@ impl<P, E, Inner, Buffer> Step<P> for Retry<Inner, Buffer>
                      msg: &Addrd<platform::Message<P>>)
                      -> Result<(), Self::Error> {
     self.inner.on_message_sent(snap, effects, msg)?;
+
+    let policy = self.policy(Addrd(msg.data().token, msg.addr()));
+
     self.buf
-        .map_mut(|b| b.store_retryables(snap.time, effects, msg, snap.config))
+        .map_mut(|b| b.store_retryables(snap.time, effects, msg, snap.config, policy))
+  }
+
+  fn cancel(&self,
+            snap: &Snapshot<P>,
+            effects: &mut P::Effects,
+            token: Token)
+            -> Result<(), Self::Error> {
+    self.inner.cancel(snap, effects, token)?;
+    self.buf.map_mut(|b| b.cancel(effects, token));
+    self.forget_policies(token);
+    Ok(())
+  }
+
+  fn set_retry_policy(&self,
+                      snap: &Snapshot<P>,
+                      effects: &mut P::Effects,
+                      token: Addrd<Token>,
+                      policy: Policy)
+                      -> Result<(), Self::Error> {
+    self.inner.set_retry_policy(snap, effects, token, policy)?;
+    self.remember_policy(token, policy);
+
+    // the message was stored according to the config when it was sent,
+    // so store it again according to its policy.
+    let sent = self.buf.map_mut(|b| {
+                         b.iter()
+                          .position(|(_, msg)| {
+                            msg.addr() == token.addr() && msg.data().token == *token.data()
+                          })
+                          .map(|ix| b.remove(ix))
+                       });
+
+    match sent {
+      | Some(Some((_, msg))) => {
+        self.buf
+            .map_mut(|b| b.store_retryables(snap.time, effects, &msg, snap.config, policy))
+      },
+      | _ => Ok(()),
+    }
   }
 }
 
@@ -480,6 +695,9 @@ mod tests {
   fn snap_time(config: Config, time: u64) -> test::Snapshot {
     test::Snapshot { config,
                      recvd_dgram: Some(Addrd(tinyvec::array_vec!(1), test::dummy_addr())),
+                     recvd_identity: None,
+                     recvd_dest: None,
+                     session: None,
                      time: ClockMock::instant(time * 1000) }
   }
 
@@ -528,6 +746,62 @@ mod tests {
     ]
   );
 
+  #[test]
+  fn when_session_closes_messages_to_peer_should_not_be_retried() {
+    type Mock = test::MockStep<(), Addrd<test::Req>, Addrd<test::Resp>, ()>;
+    let s = Retry::<Mock>::default();
+    s.inner().set_poll_resp(|_, _, _, _, _| None);
+
+    let cfg = config(200, 400);
+    let mut effs = Vec::<test::Effect>::new();
+
+    let req = test::msg!(CON GET x.x.x.x:1111);
+    let other = test::msg!(CON GET x.x.x.x:2222);
+    s.on_message_sent(&snap_time(cfg, 0), &mut effs, &req).unwrap();
+    s.on_message_sent(&snap_time(cfg, 0), &mut effs, &other).unwrap();
+
+    let mut snap = snap_time(cfg, 0);
+    snap.session = Some(Addrd(crate::net::Session::Disconnected, req.addr()));
+    s.poll_resp(&snap, &mut effs, Token(Default::default()), req.addr());
+
+    s.buf.map_ref(|buf| {
+           assert_eq!(buf.len(), 1);
+           assert_eq!(buf[0].1.addr(), other.addr());
+         });
+  }
+
+  #[test]
+  fn when_retry_policy_is_no_retry_message_should_not_be_retried() {
+    type Mock = test::MockStep<(), Addrd<test::Req>, Addrd<test::Resp>, ()>;
+    let s = Retry::<Mock>::default();
+
+    let cfg = config(200, 400);
+    let mut effs = Vec::<test::Effect>::new();
+
+    let req = test::msg!(CON GET x.x.x.x:1111);
+    let other = test::msg!(CON GET x.x.x.x:2222);
+
+    for msg in [&req, &other] {
+      s.on_message_sent(&snap_time(cfg, 0), &mut effs, msg)
+       .unwrap();
+    }
+
+    s.set_retry_policy(&snap_time(cfg, 0),
+                       &mut effs,
+                       req.as_ref().map(|m| m.token),
+                       Policy::NoRetry)
+     .unwrap();
+
+    // e.g. re-sent by the Backoff step
+    s.on_message_sent(&snap_time(cfg, 10), &mut effs, &req)
+     .unwrap();
+
+    s.buf.map_ref(|buf| {
+           assert_eq!(buf.len(), 1);
+           assert_eq!(buf[0].1.addr(), other.addr());
+         });
+  }
+
   /*
    * | t      | what                                              |
    * | ------ | ------------------------------------------------- |
@@ -639,6 +913,31 @@ mod tests {
     assert_eq!(sent!().len(), 2);
   }
 
+  #[test]
+  fn when_message_retransmitted_retry_should_not_track_it_twice() {
+    type Mock = test::MockStep<(), Addrd<test::Req>, Addrd<test::Resp>, ()>;
+    let s = Retry::<Mock>::default();
+    let cfg = config(200, 400);
+    let mut effs = Vec::<test::Effect>::new();
+
+    let req = test::msg!(CON GET x.x.x.x:1111);
+
+    // sent, then retransmitted at 250
+    s.on_message_sent(&snap_time(cfg, 50), &mut effs, &req)
+     .unwrap();
+    s.on_message_sent(&snap_time(cfg, 250), &mut effs, &req)
+     .unwrap();
+
+    s.poll_resp(&snap_time(cfg, 500),
+                &mut effs,
+                req.data().token,
+                req.addr());
+    assert_eq!(effs.iter()
+                   .filter(|e| matches!(e, Effect::Send(_)))
+                   .count(),
+               1);
+  }
+
   /*
    * | t      | what                                              |
    * | ------ | ------------------------------------------------- |
@@ -811,6 +1110,75 @@ mod tests {
     assert_eq!(sent!().len(), 1);
   }
 
+  #[test]
+  fn when_cancelled_retry_should_stop_retrying() {
+    type Mock = test::MockStep<(), Addrd<test::Req>, Addrd<test::Resp>, ()>;
+    let s = Retry::<Mock>::default();
+    s.inner().set_poll_resp(|_, _, _, _, _| None);
+
+    let cfg = config(100, 100);
+    let mut effs = Vec::<test::Effect>::new();
+
+    let mut req = test::msg!(CON GET x.x.x.x:1111);
+    req.as_mut().token = Token(array_vec![1, 2, 3]);
+
+    s.on_message_sent(&snap_time(cfg, 0), &mut effs, &req)
+     .unwrap();
+    s.cancel(&snap_time(cfg, 50), &mut effs, req.data().token)
+     .unwrap();
+
+    s.poll_resp(&snap_time(cfg, 1000),
+                &mut effs,
+                req.data().token,
+                req.addr())
+     .ok_or(())
+     .unwrap_err();
+    assert!(!effs.iter().any(|e| matches!(e, Effect::Send(_))));
+  }
+
+  #[test]
+  fn when_con_response_strategy_configured_retry_should_use_it_for_con_responses() {
+    type Mock = test::MockStep<(), Addrd<test::Req>, Addrd<test::Resp>, ()>;
+    let s = Retry::<Mock>::default();
+    s.inner().set_poll_req(|_, _, _| None);
+
+    let mut cfg = config(200, 400);
+    let response_strategy = Strategy::Delay { min: Milliseconds(1000),
+                                              max: Milliseconds(1000) };
+    cfg.msg.con.unacked_response_retry_strategy = Some(response_strategy);
+
+    let mut effs = Vec::<test::Effect>::new();
+    let sent = |effs: &Vec<test::Effect>| {
+      effs.iter()
+          .filter(|e| matches!(e, Effect::Send(_)))
+          .count()
+    };
+
+    let mut rep = test::msg!(CON {2 . 04} x.x.x.x:1111);
+    rep.as_mut().token = Token(array_vec![1, 2, 3]);
+    let mut req = test::msg!(CON GET x.x.x.x:2222);
+    req.as_mut().token = Token(array_vec![4, 5, 6]);
+
+    s.on_message_sent(&snap_time(cfg, 0), &mut effs, &rep)
+     .unwrap();
+    s.on_message_sent(&snap_time(cfg, 0), &mut effs, &req)
+     .unwrap();
+
+    s.poll_req(&snap_time(cfg, 250), &mut effs)
+     .ok_or(())
+     .unwrap_err();
+    assert_eq!(sent(&effs), 1);
+    assert!(effs.iter()
+                .any(|e| matches!(e, Effect::Send(m) if m.data().token == req.data().token)));
+
+    effs.clear();
+    s.poll_req(&snap_time(cfg, 1000), &mut effs)
+     .ok_or(())
+     .unwrap_err();
+    assert!(effs.iter()
+                .any(|e| matches!(e, Effect::Send(m) if m.data().token == rep.data().token)));
+  }
+
   /*
    * | t      | what                                              |
    * | ------ | ------------------------------------------------- |
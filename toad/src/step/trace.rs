@@ -0,0 +1,324 @@
+use core::hash::{Hash, Hasher};
+
+use no_std_net::SocketAddr;
+use toad_array::Array;
+use toad_hash::Blake2Hasher;
+use toad_msg::Token;
+
+use super::{Step, StepOutput};
+use crate::net::Addrd;
+use crate::platform::{self, Effect, PlatformTypes};
+
+/// Identifies the exchange a message belongs to, so that
+/// the [`Span`]s of a request, its ACK & its response can be told
+/// apart from those of other exchanges.
+///
+/// Derived from the peer's address & the message token,
+/// so every step computes the same id for the same message
+/// without any of them needing to carry it along.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CorrelationId(pub u64);
+
+impl CorrelationId {
+  /// Get the correlation id of messages exchanged with `addr` using `token`
+  ///
+  /// ```
+  /// use toad::net::ipv4_socketaddr;
+  /// use toad::step::trace::CorrelationId;
+  /// use toad_msg::Token;
+  ///
+  /// let a = ipv4_socketaddr([10, 0, 0, 1], 5683);
+  /// let b = ipv4_socketaddr([10, 0, 0, 2], 5683);
+  /// let token = Token(Default::default());
+  ///
+  /// assert_eq!(CorrelationId::of(a, token), CorrelationId::of(a, token));
+  /// assert_ne!(CorrelationId::of(a, token), CorrelationId::of(b, token));
+  /// ```
+  pub fn of(addr: SocketAddr, token: Token) -> Self {
+    let mut h = Blake2Hasher::new();
+    addr.hash(&mut h);
+    token.hash(&mut h);
+    Self(h.finish())
+  }
+
+  /// Get the correlation id of a message
+  pub fn of_msg<P>(msg: &Addrd<platform::Message<P>>) -> Self
+    where P: PlatformTypes
+  {
+    Self::of(msg.addr(), msg.data().token)
+  }
+
+  /// Get the correlation id of a raw datagram, reading only
+  /// as much of the message as needed to find its token
+  pub fn of_dgram(dgram: Addrd<&[u8]>) -> Option<Self> {
+    let bytes = dgram.data();
+    let tkl = (*bytes.first()? & 0b1111) as usize;
+    let token = bytes.get(4..4 + tkl).filter(|_| tkl <= 8)?;
+
+    Some(Self::of(dgram.addr(), Token(token.iter().copied().collect())))
+  }
+}
+
+impl core::fmt::Display for CorrelationId {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    write!(f, "{:016x}", self.0)
+  }
+}
+
+/// The [`Step`] event handler a [`Span`] was emitted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Hook {
+  /// [`Step::poll_req`]
+  PollReq,
+  /// [`Step::poll_resp`]
+  PollResp,
+  /// [`Step::before_message_sent`]
+  BeforeMessageSent,
+  /// [`Step::on_message_sent`]
+  OnMessageSent,
+}
+
+impl Hook {
+  /// The name of the event handler, e.g. `"poll_req"`
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      | Hook::PollReq => "poll_req",
+      | Hook::PollResp => "poll_resp",
+      | Hook::BeforeMessageSent => "before_message_sent",
+      | Hook::OnMessageSent => "on_message_sent",
+    }
+  }
+}
+
+/// Whether a step's event handler was entered or exited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Edge {
+  /// The step is about to run, and will invoke the step it wraps
+  Enter,
+  /// The step and everything it wraps has finished running
+  Exit,
+}
+
+/// A step's event handler was entered or exited,
+/// see [`Effect::Trace`]
+///
+/// Since steps wrap one another, the spans emitted while
+/// handling an event nest like function calls: the outermost
+/// step is entered first and exited last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+  /// Name of the step, e.g. `"Retry"`
+  pub step: &'static str,
+  /// The event being handled
+  pub hook: Hook,
+  /// Entered or exited
+  pub edge: Edge,
+  /// The exchange of the message being handled, if there is one.
+  ///
+  /// When polling for requests, this is only known
+  /// if a datagram was received.
+  pub correlation: Option<CorrelationId>,
+}
+
+impl core::fmt::Display for Span {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let edge = match self.edge {
+      | Edge::Enter => "enter",
+      | Edge::Exit => "exit",
+    };
+
+    write!(f, "[{}::{}] {}", self.step, self.hook.as_str(), edge)?;
+    match self.correlation {
+      | Some(id) => write!(f, " ({})", id),
+      | None => Ok(()),
+    }
+  }
+}
+
+/// `toad::step::ack::Ack<toad::step::parse::Parse<()>>` -> `Ack`
+fn short_name<S>() -> &'static str {
+  let name = core::any::type_name::<S>();
+  let path = name.split('<').next().unwrap_or(name);
+  path.rsplit("::").next().unwrap_or(path)
+}
+
+/// Emit [`Span`]s when the step it wraps handles an event
+///
+/// See the [module documentation](crate::step::trace) for more
+#[derive(Debug, Default)]
+pub struct Trace<S>(S);
+
+impl<S> Trace<S> {
+  /// Trace the events handled by `inner`
+  pub fn new(inner: S) -> Self {
+    Self(inner)
+  }
+
+  fn span<P>(effects: &mut P::Effects, hook: Hook, edge: Edge, correlation: Option<CorrelationId>)
+    where P: PlatformTypes
+  {
+    effects.append(Effect::Trace(Span { step: short_name::<S>(),
+                                      hook,
+                                      edge,
+                                      correlation }));
+  }
+}
+
+impl<P, S> Step<P> for Trace<S>
+  where P: PlatformTypes,
+        S: Step<P>
+{
+  type PollReq = S::PollReq;
+  type PollResp = S::PollResp;
+  type Error = S::Error;
+  type Inner = S;
+
+  fn inner(&self) -> &S {
+    &self.0
+  }
+
+  fn poll_req(&self,
+              snap: &platform::Snapshot<P>,
+              effects: &mut P::Effects)
+              -> StepOutput<Self::PollReq, Self::Error> {
+    let recvd = snap.recvd_dgram
+                    .as_ref()
+                    .and_then(|d| CorrelationId::of_dgram(d.as_ref().map(|d| d.as_ref())));
+
+    Self::span::<P>(effects, Hook::PollReq, Edge::Enter, recvd);
+    let out = self.0.poll_req(snap, effects);
+    Self::span::<P>(effects, Hook::PollReq, Edge::Exit, recvd);
+
+    out
+  }
+
+  fn poll_resp(&self,
+               snap: &platform::Snapshot<P>,
+               effects: &mut P::Effects,
+               token: Token,
+               addr: SocketAddr)
+               -> StepOutput<Self::PollResp, Self::Error> {
+    let id = Some(CorrelationId::of(addr, token));
+
+    Self::span::<P>(effects, Hook::PollResp, Edge::Enter, id);
+    let out = self.0.poll_resp(snap, effects, token, addr);
+    Self::span::<P>(effects, Hook::PollResp, Edge::Exit, id);
+
+    out
+  }
+
+  fn before_message_sent(&self,
+                         snap: &platform::Snapshot<P>,
+                         effects: &mut P::Effects,
+                         msg: &mut Addrd<platform::Message<P>>)
+                         -> Result<(), Self::Error> {
+    Self::span::<P>(effects,
+                    Hook::BeforeMessageSent,
+                    Edge::Enter,
+                    Some(CorrelationId::of_msg(msg)));
+    let out = self.0.before_message_sent(snap, effects, msg);
+
+    // steps may assign the token before the message is sent
+    Self::span::<P>(effects,
+                    Hook::BeforeMessageSent,
+                    Edge::Exit,
+                    Some(CorrelationId::of_msg(msg)));
+
+    out
+  }
+
+  fn on_message_sent(&self,
+                     snap: &platform::Snapshot<P>,
+                     effects: &mut P::Effects,
+                     msg: &Addrd<platform::Message<P>>)
+                     -> Result<(), Self::Error> {
+    let id = Some(CorrelationId::of_msg(msg));
+
+    Self::span::<P>(effects, Hook::OnMessageSent, Edge::Enter, id);
+    let out = self.0.on_message_sent(snap, effects, msg);
+    Self::span::<P>(effects, Hook::OnMessageSent, Edge::Exit, id);
+
+    out
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use tinyvec::array_vec;
+  use toad_msg::{Code, Id, Payload, TryIntoBytes, Type};
+
+  use super::*;
+  use crate::step::ack::Ack;
+  use crate::step::parse::Parse;
+  use crate::test::{self, Platform as P};
+
+  fn msg(ty: Type, code: Code) -> Addrd<test::Message> {
+    let msg = test::Message { ver: Default::default(),
+                              token: Token(array_vec!([u8; 8] => 1, 2)),
+                              ty,
+                              code,
+                              id: Id(1),
+                              opts: Default::default(),
+                              payload: Payload(vec![]) };
+
+    Addrd(msg, test::x.x.x.x(80))
+  }
+
+  fn traces(effects: &[test::Effect]) -> Vec<Span> {
+    effects.iter()
+           .filter_map(|e| match e {
+             | Effect::Trace(span) => Some(*span),
+             | _ => None,
+           })
+           .collect()
+  }
+
+  #[test]
+  fn short_name_strips_path_and_generics() {
+    assert_eq!(short_name::<Ack<Parse<()>>>(), "Ack");
+    assert_eq!(short_name::<()>(), "()");
+  }
+
+  #[test]
+  fn correlation_id_of_dgram_matches_msg() {
+    let req = msg(Type::Con, Code::GET);
+    let bytes: Vec<u8> = req.data().clone().try_into_bytes().unwrap();
+
+    assert_eq!(CorrelationId::of_dgram(Addrd(&bytes[..], req.addr())),
+               Some(CorrelationId::of_msg(&req)));
+    assert_eq!(CorrelationId::of_dgram(Addrd(&bytes[..4], req.addr())),
+               None);
+  }
+
+  #[test]
+  fn spans_nest_like_steps() {
+    type Stack = crate::steps![Parse, Trace, Ack, Trace];
+
+    let s = Stack::default();
+    let req = msg(Type::Con, Code::GET);
+    let bytes = req.data().clone().try_into_bytes().unwrap();
+    let snap = platform::Snapshot { time: test::ClockMock::instant(0),
+                                    recvd_dgram: Some(Addrd(bytes, req.addr())),
+                                    recvd_identity: None,
+                                    recvd_dest: None,
+                                    session: None,
+                                    config: Default::default() };
+
+    let mut effects = vec![];
+    Step::<P>::poll_req(&s, &snap, &mut effects);
+
+    let id = Some(CorrelationId::of_msg(&req));
+    let span = |step, edge| Span { step,
+                                   hook: Hook::PollReq,
+                                   edge,
+                                   correlation: id };
+
+    assert_eq!(traces(&effects),
+               vec![span("Ack", Edge::Enter),
+                    span("Parse", Edge::Enter),
+                    span("Parse", Edge::Exit),
+                    span("Ack", Edge::Exit)]);
+    assert_eq!(span("Ack", Edge::Enter).to_string(),
+               format!("[Ack::poll_req] enter ({:016x})", id.unwrap().0));
+  }
+}
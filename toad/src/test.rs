@@ -1,7 +1,7 @@
 #![allow(dead_code)]
 
 use ::core::cell::Cell;
-use ::core::ops::Deref;
+use ::core::ops::{Deref, RangeInclusive};
 use ::core::time::Duration;
 use ::std::sync::{Mutex, RwLock};
 use ::std::thread;
@@ -10,6 +10,8 @@ use embedded_time::rate::Fraction;
 use embedded_time::Instant;
 use net::*;
 use no_std_net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use std_alloc::sync::Arc;
 use tinyvec::ArrayVec;
 use toad_msg::Token;
@@ -17,6 +19,8 @@ use toad_stem::Stem;
 
 use super::*;
 
+pub mod sim;
+
 // lol `crate::test::x.x.x.x(80)`
 pub struct X1 {
   pub x: X2,
@@ -98,9 +102,43 @@ pub type Effect = crate::platform::Effect<Platform>;
 pub type Req = crate::req::Req<Platform>;
 pub type Resp = crate::resp::Resp<Platform>;
 
+/// Arbitrary messages whose code class is in `classes`,
+/// with any options (known or not) and payload
+pub fn arbitrary_message(classes: RangeInclusive<u8>)
+                         -> impl proptest::strategy::Strategy<Value = Message> {
+  use proptest::prelude::*;
+  use toad_msg::{Code, Id, OptNumber, OptValue, Payload, Type, Version};
+
+  let bytes = |max: usize| prop::collection::vec(any::<u8>(), 0..=max);
+  let opts = prop::collection::btree_map(any::<u16>(), prop::collection::vec(bytes(32), 1..=3), 0..=8);
+
+  (any::<u16>(),
+   prop::sample::select(vec![Type::Con, Type::Non, Type::Ack, Type::Reset]),
+   bytes(8),
+   classes,
+   0u8..=0b11111,
+   opts,
+   bytes(64)).prop_map(|(id, ty, token, class, detail, opts, payload)| {
+                Message { id: Id(id),
+                          ty,
+                          ver: Version(1),
+                          token: Token(token.into_iter().collect()),
+                          code: Code::new(class, detail),
+                          opts: opts.into_iter()
+                                    .map(|(num, vals)| {
+                                      (OptNumber(num as u32), vals.into_iter().map(OptValue).collect())
+                                    })
+                                    .collect(),
+                          payload: Payload(payload) }
+              })
+}
+
 pub fn snapshot() -> Snapshot {
   Snapshot { config: Default::default(),
              time: ClockMock::instant(0),
+             recvd_identity: None,
+             recvd_dest: None,
+             session: None,
              recvd_dgram: None }
 }
 
@@ -354,6 +392,38 @@ impl Timeout {
   }
 }
 
+::std::thread_local! {
+  static LOG_LEVEL: Cell<log::LevelFilter> = Cell::new(log::LevelFilter::Off);
+}
+
+/// Logger enabling the level most recently passed to [`log_level`]
+/// on the current thread, so that tests running in parallel
+/// don't change which log effects each other see
+struct Logger;
+
+impl log::Log for Logger {
+  fn enabled(&self, meta: &log::Metadata) -> bool {
+    meta.level() <= LOG_LEVEL.with(Cell::get)
+  }
+
+  fn log(&self, _: &log::Record) {}
+
+  fn flush(&self) {}
+}
+
+/// Steps only issue log effects for levels enabled by the installed logger;
+/// for the rest of the current test, issue them for `level` and more severe.
+pub fn log_level(level: log::LevelFilter) {
+  static LOGGER: Logger = Logger;
+  static INIT: ::std::sync::Once = ::std::sync::Once::new();
+
+  INIT.call_once(|| {
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+      });
+  LOG_LEVEL.with(|l| l.set(level));
+}
+
 /// Config implementor using mocks for clock and sock
 pub type Platform = crate::platform::Alloc<ClockMock, SockMock>;
 
@@ -384,6 +454,95 @@ impl embedded_time::Clock for ClockMock {
   }
 }
 
+/// Ways that datagrams travelling in one direction
+/// through a [`SockMock`] should be mistreated
+#[derive(Debug, Clone, PartialEq)]
+pub struct Impairment {
+  /// Probability (0.0 ..= 1.0) that a datagram is dropped
+  pub drop: f64,
+  /// Probability (0.0 ..= 1.0) that a datagram is delivered twice
+  pub duplicate: f64,
+  /// Probability (0.0 ..= 1.0) that a datagram swaps places
+  /// with the one queued next to it
+  pub reorder: f64,
+  /// Number of socket operations (sends or recvs) a datagram
+  /// is held for before it is delivered
+  pub latency: RangeInclusive<u32>,
+}
+
+impl Default for Impairment {
+  fn default() -> Self {
+    Self { drop: 0.0,
+           duplicate: 0.0,
+           reorder: 0.0,
+           latency: 0..=0 }
+  }
+}
+
+enum Fate {
+  Drop,
+  Deliver { copies: usize, delay: u32, reorder: bool },
+}
+
+impl Impairment {
+  fn fate(&self, rng: &mut ChaCha8Rng) -> Fate {
+    if rng.gen_bool(self.drop) {
+      Fate::Drop
+    } else {
+      Fate::Deliver { copies: 1 + rng.gen_bool(self.duplicate) as usize,
+                      delay: rng.gen_range(self.latency.clone()),
+                      reorder: rng.gen_bool(self.reorder) }
+    }
+  }
+}
+
+/// Deterministic (seeded) fault injection for [`SockMock`]
+#[derive(Debug)]
+pub struct Faults {
+  /// Impairment of datagrams received by the socket
+  pub rx: Impairment,
+  /// Impairment of datagrams sent by the socket
+  pub tx: Impairment,
+  rng: ChaCha8Rng,
+  /// Received datagrams that have already been impaired,
+  /// and will be yielded before any others
+  rx_ready: Vec<Addrd<Vec<u8>>>,
+  rx_delayed: Vec<(u32, Addrd<Vec<u8>>)>,
+  tx_delayed: Vec<(u32, Addrd<Vec<u8>>)>,
+}
+
+impl Faults {
+  pub fn new(seed: u64, rx: Impairment, tx: Impairment) -> Self {
+    Self { rx,
+           tx,
+           rng: ChaCha8Rng::seed_from_u64(seed),
+           rx_ready: vec![],
+           rx_delayed: vec![],
+           tx_delayed: vec![] }
+  }
+
+  /// Advance delayed datagrams by one socket operation,
+  /// delivering those whose delay has elapsed.
+  fn tick(&mut self, tx: &Mutex<Vec<Addrd<Vec<u8>>>>) {
+    fn release(delayed: &mut Vec<(u32, Addrd<Vec<u8>>)>, mut to: impl FnMut(Addrd<Vec<u8>>)) {
+      delayed.iter_mut().for_each(|(n, _)| *n = n.saturating_sub(1));
+      while let Some(ix) = delayed.iter().position(|(n, _)| *n == 0) {
+        to(delayed.remove(ix).1);
+      }
+    }
+
+    let rx_ready = &mut self.rx_ready;
+    release(&mut self.rx_delayed, |d| rx_ready.push(d));
+    release(&mut self.tx_delayed, |d| tx.lock().unwrap().push(d));
+  }
+}
+
+impl Default for Faults {
+  fn default() -> Self {
+    Self::new(0, Default::default(), Default::default())
+  }
+}
+
 /// A mocked socket
 #[derive(Debug)]
 pub struct SockMock {
@@ -391,12 +550,33 @@ pub struct SockMock {
   pub rx: Arc<Mutex<Vec<Addrd<Vec<u8>>>>>,
   /// Outbound bytes to remote sockets. Address represents the destination
   pub tx: Arc<Mutex<Vec<Addrd<Vec<u8>>>>>,
+  /// Multicast groups that have been joined
+  pub multicast_groups: Arc<Mutex<Vec<no_std_net::IpAddr>>>,
+  /// TTL set by [`Socket::set_ttl`]
+  pub ttl: Arc<Mutex<Option<u32>>>,
+  /// (recv, send) buffer sizes set by [`Socket::set_recv_buffer_size`]
+  /// and [`Socket::set_send_buffer_size`]
+  pub buffer_sizes: Arc<Mutex<(Option<usize>, Option<usize>)>>,
+  /// Loss, duplication, reordering and latency to inflict
+  /// on datagrams sent & received
+  pub faults: Arc<Mutex<Faults>>,
 }
 
 impl SockMock {
   pub fn new() -> Self {
     Self { rx: Default::default(),
-           tx: Default::default() }
+           tx: Default::default(),
+           multicast_groups: Default::default(),
+           ttl: Default::default(),
+           buffer_sizes: Default::default(),
+           faults: Default::default() }
+  }
+
+  /// Create a socket that impairs datagrams it receives according to `rx`
+  /// and datagrams it sends according to `tx`, making random decisions from `seed`
+  pub fn impaired(seed: u64, rx: Impairment, tx: Impairment) -> Self {
+    Self { faults: Arc::new(Mutex::new(Faults::new(seed, rx, tx))),
+           ..Self::new() }
   }
 
   pub fn send_msg<P: platform::PlatformTypes>(rx: &Arc<Mutex<Vec<Addrd<Vec<u8>>>>>,
@@ -436,13 +616,45 @@ impl Socket for SockMock {
   }
 
   fn recv(&self, buf: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
+    let mut faults = self.faults.lock().unwrap();
+    faults.tick(&self.tx);
+
+    let Faults { rx: impairment,
+                 rng,
+                 rx_ready,
+                 rx_delayed,
+                 .. } = &mut *faults;
     let mut rx = self.rx.lock().unwrap();
 
-    if rx.is_empty() {
-      return Err(nb::Error::WouldBlock);
-    }
+    let dgram = loop {
+      if !rx_ready.is_empty() {
+        break rx_ready.remove(0);
+      }
+
+      if rx.is_empty() {
+        return Err(nb::Error::WouldBlock);
+      }
 
-    let dgram = rx.drain(0..1).next().unwrap();
+      let dgram = rx.remove(0);
+
+      match impairment.fate(rng) {
+        | Fate::Drop => continue,
+        | Fate::Deliver { copies, delay, .. } if delay > 0 => {
+          (0..copies).for_each(|_| rx_delayed.push((delay, dgram.clone())));
+        },
+        | Fate::Deliver { copies,
+                          reorder: true,
+                          .. } if !rx.is_empty() => {
+          let next = rx.remove(0);
+          (0..copies).for_each(|_| rx_ready.push(dgram.clone()));
+          break next;
+        },
+        | Fate::Deliver { copies, .. } => {
+          (1..copies).for_each(|_| rx_ready.push(dgram.clone()));
+          break dgram;
+        },
+      }
+    };
 
     dgram.data()
          .iter()
@@ -453,13 +665,44 @@ impl Socket for SockMock {
   }
 
   fn send(&self, buf: Addrd<&[u8]>) -> nb::Result<(), Self::Error> {
+    let mut faults = self.faults.lock().unwrap();
+    faults.tick(&self.tx);
+
+    let Faults { tx: impairment,
+                 rng,
+                 tx_delayed,
+                 .. } = &mut *faults;
     let mut vec = self.tx.lock().unwrap();
-    vec.push(buf.map(Vec::from));
+    let dgram = buf.map(Vec::from);
+
+    match impairment.fate(rng) {
+      | Fate::Drop => (),
+      | Fate::Deliver { copies, delay, .. } if delay > 0 => {
+        (0..copies).for_each(|_| tx_delayed.push((delay, dgram.clone())));
+      },
+      | Fate::Deliver { copies, reorder, .. } => {
+        let ix = match reorder {
+          | true => vec.len().saturating_sub(1),
+          | false => vec.len(),
+        };
+        (0..copies).for_each(|_| vec.insert(ix, dgram.clone()));
+      },
+    }
+
     Ok(())
   }
 
-  fn join_multicast(&self, _: no_std_net::IpAddr) -> Result<(), Self::Error> {
-    todo!()
+  fn join_multicast(&self, addr: no_std_net::IpAddr) -> Result<(), Self::Error> {
+    let mut groups = self.multicast_groups.lock().unwrap();
+    if !groups.contains(&addr) {
+      groups.push(addr);
+    }
+    Ok(())
+  }
+
+  fn leave_multicast(&self, addr: no_std_net::IpAddr) -> Result<(), Self::Error> {
+    self.multicast_groups.lock().unwrap().retain(|a| a != &addr);
+    Ok(())
   }
 
   fn bind_raw<A: no_std_net::ToSocketAddrs>(_: A) -> Result<Self, Self::Error> {
@@ -471,10 +714,113 @@ impl Socket for SockMock {
   }
 
   fn local_addr(&self) -> SocketAddr {
-    todo!()
+    x.x.x.x(5683)
+  }
+
+  fn set_ttl(&self, ttl: u32) -> Result<(), Self::Error> {
+    *self.ttl.lock().unwrap() = Some(ttl);
+    Ok(())
+  }
+
+  fn ttl(&self) -> Result<Option<u32>, Self::Error> {
+    Ok(*self.ttl.lock().unwrap())
+  }
+
+  fn set_recv_buffer_size(&self, bytes: usize) -> Result<(), Self::Error> {
+    self.buffer_sizes.lock().unwrap().0 = Some(bytes);
+    Ok(())
+  }
+
+  fn set_send_buffer_size(&self, bytes: usize) -> Result<(), Self::Error> {
+    self.buffer_sizes.lock().unwrap().1 = Some(bytes);
+    Ok(())
   }
 }
 
+#[test]
+fn sock_mock_unimpaired_by_default() {
+  let sock = SockMock::new();
+  (0..3u8).for_each(|n| sock.send(Addrd(&[n], x.x.x.x(1))).unwrap());
+  assert_eq!(sock.tx.lock().unwrap().iter().map(|d| d.data()[0]).collect::<Vec<_>>(),
+             vec![0, 1, 2]);
+}
+
+#[test]
+fn sock_mock_drop_and_duplicate() {
+  let dropping = SockMock::impaired(0,
+                                    Impairment { drop: 1.0,
+                                                 ..Default::default() },
+                                    Impairment { drop: 1.0,
+                                                 ..Default::default() });
+  dropping.rx.lock().unwrap().push(Addrd(vec![1], x.x.x.x(1)));
+  dropping.send(Addrd(&[1], x.x.x.x(1))).unwrap();
+
+  let mut buf = [0u8; 8];
+  assert_eq!(dropping.recv(&mut buf), Err(nb::Error::WouldBlock));
+  assert!(dropping.tx.lock().unwrap().is_empty());
+
+  let duping = SockMock::impaired(0,
+                                  Impairment { duplicate: 1.0,
+                                               ..Default::default() },
+                                  Impairment { duplicate: 1.0,
+                                               ..Default::default() });
+  duping.rx.lock().unwrap().push(Addrd(vec![1], x.x.x.x(1)));
+  duping.send(Addrd(&[1], x.x.x.x(1))).unwrap();
+
+  assert_eq!(duping.recv(&mut buf), Ok(Addrd(1, x.x.x.x(1))));
+  assert_eq!(duping.recv(&mut buf), Ok(Addrd(1, x.x.x.x(1))));
+  assert_eq!(duping.tx.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn sock_mock_reorder_and_latency() {
+  let reordering = SockMock::impaired(0,
+                                      Impairment { reorder: 1.0,
+                                                   ..Default::default() },
+                                      Default::default());
+  reordering.rx
+            .lock()
+            .unwrap()
+            .extend([Addrd(vec![1], x.x.x.x(1)), Addrd(vec![2], x.x.x.x(1))]);
+
+  let mut buf = [0u8; 8];
+  reordering.recv(&mut buf).unwrap();
+  assert_eq!(buf[0], 2);
+  reordering.recv(&mut buf).unwrap();
+  assert_eq!(buf[0], 1);
+
+  let slow = SockMock::impaired(0,
+                                Impairment { latency: 2..=2,
+                                             ..Default::default() },
+                                Default::default());
+  slow.rx.lock().unwrap().push(Addrd(vec![1], x.x.x.x(1)));
+
+  assert_eq!(slow.recv(&mut buf), Err(nb::Error::WouldBlock));
+  assert_eq!(slow.recv(&mut buf), Err(nb::Error::WouldBlock));
+  assert_eq!(slow.recv(&mut buf), Ok(Addrd(1, x.x.x.x(1))));
+}
+
+#[test]
+fn sock_mock_faults_deterministic() {
+  fn received(seed: u64) -> Vec<u8> {
+    let impairment = Impairment { drop: 0.3,
+                                  duplicate: 0.2,
+                                  reorder: 0.2,
+                                  latency: 0..=3 };
+    let sock = SockMock::impaired(seed, impairment, Default::default());
+    sock.rx
+        .lock()
+        .unwrap()
+        .extend((0..32u8).map(|n| Addrd(vec![n], x.x.x.x(1))));
+
+    let mut buf = [0u8; 8];
+    (0..64).filter_map(|_| sock.recv(&mut buf).ok().map(|_| buf[0]))
+           .collect()
+  }
+
+  assert_eq!(received(1), received(1));
+}
+
 #[test]
 #[should_panic]
 fn times_out() {
@@ -0,0 +1,911 @@
+//! Deterministic simulation of many runtimes talking over a virtual network
+//!
+//! Every [`Node`] in a [`Sim`] shares a virtual clock that only moves when
+//! the simulation is [`run`](Sim::run), and sends datagrams over a [`Network`]
+//! that drops, duplicates and delays them according to a [`Link`] and a seeded RNG.
+//!
+//! This means that scenarios involving retransmission, loss and reordering
+//! play out identically on every run, without real sockets or sleeping.
+
+use core::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use embedded_time::rate::Fraction;
+use embedded_time::Instant;
+use no_std_net::SocketAddr;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use tinyvec::ArrayVec;
+
+use crate::config::Config;
+use crate::net::{Addrd, Socket};
+use crate::platform::{self, Platform};
+use crate::req::Req;
+use crate::resp::Resp;
+use crate::step::Step;
+use crate::todo::String;
+
+/// Platform types used by simulated nodes
+pub type Types = platform::Alloc<SimClock, SimSocket>;
+
+/// The default runtime, for simulated nodes
+pub type Runtime = crate::step::runtime::Runtime<Types, naan::hkt::Vec, naan::hkt::BTreeMap>;
+
+/// A clock (with millisecond resolution) shared by every node in a [`Sim`]
+#[derive(Debug, Clone)]
+pub struct SimClock(Arc<AtomicU64>);
+
+impl embedded_time::Clock for SimClock {
+  type T = u64;
+
+  const SCALING_FACTOR: Fraction = Fraction::new(1, 1_000);
+
+  fn try_now(&self) -> Result<Instant<Self>, embedded_time::clock::Error> {
+    Ok(Instant::new(self.0.load(Ordering::SeqCst)))
+  }
+}
+
+/// Conditions of the virtual network
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+  /// Probability (0.0 ..= 1.0) that a datagram is dropped
+  pub loss: f64,
+  /// Probability (0.0 ..= 1.0) that a delivered datagram is delivered twice
+  pub duplicate: f64,
+  /// Range of delays (in milliseconds) before a datagram is delivered.
+  ///
+  /// Datagrams are delivered in order of arrival, so a wide range
+  /// reorders them.
+  pub latency: RangeInclusive<u64>,
+}
+
+impl Default for Link {
+  fn default() -> Self {
+    Self { loss: 0.0,
+           duplicate: 0.0,
+           latency: 0..=0 }
+  }
+}
+
+/// A datagram sent over the [`Network`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+  /// Sender
+  pub from: SocketAddr,
+  /// Destination
+  pub to: SocketAddr,
+  /// Datagram
+  pub bytes: Vec<u8>,
+  /// Time (ms) this was sent
+  pub sent_at: u64,
+  /// Time (ms) this will be delivered, `None` if it was dropped
+  pub deliver_at: Option<u64>,
+}
+
+/// The virtual network connecting all nodes in a [`Sim`]
+#[derive(Debug)]
+pub struct Network {
+  rng: ChaCha8Rng,
+  link: Link,
+  /// Packets that have not been received yet, along with the node receiving them
+  in_flight: Vec<(SocketAddr, Packet)>,
+  /// Multicast groups joined by nodes
+  groups: Vec<(no_std_net::IpAddr, SocketAddr)>,
+  trace: Vec<Packet>,
+}
+
+impl Network {
+  /// Nodes that receive datagrams sent to `to`
+  fn recipients(&self, to: SocketAddr) -> Vec<SocketAddr> {
+    match to.ip().is_multicast() {
+      | true => self.groups
+                    .iter()
+                    .filter(|(group, node)| *group == to.ip() && node.port() == to.port())
+                    .map(|(_, node)| *node)
+                    .collect(),
+      | false => vec![to],
+    }
+  }
+
+  fn send(&mut self, now: u64, from: SocketAddr, to: SocketAddr, bytes: &[u8]) {
+    let mut packet = Packet { from,
+                              to,
+                              bytes: bytes.to_vec(),
+                              sent_at: now,
+                              deliver_at: None };
+
+    let copies = match self.rng.gen_bool(self.link.loss) {
+      | true => 0,
+      | false => 1 + self.rng.gen_bool(self.link.duplicate) as usize,
+    };
+
+    for node in self.recipients(to) {
+      (0..copies).for_each(|_| {
+                   let at = now + self.rng.gen_range(self.link.latency.clone());
+                   packet.deliver_at.get_or_insert(at);
+                   self.in_flight.push((node,
+                                        Packet { deliver_at: Some(at),
+                                                 ..packet.clone() }));
+                 });
+    }
+
+    self.trace.push(packet);
+  }
+
+  /// Index of the next packet that has arrived at `to`
+  fn arrived(&self, now: u64, to: SocketAddr) -> Option<usize> {
+    self.in_flight
+        .iter()
+        .enumerate()
+        .filter(|(_, (node, p))| {
+          *node == to && p.deliver_at.map(|at| at <= now).unwrap_or(false)
+        })
+        .min_by_key(|(_, (_, p))| p.deliver_at)
+        .map(|(ix, _)| ix)
+  }
+}
+
+/// [`Socket`] attached to a [`Network`]
+#[derive(Debug)]
+pub struct SimSocket {
+  addr: SocketAddr,
+  clock: SimClock,
+  net: Arc<Mutex<Network>>,
+  /// Sender & destination of the last packet received
+  last_recvd: Mutex<Option<(SocketAddr, SocketAddr)>>,
+}
+
+impl SimSocket {
+  fn now(&self) -> u64 {
+    self.clock.0.load(Ordering::SeqCst)
+  }
+
+  fn copy(&self, buf: &mut [u8], remove: bool) -> nb::Result<Addrd<usize>, ()> {
+    let mut net = self.net.lock().unwrap();
+    let ix = net.arrived(self.now(), self.addr)
+                .ok_or(nb::Error::WouldBlock)?;
+
+    let packet = match remove {
+      | true => {
+        let (_, packet) = net.in_flight.remove(ix);
+        *self.last_recvd.lock().unwrap() = Some((packet.from, packet.to));
+        packet
+      },
+      | false => net.in_flight[ix].1.clone(),
+    };
+
+    let n = packet.bytes.len().min(buf.len());
+    buf[..n].copy_from_slice(&packet.bytes[..n]);
+    Ok(Addrd(n, packet.from))
+  }
+}
+
+impl Socket for SimSocket {
+  type Error = ();
+  type Dgram = ArrayVec<[u8; 1152]>;
+
+  fn local_addr(&self) -> SocketAddr {
+    self.addr
+  }
+
+  fn empty_dgram() -> Self::Dgram {
+    ArrayVec::from([0u8; 1152])
+  }
+
+  fn bind_raw<A: no_std_net::ToSocketAddrs>(_: A) -> Result<Self, Self::Error> {
+    Err(())
+  }
+
+  fn send(&self, msg: Addrd<&[u8]>) -> nb::Result<(), Self::Error> {
+    self.net
+        .lock()
+        .unwrap()
+        .send(self.now(), self.addr, msg.addr(), msg.data());
+    Ok(())
+  }
+
+  fn recv(&self, buf: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
+    self.copy(buf, true)
+  }
+
+  fn peek(&self, buf: &mut [u8]) -> nb::Result<Addrd<usize>, Self::Error> {
+    self.copy(buf, false)
+  }
+
+  fn recvd_dest(&self, addr: SocketAddr) -> SocketAddr {
+    match *self.last_recvd.lock().unwrap() {
+      | Some((from, to)) if from == addr => to,
+      | _ => self.addr,
+    }
+  }
+
+  fn join_multicast(&self, group: no_std_net::IpAddr) -> Result<(), Self::Error> {
+    self.net.lock().unwrap().groups.push((group, self.addr));
+    Ok(())
+  }
+
+  fn leave_multicast(&self, group: no_std_net::IpAddr) -> Result<(), Self::Error> {
+    self.net
+        .lock()
+        .unwrap()
+        .groups
+        .retain(|member| *member != (group, self.addr));
+    Ok(())
+  }
+}
+
+/// A runtime instance in a [`Sim`]
+pub struct Node<S = Runtime> {
+  steps: S,
+  config: Config,
+  socket: SimSocket,
+  clock: SimClock,
+}
+
+impl<S> Platform<S> for Node<S>
+  where S: Step<Types, PollReq = Addrd<Req<Types>>, PollResp = Addrd<Resp<Types>>>
+{
+  type Types = Types;
+  type Error = platform::Error<S::Error, ()>;
+
+  fn log(&self, level: log::Level, msg: String<1000>) -> Result<(), Self::Error> {
+    log::log!(target: "toad", level, "[{:?}] {}", self.socket.addr, msg.as_str());
+    Ok(())
+  }
+
+  fn config(&self) -> Config {
+    self.config
+  }
+
+  fn steps(&self) -> &S {
+    &self.steps
+  }
+
+  fn socket(&self) -> &SimSocket {
+    &self.socket
+  }
+
+  fn clock(&self) -> &SimClock {
+    &self.clock
+  }
+}
+
+type Event = Box<dyn FnOnce(&Sim)>;
+
+/// A deterministic simulation of many [`Node`]s on a virtual [`Network`]
+pub struct Sim {
+  clock: SimClock,
+  net: Arc<Mutex<Network>>,
+  events: Vec<(u64, Event)>,
+}
+
+impl Sim {
+  /// Create a simulation whose network behaves according to `link`,
+  /// making random decisions from `seed`
+  pub fn new(seed: u64, link: Link) -> Self {
+    let net = Network { rng: ChaCha8Rng::seed_from_u64(seed),
+                        link,
+                        in_flight: vec![],
+                        groups: vec![],
+                        trace: vec![] };
+
+    Self { clock: SimClock(Arc::new(AtomicU64::new(0))),
+           net: Arc::new(Mutex::new(net)),
+           events: vec![] }
+  }
+
+  /// Current virtual time, in milliseconds
+  pub fn now(&self) -> u64 {
+    self.clock.0.load(Ordering::SeqCst)
+  }
+
+  /// Add a node to the network at `addr`
+  pub fn node<S: Default>(&self, addr: SocketAddr, config: Config) -> Node<S> {
+    Node { steps: S::default(),
+           config,
+           socket: SimSocket { addr,
+                               clock: self.clock.clone(),
+                               net: self.net.clone(),
+                               last_recvd: Mutex::new(None) },
+           clock: self.clock.clone() }
+  }
+
+  /// Change the conditions of the network
+  pub fn link(&self, f: impl FnOnce(&mut Link)) {
+    f(&mut self.net.lock().unwrap().link)
+  }
+
+  /// Every datagram sent so far, in the order they were sent
+  pub fn trace(&self) -> Vec<Packet> {
+    self.net.lock().unwrap().trace.clone()
+  }
+
+  /// Script something to happen at `at` milliseconds
+  pub fn at(&mut self, at: u64, f: impl FnOnce(&Sim) + 'static) -> &mut Self {
+    self.events.push((at, Box::new(f)));
+    self
+  }
+
+  /// Run until the virtual clock reaches `until` milliseconds,
+  /// advancing it by `tick` milliseconds at a time.
+  ///
+  /// Every tick, scripted events that are due are fired, then `poll`
+  /// is invoked so that the caller can drive its nodes.
+  pub fn run(&mut self, until: u64, tick: u64, mut poll: impl FnMut(&Sim)) {
+    while self.now() < until {
+      let now = self.now();
+      let (due, later) = core::mem::take(&mut self.events).into_iter()
+                                                          .partition::<Vec<_>, _>(|(at, _)| {
+                                                            *at <= now
+                                                          });
+      self.events = later;
+      due.into_iter().for_each(|(_, f)| f(self));
+
+      poll(self);
+      self.clock.0.fetch_add(tick, Ordering::SeqCst);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::retry::Attempts;
+
+  fn addr(n: u8) -> SocketAddr {
+    crate::net::ipv4_socketaddr([10, 0, 0, n], 5683)
+  }
+
+  /// Respond to any requests the node has received with 2.05 Content
+  fn serve(node: &Node) {
+    if let Ok(req) = node.poll_req() {
+      if let Some(mut resp) = Resp::for_request(req.data()) {
+        resp.set_payload("hi".bytes());
+        nb::block!(node.send_msg(Addrd(resp.into(), req.addr()))).ok();
+      }
+    }
+  }
+
+  #[test]
+  fn con_request_to_unreachable_server_sent_max_attempts_times() {
+    let config = Config::default();
+    let mut sim = Sim::new(0,
+                           Link { loss: 1.0,
+                                  ..Default::default() });
+    let client = sim.node::<Runtime>(addr(1), config);
+    let server = addr(2);
+
+    let req = Req::<Types>::get("hello");
+    let (_, token) = nb::block!(client.send_msg(Addrd(req.into(), server))).unwrap();
+
+    sim.run(60_000, 10, |_| {
+         client.poll_resp(token, server).ok();
+       });
+
+    let sent = sim.trace()
+                  .into_iter()
+                  .filter(|p| p.from == addr(1))
+                  .map(|p| p.sent_at)
+                  .collect::<Vec<_>>();
+
+    let Attempts(max_attempts) = config.msg.con.max_attempts;
+    assert_eq!(sent.len(), max_attempts as usize);
+    assert!(sent.windows(2).all(|w| w[0] < w[1]));
+
+    let max_time = config.msg
+                         .con
+                         .unacked_retry_strategy
+                         .max_time(config.msg.con.max_attempts);
+    assert!(sent[sent.len() - 1] - sent[0] <= max_time.0);
+  }
+
+  #[test]
+  fn request_gets_response_over_lossless_link() {
+    let config = Config::default();
+    let mut sim = Sim::new(0,
+                           Link { latency: 5..=20,
+                                  ..Default::default() });
+    let client = sim.node::<Runtime>(addr(1), config);
+    let server = sim.node::<Runtime>(addr(2), config);
+
+    let req = Req::<Types>::get("hello");
+    let (_, token) = nb::block!(client.send_msg(Addrd(req.into(), addr(2)))).unwrap();
+
+    let mut resps = vec![];
+    sim.run(1_000, 5, |_| {
+         serve(&server);
+         if let Ok(rep) = client.poll_resp(token, addr(2)) {
+           resps.push(rep);
+         }
+       });
+
+    assert!(resps.iter()
+                 .any(|r| r.data().payload().copied().collect::<Vec<u8>>() == b"hi"));
+
+    // acknowledged immediately, so the request was never retransmitted
+    assert_eq!(sim.trace()
+                  .iter()
+                  .filter(|p| p.from == addr(1))
+                  .count(),
+               1);
+  }
+
+  #[test]
+  #[cfg(feature = "server")]
+  fn error_responses_to_multicast_requests_are_suppressed() {
+    use toad_msg::{Code, Type};
+
+    let config = Config::default();
+    let mut sim = Sim::new(0, Link::default());
+    let client = sim.node::<Runtime>(addr(1), config);
+    let server = sim.node::<Runtime>(addr(2), config);
+
+    let group = crate::net::ipv4_socketaddr([224, 0, 1, 187], 5683);
+    server.socket().join_multicast(group.ip()).unwrap();
+
+    for (path, dest) in [("missing", group), ("hello", group), ("missing", addr(2))] {
+      let mut req = Req::<Types>::get(path);
+      req.non();
+      nb::block!(client.send_msg(Addrd(req.into(), dest))).unwrap();
+    }
+
+    let mut multicast = vec![];
+    sim.run(100, 5, |_| {
+         if let Ok(req) = server.poll_req() {
+           multicast.push(req.data().meta().map(|m| m.is_multicast()));
+
+           let mut resp = Resp::for_request(req.data()).unwrap();
+           if req.data().path().unwrap() != Some("hello") {
+             resp.set_code(Code::new(4, 4));
+           }
+           nb::block!(server.send_msg(Addrd(resp.into(), req.addr()))).unwrap();
+         }
+       });
+
+    assert_eq!(multicast, vec![Some(true), Some(true), Some(false)]);
+
+    let from_server = sim.trace()
+                         .iter()
+                         .filter(|p| p.from == addr(2))
+                         .map(|p| parse(&p.bytes))
+                         .collect::<Vec<_>>();
+
+    // the 4.04 to the multicast request was dropped,
+    // but not the 2.05 or the 4.04 to the unicast request
+    assert_eq!(from_server.iter()
+                          .map(|m| (m.ty, m.code))
+                          .collect::<Vec<_>>(),
+               vec![(Type::Non, Code::new(2, 5)), (Type::Non, Code::new(4, 4))]);
+  }
+
+  fn parse(bytes: &[u8]) -> platform::Message<Types> {
+    use toad_msg::TryFromBytes;
+
+    platform::Message::<Types>::try_from_bytes(bytes.to_vec()).unwrap()
+  }
+
+  #[test]
+  fn piggybacked_response_is_not_acked() {
+    let config = Config::default();
+    let mut sim = Sim::new(0,
+                           Link { latency: 5..=20,
+                                  ..Default::default() });
+    let client = sim.node::<Runtime>(addr(1), config);
+    let server = sim.node::<Runtime>(addr(2), config);
+
+    let req = Req::<Types>::get("hello");
+    let (_, token) = nb::block!(client.send_msg(Addrd(req.into(), addr(2)))).unwrap();
+
+    let mut resp = None;
+    sim.run(1_000, 5, |_| {
+         serve(&server);
+         if let Ok(rep) = client.poll_resp(token, addr(2)) {
+           resp = Some(rep);
+         }
+       });
+
+    assert_eq!(resp.unwrap().data().payload_str(), Ok("hi"));
+
+    let sent_by = |a| {
+      sim.trace()
+         .iter()
+         .filter(|p| p.from == a)
+         .map(|p| parse(&p.bytes))
+         .collect::<Vec<_>>()
+    };
+    let (from_client, from_server) = (sent_by(addr(1)), sent_by(addr(2)));
+
+    assert_eq!(from_client.len(), 1);
+    assert_eq!(from_client[0].ty, toad_msg::Type::Con);
+
+    // RFC 7252 §5.2.2: one ACK for the request, carrying the response
+    assert_eq!(from_server.len(), 1);
+    assert_eq!(from_server[0].ty, toad_msg::Type::Ack);
+    assert_eq!(from_server[0].id, from_client[0].id);
+    assert_eq!(from_server[0].payload.0, b"hi".to_vec());
+  }
+
+  #[test]
+  fn response_is_not_piggybacked_when_disabled_for_peer() {
+    use no_std_net::{IpAddr, Ipv4Addr};
+    use toad_msg::{Code, CodeKind, Type};
+
+    static OVERRIDES: [(IpAddr, bool); 1] = [(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), false)];
+
+    let mut sim = Sim::new(0,
+                           Link { latency: 5..=20,
+                                  ..Default::default() });
+    let client = sim.node::<Runtime>(addr(1), Config::default());
+    let server = sim.node::<Runtime>(addr(2),
+                                     Config::builder().piggyback_overrides(&OVERRIDES)
+                                                      .build()
+                                                      .unwrap());
+
+    let req = Req::<Types>::get("hello");
+    let (_, token) = nb::block!(client.send_msg(Addrd(req.into(), addr(2)))).unwrap();
+
+    let mut resp = None;
+    sim.run(1_000, 5, |_| {
+         serve(&server);
+         if let Ok(rep) = client.poll_resp(token, addr(2)) {
+           resp = Some(rep);
+         }
+       });
+
+    assert_eq!(resp.unwrap().data().payload_str(), Ok("hi"));
+
+    let sent_by = |a| {
+      sim.trace()
+         .iter()
+         .filter(|p| p.from == a)
+         .map(|p| parse(&p.bytes))
+         .collect::<Vec<_>>()
+    };
+    let (from_client, from_server) = (sent_by(addr(1)), sent_by(addr(2)));
+    let req = &from_client[0];
+
+    // RFC 7252 §5.2.2: the request is ACKed with an Empty message,
+    assert_eq!(from_server[0].ty, Type::Ack);
+    assert_eq!(from_server[0].code, Code::EMPTY);
+    assert_eq!(from_server[0].id, req.id);
+    assert!(from_server[0].token.0.is_empty());
+
+    // the response is sent as a CON with a new Id and the request's Token,
+    let resp = &from_server[1];
+    assert_eq!(resp.ty, Type::Con);
+    assert_eq!(resp.code.kind(), CodeKind::Response);
+    assert_ne!(resp.id, req.id);
+    assert_eq!(resp.token, req.token);
+
+    // and the client ACKs the response
+    assert_eq!(from_client.len(), 2);
+    assert_eq!(from_client[1].ty, Type::Ack);
+    assert_eq!(from_client[1].code, Code::EMPTY);
+    assert_eq!(from_client[1].id, resp.id);
+    assert!(from_client[1].token.0.is_empty());
+    assert!(from_server.iter().all(|m| m.ty != Type::Ack || m.code == Code::EMPTY));
+  }
+
+  #[test]
+  fn separate_response_is_acked() {
+    use toad_msg::{Code, Id, TryIntoBytes, Type};
+
+    let config = Config::default();
+    let mut sim = Sim::new(0,
+                           Link { latency: 5..=20,
+                                  ..Default::default() });
+    let client = sim.node::<Runtime>(addr(1), config);
+
+    // A server that ACKs requests immediately, and responds 100ms later.
+    //
+    // Its runtime is bypassed so that we control exactly what is sent.
+    let server = sim.node::<Runtime>(addr(2), config);
+    let send = |msg: platform::Message<Types>, to: SocketAddr| {
+      let bytes = msg.try_into_bytes::<Vec<u8>>().unwrap();
+      server.socket().send(Addrd(&bytes, to)).unwrap();
+    };
+
+    let req = Req::<Types>::get("hello");
+    let (_, token) = nb::block!(client.send_msg(Addrd(req.into(), addr(2)))).unwrap();
+
+    let mut pending = None;
+    let mut resps = vec![];
+    sim.run(10_000, 5, |sim| {
+         let mut buf = [0u8; 1152];
+         if let Ok(Addrd(n, from)) = server.socket().recv(&mut buf) {
+           let msg = parse(&buf[..n]);
+           if msg.ty == Type::Con && msg.code.kind() == toad_msg::CodeKind::Request {
+             let mut ack = msg.ack(msg.id);
+             ack.token = toad_msg::Token(Default::default());
+             send(ack, from);
+             pending = Some((sim.now() + 100, msg, from));
+           }
+         }
+
+         match pending.take() {
+           | Some((at, req, from)) if at <= sim.now() => {
+             let mut resp =
+               platform::Message::<Types>::new(Type::Con, Code::new(2, 5), Id(100), req.token);
+             resp.payload = toad_msg::Payload(b"hi".to_vec());
+             send(resp, from);
+           },
+           | other => pending = other,
+         }
+
+         if let Ok(rep) = client.poll_resp(token, addr(2)) {
+           resps.push(rep);
+         }
+       });
+
+    assert_eq!(resps.len(), 1);
+    assert_eq!(resps[0].data().payload().copied().collect::<Vec<u8>>(), b"hi");
+
+    let from_client = sim.trace()
+                         .iter()
+                         .filter(|p| p.from == addr(1))
+                         .map(|p| parse(&p.bytes))
+                         .collect::<Vec<_>>();
+
+    // the request was ACKed, so it was never retransmitted,
+    // and the separate response was ACKed by the client
+    assert_eq!(from_client.len(), 2);
+    assert_eq!(from_client[0].ty, Type::Con);
+    assert_eq!(from_client[0].code, Code::GET);
+    assert_eq!(from_client[1].ty, Type::Ack);
+    assert_eq!(from_client[1].code, Code::EMPTY);
+    assert_eq!(from_client[1].id, Id(100));
+    assert!(from_client[1].token.0.is_empty());
+  }
+
+  #[test]
+  #[cfg(feature = "server")]
+  fn deferred_response_is_retransmitted_until_acked() {
+    use toad_msg::{Code, Id, Token, TryIntoBytes, Type};
+
+    use crate::server::{respond, Run};
+
+    let config = Config::default();
+    let mut sim = Sim::new(0,
+                           Link { latency: 5..=20,
+                                  ..Default::default() });
+    let server = sim.node::<Runtime>(addr(2), config);
+
+    // A client that ACKs the separate response only once it has been retransmitted.
+    //
+    // Its runtime is bypassed so that we control exactly what is sent.
+    let client = sim.node::<Runtime>(addr(1), config);
+    let send = |msg: platform::Message<Types>| {
+      let bytes = msg.try_into_bytes::<Vec<u8>>().unwrap();
+      client.socket().send(Addrd(&bytes, addr(2))).unwrap();
+    };
+
+    let token = Token(core::iter::once(9).collect());
+    send(platform::Message::<Types>::new(Type::Con, Code::GET, Id(7), token));
+
+    let mut pending = None;
+    let mut cons = 0;
+    sim.run(10_000, 5, |sim| {
+         if let Ok(req) = server.poll_req() {
+           pending = Some((sim.now() + 100, req));
+         }
+
+         match pending.take() {
+           | Some((at, req)) if at <= sim.now() => {
+             let run = Run::<Types, ()>::Unmatched(req).maybe(|ap| {
+                                                         ap.bind(|_| respond::ok("hi".into()))
+                                                           .deferred()
+                                                       });
+             if let Run::Matched(rep) = run {
+               nb::block!(server.send_msg(rep)).unwrap();
+             }
+           },
+           | other => pending = other,
+         }
+
+         let mut buf = [0u8; 1152];
+         if let Ok(Addrd(n, _)) = client.socket().recv(&mut buf) {
+           let msg = parse(&buf[..n]);
+           if msg.ty == Type::Con {
+             cons += 1;
+             if cons == 2 {
+               let mut ack = msg.ack(msg.id);
+               ack.token = Token(Default::default());
+               send(ack);
+             }
+           }
+         }
+       });
+
+    let from_server = sim.trace()
+                         .iter()
+                         .filter(|p| p.from == addr(2))
+                         .map(|p| parse(&p.bytes))
+                         .collect::<Vec<_>>();
+
+    // the request was ACKed immediately with an Empty message,
+    // then the response was sent as a CON until it was ACKed
+    assert_eq!(from_server.len(), 3);
+    assert_eq!(from_server[0].ty, Type::Ack);
+    assert_eq!(from_server[0].code, Code::EMPTY);
+    assert_eq!(from_server[0].id, Id(7));
+    assert_eq!(from_server[0].token, token);
+
+    from_server[1..].iter().for_each(|rep| {
+                             assert_eq!(rep.ty, Type::Con);
+                             assert_eq!(rep.code, Code::new(2, 5));
+                             assert_ne!(rep.id, Id(7));
+                             assert_eq!(rep.id, from_server[1].id);
+                             assert_eq!(rep.token, token);
+                             assert_eq!(rep.payload.0, b"hi".to_vec());
+                           });
+  }
+
+  #[test]
+  fn same_seed_same_trace() {
+    fn scenario(seed: u64) -> Vec<Packet> {
+      let config = Config::default();
+      let mut sim = Sim::new(seed,
+                             Link { loss: 0.3,
+                                    duplicate: 0.1,
+                                    latency: 5..=50 });
+      let client = sim.node::<Runtime>(addr(1), config);
+      let server = sim.node::<Runtime>(addr(2), config);
+
+      let req = Req::<Types>::get("hello");
+      let (_, token) = nb::block!(client.send_msg(Addrd(req.into(), addr(2)))).unwrap();
+
+      // the link heals halfway through
+      sim.at(5_000, |sim| sim.link(|l| l.loss = 0.0));
+      sim.run(10_000, 5, |_| {
+           serve(&server);
+           client.poll_resp(token, addr(2)).ok();
+         });
+
+      sim.trace()
+    }
+
+    let trace = scenario(7);
+    assert!(!trace.is_empty());
+    assert_eq!(trace, scenario(7));
+  }
+
+  #[test]
+  fn latency_delays_delivery() {
+    let sim = Sim::new(0,
+                       Link { latency: 100..=100,
+                              ..Default::default() });
+    let a = sim.node::<Runtime>(addr(1), Config::default());
+    let b = sim.node::<Runtime>(addr(2), Config::default());
+
+    a.socket().send(Addrd(&[1, 2, 3], addr(2))).unwrap();
+
+    let mut buf = [0u8; 8];
+    assert_eq!(b.socket().recv(&mut buf), Err(nb::Error::WouldBlock));
+
+    sim.clock.0.store(100, Ordering::SeqCst);
+    assert_eq!(b.socket().peek(&mut buf), Ok(Addrd(3, addr(1))));
+    assert_eq!(b.socket().recv(&mut buf), Ok(Addrd(3, addr(1))));
+    assert_eq!(&buf[..3], &[1, 2, 3]);
+    assert_eq!(b.socket().recv(&mut buf), Err(nb::Error::WouldBlock));
+  }
+
+  #[test]
+  fn poll_req_drains_burst_up_to_budget() {
+    use toad_msg::{Code, Id, Token, TryIntoBytes, Type};
+
+    let burst = |sim: &Sim, server: SocketAddr| {
+      let client = sim.node::<Runtime>(addr(1), Config::default());
+      let send = |msg: platform::Message<Types>| {
+        let bytes = msg.try_into_bytes::<Vec<u8>>().unwrap();
+        client.socket().send(Addrd(&bytes, server)).unwrap();
+      };
+
+      (1..=3).for_each(|id| {
+               send(platform::Message::<Types>::new(Type::Ack,
+                                                    Code::EMPTY,
+                                                    Id(id),
+                                                    Token(Default::default())))
+             });
+      send(platform::Message::<Types>::new(Type::Non,
+                                           Code::GET,
+                                           Id(4),
+                                           Token(core::iter::once(4).collect())));
+    };
+
+    // the request is behind 3 datagrams that are not requests
+    let sim = Sim::new(0, Link::default());
+    let server = sim.node::<Runtime>(addr(2), Config::default());
+    burst(&sim, addr(2));
+    let req = server.poll_req().unwrap();
+    assert_eq!(req.data().msg().id, Id(4));
+
+    let sim = Sim::new(0, Link::default());
+    let config = Config::builder().max_dgrams_per_poll(1).build().unwrap();
+    let server = sim.node::<Runtime>(addr(2), config);
+    burst(&sim, addr(2));
+    (0..3).for_each(|_| assert!(matches!(server.poll_req(), Err(nb::Error::WouldBlock))));
+    let req = server.poll_req().unwrap();
+    assert_eq!(req.data().msg().id, Id(4));
+  }
+
+  #[test]
+  fn try_poll_req_tells_apart_no_data_and_busy() {
+    use toad_msg::{Code, Id, Token, TryIntoBytes, Type};
+
+    let sim = Sim::new(0, Link::default());
+    let config = Config::builder().max_dgrams_per_poll(2).build().unwrap();
+    let server = sim.node::<Runtime>(addr(2), config);
+    let client = sim.node::<Runtime>(addr(1), Config::default());
+    let send = |msg: platform::Message<Types>| {
+      let bytes = msg.try_into_bytes::<Vec<u8>>().unwrap();
+      client.socket().send(Addrd(&bytes, addr(2))).unwrap();
+    };
+
+    assert!(matches!(server.try_poll_req(), Ok(platform::Poll::NoData)));
+
+    (1..=3).for_each(|id| {
+             send(platform::Message::<Types>::new(Type::Ack,
+                                                  Code::EMPTY,
+                                                  Id(id),
+                                                  Token(Default::default())))
+           });
+
+    // 2 of the 3 datagrams were read
+    assert!(matches!(server.try_poll_req(), Ok(platform::Poll::Busy)));
+    assert!(matches!(server.try_poll_req(), Ok(platform::Poll::NoData)));
+  }
+
+  #[test]
+  fn superloop_slice_stops_polling_when_runtime_budget_spent() {
+    use embedded_time::duration::Milliseconds;
+    use toad_msg::{Id, Token, TryIntoBytes};
+
+    use crate::exec::{self, Budget, Control};
+
+    let sim = Sim::new(0, Link::default());
+    let server = sim.node::<Runtime>(addr(2), Config::default());
+    let client = sim.node::<Runtime>(addr(1), Config::default());
+
+    (1..=2u16).for_each(|n| {
+                let mut req = Req::<Types>::get("hello");
+                req.non();
+                req.msg_mut().id = Id(n);
+                req.msg_mut().token = Token(n.to_be_bytes().into_iter().collect());
+                let bytes = platform::Message::<Types>::from(req).try_into_bytes::<Vec<u8>>()
+                                                                  .unwrap();
+                client.socket().send(Addrd(&bytes, addr(2))).unwrap();
+              });
+
+    let budget = Budget { runtime: Milliseconds(5),
+                          slice: Milliseconds(20) };
+    let mut handled = 0;
+
+    // each request takes 10ms to handle, so only the first fits in the runtime budget
+    let mut remaining = None;
+    let control = exec::slice(&server,
+                              budget,
+                              |_| {
+                                handled += 1;
+                                sim.clock.0.fetch_add(10, Ordering::SeqCst);
+                                Ok(())
+                              },
+                              |left| {
+                                remaining = Some(left);
+                                Control::Continue
+                              }).unwrap();
+    assert_eq!(control, Control::Continue);
+    assert_eq!(handled, 1);
+    assert_eq!(remaining, Some(Milliseconds(10)));
+
+    // the next slice picks up the other request, then the task stops the loop
+    exec::superloop(&server,
+                    budget,
+                    |_| {
+                      handled += 1;
+                      Ok(())
+                    },
+                    |_| Control::Break).unwrap();
+    assert_eq!(handled, 2);
+  }
+}
@@ -1,16 +1,131 @@
 use embedded_time::clock::Error;
+use embedded_time::duration::Milliseconds;
 use embedded_time::Instant;
 
 use crate::todo::String;
 
+/// [`Clock`](embedded_time::Clock) and [`DelayNs`](embedded_hal::delay::DelayNs)
+/// adapters for `embedded-hal` 1.0 platforms
+#[cfg(feature = "embedded_hal")]
+#[cfg_attr(docsrs, doc(cfg(feature = "embedded_hal")))]
+pub mod hal;
+
 /// A duration, in milliseconds
 pub type Millis = embedded_time::duration::Milliseconds<u64>;
 
+/// A duration, in seconds
+pub type Seconds = embedded_time::duration::Seconds<u64>;
+
+/// Convert a number of real seconds (e.g. a Max-Age) to [`Millis`]
+///
+/// ```
+/// use embedded_time::duration::Milliseconds;
+/// use toad::time::from_secs;
+///
+/// assert_eq!(from_secs(60), Milliseconds(60_000u64));
+/// assert_eq!(from_secs(u64::MAX), Milliseconds(u64::MAX));
+/// ```
+pub const fn from_secs(secs: u64) -> Millis {
+  Milliseconds(secs.saturating_mul(1000))
+}
+
+/// Add two durations, saturating at `u64::MAX` milliseconds
+/// rather than overflowing
+///
+/// ```
+/// use embedded_time::duration::Milliseconds;
+/// use toad::time::saturating_add;
+///
+/// assert_eq!(saturating_add(Milliseconds(1u64), Milliseconds(2u64)), Milliseconds(3u64));
+/// assert_eq!(saturating_add(Milliseconds(u64::MAX), Milliseconds(2u64)),
+///            Milliseconds(u64::MAX));
+/// ```
+pub const fn saturating_add(Milliseconds(a): Millis, Milliseconds(b): Millis) -> Millis {
+  Milliseconds(a.saturating_add(b))
+}
+
+/// Time elapsed between `earlier` and `later`
+///
+/// Yields `None` when `later` is actually before `earlier`
+/// or the duration does not fit in [`Millis`], instead of panicking.
+///
+/// ```
+/// use embedded_time::duration::Milliseconds;
+/// use embedded_time::Clock;
+/// use toad::time::elapsed;
+///
+/// let clock = toad::std::Clock::new();
+/// let now = clock.try_now().unwrap();
+/// let later = now + Milliseconds(10u64);
+///
+/// assert_eq!(elapsed(now, later), Some(Milliseconds(10u64)));
+/// assert_eq!(elapsed(later, now), None);
+/// ```
+pub fn elapsed<C: Clock>(earlier: Instant<C>, later: Instant<C>) -> Option<Millis> {
+  later.checked_duration_since(&earlier)
+       .and_then(|d| Millis::try_from(d).ok())
+}
+
+/// The instant `delay` after `start`, saturating at the
+/// latest instant the clock can represent rather than overflowing
+///
+/// ```
+/// use embedded_time::duration::Milliseconds;
+/// use embedded_time::{Clock, Instant};
+/// use toad::time::after;
+///
+/// let clock = toad::std::Clock::new();
+/// let now = clock.try_now().unwrap();
+///
+/// assert_eq!(after(now, Milliseconds(10u64)), now + Milliseconds(10u64));
+/// assert_eq!(after(now, Milliseconds(u64::MAX)), Instant::new(u64::MAX));
+/// ```
+pub fn after<C: Clock>(start: Instant<C>, delay: Millis) -> Instant<C> {
+  start.checked_add(delay)
+       .unwrap_or_else(|| Instant::new(u64::MAX))
+}
+
 /// Supertrait of [`embedded_time::Clock`] pinning the
 /// type of "ticks" to u64
+///
+/// This clock is expected to be **monotonic**; it never goes backwards,
+/// but its epoch is arbitrary (e.g. when the device booted) so its instants
+/// are only meaningful relative to one another. Anything measured in elapsed time,
+/// like retransmission, uses this clock.
+///
+/// For points in real time see [`WallClock`].
 pub trait Clock: core::fmt::Debug + embedded_time::Clock<T = u64> {}
 impl<C: embedded_time::Clock<T = u64> + core::fmt::Debug> Clock for C {}
 
+/// A clock that knows the real ("wall clock") time
+///
+/// Unlike [`Clock`], wall time may jump (e.g. when adjusted by NTP)
+/// but is meaningful outside of this process; use it when a lifetime
+/// needs to be expressed as a point in real time, e.g. when persisting
+/// the moment a cached response or an observation expires.
+///
+/// ```
+/// use toad::time::{Seconds, WallClock};
+///
+/// let clock = toad::std::Clock::new();
+/// let now = clock.try_unix_now().unwrap();
+/// assert!(now > Seconds(1_600_000_000));
+///
+/// let expires = clock.try_expires_at(Seconds(60)).unwrap();
+/// assert!(expires.0 >= now.0 + 60);
+/// ```
+pub trait WallClock {
+  /// Time elapsed since the unix epoch
+  fn try_unix_now(&self) -> Result<Seconds, Error>;
+
+  /// The unix time at which something with a lifetime of
+  /// `max_age` (starting now) will expire
+  fn try_expires_at(&self, max_age: Seconds) -> Result<Seconds, Error> {
+    self.try_unix_now()
+        .map(|now| Seconds(now.0.saturating_add(max_age.0)))
+  }
+}
+
 /// Timeout configuration allowing for "never time out" as an option
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
 pub enum Timeout {
@@ -28,9 +143,10 @@ impl<C: Clock, T: core::fmt::Debug> core::fmt::Debug for Stamped<C, T> {
     use core::fmt::Write;
 
     let mut instant = String::<100>::default();
-    write!(instant,
-           "<{}ms since epoch>",
-           Millis::try_from(self.1.duration_since_epoch()).unwrap())?;
+    match Millis::try_from(self.1.duration_since_epoch()) {
+      | Ok(Milliseconds(ms)) => write!(instant, "<{}ms since epoch>", ms)?,
+      | Err(_) => write!(instant, "<{} ticks since epoch>", self.1.duration_since_epoch().integer())?,
+    }
 
     f.debug_tuple("Stamped")
      .field(&self.0)
@@ -0,0 +1,80 @@
+use embedded_hal::delay::DelayNs;
+use embedded_time::duration::Nanoseconds;
+use embedded_time::rate::Fraction;
+use embedded_time::Instant;
+
+use super::Clock;
+
+/// [`embedded_time::Clock`] backed by a free-running
+/// hardware counter that ticks at `HZ`
+///
+/// `embedded-hal` 1.0 does not define a clock trait, so this
+/// reads the counter with a function provided by the caller
+/// (e.g. one that reads a timer peripheral's count register).
+///
+/// The counter is expected to be monotonic and not to wrap.
+///
+/// ```
+/// use core::sync::atomic::{AtomicU64, Ordering};
+///
+/// use embedded_time::duration::Milliseconds;
+/// use embedded_time::Clock;
+/// use toad::time::hal::Counter;
+///
+/// static TICKS: AtomicU64 = AtomicU64::new(32_768);
+///
+/// let clock = Counter::<32_768>::new(|| TICKS.load(Ordering::Relaxed));
+/// let now = clock.try_now().unwrap();
+/// assert_eq!(Milliseconds::<u64>::try_from(now.duration_since_epoch()),
+///            Ok(Milliseconds(1_000)));
+/// ```
+#[derive(Clone, Copy)]
+pub struct Counter<const HZ: u32> {
+  now: fn() -> u64,
+}
+
+impl<const HZ: u32> core::fmt::Debug for Counter<HZ> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    write!(f, "Counter::<{}>", HZ)
+  }
+}
+
+impl<const HZ: u32> Counter<HZ> {
+  /// Create a clock from a function that reads the current value of the counter
+  pub const fn new(now: fn() -> u64) -> Self {
+    Self { now }
+  }
+}
+
+impl<const HZ: u32> embedded_time::Clock for Counter<HZ> {
+  type T = u64;
+
+  const SCALING_FACTOR: Fraction = Fraction::new(1, HZ);
+
+  fn try_now(&self) -> Result<Instant<Self>, embedded_time::clock::Error> {
+    Ok(Instant::new((self.now)()))
+  }
+}
+
+/// [`DelayNs`] implementation that busy-waits on a toad [`Clock`]
+///
+/// Useful for drivers that need a delay on platforms where the
+/// only timer is the one already given to the runtime.
+///
+/// Precision is limited by the resolution of the clock.
+#[derive(Debug, Clone, Copy)]
+pub struct Delay<C>(pub C);
+
+impl<C: Clock> DelayNs for Delay<C> {
+  fn delay_ns(&mut self, ns: u32) {
+    let until = self.0
+                    .try_now()
+                    .ok()
+                    .and_then(|start| start.checked_add(Nanoseconds(ns as u64)));
+
+    match until {
+      | Some(until) => while self.0.try_now().map(|now| now < until).unwrap_or(false) {},
+      | None => (),
+    }
+  }
+}